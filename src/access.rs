@@ -0,0 +1,224 @@
+//! Roles for sharing one gateway with family or a small team (`access`).
+//!
+//! Every inbound message is attributed to a user — `channel:sender` for chat
+//! channels, `api:<profile>` for API keys — and the user to a role (`owner`,
+//! `trusted`, `guest` or any other configured name). The role narrows the
+//! tools the turn sees, the models `/model` may switch to, and caps what the
+//! user spends per day. Requests made on the machine itself — the CLI, cron
+//! jobs, the heartbeat, the web UI without a profile key — act as the owner.
+
+use crate::config::{AccessConfig, ModelPrice, RoleConfig};
+use crate::usage::UsageRecord;
+use crate::vault::user_scope;
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use tracing::warn;
+
+pub const OWNER: &str = "owner";
+/// Sender of the messages `AgentLoop::process_direct` builds.
+pub const LOCAL_SENDER: &str = "@local";
+
+/// The role a turn runs under.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Role {
+    pub name: String,
+    pub config: RoleConfig,
+}
+
+impl Role {
+    pub fn owner() -> Self {
+        Self {
+            name: OWNER.to_string(),
+            config: RoleConfig::default(),
+        }
+    }
+
+    pub fn may_use_model(&self, model: &str) -> bool {
+        self.config.models.is_empty() || self.config.models.iter().any(|m| m == model)
+    }
+
+    /// The model to answer with, given the chat's `/model` choice and the
+    /// agent default: the choice when permitted, else the role's model, else
+    /// the default, else the first model the role may use.
+    pub fn model_for(&self, chosen: Option<&str>, default: &str) -> String {
+        if let Some(chosen) = chosen.filter(|m| self.may_use_model(m)) {
+            return chosen.to_string();
+        }
+        let role_model = self.config.model.trim();
+        if !role_model.is_empty() {
+            return role_model.to_string();
+        }
+        match self.config.models.first() {
+            Some(first) if !self.may_use_model(default) => first.clone(),
+            _ => default.to_string(),
+        }
+    }
+}
+
+/// Who sent a message: the API key profile for `api:` sessions, the owner
+/// on the CLI, otherwise `channel:sender`. Sender ids are never trusted to
+/// name the owner, since some channels let senders pick their own.
+pub fn user_id(channel: &str, sender_id: &str, session_key: &str) -> String {
+    match user_scope(session_key) {
+        Some(scope) => scope.to_string(),
+        None if channel == "cli" => OWNER.to_string(),
+        None => format!("{channel}:{sender_id}"),
+    }
+}
+
+/// Who a turn started in-process (`AgentLoop::process_direct`: cron, the
+/// CLI, the embedded SDK) acts for: the API key profile for `api:` sessions,
+/// otherwise the owner.
+pub fn local_user_id(session_key: &str) -> String {
+    user_scope(session_key).map_or_else(|| OWNER.to_string(), str::to_string)
+}
+
+/// Resolves users to roles.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    config: AccessConfig,
+}
+
+impl Access {
+    pub fn new(config: AccessConfig) -> Self {
+        for (user, role) in &config.users {
+            if !config.roles.contains_key(role) {
+                warn!("access.users.{user} names unknown role '{role}'");
+            }
+        }
+        Self { config }
+    }
+
+    /// The role of `user` (see [`user_id`]); `None` while access control is
+    /// off. A user's own entry wins over their channel's.
+    pub fn role_of(&self, user: &str) -> Option<Role> {
+        if !self.config.enabled {
+            return None;
+        }
+        if user == OWNER {
+            return Some(Role::owner());
+        }
+        let channel = user.split(':').next().unwrap_or(user);
+        let name = self
+            .config
+            .users
+            .get(user)
+            .or_else(|| self.config.users.get(channel))
+            .unwrap_or(&self.config.default_role);
+        // An unknown role grants nothing rather than everything.
+        let config = self
+            .config
+            .roles
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| RoleConfig {
+                tools: Some(Vec::new()),
+                ..RoleConfig::default()
+            });
+        Some(Role {
+            name: name.clone(),
+            config,
+        })
+    }
+}
+
+/// What `user` spent since local midnight of `now`, priced with `prices`.
+pub fn spent_today(
+    records: &[UsageRecord],
+    user: &str,
+    prices: &HashMap<String, ModelPrice>,
+    now: DateTime<Local>,
+) -> f64 {
+    let today = now.date_naive();
+    records
+        .iter()
+        .filter(|r| r.user == user && r.at.date_naive() == today)
+        .filter_map(|r| r.cost(prices))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_roles_and_their_models_and_spend() {
+        let mut config = AccessConfig {
+            enabled: true,
+            ..AccessConfig::default()
+        };
+        config
+            .users
+            .insert("telegram:1".to_string(), OWNER.to_string());
+        config
+            .users
+            .insert("discord".to_string(), "trusted".to_string());
+        config
+            .users
+            .insert("api:kids".to_string(), "child".to_string());
+        let access = Access::new(config.clone());
+
+        assert_eq!(access.role_of("telegram:1").unwrap().name, "owner");
+        assert_eq!(access.role_of("telegram:2").unwrap().name, "guest");
+        assert_eq!(access.role_of("discord:9").unwrap().name, "trusted");
+        assert_eq!(access.role_of(OWNER).unwrap().name, "owner");
+        assert_eq!(
+            access.role_of("api:kids").unwrap().config.tools,
+            Some(Vec::new())
+        );
+        assert!(Access::default().role_of("telegram:2").is_none());
+        assert_eq!(user_id("api", "user", "api:kids:homework"), "api:kids");
+        assert_eq!(user_id("telegram", "2", "telegram:2"), "telegram:2");
+        assert_eq!(user_id("cli", "user", "cli:direct"), OWNER);
+        assert_eq!(
+            user_id("mochat", LOCAL_SENDER, "mochat:room"),
+            "mochat:@local"
+        );
+        assert_eq!(local_user_id("cron:j1"), OWNER);
+        assert_eq!(local_user_id("api:kids:homework"), "api:kids");
+
+        let role = Role {
+            name: "guest".to_string(),
+            config: RoleConfig {
+                models: vec!["openai/gpt-4o-mini".to_string()],
+                ..RoleConfig::default()
+            },
+        };
+        assert!(!role.may_use_model("openai/gpt-4o"));
+        assert_eq!(
+            role.model_for(Some("openai/gpt-4o"), "anthropic/claude-opus-4-5"),
+            "openai/gpt-4o-mini"
+        );
+        assert_eq!(
+            Role::owner().model_for(None, "anthropic/claude-opus-4-5"),
+            "anthropic/claude-opus-4-5"
+        );
+
+        let now = Local::now();
+        let call = |user: &str, at: DateTime<Local>| UsageRecord {
+            at,
+            model: "gpt-4o".to_string(),
+            prompt_tokens: 1_000_000,
+            completion_tokens: 0,
+            ttft_ms: 0,
+            total_ms: 0,
+            session: String::new(),
+            channel: String::new(),
+            user: user.to_string(),
+            cost_usd: None,
+        };
+        let records = vec![
+            call("telegram:2", now),
+            call("telegram:2", now - chrono::Duration::days(2)),
+            call("telegram:3", now),
+        ];
+        let prices = HashMap::from([(
+            "gpt-4o".to_string(),
+            ModelPrice {
+                input_per_million: 2.5,
+                output_per_million: 10.0,
+            },
+        )]);
+        assert_eq!(spent_today(&records, "telegram:2", &prices, now), 2.5);
+    }
+}
@@ -0,0 +1,337 @@
+//! Operator routes under `/admin` on the gateway listener.
+//!
+//! Requests authenticate with the token the running service writes to
+//! `admin.token` in the data dir (what `nanobot admin` uses), or with an API
+//! key created with `--admin`.
+
+use crate::agent::AgentLoop;
+use crate::apikeys::{ApiKeyGuard, constant_time_eq, request_token};
+use crate::bus::MessageBus;
+use crate::config::{Config, ConfigEdit};
+use crate::logging::recent_error_lines;
+use crate::providers::base::LLMProvider;
+use crate::providers::swap::{self, SwappableProvider};
+use crate::session::SessionManager;
+use crate::utils::{get_data_path, write_private};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response};
+use tokio::runtime::Handle;
+
+const DEFAULT_ERROR_LIMIT: usize = 50;
+
+pub fn admin_token_path() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("admin.token"))
+}
+
+/// Generates a fresh token for this run and writes it, owner-only, to
+/// `admin_token_path()`.
+pub fn issue_admin_token() -> Result<String> {
+    let token = format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let path = admin_token_path()?;
+    write_private(&path, token.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(token)
+}
+
+pub fn read_admin_token() -> Result<String> {
+    let path = admin_token_path()?;
+    let token = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "failed to read {} (is the gateway running?)",
+            path.display()
+        )
+    })?;
+    Ok(token.trim().to_string())
+}
+
+fn respond_json(request: Request, status: u16, body: Value) {
+    let header = Header::from_bytes("Content-Type", "application/json; charset=utf-8")
+        .expect("static header is valid");
+    let _ = request.respond(
+        Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header),
+    );
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolToggle {
+    name: String,
+    enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConsolidateRequest {
+    session: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigSet {
+    key: String,
+    value: String,
+}
+
+/// Builds the gateway's provider from a changed config.
+pub type ProviderFactory = Arc<dyn Fn(&Config) -> Result<Arc<dyn LLMProvider>> + Send + Sync>;
+
+pub struct AdminApi {
+    agent: Arc<AgentLoop>,
+    bus: Arc<MessageBus>,
+    sessions: Arc<SessionManager>,
+    runtime: Handle,
+    guard: ApiKeyGuard,
+    token: String,
+    /// The live provider and how to rebuild it, for `providers.*` changes.
+    provider: Option<(Arc<SwappableProvider>, ProviderFactory)>,
+}
+
+impl AdminApi {
+    pub fn new(
+        agent: Arc<AgentLoop>,
+        bus: Arc<MessageBus>,
+        sessions: Arc<SessionManager>,
+        runtime: Handle,
+        guard: ApiKeyGuard,
+        token: String,
+    ) -> Self {
+        Self {
+            agent,
+            bus,
+            sessions,
+            runtime,
+            guard,
+            token,
+            provider: None,
+        }
+    }
+
+    /// Lets `POST /admin/config` put changed provider settings (a rotated
+    /// key) into service after a validation call.
+    pub fn with_provider_reload(
+        mut self,
+        provider: Arc<SwappableProvider>,
+        factory: ProviderFactory,
+    ) -> Self {
+        self.provider = Some((provider, factory));
+        self
+    }
+
+    fn authorize(&self, request: &Request) -> Result<(), (u16, String)> {
+        if request_token(request).is_some_and(|token| constant_time_eq(&token, &self.token)) {
+            return Ok(());
+        }
+        match self.guard.check(request) {
+            Ok(Some(key)) if key.admin => Ok(()),
+            Ok(Some(_)) => Err((403, "API key is not an admin key".to_string())),
+            Ok(None) => Err((401, "admin token or admin API key required".to_string())),
+            Err(err) => Err((err.status_code(), err.to_string())),
+        }
+    }
+
+    /// Serves `/admin/*`; hands any other request back.
+    pub fn handle(self: &Arc<Self>, mut request: Request) -> Option<Request> {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        if path != "/admin" && !path.starts_with("/admin/") {
+            return Some(request);
+        }
+        if let Err((status, error)) = self.authorize(&request) {
+            respond_json(request, status, json!({ "ok": false, "error": error }));
+            return None;
+        }
+        let mut body = String::new();
+        let _ = request.as_reader().read_to_string(&mut body);
+        let method = request.method().clone();
+        if method == Method::Post && matches!(path, "/admin/consolidate" | "/admin/config") {
+            // Both call the model; keep the listener responsive meanwhile.
+            let api = self.clone();
+            let config = path == "/admin/config";
+            std::thread::spawn(move || {
+                let (status, payload) = if config {
+                    api.set_config(&body)
+                } else {
+                    api.consolidate(&body)
+                };
+                respond_json(request, status, payload);
+            });
+            return None;
+        }
+        let (status, payload) = match (method, path) {
+            (Method::Get, "/admin/sessions") => (200, self.sessions_report()),
+            (Method::Get, "/admin/queues") => (200, self.queues_report()),
+            (Method::Get, "/admin/errors") => {
+                let limit = query_param(query, "limit")
+                    .and_then(|limit| limit.parse().ok())
+                    .unwrap_or(DEFAULT_ERROR_LIMIT);
+                (
+                    200,
+                    json!({ "ok": true, "errors": recent_error_lines(limit) }),
+                )
+            }
+            (Method::Get, "/admin/tools") => (200, self.tools_report()),
+            (Method::Post, "/admin/tools") => self.toggle_tool(&body),
+            _ => (404, json!({ "ok": false, "error": "unknown admin route" })),
+        };
+        respond_json(request, status, payload);
+        None
+    }
+
+    fn sessions_report(&self) -> Value {
+        let active = self
+            .agent
+            .active_sessions()
+            .into_iter()
+            .map(|(key, since)| json!({ "key": key, "since": since.to_rfc3339() }))
+            .collect::<Vec<_>>();
+        let mut sessions = self
+            .sessions
+            .list_session_keys()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|key| self.sessions.load_session(&key).ok())
+            .map(|session| {
+                json!({
+                    "key": session.key,
+                    "messages": session.messages.len(),
+                    "updatedAt": session.updated_at.to_rfc3339(),
+                })
+            })
+            .collect::<Vec<_>>();
+        sessions.sort_by(|a, b| b["updatedAt"].as_str().cmp(&a["updatedAt"].as_str()));
+        json!({ "ok": true, "active": active, "sessions": sessions })
+    }
+
+    fn queues_report(&self) -> Value {
+        let subagents = self.runtime.block_on(self.agent.running_subagents());
+        json!({
+            "ok": true,
+            "inbound": self.bus.inbound_size(),
+            "outbound": self.bus.outbound_size(),
+            "activeTurns": self.agent.active_sessions().len(),
+            "subagents": subagents,
+        })
+    }
+
+    fn tools_report(&self) -> Value {
+        json!({ "ok": true, "tools": self.agent.tools().list() })
+    }
+
+    fn toggle_tool(&self, body: &str) -> (u16, Value) {
+        let toggle = match serde_json::from_str::<ToolToggle>(body) {
+            Ok(toggle) => toggle,
+            Err(err) => return (400, json!({ "ok": false, "error": err.to_string() })),
+        };
+        if !self.agent.tools().set_enabled(&toggle.name, toggle.enabled) {
+            return (
+                404,
+                json!({ "ok": false, "error": format!("unknown tool '{}'", toggle.name) }),
+            );
+        }
+        tracing::info!(
+            tool = %toggle.name,
+            enabled = toggle.enabled,
+            "tool toggled via admin API"
+        );
+        (
+            200,
+            json!({ "ok": true, "name": toggle.name, "enabled": toggle.enabled }),
+        )
+    }
+
+    /// Changes one config value. `providers.*` changes are checked with a
+    /// call through the provider they set up and applied at once; the file is
+    /// left alone when that call fails. Anything else applies on restart.
+    fn set_config(&self, body: &str) -> (u16, Value) {
+        let request = match serde_json::from_str::<ConfigSet>(body) {
+            Ok(request) => request,
+            Err(err) => return (400, json!({ "ok": false, "error": err.to_string() })),
+        };
+        let edit = match ConfigEdit::new(&request.key, &request.value) {
+            Ok(edit) => edit,
+            Err(err) => return (400, json!({ "ok": false, "error": format!("{err:#}") })),
+        };
+        let reload = self
+            .provider
+            .as_ref()
+            .filter(|_| request.key.starts_with("providers."));
+        let replacement = match reload {
+            Some((_, factory)) => {
+                let checked = self
+                    .runtime
+                    .block_on(swap::verify_setting(&edit.config, &request.key))
+                    .and_then(|_| factory(&edit.config));
+                match checked {
+                    Ok(provider) => Some(provider),
+                    Err(err) => {
+                        tracing::warn!(key = %request.key, "config change rejected: {err:#}");
+                        return (422, json!({ "ok": false, "error": format!("{err:#}") }));
+                    }
+                }
+            }
+            None => None,
+        };
+        if let Err(err) = edit.save() {
+            return (500, json!({ "ok": false, "error": format!("{err:#}") }));
+        }
+        let applied = match (reload, replacement) {
+            (Some((live, _)), Some(provider)) => {
+                live.replace(provider);
+                true
+            }
+            _ => false,
+        };
+        tracing::info!(key = %request.key, applied, "config changed via admin API");
+        (
+            200,
+            json!({ "ok": true, "key": request.key, "applied": applied }),
+        )
+    }
+
+    fn consolidate(&self, body: &str) -> (u16, Value) {
+        let request = match serde_json::from_str::<ConsolidateRequest>(body) {
+            Ok(request) => request,
+            Err(err) => return (400, json!({ "ok": false, "error": err.to_string() })),
+        };
+        match self
+            .runtime
+            .block_on(self.agent.consolidate_session(&request.session))
+        {
+            Ok((before, after)) => (
+                200,
+                json!({
+                    "ok": true,
+                    "session": request.session,
+                    "before": before,
+                    "after": after,
+                }),
+            ),
+            Err(err) => (500, json!({ "ok": false, "error": format!("{err:#}") })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_query_parameters() {
+        assert_eq!(query_param("limit=5&x=a%20b", "x").as_deref(), Some("a b"));
+        assert_eq!(query_param("limit=5", "limit").as_deref(), Some("5"));
+        assert_eq!(query_param("", "limit"), None);
+    }
+}
@@ -0,0 +1,408 @@
+use crate::bus::{MessageBus, OutboundMessage};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+const CALLBACK_PREFIX: &str = "approval";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalDecision {
+    Approve,
+    Deny,
+    AlwaysAllowTool,
+}
+
+impl ApprovalDecision {
+    pub const ALL: [ApprovalDecision; 3] = [
+        ApprovalDecision::Approve,
+        ApprovalDecision::Deny,
+        ApprovalDecision::AlwaysAllowTool,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApprovalDecision::Approve => "approve",
+            ApprovalDecision::Deny => "deny",
+            ApprovalDecision::AlwaysAllowTool => "always",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ApprovalDecision::Approve => "Approve",
+            ApprovalDecision::Deny => "Deny",
+            ApprovalDecision::AlwaysAllowTool => "Always allow this tool",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "approve" | "y" | "yes" => Some(ApprovalDecision::Approve),
+            "deny" | "n" | "no" => Some(ApprovalDecision::Deny),
+            "always" | "a" => Some(ApprovalDecision::AlwaysAllowTool),
+            _ => None,
+        }
+    }
+
+    pub fn is_allowed(self) -> bool {
+        self != ApprovalDecision::Deny
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApprovalRequest {
+    pub id: String,
+    pub tool: String,
+    pub arguments: Map<String, Value>,
+    pub channel: String,
+    pub chat_id: String,
+    /// The user (as `access::user_id` names them) whose turn made the call;
+    /// only they may answer from a shared chat.
+    pub requester: Option<String>,
+}
+
+impl ApprovalRequest {
+    pub fn new(tool: &str, arguments: &Map<String, Value>, channel: &str, chat_id: &str) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            id: id[..12].to_string(),
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            channel: channel.to_string(),
+            chat_id: chat_id.to_string(),
+            requester: None,
+        }
+    }
+
+    pub fn with_requester(mut self, requester: Option<String>) -> Self {
+        self.requester = requester;
+        self
+    }
+
+    pub fn arguments_pretty(&self) -> String {
+        serde_json::to_string_pretty(&self.arguments).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    pub fn prompt_text(&self) -> String {
+        format!(
+            "Approval required: the assistant wants to run `{}` with:\n```json\n{}\n```",
+            self.tool,
+            self.arguments_pretty()
+        )
+    }
+
+    /// Metadata attached to the outbound prompt so channels can render buttons.
+    pub fn to_metadata(&self) -> Value {
+        json!({
+            "id": self.id,
+            "tool": self.tool,
+            "options": ApprovalDecision::ALL
+                .iter()
+                .map(|decision| json!({
+                    "label": decision.label(),
+                    "callback": approval_callback_data(&self.id, *decision),
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+pub fn approval_callback_data(id: &str, decision: ApprovalDecision) -> String {
+    format!("{CALLBACK_PREFIX}:{id}:{}", decision.as_str())
+}
+
+pub fn parse_approval_callback(data: &str) -> Option<(String, ApprovalDecision)> {
+    let mut parts = data.splitn(3, ':');
+    if parts.next()? != CALLBACK_PREFIX {
+        return None;
+    }
+    let id = parts.next().filter(|id| !id.is_empty())?;
+    let decision = ApprovalDecision::parse(parts.next()?)?;
+    Some((id.to_string(), decision))
+}
+
+#[async_trait]
+pub trait ApprovalPrompt: Send + Sync {
+    async fn ask(&self, request: &ApprovalRequest) -> ApprovalDecision;
+}
+
+/// What became of a button press on an approval prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Answered,
+    /// Pressed in another chat or by someone other than the requester.
+    NotAllowed,
+    /// No such request is waiting, e.g. it timed out.
+    Expired,
+}
+
+struct Waiter {
+    channel: String,
+    chat_id: String,
+    requester: Option<String>,
+    tx: oneshot::Sender<ApprovalDecision>,
+}
+
+/// Approvals waiting on an answer from a channel callback (button press).
+#[derive(Default)]
+pub struct PendingApprovals {
+    pending: Mutex<HashMap<String, Waiter>>,
+}
+
+impl PendingApprovals {
+    pub fn global() -> &'static PendingApprovals {
+        static PENDING: OnceLock<PendingApprovals> = OnceLock::new();
+        PENDING.get_or_init(PendingApprovals::default)
+    }
+
+    pub fn register(&self, request: &ApprovalRequest) -> oneshot::Receiver<ApprovalDecision> {
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(
+                request.id.clone(),
+                Waiter {
+                    channel: request.channel.clone(),
+                    chat_id: request.chat_id.clone(),
+                    requester: request.requester.clone(),
+                    tx,
+                },
+            );
+        }
+        rx
+    }
+
+    /// Answers request `id` with a button `user` pressed in
+    /// `channel:chat_id`. The request keeps waiting when the press came from
+    /// another chat or user.
+    pub fn resolve(
+        &self,
+        id: &str,
+        channel: &str,
+        chat_id: &str,
+        user: &str,
+        decision: ApprovalDecision,
+    ) -> Resolution {
+        let Ok(mut pending) = self.pending.lock() else {
+            return Resolution::Expired;
+        };
+        let Some(waiter) = pending.get(id) else {
+            return Resolution::Expired;
+        };
+        if waiter.channel != channel
+            || waiter.chat_id != chat_id
+            || waiter.requester.as_deref().is_some_and(|r| r != user)
+        {
+            warn!(
+                id,
+                channel, chat_id, user, "approval answered from the wrong chat or user"
+            );
+            return Resolution::NotAllowed;
+        }
+        let waiter = pending.remove(id).expect("just found");
+        match waiter.tx.send(decision) {
+            Ok(()) => Resolution::Answered,
+            Err(_) => Resolution::Expired,
+        }
+    }
+
+    pub fn cancel(&self, id: &str) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(id);
+        }
+    }
+}
+
+/// Sends the prompt through the bus and waits for a channel callback.
+pub struct ChannelApprovalPrompt {
+    bus: Arc<MessageBus>,
+    timeout: Duration,
+}
+
+impl ChannelApprovalPrompt {
+    pub fn new(bus: Arc<MessageBus>, timeout: Duration) -> Self {
+        Self { bus, timeout }
+    }
+}
+
+#[async_trait]
+impl ApprovalPrompt for ChannelApprovalPrompt {
+    async fn ask(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        let pending = PendingApprovals::global();
+        let rx = pending.register(request);
+        let mut outbound = OutboundMessage::new(
+            request.channel.clone(),
+            request.chat_id.clone(),
+            request.prompt_text(),
+        );
+        outbound
+            .metadata
+            .insert("approval".to_string(), request.to_metadata());
+        if let Err(err) = self.bus.publish_outbound(outbound).await {
+            warn!("failed to send approval prompt: {err}");
+            pending.cancel(&request.id);
+            return ApprovalDecision::Deny;
+        }
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(decision)) => decision,
+            _ => {
+                pending.cancel(&request.id);
+                info!(tool = %request.tool, "approval timed out, denying");
+                ApprovalDecision::Deny
+            }
+        }
+    }
+}
+
+pub struct ApprovalGate {
+    tools: HashSet<String>,
+    /// `(channel, chat_id, tool)` answered "always" in that chat.
+    always_allowed: Mutex<HashSet<(String, String, String)>>,
+    prompts: HashMap<String, Arc<dyn ApprovalPrompt>>,
+}
+
+impl ApprovalGate {
+    pub fn new(tools: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            tools: tools.into_iter().collect(),
+            always_allowed: Mutex::new(HashSet::new()),
+            prompts: HashMap::new(),
+        }
+    }
+
+    pub fn with_prompt(mut self, channel: &str, prompt: Arc<dyn ApprovalPrompt>) -> Self {
+        self.prompts.insert(channel.to_string(), prompt);
+        self
+    }
+
+    /// Whether `channel` can show an approval prompt and wait for the answer.
+    pub fn can_prompt(&self, channel: &str) -> bool {
+        self.prompts.contains_key(channel)
+    }
+
+    pub fn requires_approval(&self, channel: &str, chat_id: &str, tool: &str) -> bool {
+        let key = (channel.to_string(), chat_id.to_string(), tool.to_string());
+        self.tools.contains(tool)
+            && !self
+                .always_allowed
+                .lock()
+                .map(|allowed| allowed.contains(&key))
+                .unwrap_or(false)
+    }
+
+    pub async fn check(
+        &self,
+        channel: &str,
+        chat_id: &str,
+        tool: &str,
+        arguments: &Map<String, Value>,
+    ) -> ApprovalDecision {
+        if !self.requires_approval(channel, chat_id, tool) {
+            return ApprovalDecision::Approve;
+        }
+        let Some(prompt) = self.prompts.get(channel) else {
+            warn!(tool, channel, "approval required but channel cannot prompt");
+            return ApprovalDecision::Deny;
+        };
+        // Turns not started by a user of this channel (cron, subagent results
+        // attributed to the chat itself) can be answered by anyone there.
+        let requester = crate::usage::origin_user().filter(|user| {
+            user.starts_with(&format!("{channel}:")) && *user != format!("{channel}:{chat_id}")
+        });
+        let request =
+            ApprovalRequest::new(tool, arguments, channel, chat_id).with_requester(requester);
+        let decision = prompt.ask(&request).await;
+        if decision == ApprovalDecision::AlwaysAllowTool
+            && let Ok(mut allowed) = self.always_allowed.lock()
+        {
+            allowed.insert((channel.to_string(), chat_id.to_string(), tool.to_string()));
+        }
+        info!(
+            tool,
+            channel,
+            decision = decision.as_str(),
+            "approval decided"
+        );
+        decision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedPrompt(ApprovalDecision);
+
+    #[async_trait]
+    impl ApprovalPrompt for FixedPrompt {
+        async fn ask(&self, _request: &ApprovalRequest) -> ApprovalDecision {
+            self.0
+        }
+    }
+
+    #[test]
+    fn callback_data_round_trips() {
+        let data = approval_callback_data("abc123", ApprovalDecision::AlwaysAllowTool);
+        assert_eq!(data, "approval:abc123:always");
+        assert_eq!(
+            parse_approval_callback(&data),
+            Some(("abc123".to_string(), ApprovalDecision::AlwaysAllowTool))
+        );
+        assert_eq!(parse_approval_callback("other:abc:approve"), None);
+        assert_eq!(parse_approval_callback("approval::approve"), None);
+    }
+
+    #[tokio::test]
+    async fn gate_remembers_always_allow_and_denies_unprompted_channels() {
+        let gate = ApprovalGate::new(["exec".to_string()]).with_prompt(
+            "cli",
+            Arc::new(FixedPrompt(ApprovalDecision::AlwaysAllowTool)),
+        );
+        let args = Map::new();
+        assert_eq!(
+            gate.check("cli", "direct", "read_file", &args).await,
+            ApprovalDecision::Approve
+        );
+        assert_eq!(
+            gate.check("feishu", "chat", "exec", &args).await,
+            ApprovalDecision::Deny
+        );
+        assert_eq!(
+            gate.check("cli", "direct", "exec", &args).await,
+            ApprovalDecision::AlwaysAllowTool
+        );
+        assert!(!gate.requires_approval("cli", "direct", "exec"));
+        // "Always" holds for that chat only.
+        assert!(gate.requires_approval("cli", "other", "exec"));
+    }
+
+    #[tokio::test]
+    async fn pending_approvals_resolve_waiting_receiver() {
+        let pending = PendingApprovals::default();
+        let request = ApprovalRequest::new("exec", &Map::new(), "telegram", "42")
+            .with_requester(Some("telegram:7|alice".to_string()));
+        let rx = pending.register(&request);
+        let id = request.id.as_str();
+        let approve = ApprovalDecision::Approve;
+        assert_eq!(
+            pending.resolve(id, "telegram", "99", "telegram:7|alice", approve),
+            Resolution::NotAllowed
+        );
+        assert_eq!(
+            pending.resolve(id, "telegram", "42", "telegram:8|mallory", approve),
+            Resolution::NotAllowed
+        );
+        assert_eq!(
+            pending.resolve(id, "telegram", "42", "telegram:7|alice", approve),
+            Resolution::Answered
+        );
+        assert_eq!(rx.await.ok(), Some(ApprovalDecision::Approve));
+        assert_eq!(
+            pending.resolve(id, "telegram", "42", "telegram:7|alice", approve),
+            Resolution::Expired
+        );
+    }
+}
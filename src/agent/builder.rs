@@ -0,0 +1,489 @@
+//! Named-option construction of an [`AgentLoop`] for embedding nanobot as a
+//! library.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # fn example(provider: Arc<dyn nanobot::providers::base::LLMProvider>) -> anyhow::Result<()> {
+//! let agent = nanobot::agent::AgentLoopBuilder::new(provider)
+//!     .workspace("/tmp/bot")
+//!     .restrict_to_workspace(true)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::agent::approval::ApprovalGate;
+use crate::agent::r#loop::AgentLoop;
+use crate::budget;
+use crate::bus::MessageBus;
+use crate::config::{
+    AccessConfig, AgentDefaults, AgentProfile, BudgetConfig, Config, ContentFilterConfig,
+    ContextSourceConfig, IdentityConfig, LanguageConfig, ModelPrice, PostProcessConfig,
+    PublishConfig, RouterConfig, StreamingConfig, ToolSelectionConfig, ToolStatsConfig,
+    VerifyConfig, WebSearchConfig, WorkspaceConfig,
+};
+use crate::cron::CronService;
+use crate::location::LocationService;
+use crate::providers::base::LLMProvider;
+use crate::session::SessionManager;
+use crate::tools::registry::ToolRegistry;
+use crate::utils::resolve_workspace;
+use crate::workflows::WorkflowRunner;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const DEFAULT_BUS_CAPACITY: usize = 1024;
+
+pub struct AgentLoopBuilder {
+    provider: Arc<dyn LLMProvider>,
+    bus: Option<Arc<MessageBus>>,
+    workspace: PathBuf,
+    model: Option<String>,
+    max_iterations: u32,
+    memory_window: usize,
+    max_concurrent_turns: usize,
+    web_search: WebSearchConfig,
+    exec_timeout_s: u64,
+    restrict_to_workspace: bool,
+    cron: Option<Arc<CronService>>,
+    sessions: Option<Arc<SessionManager>>,
+    tools: Option<ToolRegistry>,
+    approval: Option<Arc<ApprovalGate>>,
+    content_filter: ContentFilterConfig,
+    language: LanguageConfig,
+    post_process: PostProcessConfig,
+    identity: IdentityConfig,
+    context_sources: Vec<ContextSourceConfig>,
+    workflows: Option<Arc<WorkflowRunner>>,
+    publish: Option<PublishConfig>,
+    budget: Option<BudgetConfig>,
+    profiles: HashMap<String, AgentProfile>,
+    access: AccessConfig,
+    tool_stats: Option<ToolStatsConfig>,
+    verify: VerifyConfig,
+    tool_selection: ToolSelectionConfig,
+    router: RouterConfig,
+    read_only: bool,
+    location: Option<Arc<LocationService>>,
+    streaming: StreamingConfig,
+    prices: HashMap<String, ModelPrice>,
+    workspace_routes: Vec<(WorkspaceConfig, Option<Arc<AgentLoop>>)>,
+}
+
+impl AgentLoopBuilder {
+    /// Starts from the same defaults as an empty `config.json`.
+    pub fn new(provider: Arc<dyn LLMProvider>) -> Self {
+        let defaults = AgentDefaults::default();
+        Self {
+            provider,
+            bus: None,
+            workspace: resolve_workspace(&defaults.workspace),
+            model: None,
+            max_iterations: defaults.max_tool_iterations,
+            memory_window: defaults.memory_window,
+            max_concurrent_turns: defaults.max_concurrent_turns,
+            web_search: WebSearchConfig::default(),
+            exec_timeout_s: 60,
+            restrict_to_workspace: false,
+            cron: None,
+            sessions: None,
+            tools: None,
+            approval: None,
+            content_filter: defaults.content_filter,
+            language: defaults.language,
+            post_process: defaults.post_process,
+            identity: defaults.identity,
+            context_sources: defaults.context_sources,
+            workflows: None,
+            publish: None,
+            budget: None,
+            profiles: HashMap::new(),
+            access: AccessConfig::default(),
+            tool_stats: None,
+            verify: VerifyConfig::default(),
+            tool_selection: ToolSelectionConfig::default(),
+            router: RouterConfig::default(),
+            read_only: false,
+            location: None,
+            streaming: StreamingConfig::default(),
+            prices: HashMap::new(),
+            workspace_routes: Vec::new(),
+        }
+    }
+
+    /// Takes workspace, limits, tool settings, access, location and budget
+    /// from `config`.
+    pub fn from_config(provider: Arc<dyn LLMProvider>, config: &Config) -> Self {
+        let defaults = &config.agents.defaults;
+        let mut builder = Self::new(provider)
+            .workspace(config.workspace_path())
+            .model(defaults.model.clone())
+            .max_iterations(defaults.max_tool_iterations)
+            .memory_window(defaults.memory_window)
+            .max_concurrent_turns(defaults.max_concurrent_turns)
+            .web_search(config.tools.web.search.clone())
+            .exec_timeout(config.tools.exec.timeout)
+            .restrict_to_workspace(config.tools.restrict_to_workspace)
+            .content_filter(defaults.content_filter.clone())
+            .language(defaults.language.clone())
+            .post_process(defaults.post_process.clone())
+            .identity(defaults.identity.clone())
+            .context_sources(defaults.context_sources.clone())
+            .profiles(config.agents.profiles.clone())
+            .access(config.access.clone())
+            .tool_stats(config.tools.stats.clone())
+            .verify(config.tools.verify.clone())
+            .tool_selection(config.tools.selection.clone())
+            .router(config.router.clone())
+            .publish(config.publish.clone())
+            .read_only(config.read_only())
+            .streaming(config.channels.streaming.clone())
+            .prices(config.budget.model_prices());
+        if let Some(location) = LocationService::from_config(&config.location) {
+            builder = builder.location(location);
+        }
+        if config.budget.enabled {
+            builder = builder.budget(config.budget.clone());
+        }
+        builder
+    }
+
+    /// The bus outbound messages go to; a private one is created otherwise.
+    pub fn bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn workspace(mut self, workspace: impl Into<PathBuf>) -> Self {
+        self.workspace = workspace.into();
+        self
+    }
+
+    /// Defaults to the provider's default model.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: u32) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn memory_window(mut self, memory_window: usize) -> Self {
+        self.memory_window = memory_window;
+        self
+    }
+
+    /// Chats `AgentLoop::run` answers at once.
+    pub fn max_concurrent_turns(mut self, turns: usize) -> Self {
+        self.max_concurrent_turns = turns;
+        self
+    }
+
+    pub fn web_search(mut self, config: WebSearchConfig) -> Self {
+        self.web_search = config;
+        self
+    }
+
+    pub fn exec_timeout(mut self, seconds: u64) -> Self {
+        self.exec_timeout_s = seconds;
+        self
+    }
+
+    pub fn restrict_to_workspace(mut self, restrict: bool) -> Self {
+        self.restrict_to_workspace = restrict;
+        self
+    }
+
+    /// Enables the `cron` tool and scheduled task reminders.
+    pub fn cron(mut self, cron: Arc<CronService>) -> Self {
+        self.cron = Some(cron);
+        self
+    }
+
+    /// The session store; defaults to `~/.nanobot/sessions`.
+    pub fn sessions(mut self, sessions: Arc<SessionManager>) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Extra tools, added after the built-ins (see `AgentLoop::with_tools`).
+    pub fn tools(mut self, registry: ToolRegistry) -> Self {
+        self.tools = Some(registry);
+        self
+    }
+
+    /// Asks for approval before the gate's tools run.
+    pub fn approval_gate(mut self, gate: Arc<ApprovalGate>) -> Self {
+        self.approval = Some(gate);
+        self
+    }
+
+    pub fn content_filter(mut self, config: ContentFilterConfig) -> Self {
+        self.content_filter = config;
+        self
+    }
+
+    pub fn language(mut self, config: LanguageConfig) -> Self {
+        self.language = config;
+        self
+    }
+
+    pub fn post_process(mut self, config: PostProcessConfig) -> Self {
+        self.post_process = config;
+        self
+    }
+
+    /// `agents.defaults.identity`: snippets that replace SOUL.md per channel.
+    pub fn identity(mut self, config: IdentityConfig) -> Self {
+        self.identity = config;
+        self
+    }
+
+    pub fn context_sources(mut self, sources: Vec<ContextSourceConfig>) -> Self {
+        self.context_sources = sources;
+        self
+    }
+
+    /// Registers `run_workflow`; call `runner.attach` once the loop is shared.
+    pub fn workflows(mut self, runner: Arc<WorkflowRunner>) -> Self {
+        self.workflows = Some(runner);
+        self
+    }
+
+    /// `publish`: registers `publish_note`, writing notes to its folder.
+    pub fn publish(mut self, config: PublishConfig) -> Self {
+        self.publish = Some(config);
+        self
+    }
+
+    /// Tracks spend against `config` and alerts over the bus.
+    pub fn budget(mut self, config: BudgetConfig) -> Self {
+        self.budget = Some(config);
+        self
+    }
+
+    /// Leaves spend tracking to the caller, for a provider already wrapped by
+    /// `budget::wrap_provider` and shared by several loops.
+    pub fn without_budget(mut self) -> Self {
+        self.budget = None;
+        self
+    }
+
+    /// `agents.profiles`: tool allowlists for `api:<profile>` sessions.
+    pub fn profiles(mut self, profiles: HashMap<String, AgentProfile>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// `access`: roles that narrow tools, models and spend per user.
+    pub fn access(mut self, config: AccessConfig) -> Self {
+        self.access = config;
+        self
+    }
+
+    /// `tools.stats`: records tool calls to `tool_stats.jsonl`.
+    pub fn tool_stats(mut self, config: ToolStatsConfig) -> Self {
+        self.tool_stats = Some(config);
+        self
+    }
+
+    /// `tools.verify`: the check run after the agent edits files.
+    pub fn verify(mut self, config: VerifyConfig) -> Self {
+        self.verify = config;
+        self
+    }
+
+    pub fn tool_selection(mut self, config: ToolSelectionConfig) -> Self {
+        self.tool_selection = config;
+        self
+    }
+
+    /// `router.tiers`: the models cron, heartbeat and workflow turns run on.
+    pub fn router(mut self, config: RouterConfig) -> Self {
+        self.router = config;
+        self
+    }
+
+    /// `tools.readOnly`: refuses tools that change anything.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// `location`: the user's location in the prompt and the place tools.
+    pub fn location(mut self, service: Arc<LocationService>) -> Self {
+        self.location = Some(service);
+        self
+    }
+
+    /// `channels.streaming`: answers shown on chat channels as they are written.
+    pub fn streaming(mut self, config: StreamingConfig) -> Self {
+        self.streaming = config;
+        self
+    }
+
+    /// `budget.prices` over the built-in table, for costs in `/usage`.
+    pub fn prices(mut self, prices: HashMap<String, ModelPrice>) -> Self {
+        self.prices = prices;
+        self
+    }
+
+    /// Loops of other workspaces that `run` hands their chats to.
+    pub fn workspace_routes(
+        mut self,
+        routes: Vec<(WorkspaceConfig, Option<Arc<AgentLoop>>)>,
+    ) -> Self {
+        self.workspace_routes = routes;
+        self
+    }
+
+    /// Routes for [`AgentLoopBuilder::workspace_routes`]: a loop per other
+    /// workspace that claims chats (`workspaces.<name>.channels`), built with
+    /// `from_config` on that workspace's config and finished by `setup` with
+    /// what it shares with the calling loop. The active workspace keeps its
+    /// chats on the calling loop.
+    pub fn routes_from_config(
+        provider: &Arc<dyn LLMProvider>,
+        config: &Config,
+        setup: impl Fn(&Config, Self) -> Result<Self>,
+    ) -> Result<Vec<(WorkspaceConfig, Option<Arc<AgentLoop>>)>> {
+        let mut routes = Vec::new();
+        for (name, workspace) in &config.workspaces {
+            if workspace.channels.is_empty() {
+                continue;
+            }
+            if config.active_workspace.as_deref() == Some(name.as_str()) {
+                routes.push((workspace.clone(), None));
+                continue;
+            }
+            let ws_config = config.for_workspace(name)?;
+            let builder = setup(&ws_config, Self::from_config(provider.clone(), &ws_config))?;
+            tracing::info!(
+                workspace = %name,
+                path = %ws_config.workspace_path().display(),
+                channels = ?workspace.channels,
+                "serving workspace"
+            );
+            routes.push((workspace.clone(), Some(Arc::new(builder.build()?))));
+        }
+        Ok(routes)
+    }
+
+    pub fn build(self) -> Result<AgentLoop> {
+        let bus = self
+            .bus
+            .unwrap_or_else(|| Arc::new(MessageBus::new(DEFAULT_BUS_CAPACITY)));
+        let provider = match &self.budget {
+            Some(config) => budget::wrap_with_budget(self.provider, config, bus.clone()),
+            None => self.provider,
+        };
+        let mut agent = AgentLoop::assemble(
+            bus,
+            provider,
+            self.workspace,
+            self.model,
+            self.max_iterations,
+            self.memory_window,
+            self.web_search,
+            self.exec_timeout_s,
+            self.restrict_to_workspace,
+            self.cron,
+            self.sessions,
+        )?
+        .with_content_filter(self.content_filter)
+        .with_language(self.language)
+        .with_post_process(self.post_process)
+        .with_identity(self.identity)
+        .with_context_sources(self.context_sources)
+        .with_profiles(self.profiles)
+        .with_access(self.access)
+        .with_verify(&self.verify)
+        .with_tool_selection(self.tool_selection)
+        .with_router(self.router)
+        .with_read_only(self.read_only)
+        .with_max_concurrent_turns(self.max_concurrent_turns)
+        .with_location(self.location)
+        .with_streaming(self.streaming)
+        .with_prices(self.prices)
+        .with_workspace_routes(self.workspace_routes);
+        if let Some(gate) = self.approval {
+            agent = agent.with_approval_gate(gate);
+        }
+        if let Some(runner) = self.workflows {
+            agent = agent.with_workflows(runner);
+        }
+        if let Some(config) = self.publish {
+            agent = agent.with_publish(&config);
+        }
+        if let Some(registry) = self.tools {
+            agent = agent.with_tools(registry);
+        }
+        if let Some(config) = self.tool_stats {
+            agent = agent.with_tool_stats(config);
+        }
+        Ok(agent)
+    }
+}
+
+impl AgentLoop {
+    pub fn builder(provider: Arc<dyn LLMProvider>) -> AgentLoopBuilder {
+        AgentLoopBuilder::new(provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Cassette, EvalProvider};
+    use crate::tools::base::Tool;
+    use async_trait::async_trait;
+    use serde_json::{Map, Value, json};
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echo the input"
+        }
+
+        fn parameters(&self) -> Value {
+            json!({"type": "object", "properties": {"text": {"type": "string"}}})
+        }
+
+        async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+            Ok(params["text"].as_str().unwrap_or_default().to_string())
+        }
+    }
+
+    #[test]
+    fn builds_with_defaults_and_custom_tools() {
+        let dir = std::env::temp_dir().join(format!("nanobot-builder-{}", uuid::Uuid::new_v4()));
+        let provider = Arc::new(EvalProvider::replay(Cassette::default(), "test-model"));
+        let mut tools = ToolRegistry::new();
+        tools.register(Arc::new(EchoTool));
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("workspace"))
+            .sessions(Arc::new(
+                SessionManager::with_dir(dir.join("sessions")).expect("sessions"),
+            ))
+            .max_iterations(3)
+            .tools(tools)
+            .build()
+            .expect("agent");
+
+        let names = agent.tools().tool_names();
+        assert!(names.iter().any(|name| name == "echo"));
+        assert!(names.iter().any(|name| name == "read_file"));
+        assert!(!names.iter().any(|name| name == "cron"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
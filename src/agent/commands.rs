@@ -0,0 +1,131 @@
+//! Chat commands (`/new`, `/status`, `/model`, ...) answered by the agent
+//! loop before a turn starts.
+//!
+//! Channels forward command messages as text; [`COMMANDS`] is the one list
+//! used for parsing, the `/help` text and channel command menus, so every
+//! adapter offers the same commands.
+
+use crate::i18n::tr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    New,
+    Compact,
+    Status,
+    Model,
+    Usage,
+    Help,
+}
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    /// Shown after the name in `/help`, e.g. `[name]`.
+    pub usage: &'static str,
+    /// i18n id of the one-line description.
+    pub description: &'static str,
+    pub kind: CommandKind,
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "new",
+        aliases: &["reset"],
+        usage: "",
+        description: "command-new",
+        kind: CommandKind::New,
+    },
+    CommandSpec {
+        name: "compact",
+        aliases: &[],
+        usage: "",
+        description: "command-compact",
+        kind: CommandKind::Compact,
+    },
+    CommandSpec {
+        name: "status",
+        aliases: &[],
+        usage: "",
+        description: "command-status",
+        kind: CommandKind::Status,
+    },
+    CommandSpec {
+        name: "model",
+        aliases: &[],
+        usage: "[name|default]",
+        description: "command-model",
+        kind: CommandKind::Model,
+    },
+    CommandSpec {
+        name: "usage",
+        aliases: &[],
+        usage: "",
+        description: "command-usage",
+        kind: CommandKind::Usage,
+    },
+    CommandSpec {
+        name: "help",
+        aliases: &[],
+        usage: "",
+        description: "command-help",
+        kind: CommandKind::Help,
+    },
+];
+
+#[derive(Clone, Copy)]
+pub struct Command<'a> {
+    pub spec: &'static CommandSpec,
+    /// Text after the command name, trimmed.
+    pub args: &'a str,
+}
+
+/// The registered command `content` invokes, as in `/model gpt-4o` or
+/// Telegram's `/status@my_bot`; `None` for other text and unknown commands.
+pub fn parse(content: &str) -> Option<Command<'_>> {
+    let rest = content.trim().strip_prefix('/')?;
+    let (word, args) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(word, args)| (word, args.trim()));
+    let name = word.split('@').next().unwrap_or_default().to_lowercase();
+    let spec = COMMANDS
+        .iter()
+        .find(|spec| spec.name == name || spec.aliases.contains(&name.as_str()))?;
+    Some(Command { spec, args })
+}
+
+/// The `/help` text listing every command.
+pub fn help() -> String {
+    let mut text = tr("help-header");
+    for spec in COMMANDS {
+        let usage = if spec.usage.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", spec.usage)
+        };
+        text.push_str(&format!(
+            "\n/{}{usage} - {}",
+            spec.name,
+            tr(spec.description)
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_registered_commands_with_arguments() {
+        let command = parse("  /Model@nanobot_bot  openai/gpt-4o ").expect("model");
+        assert_eq!(command.spec.kind, CommandKind::Model);
+        assert_eq!(command.args, "openai/gpt-4o");
+        assert_eq!(parse("/reset").map(|c| c.spec.kind), Some(CommandKind::New));
+        assert!(parse("/unknown").is_none());
+        assert!(parse("status").is_none());
+
+        let help = help();
+        assert!(help.contains("/model [name|default] - "));
+        assert_eq!(help.lines().count(), COMMANDS.len() + 1);
+    }
+}
@@ -1,14 +1,75 @@
 use crate::memory::MemoryStore;
+use crate::prompts::PromptTemplate;
 use crate::skills::SkillsLoader;
 use base64::Engine;
 use chrono::Local;
 use serde_json::{Value, json};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::warn;
+
+const BOOTSTRAP_FILES: [&str; 5] = ["AGENTS.md", "SOUL.md", "USER.md", "TOOLS.md", "IDENTITY.md"];
+/// Workspace directory of identity snippets, prompt templates named after
+/// the identity.
+const IDENTITIES_DIR: &str = "identities";
+
+/// Modification time and size of every watched file, keyed by workspace-relative path.
+type Stamps = BTreeMap<String, (Option<SystemTime>, u64)>;
+
+/// Prompt sections read from workspace files, rebuilt when those files change.
+struct PromptCache {
+    stamps: Stamps,
+    /// Instruction files that exist, by name.
+    bootstrap: BTreeMap<&'static str, String>,
+    /// Snippets in `identities/` that can stand in for SOUL.md, by name.
+    identities: BTreeMap<String, PromptTemplate>,
+    always_skills: String,
+    skills_summary: String,
+}
+
+impl PromptCache {
+    /// The instruction file sections, with SOUL.md replaced by the
+    /// `identity` snippet when it exists and renders.
+    fn bootstrap(&self, identity: Option<&str>, channel: &str) -> String {
+        let identity = identity.and_then(|name| {
+            let template = self.identities.get(name).or_else(|| {
+                warn!("identity '{name}' not found in {IDENTITIES_DIR}/");
+                None
+            })?;
+            let vars = BTreeMap::from([("channel".to_string(), channel.to_string())]);
+            match template.render(&vars) {
+                Ok(text) => Some(format!("## Identity: {name}\n\n{text}")),
+                Err(err) => {
+                    warn!("identity '{name}' not used: {err:#}");
+                    None
+                }
+            }
+        });
+        BOOTSTRAP_FILES
+            .iter()
+            .filter_map(|filename| match (*filename, &identity) {
+                ("SOUL.md", Some(section)) => Some(section.clone()),
+                _ => self
+                    .bootstrap
+                    .get(filename)
+                    .map(|content| format!("## {filename}\n\n{content}")),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
 
 pub struct ContextBuilder {
     workspace: PathBuf,
     memory: MemoryStore,
     skills: SkillsLoader,
+    cache: Mutex<Option<PromptCache>>,
+    /// Latest rendering of the configured context sources.
+    live_context: Mutex<String>,
+    /// Where the user is, when `location` is configured.
+    location: Mutex<String>,
 }
 
 impl ContextBuilder {
@@ -19,71 +80,189 @@ impl ContextBuilder {
             workspace,
             memory,
             skills,
+            cache: Mutex::new(None),
+            live_context: Mutex::new(String::new()),
+            location: Mutex::new(String::new()),
         })
     }
 
+    fn watched_stamps(&self) -> Stamps {
+        let mut stamps = Stamps::new();
+        let mut add = |path: &Path| {
+            if let Ok(meta) = std::fs::metadata(path) {
+                let name = path
+                    .strip_prefix(&self.workspace)
+                    .unwrap_or(path)
+                    .display()
+                    .to_string();
+                stamps.insert(name, (meta.modified().ok(), meta.len()));
+            }
+        };
+        for filename in BOOTSTRAP_FILES {
+            add(&self.workspace.join(filename));
+        }
+        if let Ok(entries) = std::fs::read_dir(self.workspace.join(IDENTITIES_DIR)) {
+            for entry in entries.flatten() {
+                add(&entry.path());
+            }
+        }
+        let skills_dir = self.workspace.join("skills");
+        if let Ok(entries) = std::fs::read_dir(&skills_dir) {
+            for entry in entries.flatten() {
+                add(&entry.path().join("SKILL.md"));
+            }
+        }
+        stamps
+    }
+
+    fn load_cache(&self, stamps: Stamps) -> PromptCache {
+        let bootstrap = BOOTSTRAP_FILES
+            .iter()
+            .filter_map(|filename| {
+                std::fs::read_to_string(self.workspace.join(filename))
+                    .ok()
+                    .map(|content| (*filename, content))
+            })
+            .collect();
+        let always = self.skills.get_always_skills();
+        let always_skills = if always.is_empty() {
+            String::new()
+        } else {
+            self.skills.load_skills_for_context(&always)
+        };
+        PromptCache {
+            stamps,
+            bootstrap,
+            identities: load_identities(&self.workspace.join(IDENTITIES_DIR)),
+            always_skills,
+            skills_summary: self.skills.build_skills_summary(),
+        }
+    }
+
+    /// Reloads the file-backed prompt sections if AGENTS.md, SOUL.md, USER.md,
+    /// TOOLS.md, IDENTITY.md or a workspace skill changed since the last call,
+    /// returning the changed paths. The first load reports nothing.
+    pub fn refresh(&self) -> Vec<String> {
+        let stamps = self.watched_stamps();
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = match cache.as_ref() {
+            Some(current) if current.stamps == stamps => return Vec::new(),
+            Some(current) => changed_paths(&current.stamps, &stamps),
+            None => Vec::new(),
+        };
+        *cache = Some(self.load_cache(stamps));
+        changed
+    }
+
+    pub fn set_live_context(&self, content: String) {
+        *self.live_context.lock().unwrap_or_else(|e| e.into_inner()) = content;
+    }
+
+    pub fn set_location(&self, location: String) {
+        *self.location.lock().unwrap_or_else(|e| e.into_inner()) = location;
+    }
+
     pub fn build_system_prompt(&self, skill_names: Option<&[String]>) -> String {
+        let (stable, volatile) = self.build_system_parts(skill_names, None, None);
+        format!("{stable}\n\n---\n\n{volatile}")
+    }
+
+    /// The system prompt as a part that stays the same between turns
+    /// (identity, instruction files, memory, skills), so providers can cache
+    /// it, and a part that changes (time, live context). `identity` names a
+    /// snippet in `identities/` to use instead of SOUL.md on `channel`.
+    fn build_system_parts(
+        &self,
+        skill_names: Option<&[String]>,
+        identity: Option<&str>,
+        channel: Option<&str>,
+    ) -> (String, String) {
         let mut parts = Vec::new();
 
-        let now = Local::now().format("%Y-%m-%d %H:%M (%A)").to_string();
-        let tz = {
-            let value = Local::now().format("%Z").to_string();
-            if value.trim().is_empty() {
-                "UTC".to_string()
-            } else {
-                value
-            }
-        };
         let runtime = format!("{} {}", std::env::consts::OS, std::env::consts::ARCH);
         let workspace = self.workspace.display().to_string();
         parts.push(format!(
-            "# nanobot-rs\n\nYou are nanobot, a helpful AI assistant.\n\n## Current Time\n{now} ({tz})\n\n## Runtime\n{runtime}\n\n## Workspace\n{workspace}\n- Long-term memory: {workspace}/memory/MEMORY.md\n- History log: {workspace}/memory/HISTORY.md (grep-searchable)\n\nIMPORTANT: Respond directly in text for normal chat.\nOnly use the 'message' tool for proactive channel messages.\nAlways be helpful, accurate, and concise. When using tools, think step by step: what you know, what you need, and why you chose this tool.\nWhen remembering something important, write to {workspace}/memory/MEMORY.md\nTo recall past events, grep {workspace}/memory/HISTORY.md"
+            "# nanobot-rs\n\nYou are nanobot, a helpful AI assistant.\n\n## Runtime\n{runtime}\n\n## Workspace\n{workspace}\n- Long-term memory: {workspace}/memory/MEMORY.md\n- History log: {workspace}/memory/HISTORY.md (grep-searchable)\n\nIMPORTANT: Respond directly in text for normal chat.\nOnly use the 'message' tool for proactive channel messages.\nAlways be helpful, accurate, and concise. When using tools, think step by step: what you know, what you need, and why you chose this tool.\nWhen remembering something important, write to {workspace}/memory/MEMORY.md\nTo recall past events, grep {workspace}/memory/HISTORY.md"
         ));
 
-        let bootstrap_files = ["AGENTS.md", "SOUL.md", "USER.md", "TOOLS.md", "IDENTITY.md"];
-        let mut bootstrap_parts = Vec::new();
-        for filename in bootstrap_files {
-            let path = self.workspace.join(filename);
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                bootstrap_parts.push(format!("## {filename}\n\n{content}"));
-            }
-        }
-        if !bootstrap_parts.is_empty() {
-            parts.push(bootstrap_parts.join("\n\n"));
+        self.refresh();
+        let (bootstrap, always_skills, skills_summary) = {
+            let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            cache
+                .as_ref()
+                .map(|cache| {
+                    (
+                        cache.bootstrap(identity, channel.unwrap_or_default()),
+                        cache.always_skills.clone(),
+                        cache.skills_summary.clone(),
+                    )
+                })
+                .unwrap_or_default()
+        };
+        if !bootstrap.is_empty() {
+            parts.push(bootstrap);
         }
 
-        let memory_context = self.memory.get_memory_context();
+        let memory_context = crate::memory::user_memory()
+            .unwrap_or_else(|| self.memory.clone())
+            .get_memory_context();
         if !memory_context.is_empty() {
             parts.push(format!("# Memory\n\n{memory_context}"));
         }
 
-        let always_skills = self.skills.get_always_skills();
         if !always_skills.is_empty() {
-            let content = self.skills.load_skills_for_context(&always_skills);
-            if !content.is_empty() {
-                parts.push(format!("# Active Skills\n\n{content}"));
-            }
+            parts.push(format!("# Active Skills\n\n{always_skills}"));
         }
 
-        if let Some(skill_names) = skill_names {
-            if !skill_names.is_empty() {
-                let content = self.skills.load_skills_for_context(skill_names);
-                if !content.is_empty() {
-                    parts.push(format!("# Requested Skills\n\n{content}"));
-                }
+        if let Some(skill_names) = skill_names
+            && !skill_names.is_empty()
+        {
+            let content = self.skills.load_skills_for_context(skill_names);
+            if !content.is_empty() {
+                parts.push(format!("# Requested Skills\n\n{content}"));
             }
         }
 
-        let summary = self.skills.build_skills_summary();
-        if !summary.is_empty() {
+        if !skills_summary.is_empty() {
             parts.push(format!(
-                "# Skills\n\nThe following skills extend your capabilities. To use a skill, read its SKILL.md file using the read_file tool.\n\n{summary}"
+                "# Skills\n\nThe following skills extend your capabilities. To use a skill, read its SKILL.md file using the read_file tool.\n\n{skills_summary}"
+            ));
+        }
+
+        let now = Local::now().format("%Y-%m-%d %H:%M (%A)").to_string();
+        let tz = {
+            let value = Local::now().format("%Z").to_string();
+            if value.trim().is_empty() {
+                "UTC".to_string()
+            } else {
+                value
+            }
+        };
+        let mut volatile = format!("## Current Time\n{now} ({tz})");
+        let location = self
+            .location
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if !location.is_empty() {
+            volatile.push_str(&format!(
+                "\n\n## Current Location\n{location}\nUse it for weather, local search and \"near me\" questions unless the user names another place."
             ));
         }
+        let live_context = self
+            .live_context
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if !live_context.is_empty() {
+            volatile.push_str(&format!("\n\n---\n\n# Live Context\n\n{live_context}"));
+        }
 
-        parts.join("\n\n---\n\n")
+        (parts.join("\n\n---\n\n"), volatile)
     }
 
+    /// `[stable system prompt, current time and session, history.., user]`.
+    #[allow(clippy::too_many_arguments)]
     pub fn build_messages(
         &self,
         history: &[Value],
@@ -92,10 +271,11 @@ impl ContextBuilder {
         channel: Option<&str>,
         chat_id: Option<&str>,
         media: Option<&[String]>,
+        identity: Option<&str>,
     ) -> Vec<Value> {
-        let mut system_prompt = self.build_system_prompt(skill_names);
+        let (system_prompt, mut volatile) = self.build_system_parts(skill_names, identity, channel);
         if let (Some(channel), Some(chat_id)) = (channel, chat_id) {
-            system_prompt.push_str(&format!(
+            volatile.push_str(&format!(
                 "\n\n## Current Session\nChannel: {channel}\nChat ID: {chat_id}"
             ));
         }
@@ -105,6 +285,10 @@ impl ContextBuilder {
             "role": "system",
             "content": system_prompt,
         }));
+        messages.push(json!({
+            "role": "system",
+            "content": volatile,
+        }));
         messages.extend(history.iter().cloned());
         let user_content = build_user_content(current_message, media);
         messages.push(json!({
@@ -143,16 +327,55 @@ impl ContextBuilder {
         if let Some(calls) = tool_calls {
             msg["tool_calls"] = Value::Array(calls);
         }
-        if let Some(reasoning) = reasoning_content {
-            if !reasoning.is_empty() {
-                msg["reasoning_content"] = Value::String(reasoning.to_string());
-            }
+        if let Some(reasoning) = reasoning_content
+            && !reasoning.is_empty()
+        {
+            msg["reasoning_content"] = Value::String(reasoning.to_string());
         }
         messages.push(msg);
     }
 }
 
-fn build_user_content(text: &str, media: Option<&[String]>) -> Value {
+/// The identity snippets in `dir`; unreadable ones are skipped.
+fn load_identities(dir: &Path) -> BTreeMap<String, PromptTemplate> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return BTreeMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let text = std::fs::read_to_string(&path).ok()?;
+            match PromptTemplate::parse(&name, &path, &text) {
+                Ok(template) => Some((name, template)),
+                Err(err) => {
+                    warn!("skipping identity: {err:#}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn changed_paths(before: &Stamps, after: &Stamps) -> Vec<String> {
+    let mut changed = after
+        .iter()
+        .filter(|(path, stamp)| before.get(*path) != Some(*stamp))
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    changed.extend(
+        before
+            .keys()
+            .filter(|path| !after.contains_key(*path))
+            .cloned(),
+    );
+    changed
+}
+
+/// The user message content: plain text, or image parts for the image
+/// files in `media` followed by the text.
+pub(crate) fn build_user_content(text: &str, media: Option<&[String]>) -> Value {
     let Some(media_paths) = media else {
         return Value::String(text.to_string());
     };
@@ -191,7 +414,7 @@ fn build_user_content(text: &str, media: Option<&[String]>) -> Value {
 
 #[cfg(test)]
 mod tests {
-    use super::build_user_content;
+    use super::{ContextBuilder, build_user_content};
     use serde_json::Value;
     use uuid::Uuid;
 
@@ -215,4 +438,62 @@ mod tests {
 
         let _ = std::fs::remove_file(temp);
     }
+
+    #[test]
+    fn refresh_reports_edited_instruction_files_once() {
+        let dir = std::env::temp_dir().join(format!("nanobot-rs-ctx-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create workspace");
+        std::fs::write(dir.join("SOUL.md"), "Be terse.").expect("write soul");
+        let context = ContextBuilder::new(dir.clone()).expect("context");
+
+        assert!(context.refresh().is_empty());
+        assert!(context.build_system_prompt(None).contains("Be terse."));
+
+        std::fs::write(dir.join("SOUL.md"), "Be warm and chatty.").expect("edit soul");
+        std::fs::write(dir.join("USER.md"), "Name: Ada").expect("write user");
+        assert_eq!(context.refresh(), ["SOUL.md", "USER.md"]);
+        assert!(context.refresh().is_empty());
+        let prompt = context.build_system_prompt(None);
+        assert!(prompt.contains("Be warm and chatty.") && prompt.contains("Name: Ada"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn identity_snippets_stand_in_for_soul() {
+        let dir = std::env::temp_dir().join(format!("nanobot-rs-identity-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("identities")).expect("create identities");
+        std::fs::write(dir.join("SOUL.md"), "Be warm and chatty.").expect("write soul");
+        std::fs::write(
+            dir.join("identities/formal.md"),
+            "---\nvariables:\n  - name: signoff\n    default: Kind regards\n---\nWrite formally on {{channel}}. Sign off with \"{{signoff}}\".",
+        )
+        .expect("write identity");
+        let context = ContextBuilder::new(dir.clone()).expect("context");
+
+        let system = |identity: Option<&str>| {
+            let messages = context.build_messages(
+                &[],
+                "hi",
+                None,
+                Some("email"),
+                Some("a@b.c"),
+                None,
+                identity,
+            );
+            messages[0]["content"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string()
+        };
+        let formal = system(Some("formal"));
+        assert!(formal.contains(
+            "## Identity: formal\n\nWrite formally on email. Sign off with \"Kind regards\"."
+        ));
+        assert!(!formal.contains("chatty"));
+        assert!(system(None).contains("Be warm and chatty."));
+        assert!(system(Some("missing")).contains("Be warm and chatty."));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
@@ -0,0 +1,217 @@
+//! `agents.defaults.contextSources`: URLs, workspace files and command output
+//! kept fresh on an interval and added to the system prompt.
+//!
+//! Stale sources are refreshed at the start of a turn. A failed refresh keeps
+//! the last good content, so a flaky endpoint does not blank the context.
+
+use crate::config::ContextSourceConfig;
+use crate::tools::web::{normalize_text, strip_tags};
+use anyhow::{Result, anyhow, bail};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+/// Rough characters per token, for the `maxTokens` cap.
+const CHARS_PER_TOKEN: usize = 4;
+
+struct Cached {
+    content: String,
+    fetched_at: Option<Instant>,
+}
+
+pub struct ContextSources {
+    workspace: PathBuf,
+    sources: Vec<ContextSourceConfig>,
+    cache: Mutex<Vec<Cached>>,
+    client: reqwest::Client,
+}
+
+impl ContextSources {
+    pub fn new(workspace: PathBuf, sources: Vec<ContextSourceConfig>) -> Self {
+        let cache = sources
+            .iter()
+            .map(|_| Cached {
+                content: String::new(),
+                fetched_at: None,
+            })
+            .collect();
+        Self {
+            workspace,
+            sources,
+            cache: Mutex::new(cache),
+            client: crate::network::client_builder()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Refetches sources older than their `refreshS` and renders the prompt section.
+    pub async fn render(&self) -> String {
+        let mut cache = self.cache.lock().await;
+        for (source, cached) in self.sources.iter().zip(cache.iter_mut()) {
+            let fresh = cached
+                .fetched_at
+                .is_some_and(|at| at.elapsed() < Duration::from_secs(source.refresh_s));
+            if fresh {
+                continue;
+            }
+            match self.fetch(source).await {
+                Ok(content) => cached.content = truncate_tokens(content.trim(), source.max_tokens),
+                Err(err) => {
+                    tracing::warn!(source = %source_name(source), "context source failed: {err:#}");
+                    if cached.content.is_empty() {
+                        cached.content = format!("(unavailable: {err:#})");
+                    }
+                }
+            }
+            cached.fetched_at = Some(Instant::now());
+        }
+        self.sources
+            .iter()
+            .zip(cache.iter())
+            .filter(|(_, cached)| !cached.content.is_empty())
+            .map(|(source, cached)| format!("## {}\n\n{}", source_name(source), cached.content))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    async fn fetch(&self, source: &ContextSourceConfig) -> Result<String> {
+        if !source.url.is_empty() {
+            let response = self.client.get(&source.url).send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                bail!("HTTP {status}");
+            }
+            let is_html = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("html"));
+            let body = response.text().await?;
+            return Ok(if is_html {
+                normalize_text(&strip_tags(&body))
+            } else {
+                body
+            });
+        }
+        if !source.path.is_empty() {
+            return read_files(&self.workspace, &source.path);
+        }
+        if !source.command.is_empty() {
+            return run_command(&self.workspace, &source.command).await;
+        }
+        Err(anyhow!("set one of url, path or command"))
+    }
+}
+
+fn source_name(source: &ContextSourceConfig) -> &str {
+    [&source.name, &source.url, &source.path, &source.command]
+        .into_iter()
+        .find(|value| !value.is_empty())
+        .map(String::as_str)
+        .unwrap_or("context")
+}
+
+fn read_files(workspace: &Path, pattern: &str) -> Result<String> {
+    let full = if Path::new(pattern).is_absolute() {
+        pattern.to_string()
+    } else {
+        workspace.join(pattern).to_string_lossy().to_string()
+    };
+    let mut paths = glob::glob(&full)?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    paths.sort();
+    match paths.as_slice() {
+        [] => bail!("no files match {pattern}"),
+        [single] => Ok(std::fs::read_to_string(single)?),
+        many => Ok(many
+            .iter()
+            .filter_map(|path| {
+                let content = std::fs::read_to_string(path).ok()?;
+                let name = path.strip_prefix(workspace).unwrap_or(path).display();
+                Some(format!("### {name}\n{}", content.trim()))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")),
+    }
+}
+
+async fn run_command(workspace: &Path, command: &str) -> Result<String> {
+    let mut process = if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    process
+        .current_dir(workspace)
+        .envs(crate::network::proxy_env())
+        .kill_on_drop(true);
+    let output = tokio::time::timeout(FETCH_TIMEOUT, process.output())
+        .await
+        .map_err(|_| anyhow!("timed out after {}s", FETCH_TIMEOUT.as_secs()))??;
+    if !output.status.success() {
+        bail!(
+            "exit code {}: {}",
+            output.status.code().unwrap_or(-1),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn truncate_tokens(text: &str, max_tokens: usize) -> String {
+    let max_chars = max_tokens.saturating_mul(CHARS_PER_TOKEN);
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}\n[truncated]", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn renders_files_and_commands_with_caps_and_caching() {
+        let dir = std::env::temp_dir().join(format!("nanobot-ctxsrc-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("oncall")).expect("dir");
+        std::fs::write(dir.join("oncall/week.md"), "Alice is on call").expect("write");
+        let sources = ContextSources::new(
+            dir.clone(),
+            vec![
+                ContextSourceConfig {
+                    name: "On-call".to_string(),
+                    path: "oncall/*.md".to_string(),
+                    ..Default::default()
+                },
+                ContextSourceConfig {
+                    command: "echo 0123456789abcdef".to_string(),
+                    max_tokens: 2,
+                    ..Default::default()
+                },
+                ContextSourceConfig {
+                    path: "missing.md".to_string(),
+                    ..Default::default()
+                },
+            ],
+        );
+        let rendered = sources.render().await;
+        assert!(rendered.contains("## On-call\n\nAlice is on call"));
+        assert!(rendered.contains("## echo 0123456789abcdef\n\n01234567\n[truncated]"));
+        assert!(rendered.contains("## missing.md\n\n(unavailable: no files match missing.md)"));
+
+        // Within refreshS the cached copy is used.
+        std::fs::write(dir.join("oncall/week.md"), "Bob is on call").expect("write");
+        assert!(sources.render().await.contains("Alice is on call"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
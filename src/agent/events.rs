@@ -0,0 +1,126 @@
+//! Live progress of a turn (token deltas, tool calls, prompt reloads, model
+//! routing, context trimming) for streaming clients.
+//!
+//! Events go to the sink installed with `with_events` for the current task
+//! and, inside `with_bus`, to the bus's agent topic tagged with the session;
+//! outside such scopes `emit` is a no-op.
+
+use crate::bus::{MessageBus, SessionEvent};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+const TOOL_PREVIEW_CHARS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    Delta {
+        text: String,
+    },
+    ToolStart {
+        name: String,
+        arguments: String,
+    },
+    ToolEnd {
+        name: String,
+        preview: String,
+    },
+    /// Workspace instructions or skills changed and the system prompt was rebuilt.
+    PromptReloaded {
+        files: Vec<String>,
+    },
+    /// The model router picked `model` for the next request.
+    Routed {
+        model: String,
+        reason: String,
+    },
+    /// The request overflowed the context window and was trimmed for a retry.
+    ContextTrimmed {
+        dropped: usize,
+        truncated: usize,
+    },
+}
+
+impl AgentEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentEvent::Delta { .. } => "delta",
+            AgentEvent::ToolStart { .. } => "tool_start",
+            AgentEvent::ToolEnd { .. } => "tool_end",
+            AgentEvent::PromptReloaded { .. } => "prompt_reloaded",
+            AgentEvent::Routed { .. } => "routed",
+            AgentEvent::ContextTrimmed { .. } => "context_trimmed",
+        }
+    }
+
+    pub fn tool_end(name: &str, result: &str) -> Self {
+        let mut preview = result.chars().take(TOOL_PREVIEW_CHARS).collect::<String>();
+        if result.chars().nth(TOOL_PREVIEW_CHARS).is_some() {
+            preview.push('…');
+        }
+        AgentEvent::ToolEnd {
+            name: name.to_string(),
+            preview,
+        }
+    }
+}
+
+pub type EventSender = mpsc::UnboundedSender<AgentEvent>;
+
+tokio::task_local! {
+    static EVENTS: EventSender;
+    static BUS: (Arc<MessageBus>, String);
+}
+
+/// Runs `fut` with `tx` receiving the events it emits.
+pub async fn with_events<F: Future>(tx: EventSender, fut: F) -> F::Output {
+    EVENTS.scope(tx, fut).await
+}
+
+/// Runs `fut` with its events also published on `bus` for `session`.
+pub async fn with_bus<F: Future>(bus: Arc<MessageBus>, session: String, fut: F) -> F::Output {
+    BUS.scope((bus, session), fut).await
+}
+
+pub fn is_streaming() -> bool {
+    EVENTS.try_with(|_| ()).is_ok()
+}
+
+pub fn emit(event: AgentEvent) {
+    let _ = BUS.try_with(|(bus, session)| {
+        bus.publish(SessionEvent {
+            session: session.clone(),
+            event: event.clone(),
+        })
+    });
+    let _ = EVENTS.try_with(|tx| tx.send(event));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn events_reach_the_scoped_sink_only() {
+        emit(AgentEvent::Delta {
+            text: "dropped".to_string(),
+        });
+        assert!(!is_streaming());
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        with_events(tx, async {
+            assert!(is_streaming());
+            emit(AgentEvent::tool_end("exec", &"x".repeat(300)));
+        })
+        .await;
+
+        let event = rx.recv().await.expect("event");
+        assert_eq!(event.name(), "tool_end");
+        let json = serde_json::to_value(&event).expect("json");
+        assert_eq!(json["type"], "tool_end");
+        assert_eq!(json["preview"].as_str().unwrap().chars().count(), 201);
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,127 @@
+//! What a user is told when a turn fails.
+//!
+//! The full error chain goes to the log under a short reference id; the
+//! chat gets a plain sentence about what went wrong, what to do next and
+//! that reference. Only operator surfaces (the CLI and the web UI) also see
+//! the provider's own message.
+
+use crate::i18n::{tr, tr_args};
+use crate::providers::error::ProviderError;
+use tracing::error;
+
+/// Channels used by whoever runs nanobot, who can act on raw provider errors.
+const OPERATOR_CHANNELS: &[&str] = &["cli", "webui"];
+
+/// Kind of failure, as far as the user can do something about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    Auth,
+    Quota,
+    RateLimited,
+    ContextOverflow,
+    ContentFilter,
+    Network,
+    InvalidRequest,
+    Server,
+    Timeout,
+    Internal,
+}
+
+impl Failure {
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(provider_err) = ProviderError::find(err) {
+            return match provider_err {
+                ProviderError::Auth { .. } => Self::Auth,
+                ProviderError::Quota { .. } => Self::Quota,
+                ProviderError::RateLimited { .. } => Self::RateLimited,
+                ProviderError::ContextOverflow { .. } => Self::ContextOverflow,
+                ProviderError::ContentFilter { .. } => Self::ContentFilter,
+                ProviderError::Network { .. } => Self::Network,
+                ProviderError::InvalidRequest { .. } => Self::InvalidRequest,
+                ProviderError::Server { .. } => Self::Server,
+            };
+        }
+        if err
+            .chain()
+            .any(|cause| cause.is::<tokio::time::error::Elapsed>())
+        {
+            return Self::Timeout;
+        }
+        Self::Internal
+    }
+
+    fn message_id(self) -> &'static str {
+        match self {
+            Self::Auth => "error-auth",
+            Self::Quota => "error-quota",
+            Self::RateLimited => "error-rate-limited",
+            Self::ContextOverflow => "error-context-overflow",
+            Self::ContentFilter => "error-content-filter",
+            Self::Network => "error-network",
+            Self::InvalidRequest => "error-invalid-request",
+            Self::Server => "error-server",
+            Self::Timeout => "error-timeout",
+            Self::Internal => "error-internal",
+        }
+    }
+}
+
+/// Logs `err` under a new reference id and returns the reply for `channel`.
+pub fn error_reply(err: &anyhow::Error, channel: &str) -> String {
+    let reference = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    error!(error_ref = %reference, channel, "turn failed: {err:#}");
+    reply_for(err, channel, &reference)
+}
+
+fn reply_for(err: &anyhow::Error, channel: &str, reference: &str) -> String {
+    let mut reply = tr(Failure::of(err).message_id());
+    if OPERATOR_CHANNELS.contains(&channel) {
+        let detail = match ProviderError::find(err) {
+            Some(provider_err) => format!("{provider_err}\n{}", provider_err.hint()),
+            None => format!("{err:#}"),
+        };
+        reply.push_str(&format!("\n{detail}"));
+    }
+    reply.push('\n');
+    reply.push_str(&tr_args("error-reference", &[("ref", reference)]));
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn chat_users_get_a_friendly_reply_and_operators_the_details() {
+        let err = anyhow::Error::new(ProviderError::Auth {
+            message: "Incorrect API key provided: sk-123".to_string(),
+        })
+        .context("model call failed");
+        assert_eq!(Failure::of(&err), Failure::Auth);
+
+        let chat = reply_for(&err, "telegram", "ab12cd34");
+        assert!(chat.starts_with(&tr("error-auth")));
+        assert!(chat.ends_with(&tr_args("error-reference", &[("ref", "ab12cd34")])));
+        assert!(!chat.contains("sk-123"));
+        assert!(reply_for(&err, "cli", "ab12cd34").contains("sk-123"));
+
+        let internal = anyhow::anyhow!("session file is corrupt").context("loading session");
+        assert_eq!(Failure::of(&internal), Failure::Internal);
+        assert!(!reply_for(&internal, "slack", "x").contains("corrupt"));
+    }
+
+    #[tokio::test]
+    async fn timeouts_are_recognized_anywhere_in_the_chain() {
+        let elapsed = tokio::time::timeout(
+            std::time::Duration::from_millis(1),
+            std::future::pending::<()>(),
+        )
+        .await
+        .expect_err("timeout");
+        let err = Err::<(), _>(elapsed)
+            .context("tool took too long")
+            .unwrap_err();
+        assert_eq!(Failure::of(&err), Failure::Timeout);
+    }
+}
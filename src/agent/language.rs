@@ -0,0 +1,302 @@
+//! Guessing the language of a message so replies can match it.
+//!
+//! Detection is by script (Han, Hangul, Cyrillic, ...) and, for Latin text,
+//! a handful of common words. It only has to be good enough to tell the
+//! model which language to answer in and to notice an answer in the wrong
+//! script.
+
+use crate::config::LanguageConfig;
+use serde_json::{Value, json};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Script {
+    Latin,
+    Han,
+    Kana,
+    Hangul,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Thai,
+    Devanagari,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    /// English name, when the script and vocabulary are enough to tell.
+    pub name: Option<&'static str>,
+    pub script: Script,
+}
+
+/// Letters needed before a guess is made; CJK characters count three times.
+const MIN_WEIGHT: usize = 6;
+
+const LATIN_WORDS: &[(&str, &[&str])] = &[
+    (
+        "English",
+        &[
+            "the", "and", "is", "are", "you", "what", "how", "this", "that", "with", "for",
+            "please", "can", "my", "it",
+        ],
+    ),
+    (
+        "Spanish",
+        &[
+            "el", "la", "los", "las", "que", "es", "por", "para", "con", "una", "cómo", "qué",
+            "gracias", "hola", "puedes",
+        ],
+    ),
+    (
+        "French",
+        &[
+            "le", "les", "est", "et", "des", "une", "pour", "avec", "vous", "je", "pas", "merci",
+            "bonjour", "quoi", "comment",
+        ],
+    ),
+    (
+        "German",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ich", "mit", "ein", "eine", "bitte",
+            "danke", "wie", "was", "kannst",
+        ],
+    ),
+    (
+        "Portuguese",
+        &[
+            "o", "os", "não", "uma", "com", "para", "você", "obrigado", "obrigada", "olá", "como",
+            "está", "isso", "pode", "do",
+        ],
+    ),
+    (
+        "Italian",
+        &[
+            "il", "gli", "non", "che", "sono", "per", "una", "grazie", "ciao", "come", "questo",
+            "puoi", "della", "di", "è",
+        ],
+    ),
+];
+
+const SCRIPT_NAMES: &[(Script, &str)] = &[
+    (Script::Han, "Chinese"),
+    (Script::Kana, "Japanese"),
+    (Script::Hangul, "Korean"),
+    (Script::Cyrillic, "Russian"),
+    (Script::Greek, "Greek"),
+    (Script::Arabic, "Arabic"),
+    (Script::Hebrew, "Hebrew"),
+    (Script::Thai, "Thai"),
+    (Script::Devanagari, "Hindi"),
+];
+
+fn script_of(c: char) -> Option<Script> {
+    let script = match c as u32 {
+        0x3040..=0x30FF | 0x31F0..=0x31FF => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF => Script::Han,
+        0xAC00..=0xD7AF | 0x1100..=0x11FF | 0x3130..=0x318F => Script::Hangul,
+        0x0400..=0x04FF => Script::Cyrillic,
+        0x0370..=0x03FF => Script::Greek,
+        0x0600..=0x06FF | 0x0750..=0x077F => Script::Arabic,
+        0x0590..=0x05FF => Script::Hebrew,
+        0x0E00..=0x0E7F => Script::Thai,
+        0x0900..=0x097F => Script::Devanagari,
+        _ if c.is_alphabetic() && (c.is_ascii() || ('\u{00C0}'..='\u{024F}').contains(&c)) => {
+            Script::Latin
+        }
+        _ => return None,
+    };
+    Some(script)
+}
+
+fn is_wide(script: Script) -> bool {
+    matches!(script, Script::Han | Script::Kana | Script::Hangul)
+}
+
+/// Drops fenced code blocks and inline code, which say nothing about the
+/// language of the prose around them.
+fn prose(text: &str) -> String {
+    let mut out = String::new();
+    for (i, block) in text.split("```").enumerate() {
+        if i % 2 == 0 {
+            for (j, part) in block.split('`').enumerate() {
+                if j % 2 == 0 {
+                    out.push_str(part);
+                    out.push(' ');
+                }
+            }
+        }
+    }
+    out
+}
+
+pub fn detect(text: &str) -> Option<Language> {
+    let text = prose(text);
+    let mut weights: Vec<(Script, usize)> = Vec::new();
+    for script in text.chars().filter_map(script_of) {
+        let weight = if is_wide(script) { 3 } else { 1 };
+        match weights.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, total)) => *total += weight,
+            None => weights.push((script, weight)),
+        }
+    }
+    // Japanese mixes kana with kanji; any kana among Han text decides it.
+    let kana = weights.iter().any(|(s, _)| *s == Script::Kana);
+    if kana && let Some(han) = weights.iter().position(|(s, _)| *s == Script::Han) {
+        let (_, han_weight) = weights.remove(han);
+        if let Some((_, total)) = weights.iter_mut().find(|(s, _)| *s == Script::Kana) {
+            *total += han_weight;
+        }
+    }
+    let total: usize = weights.iter().map(|(_, w)| w).sum();
+    let &(script, _) = weights.iter().max_by_key(|(_, w)| *w)?;
+    if total < MIN_WEIGHT {
+        return None;
+    }
+    let name = match script {
+        Script::Latin => latin_language(&text),
+        _ => SCRIPT_NAMES
+            .iter()
+            .find(|(s, _)| *s == script)
+            .map(|(_, name)| *name),
+    };
+    Some(Language { name, script })
+}
+
+fn latin_language(text: &str) -> Option<&'static str> {
+    let lower = text.to_lowercase();
+    let words = lower
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    LATIN_WORDS
+        .iter()
+        .map(|(name, common)| {
+            let hits = words.iter().filter(|word| common.contains(word)).count();
+            (*name, hits)
+        })
+        .filter(|(_, hits)| *hits > 0)
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(name, _)| name)
+}
+
+/// The script a configured language name is written in, if known.
+pub fn script_for_name(name: &str) -> Option<Script> {
+    let name = name.trim();
+    SCRIPT_NAMES
+        .iter()
+        .find(|(_, known)| known.eq_ignore_ascii_case(name))
+        .map(|(script, _)| *script)
+        .or_else(|| {
+            LATIN_WORDS
+                .iter()
+                .any(|(known, _)| known.eq_ignore_ascii_case(name))
+                .then_some(Script::Latin)
+        })
+}
+
+/// The language a reply should be written in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyLanguage {
+    pub name: Option<String>,
+    pub script: Option<Script>,
+}
+
+/// What `language.reply` asks for, given the user's message.
+pub fn reply_language(config: &LanguageConfig, message: &str) -> Option<ReplyLanguage> {
+    match config.reply.trim() {
+        "" | "off" => None,
+        "auto" => detect(message).map(|lang| ReplyLanguage {
+            name: lang.name.map(str::to_string),
+            script: Some(lang.script),
+        }),
+        name => Some(ReplyLanguage {
+            name: Some(name.to_string()),
+            script: script_for_name(name),
+        }),
+    }
+}
+
+impl ReplyLanguage {
+    pub fn instruction(&self) -> Value {
+        let content = match &self.name {
+            Some(name) => format!(
+                "Reply in {name}, even where instructions, memory or tool results use another \
+                 language. Keep code, commands and names as they are."
+            ),
+            None => "Reply in the same language as the user's latest message, even where \
+                     instructions, memory or tool results use another language."
+                .to_string(),
+        };
+        json!({"role": "system", "content": content})
+    }
+
+    /// True when `answer` is clearly written in another script.
+    pub fn mismatched(&self, answer: &str) -> bool {
+        match (self.script, detect(answer)) {
+            (Some(expected), Some(found)) => !same_family(expected, found.script),
+            _ => false,
+        }
+    }
+}
+
+/// Japanese answers use kanji and may be detected as Han either way.
+fn same_family(a: Script, b: Script) -> bool {
+    a == b
+        || matches!(
+            (a, b),
+            (Script::Han, Script::Kana) | (Script::Kana, Script::Han)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_scripts_and_common_latin_languages() {
+        let name = |text: &str| detect(text).and_then(|lang| lang.name);
+        assert_eq!(name("帮我看看这个报错 cannot borrow"), Some("Chinese"));
+        assert_eq!(name("これは何ですか"), Some("Japanese"));
+        assert_eq!(name("오늘 날씨 어때요?"), Some("Korean"));
+        assert_eq!(name("Привет, как дела?"), Some("Russian"));
+        assert_eq!(name("What is the weather like today?"), Some("English"));
+        assert_eq!(name("¿Qué tiempo hace hoy en la ciudad?"), Some("Spanish"));
+        assert_eq!(name("Kannst du mir bitte helfen?"), Some("German"));
+        assert_eq!(detect("ok"), None);
+        assert_eq!(
+            detect("解释一下 ```fn main() { println!(\"hello world\"); }```")
+                .map(|lang| lang.script),
+            Some(Script::Han)
+        );
+        assert_eq!(script_for_name("chinese"), Some(Script::Han));
+        assert_eq!(script_for_name("French"), Some(Script::Latin));
+        assert_eq!(script_for_name("Klingon"), None);
+    }
+
+    #[test]
+    fn reply_language_follows_config() {
+        let auto = LanguageConfig::default();
+        let target = reply_language(&auto, "今天天气怎么样？").expect("target");
+        assert_eq!(target.name.as_deref(), Some("Chinese"));
+        assert!(target.mismatched("The weather today is sunny and warm."));
+        assert!(!target.mismatched("今天是晴天。Use `cargo run` to start."));
+
+        let fixed = LanguageConfig {
+            reply: "German".to_string(),
+            ..LanguageConfig::default()
+        };
+        let target = reply_language(&fixed, "hello").expect("target");
+        assert!(
+            target.instruction()["content"]
+                .as_str()
+                .unwrap()
+                .starts_with("Reply in German")
+        );
+
+        let off = LanguageConfig {
+            reply: "off".to_string(),
+            ..LanguageConfig::default()
+        };
+        assert_eq!(reply_language(&off, "今天天气怎么样？"), None);
+    }
+}
@@ -1,28 +1,97 @@
+use crate::access::{self, Access, Role};
+use crate::agent::approval::ApprovalGate;
+use crate::agent::commands::{self, Command, CommandKind};
 use crate::agent::context::ContextBuilder;
+use crate::agent::context_sources::ContextSources;
+use crate::agent::events::{self, AgentEvent, EventSender};
+use crate::agent::failure::error_reply;
+use crate::agent::language::{self, ReplyLanguage};
+use crate::agent::pending::{Confirmation, PendingAction, PendingActions, parse_confirmation};
+use crate::agent::postprocess;
+use crate::agent::stream::LiveReply;
 use crate::agent::subagent::SubagentManager;
+use crate::agent::text_tools;
+use crate::agent::tool_select;
 use crate::agent::turn_guard::TurnGuard;
+use crate::agent::verify::{self, Verifier, append_to_last_tool_result};
+use crate::artifacts;
+use crate::attachments;
 use crate::bus::{InboundMessage, MessageBus, OutboundMessage};
-use crate::config::WebSearchConfig;
+use crate::channels::live::EDITABLE_CHANNELS;
+use crate::config::{
+    AccessConfig, AgentProfile, BudgetConfig, ContentFilterConfig, ContextSourceConfig,
+    IdentityConfig, LanguageConfig, ModelPrice, PostProcessConfig, PublishConfig, RouterConfig,
+    StreamingConfig, ToolSelectionConfig, ToolStatsConfig, VerifyConfig, WebSearchConfig,
+    WorkspaceConfig,
+};
 use crate::cron::CronService;
-use crate::memory::MemoryStore;
-use crate::providers::base::LLMProvider;
-use crate::session::SessionManager;
+use crate::i18n::{tr, tr_args};
+use crate::location::LocationService;
+use crate::memory::{self, Fact, MemoryStore};
+use crate::providers::base::{LLMProvider, LLMResponse, ToolCallRequest};
+use crate::providers::capabilities::Capabilities;
+use crate::providers::error::ProviderError;
+use crate::publish;
+use crate::router;
+use crate::session::{Compaction, Session, SessionManager};
+use crate::tool_stats::{ToolStatsRecorder, is_failure, tool_stats_path};
+use crate::tools::ask::AskUserTool;
+use crate::tools::base::with_turn_chat;
+use crate::tools::bookmarks::BookmarkTools;
+use crate::tools::contacts::ContactTools;
 use crate::tools::cron::CronTool;
 use crate::tools::filesystem::{EditFileTool, ListDirTool, ReadFileTool, WriteFileTool};
 use crate::tools::http::HttpRequestTool;
+use crate::tools::location::LocationTools;
+use crate::tools::logbook::LogbookTools;
 use crate::tools::message::MessageTool;
+use crate::tools::pages::{CONTINUE_RESULT_TOOL, DEFAULT_PAGE_CHARS};
+use crate::tools::publish::PublishNoteTool;
 use crate::tools::registry::ToolRegistry;
 use crate::tools::sessions::{SessionsHistoryTool, SessionsListTool, SessionsSendTool};
-use crate::tools::shell::ExecTool;
+use crate::tools::shell::{self, ExecTool};
 use crate::tools::spawn::SpawnTool;
+use crate::tools::tasks::TaskTools;
+use crate::tools::template::UseTemplateTool;
 use crate::tools::web::{WebFetchTool, WebSearchTool};
+use crate::tools::workflow::RunWorkflowTool;
+use crate::usage;
+use crate::vault;
+use crate::workflows::WorkflowRunner;
 use anyhow::{Context, Result};
-use chrono::Local;
-use serde_json::{Value, json};
-use std::path::PathBuf;
+use chrono::{DateTime, Local};
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use serde_json::{Map, Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::time::{Duration, timeout};
+use tracing::{Instrument, debug, info, info_span, warn};
+
+/// How much of a confirmed action's result is echoed back.
+const PENDING_RESULT_CHARS: usize = 1_500;
+/// Longest `Retry-After` the loop waits out before giving up on a turn.
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(30);
+/// Turns `run` works on at once unless `agents.defaults.maxConcurrentTurns`
+/// says otherwise.
+pub const DEFAULT_CONCURRENT_TURNS: usize = 4;
+/// Session metadata key of the chat's `/model` choice.
+const MODEL_KEY: &str = "model";
+/// Session metadata key of the `updated_at` facts were last extracted at.
+const FACTS_EXTRACTED_KEY: &str = "factsExtractedAt";
+
+tokio::task_local! {
+    /// Model of the chat the current turn runs for.
+    static TURN_MODEL: String;
+    /// Role of the user the current turn runs for, with `access` on.
+    static TURN_ROLE: Option<Role>;
+}
+
+fn turn_role() -> Option<Role> {
+    TURN_ROLE.try_with(Clone::clone).ok().flatten()
+}
 
 pub struct AgentLoop {
     bus: Arc<MessageBus>,
@@ -34,17 +103,43 @@ pub struct AgentLoop {
     context: ContextBuilder,
     sessions: Arc<SessionManager>,
     tools: ToolRegistry,
-    message_tool: Arc<MessageTool>,
-    sessions_send_tool: Arc<SessionsSendTool>,
-    spawn_tool: Arc<SpawnTool>,
-    cron_tool: Option<Arc<CronTool>>,
+    location_tools: Option<Arc<LocationTools>>,
     subagents: Arc<SubagentManager>,
+    approval: Option<Arc<ApprovalGate>>,
+    context_sources: Option<Arc<ContextSources>>,
+    content_filter: ContentFilterConfig,
+    profiles: HashMap<String, AgentProfile>,
+    access: Access,
+    language: LanguageConfig,
+    post_process: PostProcessConfig,
+    identity: IdentityConfig,
+    /// Gated tool calls waiting for the user's yes, by `channel:chat_id`.
+    pending: PendingActions,
+    verifier: Option<Verifier>,
+    tool_selection: ToolSelectionConfig,
+    /// `router.tiers`, for turns run by background jobs.
+    router: RouterConfig,
+    /// Chats claimed by named workspaces; `None` marks this loop's own.
+    workspace_routes: Vec<(WorkspaceConfig, Option<Arc<AgentLoop>>)>,
+    /// Live replies for chats answered in `run`.
+    streaming: StreamingConfig,
+    /// `budget.prices` over the built-in table, for `/usage`.
+    prices: HashMap<String, ModelPrice>,
+    /// Turns `run` works on at once; each session still takes one at a time.
+    max_concurrent_turns: usize,
     running: AtomicBool,
+    draining: AtomicBool,
+    /// Sessions with a turn in progress, and when it started.
+    active_turns: std::sync::Mutex<HashMap<String, DateTime<Local>>>,
+    /// Held for the length of a turn, so a session's turns never overlap.
+    session_locks: std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 impl AgentLoop {
-    fn available_tools_text(&self) -> String {
+    fn available_tools_text(&self, allowed: Option<&[String]>) -> String {
         let mut tool_names = self.tools.tool_names();
+        tool_names
+            .retain(|name| tool_permitted(allowed, name) && !self.tools.hidden_by_read_only(name));
         tool_names.sort();
         if tool_names.is_empty() {
             "(none)".to_string()
@@ -53,21 +148,41 @@ impl AgentLoop {
         }
     }
 
-    fn runtime_facts_message(&self) -> serde_json::Value {
-        let tools_text = self.available_tools_text();
+    fn runtime_facts_message(&self, allowed: Option<&[String]>) -> serde_json::Value {
+        let tools_text = self.available_tools_text(allowed);
+        let mut content = format!(
+            "Runtime facts (authoritative): active model is '{model}'; available tools are: {tools}. \
+        If a user asks for external actions (network/file/command/scheduling), do not claim tools are unavailable; call the matching tool directly. \
+        Focus on the current user message only; do not summarize prior tasks unless explicitly requested.",
+            model = self.active_model(),
+            tools = tools_text
+        );
+        if self.tools.is_read_only() {
+            content.push_str(
+                " Read-only mode is on: you cannot write files, run commands, schedule jobs or send anything outside this conversation; \
+        say so when asked to and offer what you can do by reading instead.",
+            );
+        }
 
         json!({
             "role": "system",
-            "content": format!(
-                "Runtime facts (authoritative): active model is '{model}'; available tools are: {tools}. \
-        If a user asks for external actions (network/file/command/scheduling), do not claim tools are unavailable; call the matching tool directly. \
-        Focus on the current user message only; do not summarize prior tasks unless explicitly requested.",
-                model = self.model,
-                tools = tools_text
-            )
+            "content": content
         })
     }
 
+    /// Picks up edits to the workspace instruction files and skills; returns
+    /// the changed files, which are also reported as a `prompt_reloaded` event.
+    pub fn reload_prompt(&self) -> Vec<String> {
+        let changed = self.context.refresh();
+        if !changed.is_empty() {
+            info!("workspace prompt reloaded: {}", changed.join(", "));
+            events::emit(AgentEvent::PromptReloaded {
+                files: changed.clone(),
+            });
+        }
+        changed
+    }
+
     fn build_turn_messages(
         &self,
         history: &[Value],
@@ -75,7 +190,10 @@ impl AgentLoop {
         channel: &str,
         chat_id: &str,
         media: Option<&[String]>,
+        allowed: Option<&[String]>,
     ) -> Vec<Value> {
+        self.reload_prompt();
+        let identity = self.identity(&format!("{channel}:{chat_id}"), channel);
         let mut messages = self.context.build_messages(
             history,
             current_message,
@@ -83,8 +201,9 @@ impl AgentLoop {
             Some(channel),
             Some(chat_id),
             media,
+            identity,
         );
-        messages.insert(1, self.runtime_facts_message());
+        messages.insert(1, self.runtime_facts_message(allowed));
         messages
     }
 
@@ -109,6 +228,9 @@ impl AgentLoop {
         None
     }
 
+    /// A loop with the builder's defaults for everything not passed here.
+    #[deprecated(note = "use AgentLoopBuilder")]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         bus: Arc<MessageBus>,
         provider: Arc<dyn LLMProvider>,
@@ -121,10 +243,47 @@ impl AgentLoop {
         restrict_to_workspace: bool,
         cron_service: Option<Arc<CronService>>,
         session_manager: Option<Arc<SessionManager>>,
+    ) -> Result<Self> {
+        let mut builder = Self::builder(provider)
+            .bus(bus)
+            .workspace(workspace)
+            .max_iterations(max_iterations)
+            .memory_window(memory_window)
+            .web_search(web_search)
+            .exec_timeout(exec_timeout_s)
+            .restrict_to_workspace(restrict_to_workspace);
+        if let Some(model) = model {
+            builder = builder.model(model);
+        }
+        if let Some(cron) = cron_service {
+            builder = builder.cron(cron);
+        }
+        if let Some(sessions) = session_manager {
+            builder = builder.sessions(sessions);
+        }
+        builder.build()
+    }
+
+    /// The loop with its built-in tools; `AgentLoopBuilder::build` adds the
+    /// rest.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn assemble(
+        bus: Arc<MessageBus>,
+        provider: Arc<dyn LLMProvider>,
+        workspace: PathBuf,
+        model: Option<String>,
+        max_iterations: u32,
+        memory_window: usize,
+        web_search: WebSearchConfig,
+        exec_timeout_s: u64,
+        restrict_to_workspace: bool,
+        cron_service: Option<Arc<CronService>>,
+        session_manager: Option<Arc<SessionManager>>,
     ) -> Result<Self> {
         let context = ContextBuilder::new(workspace.clone())?;
         let sessions = session_manager.unwrap_or(Arc::new(SessionManager::new()?));
         let mut tools = ToolRegistry::new();
+        tools.enable_paging(DEFAULT_PAGE_CHARS);
         let model_name = model.unwrap_or_else(|| provider.default_model().to_string());
 
         let allowed_dir = if restrict_to_workspace {
@@ -147,13 +306,13 @@ impl AgentLoop {
         tools.register(Arc::new(WebSearchTool::from_config(web_search.clone())));
         tools.register(Arc::new(WebFetchTool::new(50_000)));
         tools.register(Arc::new(HttpRequestTool::new(30, 50_000)));
+        tools.register(Arc::new(UseTemplateTool::new(workspace.clone())));
 
-        let message_tool = Arc::new(MessageTool::new(bus.outbound_sender()));
-        tools.register(message_tool.clone());
+        tools.register(Arc::new(MessageTool::new(bus.clone())));
+        tools.register(Arc::new(AskUserTool::new(bus.clone())));
         tools.register(Arc::new(SessionsListTool::new(sessions.clone())));
         tools.register(Arc::new(SessionsHistoryTool::new(sessions.clone())));
-        let sessions_send_tool = Arc::new(SessionsSendTool::new(bus.outbound_sender()));
-        tools.register(sessions_send_tool.clone());
+        tools.register(Arc::new(SessionsSendTool::new(bus.clone())));
 
         let subagents = Arc::new(SubagentManager::new(
             provider.clone(),
@@ -164,16 +323,26 @@ impl AgentLoop {
             exec_timeout_s,
             restrict_to_workspace,
         ));
-        let spawn_tool = Arc::new(SpawnTool::new(subagents.clone()));
-        tools.register(spawn_tool.clone());
+        tools.register(Arc::new(SpawnTool::new(subagents.clone())));
 
-        let cron_tool = if let Some(cron_service) = cron_service {
-            let tool = Arc::new(CronTool::new(cron_service));
-            tools.register(tool.clone());
-            Some(tool)
-        } else {
-            None
-        };
+        for tool in Arc::new(TaskTools::new(&workspace, cron_service.clone())).tools() {
+            tools.register(tool);
+        }
+        for tool in Arc::new(ContactTools::new(&workspace, cron_service.clone())).tools() {
+            tools.register(tool);
+        }
+        for tool in Arc::new(BookmarkTools::new(&workspace)).tools() {
+            tools.register(tool);
+        }
+        for tool in Arc::new(LogbookTools::new(&workspace)).tools() {
+            tools.register(tool);
+        }
+
+        if let Some(cron_service) = cron_service {
+            for tool in Arc::new(CronTool::new(cron_service)).tools() {
+                tools.register(tool);
+            }
+        }
 
         Ok(Self {
             bus,
@@ -185,256 +354,1222 @@ impl AgentLoop {
             context,
             sessions,
             tools,
-            message_tool,
-            sessions_send_tool,
-            spawn_tool,
-            cron_tool,
+            location_tools: None,
             subagents,
+            approval: None,
+            context_sources: None,
+            content_filter: ContentFilterConfig::default(),
+            profiles: HashMap::new(),
+            access: Access::default(),
+            language: LanguageConfig::default(),
+            post_process: PostProcessConfig::default(),
+            identity: IdentityConfig::default(),
+            pending: PendingActions::default(),
+            verifier: None,
+            tool_selection: ToolSelectionConfig::default(),
+            router: RouterConfig::default(),
+            workspace_routes: Vec::new(),
+            streaming: StreamingConfig::default(),
+            prices: BudgetConfig::default().model_prices(),
+            max_concurrent_turns: DEFAULT_CONCURRENT_TURNS,
             running: AtomicBool::new(false),
+            draining: AtomicBool::new(false),
+            active_turns: std::sync::Mutex::new(HashMap::new()),
+            session_locks: std::sync::Mutex::new(HashMap::new()),
         })
     }
 
-    pub async fn run(&self) -> Result<()> {
-        self.running.store(true, Ordering::Relaxed);
-        while self.running.load(Ordering::Relaxed) {
-            let message = timeout(Duration::from_secs(1), self.bus.consume_inbound()).await;
-            let Some(msg) = (match message {
-                Ok(v) => v,
-                Err(_) => continue,
-            }) else {
-                continue;
-            };
+    pub fn with_approval_gate(mut self, gate: Arc<ApprovalGate>) -> Self {
+        self.approval = Some(gate);
+        self
+    }
 
-            let response = match self.process_message(msg.clone(), None).await {
-                Ok(resp) => resp,
-                Err(err) => {
-                    let mut out = OutboundMessage::new(
-                        msg.channel.clone(),
-                        msg.chat_id.clone(),
-                        format!("Sorry, I encountered an error: {err}"),
-                    );
-                    out.metadata = msg.metadata.clone();
-                    out
-                }
-            };
-            let _ = self.bus.publish_outbound(response).await;
+    /// Adds `agents.defaults.contextSources` to the system prompt, refreshed
+    /// at the start of a turn once their interval has passed.
+    pub fn with_context_sources(mut self, sources: Vec<ContextSourceConfig>) -> Self {
+        self.context_sources = (!sources.is_empty())
+            .then(|| Arc::new(ContextSources::new(self.workspace.clone(), sources)));
+        self
+    }
+
+    /// `location`: puts the user's location in the prompt and registers
+    /// `get_location`, plus the place reminder tools for the phone webhook.
+    pub fn with_location(mut self, service: Option<Arc<LocationService>>) -> Self {
+        if let Some(service) = service {
+            let location_tools = Arc::new(LocationTools::new(service));
+            for tool in location_tools.tools() {
+                self.tools.register(tool);
+            }
+            self.location_tools = Some(location_tools);
         }
-        Ok(())
+        self
     }
 
-    pub fn stop(&self) {
-        self.running.store(false, Ordering::Relaxed);
+    /// Adds the tools in `registry`, replacing built-in tools of the same name.
+    pub fn with_tools(mut self, registry: ToolRegistry) -> Self {
+        self.tools.extend(registry);
+        self
     }
 
-    async fn process_message(
-        &self,
-        msg: InboundMessage,
-        session_key: Option<&str>,
-    ) -> Result<OutboundMessage> {
-        if msg.channel == "system" {
-            return self.process_system_message(msg).await;
-        }
+    /// `tools.readOnly` / `--read-only`: hides and refuses the tools that
+    /// write files, run commands or act outside the conversation.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.tools.set_read_only(read_only);
+        self
+    }
 
-        let mut session = self
-            .sessions
-            .get_or_create(session_key.unwrap_or(&msg.session_key()));
+    /// Hands chats claimed by other workspaces (`workspaces.<name>.channels`)
+    /// to their own loops in `run`. A route without a loop keeps the chats
+    /// it claims here, ahead of less specific claims.
+    pub fn with_workspace_routes(
+        mut self,
+        routes: Vec<(WorkspaceConfig, Option<Arc<AgentLoop>>)>,
+    ) -> Self {
+        self.workspace_routes = routes;
+        self
+    }
 
-        let cmd = msg.content.trim().to_ascii_lowercase();
-        if cmd == "/new" || cmd == "/reset" {
-            if let Err(err) = self.consolidate_memory(&mut session, true).await {
-                eprintln!("Warning: memory consolidation failed: {err}");
-            }
-            session.messages.clear();
-            self.sessions.save(&session)?;
+    /// `channels.streaming`: shows answers on chat channels while they are
+    /// written.
+    pub fn with_streaming(mut self, streaming: StreamingConfig) -> Self {
+        self.streaming = streaming;
+        self
+    }
 
-            let mut outbound = OutboundMessage::new(
-                msg.channel,
-                msg.chat_id,
-                "🐈 New session started. Memory consolidated.".to_string(),
-            );
-            outbound.metadata = msg.metadata;
-            return Ok(outbound);
+    /// `agents.defaults.maxConcurrentTurns`: how many chats `run` answers at
+    /// once (at least one).
+    pub fn with_max_concurrent_turns(mut self, turns: usize) -> Self {
+        self.max_concurrent_turns = turns.max(1);
+        self
+    }
+
+    /// `budget.prices` over the built-in table, used to show costs in
+    /// `/usage`.
+    pub fn with_prices(mut self, prices: HashMap<String, ModelPrice>) -> Self {
+        self.prices = prices;
+        self
+    }
+
+    /// A live reply for `msg` when its channel streams.
+    fn live_reply(&self, msg: &InboundMessage) -> Option<LiveReply> {
+        let channel = msg.channel.as_str();
+        let streams = self.streaming.enabled
+            && EDITABLE_CHANNELS.contains(&channel)
+            && self.streaming.channels.iter().any(|c| c == channel);
+        streams.then(|| {
+            LiveReply::start(
+                self.bus.clone(),
+                channel,
+                &msg.chat_id,
+                &msg.metadata,
+                Duration::from_millis(self.streaming.interval_ms.max(500)),
+            )
+        })
+    }
+
+    /// The loop that answers `msg`, when it is not this one. Subagent
+    /// results (`system`) follow the chat they report back to.
+    fn route(&self, msg: &InboundMessage) -> Option<Arc<AgentLoop>> {
+        let (channel, chat_id) = if msg.channel == "system" {
+            msg.chat_id.split_once(':')?
+        } else {
+            (msg.channel.as_str(), msg.chat_id.as_str())
+        };
+        self.workspace_routes
+            .iter()
+            .filter_map(|(workspace, agent)| {
+                workspace.claims(channel, chat_id).map(|rank| (rank, agent))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .and_then(|(_, agent)| agent.clone())
+    }
+
+    /// `tools.verify`: runs the project check after rounds that edit files.
+    pub fn with_verify(mut self, config: &VerifyConfig) -> Self {
+        self.verifier = Verifier::new(config, &self.workspace);
+        self
+    }
+
+    /// `tools.selection`: offers only the tools relevant to each message
+    /// when many are registered.
+    pub fn with_tool_selection(mut self, config: ToolSelectionConfig) -> Self {
+        self.tool_selection = config;
+        self
+    }
+
+    /// Answers cron, heartbeat and workflow turns with the model of their
+    /// quality tier (`router.tiers`).
+    pub fn with_router(mut self, config: RouterConfig) -> Self {
+        self.router = config;
+        self
+    }
+
+    /// Runs the check when `edited` and adds its outcome to the round's
+    /// last tool result.
+    async fn verify_round(&self, messages: &mut [Value], edited: bool, attempts: &mut u32) {
+        let Some(verifier) = self.verifier.as_ref().filter(|_| edited) else {
+            return;
+        };
+        if let Some(note) = verifier.check(attempts).await {
+            debug!(attempts = *attempts, "verification ran after edits");
+            append_to_last_tool_result(messages, &note);
         }
-        if cmd == "/help" {
-            let mut outbound = OutboundMessage::new(
-                msg.channel,
-                msg.chat_id,
-                "🐈 nanobot commands:\n/new - Start a new conversation\n/help - Show available commands".to_string(),
-            );
-            outbound.metadata = msg.metadata;
-            return Ok(outbound);
+    }
+
+    /// `tools.stats`: records each tool call and, with `autoDisable`,
+    /// switches off tools that keep failing.
+    pub fn with_tool_stats(mut self, config: ToolStatsConfig) -> Self {
+        if config.enabled || config.auto_disable {
+            let path = config.enabled.then(|| tool_stats_path().ok()).flatten();
+            self.tools
+                .set_stats(Arc::new(ToolStatsRecorder::new(path, config)));
+        }
+        self
+    }
+
+    pub fn with_content_filter(mut self, config: ContentFilterConfig) -> Self {
+        self.content_filter = config;
+        self
+    }
+
+    /// `agents.defaults.language`: which language replies are written in.
+    pub fn with_language(mut self, config: LanguageConfig) -> Self {
+        self.language = config;
+        self
+    }
+
+    /// Translates `answer` when it came back in another script than asked for.
+    async fn match_language(&self, answer: String, target: &ReplyLanguage) -> String {
+        let Some(name) = target.name.as_deref() else {
+            return answer;
+        };
+        if !self.language.enforce || !target.mismatched(&answer) {
+            return answer;
         }
+        self.translate(answer, name).await
+    }
 
-        if session.messages.len() > self.memory_window {
-            if let Err(err) = self.consolidate_memory(&mut session, false).await {
-                eprintln!("Warning: memory consolidation failed: {err}");
+    /// `text` translated into `language`; unchanged if translation fails.
+    async fn translate(&self, text: String, language: &str) -> String {
+        let messages = [
+            json!({
+                "role": "system",
+                "content": format!(
+                    "Translate the user's text into {language}. Keep Markdown, code, commands, \
+                     links and names unchanged. Output only the translation."
+                )
+            }),
+            json!({"role": "user", "content": text}),
+        ];
+        match self
+            .provider
+            .chat(&messages, None, Some(&self.model), 4096, 0.3)
+            .await
+        {
+            Ok(response) => match response.content {
+                Some(translated) if !translated.trim().is_empty() => {
+                    info!(language, "translated answer");
+                    translated
+                }
+                _ => text,
+            },
+            Err(err) => {
+                warn!("failed to translate answer into {language}: {err:#}");
+                text
             }
         }
-        self.message_tool
-            .set_context(msg.channel.clone(), msg.chat_id.clone());
-        self.sessions_send_tool
-            .set_context(msg.channel.clone(), msg.chat_id.clone());
-        self.spawn_tool
-            .set_context(msg.channel.clone(), msg.chat_id.clone());
-        if let Some(cron_tool) = &self.cron_tool {
-            cron_tool.set_context(msg.channel.clone(), msg.chat_id.clone());
+    }
+
+    /// `agents.defaults.postProcess`: transforms applied to replies before
+    /// they are sent.
+    pub fn with_post_process(mut self, config: PostProcessConfig) -> Self {
+        postprocess::validate(&config.default);
+        config
+            .channels
+            .values()
+            .for_each(|steps| postprocess::validate(steps));
+        self.post_process = config;
+        self
+    }
+
+    /// Runs the reply chain for `channel` and the profile of `session_key`.
+    async fn post_process_reply(
+        &self,
+        session_key: &str,
+        channel: &str,
+        mut text: String,
+    ) -> String {
+        for step in postprocess::chain(&self.post_process, self.profile(session_key), channel) {
+            text = match step.kind.as_str() {
+                "translate" if !step.language.trim().is_empty() => {
+                    self.translate(text, step.language.trim()).await
+                }
+                _ => postprocess::apply(step, text),
+            };
         }
+        text
+    }
 
-        let media = if msg.media.is_empty() {
-            None
-        } else {
-            Some(msg.media.as_slice())
-        };
-        // Deterministic anti-contamination: only current turn is sent to the model.
-        let history = session.get_history(0);
-        let mut messages =
-            self.build_turn_messages(&history, &msg.content, &msg.channel, &msg.chat_id, media);
+    /// `agents.defaults.identity`: snippets that replace SOUL.md per channel.
+    pub fn with_identity(mut self, config: IdentityConfig) -> Self {
+        self.identity = config;
+        self
+    }
 
-        let mut final_content: Option<String> = None;
-        let mut retried_with_fresh_context = false;
-        let mut tools_used: Vec<String> = Vec::new();
-        let mut iterations_run = 0u32;
-        let turn_guard = TurnGuard::new(
-            self.provider.as_ref(),
-            &self.model,
-            self.available_tools_text(),
-            self.max_iterations,
-        );
-        for iteration in 1..=self.max_iterations {
-            iterations_run = iteration;
-            let tool_defs = self.tools.get_definitions();
-            let response = self
-                .provider
-                .chat(&messages, Some(&tool_defs), Some(&self.model), 4096, 0.7)
-                .await?;
+    /// The identity snippet for `channel` and the profile of `session_key`.
+    fn identity(&self, session_key: &str, channel: &str) -> Option<&str> {
+        self.profile(session_key)
+            .and_then(|profile| profile.identity.as_deref())
+            .or_else(|| self.identity.channels.get(channel).map(String::as_str))
+            .or(self.identity.default.as_deref())
+    }
 
-            if response.has_tool_calls() {
-                let tool_call_dicts = response
-                    .tool_calls
-                    .iter()
-                    .map(|tc| {
-                        json!({
-                            "id": tc.id,
-                            "type": "function",
-                            "function": {
-                                "name": tc.name,
-                                "arguments": serde_json::to_string(&tc.arguments).unwrap_or_else(|_| "{}".to_string()),
-                            }
-                        })
-                    })
-                    .collect::<Vec<_>>();
-                self.context.add_assistant_message(
-                    &mut messages,
-                    response.content.as_deref(),
-                    Some(tool_call_dicts),
-                    response.reasoning_content.as_deref(),
-                );
+    /// `agents.profiles`; sessions bound to a profile only see its tools.
+    pub fn with_profiles(mut self, profiles: HashMap<String, AgentProfile>) -> Self {
+        self.profiles = profiles;
+        self
+    }
 
-                for tool_call in response.tool_calls {
-                    tools_used.push(tool_call.name.clone());
-                    let result = self
-                        .tools
-                        .execute(&tool_call.name, &tool_call.arguments)
-                        .await;
-                    self.context.add_tool_result(
-                        &mut messages,
-                        &tool_call.id,
-                        &tool_call.name,
-                        &result,
-                    );
-                }
-                messages.push(json!({
-                    "role": "user",
-                    "content": "Reflect on the results and decide next steps."
-                }));
-            } else {
-                if turn_guard
-                    .should_retry_after_false_no_tools_claim(response.content.as_deref(), iteration)
-                    .await
-                {
-                    if !retried_with_fresh_context {
-                        messages = self.build_turn_messages(
-                            &[],
-                            &msg.content,
-                            &msg.channel,
-                            &msg.chat_id,
-                            media,
-                        );
-                        messages.push(turn_guard.correction_message());
-                        retried_with_fresh_context = true;
-                        continue;
-                    }
-                    final_content = Some(turn_guard.tools_available_response());
-                    break;
-                }
-                final_content = response.content;
-                break;
+    /// `access`: roles per user, enforced on every turn.
+    pub fn with_access(mut self, config: AccessConfig) -> Self {
+        self.access = Access::new(config);
+        self
+    }
+
+    /// The profile `session_key` belongs to (`api:<profile>` or
+    /// `api:<profile>:<session>`).
+    fn profile(&self, session_key: &str) -> Option<&AgentProfile> {
+        session_key
+            .strip_prefix("api:")
+            .and_then(|rest| rest.split(':').next())
+            .and_then(|profile| self.profiles.get(profile))
+    }
+
+    /// The tool allowlist of the profile `session_key` belongs to, narrowed
+    /// by the role of
+    /// the current turn; `None` when neither restricts tools.
+    fn allowed_tools(&self, session_key: &str) -> Option<Vec<String>> {
+        let profile = self
+            .profile(session_key)
+            .and_then(|profile| profile.tools.clone());
+        let role = turn_role().and_then(|role| role.config.tools);
+        match (profile, role) {
+            (Some(profile), Some(role)) => {
+                Some(profile.into_iter().filter(|t| role.contains(t)).collect())
             }
+            (profile, role) => profile.or(role),
         }
+    }
 
-        let answer = final_content.unwrap_or_else(|| {
-            if iterations_run >= self.max_iterations {
-                format!(
-                    "Reached {} iterations without completion.",
-                    self.max_iterations
-                )
-            } else {
-                "I've completed processing but have no response to give.".to_string()
-            }
+    /// The tools `tools.selection` picks for `message` out of those
+    /// `allowed`; `None` offers all of them.
+    async fn offered_tools(
+        &self,
+        message: &str,
+        history: &[Value],
+        allowed: Option<&[String]>,
+    ) -> Option<Vec<String>> {
+        if !self.tool_selection.enabled {
+            return None;
+        }
+        let defs = self.tool_definitions(allowed);
+        let previous = history
+            .iter()
+            .rev()
+            .find(|msg| msg["role"] == "assistant")
+            .and_then(|msg| msg["content"].as_str());
+        tool_select::select_tools(
+            self.provider.as_ref(),
+            &self.active_model(),
+            &self.tool_selection,
+            &defs,
+            message,
+            previous,
+        )
+        .await
+    }
+
+    fn tool_definitions(&self, allowed: Option<&[String]>) -> Vec<Value> {
+        let mut defs = self.tools.get_definitions();
+        defs.retain(|def| {
+            def["function"]["name"]
+                .as_str()
+                .is_some_and(|name| tool_permitted(allowed, name))
         });
+        defs
+    }
 
-        session.add_message("user", &msg.content);
-        session.add_message_with_tools("assistant", &answer, Some(&tools_used));
-        self.sessions.save(&session)?;
+    async fn refresh_context_sources(&self) {
+        if let Some(sources) = &self.context_sources {
+            self.context.set_live_context(sources.render().await);
+        }
+        if let Some(location_tools) = &self.location_tools {
+            self.context
+                .set_location(location_tools.service().prompt_section().await);
+        }
+    }
 
-        let mut outbound = OutboundMessage::new(msg.channel, msg.chat_id, answer);
-        outbound.metadata = msg.metadata;
-        Ok(outbound)
+    /// Registers `run_workflow`; call `runner.attach` once the loop is shared.
+    pub fn with_workflows(mut self, runner: Arc<WorkflowRunner>) -> Self {
+        self.tools.register(Arc::new(RunWorkflowTool::new(runner)));
+        self
     }
 
-    async fn process_system_message(&self, msg: InboundMessage) -> Result<OutboundMessage> {
-        let (origin_channel, origin_chat_id) = msg
-            .chat_id
-            .split_once(':')
-            .map(|(c, id)| (c.to_string(), id.to_string()))
-            .unwrap_or_else(|| ("cli".to_string(), msg.chat_id.clone()));
+    /// `publish`: registers `publish_note`, writing to the configured folder.
+    pub fn with_publish(mut self, config: &PublishConfig) -> Self {
+        self.tools.register(Arc::new(PublishNoteTool::new(
+            self.provider.clone(),
+            self.model.clone(),
+            self.sessions.clone(),
+            publish::notes_dir(config, &self.workspace),
+        )));
+        self
+    }
 
-        self.message_tool
-            .set_context(origin_channel.clone(), origin_chat_id.clone());
-        self.sessions_send_tool
-            .set_context(origin_channel.clone(), origin_chat_id.clone());
-        self.spawn_tool
-            .set_context(origin_channel.clone(), origin_chat_id.clone());
-        if let Some(cron_tool) = &self.cron_tool {
-            cron_tool.set_context(origin_channel.clone(), origin_chat_id.clone());
+    /// Streams content deltas to the current event sink, if any. Tools and
+    /// images are left out for models that do not support them. A completion
+    /// blocked by the content filter is retried once with
+    /// `contentFilter.hint` and otherwise replaced by an explanation.
+    async fn request_completion(
+        &self,
+        messages: &mut Vec<Value>,
+        tool_defs: &[Value],
+    ) -> Result<LLMResponse> {
+        let result = self.request_within_context(messages, tool_defs).await;
+        if !content_filtered(&result) {
+            return result;
         }
+        let hint = self.content_filter.hint.trim();
+        if self.content_filter.retry && !hint.is_empty() {
+            warn!("completion blocked by the content filter, retrying with hint");
+            let mut hinted = messages.clone();
+            hinted.push(json!({ "role": "system", "content": hint }));
+            let retry = self.request_within_context(&mut hinted, tool_defs).await;
+            if !content_filtered(&retry) {
+                return retry;
+            }
+        }
+        warn!("completion blocked by the content filter");
+        let partial = result
+            .ok()
+            .and_then(|response| response.content)
+            .filter(|content| !content.trim().is_empty());
+        let notice = tr("content-filtered");
+        Ok(LLMResponse {
+            content: Some(match partial {
+                Some(partial) => format!("{partial}\n\n{notice}"),
+                None => notice,
+            }),
+            tool_calls: Vec::new(),
+            finish_reason: "content_filter".to_string(),
+            usage: Map::new(),
+            reasoning_content: None,
+            images: Vec::new(),
+        })
+    }
 
-        let session_key = format!("{origin_channel}:{origin_chat_id}");
-        let mut session = self.sessions.get_or_create(&session_key);
-        // Deterministic anti-contamination: only current turn is sent to the model.
-        let history = session.get_history(0);
-        let mut messages = self.build_turn_messages(
-            &history,
-            &msg.content,
-            &origin_channel,
-            &origin_chat_id,
-            None,
-        );
-
-        let mut final_content: Option<String> = None;
+    /// `request_completion_once`, trimming `messages` and retrying once when
+    /// the request overflows the context window.
+    async fn request_within_context(
+        &self,
+        messages: &mut Vec<Value>,
+        tool_defs: &[Value],
+    ) -> Result<LLMResponse> {
+        match self.request_completion_once(messages, tool_defs).await {
+            Err(err)
+                if matches!(
+                    ProviderError::find(&err),
+                    Some(ProviderError::ContextOverflow { .. })
+                ) =>
+            {
+                let Some(trimmed) = trim_for_context(messages) else {
+                    return Err(err);
+                };
+                warn!(
+                    dropped = trimmed.dropped,
+                    truncated = trimmed.truncated,
+                    "context overflow, retrying with trimmed messages"
+                );
+                events::emit(AgentEvent::ContextTrimmed {
+                    dropped: trimmed.dropped,
+                    truncated: trimmed.truncated,
+                });
+                self.request_completion_once(messages, tool_defs).await
+            }
+            response => response,
+        }
+    }
+
+    async fn request_completion_once(
+        &self,
+        messages: &[Value],
+        tool_defs: &[Value],
+    ) -> Result<LLMResponse> {
+        let model = self.active_model();
+        let caps = self.provider.capabilities(&model);
+        let adapted;
+        let messages = if caps.tools && caps.vision {
+            messages
+        } else {
+            adapted = adapt_to_capabilities(messages, tool_defs, &caps);
+            adapted.as_slice()
+        };
+        let native_tools = caps.tools.then_some(tool_defs);
+        let mut response = match self.send_completion(messages, native_tools).await {
+            Err(err) => match ProviderError::find(&err) {
+                Some(ProviderError::RateLimited {
+                    retry_after: Some(wait),
+                    ..
+                }) if *wait <= MAX_RATE_LIMIT_WAIT => {
+                    warn!("rate limited, retrying in {}ms", wait.as_millis());
+                    tokio::time::sleep(*wait).await;
+                    self.send_completion(messages, native_tools).await?
+                }
+                _ => return Err(err),
+            },
+            response => response?,
+        };
+        if !caps.tools
+            && !response.has_tool_calls()
+            && let Some(content) = response.content.as_deref()
+        {
+            let known = tool_defs
+                .iter()
+                .filter_map(|def| def["function"]["name"].as_str())
+                .collect::<Vec<_>>();
+            let (text, calls) = text_tools::parse_tool_calls(content, &known);
+            if !calls.is_empty() {
+                debug!(calls = calls.len(), "parsed text tool calls");
+                response.content = (!text.is_empty()).then_some(text);
+                response.tool_calls = calls;
+            }
+        }
+        Ok(response)
+    }
+
+    async fn send_completion(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+    ) -> Result<LLMResponse> {
+        let model = self.active_model();
+        if events::is_streaming() {
+            let on_delta = |text: &str| {
+                events::emit(AgentEvent::Delta {
+                    text: text.to_string(),
+                })
+            };
+            self.provider
+                .chat_stream(messages, tools, Some(&model), 4096, 0.7, &on_delta)
+                .await
+        } else {
+            self.provider
+                .chat(messages, tools, Some(&model), 4096, 0.7)
+                .await
+        }
+    }
+
+    /// Saves generated images to `workspace/artifacts/` and lists them under
+    /// `answer`; returns their paths for channels to upload.
+    async fn attach_images(&self, images: &[String], answer: &mut String) -> Vec<String> {
+        let mut saved = Vec::new();
+        for url in images {
+            match artifacts::save_image(&self.workspace, url).await {
+                Ok(path) => saved.push(path.display().to_string()),
+                Err(err) => warn!("failed to save generated image: {err:#}"),
+            }
+        }
+        for path in &saved {
+            answer.push_str(&format!("\n\n[image: {path}]"));
+        }
+        saved
+    }
+
+    async fn execute_tool(
+        &self,
+        channel: &str,
+        chat_id: &str,
+        tool_call: &ToolCallRequest,
+        allowed: Option<&[String]>,
+    ) -> String {
+        let span = info_span!(
+            "tool.execute",
+            tool = %tool_call.name,
+            session_id = %format!("{channel}:{chat_id}")
+        );
+        events::emit(AgentEvent::ToolStart {
+            name: tool_call.name.clone(),
+            arguments: Value::Object(tool_call.arguments.clone()).to_string(),
+        });
+        let result = self
+            .execute_tool_inner(channel, chat_id, tool_call, allowed)
+            .instrument(span)
+            .await;
+        events::emit(AgentEvent::tool_end(&tool_call.name, &result));
+        result
+    }
+
+    async fn execute_tool_inner(
+        &self,
+        channel: &str,
+        chat_id: &str,
+        tool_call: &ToolCallRequest,
+        allowed: Option<&[String]>,
+    ) -> String {
+        if !tool_permitted(allowed, &tool_call.name) {
+            return format!(
+                "Error: tool '{}' is not available in this session",
+                tool_call.name
+            );
+        }
+        if let Some(gate) = &self.approval {
+            if gate.requires_approval(channel, chat_id, &tool_call.name)
+                && !gate.can_prompt(channel)
+            {
+                self.pending.stage(
+                    &format!("{channel}:{chat_id}"),
+                    PendingAction::new(&tool_call.name, &tool_call.arguments),
+                );
+                info!(tool = %tool_call.name, channel, "tool call staged for confirmation");
+                return format!(
+                    "Pending confirmation: '{}' has not run yet. Show the user what it will do \
+                     and ask them to reply yes to run it or no to cancel.",
+                    tool_call.name
+                );
+            }
+            if !gate
+                .check(channel, chat_id, &tool_call.name, &tool_call.arguments)
+                .await
+                .is_allowed()
+            {
+                return format!(
+                    "Error: the user denied permission to run tool '{}'",
+                    tool_call.name
+                );
+            }
+        }
+        self.tools
+            .execute(&tool_call.name, &tool_call.arguments)
+            .await
+    }
+
+    /// Runs or drops the action staged in this chat when `msg` is a bare
+    /// yes or no; returns the reply, or `None` to carry on with a turn.
+    async fn resolve_pending(
+        &self,
+        msg: &InboundMessage,
+        session: &mut Session,
+    ) -> Result<Option<String>> {
+        let Some(confirmation) = parse_confirmation(&msg.content) else {
+            return Ok(None);
+        };
+        let Some(action) = self
+            .pending
+            .take(&format!("{}:{}", msg.channel, msg.chat_id))
+        else {
+            return Ok(None);
+        };
+        let reply = match confirmation {
+            Confirmation::Confirm => {
+                info!(tool = %action.tool, "running confirmed action");
+                let result = self.tools.execute(&action.tool, &action.arguments).await;
+                let preview = result
+                    .chars()
+                    .take(PENDING_RESULT_CHARS)
+                    .collect::<String>();
+                tr_args(
+                    "pending-done",
+                    &[("tool", &action.tool), ("result", preview.trim())],
+                )
+            }
+            Confirmation::Cancel => tr_args("pending-cancelled", &[("tool", &action.tool)]),
+        };
+        session.add_message("user", &msg.content);
+        let tools_used = (confirmation == Confirmation::Confirm).then(|| vec![action.tool]);
+        session.add_message_with_tools("assistant", &reply, tools_used.as_deref());
+        self.sessions.save(session)?;
+        Ok(Some(reply))
+    }
+
+    /// Per-turn system notes: the reply language and any action waiting for
+    /// confirmation in this chat.
+    fn add_turn_notes(
+        &self,
+        messages: &mut Vec<Value>,
+        reply_language: Option<&ReplyLanguage>,
+        pending_key: &str,
+    ) {
+        if let Some(action) = self.pending.get(pending_key) {
+            messages.insert(2, json!({"role": "system", "content": action.reminder()}));
+        }
+        if let Some(target) = reply_language {
+            messages.insert(2, target.instruction());
+        }
+    }
+
+    /// Answers inbound messages until `stop`, up to `max_concurrent_turns`
+    /// chats at a time. Messages for a session that is busy wait their turn
+    /// in arrival order.
+    pub async fn run(&self) -> Result<()> {
+        self.running.store(true, Ordering::Relaxed);
+        let mut turns = FuturesUnordered::new();
+        // Busy sessions and the messages waiting for them.
+        let mut waiting: HashMap<String, VecDeque<InboundMessage>> = HashMap::new();
+        while self.running.load(Ordering::Relaxed) {
+            let full = turns.len() >= self.max_concurrent_turns;
+            tokio::select! {
+                Some(key) = turns.next(), if !turns.is_empty() => {
+                    match waiting.get_mut(&key).and_then(VecDeque::pop_front) {
+                        Some(next) => turns.push(self.serve(key, next)),
+                        None => {
+                            waiting.remove(&key);
+                        }
+                    }
+                }
+                message = timeout(Duration::from_secs(1), self.bus.consume_inbound()), if !full => {
+                    let Ok(Some(msg)) = message else {
+                        continue;
+                    };
+                    let key = Self::queue_key(&msg);
+                    match waiting.get_mut(&key) {
+                        Some(queue) => queue.push_back(msg),
+                        None => {
+                            waiting.insert(key.clone(), VecDeque::new());
+                            turns.push(self.serve(key, msg));
+                        }
+                    }
+                }
+            }
+        }
+        // Let turns already under way deliver their answers.
+        while turns.next().await.is_some() {}
+        Ok(())
+    }
+
+    /// The session a message's turn runs in; system messages continue the
+    /// chat they report back to.
+    fn queue_key(msg: &InboundMessage) -> String {
+        match msg.channel.as_str() {
+            "system" => msg.chat_id.clone(),
+            _ => msg.session_key(),
+        }
+    }
+
+    /// Runs one turn for `run` and publishes the answer; resolves to `key`.
+    async fn serve(&self, key: String, msg: InboundMessage) -> String {
+        debug!(channel = %msg.channel, chat_id = %msg.chat_id, "processing inbound message");
+        let turn = async {
+            match self.route(&msg) {
+                Some(agent) => agent.process_message(msg.clone(), None).await,
+                None => self.process_message(msg.clone(), None).await,
+            }
+        };
+        let live = self.live_reply(&msg);
+        let result = match &live {
+            Some(live) => events::with_events(live.sender(), turn).await,
+            None => turn.await,
+        };
+        let mut response = match result {
+            Ok(resp) => resp,
+            Err(err) => {
+                let mut out = OutboundMessage::new(
+                    msg.channel.clone(),
+                    msg.chat_id.clone(),
+                    error_reply(&err, &msg.channel),
+                );
+                out.metadata = msg.metadata.clone();
+                out
+            }
+        };
+        if let Some(live) = live {
+            live.finish(&mut response).await;
+        }
+        let _ = self.bus.publish_outbound(response).await;
+        key
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    /// Stops starting new turns: from now on user messages are answered with
+    /// a short unavailability notice while in-flight turns finish.
+    pub fn begin_drain(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Waits until no turn is in progress; false if `deadline` passes first.
+    pub async fn wait_idle(&self, deadline: tokio::time::Instant) -> bool {
+        loop {
+            if self.active_sessions().is_empty() {
+                return true;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    async fn process_message(
+        &self,
+        msg: InboundMessage,
+        session_key: Option<&str>,
+    ) -> Result<OutboundMessage> {
+        let session_id = session_key
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| msg.session_key());
+        let span = info_span!(
+            "agent.turn",
+            session_id = %session_id,
+            channel = %msg.channel,
+            chat_id = %msg.chat_id
+        );
+        if self.is_draining() && msg.channel != "system" {
+            debug!(session = %session_id, "rejecting message while draining");
+            let mut outbound = OutboundMessage::new(msg.channel, msg.chat_id, tr("drain-notice"));
+            outbound.metadata = msg.metadata;
+            return Ok(outbound);
+        }
+        self.set_active(&session_id, true);
+        // System messages continue the turn of the chat they came from.
+        let (turn_session, turn_channel) = match msg.channel.as_str() {
+            "system" => (
+                msg.chat_id.clone(),
+                msg.chat_id
+                    .split_once(':')
+                    .map_or("cli", |(channel, _)| channel)
+                    .to_string(),
+            ),
+            channel => (session_id.clone(), channel.to_string()),
+        };
+        // A system message acts for the chat it reports back to.
+        let (tool_channel, tool_chat_id) = match msg.channel.as_str() {
+            "system" => msg
+                .chat_id
+                .split_once(':')
+                .map(|(channel, chat)| (channel.to_string(), chat.to_string()))
+                .unwrap_or_else(|| ("cli".to_string(), msg.chat_id.clone())),
+            _ => (msg.channel.clone(), msg.chat_id.clone()),
+        };
+        let user = match msg.channel.as_str() {
+            "system" => {
+                let (channel, chat) = msg.chat_id.split_once(':').unwrap_or(("cli", ""));
+                access::user_id(channel, chat, &turn_session)
+            }
+            // Only `process_direct` passes a session key.
+            _ if session_key.is_some() => access::local_user_id(&turn_session),
+            channel => access::user_id(channel, &msg.sender_id, &turn_session),
+        };
+        let lock = self.session_lock(&turn_session);
+        let _turn = lock.lock().await;
+        let role = self.access.role_of(&user);
+        let prepared = self
+            .sessions
+            .get_or_create(&turn_session)
+            .and_then(|session| {
+                let model = self.model_for_role(&session, role.as_ref());
+                Ok((model, self.user_memory(&turn_session)?))
+            });
+        let (model, user_memory) = match prepared {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                self.set_active(&session_id, false);
+                return Err(err);
+            }
+        };
+        let result = TURN_MODEL
+            .scope(
+                model,
+                TURN_ROLE.scope(
+                    role,
+                    usage::with_origin(
+                        turn_session.clone(),
+                        turn_channel,
+                        user,
+                        events::with_bus(
+                            self.bus.clone(),
+                            turn_session,
+                            memory::with_user_memory(
+                                user_memory,
+                                with_turn_chat(
+                                    tool_channel,
+                                    tool_chat_id,
+                                    self.process_turn(msg, session_key).instrument(span),
+                                ),
+                            ),
+                        ),
+                    ),
+                ),
+            )
+            .await;
+        self.set_active(&session_id, false);
+        result
+    }
+
+    /// The lock `session` holds for its turns; locks nobody holds are
+    /// dropped on the way.
+    fn session_lock(&self, session: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.session_locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+        locks.entry(session.to_string()).or_default().clone()
+    }
+
+    fn set_active(&self, session_id: &str, active: bool) {
+        let mut turns = self.active_turns.lock().unwrap_or_else(|e| e.into_inner());
+        if active {
+            turns.insert(session_id.to_string(), Local::now());
+        } else {
+            turns.remove(session_id);
+        }
+    }
+
+    /// Sessions currently processing a turn, with their start times.
+    pub fn active_sessions(&self) -> Vec<(String, DateTime<Local>)> {
+        let turns = self.active_turns.lock().unwrap_or_else(|e| e.into_inner());
+        let mut sessions = turns
+            .iter()
+            .map(|(key, since)| (key.clone(), *since))
+            .collect::<Vec<_>>();
+        sessions.sort_by_key(|(_, since)| *since);
+        sessions
+    }
+
+    /// Archives older messages of `session_key` into memory, keeping the
+    /// recent window. Returns the message counts before and after.
+    pub async fn consolidate_session(&self, session_key: &str) -> Result<(usize, usize)> {
+        let mut session = self.sessions.get_or_create(session_key)?;
+        let before = session.messages.len();
+        self.consolidate_memory(&mut session, false).await?;
+        self.sessions.save(&session)?;
+        Ok((before, session.messages.len()))
+    }
+
+    /// Replaces the transcript of `session_key` with a model-written synopsis,
+    /// archiving the original so `SessionManager::restore_archive` can undo it.
+    pub async fn compact_session(&self, session_key: &str) -> Result<Compaction> {
+        let mut session = self.sessions.get_or_create(session_key)?;
+        let lines = Self::transcript_lines(&session.messages);
+        if lines.len() < 2 {
+            anyhow::bail!("session {session_key} is too short to compact");
+        }
+        let conversation = lines.join("\n");
+        let prompt = format!(
+            "Summarize this conversation into a synopsis you can continue it from without the transcript. \
+Keep the user's goals, decisions made, facts, names and numbers established, and open questions or next steps. \
+Use terse bullet points, at most a tenth of the original length. Reply with the synopsis only.\n\n\
+## Conversation\n{conversation}"
+        );
+        let response = self
+            .provider
+            .chat(
+                &[
+                    json!({
+                        "role": "system",
+                        "content": "You compress conversations into short, faithful synopses."
+                    }),
+                    json!({ "role": "user", "content": prompt }),
+                ],
+                None,
+                Some(&self.model),
+                1500,
+                0.0,
+            )
+            .await?;
+        let synopsis = response
+            .content
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .context("compaction returned an empty synopsis")?
+            .to_string();
+
+        let archive = self.sessions.archive(&session)?;
+        let messages = session.messages.len();
+        session.set_synopsis(&synopsis);
+        self.sessions.save(&session)?;
+        info!(session = %session_key, messages, "session compacted");
+        Ok(Compaction {
+            messages,
+            chars_before: conversation.chars().count(),
+            chars_after: synopsis.chars().count(),
+            archive,
+        })
+    }
+
+    /// Writes `session_key` up as a note in `dir` (see `publish`).
+    pub async fn publish_session(
+        &self,
+        session_key: &str,
+        dir: &Path,
+        title: Option<&str>,
+    ) -> Result<PathBuf> {
+        let path = publish::publish_session(
+            self.provider.as_ref(),
+            &self.model,
+            &self.sessions,
+            session_key,
+            dir,
+            title,
+        )
+        .await?;
+        info!(session = %session_key, path = %path.display(), "session published");
+        Ok(path)
+    }
+
+    /// The model `session` is answered with: the tier model of a background
+    /// job, its `/model` choice or the default, within what the role of the
+    /// current turn allows.
+    fn session_model(&self, session: &Session) -> String {
+        self.model_for_role(session, turn_role().as_ref())
+    }
+
+    fn model_for_role(&self, session: &Session, role: Option<&Role>) -> String {
+        let tier = router::job_tier();
+        let chosen = tier
+            .as_deref()
+            .and_then(|tier| self.router.tier_model(tier))
+            .or_else(|| {
+                session
+                    .metadata
+                    .get(MODEL_KEY)
+                    .and_then(Value::as_str)
+                    .filter(|model| !model.is_empty())
+            });
+        match role {
+            Some(role) => role.model_for(chosen, &self.model),
+            None => chosen.map_or_else(|| self.model.clone(), ToOwned::to_owned),
+        }
+    }
+
+    /// The user's spend today when the role of the current turn has a daily
+    /// limit and it is used up.
+    fn over_role_budget(&self) -> Option<(f64, f64)> {
+        let role = turn_role()?;
+        let user = usage::origin_user()?;
+        let limit = role.config.daily_usd;
+        if limit <= 0.0 {
+            return None;
+        }
+        let now = Local::now();
+        let records = usage::usage_path()
+            .map(|path| usage::load_records(&path, Some(now - chrono::Duration::days(1))))
+            .unwrap_or_default();
+        let spent = access::spent_today(&records, &user, &self.prices, now);
+        (spent >= limit).then_some((spent, limit))
+    }
+
+    /// The model of the current turn.
+    fn active_model(&self) -> String {
+        TURN_MODEL
+            .try_with(Clone::clone)
+            .unwrap_or_else(|_| self.model.clone())
+    }
+
+    /// Answers a chat command for `session`.
+    async fn run_command(&self, command: Command<'_>, session: &mut Session) -> Result<String> {
+        Ok(match command.spec.kind {
+            CommandKind::New => {
+                if let Err(err) = self.consolidate_memory(session, true).await {
+                    warn!(session = %session.key, "memory consolidation failed: {err}");
+                }
+                session.messages.clear();
+                self.sessions.save(session)?;
+                if let Err(err) = shell::remove_sandbox(&self.workspace, &session.key) {
+                    warn!(session = %session.key, "failed to clear the exec sandbox: {err}");
+                }
+                tr("session-new")
+            }
+            CommandKind::Compact => match self.compact_session(&session.key).await {
+                Ok(done) => tr_args(
+                    "session-compacted",
+                    &[
+                        ("messages", &done.messages.to_string()),
+                        ("before", &done.chars_before.to_string()),
+                        ("after", &done.chars_after.to_string()),
+                        ("session", &session.key),
+                    ],
+                ),
+                Err(err) => tr_args("session-compact-failed", &[("error", &format!("{err:#}"))]),
+            },
+            CommandKind::Status => tr_args(
+                "status-summary",
+                &[
+                    ("version", env!("CARGO_PKG_VERSION")),
+                    ("model", &self.session_model(session)),
+                    ("session", &session.key),
+                    ("messages", &session.messages.len().to_string()),
+                    ("tools", &self.tools.tool_names().len().to_string()),
+                    ("active", &self.active_sessions().len().to_string()),
+                ],
+            ),
+            CommandKind::Model => match command.args {
+                "" => tr_args("model-current", &[("model", &self.session_model(session))]),
+                "default" | "reset" => {
+                    session.metadata.remove(MODEL_KEY);
+                    self.sessions.save(session)?;
+                    tr_args("model-reset", &[("model", &self.session_model(session))])
+                }
+                model => match turn_role().filter(|role| !role.may_use_model(model)) {
+                    Some(role) => tr_args(
+                        "model-not-allowed",
+                        &[
+                            ("model", model),
+                            ("role", &role.name),
+                            ("models", &role.config.models.join(", ")),
+                        ],
+                    ),
+                    None => {
+                        session
+                            .metadata
+                            .insert(MODEL_KEY.to_string(), Value::String(model.to_string()));
+                        self.sessions.save(session)?;
+                        tr_args("model-set", &[("model", model)])
+                    }
+                },
+            },
+            CommandKind::Usage => self.usage_summary(&session.key),
+            CommandKind::Help => commands::help(),
+        })
+    }
+
+    /// Calls, tokens and cost of `session_key` and of today across chats.
+    fn usage_summary(&self, session_key: &str) -> String {
+        let records = usage::usage_path()
+            .map(|path| usage::load_records(&path, None))
+            .unwrap_or_default();
+        let today = Local::now().date_naive();
+        let total = |rows: &[&usage::UsageRecord]| {
+            let calls = rows.len();
+            let tokens = rows
+                .iter()
+                .map(|r| r.prompt_tokens + r.completion_tokens)
+                .sum::<u64>();
+            let cost = rows
+                .iter()
+                .map(|r| r.cost(&self.prices))
+                .sum::<Option<f64>>()
+                .filter(|_| calls > 0)
+                .map(|cost| format!(", ${cost:.4}"))
+                .unwrap_or_default();
+            (calls.to_string(), tokens.to_string(), cost)
+        };
+        let session_rows = records
+            .iter()
+            .filter(|r| r.session == session_key)
+            .collect::<Vec<_>>();
+        let today_rows = records
+            .iter()
+            .filter(|r| r.at.date_naive() == today)
+            .collect::<Vec<_>>();
+        let (session_calls, session_tokens, session_cost) = total(&session_rows);
+        let (today_calls, today_tokens, today_cost) = total(&today_rows);
+        tr_args(
+            "usage-summary",
+            &[
+                ("session_calls", &session_calls),
+                ("session_tokens", &session_tokens),
+                ("session_cost", &session_cost),
+                ("today_calls", &today_calls),
+                ("today_tokens", &today_tokens),
+                ("today_cost", &today_cost),
+            ],
+        )
+    }
+
+    async fn process_turn(
+        &self,
+        msg: InboundMessage,
+        session_key: Option<&str>,
+    ) -> Result<OutboundMessage> {
+        if msg.channel == "system" {
+            return self.process_system_message(msg).await;
+        }
+
+        let mut session = self
+            .sessions
+            .get_or_create(session_key.unwrap_or(&msg.session_key()))?;
+
+        if let Some(command) = commands::parse(&msg.content) {
+            let content = self.run_command(command, &mut session).await?;
+            let mut outbound = OutboundMessage::new(msg.channel, msg.chat_id, content);
+            outbound.metadata = msg.metadata;
+            return Ok(outbound);
+        }
+
+        if let Some((spent, limit)) = self.over_role_budget() {
+            info!(session = %session.key, spent, limit, "daily role budget used up");
+            let content = tr_args(
+                "access-budget-exceeded",
+                &[
+                    ("spent", &format!("{spent:.2}")),
+                    ("limit", &format!("{limit:.2}")),
+                ],
+            );
+            let mut outbound = OutboundMessage::new(msg.channel, msg.chat_id, content);
+            outbound.metadata = msg.metadata;
+            return Ok(outbound);
+        }
+
+        if session.messages.len() > self.memory_window
+            && let Err(err) = self.consolidate_memory(&mut session, false).await
+        {
+            warn!(session = %session.key, "memory consolidation failed: {err}");
+        }
+        if let Some(reply) = self.resolve_pending(&msg, &mut session).await? {
+            let mut outbound = OutboundMessage::new(msg.channel, msg.chat_id, reply);
+            outbound.metadata = msg.metadata;
+            return Ok(outbound);
+        }
+
+        let images = attachments::localize_images(&msg.media).await;
+        let media = (!images.is_empty()).then_some(images.as_slice());
+        // Deterministic anti-contamination: only current turn is sent to the model.
+        let history = session.get_history(0);
+        let allowed = self.allowed_tools(&session.key);
+        let allowed = allowed.as_deref();
+        // What the model is shown; `allowed` still decides what may run.
+        let offered = self.offered_tools(&msg.content, &history, allowed).await;
+        let offered = offered.as_deref().or(allowed);
+        self.refresh_context_sources().await;
+        let mut messages = self.build_turn_messages(
+            &history,
+            &msg.content,
+            &msg.channel,
+            &msg.chat_id,
+            media,
+            offered,
+        );
+        let reply_language = language::reply_language(&self.language, &msg.content);
+        let pending_key = format!("{}:{}", msg.channel, msg.chat_id);
+        self.add_turn_notes(&mut messages, reply_language.as_ref(), &pending_key);
+
+        let mut final_content: Option<String> = None;
+        let mut images: Vec<String> = Vec::new();
         let mut retried_with_fresh_context = false;
+        let mut tools_used: Vec<String> = Vec::new();
+        let mut iterations_run = 0u32;
+        let model = self.active_model();
         let turn_guard = TurnGuard::new(
             self.provider.as_ref(),
-            &self.model,
-            self.available_tools_text(),
+            &model,
+            self.available_tools_text(offered),
             self.max_iterations,
         );
+        let mut verify_attempts = 0u32;
         for iteration in 1..=self.max_iterations {
-            let tool_defs = self.tools.get_definitions();
-            let response = self
-                .provider
-                .chat(&messages, Some(&tool_defs), Some(&self.model), 4096, 0.7)
+            iterations_run = iteration;
+            let tool_defs = self.tool_definitions(offered);
+            debug!(iteration, model = %model, "requesting completion");
+            let mut response = self
+                .request_completion(&mut messages, &tool_defs)
+                .instrument(info_span!("llm.chat", model = %model, iteration))
                 .await?;
+            images.append(&mut response.images);
 
             if response.has_tool_calls() {
                 let tool_call_dicts = response
@@ -458,11 +1593,14 @@ impl AgentLoop {
                     response.reasoning_content.as_deref(),
                 );
 
+                let mut edited = false;
                 for tool_call in response.tool_calls {
+                    tools_used.push(tool_call.name.clone());
+                    debug!(tool = %tool_call.name, iteration, "executing tool call");
                     let result = self
-                        .tools
-                        .execute(&tool_call.name, &tool_call.arguments)
+                        .execute_tool(&msg.channel, &msg.chat_id, &tool_call, allowed)
                         .await;
+                    edited |= verify::is_edit(&tool_call.name) && !is_failure(&result);
                     self.context.add_tool_result(
                         &mut messages,
                         &tool_call.id,
@@ -470,6 +1608,8 @@ impl AgentLoop {
                         &result,
                     );
                 }
+                self.verify_round(&mut messages, edited, &mut verify_attempts)
+                    .await;
                 messages.push(json!({
                     "role": "user",
                     "content": "Reflect on the results and decide next steps."
@@ -483,10 +1623,12 @@ impl AgentLoop {
                         messages = self.build_turn_messages(
                             &[],
                             &msg.content,
-                            &origin_channel,
-                            &origin_chat_id,
-                            None,
+                            &msg.channel,
+                            &msg.chat_id,
+                            media,
+                            offered,
                         );
+                        self.add_turn_notes(&mut messages, reply_language.as_ref(), &pending_key);
                         messages.push(turn_guard.correction_message());
                         retried_with_fresh_context = true;
                         continue;
@@ -494,45 +1636,171 @@ impl AgentLoop {
                     final_content = Some(turn_guard.tools_available_response());
                     break;
                 }
-                final_content = response.content;
+                final_content = match (response.content, &reply_language) {
+                    (Some(content), Some(target)) => {
+                        Some(self.match_language(content, target).await)
+                    }
+                    (content, _) => content,
+                };
                 break;
             }
         }
 
-        let answer = final_content.unwrap_or_else(|| "Background task completed.".to_string());
-        session.add_message(
-            "user",
-            &format!("[System: {}] {}", msg.sender_id, msg.content),
-        );
-        session.add_message("assistant", &answer);
+        let mut answer = final_content.unwrap_or_else(|| {
+            if iterations_run >= self.max_iterations {
+                format!(
+                    "Reached {} iterations without completion.",
+                    self.max_iterations
+                )
+            } else {
+                "I've completed processing but have no response to give.".to_string()
+            }
+        });
+        let media = self.attach_images(&images, &mut answer).await;
+
+        session.add_message_with_media("user", &msg.content, &images);
+        session.add_message_with_tools("assistant", &answer, Some(&tools_used));
         self.sessions.save(&session)?;
 
-        Ok(OutboundMessage::new(origin_channel, origin_chat_id, answer))
+        let answer = self
+            .post_process_reply(&session.key, &msg.channel, answer)
+            .await;
+        let mut outbound = OutboundMessage::new(msg.channel, msg.chat_id, answer);
+        outbound.media = media;
+        outbound.metadata = msg.metadata;
+        Ok(outbound)
     }
 
-    async fn consolidate_memory(
-        &self,
-        session: &mut crate::session::Session,
-        archive_all: bool,
-    ) -> Result<()> {
-        let memory = MemoryStore::new(self.workspace.clone())?;
-        if session.messages.is_empty() {
-            return Ok(());
-        }
+    async fn process_system_message(&self, msg: InboundMessage) -> Result<OutboundMessage> {
+        let (origin_channel, origin_chat_id) = msg
+            .chat_id
+            .split_once(':')
+            .map(|(c, id)| (c.to_string(), id.to_string()))
+            .unwrap_or_else(|| ("cli".to_string(), msg.chat_id.clone()));
 
-        let keep_count = if archive_all {
-            0
-        } else {
-            usize::min(10, usize::max(2, self.memory_window / 2))
-        };
-        if !archive_all && session.messages.len() <= keep_count {
-            return Ok(());
+        let session_key = format!("{origin_channel}:{origin_chat_id}");
+        let mut session = self.sessions.get_or_create(&session_key)?;
+        // Deterministic anti-contamination: only current turn is sent to the model.
+        let history = session.get_history(0);
+        let allowed = self.allowed_tools(&session_key);
+        let allowed = allowed.as_deref();
+        self.refresh_context_sources().await;
+        let mut messages = self.build_turn_messages(
+            &history,
+            &msg.content,
+            &origin_channel,
+            &origin_chat_id,
+            None,
+            allowed,
+        );
+
+        let mut final_content: Option<String> = None;
+        let mut images: Vec<String> = Vec::new();
+        let mut retried_with_fresh_context = false;
+        let model = self.active_model();
+        let turn_guard = TurnGuard::new(
+            self.provider.as_ref(),
+            &model,
+            self.available_tools_text(allowed),
+            self.max_iterations,
+        );
+        let mut verify_attempts = 0u32;
+        for iteration in 1..=self.max_iterations {
+            let tool_defs = self.tool_definitions(allowed);
+            let mut response = self
+                .request_completion(&mut messages, &tool_defs)
+                .instrument(info_span!("llm.chat", model = %model, iteration))
+                .await?;
+            images.append(&mut response.images);
+
+            if response.has_tool_calls() {
+                let tool_call_dicts = response
+                    .tool_calls
+                    .iter()
+                    .map(|tc| {
+                        json!({
+                            "id": tc.id,
+                            "type": "function",
+                            "function": {
+                                "name": tc.name,
+                                "arguments": serde_json::to_string(&tc.arguments).unwrap_or_else(|_| "{}".to_string()),
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                self.context.add_assistant_message(
+                    &mut messages,
+                    response.content.as_deref(),
+                    Some(tool_call_dicts),
+                    response.reasoning_content.as_deref(),
+                );
+
+                let mut edited = false;
+                for tool_call in response.tool_calls {
+                    let result = self
+                        .execute_tool(&origin_channel, &origin_chat_id, &tool_call, allowed)
+                        .await;
+                    edited |= verify::is_edit(&tool_call.name) && !is_failure(&result);
+                    self.context.add_tool_result(
+                        &mut messages,
+                        &tool_call.id,
+                        &tool_call.name,
+                        &result,
+                    );
+                }
+                self.verify_round(&mut messages, edited, &mut verify_attempts)
+                    .await;
+                messages.push(json!({
+                    "role": "user",
+                    "content": "Reflect on the results and decide next steps."
+                }));
+            } else {
+                if turn_guard
+                    .should_retry_after_false_no_tools_claim(response.content.as_deref(), iteration)
+                    .await
+                {
+                    if !retried_with_fresh_context {
+                        messages = self.build_turn_messages(
+                            &[],
+                            &msg.content,
+                            &origin_channel,
+                            &origin_chat_id,
+                            None,
+                            allowed,
+                        );
+                        messages.push(turn_guard.correction_message());
+                        retried_with_fresh_context = true;
+                        continue;
+                    }
+                    final_content = Some(turn_guard.tools_available_response());
+                    break;
+                }
+                final_content = response.content;
+                break;
+            }
         }
 
-        let split_idx = session.messages.len().saturating_sub(keep_count);
-        let old_messages = &session.messages[..split_idx];
+        let mut answer = final_content.unwrap_or_else(|| "Background task completed.".to_string());
+        let media = self.attach_images(&images, &mut answer).await;
+        session.add_message(
+            "user",
+            &format!("[System: {}] {}", msg.sender_id, msg.content),
+        );
+        session.add_message("assistant", &answer);
+        self.sessions.save(&session)?;
+
+        let answer = self
+            .post_process_reply(&session_key, &origin_channel, answer)
+            .await;
+        let mut outbound = OutboundMessage::new(origin_channel, origin_chat_id, answer);
+        outbound.media = media;
+        Ok(outbound)
+    }
+
+    /// `[timestamp] ROLE [tools: ...]: content` lines for summarization prompts.
+    pub(crate) fn transcript_lines(messages: &[Value]) -> Vec<String> {
         let mut lines = Vec::new();
-        for msg in old_messages {
+        for msg in messages {
             let Some(content) = msg.get("content").and_then(Value::as_str) else {
                 continue;
             };
@@ -573,6 +1841,47 @@ impl AgentLoop {
                 content = content.trim()
             ));
         }
+        lines
+    }
+
+    /// The encrypted memory of the user `session_key` belongs to; `None` for
+    /// sessions that use the workspace memory.
+    fn user_memory(&self, session_key: &str) -> Result<Option<MemoryStore>> {
+        let Some(key) = self.sessions.user_key(session_key)? else {
+            return Ok(None);
+        };
+        let scope = vault::user_scope(session_key).unwrap_or(session_key);
+        Ok(Some(MemoryStore::for_user(
+            self.workspace.clone(),
+            scope,
+            key,
+        )?))
+    }
+
+    async fn consolidate_memory(
+        &self,
+        session: &mut crate::session::Session,
+        archive_all: bool,
+    ) -> Result<()> {
+        let memory = match self.user_memory(&session.key)? {
+            Some(memory) => memory,
+            None => MemoryStore::new(self.workspace.clone())?,
+        };
+        if session.messages.is_empty() {
+            return Ok(());
+        }
+
+        let keep_count = if archive_all {
+            0
+        } else {
+            usize::min(10, usize::max(2, self.memory_window / 2))
+        };
+        if !archive_all && session.messages.len() <= keep_count {
+            return Ok(());
+        }
+
+        let split_idx = session.messages.len().saturating_sub(keep_count);
+        let lines = Self::transcript_lines(&session.messages[..split_idx]);
 
         if lines.is_empty() {
             session.messages = session.messages[split_idx..].to_vec();
@@ -643,12 +1952,182 @@ Respond with ONLY valid JSON, no markdown fences.",
         Ok(())
     }
 
+    /// Reads sessions that have been quiet for `idle` and files the durable
+    /// preferences, facts and decisions in them into the memory's
+    /// `FACTS.md`, each tagged with its session and date. A session is read
+    /// again only once it has new messages. Returns how many facts were added.
+    pub async fn extract_idle_sessions(
+        &self,
+        idle: Duration,
+        min_messages: usize,
+    ) -> Result<usize> {
+        let idle = chrono::Duration::from_std(idle).unwrap_or(chrono::Duration::MAX);
+        let active: Vec<String> = self
+            .active_sessions()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        let mut added = 0;
+        for key in self.sessions.list_session_keys()? {
+            if key.starts_with("cron:") || key == "heartbeat" || active.contains(&key) {
+                continue;
+            }
+            // A user's sessions stay sealed until they authenticate.
+            if self.sessions.user_key(&key).is_err() {
+                continue;
+            }
+            let session = match self.sessions.get_or_create(&key) {
+                Ok(session) => session,
+                Err(err) => {
+                    warn!(session = %key, "cannot read session: {err:#}");
+                    continue;
+                }
+            };
+            if Local::now() - session.updated_at < idle {
+                continue;
+            }
+            let since = session
+                .metadata
+                .get(FACTS_EXTRACTED_KEY)
+                .and_then(Value::as_str)
+                .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok())
+                .map(|at| at.with_timezone(&Local));
+            if since.is_some_and(|since| since >= session.updated_at) {
+                continue;
+            }
+            let fresh: Vec<Value> = session
+                .messages
+                .iter()
+                .filter(|msg| {
+                    let at = msg
+                        .get("timestamp")
+                        .and_then(Value::as_str)
+                        .and_then(|raw| DateTime::parse_from_rfc3339(raw).ok());
+                    match (since, at) {
+                        (Some(since), Some(at)) => at > since,
+                        _ => true,
+                    }
+                })
+                .cloned()
+                .collect();
+            let lines = Self::transcript_lines(&fresh);
+            if lines.len() < min_messages.max(1) {
+                continue;
+            }
+            match self.extract_facts(&key, &lines, session.updated_at).await {
+                Ok(count) => added += count,
+                Err(err) => {
+                    warn!(session = %key, "fact extraction failed: {err:#}");
+                    continue;
+                }
+            }
+            // Re-read so a turn that started meanwhile is not overwritten.
+            if self.active_sessions().iter().any(|(k, _)| *k == key) {
+                continue;
+            }
+            let mut current = self.sessions.get_or_create(&key)?;
+            current.metadata.insert(
+                FACTS_EXTRACTED_KEY.to_string(),
+                Value::String(session.updated_at.to_rfc3339()),
+            );
+            self.sessions.save(&current)?;
+        }
+        Ok(added)
+    }
+
+    async fn extract_facts(
+        &self,
+        session_key: &str,
+        lines: &[String],
+        at: DateTime<Local>,
+    ) -> Result<usize> {
+        let memory = match self.user_memory(session_key)? {
+            Some(memory) => memory,
+            None => MemoryStore::new(self.workspace.clone())?,
+        };
+        let known = memory.read_facts();
+        let prompt = format!(
+            "Extract the durable knowledge from this conversation: the user's preferences, facts about them, their projects and people, and decisions that were made. Leave out small talk, one-off requests and anything only relevant to this conversation.\n\n\
+Return a JSON object {{\"facts\": [{{\"kind\": \"preference|fact|decision\", \"text\": \"...\"}}]}} with one short, self-contained sentence per item. Skip what is already known. Return {{\"facts\": []}} if there is nothing new.\n\n\
+## Already Known\n{known}\n\n\
+## Conversation\n{conversation}\n\n\
+Respond with ONLY valid JSON, no markdown fences.",
+            known = if known.trim().is_empty() {
+                "(nothing)"
+            } else {
+                known.trim()
+            },
+            conversation = lines.join("\n")
+        );
+        let response = self
+            .provider
+            .chat(
+                &[
+                    json!({
+                        "role": "system",
+                        "content": "You are a memory extraction agent. Respond only with valid JSON."
+                    }),
+                    json!({
+                        "role": "user",
+                        "content": prompt
+                    }),
+                ],
+                None,
+                Some(&self.model),
+                1200,
+                0.0,
+            )
+            .await?;
+        let parsed = response
+            .content
+            .as_deref()
+            .and_then(Self::extract_json_object)
+            .context("fact extraction returned non-JSON content")?;
+        let at = at.format("%Y-%m-%d %H:%M").to_string();
+        let facts: Vec<Fact> = parsed
+            .get("facts")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|item| {
+                Some(Fact {
+                    kind: item
+                        .get("kind")
+                        .and_then(Value::as_str)
+                        .unwrap_or("fact")
+                        .to_string(),
+                    text: item.get("text").and_then(Value::as_str)?.to_string(),
+                    source: session_key.to_string(),
+                    at: at.clone(),
+                })
+            })
+            .collect();
+        let added = memory.add_facts(&facts)?;
+        if added > 0 {
+            info!(session = %session_key, "filed {added} fact(s) from idle session");
+        }
+        Ok(added)
+    }
+
     pub async fn process_direct(
         &self,
         content: &str,
         session_key: Option<&str>,
         channel: Option<&str>,
         chat_id: Option<&str>,
+    ) -> Result<String> {
+        self.process_direct_with_media(content, Vec::new(), session_key, channel, chat_id)
+            .await
+    }
+
+    /// `process_direct` with images (file paths or http(s) URLs) sent along.
+    pub async fn process_direct_with_media(
+        &self,
+        content: &str,
+        media: Vec<String>,
+        session_key: Option<&str>,
+        channel: Option<&str>,
+        chat_id: Option<&str>,
     ) -> Result<String> {
         let session_key = session_key.unwrap_or("cli:direct");
         let (default_channel, default_chat_id) = session_key
@@ -658,16 +2137,737 @@ Respond with ONLY valid JSON, no markdown fences.",
         let channel = channel.unwrap_or(&default_channel);
         let chat_id = chat_id.unwrap_or(&default_chat_id);
 
-        let msg = InboundMessage::new(channel, "user", chat_id, content);
+        let mut msg = InboundMessage::new(channel, access::LOCAL_SENDER, chat_id, content);
+        msg.media = media;
         let response = self.process_message(msg, Some(session_key)).await?;
         Ok(response.content)
     }
 
+    /// `process_direct`, reporting deltas and tool progress to `events`.
+    pub async fn process_direct_streaming(
+        &self,
+        content: &str,
+        session_key: Option<&str>,
+        channel: Option<&str>,
+        chat_id: Option<&str>,
+        events: EventSender,
+    ) -> Result<String> {
+        events::with_events(
+            events,
+            self.process_direct(content, session_key, channel, chat_id),
+        )
+        .await
+    }
+
+    /// Runs a tool outside a model turn, still subject to the approval gate.
+    pub async fn run_tool(
+        &self,
+        channel: &str,
+        chat_id: &str,
+        name: &str,
+        arguments: &Map<String, Value>,
+    ) -> String {
+        let call = ToolCallRequest {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            name: name.to_string(),
+            arguments: arguments.clone(),
+        };
+        self.execute_tool(channel, chat_id, &call, None).await
+    }
+
     pub fn workspace(&self) -> &PathBuf {
         &self.workspace
     }
 
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
     pub async fn running_subagents(&self) -> usize {
         self.subagents.get_running_count().await
     }
 }
+
+/// Finish reasons providers use for filtered completions.
+const FILTER_FINISH_REASONS: &[&str] = &["content_filter", "safety", "refusal", "sensitive"];
+
+/// Whether the provider's content filter blocked this completion.
+fn content_filtered(result: &Result<LLMResponse>) -> bool {
+    match result {
+        Ok(response) => {
+            !response.has_tool_calls()
+                && FILTER_FINISH_REASONS
+                    .contains(&response.finish_reason.to_ascii_lowercase().as_str())
+        }
+        Err(err) => matches!(
+            ProviderError::find(err),
+            Some(ProviderError::ContentFilter { .. })
+        ),
+    }
+}
+
+/// Whether a profile's tool list lets the session call `name`. Paging
+/// follows whatever tools are allowed, so `continue_result` always is.
+fn tool_permitted(allowed: Option<&[String]>, name: &str) -> bool {
+    name == CONTINUE_RESULT_TOOL || allowed.is_none_or(|allowed| allowed.iter().any(|a| a == name))
+}
+
+/// Longest tool result kept when trimming a turn that no longer fits.
+const TRIMMED_TOOL_RESULT_CHARS: usize = 2_000;
+const TRUNCATED_NOTE: &str = "\n… (truncated to fit the context window)";
+
+/// What `trim_for_context` removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Trimmed {
+    /// Earlier tool rounds removed.
+    dropped: usize,
+    /// Tool results cut down to `TRIMMED_TOOL_RESULT_CHARS`.
+    truncated: usize,
+}
+
+/// Shrinks a turn that overflowed the context window. System messages and the
+/// user's request are pinned; the older half of the tool rounds after it are
+/// replaced by a short note, or, with a single round left, long tool results
+/// are truncated. `None` when there is nothing left to trim.
+fn trim_for_context(messages: &mut Vec<Value>) -> Option<Trimmed> {
+    let role = |m: &Value| m.get("role").and_then(Value::as_str).map(str::to_owned);
+    let request = messages
+        .iter()
+        .position(|m| role(m).as_deref() == Some("user"))?;
+    let rounds = messages
+        .iter()
+        .enumerate()
+        .skip(request + 1)
+        .filter(|(_, m)| role(m).as_deref() == Some("assistant"))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if rounds.len() > 1 {
+        let end = rounds[rounds.len() / 2];
+        let removed = messages
+            .splice(
+                request + 1..end,
+                [json!({
+                    "role": "user",
+                    "content": "(Earlier tool steps of this turn were removed to fit the context window.)",
+                })],
+            )
+            .filter(|m| role(m).as_deref() == Some("assistant"))
+            .count();
+        return Some(Trimmed {
+            dropped: removed,
+            truncated: 0,
+        });
+    }
+    let mut truncated = 0;
+    for message in messages.iter_mut().skip(request + 1) {
+        if role(message).as_deref() != Some("tool") {
+            continue;
+        }
+        let Some(content) = message.get("content").and_then(Value::as_str) else {
+            continue;
+        };
+        if content.chars().count() > TRIMMED_TOOL_RESULT_CHARS && !content.ends_with(TRUNCATED_NOTE)
+        {
+            let mut short = content
+                .chars()
+                .take(TRIMMED_TOOL_RESULT_CHARS)
+                .collect::<String>();
+            short.push_str(TRUNCATED_NOTE);
+            message["content"] = Value::String(short);
+            truncated += 1;
+        }
+    }
+    (truncated > 0).then_some(Trimmed {
+        dropped: 0,
+        truncated,
+    })
+}
+
+/// Rewrites a request for a model without native tools or vision: tools use
+/// the text protocol in `text_tools` and images become a short placeholder.
+fn adapt_to_capabilities(
+    messages: &[Value],
+    tool_defs: &[Value],
+    caps: &Capabilities,
+) -> Vec<Value> {
+    let mut messages = if caps.tools {
+        messages.to_vec()
+    } else {
+        text_tools::to_text_protocol(messages)
+    };
+    if !caps.tools
+        && !tool_defs.is_empty()
+        && let Some(system) = messages
+            .iter_mut()
+            .find(|m| m.get("role").and_then(Value::as_str) == Some("system"))
+    {
+        let prompt = system
+            .get("content")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        system["content"] = Value::String(format!(
+            "{prompt}\n\n{}",
+            text_tools::describe_tools(tool_defs)
+        ));
+    }
+    if !caps.vision {
+        for message in &mut messages {
+            let Some(parts) = message.get("content").and_then(Value::as_array) else {
+                continue;
+            };
+            if !parts
+                .iter()
+                .any(|p| p.get("type").and_then(Value::as_str) == Some("image_url"))
+            {
+                continue;
+            }
+            let mut text = parts
+                .iter()
+                .filter(|p| p.get("type").and_then(Value::as_str) == Some("text"))
+                .filter_map(|p| p.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n");
+            text.push_str("\n[image omitted: this model cannot view images]");
+            message["content"] = Value::String(text);
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentLoopBuilder;
+    use crate::config::RoleConfig;
+    use crate::eval::{Cassette, EvalProvider};
+
+    #[test]
+    fn overflowing_turns_drop_old_rounds_then_truncate_results() {
+        let round = |id: &str, result: &str| {
+            vec![
+                json!({"role": "assistant", "content": "", "tool_calls": [{
+                    "id": id, "type": "function",
+                    "function": {"name": "exec", "arguments": "{}"}
+                }]}),
+                json!({"role": "tool", "tool_call_id": id, "name": "exec", "content": result}),
+            ]
+        };
+        let mut messages = vec![
+            json!({"role": "system", "content": "You are nanobot."}),
+            json!({"role": "user", "content": "build it"}),
+        ];
+        for id in ["1", "2", "3", "4"] {
+            messages.extend(round(id, &"x".repeat(5_000)));
+        }
+
+        let trimmed = trim_for_context(&mut messages).expect("trimmed");
+        assert_eq!((trimmed.dropped, trimmed.truncated), (2, 0));
+        assert_eq!(messages.len(), 7);
+        assert_eq!(messages[1]["content"], "build it");
+        assert_eq!(messages[3]["tool_calls"][0]["id"], "3");
+
+        trim_for_context(&mut messages).expect("trimmed");
+        let trimmed = trim_for_context(&mut messages).expect("trimmed");
+        assert_eq!(trimmed.truncated, 1);
+        assert!(messages.last().unwrap()["content"].as_str().unwrap().len() < 2_100);
+        assert_eq!(trim_for_context(&mut messages), None);
+    }
+
+    #[test]
+    fn filtered_completions_are_detected() {
+        let response = |finish_reason: &str| LLMResponse {
+            content: None,
+            tool_calls: Vec::new(),
+            finish_reason: finish_reason.to_string(),
+            usage: Map::new(),
+            reasoning_content: None,
+            images: Vec::new(),
+        };
+        assert!(content_filtered(&Ok(response("content_filter"))));
+        assert!(content_filtered(&Ok(response("SAFETY"))));
+        assert!(!content_filtered(&Ok(response("stop"))));
+        let err = anyhow::Error::new(ProviderError::ContentFilter {
+            message: "blocked".to_string(),
+        });
+        assert!(content_filtered(&Err(err)));
+    }
+
+    #[test]
+    fn text_only_models_get_tools_in_prompt_and_no_images() {
+        let messages = vec![
+            json!({"role": "system", "content": "You are nanobot."}),
+            json!({"role": "user", "content": [
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,AA=="}},
+                {"type": "text", "text": "what is this?"}
+            ]}),
+        ];
+        let tools = vec![json!({"type": "function", "function": {
+            "name": "read_file", "description": "Read a file", "parameters": {}
+        }})];
+        let caps = Capabilities {
+            tools: false,
+            ..Capabilities::default()
+        };
+        let adapted = adapt_to_capabilities(&messages, &tools, &caps);
+        let system = adapted[0]["content"].as_str().unwrap();
+        assert!(system.contains("TOOL: <tool name>"));
+        assert!(system.contains("- read_file: Read a file"));
+        assert_eq!(
+            adapted[1]["content"],
+            "what is this?\n[image omitted: this model cannot view images]"
+        );
+    }
+
+    #[tokio::test]
+    async fn draining_answers_new_messages_without_a_turn() {
+        let dir = std::env::temp_dir().join(format!("nanobot-drain-{}", uuid::Uuid::new_v4()));
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        // An empty cassette fails any model call, so a reply proves none was made.
+        let provider = Arc::new(EvalProvider::replay(Cassette::default(), "test-model"));
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("workspace"))
+            .max_iterations(4)
+            .restrict_to_workspace(true)
+            .sessions(sessions)
+            .build()
+            .expect("agent");
+
+        agent.begin_drain();
+        let reply = agent
+            .process_direct("hello", Some("cli:drain"), None, None)
+            .await
+            .expect("reply");
+        assert_eq!(reply, tr("drain-notice"));
+        assert!(agent.active_sessions().is_empty());
+        assert!(agent.wait_idle(tokio::time::Instant::now()).await);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn chat_commands_switch_the_model_without_a_turn() {
+        let dir = std::env::temp_dir().join(format!("nanobot-commands-{}", uuid::Uuid::new_v4()));
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let provider = Arc::new(EvalProvider::replay(Cassette::default(), "test-model"));
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("workspace"))
+            .sessions(sessions.clone())
+            .build()
+            .expect("agent");
+        let run = |text: &'static str| agent.process_direct(text, Some("cli:cmd"), None, None);
+
+        assert_eq!(
+            run("/model openai/gpt-4o").await.expect("model"),
+            tr_args("model-set", &[("model", "openai/gpt-4o")])
+        );
+        assert_eq!(
+            agent.session_model(&sessions.get_or_create("cli:cmd").expect("session")),
+            "openai/gpt-4o"
+        );
+        assert!(
+            run("/status")
+                .await
+                .expect("status")
+                .contains("openai/gpt-4o")
+        );
+        run("/model default").await.expect("reset");
+        assert!(run("/STATUS").await.expect("status").contains("test-model"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// Answers only once two calls are waiting, so it stalls a loop that
+    /// handles one chat at a time.
+    struct PairedProvider(tokio::sync::Barrier);
+
+    #[async_trait::async_trait]
+    impl LLMProvider for PairedProvider {
+        async fn chat(
+            &self,
+            _messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f32,
+        ) -> Result<LLMResponse> {
+            self.0.wait().await;
+            Ok(LLMResponse {
+                content: Some("done".to_string()),
+                tool_calls: Vec::new(),
+                finish_reason: "stop".to_string(),
+                usage: Map::new(),
+                reasoning_content: None,
+                images: Vec::new(),
+            })
+        }
+
+        fn default_model(&self) -> &str {
+            "test-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn run_answers_different_chats_concurrently() {
+        let dir = std::env::temp_dir().join(format!("nanobot-serve-{}", uuid::Uuid::new_v4()));
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let bus = Arc::new(MessageBus::new(16));
+        let agent = Arc::new(
+            AgentLoop::builder(Arc::new(PairedProvider(tokio::sync::Barrier::new(2))))
+                .bus(bus.clone())
+                .workspace(dir.join("workspace"))
+                .sessions(sessions)
+                .build()
+                .expect("agent"),
+        );
+        let runner = tokio::spawn({
+            let agent = agent.clone();
+            async move { agent.run().await }
+        });
+        for sender in ["1", "2"] {
+            bus.publish_inbound(InboundMessage::new("telegram", sender, sender, "hello"))
+                .await
+                .expect("publish");
+        }
+        let mut chats = Vec::new();
+        for _ in 0..2 {
+            let reply = timeout(Duration::from_secs(10), bus.consume_outbound())
+                .await
+                .expect("both chats answered")
+                .expect("reply");
+            assert_eq!(reply.content, "done");
+            chats.push(reply.chat_id);
+        }
+        chats.sort();
+        assert_eq!(chats, ["1", "2"]);
+        agent.stop();
+        runner.await.expect("join").expect("run");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn background_jobs_run_on_their_tier_model() {
+        let dir = std::env::temp_dir().join(format!("nanobot-tiers-{}", uuid::Uuid::new_v4()));
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let provider = Arc::new(EvalProvider::replay(Cassette::default(), "test-model"));
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("workspace"))
+            .sessions(sessions.clone())
+            .router(RouterConfig {
+                tiers: [("cheap".to_string(), "cheap-model".to_string())].into(),
+                background_tier: "cheap".to_string(),
+                ..RouterConfig::default()
+            })
+            .build()
+            .expect("agent");
+        let session = sessions.get_or_create("cron:digest").expect("session");
+
+        assert_eq!(agent.session_model(&session), "test-model");
+        let model = router::with_job_tier(None, async { agent.session_model(&session) }).await;
+        assert_eq!(model, "cheap-model");
+        let model =
+            router::with_job_tier(Some("premium"), async { agent.session_model(&session) }).await;
+        assert_eq!(model, "test-model");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn profile_sessions_only_see_their_tools() {
+        let dir = std::env::temp_dir().join(format!("nanobot-profile-{}", uuid::Uuid::new_v4()));
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let provider = Arc::new(EvalProvider::replay(Cassette::default(), "test-model"));
+        let profile = AgentProfile {
+            tools: Some(vec!["read_file".to_string(), "list_dir".to_string()]),
+            ..AgentProfile::default()
+        };
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("workspace"))
+            .sessions(sessions)
+            .profiles(HashMap::from([("research".to_string(), profile)]))
+            .build()
+            .expect("agent");
+
+        let allowed = agent.allowed_tools("api:research:notes");
+        let allowed = allowed.as_deref();
+        let names = agent
+            .tool_definitions(allowed)
+            .iter()
+            .filter_map(|def| def["function"]["name"].as_str().map(str::to_string))
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"read_file".to_string()));
+        assert_eq!(
+            agent.available_tools_text(allowed),
+            "continue_result, list_dir, read_file"
+        );
+        assert!(agent.allowed_tools("api:other").is_none());
+        assert!(agent.allowed_tools("telegram:research").is_none());
+
+        let call = ToolCallRequest {
+            id: "1".to_string(),
+            name: "exec".to_string(),
+            arguments: Map::new(),
+        };
+        let result = agent
+            .execute_tool("api", "research:notes", &call, allowed)
+            .await;
+        assert!(result.contains("not available"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn roles_narrow_tools_and_models_per_user() {
+        let dir = std::env::temp_dir().join(format!("nanobot-access-{}", uuid::Uuid::new_v4()));
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let provider = Arc::new(EvalProvider::replay(Cassette::default(), "test-model"));
+        let mut access = AccessConfig {
+            enabled: true,
+            ..AccessConfig::default()
+        };
+        access.roles.insert(
+            "guest".to_string(),
+            RoleConfig {
+                tools: Some(vec!["web_search".to_string()]),
+                models: vec!["cheap-model".to_string()],
+                ..RoleConfig::default()
+            },
+        );
+        access
+            .users
+            .insert("telegram:1".to_string(), "owner".to_string());
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("workspace"))
+            .sessions(sessions)
+            .access(access)
+            .build()
+            .expect("agent");
+        let send = |sender: &str, text: &str| {
+            agent.process_message(InboundMessage::new("telegram", sender, sender, text), None)
+        };
+
+        let refused = send("2", "/model test-model").await.expect("model");
+        assert_eq!(
+            refused.content,
+            tr_args(
+                "model-not-allowed",
+                &[
+                    ("model", "test-model"),
+                    ("role", "guest"),
+                    ("models", "cheap-model")
+                ]
+            )
+        );
+        assert!(
+            send("2", "/status")
+                .await
+                .expect("status")
+                .content
+                .contains("cheap-model")
+        );
+        assert!(
+            send("1", "/status")
+                .await
+                .expect("status")
+                .content
+                .contains("test-model")
+        );
+
+        let guest = agent.access.role_of("telegram:2");
+        let allowed = TURN_ROLE.sync_scope(guest, || agent.allowed_tools("telegram:2"));
+        assert_eq!(allowed, Some(vec!["web_search".to_string()]));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn gated_calls_on_unprompted_channels_wait_for_a_yes() {
+        let dir = std::env::temp_dir().join(format!("nanobot-pending-{}", uuid::Uuid::new_v4()));
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let reply = |content: Option<&str>, tool_calls: Vec<ToolCallRequest>| LLMResponse {
+            content: content.map(str::to_string),
+            tool_calls,
+            finish_reason: "stop".to_string(),
+            usage: Map::new(),
+            reasoning_content: None,
+            images: Vec::new(),
+        };
+        let call = ToolCallRequest {
+            id: "call-1".to_string(),
+            name: "list_dir".to_string(),
+            arguments: json!({"path": "."})
+                .as_object()
+                .cloned()
+                .unwrap_or_default(),
+        };
+        let mut cassette = Cassette::default();
+        cassette.cases.insert(
+            String::new(),
+            vec![
+                reply(None, vec![call]),
+                reply(Some("Shall I list it?"), Vec::new()),
+            ],
+        );
+        let provider = Arc::new(EvalProvider::replay(cassette, "test-model"));
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("workspace"))
+            .sessions(sessions)
+            .approval_gate(Arc::new(ApprovalGate::new(["list_dir".to_string()])))
+            .build()
+            .expect("agent");
+
+        let answer = agent
+            .process_direct("list files", Some("feishu:chat1"), None, None)
+            .await
+            .expect("reply");
+        assert_eq!(answer, "Shall I list it?");
+        assert_eq!(
+            agent.pending.get("feishu:chat1").map(|action| action.tool),
+            Some("list_dir".to_string())
+        );
+
+        let done = agent
+            .process_direct("yes", Some("feishu:chat1"), None, None)
+            .await
+            .expect("reply");
+        assert!(done.contains("list_dir"));
+        assert!(agent.pending.get("feishu:chat1").is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// Records whether it ran.
+    struct FlagTool(Arc<std::sync::atomic::AtomicBool>);
+
+    #[async_trait::async_trait]
+    impl crate::tools::base::Tool for FlagTool {
+        fn name(&self) -> &str {
+            "flag"
+        }
+
+        fn description(&self) -> &str {
+            "Raise the flag"
+        }
+
+        fn parameters(&self) -> Value {
+            json!({"type": "object", "properties": {}})
+        }
+
+        async fn execute(&self, _params: &Map<String, Value>) -> Result<String> {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok("raised".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn routed_workspaces_keep_role_limits() {
+        let dir = std::env::temp_dir().join(format!("nanobot-routes-{}", uuid::Uuid::new_v4()));
+        let mut config = crate::config::Config::default();
+        config.agents.defaults.workspace = dir.join("home").display().to_string();
+        config.workspaces.insert(
+            "work".to_string(),
+            WorkspaceConfig {
+                path: dir.join("work").display().to_string(),
+                channels: vec!["telegram".to_string()],
+            },
+        );
+        config.access.enabled = true;
+        config.access.roles.insert(
+            "guest".to_string(),
+            RoleConfig {
+                tools: Some(vec!["web_search".to_string()]),
+                ..RoleConfig::default()
+            },
+        );
+        let call = ToolCallRequest {
+            id: "call-1".to_string(),
+            name: "flag".to_string(),
+            arguments: Map::new(),
+        };
+        let reply = |content: Option<&str>, tool_calls: Vec<ToolCallRequest>| LLMResponse {
+            content: content.map(str::to_string),
+            tool_calls,
+            finish_reason: "stop".to_string(),
+            usage: Map::new(),
+            reasoning_content: None,
+            images: Vec::new(),
+        };
+        let mut cassette = Cassette::default();
+        cassette.cases.insert(
+            String::new(),
+            vec![reply(None, vec![call]), reply(Some("Done."), Vec::new())],
+        );
+        let provider: Arc<dyn LLMProvider> = Arc::new(EvalProvider::replay(cassette, "test-model"));
+        let raised = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let routes =
+            AgentLoopBuilder::routes_from_config(&provider, &config, |ws_config, builder| {
+                let sessions =
+                    SessionManager::with_dir(ws_config.workspace_path().join("sessions"))?;
+                let mut tools = ToolRegistry::new();
+                tools.register(Arc::new(FlagTool(raised.clone())));
+                Ok(builder.sessions(Arc::new(sessions)).tools(tools))
+            })
+            .expect("routes");
+        let agent = AgentLoop::builder(provider)
+            .workspace(dir.join("home"))
+            .sessions(Arc::new(
+                SessionManager::with_dir(dir.join("sessions")).expect("sessions"),
+            ))
+            .workspace_routes(routes)
+            .build()
+            .expect("agent");
+
+        let msg = InboundMessage::new("telegram", "2", "2", "raise the flag");
+        let routed = agent.route(&msg).expect("routed");
+        assert_eq!(routed.workspace(), &dir.join("work"));
+        let answer = routed.process_message(msg, None).await.expect("answer");
+        assert_eq!(answer.content, "Done.");
+        assert!(!raised.load(std::sync::atomic::Ordering::SeqCst));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn cli_tool_runs_pass_the_approval_and_read_only_gates() {
+        let dir = std::env::temp_dir().join(format!("nanobot-run-tool-{}", uuid::Uuid::new_v4()));
+        let agent = |raised: &Arc<std::sync::atomic::AtomicBool>| {
+            let mut tools = ToolRegistry::new();
+            tools.register(Arc::new(FlagTool(raised.clone())));
+            let provider = Arc::new(EvalProvider::replay(Cassette::default(), "test-model"));
+            AgentLoop::builder(provider)
+                .workspace(dir.join("workspace"))
+                .sessions(Arc::new(
+                    SessionManager::with_dir(dir.join("sessions")).expect("sessions"),
+                ))
+                .tools(tools)
+        };
+        let raised = || Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let args = Map::new();
+
+        let flag = raised();
+        let open = agent(&flag).build().expect("agent");
+        assert_eq!(
+            open.run_tool("cli", "direct", "flag", &args).await,
+            "raised"
+        );
+        assert!(flag.load(std::sync::atomic::Ordering::SeqCst));
+
+        let flag = raised();
+        let read_only = agent(&flag).read_only(true).build().expect("agent");
+        assert!(
+            read_only
+                .run_tool("cli", "direct", "flag", &args)
+                .await
+                .contains("read-only")
+        );
+        assert!(!flag.load(std::sync::atomic::Ordering::SeqCst));
+
+        // No prompt for the CLI here, so the call waits for a yes.
+        let flag = raised();
+        let gated = agent(&flag)
+            .approval_gate(Arc::new(ApprovalGate::new(["flag".to_string()])))
+            .build()
+            .expect("agent");
+        assert!(
+            gated
+                .run_tool("cli", "direct", "flag", &args)
+                .await
+                .starts_with("Pending confirmation")
+        );
+        assert!(!flag.load(std::sync::atomic::Ordering::SeqCst));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
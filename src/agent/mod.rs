@@ -1,6 +1,21 @@
+pub mod approval;
+pub mod builder;
+pub mod commands;
 pub mod context;
+pub mod context_sources;
+pub mod events;
+pub mod failure;
+pub mod language;
 pub mod r#loop;
+pub mod pending;
+pub mod postprocess;
+pub mod stream;
 pub mod subagent;
+pub mod text_tools;
+pub mod tool_select;
 pub mod turn_guard;
+pub mod verify;
 
-pub use r#loop::AgentLoop;
+pub use builder::AgentLoopBuilder;
+pub use failure::error_reply;
+pub use r#loop::{AgentLoop, DEFAULT_CONCURRENT_TURNS};
@@ -0,0 +1,142 @@
+//! Actions waiting for the user's go-ahead in a later message.
+//!
+//! When a tool needs approval on a channel that cannot show an approval
+//! prompt, the call is staged here under the session instead of being
+//! denied. The model shows the user what will happen; the user's next
+//! "yes" runs the staged call and "no" drops it, without relying on the
+//! model to remember that confirmation was required.
+
+use chrono::{DateTime, Local};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How long a staged action waits for an answer.
+pub const PENDING_ACTION_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingAction {
+    pub tool: String,
+    pub arguments: Map<String, Value>,
+    pub created_at: DateTime<Local>,
+}
+
+impl PendingAction {
+    pub fn new(tool: &str, arguments: &Map<String, Value>) -> Self {
+        Self {
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            created_at: Local::now(),
+        }
+    }
+
+    fn expired(&self, now: DateTime<Local>) -> bool {
+        (now - self.created_at)
+            .to_std()
+            .is_ok_and(|age| age > PENDING_ACTION_TTL)
+    }
+
+    /// Reminder added to turns while the action waits.
+    pub fn reminder(&self) -> String {
+        format!(
+            "A `{}` action with arguments {} is staged and waiting for the user's confirmation. \
+             It runs only when the user replies yes; if they ask for changes, call the tool again \
+             with the revised arguments to replace it.",
+            self.tool,
+            Value::Object(self.arguments.clone())
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confirmation {
+    Confirm,
+    Cancel,
+}
+
+const CONFIRM_WORDS: &[&str] = &[
+    "yes", "y", "ok", "okay", "confirm", "approve", "sure", "go ahead", "send it", "do it",
+    "/approve", "/confirm", "是", "是的", "好", "好的", "确认", "可以", "发送", "同意",
+];
+const CANCEL_WORDS: &[&str] = &[
+    "no", "n", "cancel", "deny", "stop", "don't", "/cancel", "/deny", "不", "不要", "取消", "算了",
+];
+
+/// Whether `text` is a bare yes or no, ignoring case and trailing punctuation.
+pub fn parse_confirmation(text: &str) -> Option<Confirmation> {
+    let normalized = text
+        .trim()
+        .trim_end_matches(['.', '!', '。', '！', '~'])
+        .trim()
+        .to_lowercase();
+    if CONFIRM_WORDS.contains(&normalized.as_str()) {
+        Some(Confirmation::Confirm)
+    } else if CANCEL_WORDS.contains(&normalized.as_str()) {
+        Some(Confirmation::Cancel)
+    } else {
+        None
+    }
+}
+
+/// Staged actions by session key; one per session, the latest wins.
+#[derive(Default)]
+pub struct PendingActions {
+    actions: Mutex<HashMap<String, PendingAction>>,
+}
+
+impl PendingActions {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, PendingAction>> {
+        self.actions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn stage(&self, session_key: &str, action: PendingAction) {
+        self.lock().insert(session_key.to_string(), action);
+    }
+
+    /// The action waiting in `session_key`, dropping it once expired.
+    pub fn get(&self, session_key: &str) -> Option<PendingAction> {
+        let mut actions = self.lock();
+        if actions
+            .get(session_key)
+            .is_some_and(|action| action.expired(Local::now()))
+        {
+            actions.remove(session_key);
+        }
+        actions.get(session_key).cloned()
+    }
+
+    pub fn take(&self, session_key: &str) -> Option<PendingAction> {
+        self.lock()
+            .remove(session_key)
+            .filter(|action| !action.expired(Local::now()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirmations_and_staged_actions() {
+        assert_eq!(parse_confirmation("Yes!"), Some(Confirmation::Confirm));
+        assert_eq!(parse_confirmation(" 确认。"), Some(Confirmation::Confirm));
+        assert_eq!(parse_confirmation("/cancel"), Some(Confirmation::Cancel));
+        assert_eq!(parse_confirmation("yes, but shorter"), None);
+
+        let pending = PendingActions::default();
+        pending.stage("telegram:1", PendingAction::new("send_email", &Map::new()));
+        assert_eq!(
+            pending.get("telegram:1").map(|action| action.tool),
+            Some("send_email".to_string())
+        );
+        assert!(pending.get("telegram:2").is_none());
+        assert!(pending.take("telegram:1").is_some());
+        assert!(pending.take("telegram:1").is_none());
+
+        let mut stale = PendingAction::new("exec", &Map::new());
+        stale.created_at = Local::now() - chrono::Duration::hours(2);
+        pending.stage("telegram:1", stale);
+        assert!(pending.get("telegram:1").is_none());
+    }
+}
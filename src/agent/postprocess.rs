@@ -0,0 +1,281 @@
+//! Output transforms applied to replies just before they are sent.
+//!
+//! A chain is picked per reply — the API profile's, else the channel's, else
+//! the default — and its steps run in order. `translate` needs the model and
+//! is run by the agent loop; the other steps are plain text rewrites.
+
+use crate::config::{AgentProfile, PostProcessConfig, PostProcessStep};
+use regex::{Captures, Regex};
+use std::sync::LazyLock;
+use tracing::warn;
+
+pub const KINDS: &[&str] = &[
+    "strip_reasoning",
+    "max_length",
+    "signature",
+    "units",
+    "translate",
+];
+
+static REASONING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<(?:think|thinking|reasoning)>.*?</(?:think|thinking|reasoning)>")
+        .expect("reasoning pattern is valid")
+});
+
+/// A unit the `units` step converts from, and what into.
+struct Conversion {
+    /// Alternatives in the pattern, longest first.
+    names: &'static str,
+    to: &'static str,
+    convert: fn(f64) -> f64,
+}
+
+const TO_METRIC: &[Conversion] = &[
+    Conversion {
+        names: "°F|℉|degrees Fahrenheit",
+        to: "°C",
+        convert: |f| (f - 32.0) * 5.0 / 9.0,
+    },
+    Conversion {
+        names: "miles|mile|mi",
+        to: "km",
+        convert: |v| v * 1.609_344,
+    },
+    Conversion {
+        names: "mph",
+        to: "km/h",
+        convert: |v| v * 1.609_344,
+    },
+    Conversion {
+        names: "pounds|pound|lbs|lb",
+        to: "kg",
+        convert: |v| v * 0.453_592_37,
+    },
+    Conversion {
+        names: "ounces|ounce|oz",
+        to: "g",
+        convert: |v| v * 28.349_523,
+    },
+    Conversion {
+        names: "feet|foot|ft",
+        to: "m",
+        convert: |v| v * 0.3048,
+    },
+    Conversion {
+        names: "inches|inch",
+        to: "cm",
+        convert: |v| v * 2.54,
+    },
+    Conversion {
+        names: "gallons|gallon|gal",
+        to: "L",
+        convert: |v| v * 3.785_411_784,
+    },
+];
+
+const TO_IMPERIAL: &[Conversion] = &[
+    Conversion {
+        names: "°C|℃|degrees Celsius",
+        to: "°F",
+        convert: |c| c * 9.0 / 5.0 + 32.0,
+    },
+    Conversion {
+        names: "km/h|kph",
+        to: "mph",
+        convert: |v| v / 1.609_344,
+    },
+    Conversion {
+        names: "kilometres|kilometers|kilometre|kilometer|km",
+        to: "mi",
+        convert: |v| v / 1.609_344,
+    },
+    Conversion {
+        names: "kilograms|kilogram|kg",
+        to: "lb",
+        convert: |v| v / 0.453_592_37,
+    },
+    Conversion {
+        names: "grams|gram",
+        to: "oz",
+        convert: |v| v / 28.349_523,
+    },
+    Conversion {
+        names: "metres|meters|metre|meter",
+        to: "ft",
+        convert: |v| v / 0.3048,
+    },
+    Conversion {
+        names: "centimetres|centimeters|centimetre|centimeter|cm",
+        to: "in",
+        convert: |v| v / 2.54,
+    },
+    Conversion {
+        names: "litres|liters|litre|liter",
+        to: "gal",
+        convert: |v| v / 3.785_411_784,
+    },
+];
+
+static METRIC_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| patterns(TO_METRIC));
+static IMPERIAL_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| patterns(TO_IMPERIAL));
+
+fn patterns(conversions: &[Conversion]) -> Vec<Regex> {
+    conversions
+        .iter()
+        .map(|c| {
+            Regex::new(&format!(r"(?i)(-?\d+(?:\.\d+)?)\s?(?:{})(?:\b|$)", c.names))
+                .expect("unit pattern is valid")
+        })
+        .collect()
+}
+
+/// The chain for a reply in `channel` to a session of `profile`.
+pub fn chain<'a>(
+    config: &'a PostProcessConfig,
+    profile: Option<&'a AgentProfile>,
+    channel: &str,
+) -> &'a [PostProcessStep] {
+    profile
+        .and_then(|profile| profile.post_process.as_deref())
+        .or_else(|| config.channels.get(channel).map(Vec::as_slice))
+        .unwrap_or(&config.default)
+}
+
+/// Warns about steps of an unknown type; they are skipped.
+pub fn validate(steps: &[PostProcessStep]) {
+    for step in steps {
+        if !KINDS.contains(&step.kind.as_str()) {
+            warn!("ignoring unknown post-process step '{}'", step.kind);
+        }
+    }
+}
+
+/// Applies one text step; `translate` and unknown steps return `text` as is.
+pub fn apply(step: &PostProcessStep, text: String) -> String {
+    match step.kind.as_str() {
+        "strip_reasoning" => strip_reasoning(&text),
+        "max_length" if step.max_chars > 0 => truncate(text, step.max_chars),
+        "signature" if !step.text.trim().is_empty() => {
+            format!("{}\n\n{}", text.trim_end(), step.text.trim())
+        }
+        "units" => convert_units(&text, &step.system),
+        _ => text,
+    }
+}
+
+pub fn strip_reasoning(text: &str) -> String {
+    REASONING.replace_all(text, "").trim().to_string()
+}
+
+/// Cuts `text` to `max_chars` characters, at a word boundary when one is near.
+pub fn truncate(text: String, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let keep = max_chars.saturating_sub(1);
+    let mut cut: String = text.chars().take(keep).collect();
+    if let Some(space) = cut.rfind(char::is_whitespace)
+        && cut[..space].chars().count() >= keep * 4 / 5
+    {
+        cut.truncate(space);
+    }
+    format!("{}…", cut.trim_end())
+}
+
+/// Rewrites quantities into `system` (`metric` or `imperial`).
+pub fn convert_units(text: &str, system: &str) -> String {
+    let (conversions, patterns) = match system {
+        "metric" => (TO_METRIC, &*METRIC_PATTERNS),
+        "imperial" => (TO_IMPERIAL, &*IMPERIAL_PATTERNS),
+        other => {
+            warn!("unknown unit system '{other}'; expected metric or imperial");
+            return text.to_string();
+        }
+    };
+    let mut text = text.to_string();
+    for (conversion, pattern) in conversions.iter().zip(patterns) {
+        text = pattern
+            .replace_all(&text, |caps: &Captures| {
+                let value: f64 = caps[1].parse().unwrap_or_default();
+                let value = format_number((conversion.convert)(value));
+                if conversion.to.starts_with('°') {
+                    format!("{value}{}", conversion.to)
+                } else {
+                    format!("{value} {}", conversion.to)
+                }
+            })
+            .into_owned();
+    }
+    text
+}
+
+fn format_number(value: f64) -> String {
+    let rounded = format!("{value:.1}");
+    rounded
+        .strip_suffix(".0")
+        .map(str::to_string)
+        .unwrap_or(rounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn step(kind: &str) -> PostProcessStep {
+        PostProcessStep {
+            kind: kind.to_string(),
+            ..PostProcessStep::default()
+        }
+    }
+
+    #[test]
+    fn steps_rewrite_replies_and_chains_are_picked_per_profile_then_channel() {
+        assert_eq!(
+            apply(
+                &step("strip_reasoning"),
+                "<think>The user wants…</think>\nIt is 5 pm.".to_string()
+            ),
+            "It is 5 pm."
+        );
+        let max = PostProcessStep {
+            max_chars: 20,
+            ..step("max_length")
+        };
+        assert_eq!(
+            apply(&max, "The forecast for tomorrow is sunny".to_string()),
+            "The forecast for…"
+        );
+        assert_eq!(apply(&max, "Short".to_string()), "Short");
+        let signature = PostProcessStep {
+            text: "— nanobot".to_string(),
+            ..step("signature")
+        };
+        assert_eq!(apply(&signature, "Hi\n".to_string()), "Hi\n\n— nanobot");
+        assert_eq!(
+            convert_units("It is 68°F and 3 miles away, 12 lbs.", "metric"),
+            "It is 20°C and 4.8 km away, 5.4 kg."
+        );
+        assert_eq!(
+            convert_units("Run 5 km at 20°C in the mind, 50 km/h", "imperial"),
+            "Run 3.1 mi at 68°F in the mind, 31.1 mph"
+        );
+
+        let sms = vec![max.clone()];
+        let config = PostProcessConfig {
+            default: vec![step("strip_reasoning")],
+            channels: HashMap::from([("sms".to_string(), sms.clone())]),
+        };
+        let kids = AgentProfile {
+            post_process: Some(vec![signature.clone()]),
+            ..AgentProfile::default()
+        };
+        assert_eq!(chain(&config, None, "sms"), sms.as_slice());
+        assert_eq!(chain(&config, None, "telegram"), config.default.as_slice());
+        assert_eq!(chain(&config, Some(&kids), "sms"), [signature].as_slice());
+        assert_eq!(
+            chain(&config, Some(&AgentProfile::default()), "sms"),
+            sms.as_slice()
+        );
+    }
+}
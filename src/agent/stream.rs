@@ -0,0 +1,156 @@
+//! Live replies on chat channels (`channels.streaming`).
+//!
+//! While a turn runs, its text deltas are collected and published as
+//! outbound updates at most once per interval; the channel edits one message
+//! with each (see `channels::live`). Text written before a tool call is
+//! replaced by the text that follows it.
+
+use crate::agent::events::{AgentEvent, EventSender};
+use crate::bus::{MessageBus, OutboundMessage};
+use crate::channels::live::{LiveUpdate, STREAM_KEY};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::debug;
+
+pub struct LiveReply {
+    id: String,
+    tx: EventSender,
+    task: JoinHandle<bool>,
+}
+
+impl LiveReply {
+    /// Starts publishing the partial answer for `channel`/`chat_id`.
+    pub fn start(
+        bus: Arc<MessageBus>,
+        channel: &str,
+        chat_id: &str,
+        metadata: &Map<String, Value>,
+        interval: Duration,
+    ) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut template = OutboundMessage::new(channel, chat_id, "");
+        template.metadata = metadata.clone();
+        template.metadata.insert(
+            STREAM_KEY.to_string(),
+            LiveUpdate {
+                id: id.clone(),
+                done: false,
+            }
+            .to_metadata(),
+        );
+        let task = tokio::spawn(async move {
+            let mut text = String::new();
+            let mut shown = String::new();
+            let mut restart = false;
+            let mut next = Instant::now();
+            loop {
+                // `None`: the interval passed with text still to show.
+                let event = if text != shown {
+                    match tokio::time::timeout_at(next, rx.recv()).await {
+                        Ok(Some(event)) => Some(event),
+                        Ok(None) => break,
+                        Err(_) => None,
+                    }
+                } else {
+                    match rx.recv().await {
+                        Some(event) => Some(event),
+                        None => break,
+                    }
+                };
+                match event {
+                    Some(AgentEvent::Delta { text: delta }) => {
+                        if restart {
+                            text.clear();
+                            restart = false;
+                        }
+                        text.push_str(&delta);
+                    }
+                    Some(AgentEvent::ToolStart { .. }) => restart = true,
+                    _ => {}
+                }
+                if text == shown || text.trim().is_empty() || Instant::now() < next {
+                    continue;
+                }
+                let mut update = template.clone();
+                update.content = text.clone();
+                if let Err(err) = bus.publish_outbound(update).await {
+                    debug!("failed to publish live reply: {err}");
+                    break;
+                }
+                shown = text.clone();
+                next = Instant::now() + interval;
+            }
+            !shown.is_empty()
+        });
+        Self { id, tx, task }
+    }
+
+    /// Receives the turn's events; pass to `events::with_events`.
+    pub fn sender(&self) -> EventSender {
+        self.tx.clone()
+    }
+
+    /// Stops the updates and, when any was shown, marks `response` as the
+    /// final state of the live message.
+    pub async fn finish(self, response: &mut OutboundMessage) {
+        let Self { id, tx, task } = self;
+        drop(tx);
+        if task.await.unwrap_or(false) {
+            response.metadata.insert(
+                STREAM_KEY.to_string(),
+                LiveUpdate { id, done: true }.to_metadata(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn throttles_updates_and_marks_the_final_reply() {
+        let bus = Arc::new(MessageBus::new(16));
+        let live = LiveReply::start(
+            bus.clone(),
+            "telegram",
+            "42",
+            &Map::new(),
+            Duration::from_secs(60),
+        );
+        let tx = live.sender();
+        for delta in ["Let me ", "check."] {
+            tx.send(AgentEvent::Delta {
+                text: delta.to_string(),
+            })
+            .unwrap();
+        }
+        tx.send(AgentEvent::ToolStart {
+            name: "web_search".to_string(),
+            arguments: "{}".to_string(),
+        })
+        .unwrap();
+        tx.send(AgentEvent::Delta {
+            text: "It is sunny.".to_string(),
+        })
+        .unwrap();
+        drop(tx);
+
+        let mut response = OutboundMessage::new("telegram", "42", "It is sunny.");
+        live.finish(&mut response).await;
+
+        let first = bus.consume_outbound().await.unwrap();
+        assert_eq!(first.content, "Let me ");
+        let update = LiveUpdate::from_metadata(&first.metadata).unwrap();
+        assert!(!update.done);
+        assert_eq!(bus.outbound_size(), 0);
+        let last = LiveUpdate::from_metadata(&response.metadata).unwrap();
+        assert_eq!(last.id, update.id);
+        assert!(last.done);
+    }
+}
@@ -3,6 +3,7 @@ use crate::config::WebSearchConfig;
 use crate::providers::base::LLMProvider;
 use crate::tools::filesystem::{EditFileTool, ListDirTool, ReadFileTool, WriteFileTool};
 use crate::tools::http::HttpRequestTool;
+use crate::tools::pages::DEFAULT_PAGE_CHARS;
 use crate::tools::registry::ToolRegistry;
 use crate::tools::shell::ExecTool;
 use crate::tools::web::{WebFetchTool, WebSearchTool};
@@ -131,6 +132,7 @@ impl SubagentManager {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_subagent(
     provider: Arc<dyn LLMProvider>,
     workspace: PathBuf,
@@ -143,6 +145,7 @@ async fn run_subagent(
     _label: String,
 ) -> anyhow::Result<String> {
     let mut tools = ToolRegistry::new();
+    tools.enable_paging(DEFAULT_PAGE_CHARS);
     let allowed_dir = if restrict_to_workspace {
         Some(workspace.clone())
     } else {
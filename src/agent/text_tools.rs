@@ -0,0 +1,215 @@
+//! Tool calling for models without native function calling.
+//!
+//! Tools are described in the system prompt and the model asks for one by
+//! replying with
+//!
+//! ```text
+//! TOOL: read_file
+//! {"path": "notes.md"}
+//! ```
+//!
+//! or with a JSON object `{"tool": "read_file", "arguments": {...}}`. Earlier
+//! tool calls and results in the conversation are rewritten as plain
+//! assistant and user messages, which every chat endpoint accepts.
+
+use crate::providers::base::ToolCallRequest;
+use serde_json::{Map, Value, json};
+
+const MARKER: &str = "TOOL:";
+
+/// System prompt section explaining the protocol and listing `tool_defs`.
+pub fn describe_tools(tool_defs: &[Value]) -> String {
+    let listing = tool_defs
+        .iter()
+        .filter_map(|def| {
+            let function = def.get("function")?;
+            let name = function.get("name")?.as_str()?;
+            let description = function
+                .get("description")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let parameters = function
+                .get("parameters")
+                .map(Value::to_string)
+                .unwrap_or_else(|| "{}".to_string());
+            Some(format!(
+                "- {name}: {description}\n  parameters: {parameters}"
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "## Tools\nTo use a tool, reply with exactly these two lines and nothing after them:\n{MARKER} <tool name>\n{{\"argument\": \"value\"}}\n\nThe result comes back in the next message. Use one tool at a time. When you can answer, reply normally without {MARKER}\n\n{listing}"
+    )
+}
+
+/// Rewrites native tool-call turns as text: assistant calls become `TOOL:`
+/// blocks and `tool` results become user messages.
+pub fn to_text_protocol(messages: &[Value]) -> Vec<Value> {
+    messages
+        .iter()
+        .map(
+            |message| match message.get("role").and_then(Value::as_str) {
+                Some("assistant") if message.get("tool_calls").is_some() => {
+                    let mut text = message
+                        .get("content")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string();
+                    for call in message["tool_calls"].as_array().into_iter().flatten() {
+                        let function = &call["function"];
+                        let name = function["name"].as_str().unwrap_or_default();
+                        let arguments = function["arguments"].as_str().unwrap_or("{}");
+                        if !text.is_empty() {
+                            text.push('\n');
+                        }
+                        text.push_str(&format!("{MARKER} {name}\n{arguments}"));
+                    }
+                    json!({ "role": "assistant", "content": text })
+                }
+                Some("tool") => {
+                    let name = message["name"].as_str().unwrap_or("tool");
+                    let result = message["content"].as_str().unwrap_or_default();
+                    json!({ "role": "user", "content": format!("TOOL RESULT ({name}):\n{result}") })
+                }
+                _ => message.clone(),
+            },
+        )
+        .collect()
+}
+
+/// Pulls tool requests for `known` tools out of a completion. Returns the text
+/// before the first request and the calls, in order.
+pub fn parse_tool_calls(content: &str, known: &[&str]) -> (String, Vec<ToolCallRequest>) {
+    let mut calls = Vec::new();
+    let mut prefix_end = content.len();
+    let mut rest = content;
+    let mut offset = 0;
+    while let Some(pos) = rest.find(MARKER) {
+        let at_line_start = pos == 0 || rest[..pos].ends_with('\n');
+        let after = &rest[pos + MARKER.len()..];
+        let line_end = after.find('\n').unwrap_or(after.len());
+        let name = after[..line_end].trim().trim_matches('`').trim();
+        if at_line_start && known.contains(&name) {
+            prefix_end = prefix_end.min(offset + pos);
+            let body = &after[line_end..];
+            let (arguments, used) = first_object(body).unwrap_or_default();
+            calls.push(call(name, arguments));
+            let consumed = pos + MARKER.len() + line_end + used;
+            offset += consumed;
+            rest = &rest[consumed..];
+        } else {
+            offset += pos + MARKER.len();
+            rest = after;
+        }
+    }
+
+    if calls.is_empty()
+        && let Some(start) = content.find('{')
+        && let Some((object, _)) = first_object(&content[start..])
+        && let Some(name) = object
+            .get("tool")
+            .or_else(|| object.get("name"))
+            .and_then(Value::as_str)
+        && known.contains(&name)
+    {
+        let arguments = object
+            .get("arguments")
+            .or_else(|| object.get("parameters"))
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default();
+        calls.push(call(name, arguments));
+        let before = content[..start].trim_end();
+        let before = before.strip_suffix("json").unwrap_or(before).trim_end();
+        prefix_end = before
+            .strip_suffix("```")
+            .map_or(start, |unfenced| unfenced.len());
+    }
+
+    let prefix = if calls.is_empty() {
+        content
+    } else {
+        &content[..prefix_end]
+    };
+    (prefix.trim().to_string(), calls)
+}
+
+/// The first JSON object at the start of `text` (after whitespace or a code
+/// fence) and how many bytes it spans, fence included.
+fn first_object(text: &str) -> Option<(Map<String, Value>, usize)> {
+    let start = text.find('{')?;
+    let lead = text[..start]
+        .trim()
+        .trim_start_matches("```")
+        .trim_start_matches("json");
+    if !lead.trim().is_empty() {
+        return None;
+    }
+    let mut stream = serde_json::Deserializer::from_str(&text[start..]).into_iter::<Value>();
+    let object = stream.next()?.ok()?.as_object()?.clone();
+    let mut end = start + stream.byte_offset();
+    let tail = &text[end..];
+    if let Some(fence) = tail.trim_start().strip_prefix("```") {
+        end = text.len() - fence.len();
+    }
+    Some((object, end))
+}
+
+fn call(name: &str, arguments: Map<String, Value>) -> ToolCallRequest {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    ToolCallRequest {
+        id: format!("call_{}", &id[..12]),
+        name: name.to_string(),
+        arguments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_marker_and_json_requests_for_known_tools() {
+        let known = ["read_file", "exec"];
+        let (text, calls) = parse_tool_calls(
+            "Let me look.\nTOOL: read_file\n{\"path\": \"a.md\"}\nTOOL: exec\n```json\n{\"command\": \"ls\"}\n```",
+            &known,
+        );
+        assert_eq!(text, "Let me look.");
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].arguments["path"], "a.md");
+        assert_eq!(calls[1].name, "exec");
+
+        let (text, calls) = parse_tool_calls(
+            "```json\n{\"tool\": \"exec\", \"arguments\": {\"command\": \"pwd\"}}\n```",
+            &known,
+        );
+        assert_eq!(text, "");
+        assert_eq!(calls[0].arguments["command"], "pwd");
+
+        let plain = "Use TOOL: hammer to fix it. {\"tool\": \"hammer\"}";
+        let (text, calls) = parse_tool_calls(plain, &known);
+        assert!(calls.is_empty());
+        assert_eq!(text, plain);
+    }
+
+    #[test]
+    fn native_tool_turns_become_text() {
+        let messages = vec![
+            json!({"role": "assistant", "content": "", "tool_calls": [{
+                "id": "call_1", "type": "function",
+                "function": {"name": "exec", "arguments": "{\"command\":\"ls\"}"}
+            }]}),
+            json!({"role": "tool", "tool_call_id": "call_1", "name": "exec", "content": "a.md"}),
+        ];
+        let text = to_text_protocol(&messages);
+        assert_eq!(
+            text[0],
+            json!({"role": "assistant", "content": "TOOL: exec\n{\"command\":\"ls\"}"})
+        );
+        assert_eq!(text[1]["role"], "user");
+        assert_eq!(text[1]["content"], "TOOL RESULT (exec):\na.md");
+    }
+}
@@ -0,0 +1,148 @@
+//! Per-turn tool selection (`tools.selection`).
+//!
+//! With many tools registered, every request pays for all their schemas and
+//! the model picks worse among them. Before the turn, a model ranks the
+//! tools against the message from a one-line catalog and only the top
+//! `topK`, plus `always`, are offered. Selection only narrows what the model
+//! is shown; a tool it calls anyway still runs if the session may use it.
+//! Any failure falls back to offering every tool.
+
+use crate::config::ToolSelectionConfig;
+use crate::providers::base::LLMProvider;
+use serde_json::{Value, json};
+use tracing::{debug, warn};
+
+/// Longest description shown per tool in the catalog.
+const CATALOG_DESCRIPTION_CHARS: usize = 160;
+/// Longest previous answer shown for context.
+const PREVIOUS_ANSWER_CHARS: usize = 500;
+
+/// `name: first sentence of the description`.
+fn catalog_line(def: &Value) -> Option<String> {
+    let function = &def["function"];
+    let name = function["name"].as_str()?;
+    let description = function["description"].as_str().unwrap_or_default();
+    let first = description
+        .split_inclusive(". ")
+        .next()
+        .unwrap_or(description)
+        .trim();
+    let short: String = first.chars().take(CATALOG_DESCRIPTION_CHARS).collect();
+    Some(format!("{name}: {short}"))
+}
+
+/// The names in the first JSON array in `text` that are among `known`.
+pub fn parse_selection(text: &str, known: &[&str], top_k: usize) -> Vec<String> {
+    let (Some(start), Some(end)) = (text.find('['), text.rfind(']')) else {
+        return Vec::new();
+    };
+    let Ok(names) = serde_json::from_str::<Vec<String>>(text.get(start..=end).unwrap_or_default())
+    else {
+        return Vec::new();
+    };
+    let mut picked: Vec<String> = Vec::new();
+    for name in names {
+        let name = name.trim();
+        if known.contains(&name) && !picked.iter().any(|p| p == name) {
+            picked.push(name.to_string());
+        }
+    }
+    picked.truncate(top_k);
+    picked
+}
+
+/// The tools to offer for `message`, or `None` to offer all of `defs`.
+pub async fn select_tools(
+    provider: &dyn LLMProvider,
+    model: &str,
+    config: &ToolSelectionConfig,
+    defs: &[Value],
+    message: &str,
+    previous_answer: Option<&str>,
+) -> Option<Vec<String>> {
+    if !config.enabled || defs.len() <= config.min_tools || config.top_k == 0 {
+        return None;
+    }
+    let known: Vec<&str> = defs
+        .iter()
+        .filter_map(|def| def["function"]["name"].as_str())
+        .collect();
+    let catalog = defs
+        .iter()
+        .filter_map(catalog_line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let previous = previous_answer
+        .map(|answer| {
+            let answer: String = answer.chars().take(PREVIOUS_ANSWER_CHARS).collect();
+            format!("## Previous Answer\n{answer}\n\n")
+        })
+        .unwrap_or_default();
+    let prompt = format!(
+        "Pick the tools most likely needed to handle the user's message, most relevant first, \
+at most {top_k}. Return a JSON array of tool names, e.g. [\"web_search\", \"read_file\"]; \
+return [] if none are needed.\n\n## Tools\n{catalog}\n\n{previous}## Message\n{message}",
+        top_k = config.top_k
+    );
+    let model = match config.model.trim() {
+        "" => model,
+        chosen => chosen,
+    };
+    let response = provider
+        .chat(
+            &[
+                json!({"role": "system", "content": "You select tools. Respond only with a JSON array."}),
+                json!({"role": "user", "content": prompt}),
+            ],
+            None,
+            Some(model),
+            300,
+            0.0,
+        )
+        .await;
+    let content = match response {
+        Ok(response) => response.content.unwrap_or_default(),
+        Err(err) => {
+            warn!("tool selection failed, offering all tools: {err:#}");
+            return None;
+        }
+    };
+    if !content.contains('[') {
+        warn!("tool selection returned no list, offering all tools");
+        return None;
+    }
+    let mut picked = parse_selection(&content, &known, config.top_k);
+    for name in &config.always {
+        if known.contains(&name.as_str()) && !picked.contains(name) {
+            picked.push(name.clone());
+        }
+    }
+    debug!(offered = picked.len(), of = defs.len(), tools = ?picked, "selected tools");
+    Some(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_keeps_known_unique_names_in_order() {
+        let known = ["web_search", "read_file", "exec", "cron"];
+        assert_eq!(
+            parse_selection(
+                "Sure: [\"read_file\", \"made_up\", \"read_file\", \"exec\", \"cron\"]",
+                &known,
+                2
+            ),
+            vec!["read_file".to_string(), "exec".to_string()]
+        );
+        assert!(parse_selection("[]", &known, 5).is_empty());
+        assert!(parse_selection("no idea", &known, 5).is_empty());
+        assert_eq!(
+            catalog_line(
+                &json!({"function": {"name": "exec", "description": "Run a shell command. Output is truncated."}})
+            ),
+            Some("exec: Run a shell command.".to_string())
+        );
+    }
+}
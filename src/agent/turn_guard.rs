@@ -1,3 +1,4 @@
+use crate::i18n::{tr, tr_args};
 use crate::providers::base::LLMProvider;
 use serde_json::{Value, json};
 
@@ -36,13 +37,10 @@ impl<'a> TurnGuard<'a> {
 
     pub fn tools_available_response(&self) -> String {
         if self.tools_text == "(none)" {
-            "当前运行时未注册任何工具。".to_string()
+            tr("tools-none")
         } else {
             let items = self.tools_text.split(", ").collect::<Vec<_>>().join("\n- ");
-            format!(
-                "当前运行时可用工具：\n- {}\n如需执行网络访问、命令执行、文件操作或定时任务，请直接给出目标。",
-                items
-            )
+            tr_args("tools-available", &[("tools", &items)])
         }
     }
 
@@ -0,0 +1,194 @@
+//! Running the project's check command after the agent edits files.
+//!
+//! With `tools.verify.command` set, every tool round that changed a file is
+//! followed by the check (tests, a linter, a build). Its outcome is appended
+//! to the round's last tool result, so a failure reaches the model the same
+//! way any tool error does and it can fix and try again, up to
+//! `maxAttempts` failed checks per turn.
+
+use crate::config::VerifyConfig;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Output kept from a failed check; the end is where the errors usually are.
+const VERIFY_OUTPUT_CHARS: usize = 4_000;
+
+/// Tools whose success means a file in the project changed.
+pub fn is_edit(tool: &str) -> bool {
+    matches!(tool, "write_file" | "edit_file")
+}
+
+pub struct Verifier {
+    command: String,
+    cwd: PathBuf,
+    timeout: Duration,
+    max_attempts: u32,
+}
+
+/// Last `max_chars` characters of `text`.
+fn tail(text: &str, max_chars: usize) -> String {
+    let count = text.chars().count();
+    if count <= max_chars {
+        return text.to_string();
+    }
+    let kept = text.chars().skip(count - max_chars).collect::<String>();
+    format!("…{kept}")
+}
+
+impl Verifier {
+    /// `None` when no check command is configured.
+    pub fn new(config: &VerifyConfig, workspace: &Path) -> Option<Self> {
+        let command = config.command.trim();
+        if command.is_empty() {
+            return None;
+        }
+        Some(Self {
+            command: command.to_string(),
+            cwd: workspace.join(config.cwd.trim()),
+            timeout: Duration::from_secs(config.timeout.max(1)),
+            max_attempts: config.max_attempts.max(1),
+        })
+    }
+
+    /// Runs the check; the output (stdout then stderr) on failure.
+    async fn run(&self) -> Result<(), String> {
+        let mut process = if cfg!(target_os = "windows") {
+            let mut cmd = Command::new("cmd");
+            cmd.args(["/C", &self.command]);
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", &self.command]);
+            cmd
+        };
+        process
+            .current_dir(&self.cwd)
+            .envs(crate::network::proxy_env())
+            .kill_on_drop(true);
+        let output = match tokio::time::timeout(self.timeout, process.output()).await {
+            Ok(Ok(output)) => output,
+            Ok(Err(err)) => return Err(format!("could not run the check: {err}")),
+            Err(_) => {
+                return Err(format!(
+                    "timed out after {} seconds",
+                    self.timeout.as_secs()
+                ));
+            }
+        };
+        if output.status.success() {
+            return Ok(());
+        }
+        let mut text = String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(stderr.trim_end());
+        }
+        text.push_str(&format!(
+            "\nExit code: {}",
+            output.status.code().unwrap_or(-1)
+        ));
+        Err(tail(&text, VERIFY_OUTPUT_CHARS))
+    }
+
+    /// Runs the check after a round that edited files and returns the note
+    /// for the model. `attempts` counts failed checks in this turn; once it
+    /// reaches the cap no further checks run.
+    pub async fn check(&self, attempts: &mut u32) -> Option<String> {
+        if *attempts >= self.max_attempts {
+            return None;
+        }
+        match self.run().await {
+            Ok(()) => {
+                *attempts = 0;
+                Some(format!("[verify] `{}` passed.", self.command))
+            }
+            Err(output) => {
+                *attempts += 1;
+                let next = if *attempts >= self.max_attempts {
+                    format!(
+                        "The check has failed {} times in a row; stop changing files and tell the user what is still failing.",
+                        self.max_attempts
+                    )
+                } else {
+                    "Fix the problem and the check runs again after your next edit.".to_string()
+                };
+                Some(format!(
+                    "[verify] `{}` failed (attempt {} of {}):\n{output}\n{next}",
+                    self.command, *attempts, self.max_attempts
+                ))
+            }
+        }
+    }
+}
+
+/// Appends `note` to the newest tool result in `messages`.
+pub fn append_to_last_tool_result(messages: &mut [Value], note: &str) {
+    let Some(message) = messages
+        .iter_mut()
+        .rev()
+        .find(|m| m.get("role").and_then(Value::as_str) == Some("tool"))
+    else {
+        return;
+    };
+    let content = message
+        .get("content")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    message["content"] = Value::String(format!("{content}\n\n{note}"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn failed_checks_are_reported_until_the_cap() {
+        let workspace = std::env::temp_dir();
+        let config = VerifyConfig {
+            command: "echo broken && exit 3".to_string(),
+            max_attempts: 2,
+            ..VerifyConfig::default()
+        };
+        let verifier = Verifier::new(&config, &workspace).expect("verifier");
+        let mut attempts = 0;
+
+        let first = verifier.check(&mut attempts).await.expect("note");
+        assert!(first.contains("failed (attempt 1 of 2)"));
+        assert!(first.contains("broken"));
+        assert!(first.contains("Exit code: 3"));
+        let second = verifier.check(&mut attempts).await.expect("note");
+        assert!(second.contains("stop changing files"));
+        assert!(verifier.check(&mut attempts).await.is_none());
+
+        let passing = VerifyConfig {
+            command: "echo fine".to_string(),
+            ..VerifyConfig::default()
+        };
+        let mut attempts = 1;
+        let note = Verifier::new(&passing, &workspace)
+            .expect("verifier")
+            .check(&mut attempts)
+            .await;
+        assert_eq!(note.as_deref(), Some("[verify] `echo fine` passed."));
+        assert_eq!(attempts, 0);
+        assert!(Verifier::new(&VerifyConfig::default(), &workspace).is_none());
+
+        let mut messages = vec![
+            json!({"role": "tool", "content": "Successfully edited a.rs"}),
+            json!({"role": "user", "content": "next"}),
+        ];
+        append_to_last_tool_result(&mut messages, "[verify] ok");
+        assert_eq!(
+            messages[0]["content"],
+            "Successfully edited a.rs\n\n[verify] ok"
+        );
+    }
+}
@@ -0,0 +1,502 @@
+//! Scoped API keys for the HTTP endpoints.
+//!
+//! Keys look like `nbk_<id>_<secret>`; only a SHA-256 hash is stored in
+//! `apikeys.json` in the data dir. The store is re-read on every check so keys
+//! created or revoked from the CLI apply to a running service immediately.
+
+use crate::utils::get_data_path;
+use crate::vault::{UserKey, Vault};
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tiny_http::Request;
+
+const KEY_PREFIX: &str = "nbk_";
+const APIKEYS_FILE: &str = "apikeys.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub hash: String,
+    /// Read-only keys may only use GET/HEAD routes.
+    pub read_only: bool,
+    /// Agent profile the key is confined to; its sessions live under `api:<profile>`.
+    pub profile: Option<String>,
+    /// May use the `/admin` routes.
+    #[serde(default)]
+    pub admin: bool,
+    pub created_at: DateTime<Local>,
+    pub expires_at: Option<DateTime<Local>>,
+    pub revoked_at: Option<DateTime<Local>>,
+    /// The profile's data key (`gateway.encryptUserData`), wrapped under
+    /// this key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_key: Option<String>,
+}
+
+impl ApiKey {
+    pub fn status(&self, now: DateTime<Local>) -> &'static str {
+        if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.expires_at.is_some_and(|at| at <= now) {
+            "expired"
+        } else {
+            "active"
+        }
+    }
+
+    /// Session key for a chat made with this key, keeping profile-bound
+    /// keys out of the owner's sessions.
+    pub fn scoped_session(&self, requested: Option<&str>) -> Option<String> {
+        let profile = self.profile.as_deref()?;
+        Some(match requested.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(session) => format!("api:{profile}:{session}"),
+            None => format!("api:{profile}"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    Missing,
+    Invalid,
+    Expired,
+    Revoked,
+    ReadOnly,
+}
+
+impl AuthError {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            AuthError::ReadOnly => 403,
+            _ => 401,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            AuthError::Missing => "API key required (Authorization: Bearer <key>)",
+            AuthError::Invalid => "invalid API key",
+            AuthError::Expired => "API key expired",
+            AuthError::Revoked => "API key revoked",
+            AuthError::ReadOnly => "API key is read-only",
+        };
+        f.write_str(message)
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// `apikeys.json` in the data dir, out of reach of the agent's file tools
+/// under `restrictToWorkspace`. A store earlier versions kept in
+/// `<workspace>` is moved there.
+pub fn apikeys_path(workspace: &Path) -> Result<PathBuf> {
+    let path = get_data_path()?.join(APIKEYS_FILE);
+    let legacy = workspace.join(APIKEYS_FILE);
+    if legacy != path && legacy.exists() {
+        if path.exists() {
+            tracing::warn!(
+                "ignoring {}; API keys live in {}",
+                legacy.display(),
+                path.display()
+            );
+        } else {
+            std::fs::rename(&legacy, &path).with_context(|| {
+                format!("failed to move {} to {}", legacy.display(), path.display())
+            })?;
+        }
+    }
+    Ok(path)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ApiKeyStore {
+    #[serde(skip)]
+    path: PathBuf,
+    pub keys: Vec<ApiKey>,
+}
+
+impl ApiKeyStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut store = if path.exists() {
+            let raw = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            serde_json::from_str::<Self>(&raw)
+                .with_context(|| format!("invalid API key store {}", path.display()))?
+        } else {
+            Self::default()
+        };
+        store.path = path.to_path_buf();
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    /// Issues a key and returns it with the plaintext token, which is not
+    /// recoverable afterwards.
+    pub fn create(
+        &mut self,
+        name: &str,
+        read_only: bool,
+        admin: bool,
+        profile: Option<String>,
+        expires_in: Option<Duration>,
+    ) -> Result<(ApiKey, String)> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("API key name cannot be empty"));
+        }
+        if self.find(name).is_some() {
+            return Err(anyhow!("an API key named '{name}' already exists"));
+        }
+        let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        let secret = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        let token = format!("{KEY_PREFIX}{id}_{secret}");
+        let now = Local::now();
+        let key = ApiKey {
+            id,
+            name: name.to_string(),
+            hash: hash_token(&token),
+            read_only,
+            profile: profile.filter(|p| !p.trim().is_empty()),
+            admin,
+            created_at: now,
+            expires_at: expires_in.map(|ttl| now + ttl),
+            revoked_at: None,
+            data_key: None,
+        };
+        self.keys.push(key.clone());
+        Ok((key, token))
+    }
+
+    fn find(&self, id_or_name: &str) -> Option<usize> {
+        self.keys
+            .iter()
+            .position(|key| key.id == id_or_name || key.name == id_or_name)
+    }
+
+    /// Marks a key revoked; returns false when no key matches.
+    pub fn revoke(&mut self, id_or_name: &str) -> bool {
+        match self.find(id_or_name) {
+            Some(index) => {
+                self.keys[index].revoked_at.get_or_insert_with(Local::now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The key `token` belongs to, whatever its status.
+    fn find_token(&self, token: &str) -> Option<usize> {
+        let id = token
+            .strip_prefix(KEY_PREFIX)
+            .and_then(|rest| rest.split_once('_'))
+            .map(|(id, _)| id)?;
+        let hash = hash_token(token);
+        self.keys
+            .iter()
+            .position(|key| key.id == id && constant_time_eq(&key.hash, &hash))
+    }
+
+    pub fn authenticate(
+        &self,
+        token: &str,
+        now: DateTime<Local>,
+    ) -> std::result::Result<&ApiKey, AuthError> {
+        let key = self
+            .find_token(token)
+            .map(|index| &self.keys[index])
+            .ok_or(AuthError::Invalid)?;
+        match key.status(now) {
+            "revoked" => Err(AuthError::Revoked),
+            "expired" => Err(AuthError::Expired),
+            _ => Ok(key),
+        }
+    }
+
+    /// Whether a key of `profile` holds its data key, i.e. the profile's
+    /// data may already be encrypted.
+    pub fn has_data_key(&self, profile: &str) -> bool {
+        self.keys
+            .iter()
+            .any(|key| key.profile.as_deref() == Some(profile) && key.data_key.is_some())
+    }
+
+    /// The data key of `profile`, unwrapped with `token`. Revoked and
+    /// expired keys still open it, so revoking a key before issuing its
+    /// successor does not lose the data.
+    pub fn data_key(&self, token: &str, profile: &str) -> Result<UserKey> {
+        let key = self
+            .find_token(token)
+            .map(|index| &self.keys[index])
+            .filter(|key| key.profile.as_deref() == Some(profile))
+            .ok_or_else(|| anyhow!("not an API key of profile '{profile}'"))?;
+        let wrapped = key
+            .data_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("API key '{}' holds no data key", key.name))?;
+        UserKey::derive(token, &key.id).unwrap_key(wrapped)
+    }
+
+    /// Wraps `data_key` under the key `token` belongs to.
+    pub fn wrap_data_key(&mut self, token: &str, data_key: &UserKey) -> Result<()> {
+        let index = self
+            .find_token(token)
+            .ok_or_else(|| anyhow!("unknown API key"))?;
+        let key = &mut self.keys[index];
+        key.data_key = Some(UserKey::derive(token, &key.id).wrap(data_key)?);
+        Ok(())
+    }
+
+    /// Gives the key `token` belongs to its profile's data key: the one
+    /// `unlock_with` (another key of the profile) opens, else a new one. A
+    /// profile that already has a data key needs `unlock_with`, since a new
+    /// data key would leave its encrypted data unreadable.
+    pub fn issue_data_key(&mut self, token: &str, unlock_with: Option<&str>) -> Result<UserKey> {
+        let profile = self
+            .find_token(token)
+            .and_then(|index| self.keys[index].profile.clone())
+            .ok_or_else(|| anyhow!("only profile-bound API keys hold a data key"))?;
+        let data_key = match unlock_with {
+            Some(existing) => self.data_key(existing, &profile)?,
+            None if self.has_data_key(&profile) => {
+                return Err(anyhow!(
+                    "profile '{profile}' already has encrypted data; pass one of its keys \
+                     with --unlock-with so the new key can read it"
+                ));
+            }
+            None => UserKey::generate(),
+        };
+        self.wrap_data_key(token, &data_key)?;
+        Ok(data_key)
+    }
+}
+
+/// The bearer token or `X-API-Key` presented with the request.
+pub fn request_token(request: &Request) -> Option<String> {
+    request.headers().iter().find_map(|header| {
+        let value = header.value.as_str().trim();
+        if header.field.equiv("Authorization") {
+            let (scheme, token) = value.split_once(' ')?;
+            scheme
+                .eq_ignore_ascii_case("bearer")
+                .then(|| token.trim().to_string())
+        } else if header.field.equiv("X-API-Key") {
+            Some(value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Request middleware for the HTTP endpoints. When `required` is false,
+/// requests without a key pass unauthenticated, but a presented key is
+/// still validated and its scopes apply.
+#[derive(Debug, Clone)]
+pub struct ApiKeyGuard {
+    path: PathBuf,
+    required: bool,
+    /// Receives the data key of each profile-bound key that authenticates.
+    vault: Option<Arc<Vault>>,
+}
+
+impl ApiKeyGuard {
+    /// Checks keys against the store at `path` (see [`apikeys_path`]).
+    pub fn new(path: PathBuf, required: bool) -> Self {
+        Self {
+            path,
+            required,
+            vault: None,
+        }
+    }
+
+    /// Unlocks the encrypted data of users as they authenticate.
+    pub fn with_vault(mut self, vault: Option<Arc<Vault>>) -> Self {
+        self.vault = vault;
+        self
+    }
+
+    pub fn check(&self, request: &Request) -> std::result::Result<Option<ApiKey>, AuthError> {
+        let Some(token) = request_token(request) else {
+            return if self.required {
+                Err(AuthError::Missing)
+            } else {
+                Ok(None)
+            };
+        };
+        let store = ApiKeyStore::load(&self.path).map_err(|err| {
+            tracing::warn!("{err:#}");
+            AuthError::Invalid
+        })?;
+        let key = store.authenticate(&token, Local::now())?;
+        let method = request.method();
+        let is_read = matches!(method, tiny_http::Method::Get | tiny_http::Method::Head);
+        if key.read_only && !is_read {
+            return Err(AuthError::ReadOnly);
+        }
+        let key = key.clone();
+        if let Some(vault) = &self.vault
+            && let Some(scope) = key.scoped_session(None)
+        {
+            match unlock_data_key(store, vault, &scope, &token, &key) {
+                Ok(data_key) => vault.unlock(&scope, data_key),
+                Err(err) => tracing::warn!("{scope} stays locked: {err:#}"),
+            }
+        }
+        Ok(Some(key))
+    }
+}
+
+/// The data key of `key`'s profile. A key that holds none yet, such as one
+/// issued before encryption was turned on, gets the data key already
+/// unlocked, or a new one when the profile has no encrypted data.
+fn unlock_data_key(
+    mut store: ApiKeyStore,
+    vault: &Vault,
+    scope: &str,
+    token: &str,
+    key: &ApiKey,
+) -> Result<UserKey> {
+    if let Some(profile) = key.profile.as_deref()
+        && key.data_key.is_some()
+    {
+        return store.data_key(token, profile);
+    }
+    let data_key = match vault.key_for(scope) {
+        Ok(Some(data_key)) => {
+            store.wrap_data_key(token, &data_key)?;
+            data_key
+        }
+        _ => store.issue_data_key(token, None)?,
+    };
+    store.save()?;
+    Ok(data_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::{Session, SessionManager};
+
+    #[test]
+    fn create_authenticate_and_revoke() {
+        let dir = std::env::temp_dir().join(format!("nanobot-keys-{}", uuid::Uuid::new_v4()));
+        let path = dir.join(APIKEYS_FILE);
+        let mut store = ApiKeyStore::load(&path).expect("load");
+        let (key, token) = store
+            .create("ci", true, false, Some("research".to_string()), None)
+            .expect("create");
+        assert!(token.starts_with("nbk_"));
+        assert!(!key.hash.contains(&token));
+        assert!(store.create("ci", false, false, None, None).is_err());
+        store.save().expect("save");
+
+        let mut store = ApiKeyStore::load(&path).expect("reload");
+        let now = Local::now();
+        assert_eq!(store.authenticate(&token, now).expect("auth").name, "ci");
+        assert_eq!(
+            store.authenticate("nbk_bogus_x", now).unwrap_err(),
+            AuthError::Invalid
+        );
+        assert_eq!(
+            key.scoped_session(Some("notes")).as_deref(),
+            Some("api:research:notes")
+        );
+
+        assert!(store.revoke(&key.id));
+        assert_eq!(
+            store.authenticate(&token, now).unwrap_err(),
+            AuthError::Revoked
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rotated_keys_read_the_old_sessions() {
+        let dir = std::env::temp_dir().join(format!("nanobot-rotate-{}", uuid::Uuid::new_v4()));
+        let path = dir.join(APIKEYS_FILE);
+        let sessions = |token: &str| {
+            let store = ApiKeyStore::load(&path).expect("load");
+            let key = store
+                .authenticate(token, Local::now())
+                .expect("auth")
+                .clone();
+            let vault = Arc::new(Vault::new());
+            let data_key =
+                unlock_data_key(store, &vault, "api:alice", token, &key).expect("unlock");
+            vault.unlock("api:alice", data_key);
+            SessionManager::with_dir(dir.join("sessions"))
+                .expect("sessions")
+                .with_vault(Some(vault))
+        };
+        let mut store = ApiKeyStore::load(&path).expect("load");
+        let alice = Some("alice".to_string());
+        let (_, old) = store
+            .create("alice-1", false, false, alice.clone(), None)
+            .expect("create");
+        store.save().expect("save");
+        let mut session = Session::new("api:alice:notes");
+        session.add_message("user", "my diary");
+        sessions(&old).save(&session).expect("save");
+
+        let mut store = ApiKeyStore::load(&path).expect("reload");
+        let (_, new) = store
+            .create("alice-2", false, false, alice, None)
+            .expect("create");
+        assert!(store.issue_data_key(&new, None).is_err());
+        store.issue_data_key(&new, Some(&old)).expect("rewrap");
+        assert!(store.revoke("alice-1"));
+        store.save().expect("save");
+
+        let session = sessions(&new)
+            .load_session("api:alice:notes")
+            .expect("load");
+        assert_eq!(session.messages[0]["content"], "my diary");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn expired_keys_are_rejected() {
+        let mut store = ApiKeyStore::default();
+        let (_, token) = store
+            .create("temp", false, false, None, Some(Duration::hours(1)))
+            .expect("create");
+        assert!(store.authenticate(&token, Local::now()).is_ok());
+        let later = Local::now() + Duration::hours(2);
+        assert_eq!(
+            store.authenticate(&token, later).unwrap_err(),
+            AuthError::Expired
+        );
+    }
+}
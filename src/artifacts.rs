@@ -0,0 +1,94 @@
+//! Files the model produced for the user, kept in `workspace/artifacts/`.
+
+use anyhow::{Context, Result, anyhow};
+use base64::Engine;
+use chrono::Local;
+use std::path::{Path, PathBuf};
+
+pub fn artifacts_dir(workspace: &Path) -> PathBuf {
+    workspace.join("artifacts")
+}
+
+fn extension_for(mime: &str) -> &'static str {
+    match mime.split(';').next().unwrap_or_default().trim() {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        "image/svg+xml" => "svg",
+        _ => "png",
+    }
+}
+
+/// Decodes a `data:<mime>;base64,<data>` URL.
+fn decode_data_url(url: &str) -> Result<(Vec<u8>, &'static str)> {
+    let rest = url
+        .strip_prefix("data:")
+        .ok_or_else(|| anyhow!("not a data URL"))?;
+    let (meta, data) = rest
+        .split_once(',')
+        .ok_or_else(|| anyhow!("malformed data URL"))?;
+    if !meta.ends_with(";base64") {
+        return Err(anyhow!("data URL is not base64"));
+    }
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.trim())
+        .context("invalid base64 in data URL")?;
+    Ok((bytes, extension_for(meta)))
+}
+
+/// Writes a generated image (a `data:` or `http(s)` URL) to the artifacts
+/// directory and returns its path.
+pub async fn save_image(workspace: &Path, url: &str) -> Result<PathBuf> {
+    let (bytes, ext) = if url.starts_with("data:") {
+        decode_data_url(url)?
+    } else {
+        let response = reqwest::get(url)
+            .await
+            .with_context(|| format!("failed to download {url}"))?
+            .error_for_status()?;
+        let ext = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map_or("png", extension_for);
+        (response.bytes().await?.to_vec(), ext)
+    };
+    let dir = artifacts_dir(workspace);
+    tokio::fs::create_dir_all(&dir).await?;
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let path = dir.join(format!(
+        "image-{}-{}.{ext}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        &id[..8]
+    ));
+    tokio::fs::write(&path, bytes)
+        .await
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn data_urls_are_saved_with_their_type() {
+        let workspace =
+            std::env::temp_dir().join(format!("nanobot-artifacts-{}", uuid::Uuid::new_v4()));
+        let path = save_image(&workspace, "data:image/jpeg;base64,/9j/4AAQ")
+            .await
+            .expect("saved");
+        assert_eq!(path.extension().and_then(|e| e.to_str()), Some("jpg"));
+        assert!(path.starts_with(artifacts_dir(&workspace)));
+        assert_eq!(
+            std::fs::read(&path).expect("read"),
+            [0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10]
+        );
+        assert!(
+            save_image(&workspace, "data:text/plain,hello")
+                .await
+                .is_err()
+        );
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::utils::get_home_data_path;
+use anyhow::{Context, Result, anyhow, bail};
+use sha2::{Digest, Sha256};
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::warn;
+
+pub const DEFAULT_MAX_ATTACHMENT_BYTES: usize = 256 * 1024;
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+/// Largest image downloaded for an image URL.
+const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+const IMAGE_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub label: String,
+    pub content: String,
+    pub truncated: bool,
+    pub original_bytes: usize,
+}
+
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => false,
+        // A multi-byte character cut at the sniff boundary is still text.
+        Err(err) => err.error_len().is_some(),
+    }
+}
+
+fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while end > 0 && !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+pub fn attachment_from_bytes(
+    label: impl Into<String>,
+    bytes: &[u8],
+    max_bytes: usize,
+) -> Result<Attachment> {
+    let label = label.into();
+    if looks_binary(bytes) {
+        return Err(anyhow!("refusing to attach {label}: content looks binary"));
+    }
+    let text = String::from_utf8_lossy(bytes);
+    let kept = truncate_at_char_boundary(&text, max_bytes);
+    Ok(Attachment {
+        truncated: kept.len() < text.len(),
+        content: kept.to_string(),
+        original_bytes: bytes.len(),
+        label,
+    })
+}
+
+pub fn load_file_attachment(path: &Path, max_bytes: usize) -> Result<Attachment> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("failed to read attachment {}", path.display()))?;
+    attachment_from_bytes(path.display().to_string(), &bytes, max_bytes)
+}
+
+/// Reads piped stdin as an attachment. Returns `None` when stdin is a
+/// terminal or the pipe is empty.
+pub fn read_stdin_attachment(max_bytes: usize) -> Result<Option<Attachment>> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    stdin
+        .lock()
+        .read_to_end(&mut bytes)
+        .context("failed to read stdin")?;
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    attachment_from_bytes("stdin", &bytes, max_bytes).map(Some)
+}
+
+pub fn format_with_attachments(message: &str, attachments: &[Attachment]) -> String {
+    if attachments.is_empty() {
+        return message.to_string();
+    }
+    let mut out = String::from(message.trim_end());
+    out.push_str("\n\n## Attached context\n");
+    for attachment in attachments {
+        out.push_str(&format!("\n### {}\n", attachment.label));
+        if attachment.truncated {
+            out.push_str(&format!(
+                "(truncated to {} of {} bytes)\n",
+                attachment.content.len(),
+                attachment.original_bytes
+            ));
+        }
+        let fence = if attachment.content.contains("```") {
+            "~~~~"
+        } else {
+            "```"
+        };
+        out.push_str(fence);
+        out.push('\n');
+        out.push_str(attachment.content.trim_end());
+        out.push('\n');
+        out.push_str(fence);
+        out.push('\n');
+    }
+    out
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+fn image_mime(path: &Path) -> Option<&'static str> {
+    mime_guess::from_path(path)
+        .first_raw()
+        .filter(|mime| mime.starts_with("image/"))
+}
+
+/// Checks an image given with `--image`: an http(s) URL, or an existing
+/// file with an image extension.
+pub fn check_image_source(source: &str) -> Result<()> {
+    if is_url(source) {
+        return Ok(());
+    }
+    let path = Path::new(source);
+    if !path.is_file() {
+        bail!("image not found: {source}");
+    }
+    if image_mime(path).is_none() {
+        bail!("not an image file: {source}");
+    }
+    Ok(())
+}
+
+/// `sources` as local image files: paths are kept, http(s) URLs are
+/// downloaded to `~/.nanobot/media` like channel media. Images that cannot
+/// be fetched are left out with a warning.
+pub async fn localize_images(sources: &[String]) -> Vec<String> {
+    let mut local = Vec::new();
+    for source in sources {
+        if !is_url(source) {
+            local.push(source.clone());
+            continue;
+        }
+        match download_image(source).await {
+            Ok(path) => local.push(path.display().to_string()),
+            Err(err) => warn!("skipping image {source}: {err:#}"),
+        }
+    }
+    local
+}
+
+async fn download_image(url: &str) -> Result<PathBuf> {
+    let client = crate::network::client_builder()
+        .timeout(IMAGE_FETCH_TIMEOUT)
+        .build()?;
+    let response = client.get(url).send().await?.error_for_status()?;
+    let mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        })
+        .filter(|mime| mime.starts_with("image/"))
+        .or_else(|| {
+            image_mime(Path::new(url.split(['?', '#']).next().unwrap_or(url))).map(str::to_string)
+        })
+        .ok_or_else(|| anyhow!("not an image"))?;
+    let bytes = response.bytes().await?;
+    if bytes.len() > MAX_IMAGE_BYTES {
+        bail!(
+            "image is larger than {} MB",
+            MAX_IMAGE_BYTES / (1024 * 1024)
+        );
+    }
+    let ext = match mime.as_str() {
+        "image/jpeg" => "jpg",
+        other => mime_guess::get_mime_extensions_str(other)
+            .and_then(|exts| exts.first())
+            .copied()
+            .unwrap_or("img"),
+    };
+    let name = Sha256::digest(url.as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    let dir = get_home_data_path()?.join("media");
+    tokio::fs::create_dir_all(&dir).await?;
+    let path = dir.join(format!("{name}.{ext}"));
+    tokio::fs::write(&path, &bytes).await?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_detection_flags_nul_and_invalid_utf8() {
+        assert!(looks_binary(b"abc\0def"));
+        assert!(looks_binary(&[0xff, 0xfe, 0x41]));
+        assert!(!looks_binary("plain text, 你好".as_bytes()));
+    }
+
+    #[test]
+    fn attachment_truncates_on_char_boundary() {
+        let text = "你好世界";
+        let attachment = attachment_from_bytes("note", text.as_bytes(), 7).expect("attachment");
+        assert!(attachment.truncated);
+        assert_eq!(attachment.content, "你好");
+        assert_eq!(attachment.original_bytes, text.len());
+    }
+
+    #[test]
+    fn attachment_rejects_binary() {
+        let err = attachment_from_bytes("blob.bin", b"\0\x01\x02", 1024).expect_err("binary");
+        assert!(err.to_string().contains("looks binary"));
+    }
+
+    #[test]
+    fn format_with_attachments_appends_fenced_blocks() {
+        let attachment =
+            attachment_from_bytes("src/lib.rs", b"pub mod agent;", 1024).expect("attachment");
+        let text = format_with_attachments("review this", &[attachment]);
+        assert!(text.starts_with("review this\n\n## Attached context"));
+        assert!(text.contains("### src/lib.rs\n```\npub mod agent;\n```"));
+        assert_eq!(format_with_attachments("hi", &[]), "hi");
+    }
+}
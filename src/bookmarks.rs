@@ -0,0 +1,176 @@
+//! The user's read-later list, `<workspace>/bookmarks.json`.
+//!
+//! `save_link` fetches the page for its title and opening paragraph, so the
+//! list reads well without opening every link; `mark_link_read` moves a link
+//! out of the unread pile the morning briefing reports on.
+
+use crate::tools::web::canonical_url;
+use crate::utils::with_path_lock;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bookmark {
+    pub url: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub saved_at: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_at: Option<DateTime<Local>>,
+}
+
+impl Bookmark {
+    pub fn is_unread(&self) -> bool {
+        self.read_at.is_none()
+    }
+
+    /// `[ ] Title <url> #tag`, followed by the summary on its own line.
+    pub fn line(&self) -> String {
+        let mark = if self.is_unread() { " " } else { "x" };
+        let title = if self.title.is_empty() {
+            &self.url
+        } else {
+            &self.title
+        };
+        let mut line = format!("[{mark}] {title} <{}>", self.url);
+        for tag in &self.tags {
+            line.push_str(&format!(" #{tag}"));
+        }
+        if !self.summary.is_empty() {
+            line.push_str(&format!("\n    {}", self.summary));
+        }
+        line
+    }
+}
+
+pub struct BookmarkStore {
+    path: PathBuf,
+}
+
+impl BookmarkStore {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            path: workspace.join("bookmarks.json"),
+        }
+    }
+
+    /// Every bookmark, newest first.
+    pub fn list(&self) -> Vec<Bookmark> {
+        let mut bookmarks = std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Vec<Bookmark>>(&text).ok())
+            .unwrap_or_default();
+        bookmarks.sort_by_key(|bookmark| std::cmp::Reverse(bookmark.saved_at));
+        bookmarks
+    }
+
+    pub fn unread(&self) -> Vec<Bookmark> {
+        self.list()
+            .into_iter()
+            .filter(Bookmark::is_unread)
+            .collect()
+    }
+
+    fn write(&self, bookmarks: &[Bookmark]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(bookmarks)?)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+
+    /// Saves `bookmark`; a link saved before is updated instead, keeping its
+    /// tags and marked unread again.
+    pub fn save(&self, mut bookmark: Bookmark) -> Result<Bookmark> {
+        with_path_lock(&self.path, || {
+            let mut bookmarks = self.list();
+            let key = canonical_url(&bookmark.url);
+            if let Some(existing) = bookmarks
+                .iter()
+                .position(|saved| canonical_url(&saved.url) == key)
+            {
+                let previous = bookmarks.remove(existing);
+                for tag in previous.tags {
+                    if !bookmark.tags.contains(&tag) {
+                        bookmark.tags.push(tag);
+                    }
+                }
+            }
+            bookmarks.push(bookmark.clone());
+            self.write(&bookmarks)?;
+            Ok(bookmark)
+        })
+    }
+
+    pub fn mark_read(&self, url: &str) -> Result<Bookmark> {
+        with_path_lock(&self.path, || {
+            let mut bookmarks = self.list();
+            let key = canonical_url(url);
+            let bookmark = bookmarks
+                .iter_mut()
+                .find(|saved| canonical_url(&saved.url) == key)
+                .ok_or_else(|| anyhow!("no saved link for {url}"))?;
+            bookmark.read_at = Some(Local::now());
+            let bookmark = bookmark.clone();
+            self.write(&bookmarks)?;
+            Ok(bookmark)
+        })
+    }
+}
+
+/// Tags as given by the user: lowercase, without `#`, no duplicates.
+pub fn normalize_tags<'a>(tags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_a_link_again_updates_it() {
+        let dir = std::env::temp_dir().join(format!("nanobot-bookmarks-{}", uuid::Uuid::new_v4()));
+        let store = BookmarkStore::new(&dir);
+        let bookmark = |url: &str, tags: &[&str]| Bookmark {
+            url: url.to_string(),
+            title: "Async Rust".to_string(),
+            summary: String::new(),
+            tags: normalize_tags(tags.iter().copied()),
+            saved_at: Local::now(),
+            read_at: None,
+        };
+        store
+            .save(bookmark(
+                "https://example.com/async?utm_source=x",
+                &["#Rust"],
+            ))
+            .expect("save");
+        store
+            .mark_read("https://www.example.com/async/")
+            .expect("mark read");
+        assert!(store.unread().is_empty());
+
+        let again = store
+            .save(bookmark("https://example.com/async", &["later"]))
+            .expect("save again");
+        assert_eq!(again.tags, ["later", "rust"]);
+        assert_eq!(store.list().len(), 1);
+        assert_eq!(store.unread().len(), 1);
+        assert!(store.mark_read("https://example.com/other").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
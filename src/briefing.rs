@@ -0,0 +1,611 @@
+//! Morning briefing: today's calendar, upcoming birthdays, open tasks,
+//! reminders, unread saved links, feed headlines and the weather, written up
+//! by the agent as one message.
+//!
+//! The gateway schedules it as a `briefing` cron job at `briefing.time`
+//! (local time); `nanobot brief` runs it on demand.
+
+use crate::agent::AgentLoop;
+use crate::bookmarks::BookmarkStore;
+use crate::config::{BriefingConfig, LocationConfig};
+use crate::contacts::ContactStore;
+use crate::cron::{CronJob, CronPayload, CronSchedule, CronService, CronStore};
+use crate::location::LocationService;
+use crate::tasks::TaskStore;
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const CRON_PAYLOAD_KIND: &str = "briefing";
+pub const BRIEFING_SESSION: &str = "briefing:daily";
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+const REMINDER_HORIZON_HOURS: i64 = 24;
+const READING_LIST_ITEMS: usize = 3;
+const BIRTHDAY_HORIZON_DAYS: i64 = 7;
+
+#[derive(Debug, Clone)]
+pub struct BriefingSection {
+    pub title: &'static str,
+    pub body: String,
+}
+
+/// `config` with the weather following the user's current location when
+/// `weatherLocation` is empty.
+pub async fn with_location_weather(
+    config: &BriefingConfig,
+    location: &LocationConfig,
+) -> BriefingConfig {
+    let mut config = config.clone();
+    if config.weather_location.trim().is_empty()
+        && let Some(service) = LocationService::from_config(location)
+    {
+        match service.current().await {
+            Ok(Some(location)) => {
+                config.weather_location = location.weather_query().unwrap_or_default();
+            }
+            Ok(None) => {}
+            Err(err) => tracing::warn!("no location for the briefing weather: {err:#}"),
+        }
+    }
+    config
+}
+
+/// Collects every configured source; sources that fail are reported inline
+/// rather than failing the briefing.
+pub async fn gather(
+    config: &BriefingConfig,
+    workspace: &Path,
+    cron_store: &Path,
+) -> Vec<BriefingSection> {
+    let client = crate::network::client_builder()
+        .timeout(FETCH_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let mut sections = Vec::new();
+
+    if !config.weather_location.trim().is_empty() {
+        let body = weather(&client, config.weather_location.trim())
+            .await
+            .unwrap_or_else(|err| format!("(unavailable: {err:#})"));
+        sections.push(BriefingSection {
+            title: "Weather",
+            body,
+        });
+    }
+
+    if !config.calendars.is_empty() {
+        let today = Local::now().date_naive();
+        let mut events = Vec::new();
+        let mut errors = Vec::new();
+        for source in &config.calendars {
+            match fetch(&client, source).await {
+                Ok(text) => events.extend(parse_ical_events(&text, today)),
+                Err(err) => errors.push(format!("(unavailable: {source}: {err:#})")),
+            }
+        }
+        events.sort_by_key(|event| (!event.all_day, event.start));
+        let mut lines = events.iter().map(CalendarEvent::line).collect::<Vec<_>>();
+        lines.extend(errors);
+        sections.push(BriefingSection {
+            title: "Calendar",
+            body: non_empty(lines, "No events today."),
+        });
+    }
+
+    let today = Local::now().date_naive();
+    let birthdays = ContactStore::new(workspace)
+        .upcoming_birthdays(today, BIRTHDAY_HORIZON_DAYS)
+        .into_iter()
+        .map(|(date, contact)| {
+            let when = match (date - today).num_days() {
+                0 => "today".to_string(),
+                1 => "tomorrow".to_string(),
+                _ => date.format("%a %Y-%m-%d").to_string(),
+            };
+            format!("- {when}: {}", contact.line())
+        })
+        .collect::<Vec<_>>();
+    if !birthdays.is_empty() {
+        sections.push(BriefingSection {
+            title: "Birthdays this week",
+            body: birthdays.join("\n"),
+        });
+    }
+
+    let tasks = TaskStore::new(workspace)
+        .list()
+        .into_iter()
+        .filter(|task| task.is_open())
+        .map(|task| task.line())
+        .collect::<Vec<_>>();
+    if !tasks.is_empty() {
+        sections.push(BriefingSection {
+            title: "Open tasks",
+            body: tasks.join("\n"),
+        });
+    }
+
+    let reminders = upcoming_reminders(cron_store);
+    if !reminders.is_empty() {
+        sections.push(BriefingSection {
+            title: "Reminders in the next 24 hours",
+            body: reminders.join("\n"),
+        });
+    }
+
+    if let Some(body) = reading_list(workspace) {
+        sections.push(BriefingSection {
+            title: "Reading list",
+            body,
+        });
+    }
+
+    for source in &config.feeds {
+        let body = match fetch(&client, source).await {
+            Ok(text) => {
+                let (title, items) = parse_feed(&text, config.max_feed_items.max(1));
+                let lines = items
+                    .iter()
+                    .map(|(title, link)| format!("- {title} {link}").trim_end().to_string())
+                    .collect::<Vec<_>>();
+                format!(
+                    "{}\n{}",
+                    title.unwrap_or_else(|| source.clone()),
+                    non_empty(lines, "(no items)")
+                )
+            }
+            Err(err) => format!("{source}\n(unavailable: {err:#})"),
+        };
+        sections.push(BriefingSection {
+            title: "News",
+            body,
+        });
+    }
+    sections
+}
+
+/// "3 unread saved articles" and the newest few of them.
+fn reading_list(workspace: &Path) -> Option<String> {
+    let unread = BookmarkStore::new(workspace).unread();
+    let count = match unread.len() {
+        0 => return None,
+        1 => "1 unread saved article".to_string(),
+        n => format!("{n} unread saved articles"),
+    };
+    let lines = unread
+        .iter()
+        .take(READING_LIST_ITEMS)
+        .map(|bookmark| {
+            let title = if bookmark.title.is_empty() {
+                &bookmark.url
+            } else {
+                &bookmark.title
+            };
+            format!("- {title} {}", bookmark.url)
+        })
+        .collect::<Vec<_>>();
+    Some(format!("{count}\n{}", lines.join("\n")))
+}
+
+fn non_empty(lines: Vec<String>, empty: &str) -> String {
+    if lines.is_empty() {
+        empty.to_string()
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// Plain-text dump of the gathered material.
+pub fn render_sections(sections: &[BriefingSection]) -> String {
+    sections
+        .iter()
+        .map(|section| format!("## {}\n{}", section.title, section.body))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+pub fn compose_prompt(config: &BriefingConfig, sections: &[BriefingSection]) -> String {
+    let today = Local::now().format("%A, %Y-%m-%d");
+    let material = if sections.is_empty() {
+        "(no sources configured or nothing to report)".to_string()
+    } else {
+        render_sections(sections)
+    };
+    let mut prompt = format!(
+        "Write my morning briefing for {today} as a single message. Lead with what needs attention today (events, birthdays, due or overdue tasks, reminders), then the weather, then the most notable headlines, then how many saved articles are waiting to be read. Be brief and skimmable; skip empty sections; do not invent anything that is not in the material below.\n\n{material}"
+    );
+    if !config.instructions.trim().is_empty() {
+        prompt.push_str(&format!(
+            "\n\nAdditional instructions: {}",
+            config.instructions.trim()
+        ));
+    }
+    prompt
+}
+
+/// Gathers the sources and has the agent write the briefing.
+pub async fn generate(
+    agent: &AgentLoop,
+    config: &BriefingConfig,
+    workspace: &Path,
+    cron_store: &Path,
+    channel: Option<&str>,
+    to: Option<&str>,
+) -> Result<String> {
+    let sections = gather(config, workspace, cron_store).await;
+    agent
+        .process_direct(
+            &compose_prompt(config, &sections),
+            Some(BRIEFING_SESSION),
+            channel,
+            to,
+        )
+        .await
+}
+
+/// Daily local-time schedule for `HH:MM`.
+pub fn daily_schedule(time: &str) -> Result<CronSchedule> {
+    let (hour, minute) = time
+        .trim()
+        .split_once(':')
+        .and_then(|(h, m)| Some((h.parse::<u32>().ok()?, m.parse::<u32>().ok()?)))
+        .filter(|(h, m)| *h < 24 && *m < 60)
+        .ok_or_else(|| anyhow!("invalid briefing time '{time}': expected HH:MM"))?;
+    Ok(CronSchedule {
+        kind: "cron".to_string(),
+        expr: Some(format!("0 {minute} {hour} * * *")),
+        tz: Some("local".to_string()),
+        ..Default::default()
+    })
+}
+
+/// Keeps exactly one `briefing` cron job matching the config, or none when disabled.
+pub async fn sync_briefing_job(cron: &CronService, config: &BriefingConfig) -> Result<()> {
+    let wanted = if config.enabled {
+        if config.channel.is_empty() || config.to.is_empty() {
+            bail!("briefing.channel and briefing.to are required to deliver the briefing");
+        }
+        Some(daily_schedule(&config.time)?)
+    } else {
+        None
+    };
+    let mut found = false;
+    for job in cron.list_jobs(true).await {
+        if job.payload.kind != CRON_PAYLOAD_KIND {
+            continue;
+        }
+        let current = wanted.as_ref().is_some_and(|schedule| {
+            !found
+                && job.schedule.expr == schedule.expr
+                && job.schedule.tz == schedule.tz
+                && job.payload.channel.as_deref() == Some(config.channel.as_str())
+                && job.payload.to.as_deref() == Some(config.to.as_str())
+        });
+        if current {
+            found = true;
+        } else {
+            cron.remove_job(&job.id).await?;
+        }
+    }
+    if let (Some(schedule), false) = (wanted, found) {
+        let payload = CronPayload {
+            kind: CRON_PAYLOAD_KIND.to_string(),
+            message: String::new(),
+            deliver: true,
+            channel: Some(config.channel.clone()),
+            to: Some(config.to.clone()),
+            tier: None,
+        };
+        cron.add_job_with_payload("briefing".to_string(), schedule, payload, false)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Reminder jobs due within the next day, read straight from the cron store.
+fn upcoming_reminders(cron_store: &Path) -> Vec<String> {
+    let Ok(text) = std::fs::read_to_string(cron_store) else {
+        return Vec::new();
+    };
+    let Ok(store) = serde_json::from_str::<CronStore>(&text) else {
+        return Vec::new();
+    };
+    let now = Utc::now().timestamp_millis();
+    let horizon = now + REMINDER_HORIZON_HOURS * 3_600_000;
+    let mut jobs = store
+        .jobs
+        .into_iter()
+        .filter(|job| job.enabled && job.payload.kind == "agent_turn")
+        .filter_map(|job| {
+            let at = job.state.next_run_at_ms?;
+            (now..=horizon).contains(&at).then_some((at, job))
+        })
+        .collect::<Vec<(i64, CronJob)>>();
+    jobs.sort_by_key(|(at, _)| *at);
+    jobs.into_iter()
+        .filter_map(|(at, job)| {
+            let when = Local.timestamp_millis_opt(at).single()?;
+            Some(format!(
+                "- {} {}",
+                when.format("%H:%M"),
+                job.payload.message
+            ))
+        })
+        .collect()
+}
+
+async fn fetch(client: &reqwest::Client, source: &str) -> Result<String> {
+    if !source.starts_with("http://") && !source.starts_with("https://") {
+        return Ok(tokio::fs::read_to_string(source).await?);
+    }
+    let response = client.get(source).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("HTTP {status}");
+    }
+    Ok(response.text().await?)
+}
+
+async fn weather(client: &reqwest::Client, location: &str) -> Result<String> {
+    let url = format!("https://wttr.in/{}?format=j1", location.replace(' ', "+"));
+    let data: Value = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    summarize_weather(location, &data).ok_or_else(|| anyhow!("unexpected weather response"))
+}
+
+fn summarize_weather(location: &str, data: &Value) -> Option<String> {
+    let current = data.get("current_condition")?.get(0)?;
+    let today = data.get("weather")?.get(0)?;
+    let text = |value: &Value, key: &str| value.get(key)?.as_str().map(str::to_string);
+    let description = current
+        .get("weatherDesc")?
+        .get(0)
+        .and_then(|d| text(d, "value"))
+        .unwrap_or_default();
+    let rain = today
+        .get("hourly")
+        .and_then(Value::as_array)
+        .map(|hours| {
+            hours
+                .iter()
+                .filter_map(|hour| text(hour, "chanceofrain")?.parse::<u32>().ok())
+                .max()
+                .unwrap_or(0)
+        })
+        .unwrap_or(0);
+    Some(format!(
+        "{location}: {description}, {}°C now (feels {}°C), today {}–{}°C, up to {rain}% chance of rain",
+        text(current, "temp_C")?,
+        text(current, "FeelsLikeC").unwrap_or_default(),
+        text(today, "mintempC")?,
+        text(today, "maxtempC")?,
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CalendarEvent {
+    summary: String,
+    location: String,
+    start: DateTime<Local>,
+    all_day: bool,
+}
+
+impl CalendarEvent {
+    fn line(&self) -> String {
+        let when = if self.all_day {
+            "all day".to_string()
+        } else {
+            self.start.format("%H:%M").to_string()
+        };
+        if self.location.is_empty() {
+            format!("- {when} {}", self.summary)
+        } else {
+            format!("- {when} {} ({})", self.summary, self.location)
+        }
+    }
+}
+
+#[derive(Default)]
+struct PendingEvent {
+    summary: String,
+    location: String,
+    start: Option<(DateTime<Local>, bool)>,
+}
+
+/// Events starting on `day`. Recurrence rules are not expanded.
+fn parse_ical_events(text: &str, day: NaiveDate) -> Vec<CalendarEvent> {
+    // Unfold continuation lines (RFC 5545 §3.1).
+    let unfolded = text
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut events = Vec::new();
+    let mut current: Option<PendingEvent> = None;
+    for line in unfolded.lines() {
+        match line.trim_end() {
+            "BEGIN:VEVENT" => current = Some(PendingEvent::default()),
+            "END:VEVENT" => {
+                if let Some(PendingEvent {
+                    summary,
+                    location,
+                    start: Some((start, all_day)),
+                }) = current.take()
+                    && start.date_naive() == day
+                {
+                    events.push(CalendarEvent {
+                        summary,
+                        location,
+                        start,
+                        all_day,
+                    });
+                }
+            }
+            line => {
+                let Some(event) = current.as_mut() else {
+                    continue;
+                };
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let (name, params) = key.split_once(';').unwrap_or((key, ""));
+                match name {
+                    "SUMMARY" => event.summary = unescape_ical(value),
+                    "LOCATION" => event.location = unescape_ical(value),
+                    "DTSTART" => event.start = parse_ical_time(value, params),
+                    _ => {}
+                }
+            }
+        }
+    }
+    events
+}
+
+fn parse_ical_time(value: &str, params: &str) -> Option<(DateTime<Local>, bool)> {
+    if (params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME")) || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        let start = Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()?;
+        return Some((start, true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((Utc.from_utc_datetime(&naive).with_timezone(&Local), false));
+    }
+    // Floating or TZID times are taken as local time.
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some((Local.from_local_datetime(&naive).earliest()?, false))
+}
+
+fn unescape_ical(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+/// Feed title and up to `limit` (title, link) items from RSS or Atom.
+fn parse_feed(text: &str, limit: usize) -> (Option<String>, Vec<(String, String)>) {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    static TITLE: OnceLock<Regex> = OnceLock::new();
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let item = ITEM.get_or_init(|| {
+        Regex::new(r"(?s)<(item|entry)[\s>].*?</(item|entry)>").expect("static regex")
+    });
+    let title =
+        TITLE.get_or_init(|| Regex::new(r"(?s)<title[^>]*>(.*?)</title>").expect("static regex"));
+    let link = LINK.get_or_init(|| {
+        Regex::new(r#"(?s)<link[^>]*href="([^"]+)"[^>]*/?>|<link>(.*?)</link>"#)
+            .expect("static regex")
+    });
+    let clean = |raw: &str| {
+        let raw = raw.trim();
+        let raw = raw
+            .strip_prefix("<![CDATA[")
+            .and_then(|r| r.strip_suffix("]]>"))
+            .unwrap_or(raw);
+        html_escape::decode_html_entities(raw.trim()).to_string()
+    };
+
+    let first_item = item.find(text).map(|m| m.start()).unwrap_or(text.len());
+    let feed_title = title
+        .captures(&text[..first_item])
+        .map(|c| clean(&c[1]))
+        .filter(|t| !t.is_empty());
+    let items = item
+        .find_iter(text)
+        .filter_map(|m| {
+            let body = m.as_str();
+            let item_title = clean(&title.captures(body)?[1]);
+            let item_link = link
+                .captures(body)
+                .and_then(|c| c.get(1).or_else(|| c.get(2)))
+                .map(|l| clean(l.as_str()))
+                .unwrap_or_default();
+            Some((item_title, item_link))
+        })
+        .take(limit)
+        .collect();
+    (feed_title, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_todays_calendar_events() {
+        let ical = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Standup\r\nDTSTART;TZID=Europe/Berlin:20261015T093000\r\nLOCATION:Room 4\\, 2nd floor\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nSUMMARY:Holiday\r\nDTSTART;VALUE=DATE:20261015\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nSUMMARY:Tomorrow's\r\n  meeting\r\nDTSTART:20261016T100000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let day = NaiveDate::from_ymd_opt(2026, 10, 15).expect("date");
+        let events = parse_ical_events(ical, day);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].line(), "- 09:30 Standup (Room 4, 2nd floor)");
+        assert_eq!(events[1].line(), "- all day Holiday");
+    }
+
+    #[test]
+    fn parses_rss_and_atom_feeds() {
+        let rss = "<rss><channel><title>Tech &amp; Co</title><item><title><![CDATA[Rust 2.0]]></title><link>https://x.y/1</link></item><item><title>Second</title><link>https://x.y/2</link></item></channel></rss>";
+        let (title, items) = parse_feed(rss, 1);
+        assert_eq!(title.as_deref(), Some("Tech & Co"));
+        assert_eq!(
+            items,
+            [("Rust 2.0".to_string(), "https://x.y/1".to_string())]
+        );
+
+        let atom = r#"<feed><title>Blog</title><entry><title type="html">Post</title><link rel="alternate" href="https://b.log/p"/></entry></feed>"#;
+        let (title, items) = parse_feed(atom, 5);
+        assert_eq!(title.as_deref(), Some("Blog"));
+        assert_eq!(items, [("Post".to_string(), "https://b.log/p".to_string())]);
+    }
+
+    #[test]
+    fn daily_schedule_uses_local_cron_expression() {
+        let schedule = daily_schedule("07:05").expect("schedule");
+        assert_eq!(schedule.expr.as_deref(), Some("0 5 7 * * *"));
+        assert_eq!(schedule.tz.as_deref(), Some("local"));
+        assert!(daily_schedule("25:00").is_err());
+    }
+
+    #[tokio::test]
+    async fn sync_keeps_a_single_briefing_job() -> Result<()> {
+        let store =
+            std::env::temp_dir().join(format!("nanobot-brief-{}.json", uuid::Uuid::new_v4()));
+        let cron = CronService::new(store.clone());
+        let mut config = BriefingConfig {
+            enabled: true,
+            channel: "telegram".to_string(),
+            to: "42".to_string(),
+            ..Default::default()
+        };
+        sync_briefing_job(&cron, &config).await?;
+        sync_briefing_job(&cron, &config).await?;
+        let jobs = cron.list_jobs(true).await;
+        assert_eq!(jobs.len(), 1);
+        assert!(jobs[0].state.next_run_at_ms.is_some());
+
+        config.time = "06:00".to_string();
+        sync_briefing_job(&cron, &config).await?;
+        let jobs = cron.list_jobs(true).await;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].schedule.expr.as_deref(), Some("0 0 6 * * *"));
+
+        config.enabled = false;
+        sync_briefing_job(&cron, &config).await?;
+        assert!(cron.list_jobs(true).await.is_empty());
+        let _ = std::fs::remove_file(store);
+        Ok(())
+    }
+}
@@ -0,0 +1,433 @@
+//! Daily/monthly spend tracking with threshold alerts.
+//!
+//! `BudgetProvider` wraps the real provider, prices each response from
+//! `budget.prices` and the built-in price table, and once a limit is crossed
+//! sends one alert per period and (optionally) routes requests to
+//! `budget.fallbackModel` until the period resets.
+
+use crate::bus::{MessageBus, OutboundMessage};
+use crate::config::{BudgetConfig, Config, ModelPrice};
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use crate::utils::get_data_path;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+pub fn budget_state_path() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("budget.json"))
+}
+
+/// USD per million input and output tokens for common models, by model name
+/// or prefix without the provider; `budget.prices` adds to and overrides it.
+pub const BUILTIN_PRICES: &[(&str, f64, f64)] = &[
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-opus-4", 15.0, 75.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("claude-haiku-4-5", 1.0, 5.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("gpt-5", 1.25, 10.0),
+    ("gpt-5-mini", 0.25, 2.0),
+    ("gpt-5-nano", 0.05, 0.4),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("gpt-4.1-nano", 0.1, 0.4),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("o3", 2.0, 8.0),
+    ("o3-mini", 1.1, 4.4),
+    ("o4-mini", 1.1, 4.4),
+    ("gemini-2.5-pro", 1.25, 10.0),
+    ("gemini-2.5-flash", 0.3, 2.5),
+    ("gemini-2.5-flash-lite", 0.1, 0.4),
+    ("gemini-2.0-flash", 0.1, 0.4),
+    ("deepseek-chat", 0.27, 1.1),
+    ("deepseek-reasoner", 0.55, 2.19),
+    ("llama-3.1-8b-instant", 0.05, 0.08),
+    ("llama-3.3-70b-versatile", 0.59, 0.79),
+];
+
+/// Looks up a price by exact model name (with or without provider prefix),
+/// then by the longest configured key the model name starts with.
+pub fn price_for<'a>(
+    prices: &'a HashMap<String, ModelPrice>,
+    model: &str,
+) -> Option<&'a ModelPrice> {
+    let bare = model.rsplit('/').next().unwrap_or(model);
+    prices.get(model).or_else(|| prices.get(bare)).or_else(|| {
+        prices
+            .iter()
+            .filter(|(key, _)| bare.starts_with(key.as_str()))
+            .max_by_key(|(key, _)| key.len())
+            .map(|(_, price)| price)
+    })
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SpendState {
+    pub day: String,
+    pub day_usd: f64,
+    pub month: String,
+    pub month_usd: f64,
+    pub daily_alerted: bool,
+    pub monthly_alerted: bool,
+}
+
+impl SpendState {
+    fn roll(&mut self, now: DateTime<Local>) {
+        let day = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+        if self.day != day {
+            self.day = day;
+            self.day_usd = 0.0;
+            self.daily_alerted = false;
+        }
+        if self.month != month {
+            self.month = month;
+            self.month_usd = 0.0;
+            self.monthly_alerted = false;
+        }
+    }
+}
+
+pub struct BudgetTracker {
+    config: BudgetConfig,
+    prices: HashMap<String, ModelPrice>,
+    path: Option<PathBuf>,
+    state: Mutex<SpendState>,
+    unpriced: Mutex<HashSet<String>>,
+}
+
+impl BudgetTracker {
+    /// Resumes from the state file at `path` when given.
+    pub fn new(config: BudgetConfig, path: Option<PathBuf>) -> Self {
+        let state = path
+            .as_deref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        Self {
+            prices: config.model_prices(),
+            config,
+            path,
+            state: Mutex::new(state),
+            unpriced: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SpendState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn snapshot(&self, now: DateTime<Local>) -> SpendState {
+        let mut state = self.lock();
+        state.roll(now);
+        state.clone()
+    }
+
+    fn over(&self, state: &SpendState) -> bool {
+        (self.config.daily_usd > 0.0 && state.day_usd >= self.config.daily_usd)
+            || (self.config.monthly_usd > 0.0 && state.month_usd >= self.config.monthly_usd)
+    }
+
+    /// The model to actually request: the fallback while over budget.
+    pub fn model_for(&self, requested: &str, now: DateTime<Local>) -> String {
+        let fallback = self.config.fallback_model.trim();
+        if !fallback.is_empty() && self.over(&self.snapshot(now)) {
+            return fallback.to_string();
+        }
+        requested.to_string()
+    }
+
+    /// Adds the cost of one response and returns alerts for limits crossed
+    /// for the first time this period.
+    pub fn record(
+        &self,
+        model: &str,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        now: DateTime<Local>,
+    ) -> Vec<String> {
+        let Some(price) = price_for(&self.prices, model) else {
+            let mut unpriced = self.unpriced.lock().unwrap_or_else(|e| e.into_inner());
+            if unpriced.insert(model.to_string()) {
+                warn!("no budget price configured for model '{model}'; its spend is not tracked");
+            }
+            return Vec::new();
+        };
+        let cost = price.cost(prompt_tokens, completion_tokens);
+        let mut alerts = Vec::new();
+        let mut state = self.lock();
+        state.roll(now);
+        state.day_usd += cost;
+        state.month_usd += cost;
+        debug!(
+            cost,
+            day_usd = state.day_usd,
+            month_usd = state.month_usd,
+            "recorded spend"
+        );
+
+        let fallback = self.config.fallback_model.trim();
+        let switch_note = |until: &str| {
+            if fallback.is_empty() {
+                String::new()
+            } else {
+                format!(" Switching to {fallback} until {until}.")
+            }
+        };
+        if self.config.daily_usd > 0.0
+            && state.day_usd >= self.config.daily_usd
+            && !state.daily_alerted
+        {
+            state.daily_alerted = true;
+            alerts.push(format!(
+                "Budget alert: today's spend ${:.2} crossed the daily limit of ${:.2}.{}",
+                state.day_usd,
+                self.config.daily_usd,
+                switch_note("tomorrow")
+            ));
+        }
+        if self.config.monthly_usd > 0.0
+            && state.month_usd >= self.config.monthly_usd
+            && !state.monthly_alerted
+        {
+            state.monthly_alerted = true;
+            alerts.push(format!(
+                "Budget alert: this month's spend ${:.2} crossed the monthly limit of ${:.2}.{}",
+                state.month_usd,
+                self.config.monthly_usd,
+                switch_note("next month")
+            ));
+        }
+        if let Some(path) = &self.path
+            && let Err(err) = save_state(path, &state)
+        {
+            debug!("failed to persist budget state: {err}");
+        }
+        alerts
+    }
+}
+
+fn save_state(path: &Path, state: &SpendState) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(state)?)?;
+    Ok(())
+}
+
+pub struct BudgetProvider {
+    inner: Arc<dyn LLMProvider>,
+    tracker: Arc<BudgetTracker>,
+    bus: Arc<MessageBus>,
+    notify: Option<(String, String)>,
+}
+
+impl BudgetProvider {
+    async fn record(&self, model: &str, response: &LLMResponse) {
+        let (prompt, completion) = response.token_usage();
+        for alert in self.tracker.record(model, prompt, completion, Local::now()) {
+            self.send_alert(alert).await;
+        }
+    }
+
+    async fn send_alert(&self, alert: String) {
+        warn!("{alert}");
+        if let Some((channel, to)) = &self.notify {
+            let message = OutboundMessage::new(channel.clone(), to.clone(), alert);
+            if let Err(err) = self.bus.publish_outbound(message).await {
+                warn!("failed to deliver budget alert: {err}");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for BudgetProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        let requested = model.unwrap_or_else(|| self.inner.default_model());
+        let model = self.tracker.model_for(requested, Local::now());
+        let response = self
+            .inner
+            .chat_with_options(
+                messages,
+                tools,
+                Some(&model),
+                max_tokens,
+                temperature,
+                options,
+            )
+            .await?;
+        self.record(&model, &response).await;
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        let requested = model.unwrap_or_else(|| self.inner.default_model());
+        let model = self.tracker.model_for(requested, Local::now());
+        let response = self
+            .inner
+            .chat_stream(
+                messages,
+                tools,
+                Some(&model),
+                max_tokens,
+                temperature,
+                on_delta,
+            )
+            .await?;
+        self.record(&model, &response).await;
+        Ok(response)
+    }
+
+    fn default_model(&self) -> &str {
+        self.inner.default_model()
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.inner.capabilities(model)
+    }
+}
+
+/// Wraps `provider` with budget tracking when `budget.enabled` is set.
+pub fn wrap_provider(
+    provider: Arc<dyn LLMProvider>,
+    config: &Config,
+    bus: Arc<MessageBus>,
+) -> Arc<dyn LLMProvider> {
+    wrap_with_budget(provider, &config.budget, bus)
+}
+
+/// Like `wrap_provider`, for callers that hold a `BudgetConfig` directly.
+pub fn wrap_with_budget(
+    provider: Arc<dyn LLMProvider>,
+    budget: &BudgetConfig,
+    bus: Arc<MessageBus>,
+) -> Arc<dyn LLMProvider> {
+    if !budget.enabled {
+        return provider;
+    }
+    let notify = (!budget.notify_channel.trim().is_empty() && !budget.notify_to.trim().is_empty())
+        .then(|| {
+            (
+                budget.notify_channel.trim().to_string(),
+                budget.notify_to.trim().to_string(),
+            )
+        });
+    let tracker = BudgetTracker::new(budget.clone(), budget_state_path().ok());
+    Arc::new(BudgetProvider {
+        inner: provider,
+        tracker: Arc::new(tracker),
+        bus,
+        notify,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config() -> BudgetConfig {
+        BudgetConfig {
+            enabled: true,
+            daily_usd: 1.0,
+            monthly_usd: 1.5,
+            fallback_model: "claude-haiku-4-5".to_string(),
+            prices: HashMap::from([
+                (
+                    "claude-opus".to_string(),
+                    ModelPrice {
+                        input_per_million: 10.0,
+                        output_per_million: 50.0,
+                    },
+                ),
+                ("gpt-4o-mini".to_string(), ModelPrice::default()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn price_lookup_strips_prefix_and_matches_longest_key() {
+        let prices = config().prices;
+        assert_eq!(
+            price_for(&prices, "anthropic/claude-opus-4-5").map(|p| p.input_per_million),
+            Some(10.0)
+        );
+        assert!(price_for(&prices, "openai/gpt-4o-mini").is_some());
+        assert!(price_for(&prices, "openai/gpt-4o").is_none());
+    }
+
+    #[test]
+    fn alerts_once_per_period_and_switches_model() {
+        let tracker = BudgetTracker::new(config(), None);
+        let day1 = Local.with_ymd_and_hms(2026, 3, 10, 9, 0, 0).unwrap();
+        let opus = "anthropic/claude-opus-4-5";
+
+        // 50k prompt + 10k completion = $0.50 + $0.50
+        assert!(tracker.record(opus, 50_000, 0, day1).is_empty());
+        assert_eq!(tracker.model_for(opus, day1), opus);
+        let alerts = tracker.record(opus, 0, 10_000, day1);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("daily limit"));
+        assert_eq!(tracker.model_for(opus, day1), "claude-haiku-4-5");
+        assert!(tracker.record(opus, 10_000, 0, day1).is_empty());
+
+        let day2 = Local.with_ymd_and_hms(2026, 3, 11, 9, 0, 0).unwrap();
+        assert_eq!(tracker.model_for(opus, day2), opus);
+        let alerts = tracker.record(opus, 50_000, 0, day2);
+        assert_eq!(alerts.len(), 1);
+        assert!(alerts[0].contains("monthly limit"));
+        assert_eq!(tracker.model_for(opus, day2), "claude-haiku-4-5");
+
+        let next_month = Local.with_ymd_and_hms(2026, 4, 1, 9, 0, 0).unwrap();
+        assert_eq!(tracker.snapshot(next_month).month_usd, 0.0);
+    }
+}
@@ -1,8 +1,29 @@
+//! The message bus between channels and the agent.
+//!
+//! Inbound and outbound messages travel through queues with a single
+//! consumer each (the agent loop and the channel dispatcher). Next to them,
+//! every message, agent event and system event is broadcast on its topic to
+//! any number of observers, which subscribe to one payload type and may
+//! filter it further, e.g. `subscribe_filtered::<OutboundMessage>(|m|
+//! m.channel == "telegram")`.
+//!
+//! A caller waiting on the user's answer in a chat registers an expected
+//! reply (see [`MessageBus::ask`]); the next message from that chat goes to
+//! the waiter instead of the inbound queue, so it does not start a new turn.
+
+use crate::agent::events::AgentEvent;
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use std::marker::PhantomData;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use tokio::sync::{Mutex, mpsc};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex, broadcast, mpsc, oneshot};
+use tracing::{trace, warn};
+
+/// Events kept per topic for subscribers that fall behind.
+const TOPIC_CAPACITY: usize = 256;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InboundMessage {
@@ -65,6 +86,153 @@ impl OutboundMessage {
     }
 }
 
+/// An agent event from the turn of `session` (`channel:chat_id`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionEvent {
+    pub session: String,
+    pub event: AgentEvent,
+}
+
+/// Something that happened outside a conversation, such as a channel task
+/// exiting or a cron job running.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemEvent {
+    /// e.g. `channel_exited`, `cron_job`.
+    pub kind: String,
+    /// What it happened to: a channel name, a job id.
+    pub source: String,
+    pub detail: String,
+    pub timestamp: DateTime<Local>,
+}
+
+impl SystemEvent {
+    pub fn new(
+        kind: impl Into<String>,
+        source: impl Into<String>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind: kind.into(),
+            source: source.into(),
+            detail: detail.into(),
+            timestamp: Local::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    Inbound,
+    Outbound,
+    Agent,
+    System,
+}
+
+#[derive(Debug, Clone)]
+pub enum BusEvent {
+    Inbound(InboundMessage),
+    Outbound(OutboundMessage),
+    Agent(SessionEvent),
+    System(SystemEvent),
+}
+
+/// A payload type with its own topic.
+pub trait BusPayload: Clone + Send + Sized + 'static {
+    const TOPIC: Topic;
+    fn into_event(self) -> BusEvent;
+    fn from_event(event: BusEvent) -> Option<Self>;
+}
+
+macro_rules! bus_payload {
+    ($ty:ty, $topic:ident) => {
+        impl BusPayload for $ty {
+            const TOPIC: Topic = Topic::$topic;
+
+            fn into_event(self) -> BusEvent {
+                BusEvent::$topic(self)
+            }
+
+            fn from_event(event: BusEvent) -> Option<Self> {
+                match event {
+                    BusEvent::$topic(payload) => Some(payload),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+bus_payload!(InboundMessage, Inbound);
+bus_payload!(OutboundMessage, Outbound);
+bus_payload!(SessionEvent, Agent);
+bus_payload!(SystemEvent, System);
+
+type Filter<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
+/// Receives the payloads of one topic that pass the subscriber's filter.
+pub struct Subscription<T: BusPayload> {
+    rx: broadcast::Receiver<BusEvent>,
+    filter: Option<Filter<T>>,
+    _payload: PhantomData<T>,
+}
+
+impl<T: BusPayload> Subscription<T> {
+    /// The next matching payload; `None` once the bus is gone. A subscriber
+    /// that falls more than `TOPIC_CAPACITY` events behind skips the oldest.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.rx.recv().await {
+                Ok(event) => {
+                    let Some(payload) = T::from_event(event) else {
+                        continue;
+                    };
+                    if self.filter.as_ref().is_none_or(|keep| keep(&payload)) {
+                        return Some(payload);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(topic = ?T::TOPIC, skipped, "bus subscriber fell behind");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+struct Topics {
+    inbound: broadcast::Sender<BusEvent>,
+    outbound: broadcast::Sender<BusEvent>,
+    agent: broadcast::Sender<BusEvent>,
+    system: broadcast::Sender<BusEvent>,
+}
+
+impl Topics {
+    fn new() -> Self {
+        Self {
+            inbound: broadcast::channel(TOPIC_CAPACITY).0,
+            outbound: broadcast::channel(TOPIC_CAPACITY).0,
+            agent: broadcast::channel(TOPIC_CAPACITY).0,
+            system: broadcast::channel(TOPIC_CAPACITY).0,
+        }
+    }
+
+    fn sender(&self, topic: Topic) -> &broadcast::Sender<BusEvent> {
+        match topic {
+            Topic::Inbound => &self.inbound,
+            Topic::Outbound => &self.outbound,
+            Topic::Agent => &self.agent,
+            Topic::System => &self.system,
+        }
+    }
+}
+
+/// A waiter for the next message from one chat.
+struct ExpectedReply {
+    channel: String,
+    chat_id: String,
+    tx: oneshot::Sender<InboundMessage>,
+}
+
 pub struct MessageBus {
     inbound_tx: mpsc::Sender<InboundMessage>,
     inbound_rx: Mutex<mpsc::Receiver<InboundMessage>>,
@@ -72,6 +240,8 @@ pub struct MessageBus {
     outbound_rx: Mutex<mpsc::Receiver<OutboundMessage>>,
     inbound_size: AtomicUsize,
     outbound_size: AtomicUsize,
+    topics: Topics,
+    expected: std::sync::Mutex<Vec<ExpectedReply>>,
 }
 
 impl MessageBus {
@@ -85,9 +255,42 @@ impl MessageBus {
             outbound_rx: Mutex::new(outbound_rx),
             inbound_size: AtomicUsize::new(0),
             outbound_size: AtomicUsize::new(0),
+            topics: Topics::new(),
+            expected: std::sync::Mutex::new(Vec::new()),
         }
     }
 
+    /// Every payload of `T`'s topic published from now on.
+    pub fn subscribe<T: BusPayload>(&self) -> Subscription<T> {
+        Subscription {
+            rx: self.topics.sender(T::TOPIC).subscribe(),
+            filter: None,
+            _payload: PhantomData,
+        }
+    }
+
+    /// The payloads of `T`'s topic for which `filter` returns true.
+    pub fn subscribe_filtered<T: BusPayload>(
+        &self,
+        filter: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> Subscription<T> {
+        Subscription {
+            filter: Some(Box::new(filter)),
+            ..self.subscribe()
+        }
+    }
+
+    /// Broadcasts `payload` to the subscribers of its topic; without any it
+    /// is dropped. Messages published with `publish_inbound` and
+    /// `publish_outbound` are broadcast as well.
+    pub fn publish<T: BusPayload>(&self, payload: T) {
+        let sender = self.topics.sender(T::TOPIC);
+        if sender.receiver_count() > 0 {
+            let _ = sender.send(payload.into_event());
+        }
+    }
+
+    /// Raw queue senders; messages sent through them skip topic subscribers.
     pub fn inbound_sender(&self) -> mpsc::Sender<InboundMessage> {
         self.inbound_tx.clone()
     }
@@ -96,12 +299,77 @@ impl MessageBus {
         self.outbound_tx.clone()
     }
 
+    /// Routes the next message from `channel`/`chat_id` to the returned
+    /// receiver instead of the inbound queue. Dropping the receiver withdraws
+    /// the expectation; the oldest waiter on a chat is served first.
+    pub fn expect_reply(&self, channel: &str, chat_id: &str) -> oneshot::Receiver<InboundMessage> {
+        let (tx, rx) = oneshot::channel();
+        let mut expected = self.expected.lock().unwrap_or_else(|e| e.into_inner());
+        expected.retain(|waiter| !waiter.tx.is_closed());
+        expected.push(ExpectedReply {
+            channel: channel.to_string(),
+            chat_id: chat_id.to_string(),
+            tx,
+        });
+        rx
+    }
+
+    /// Sends `question` to the chat and waits up to `wait` for the user's
+    /// reply; `None` when none came in time.
+    pub async fn ask(
+        &self,
+        channel: &str,
+        chat_id: &str,
+        question: &str,
+        wait: Duration,
+    ) -> anyhow::Result<Option<InboundMessage>> {
+        let reply = self.expect_reply(channel, chat_id);
+        let mut outbound = OutboundMessage::new(channel, chat_id, question);
+        outbound
+            .metadata
+            .insert("expects_reply".to_string(), Value::Bool(true));
+        self.publish_outbound(outbound).await?;
+        Ok(tokio::time::timeout(wait, reply)
+            .await
+            .ok()
+            .and_then(Result::ok))
+    }
+
+    /// Hands `msg` to a waiter for its chat; gives it back when none takes it.
+    /// Slash commands always go to the agent.
+    fn deliver_expected(&self, msg: InboundMessage) -> Option<InboundMessage> {
+        if msg.channel == "system" || msg.content.trim_start().starts_with('/') {
+            return Some(msg);
+        }
+        let mut expected = self.expected.lock().unwrap_or_else(|e| e.into_inner());
+        expected.retain(|waiter| !waiter.tx.is_closed());
+        let mut msg = msg;
+        while let Some(index) = expected
+            .iter()
+            .position(|waiter| waiter.channel == msg.channel && waiter.chat_id == msg.chat_id)
+        {
+            match expected.remove(index).tx.send(msg) {
+                Ok(()) => {
+                    trace!("inbound message delivered to a waiting reply");
+                    return None;
+                }
+                Err(returned) => msg = returned,
+            }
+        }
+        Some(msg)
+    }
+
     pub async fn publish_inbound(&self, msg: InboundMessage) -> anyhow::Result<()> {
+        self.publish(msg.clone());
+        let Some(msg) = self.deliver_expected(msg) else {
+            return Ok(());
+        };
         self.inbound_size.fetch_add(1, Ordering::Relaxed);
         if let Err(err) = self.inbound_tx.send(msg).await {
             self.inbound_size.fetch_sub(1, Ordering::Relaxed);
             return Err(anyhow::anyhow!("failed to publish inbound message: {err}"));
         }
+        trace!("inbound message published");
         Ok(())
     }
 
@@ -115,11 +383,13 @@ impl MessageBus {
     }
 
     pub async fn publish_outbound(&self, msg: OutboundMessage) -> anyhow::Result<()> {
+        self.publish(msg.clone());
         self.outbound_size.fetch_add(1, Ordering::Relaxed);
         if let Err(err) = self.outbound_tx.send(msg).await {
             self.outbound_size.fetch_sub(1, Ordering::Relaxed);
             return Err(anyhow::anyhow!("failed to publish outbound message: {err}"));
         }
+        trace!("outbound message published");
         Ok(())
     }
 
@@ -140,3 +410,74 @@ impl MessageBus {
         self.outbound_size.load(Ordering::Relaxed)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::events;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn subscribers_get_their_topic_through_the_filter() {
+        let bus = Arc::new(MessageBus::new(8));
+        let mut telegram =
+            bus.subscribe_filtered::<OutboundMessage>(|msg| msg.channel == "telegram");
+        let mut agent = bus.subscribe::<SessionEvent>();
+        let mut system = bus.subscribe::<SystemEvent>();
+
+        bus.publish_outbound(OutboundMessage::new("slack", "C1", "skipped"))
+            .await
+            .unwrap();
+        bus.publish_outbound(OutboundMessage::new("telegram", "42", "hello"))
+            .await
+            .unwrap();
+        events::with_bus(bus.clone(), "telegram:42".to_string(), async {
+            events::emit(AgentEvent::Delta {
+                text: "hi".to_string(),
+            });
+        })
+        .await;
+        bus.publish(SystemEvent::new("channel_exited", "slack", "stopped"));
+
+        assert_eq!(telegram.recv().await.unwrap().content, "hello");
+        let event = agent.recv().await.unwrap();
+        assert_eq!(event.session, "telegram:42");
+        assert_eq!(event.event.name(), "delta");
+        assert_eq!(system.recv().await.unwrap().source, "slack");
+
+        // The queue still hands every message to its single consumer.
+        assert_eq!(bus.outbound_size(), 2);
+        assert_eq!(bus.consume_outbound().await.unwrap().channel, "slack");
+        assert_eq!(bus.consume_outbound().await.unwrap().channel, "telegram");
+    }
+
+    #[tokio::test]
+    async fn expected_replies_bypass_the_inbound_queue() {
+        let bus = Arc::new(MessageBus::new(8));
+        let asking = {
+            let bus = bus.clone();
+            tokio::spawn(async move {
+                bus.ask("telegram", "42", "Which day?", Duration::from_secs(5))
+                    .await
+            })
+        };
+        let question = bus.consume_outbound().await.unwrap();
+        assert_eq!(question.content, "Which day?");
+        assert_eq!(question.metadata["expects_reply"], true);
+
+        for (chat, text) in [("7", "other chat"), ("42", "/stop"), ("42", "Friday")] {
+            bus.publish_inbound(InboundMessage::new("telegram", "u", chat, text))
+                .await
+                .unwrap();
+        }
+        let reply = asking.await.unwrap().unwrap().expect("reply");
+        assert_eq!(reply.content, "Friday");
+        assert_eq!(bus.inbound_size(), 2);
+
+        // Without a waiter the next message is an ordinary turn again.
+        bus.publish_inbound(InboundMessage::new("telegram", "u", "42", "thanks"))
+            .await
+            .unwrap();
+        assert_eq!(bus.inbound_size(), 3);
+    }
+}
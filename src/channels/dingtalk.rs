@@ -64,7 +64,7 @@ impl Channel for DingTalkChannel {
 
         #[cfg(not(feature = "dingtalk-stream"))]
         {
-            eprintln!(
+            tracing::warn!(
                 "DingTalk stream support is disabled. Rebuild with --features dingtalk-stream."
             );
             self.running.store(false, Ordering::Relaxed);
@@ -176,9 +176,9 @@ impl Channel for DingTalkChannel {
                     break;
                 }
                 if let Err(err) = connect_result {
-                    eprintln!("DingTalk stream error: {err}");
+                    tracing::error!("DingTalk stream error: {err}");
                 } else {
-                    eprintln!("DingTalk stream disconnected unexpectedly.");
+                    tracing::error!("DingTalk stream disconnected unexpectedly.");
                 }
                 tokio::time::sleep(std::time::Duration::from_secs(5)).await;
             }
@@ -1,23 +1,57 @@
+use crate::access;
+use crate::agent::approval::{
+    ApprovalDecision, PendingApprovals, Resolution, parse_approval_callback,
+};
 use crate::bus::{MessageBus, OutboundMessage};
 use crate::channels::base::Channel;
+use crate::channels::format::{DISCORD_CONTENT_CHARS, DiscordFormatter, Formatter};
+use crate::channels::group::{GroupGate, strip_mention};
+use crate::channels::live::{self, LiveMessages, LiveUpdate};
 use crate::config::DiscordConfig;
 use crate::pairing::{issue_pairing, pairing_prompt};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use futures_util::{SinkExt, StreamExt};
-use reqwest::Client;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, Method};
 use serde_json::{Map, Value, json};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::{Mutex, mpsc};
 use tokio::task::JoinHandle;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, warn};
 
 const DISCORD_API_BASE: &str = "https://discord.com/api/v10";
 const MAX_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
 
+fn approval_components(metadata: &Map<String, Value>) -> Option<Value> {
+    let options = metadata.get("approval")?.get("options")?.as_array()?;
+    let buttons = options
+        .iter()
+        .filter_map(|option| {
+            let custom_id = option.get("callback")?.as_str()?;
+            let style = match parse_approval_callback(custom_id)?.1 {
+                ApprovalDecision::Approve => 3,
+                ApprovalDecision::Deny => 4,
+                ApprovalDecision::AlwaysAllowTool => 2,
+            };
+            Some(json!({
+                "type": 2,
+                "style": style,
+                "label": option.get("label")?.as_str()?,
+                "custom_id": custom_id,
+            }))
+        })
+        .collect::<Vec<_>>();
+    if buttons.is_empty() {
+        return None;
+    }
+    Some(json!([{ "type": 1, "components": buttons }]))
+}
+
 pub struct DiscordChannel {
     config: DiscordConfig,
     bus: Arc<MessageBus>,
@@ -25,17 +59,25 @@ pub struct DiscordChannel {
     seq: Arc<Mutex<Option<i64>>>,
     http: Client,
     typing_tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+    live: LiveMessages,
+    group: GroupGate,
+    /// The bot's user id, from the gateway's READY event.
+    me: Mutex<Option<String>>,
 }
 
 impl DiscordChannel {
     pub fn new(config: DiscordConfig, bus: Arc<MessageBus>) -> Self {
+        let group = GroupGate::new(config.group.clone());
         Self {
             config,
             bus,
             running: AtomicBool::new(false),
             seq: Arc::new(Mutex::new(None)),
-            http: Client::new(),
+            http: crate::network::client(),
             typing_tasks: Mutex::new(HashMap::new()),
+            live: LiveMessages::default(),
+            group,
+            me: Mutex::new(None),
         }
     }
 
@@ -59,6 +101,23 @@ impl DiscordChannel {
             return Ok(());
         }
 
+        let mut text = payload
+            .get("content")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        if payload.get("guild_id").is_some_and(|id| !id.is_null()) {
+            let (mentioned, said) = self.addressing(payload, &text).await;
+            let sender = author["global_name"]
+                .as_str()
+                .or(author["username"].as_str())
+                .unwrap_or("someone");
+            let Some(content) = self.group.admit(&channel_id, sender, &said, mentioned) else {
+                return Ok(());
+            };
+            text = content;
+        }
+
         if !self.is_allowed(&sender_id) {
             if let Ok(issue) = issue_pairing(self.name(), &sender_id, &channel_id) {
                 let prompt = pairing_prompt(&issue);
@@ -75,10 +134,8 @@ impl DiscordChannel {
         }
 
         let mut content_parts = Vec::new();
-        if let Some(content) = payload.get("content").and_then(Value::as_str) {
-            if !content.is_empty() {
-                content_parts.push(content.to_string());
-            }
+        if !text.is_empty() {
+            content_parts.push(text);
         }
         let mut media_paths = Vec::new();
 
@@ -161,6 +218,87 @@ impl DiscordChannel {
         Ok(())
     }
 
+    /// Whether a guild message mentions the bot or replies to it; the text
+    /// without the mention.
+    async fn addressing(&self, payload: &Value, text: &str) -> (bool, String) {
+        let Some(id) = self.me.lock().await.clone() else {
+            return (false, text.trim().to_string());
+        };
+        let mentioned = payload["mentions"]
+            .as_array()
+            .is_some_and(|users| users.iter().any(|user| user["id"].as_str() == Some(&id)));
+        let replied = payload["referenced_message"]["author"]["id"].as_str() == Some(&id);
+        let text = strip_mention(
+            &strip_mention(text, &format!("<@{id}>")),
+            &format!("<@!{id}>"),
+        );
+        (mentioned || replied, text)
+    }
+
+    async fn handle_interaction(&self, payload: &Value) -> Result<()> {
+        let (Some(id), Some(token)) = (
+            payload.get("id").and_then(Value::as_str),
+            payload.get("token").and_then(Value::as_str),
+        ) else {
+            return Ok(());
+        };
+        let Some((approval_id, decision)) = payload
+            .get("data")
+            .and_then(|v| v.get("custom_id"))
+            .and_then(Value::as_str)
+            .and_then(parse_approval_callback)
+        else {
+            return Ok(());
+        };
+        let user_id = payload
+            .get("member")
+            .and_then(|v| v.get("user"))
+            .or_else(|| payload.get("user"))
+            .and_then(|v| v.get("id"))
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        let channel_id = payload
+            .get("channel_id")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+
+        let outcome = if !self.is_allowed(user_id) {
+            "Not allowed".to_string()
+        } else {
+            let user = access::user_id(self.name(), user_id, "");
+            match PendingApprovals::global().resolve(
+                &approval_id,
+                self.name(),
+                channel_id,
+                &user,
+                decision,
+            ) {
+                Resolution::Answered => decision.label().to_string(),
+                Resolution::NotAllowed => "Not allowed".to_string(),
+                Resolution::Expired => "This request has expired".to_string(),
+            }
+        };
+        let original = payload
+            .get("message")
+            .and_then(|v| v.get("content"))
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let url = format!("{DISCORD_API_BASE}/interactions/{id}/{token}/callback");
+        self.http
+            .post(&url)
+            .json(&json!({
+                "type": 7,
+                "data": {
+                    "content": format!("{original}\n\n**{outcome}**"),
+                    "components": []
+                }
+            }))
+            .send()
+            .await?;
+        Ok(())
+    }
+
     async fn start_typing(&self, channel_id: String) {
         self.stop_typing(&channel_id).await;
         let channel_for_task = channel_id.clone();
@@ -185,6 +323,77 @@ impl DiscordChannel {
             task.abort();
         }
     }
+
+    /// Posts one message, waiting out rate limits; gives up after three tries.
+    /// Sends `payload` with `method`, retrying on rate limits and errors;
+    /// the message Discord returned, or `None` when every attempt failed.
+    async fn post_message(
+        &self,
+        method: Method,
+        url: &str,
+        payload: &Value,
+        media: &[String],
+    ) -> Result<Option<Value>> {
+        let headers = [("Authorization", format!("Bot {}", self.config.token))];
+        for _ in 0..3 {
+            let request = self
+                .http
+                .request(method.clone(), url)
+                .headers(headers.iter().fold(
+                    reqwest::header::HeaderMap::new(),
+                    |mut map, (k, v)| {
+                        map.insert(
+                            reqwest::header::HeaderName::from_bytes(k.as_bytes()).unwrap(),
+                            reqwest::header::HeaderValue::from_str(v).unwrap(),
+                        );
+                        map
+                    },
+                ));
+            let request = if media.is_empty() {
+                request.json(payload)
+            } else {
+                request.multipart(attachments_form(payload, media).await?)
+            };
+            let response = request.send().await?;
+
+            if response.status().as_u16() == 429 {
+                let data: Value = response.json().await.unwrap_or_else(|_| json!({}));
+                let retry_after = data
+                    .get("retry_after")
+                    .and_then(Value::as_f64)
+                    .unwrap_or(1.0);
+                tokio::time::sleep(std::time::Duration::from_secs_f64(retry_after)).await;
+                continue;
+            }
+            if response.status().is_success() {
+                return Ok(Some(response.json().await.unwrap_or(Value::Null)));
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+        Ok(None)
+    }
+
+    /// Shows the partial answer of a live reply, sending its message on the
+    /// first update and editing it afterwards.
+    async fn send_partial(&self, msg: &OutboundMessage, id: &str) -> Result<()> {
+        let url = format!("{DISCORD_API_BASE}/channels/{}/messages", msg.chat_id);
+        let mut payload =
+            json!({ "content": live::partial_text(&msg.content, DISCORD_CONTENT_CHARS) });
+        if let Some(message_id) = self.live.get(id) {
+            self.post_message(Method::PATCH, &format!("{url}/{message_id}"), &payload, &[])
+                .await?;
+            return Ok(());
+        }
+        if let Some(reply_to) = &msg.reply_to {
+            payload["message_reference"] = json!({ "message_id": reply_to });
+            payload["allowed_mentions"] = json!({ "replied_user": false });
+        }
+        let sent = self.post_message(Method::POST, &url, &payload, &[]).await?;
+        if let Some(message_id) = sent.as_ref().and_then(|m| m["id"].as_str()) {
+            self.live.insert(id, message_id.to_string());
+        }
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -292,11 +501,23 @@ impl Channel for DiscordChannel {
                             }
                         }));
                     }
+                    0 if event_type == "READY" => {
+                        if let Some(id) = payload["d"]["user"]["id"].as_str() {
+                            *self.me.lock().await = Some(id.to_string());
+                        }
+                    }
                     0 if event_type == "MESSAGE_CREATE" => {
                         if let Some(data) = payload.get("d") {
                             let _ = self.handle_message_create(data).await;
                         }
                     }
+                    0 if event_type == "INTERACTION_CREATE" => {
+                        if let Some(data) = payload.get("d")
+                            && let Err(err) = self.handle_interaction(data).await
+                        {
+                            warn!("Discord interaction handling error: {err}");
+                        }
+                    }
                     7 | 9 => {
                         break;
                     }
@@ -331,48 +552,109 @@ impl Channel for DiscordChannel {
     }
 
     async fn send(&self, msg: &OutboundMessage) -> Result<()> {
-        let url = format!("{DISCORD_API_BASE}/channels/{}/messages", msg.chat_id);
-        let mut payload = json!({ "content": msg.content });
-        if let Some(reply_to) = &msg.reply_to {
-            payload["message_reference"] = json!({ "message_id": reply_to });
-            payload["allowed_mentions"] = json!({ "replied_user": false });
+        let update = LiveUpdate::from_metadata(&msg.metadata);
+        if let Some(update) = update.as_ref().filter(|update| !update.done) {
+            if let Err(err) = self.send_partial(msg, &update.id).await {
+                debug!("failed to update live reply: {err:#}");
+            }
+            return Ok(());
         }
-
-        let headers = [("Authorization", format!("Bot {}", self.config.token))];
-        for _ in 0..3 {
-            let response = self
-                .http
-                .post(&url)
-                .headers(headers.iter().fold(
-                    reqwest::header::HeaderMap::new(),
-                    |mut map, (k, v)| {
-                        map.insert(
-                            reqwest::header::HeaderName::from_bytes(k.as_bytes()).unwrap(),
-                            reqwest::header::HeaderValue::from_str(v).unwrap(),
-                        );
-                        map
-                    },
-                ))
-                .json(&payload)
-                .send()
-                .await?;
-
-            if response.status().as_u16() == 429 {
-                let data: Value = response.json().await.unwrap_or_else(|_| json!({}));
-                let retry_after = data
-                    .get("retry_after")
-                    .and_then(Value::as_f64)
-                    .unwrap_or(1.0);
-                tokio::time::sleep(std::time::Duration::from_secs_f64(retry_after)).await;
-                continue;
+        let mut edit = update.and_then(|update| self.live.remove(&update.id));
+        let url = format!("{DISCORD_API_BASE}/channels/{}/messages", msg.chat_id);
+        let parts = DiscordFormatter.format(&msg.content);
+        let last = parts.len().saturating_sub(1);
+        for (i, part) in parts.into_iter().enumerate() {
+            let mut payload = json!({ "content": part.text });
+            if let Some(embeds) = part.extra {
+                payload["embeds"] = embeds;
             }
-            if response.status().is_success() {
+            if i == 0
+                && let Some(reply_to) = &msg.reply_to
+            {
+                payload["message_reference"] = json!({ "message_id": reply_to });
+                payload["allowed_mentions"] = json!({ "replied_user": false });
+            }
+            let media: &[String] = if i == last {
+                if let Some(components) = approval_components(&msg.metadata) {
+                    payload["components"] = components;
+                }
+                &msg.media
+            } else {
+                &[]
+            };
+            // The live message becomes the first part; attachments need a new one.
+            if let Some(message_id) = edit.take().filter(|_| media.is_empty()) {
+                let mut edit_payload = payload.clone();
+                if let Some(map) = edit_payload.as_object_mut() {
+                    map.remove("message_reference");
+                    map.remove("allowed_mentions");
+                }
+                let edited = self
+                    .post_message(
+                        Method::PATCH,
+                        &format!("{url}/{message_id}"),
+                        &edit_payload,
+                        &[],
+                    )
+                    .await?;
+                if edited.is_some() {
+                    continue;
+                }
+            }
+            if self
+                .post_message(Method::POST, &url, &payload, media)
+                .await?
+                .is_none()
+            {
                 self.stop_typing(&msg.chat_id).await;
-                return Ok(());
+                return Err(anyhow!("discord rejected message part {}", i + 1));
             }
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
         }
         self.stop_typing(&msg.chat_id).await;
         Ok(())
     }
 }
+
+/// A multipart message body carrying `paths` as file attachments.
+async fn attachments_form(payload: &Value, paths: &[String]) -> Result<Form> {
+    let mut form = Form::new().text("payload_json", payload.to_string());
+    for (index, path) in paths.iter().enumerate() {
+        let path = Path::new(path);
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("file")
+            .to_string();
+        let bytes = tokio::fs::read(path).await?;
+        form = form.part(
+            format!("files[{index}]"),
+            Part::bytes(bytes).file_name(name),
+        );
+    }
+    Ok(form)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::approval_components;
+    use crate::agent::approval::ApprovalRequest;
+    use serde_json::Map;
+
+    #[test]
+    fn approval_metadata_renders_button_row() {
+        let request = ApprovalRequest::new("exec", &Map::new(), "discord", "1");
+        let mut metadata = Map::new();
+        metadata.insert("approval".to_string(), request.to_metadata());
+        let components = approval_components(&metadata).expect("components");
+        let buttons = components[0]["components"].as_array().expect("buttons");
+        let styles = buttons
+            .iter()
+            .map(|b| b["style"].as_i64().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(styles, vec![3, 4, 2]);
+        assert_eq!(
+            buttons[0]["custom_id"],
+            format!("approval:{}:approve", request.id)
+        );
+    }
+}
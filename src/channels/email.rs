@@ -15,6 +15,7 @@ use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{error, warn};
 
 const MAX_PROCESSED_UIDS: usize = 100_000;
 
@@ -360,13 +361,13 @@ impl Channel for EmailChannel {
 
     async fn start(&self) -> Result<()> {
         if !self.config.consent_granted {
-            eprintln!(
+            warn!(
                 "Email channel disabled: consent_granted=false. Grant explicit permission before mailbox access."
             );
             return Ok(());
         }
         if let Err(err) = self.validate_config() {
-            eprintln!("{err}");
+            error!("{err}");
             return Ok(());
         }
 
@@ -410,7 +411,7 @@ impl Channel for EmailChannel {
                     }
                 }
                 Err(err) => {
-                    eprintln!("email polling error: {err}");
+                    error!("email polling error: {err}");
                 }
             }
 
@@ -426,7 +427,7 @@ impl Channel for EmailChannel {
 
     async fn send(&self, msg: &OutboundMessage) -> Result<()> {
         if !self.config.consent_granted {
-            eprintln!("skip email send: consent_granted=false");
+            warn!("skip email send: consent_granted=false");
             return Ok(());
         }
 
@@ -439,7 +440,7 @@ impl Channel for EmailChannel {
             return Ok(());
         }
         if self.config.smtp_host.trim().is_empty() {
-            eprintln!("email channel SMTP host not configured");
+            warn!("email channel SMTP host not configured");
             return Ok(());
         }
 
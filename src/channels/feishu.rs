@@ -51,7 +51,7 @@ impl FeishuChannel {
             config,
             bus,
             running: Arc::new(AtomicBool::new(false)),
-            http: Client::new(),
+            http: crate::network::client(),
             tenant_access_token: Mutex::new(None),
             #[cfg(feature = "feishu-websocket")]
             ws_thread: Mutex::new(None),
@@ -355,7 +355,7 @@ impl FeishuChannel {
                 .enable_all()
                 .build();
             let Ok(runtime) = runtime else {
-                eprintln!("Feishu: failed to create runtime for websocket receiver");
+                tracing::error!("Feishu: failed to create runtime for websocket receiver");
                 return;
             };
 
@@ -376,7 +376,7 @@ impl FeishuChannel {
                         encrypt_key.clone(),
                     );
                     let Ok(handler) = handler else {
-                        eprintln!("Feishu: failed to build event handler");
+                        tracing::error!("Feishu: failed to build event handler");
                         return;
                     };
 
@@ -412,7 +412,9 @@ impl Channel for FeishuChannel {
         self.running.store(true, Ordering::Relaxed);
         #[cfg(not(feature = "feishu-websocket"))]
         {
-            eprintln!("Feishu receive loop is disabled. Rebuild with --features feishu-websocket.");
+            tracing::warn!(
+                "Feishu receive loop is disabled. Rebuild with --features feishu-websocket."
+            );
         }
         #[cfg(feature = "feishu-websocket")]
         {
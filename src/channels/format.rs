@@ -0,0 +1,746 @@
+//! Adapting the agent's markdown to what each channel can display.
+//!
+//! Replies are parsed once into blocks (code fences, headings, quotes, list
+//! items, tables, lines) and inline spans (code, links, bold, italic,
+//! strikethrough), then rendered for the channel: Telegram MarkdownV2,
+//! Slack mrkdwn with blocks, Discord markdown with embeds for long replies,
+//! or plain text. Long replies are split into several messages at paragraph,
+//! then sentence, then word boundaries; code fences are never cut open.
+
+use serde_json::{Value, json};
+
+/// Telegram allows 4096 characters after escaping; leave room for it.
+pub(crate) const TELEGRAM_CHUNK_CHARS: usize = 3_000;
+pub(crate) const DISCORD_CONTENT_CHARS: usize = 2_000;
+const DISCORD_EMBED_CHARS: usize = 4_096;
+const DISCORD_TITLE_CHARS: usize = 256;
+const SLACK_SECTION_CHARS: usize = 3_000;
+const SLACK_HEADER_CHARS: usize = 150;
+pub(crate) const SLACK_MESSAGE_CHARS: usize = 12_000;
+
+/// One message to send.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formatted {
+    pub text: String,
+    /// Structured payload next to the text: Discord `embeds`, Slack `blocks`.
+    pub extra: Option<Value>,
+}
+
+impl Formatted {
+    fn text(text: String) -> Self {
+        Self { text, extra: None }
+    }
+}
+
+pub trait Formatter: Send + Sync {
+    /// The messages to send for `markdown`, in order.
+    fn format(&self, markdown: &str) -> Vec<Formatted>;
+}
+
+/// The formatter for a channel; channels that render markdown themselves
+/// (Feishu cards, DingTalk, Mochat) get the text unchanged.
+pub fn formatter_for(channel: &str) -> Box<dyn Formatter> {
+    match channel {
+        "telegram" => Box::new(TelegramFormatter),
+        "discord" => Box::new(DiscordFormatter),
+        "slack" => Box::new(SlackFormatter),
+        "whatsapp" => Box::new(PlainFormatter::new(4_000)),
+        "qq" => Box::new(PlainFormatter::new(2_000)),
+        "sms" => Box::new(PlainFormatter::new(1_600)),
+        _ => Box::new(MarkdownFormatter),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Parsing
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block<'a> {
+    Code { lang: &'a str, body: String },
+    Heading(&'a str),
+    Quote(&'a str),
+    Bullet(&'a str),
+    Table(Vec<&'a str>),
+    Line(&'a str),
+    Blank,
+}
+
+fn is_table_row(line: &str) -> bool {
+    let line = line.trim();
+    line.len() > 1 && line.starts_with('|')
+}
+
+fn heading_text(line: &str) -> Option<&str> {
+    let rest = line.trim_start_matches('#');
+    let level = line.len() - rest.len();
+    ((1..=6).contains(&level) && rest.starts_with(' ')).then(|| rest.trim())
+}
+
+fn parse_blocks(text: &str) -> Vec<Block<'_>> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            let mut body = String::new();
+            i += 1;
+            while i < lines.len() && !lines[i].trim_start().starts_with("```") {
+                body.push_str(lines[i]);
+                body.push('\n');
+                i += 1;
+            }
+            blocks.push(Block::Code {
+                lang: lang.trim(),
+                body,
+            });
+            i += 1;
+            continue;
+        }
+        if is_table_row(line) && lines.get(i + 1).is_some_and(|next| is_table_row(next)) {
+            let start = i;
+            while i < lines.len() && is_table_row(lines[i]) {
+                i += 1;
+            }
+            blocks.push(Block::Table(lines[start..i].to_vec()));
+            continue;
+        }
+        let block = if let Some(heading) = heading_text(trimmed) {
+            Block::Heading(heading)
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            Block::Quote(quote.trim_start())
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            Block::Bullet(item.trim_start())
+        } else if trimmed.is_empty() {
+            Block::Blank
+        } else {
+            Block::Line(line)
+        };
+        blocks.push(block);
+        i += 1;
+    }
+    blocks
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Inline<'a> {
+    Text(&'a str),
+    Code(&'a str),
+    Link { text: &'a str, url: &'a str },
+    Bold(Vec<Inline<'a>>),
+    Italic(Vec<Inline<'a>>),
+    Strike(Vec<Inline<'a>>),
+}
+
+/// `text` between `open` and the next `close` after it, if not empty.
+fn delimited<'a>(text: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let inner = text.strip_prefix(open)?;
+    let end = inner.find(close)?;
+    (end > 0).then(|| &inner[..end])
+}
+
+fn is_word_char(c: Option<char>) -> bool {
+    c.is_some_and(char::is_alphanumeric)
+}
+
+/// The span starting at `text[at..]`, with its length in bytes.
+fn inline_at(text: &str, at: usize) -> Option<(Inline<'_>, usize)> {
+    let rest = &text[at..];
+    match rest.as_bytes().first()? {
+        b'`' => delimited(rest, "`", "`").map(|code| (Inline::Code(code), code.len() + 2)),
+        b'[' => {
+            let label = delimited(rest, "[", "]")?;
+            let after = &rest[label.len() + 2..];
+            let url = delimited(after, "(", ")")?;
+            (!url.contains(char::is_whitespace)).then_some((
+                Inline::Link { text: label, url },
+                label.len() + url.len() + 4,
+            ))
+        }
+        b'*' | b'_' | b'~' => {
+            for marker in ["**", "__", "~~"] {
+                if let Some(inner) = delimited(rest, marker, marker) {
+                    let spans = parse_inline(inner);
+                    let span = if marker == "~~" {
+                        Inline::Strike(spans)
+                    } else {
+                        Inline::Bold(spans)
+                    };
+                    return Some((span, inner.len() + 4));
+                }
+            }
+            let marker = &rest[..1];
+            if marker == "~" {
+                return None;
+            }
+            let inner = delimited(rest, marker, marker)?;
+            if inner.starts_with(char::is_whitespace) || inner.ends_with(char::is_whitespace) {
+                return None;
+            }
+            // snake_case words are not emphasis.
+            if marker == "_"
+                && (is_word_char(text[..at].chars().next_back())
+                    || is_word_char(rest[inner.len() + 2..].chars().next()))
+            {
+                return None;
+            }
+            Some((Inline::Italic(parse_inline(inner)), inner.len() + 2))
+        }
+        _ => None,
+    }
+}
+
+fn parse_inline(text: &str) -> Vec<Inline<'_>> {
+    let mut spans = Vec::new();
+    let mut plain_start = 0;
+    let mut at = 0;
+    while at < text.len() {
+        if let Some((span, len)) = inline_at(text, at) {
+            if plain_start < at {
+                spans.push(Inline::Text(&text[plain_start..at]));
+            }
+            spans.push(span);
+            at += len;
+            plain_start = at;
+        } else {
+            at += text[at..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    if plain_start < text.len() {
+        spans.push(Inline::Text(&text[plain_start..]));
+    }
+    spans
+}
+
+// ---------------------------------------------------------------------------
+// Chunking
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Splits `text` after sentence ends (`.`, `!`, `?`, `。`, `！`, `？`).
+fn sentences(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends = match c {
+            '。' | '！' | '？' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if ends {
+            let end = i + c.len_utf8();
+            parts.push(&text[start..end]);
+            start = end;
+        }
+    }
+    if start < text.len() {
+        parts.push(&text[start..]);
+    }
+    parts
+}
+
+/// Packs `pieces` into chunks of at most `max_chars`, joined by `sep`.
+fn pack(pieces: Vec<String>, sep: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for piece in pieces {
+        let joined = char_len(&current) + char_len(sep) + char_len(&piece);
+        if !current.is_empty() && joined > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str(sep);
+        }
+        current.push_str(&piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Cuts one paragraph that is too long at sentences, then words, then
+/// characters.
+fn split_paragraph(text: &str, max_chars: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    for sentence in sentences(text) {
+        if char_len(sentence) <= max_chars {
+            pieces.push(sentence.to_string());
+            continue;
+        }
+        for word in sentence.split_inclusive(char::is_whitespace) {
+            if char_len(word) <= max_chars {
+                pieces.push(word.to_string());
+            } else {
+                let chars = word.chars().collect::<Vec<_>>();
+                pieces.extend(chars.chunks(max_chars).map(|c| c.iter().collect()));
+            }
+        }
+    }
+    pack(pieces, "", max_chars)
+        .into_iter()
+        .map(|chunk| chunk.trim().to_string())
+        .filter(|chunk| !chunk.is_empty())
+        .collect()
+}
+
+/// Cuts a code fence that is too long into fences of whole lines.
+fn split_code(fence: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = fence.lines();
+    let open = lines.next().unwrap_or("```");
+    let body = lines
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    let room = max_chars.saturating_sub(char_len(open) + 5).max(1);
+    pack(body, "\n", room)
+        .into_iter()
+        .map(|part| format!("{open}\n{part}\n```"))
+        .collect()
+}
+
+/// Splits `text` into chunks of at most `max_chars` characters at paragraph
+/// boundaries, cutting paragraphs that are too long at sentences.
+pub fn split_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let text = text.trim();
+    if char_len(text) <= max_chars {
+        return vec![text.to_string()];
+    }
+    // Paragraphs, with each code fence kept as one piece.
+    let mut paragraphs = Vec::new();
+    let mut current = String::new();
+    let mut in_code = false;
+    for line in text.lines() {
+        let fence = line.trim_start().starts_with("```");
+        if !in_code && line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if fence && !in_code && !current.is_empty() {
+            paragraphs.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+        if fence {
+            in_code = !in_code;
+            if !in_code {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+
+    let mut pieces = Vec::new();
+    for paragraph in paragraphs {
+        if char_len(&paragraph) <= max_chars {
+            pieces.push(paragraph);
+        } else if paragraph.trim_start().starts_with("```") {
+            pieces.extend(split_code(&paragraph, max_chars));
+        } else {
+            pieces.extend(split_paragraph(&paragraph, max_chars));
+        }
+    }
+    pack(pieces, "\n\n", max_chars)
+}
+
+// ---------------------------------------------------------------------------
+// Plain text
+
+fn plain_inline(spans: &[Inline<'_>], out: &mut String) {
+    for span in spans {
+        match span {
+            Inline::Text(text) | Inline::Code(text) => out.push_str(text),
+            Inline::Link { text, url } if text == url => out.push_str(url),
+            Inline::Link { text, url } => out.push_str(&format!("{text} ({url})")),
+            Inline::Bold(inner) | Inline::Italic(inner) | Inline::Strike(inner) => {
+                plain_inline(inner, out)
+            }
+        }
+    }
+}
+
+fn plain(text: &str) -> String {
+    let mut out = String::new();
+    plain_inline(&parse_inline(text), &mut out);
+    out
+}
+
+/// `markdown` without markup: links become `text (url)`, list items get a
+/// bullet, code keeps its content.
+pub fn to_plain(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for block in parse_blocks(markdown) {
+        match block {
+            Block::Code { body, .. } => lines.push(body.trim_end().to_string()),
+            Block::Heading(text) | Block::Quote(text) | Block::Line(text) => {
+                lines.push(plain(text))
+            }
+            Block::Bullet(text) => lines.push(format!("• {}", plain(text))),
+            Block::Table(rows) => lines.extend(rows.iter().map(|row| row.to_string())),
+            Block::Blank => lines.push(String::new()),
+        }
+    }
+    lines.join("\n")
+}
+
+/// Text without markup, for SMS-like channels.
+pub struct PlainFormatter {
+    max_chars: usize,
+}
+
+impl PlainFormatter {
+    pub fn new(max_chars: usize) -> Self {
+        Self {
+            max_chars: max_chars.max(1),
+        }
+    }
+}
+
+impl Formatter for PlainFormatter {
+    fn format(&self, markdown: &str) -> Vec<Formatted> {
+        split_chunks(&to_plain(markdown), self.max_chars)
+            .into_iter()
+            .map(Formatted::text)
+            .collect()
+    }
+}
+
+/// The text as written, for channels that render markdown.
+pub struct MarkdownFormatter;
+
+impl Formatter for MarkdownFormatter {
+    fn format(&self, markdown: &str) -> Vec<Formatted> {
+        vec![Formatted::text(markdown.to_string())]
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Telegram MarkdownV2
+
+fn escape_v2(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Inside code only the backtick and backslash are escaped.
+fn escape_v2_code(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('`', "\\`")
+}
+
+fn v2_inline(spans: &[Inline<'_>], out: &mut String) {
+    for span in spans {
+        match span {
+            Inline::Text(text) => out.push_str(&escape_v2(text)),
+            Inline::Code(code) => out.push_str(&format!("`{}`", escape_v2_code(code))),
+            Inline::Link { text, url } => {
+                out.push('[');
+                v2_inline(&parse_inline(text), out);
+                out.push_str("](");
+                out.push_str(&url.replace('\\', "\\\\").replace(')', "\\)"));
+                out.push(')');
+            }
+            Inline::Bold(inner) => wrap_v2("*", inner, out),
+            Inline::Italic(inner) => wrap_v2("_", inner, out),
+            Inline::Strike(inner) => wrap_v2("~", inner, out),
+        }
+    }
+}
+
+fn wrap_v2(marker: &str, inner: &[Inline<'_>], out: &mut String) {
+    out.push_str(marker);
+    v2_inline(inner, out);
+    out.push_str(marker);
+}
+
+fn v2(text: &str) -> String {
+    let mut out = String::new();
+    v2_inline(&parse_inline(text), &mut out);
+    out
+}
+
+/// `markdown` as Telegram MarkdownV2 (`parse_mode: MarkdownV2`).
+pub fn to_telegram_v2(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for block in parse_blocks(markdown) {
+        match block {
+            Block::Code { lang, body } => lines.push(format!(
+                "```{}\n{}```",
+                escape_v2_code(lang),
+                escape_v2_code(&body)
+            )),
+            Block::Heading(text) => lines.push(format!("*{}*", v2(text))),
+            Block::Quote(text) => lines.push(format!(">{}", v2(text))),
+            Block::Bullet(text) => lines.push(format!("• {}", v2(text))),
+            Block::Table(rows) => {
+                lines.push(format!("```\n{}\n```", escape_v2_code(&rows.join("\n"))))
+            }
+            Block::Line(text) => lines.push(v2(text)),
+            Block::Blank => lines.push(String::new()),
+        }
+    }
+    lines.join("\n")
+}
+
+pub struct TelegramFormatter;
+
+impl Formatter for TelegramFormatter {
+    fn format(&self, markdown: &str) -> Vec<Formatted> {
+        split_chunks(markdown, TELEGRAM_CHUNK_CHARS)
+            .iter()
+            .map(|chunk| Formatted::text(to_telegram_v2(chunk)))
+            .collect()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Slack mrkdwn
+
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn slack_inline(spans: &[Inline<'_>], out: &mut String) {
+    for span in spans {
+        match span {
+            Inline::Text(text) => out.push_str(&escape_slack(text)),
+            Inline::Code(code) => out.push_str(&format!("`{}`", escape_slack(code))),
+            Inline::Link { text, url } => out.push_str(&format!(
+                "<{}|{}>",
+                url,
+                escape_slack(&plain(text)).replace('|', "¦")
+            )),
+            Inline::Bold(inner) => wrap_slack("*", inner, out),
+            Inline::Italic(inner) => wrap_slack("_", inner, out),
+            Inline::Strike(inner) => wrap_slack("~", inner, out),
+        }
+    }
+}
+
+fn wrap_slack(marker: &str, inner: &[Inline<'_>], out: &mut String) {
+    out.push_str(marker);
+    slack_inline(inner, out);
+    out.push_str(marker);
+}
+
+fn mrkdwn(text: &str) -> String {
+    let mut out = String::new();
+    slack_inline(&parse_inline(text), &mut out);
+    out
+}
+
+fn slack_section(text: &str) -> Vec<Value> {
+    split_chunks(text, SLACK_SECTION_CHARS)
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .map(|part| json!({ "type": "section", "text": { "type": "mrkdwn", "text": part } }))
+        .collect()
+}
+
+/// `markdown` as Slack mrkdwn, and as blocks with headings as header blocks.
+pub fn to_slack(markdown: &str) -> (String, Vec<Value>) {
+    let mut text = Vec::new();
+    let mut blocks = Vec::new();
+    let mut section = Vec::new();
+    for block in parse_blocks(markdown) {
+        let line = match block {
+            Block::Code { body, .. } => format!("```\n{}```", escape_slack(&body)),
+            Block::Heading(heading) => {
+                blocks.extend(slack_section(section.join("\n").trim()));
+                section.clear();
+                let title = plain(heading)
+                    .chars()
+                    .take(SLACK_HEADER_CHARS)
+                    .collect::<String>();
+                blocks.push(json!({
+                    "type": "header",
+                    "text": { "type": "plain_text", "text": title }
+                }));
+                text.push(format!("*{}*", mrkdwn(heading)));
+                continue;
+            }
+            Block::Quote(quote) => format!(">{}", mrkdwn(quote)),
+            Block::Bullet(item) => format!("• {}", mrkdwn(item)),
+            Block::Table(rows) => format!("```\n{}\n```", escape_slack(&rows.join("\n"))),
+            Block::Line(line) => mrkdwn(line),
+            Block::Blank => String::new(),
+        };
+        text.push(line.clone());
+        section.push(line);
+    }
+    blocks.extend(slack_section(section.join("\n").trim()));
+    (text.join("\n"), blocks)
+}
+
+pub struct SlackFormatter;
+
+impl Formatter for SlackFormatter {
+    fn format(&self, markdown: &str) -> Vec<Formatted> {
+        split_chunks(markdown, SLACK_MESSAGE_CHARS)
+            .iter()
+            .map(|chunk| {
+                let (text, blocks) = to_slack(chunk);
+                Formatted {
+                    text,
+                    extra: Some(Value::Array(blocks)),
+                }
+            })
+            .collect()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Discord
+
+/// Discord renders markdown itself except tables, which become code blocks.
+fn to_discord(markdown: &str) -> String {
+    let mut lines = Vec::new();
+    for block in parse_blocks(markdown) {
+        match block {
+            Block::Code { lang, body } => lines.push(format!("```{lang}\n{body}```")),
+            Block::Table(rows) => lines.push(format!("```\n{}\n```", rows.join("\n"))),
+            Block::Heading(text) => lines.push(format!("## {text}")),
+            Block::Quote(text) => lines.push(format!("> {text}")),
+            Block::Bullet(text) => lines.push(format!("- {text}")),
+            Block::Line(text) => lines.push(text.to_string()),
+            Block::Blank => lines.push(String::new()),
+        }
+    }
+    lines.join("\n")
+}
+
+/// Short replies go out as message content. Longer ones become embeds, which
+/// hold twice as much; a leading heading becomes the embed title.
+pub struct DiscordFormatter;
+
+impl Formatter for DiscordFormatter {
+    fn format(&self, markdown: &str) -> Vec<Formatted> {
+        let text = to_discord(markdown);
+        if char_len(&text) <= DISCORD_CONTENT_CHARS {
+            return vec![Formatted::text(text)];
+        }
+        let (title, body) = match parse_blocks(markdown).first() {
+            Some(Block::Heading(heading)) => (
+                Some(
+                    plain(heading)
+                        .chars()
+                        .take(DISCORD_TITLE_CHARS)
+                        .collect::<String>(),
+                ),
+                to_discord(
+                    markdown
+                        .trim_start()
+                        .split_once('\n')
+                        .map_or("", |(_, rest)| rest),
+                ),
+            ),
+            _ => (None, text),
+        };
+        split_chunks(&body, DISCORD_EMBED_CHARS)
+            .into_iter()
+            .enumerate()
+            .map(|(i, description)| {
+                let mut embed = json!({ "description": description });
+                if i == 0
+                    && let Some(title) = &title
+                {
+                    embed["title"] = Value::String(title.clone());
+                }
+                Formatted {
+                    text: String::new(),
+                    extra: Some(json!([embed])),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn telegram_v2_escapes_text_and_keeps_code() {
+        let out = to_telegram_v2(
+            "# Plan v1.2\n**Bold** and _it_ with snake_case_name, [docs](https://x.io/a_b).\n```rust\nlet a = `b`;\n```\n- item 1.",
+        );
+        assert_eq!(
+            out,
+            "*Plan v1\\.2*\n*Bold* and _it_ with snake\\_case\\_name, [docs](https://x.io/a_b)\\.\n```rust\nlet a = \\`b\\`;\n```\n• item 1\\."
+        );
+    }
+
+    #[test]
+    fn slack_and_plain_render_links_and_headings() {
+        let (text, blocks) = to_slack("## Status\nAll **good** <3, see [the page](https://x.io).");
+        assert_eq!(
+            text,
+            "*Status*\nAll *good* &lt;3, see <https://x.io|the page>."
+        );
+        assert_eq!(blocks[0]["type"], "header");
+        assert_eq!(blocks[0]["text"]["text"], "Status");
+        assert_eq!(
+            blocks[1]["text"]["text"],
+            "All *good* &lt;3, see <https://x.io|the page>."
+        );
+        assert_eq!(
+            to_plain("- **Call** [Bob](tel:123) ~~today~~\n`code`"),
+            "• Call Bob (tel:123) today\ncode"
+        );
+    }
+
+    #[test]
+    fn long_replies_split_at_sentences_and_keep_code_fences() {
+        let text = format!(
+            "{}\n\n```\n{}```\n\nLast one.",
+            "One sentence here. ".repeat(10).trim(),
+            "line\n".repeat(30)
+        );
+        let chunks = split_chunks(&text, 100);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 100));
+        assert!(chunks[0].ends_with("here."));
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk.matches("```").count() % 2 == 0)
+        );
+        assert_eq!(
+            chunks.iter().filter(|chunk| chunk.contains("line")).count(),
+            2
+        );
+        assert!(chunks.last().unwrap().ends_with("```\n\nLast one."));
+
+        let sms =
+            PlainFormatter::new(40).format("**Hi** there. This is long enough to split twice.");
+        assert_eq!(
+            sms.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(),
+            ["Hi there.", "This is long enough to split twice."]
+        );
+
+        let long = format!("# Report\n{}", "word ".repeat(600));
+        let discord = DiscordFormatter.format(&long);
+        let embed = &discord[0].extra.as_ref().unwrap()[0];
+        assert_eq!(embed["title"], "Report");
+        assert!(discord[0].text.is_empty());
+    }
+}
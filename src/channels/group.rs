@@ -0,0 +1,146 @@
+//! Addressing rules for group chats (Telegram groups, Discord channels).
+//!
+//! With `group.policy` `mention` (the default) the bot answers a group only
+//! when it is mentioned, replied to, or addressed with one of
+//! `group.prefixes`; `open` answers every message. Messages the bot is not
+//! asked about are kept, up to `group.contextMessages` per group, and handed
+//! over with the next one that addresses it, so the reply can follow the
+//! conversation. Each group is one session (`channel:chat_id`), with every
+//! message tagged with its sender.
+
+use crate::agent::commands;
+use crate::config::GroupConfig;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Longest remembered group message.
+const CONTEXT_MESSAGE_CHARS: usize = 500;
+
+pub struct GroupGate {
+    config: GroupConfig,
+    /// Unanswered messages per group, oldest first.
+    recent: Mutex<HashMap<String, VecDeque<String>>>,
+}
+
+impl GroupGate {
+    pub fn new(config: GroupConfig) -> Self {
+        Self {
+            config,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// What to hand the agent for `text` from `sender` in group `chat_id`;
+    /// `None` when the bot is not addressed, in which case the message is
+    /// kept as context. Chat commands always pass unchanged.
+    pub fn admit(
+        &self,
+        chat_id: &str,
+        sender: &str,
+        text: &str,
+        mentioned: bool,
+    ) -> Option<String> {
+        let text = text.trim();
+        if commands::parse(text).is_some() {
+            return Some(text.to_string());
+        }
+        let addressed = if mentioned || self.config.policy == "open" {
+            Some(text)
+        } else {
+            self.strip_prefix(text)
+        };
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(text) = addressed else {
+            if self.config.context_messages > 0 && !text.is_empty() {
+                let line = text.chars().take(CONTEXT_MESSAGE_CHARS).collect::<String>();
+                let kept = recent.entry(chat_id.to_string()).or_default();
+                kept.push_back(format!("{sender}: {line}"));
+                while kept.len() > self.config.context_messages {
+                    kept.pop_front();
+                }
+            }
+            return None;
+        };
+        let mut content = String::new();
+        if let Some(kept) = recent.remove(chat_id).filter(|kept| !kept.is_empty()) {
+            content.push_str("[Earlier messages in this group]\n");
+            for line in kept {
+                content.push_str(&line);
+                content.push('\n');
+            }
+            content.push('\n');
+        }
+        content.push_str(&format!("[{sender}]: {text}"));
+        Some(content)
+    }
+
+    /// `text` without the prefix that addresses the bot, if it has one. A
+    /// prefix ending in a letter or digit must end a word: `nano` does not
+    /// address "nanobots".
+    fn strip_prefix<'a>(&self, text: &'a str) -> Option<&'a str> {
+        self.config
+            .prefixes
+            .iter()
+            .map(|prefix| prefix.trim())
+            .filter(|prefix| !prefix.is_empty())
+            .find_map(|prefix| {
+                let head = text.get(..prefix.len())?;
+                let rest = &text[prefix.len()..];
+                let word_prefix = prefix.ends_with(|c: char| c.is_alphanumeric());
+                let boundary = !word_prefix || !rest.starts_with(|c: char| c.is_alphanumeric());
+                (head.eq_ignore_ascii_case(prefix) && boundary)
+                    .then(|| rest.trim_start_matches([',', ':', ' ']))
+            })
+    }
+}
+
+/// `text` with every occurrence of `mention` (an ASCII handle such as
+/// `@my_bot` or `<@123>`) removed, ignoring case.
+pub fn strip_mention(text: &str, mention: &str) -> String {
+    let mut out = text.to_string();
+    if !mention.is_empty() {
+        let needle = mention.to_ascii_lowercase();
+        while let Some(start) = out.to_ascii_lowercase().find(&needle) {
+            out.replace_range(start..start + needle.len(), "");
+        }
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_addressed_messages_with_earlier_context() {
+        let gate = GroupGate::new(GroupConfig {
+            prefixes: vec!["nano".to_string()],
+            context_messages: 2,
+            ..GroupConfig::default()
+        });
+        for text in ["lunch?", "pizza again", "sure"] {
+            assert_eq!(gate.admit("g1", "Ann", text, false), None);
+        }
+        assert_eq!(
+            gate.admit("g1", "Bo", "/status", false).as_deref(),
+            Some("/status")
+        );
+        assert_eq!(
+            gate.admit("g1", "Bo", "Nano, where do we eat?", false)
+                .as_deref(),
+            Some(
+                "[Earlier messages in this group]\nAnn: pizza again\nAnn: sure\n\n[Bo]: where do we eat?"
+            )
+        );
+        assert_eq!(
+            gate.admit("g1", "Ann", "thanks", true).as_deref(),
+            Some("[Ann]: thanks")
+        );
+        assert_eq!(gate.admit("g2", "Cy", "nanobots are cool", false), None);
+
+        assert_eq!(
+            strip_mention("@NanoBot  what now?", "@nanobot"),
+            "what now?"
+        );
+    }
+}
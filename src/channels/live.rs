@@ -0,0 +1,103 @@
+//! Live replies: a turn's streamed text shown by editing one chat message.
+//!
+//! The agent publishes throttled outbound updates whose metadata carries
+//! `stream: { id, final }`. Channels that can edit messages send the first
+//! update, edit that message for the following ones and replace it with the
+//! formatted answer on the final one; other channels skip the partial updates
+//! and deliver the final message as usual.
+
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub const STREAM_KEY: &str = "stream";
+
+/// Channels that edit their live message rather than sending each update.
+pub const EDITABLE_CHANNELS: &[&str] = &["telegram", "discord", "slack"];
+
+/// Shown after partial text while the answer is still being written.
+const CURSOR: &str = " ▌";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveUpdate {
+    pub id: String,
+    /// The complete answer; the live message should become this.
+    pub done: bool,
+}
+
+impl LiveUpdate {
+    pub fn from_metadata(metadata: &Map<String, Value>) -> Option<Self> {
+        let stream = metadata.get(STREAM_KEY)?;
+        Some(Self {
+            id: stream.get("id")?.as_str()?.to_string(),
+            done: stream
+                .get("final")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        })
+    }
+
+    pub fn to_metadata(&self) -> Value {
+        json!({ "id": self.id, "final": self.done })
+    }
+}
+
+/// Partial text as plain text within `max` chars: markdown is not rendered
+/// until the answer is complete, and a long answer shows its latest part.
+pub fn partial_text(content: &str, max: usize) -> String {
+    let text = crate::channels::format::to_plain(content);
+    let text = text.trim_end();
+    let budget = max.saturating_sub(CURSOR.chars().count() + 1);
+    let count = text.chars().count();
+    if count <= budget {
+        return format!("{text}{CURSOR}");
+    }
+    let tail = text.chars().skip(count - budget).collect::<String>();
+    format!("…{tail}{CURSOR}")
+}
+
+/// The platform message id behind each live reply.
+#[derive(Default)]
+pub struct LiveMessages(Mutex<HashMap<String, String>>);
+
+impl LiveMessages {
+    pub fn get(&self, id: &str) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+    }
+
+    pub fn insert(&self, id: &str, message_id: String) {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string(), message_id);
+    }
+
+    pub fn remove(&self, id: &str) -> Option<String> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_updates_and_trims_partial_text() {
+        let mut metadata = Map::new();
+        assert_eq!(LiveUpdate::from_metadata(&metadata), None);
+        let update = LiveUpdate {
+            id: "t1".to_string(),
+            done: true,
+        };
+        metadata.insert(STREAM_KEY.to_string(), update.to_metadata());
+        assert_eq!(LiveUpdate::from_metadata(&metadata), Some(update));
+
+        assert_eq!(partial_text("**Hello**  ", 100), "Hello ▌");
+        let long = partial_text(&"abcdefghij".repeat(3), 12);
+        assert_eq!(long, "…bcdefghij ▌");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::bus::MessageBus;
+use crate::bus::{MessageBus, SystemEvent};
 use crate::channels::base::Channel;
 use crate::channels::dingtalk::DingTalkChannel;
 use crate::channels::discord::DiscordChannel;
@@ -10,17 +10,21 @@ use crate::channels::slack::SlackChannel;
 use crate::channels::telegram::TelegramChannel;
 use crate::channels::whatsapp::WhatsAppChannel;
 use crate::config::Config;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::Mutex;
+use tracing::{Instrument, error, info_span, warn};
 
 pub struct ChannelManager {
     bus: Arc<MessageBus>,
     channels: HashMap<String, Arc<dyn Channel>>,
     running: Arc<AtomicBool>,
     dispatch_task: Mutex<Option<tokio::task::JoinHandle<()>>>,
-    channel_tasks: Mutex<Vec<tokio::task::JoinHandle<()>>>,
+    channel_tasks: Mutex<HashMap<String, tokio::task::JoinHandle<()>>>,
+    /// Channels whose `start` returned `Ok`, e.g. for want of a token; they
+    /// are not restarted.
+    stopped: Arc<std::sync::Mutex<HashSet<String>>>,
 }
 
 impl ChannelManager {
@@ -119,7 +123,8 @@ impl ChannelManager {
             channels,
             running: Arc::new(AtomicBool::new(false)),
             dispatch_task: Mutex::new(None),
-            channel_tasks: Mutex::new(Vec::new()),
+            channel_tasks: Mutex::new(HashMap::new()),
+            stopped: Arc::default(),
         }
     }
 
@@ -143,7 +148,15 @@ impl ChannelManager {
             while running.load(Ordering::Relaxed) {
                 if let Some(msg) = bus.consume_outbound().await {
                     if let Some(channel) = channels_for_dispatch.get(&msg.channel) {
-                        let _ = channel.send(&msg).await;
+                        let span = info_span!(
+                            "channel.send",
+                            channel = %msg.channel,
+                            chat_id = %msg.chat_id,
+                            session_id = %format!("{}:{}", msg.channel, msg.chat_id)
+                        );
+                        if let Err(err) = channel.send(&msg).instrument(span).await {
+                            warn!(channel = %msg.channel, "outbound delivery failed: {err}");
+                        }
                     }
                 } else {
                     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -153,12 +166,8 @@ impl ChannelManager {
         *self.dispatch_task.lock().await = Some(dispatch);
 
         let mut tasks = self.channel_tasks.lock().await;
-        for channel in self.channels.values() {
-            let ch = channel.clone();
-            let task = tokio::spawn(async move {
-                let _ = ch.start().await;
-            });
-            tasks.push(task);
+        for (name, channel) in &self.channels {
+            tasks.insert(name.clone(), self.spawn_channel(channel.clone()));
         }
         drop(tasks);
 
@@ -177,11 +186,93 @@ impl ChannelManager {
             dispatch.abort();
         }
         let mut tasks = self.channel_tasks.lock().await;
-        for task in tasks.drain(..) {
+        for (_, task) in tasks.drain() {
             task.abort();
         }
     }
 
+    /// Runs the channel's receive loop; its end is published as a
+    /// `channel_exited` system event.
+    fn spawn_channel(&self, channel: Arc<dyn Channel>) -> tokio::task::JoinHandle<()> {
+        let bus = self.bus.clone();
+        let stopped = self.stopped.clone();
+        tokio::spawn(async move {
+            let detail = match channel.start().await {
+                Ok(()) => {
+                    warn!(
+                        channel = channel.name(),
+                        "channel stopped; not restarting it"
+                    );
+                    stopped
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(channel.name().to_string());
+                    "stopped".to_string()
+                }
+                Err(err) => {
+                    error!(channel = channel.name(), "channel exited with error: {err}");
+                    format!("error: {err}")
+                }
+            };
+            bus.publish(SystemEvent::new("channel_exited", channel.name(), detail));
+        })
+    }
+
+    /// Names of channels whose receive task has failed or panicked while the
+    /// manager is still running. Channels that stopped on their own are left
+    /// out.
+    pub async fn exited_channels(&self) -> Vec<String> {
+        if !self.running.load(Ordering::Relaxed) {
+            return Vec::new();
+        }
+        let tasks = self.channel_tasks.lock().await;
+        let stopped = self.stopped.lock().unwrap_or_else(|e| e.into_inner());
+        let mut exited = self
+            .channels
+            .keys()
+            .filter(|name| !stopped.contains(*name))
+            .filter(|name| tasks.get(*name).is_none_or(|task| task.is_finished()))
+            .cloned()
+            .collect::<Vec<_>>();
+        exited.sort();
+        exited
+    }
+
+    pub async fn restart_channel(&self, name: &str) -> bool {
+        let Some(channel) = self.channels.get(name) else {
+            return false;
+        };
+        warn!(channel = %name, "restarting channel task");
+        self.stopped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(name);
+        self.channel_tasks
+            .lock()
+            .await
+            .insert(name.to_string(), self.spawn_channel(channel.clone()));
+        true
+    }
+
+    /// True while the outbound dispatcher is running (or was never started).
+    pub async fn dispatcher_alive(&self) -> bool {
+        self.dispatch_task
+            .lock()
+            .await
+            .as_ref()
+            .is_none_or(|task| !task.is_finished())
+    }
+
+    /// Restarts channels whose receive task has exited while the manager is
+    /// still running. Returns the names of the restarted channels.
+    pub async fn restart_exited_channels(&self) -> Vec<String> {
+        let exited = self.exited_channels().await;
+        for name in &exited {
+            self.restart_channel(name).await;
+        }
+        exited
+    }
+
     pub fn get_status(&self) -> serde_json::Value {
         let mut map = serde_json::Map::new();
         for (name, channel) in &self.channels {
@@ -215,6 +306,8 @@ mod tests {
     struct MockChannel {
         name: String,
         running: AtomicBool,
+        /// Makes the next exit an error, like a lost connection.
+        fail: AtomicBool,
         allow_from: Vec<String>,
         bus: Arc<MessageBus>,
         sent: TokioMutex<Vec<OutboundMessage>>,
@@ -225,6 +318,7 @@ mod tests {
             Self {
                 name: name.to_string(),
                 running: AtomicBool::new(false),
+                fail: AtomicBool::new(false),
                 allow_from: Vec::new(),
                 bus,
                 sent: TokioMutex::new(Vec::new()),
@@ -255,6 +349,9 @@ mod tests {
             while self.running.load(Ordering::Relaxed) {
                 tokio::time::sleep(std::time::Duration::from_millis(10)).await;
             }
+            if self.fail.swap(false, Ordering::Relaxed) {
+                anyhow::bail!("connection lost");
+            }
             Ok(())
         }
 
@@ -317,4 +414,37 @@ mod tests {
         let _ = run_handle.await;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn restarts_channels_whose_task_exited() -> Result<()> {
+        let bus = Arc::new(MessageBus::new(16));
+        let mock = Arc::new(MockChannel::new("mock", bus.clone()));
+        let mut channels: HashMap<String, Arc<dyn Channel>> = HashMap::new();
+        channels.insert("mock".to_string(), mock.clone());
+        let manager = Arc::new(ChannelManager::from_channels(bus, channels));
+
+        let run_manager = manager.clone();
+        let run_handle = tokio::spawn(async move {
+            run_manager.start_all().await;
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(manager.restart_exited_channels().await.is_empty());
+
+        mock.fail.store(true, Ordering::Relaxed);
+        mock.stop().await?;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(manager.restart_exited_channels().await, vec!["mock"]);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(mock.is_running());
+
+        // A clean return, as for a channel without a token, is left alone.
+        mock.stop().await?;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(manager.restart_exited_channels().await.is_empty());
+        assert!(!mock.is_running());
+
+        manager.stop_all().await;
+        let _ = run_handle.await;
+        Ok(())
+    }
 }
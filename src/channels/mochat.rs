@@ -14,6 +14,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 use tokio::time::{Duration, sleep};
+use tracing::warn;
 
 const MAX_SEEN_MESSAGE_IDS: usize = 2000;
 
@@ -69,10 +70,10 @@ impl MochatChannel {
             config,
             bus,
             running: Arc::new(AtomicBool::new(false)),
-            client: reqwest::Client::builder()
+            client: crate::network::client_builder()
                 .timeout(Duration::from_secs(30))
                 .build()
-                .unwrap_or_else(|_| reqwest::Client::new()),
+                .unwrap_or_else(|_| crate::network::client()),
             shared: Arc::new(MochatShared::default()),
             cursor_path,
             initial_sessions: sessions,
@@ -120,7 +121,7 @@ impl Channel for MochatChannel {
 
     async fn start(&self) -> Result<()> {
         if self.config.claw_token.trim().is_empty() {
-            eprintln!("Mochat claw_token not configured");
+            warn!("Mochat claw_token not configured");
             return Ok(());
         }
         self.running.store(true, Ordering::Relaxed);
@@ -194,12 +195,11 @@ impl Channel for MochatChannel {
         if let Some(reply_to) = &msg.reply_to {
             body["replyTo"] = Value::String(reply_to.clone());
         }
-        if is_panel {
-            if let Some(group_id) = msg.metadata.get("group_id").and_then(Value::as_str) {
-                if !group_id.trim().is_empty() {
-                    body["groupId"] = Value::String(group_id.trim().to_string());
-                }
-            }
+        if is_panel
+            && let Some(group_id) = msg.metadata.get("group_id").and_then(Value::as_str)
+            && !group_id.trim().is_empty()
+        {
+            body["groupId"] = Value::String(group_id.trim().to_string());
         }
         let _ = post_json(&self.client, &self.config, path, &body).await?;
         Ok(())
@@ -207,57 +207,53 @@ impl Channel for MochatChannel {
 }
 
 async fn refresh_targets(rt: &Runtime) {
-    if rt.auto_discover_sessions {
-        if let Ok(resp) = post_json(
+    if rt.auto_discover_sessions
+        && let Ok(resp) = post_json(
             &rt.client,
             &rt.config,
             "/api/claw/sessions/list",
             &json!({}),
         )
         .await
-        {
-            if let Some(items) = resp.get("sessions").and_then(Value::as_array) {
-                let mut session_set = rt.shared.session_set.lock().await;
-                let cursors = rt.shared.cursors.lock().await;
-                let mut cold = rt.shared.cold_sessions.lock().await;
-                let mut converse = rt.shared.session_by_converse.lock().await;
-                for item in items {
-                    let Some(obj) = item.as_object() else {
-                        continue;
-                    };
-                    let sid = str_field(obj, &["sessionId"]);
-                    if sid.is_empty() {
-                        continue;
-                    }
-                    if session_set.insert(sid.clone()) && !cursors.contains_key(&sid) {
-                        cold.insert(sid.clone());
-                    }
-                    let cid = str_field(obj, &["converseId"]);
-                    if !cid.is_empty() {
-                        converse.insert(cid, sid);
-                    }
-                }
+        && let Some(items) = resp.get("sessions").and_then(Value::as_array)
+    {
+        let mut session_set = rt.shared.session_set.lock().await;
+        let cursors = rt.shared.cursors.lock().await;
+        let mut cold = rt.shared.cold_sessions.lock().await;
+        let mut converse = rt.shared.session_by_converse.lock().await;
+        for item in items {
+            let Some(obj) = item.as_object() else {
+                continue;
+            };
+            let sid = str_field(obj, &["sessionId"]);
+            if sid.is_empty() {
+                continue;
+            }
+            if session_set.insert(sid.clone()) && !cursors.contains_key(&sid) {
+                cold.insert(sid.clone());
+            }
+            let cid = str_field(obj, &["converseId"]);
+            if !cid.is_empty() {
+                converse.insert(cid, sid);
             }
         }
     }
-    if rt.auto_discover_panels {
-        if let Ok(resp) =
+    if rt.auto_discover_panels
+        && let Ok(resp) =
             post_json(&rt.client, &rt.config, "/api/claw/groups/get", &json!({})).await
-        {
-            if let Some(items) = resp.get("panels").and_then(Value::as_array) {
-                let mut panel_set = rt.shared.panel_set.lock().await;
-                for item in items {
-                    let Some(obj) = item.as_object() else {
-                        continue;
-                    };
-                    if obj.get("type").and_then(Value::as_i64).unwrap_or(0) != 0 {
-                        continue;
-                    }
-                    let pid = str_field(obj, &["id", "_id"]);
-                    if !pid.is_empty() {
-                        panel_set.insert(pid);
-                    }
-                }
+        && let Some(items) = resp.get("panels").and_then(Value::as_array)
+    {
+        let mut panel_set = rt.shared.panel_set.lock().await;
+        for item in items {
+            let Some(obj) = item.as_object() else {
+                continue;
+            };
+            if obj.get("type").and_then(Value::as_i64).unwrap_or(0) != 0 {
+                continue;
+            }
+            let pid = str_field(obj, &["id", "_id"]);
+            if !pid.is_empty() {
+                panel_set.insert(pid);
             }
         }
     }
@@ -340,7 +336,7 @@ async fn panel_worker(rt: Runtime, panel_id: String) {
     while rt.running.load(Ordering::Relaxed) {
         let req = json!({
             "panelId": panel_id,
-            "limit": rt.config.watch_limit.min(100).max(1),
+            "limit": rt.config.watch_limit.clamp(1, 100),
         });
         if let Ok(resp) = post_json(
             &rt.client,
@@ -615,18 +611,17 @@ fn resolve_was_mentioned(payload: &Map<String, Value>, agent_user_id: &str) -> b
     if agent_user_id.is_empty() {
         return false;
     }
-    if let Some(meta) = payload.get("meta").and_then(Value::as_object) {
-        if meta
+    if let Some(meta) = payload.get("meta").and_then(Value::as_object)
+        && (meta
             .get("mentioned")
             .and_then(Value::as_bool)
             .unwrap_or(false)
             || meta
                 .get("wasMentioned")
                 .and_then(Value::as_bool)
-                .unwrap_or(false)
-        {
-            return true;
-        }
+                .unwrap_or(false))
+    {
+        return true;
     }
     let Some(content) = payload.get("content").and_then(Value::as_str) else {
         return false;
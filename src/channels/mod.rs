@@ -3,6 +3,9 @@ pub mod dingtalk;
 pub mod discord;
 pub mod email;
 pub mod feishu;
+pub mod format;
+pub mod group;
+pub mod live;
 pub mod manager;
 pub mod mochat;
 pub mod qq;
@@ -11,6 +11,8 @@ use crate::bus::InboundMessage;
 #[cfg(feature = "qq-botrs")]
 use crate::channels::base::is_allowed_sender;
 #[cfg(feature = "qq-botrs")]
+use crate::channels::format::formatter_for;
+#[cfg(feature = "qq-botrs")]
 use crate::pairing::{issue_pairing, pairing_prompt};
 #[cfg(feature = "qq-botrs")]
 use botrs::models::message::C2CMessageParams;
@@ -155,7 +157,7 @@ impl Channel for QQChannel {
 
         #[cfg(not(feature = "qq-botrs"))]
         {
-            eprintln!("QQ support is disabled. Rebuild with --features qq-botrs.");
+            tracing::warn!("QQ support is disabled. Rebuild with --features qq-botrs.");
             self.running.store(false, Ordering::Relaxed);
             Ok(())
         }
@@ -188,9 +190,9 @@ impl Channel for QQChannel {
                     break;
                 }
                 if let Err(err) = run_result {
-                    eprintln!("QQ client error: {err}");
+                    tracing::error!("QQ client error: {err}");
                 } else {
-                    eprintln!("QQ client disconnected unexpectedly.");
+                    tracing::error!("QQ client disconnected unexpectedly.");
                 }
                 tokio::time::sleep(std::time::Duration::from_secs(5)).await;
             }
@@ -223,15 +225,17 @@ impl Channel for QQChannel {
             let Some(ctx) = ctx else {
                 return Ok(());
             };
-            let params = C2CMessageParams {
-                msg_type: 0,
-                content: Some(msg.content.clone()),
-                ..Default::default()
-            };
-            ctx.api
-                .post_c2c_message_with_params(&ctx.token, &msg.chat_id, params)
-                .await
-                .map_err(|e| anyhow!("failed to send QQ C2C message: {e}"))?;
+            for part in formatter_for("qq").format(&msg.content) {
+                let params = C2CMessageParams {
+                    msg_type: 0,
+                    content: Some(part.text),
+                    ..Default::default()
+                };
+                ctx.api
+                    .post_c2c_message_with_params(&ctx.token, &msg.chat_id, params)
+                    .await
+                    .map_err(|e| anyhow!("failed to send QQ C2C message: {e}"))?;
+            }
             Ok(())
         }
     }
@@ -1,5 +1,7 @@
 use crate::bus::{MessageBus, OutboundMessage};
 use crate::channels::base::Channel;
+use crate::channels::format::{Formatter, SLACK_MESSAGE_CHARS, SlackFormatter};
+use crate::channels::live::{self, LiveMessages, LiveUpdate};
 use crate::config::SlackConfig;
 use crate::pairing::{issue_pairing, pairing_prompt};
 use anyhow::{Result, anyhow};
@@ -11,6 +13,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::Mutex;
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message as WsMessage;
+use tracing::{debug, warn};
 
 pub struct SlackChannel {
     config: SlackConfig,
@@ -18,6 +21,7 @@ pub struct SlackChannel {
     running: AtomicBool,
     client: reqwest::Client,
     bot_user_id: Mutex<Option<String>>,
+    live: LiveMessages,
 }
 
 impl SlackChannel {
@@ -26,8 +30,9 @@ impl SlackChannel {
             config,
             bus,
             running: AtomicBool::new(false),
-            client: reqwest::Client::new(),
+            client: crate::network::client(),
             bot_user_id: Mutex::new(None),
+            live: LiveMessages::default(),
         }
     }
 
@@ -87,6 +92,33 @@ impl SlackChannel {
         Ok(payload)
     }
 
+    /// Shows the partial answer of a live reply, posting its message on the
+    /// first update and updating it afterwards.
+    async fn send_partial(
+        &self,
+        msg: &OutboundMessage,
+        id: &str,
+        thread_ts: Option<&str>,
+    ) -> Result<()> {
+        let mut body = json!({
+            "channel": msg.chat_id,
+            "text": live::partial_text(&msg.content, SLACK_MESSAGE_CHARS),
+        });
+        if let Some(ts) = self.live.get(id) {
+            body["ts"] = Value::String(ts);
+            self.post_slack_api("chat.update", body).await?;
+            return Ok(());
+        }
+        if let Some(thread_ts) = thread_ts {
+            body["thread_ts"] = Value::String(thread_ts.to_string());
+        }
+        let posted = self.post_slack_api("chat.postMessage", body).await?;
+        if let Some(ts) = posted["ts"].as_str() {
+            self.live.insert(id, ts.to_string());
+        }
+        Ok(())
+    }
+
     fn is_allowed(&self, sender_id: &str, chat_id: &str, channel_type: &str) -> bool {
         if channel_type == "im" {
             if !self.config.dm.enabled {
@@ -255,11 +287,11 @@ impl Channel for SlackChannel {
 
     async fn start(&self) -> Result<()> {
         if self.config.bot_token.is_empty() || self.config.app_token.is_empty() {
-            eprintln!("Slack bot/app token not configured");
+            warn!("Slack bot/app token not configured");
             return Ok(());
         }
         if self.config.mode != "socket" {
-            eprintln!("Unsupported Slack mode: {}", self.config.mode);
+            warn!("Unsupported Slack mode: {}", self.config.mode);
             return Ok(());
         }
 
@@ -295,7 +327,7 @@ impl Channel for SlackChannel {
                             payload.get("envelope_id").and_then(Value::as_str)
                         {
                             let ack = json!({ "envelope_id": envelope_id }).to_string();
-                            let _ = write.send(WsMessage::Text(ack.into())).await;
+                            let _ = write.send(WsMessage::Text(ack)).await;
                         }
                         if payload.get("type").and_then(Value::as_str) != Some("events_api") {
                             continue;
@@ -331,15 +363,39 @@ impl Channel for SlackChannel {
             .unwrap_or("");
         let use_thread = thread_ts.is_some() && channel_type != "im";
 
-        let mut body = json!({
-            "channel": msg.chat_id,
-            "text": msg.content,
-        });
-        if use_thread {
-            body["thread_ts"] = Value::String(thread_ts.unwrap_or_default().to_string());
+        let update = LiveUpdate::from_metadata(&msg.metadata);
+        if let Some(update) = update.as_ref().filter(|update| !update.done) {
+            let thread_ts = thread_ts.filter(|_| use_thread);
+            if let Err(err) = self.send_partial(msg, &update.id, thread_ts).await {
+                debug!("failed to update live reply: {err:#}");
+            }
+            return Ok(());
+        }
+        let mut edit = update.and_then(|update| self.live.remove(&update.id));
+
+        for part in SlackFormatter.format(&msg.content) {
+            let mut body = json!({
+                "channel": msg.chat_id,
+                "text": part.text,
+            });
+            if let Some(blocks) = part.extra {
+                body["blocks"] = blocks;
+            }
+            if use_thread {
+                body["thread_ts"] = Value::String(thread_ts.unwrap_or_default().to_string());
+            }
+            if let Some(ts) = edit.take() {
+                let mut update = body.clone();
+                update["ts"] = Value::String(ts);
+                if let Some(map) = update.as_object_mut() {
+                    map.remove("thread_ts");
+                }
+                if self.post_slack_api("chat.update", update).await.is_ok() {
+                    continue;
+                }
+            }
+            let _ = self.post_slack_api("chat.postMessage", body).await?;
         }
-
-        let _ = self.post_slack_api("chat.postMessage", body).await?;
         Ok(())
     }
 }
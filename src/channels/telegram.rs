@@ -1,83 +1,42 @@
+use crate::access;
+use crate::agent::approval::{PendingApprovals, Resolution, parse_approval_callback};
+use crate::agent::commands::{self, COMMANDS};
 use crate::bus::{MessageBus, OutboundMessage};
 use crate::channels::base::Channel;
+use crate::channels::format;
+use crate::channels::group::{GroupGate, strip_mention};
+use crate::channels::live::{self, LiveMessages, LiveUpdate};
 use crate::config::TelegramConfig;
+use crate::i18n::tr;
 use crate::providers::transcription::GroqTranscriptionProvider;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use html_escape::encode_text;
-use regex::Regex;
+use reqwest::multipart::{Form, Part};
 use reqwest::{Client, Proxy};
 use serde_json::{Map, Value, json};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
-
-fn markdown_to_telegram_html(text: &str) -> String {
-    if text.is_empty() {
-        return String::new();
-    }
-    let mut content = text.to_string();
-
-    let code_block_re =
-        Regex::new(r"(?s)```[\w]*\n?([\s\S]*?)```").expect("valid code block regex");
-    let inline_code_re = Regex::new(r"`([^`]+)`").expect("valid inline code regex");
-    let header_re = Regex::new(r"(?m)^#{1,6}\s+(.+)$").expect("valid header regex");
-    let quote_re = Regex::new(r"(?m)^>\s*(.*)$").expect("valid quote regex");
-    let link_re = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").expect("valid link regex");
-    let bold_star_re = Regex::new(r"\*\*(.+?)\*\*").expect("valid bold regex");
-    let bold_underscore_re = Regex::new(r"__(.+?)__").expect("valid bold underscore regex");
-    let italic_re =
-        Regex::new(r"(?m)(^|[^A-Za-z0-9])_([^_]+)_([^A-Za-z0-9]|$)").expect("valid italic regex");
-    let strike_re = Regex::new(r"~~(.+?)~~").expect("valid strike regex");
-    let bullet_re = Regex::new(r"(?m)^[-*]\s+").expect("valid bullet regex");
-
-    let mut code_blocks = Vec::new();
-    content = code_block_re
-        .replace_all(&content, |caps: &regex::Captures<'_>| {
-            let idx = code_blocks.len();
-            code_blocks.push(caps[1].to_string());
-            format!("\u{0001}CB{idx}\u{0002}")
-        })
-        .to_string();
-
-    let mut inline_codes = Vec::new();
-    content = inline_code_re
-        .replace_all(&content, |caps: &regex::Captures<'_>| {
-            let idx = inline_codes.len();
-            inline_codes.push(caps[1].to_string());
-            format!("\u{0001}IC{idx}\u{0002}")
+use tracing::{debug, error, warn};
+
+fn approval_keyboard(metadata: &Map<String, Value>) -> Option<Value> {
+    let options = metadata.get("approval")?.get("options")?.as_array()?;
+    let buttons = options
+        .iter()
+        .filter_map(|option| {
+            Some(json!({
+                "text": option.get("label")?.as_str()?,
+                "callback_data": option.get("callback")?.as_str()?,
+            }))
         })
-        .to_string();
-
-    content = header_re.replace_all(&content, "$1").to_string();
-    content = quote_re.replace_all(&content, "$1").to_string();
-    content = encode_text(&content).to_string();
-    content = link_re
-        .replace_all(&content, r#"<a href="$2">$1</a>"#)
-        .to_string();
-    content = bold_star_re.replace_all(&content, "<b>$1</b>").to_string();
-    content = bold_underscore_re
-        .replace_all(&content, "<b>$1</b>")
-        .to_string();
-    content = italic_re.replace_all(&content, "$1<i>$2</i>$3").to_string();
-    content = strike_re.replace_all(&content, "<s>$1</s>").to_string();
-    content = bullet_re.replace_all(&content, "• ").to_string();
-
-    for (idx, value) in inline_codes.iter().enumerate() {
-        let token = format!("\u{0001}IC{idx}\u{0002}");
-        let escaped = encode_text(value);
-        content = content.replace(&token, &format!("<code>{escaped}</code>"));
+        .collect::<Vec<_>>();
+    if buttons.is_empty() {
+        return None;
     }
-    for (idx, value) in code_blocks.iter().enumerate() {
-        let token = format!("\u{0001}CB{idx}\u{0002}");
-        let escaped = encode_text(value);
-        content = content.replace(&token, &format!("<pre><code>{escaped}</code></pre>"));
-    }
-
-    content
+    Some(json!({ "inline_keyboard": [buttons] }))
 }
 
 pub struct TelegramChannel {
@@ -88,35 +47,20 @@ pub struct TelegramChannel {
     offset: Mutex<i64>,
     groq_api_key: String,
     typing_tasks: Mutex<HashMap<String, JoinHandle<()>>>,
-}
-
-#[cfg(test)]
-mod tests {
-    use super::markdown_to_telegram_html;
-
-    #[test]
-    fn markdown_converter_preserves_code_blocks_and_escapes_html() {
-        let input = "```rust\nlet x = 1 < 2;\n```\ntext";
-        let out = markdown_to_telegram_html(input);
-        assert!(out.contains("<pre><code>let x = 1 &lt; 2;\n</code></pre>"));
-        assert!(out.contains("text"));
-    }
-
-    #[test]
-    fn markdown_converter_formats_links_and_styles() {
-        let input = "[site](https://example.com) **b** _i_ ~~s~~";
-        let out = markdown_to_telegram_html(input);
-        assert!(out.contains(r#"<a href="https://example.com">site</a>"#));
-        assert!(out.contains("<b>b</b>"));
-        assert!(out.contains("<i>i</i>"));
-        assert!(out.contains("<s>s</s>"));
-    }
+    live: LiveMessages,
+    group: GroupGate,
+    /// The bot's user id and username, from `getMe`.
+    me: Mutex<Option<(i64, String)>>,
 }
 
 impl TelegramChannel {
     fn build_http_client(proxy: Option<&str>) -> Client {
-        let base_builder = || {
-            Client::builder()
+        let base_builder = |proxy: Option<Proxy>| {
+            proxy
+                .map_or_else(
+                    crate::network::client_builder,
+                    crate::network::client_builder_via,
+                )
                 .connect_timeout(std::time::Duration::from_secs(30))
                 .timeout(std::time::Duration::from_secs(60))
                 .pool_max_idle_per_host(16)
@@ -124,22 +68,29 @@ impl TelegramChannel {
 
         if let Some(proxy_url) = proxy {
             match Proxy::all(proxy_url) {
-                Ok(proxy) => base_builder().proxy(proxy).build().unwrap_or_else(|err| {
-                    eprintln!("Telegram HTTP client build with proxy failed ({proxy_url}): {err}");
-                    base_builder().build().unwrap_or_else(|_| Client::new())
+                Ok(proxy) => base_builder(Some(proxy)).build().unwrap_or_else(|err| {
+                    error!("Telegram HTTP client build with proxy failed ({proxy_url}): {err}");
+                    base_builder(None)
+                        .build()
+                        .unwrap_or_else(|_| crate::network::client())
                 }),
                 Err(err) => {
-                    eprintln!("Telegram proxy URL is invalid ({proxy_url}): {err}");
-                    base_builder().build().unwrap_or_else(|_| Client::new())
+                    warn!("Telegram proxy URL is invalid ({proxy_url}): {err}");
+                    base_builder(None)
+                        .build()
+                        .unwrap_or_else(|_| crate::network::client())
                 }
             }
         } else {
-            base_builder().build().unwrap_or_else(|_| Client::new())
+            base_builder(None)
+                .build()
+                .unwrap_or_else(|_| crate::network::client())
         }
     }
 
     pub fn new(config: TelegramConfig, bus: Arc<MessageBus>, groq_api_key: String) -> Self {
         let client = Self::build_http_client(config.proxy.as_deref());
+        let group = GroupGate::new(config.group.clone());
         Self {
             config,
             bus,
@@ -148,6 +99,9 @@ impl TelegramChannel {
             offset: Mutex::new(0),
             groq_api_key,
             typing_tasks: Mutex::new(HashMap::new()),
+            live: LiveMessages::default(),
+            group,
+            me: Mutex::new(None),
         }
     }
 
@@ -221,6 +175,161 @@ impl TelegramChannel {
             .await;
     }
 
+    /// Sends `msg` in chunks as MarkdownV2, falling back to plain text for a
+    /// chunk Telegram rejects. With `edit`, the first chunk replaces that
+    /// message instead. Approval buttons go on the last message.
+    async fn send_content(&self, msg: &OutboundMessage, edit: Option<&str>) -> Result<()> {
+        let keyboard = approval_keyboard(&msg.metadata);
+        let chunks = format::split_chunks(&msg.content, format::TELEGRAM_CHUNK_CHARS);
+        let last = chunks.len().saturating_sub(1);
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut payload = json!({
+                "chat_id": msg.chat_id,
+                "text": format::to_telegram_v2(chunk),
+                "parse_mode": "MarkdownV2"
+            });
+            if i == last
+                && let Some(keyboard) = &keyboard
+            {
+                payload["reply_markup"] = keyboard.clone();
+            }
+            if i == 0
+                && let Some(message_id) = edit.and_then(|id| id.parse::<i64>().ok())
+            {
+                let mut edit_payload = payload.clone();
+                edit_payload["message_id"] = json!(message_id);
+                if self
+                    .post_chunk("editMessageText", edit_payload, chunk)
+                    .await
+                {
+                    continue;
+                }
+            }
+            if !self.post_chunk("sendMessage", payload, chunk).await {
+                return Err(anyhow!(
+                    "telegram rejected message chunk {} of {}",
+                    i + 1,
+                    chunks.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Posts one formatted chunk, retrying as plain text; false when both fail.
+    async fn post_chunk(&self, method: &str, mut payload: Value, chunk: &str) -> bool {
+        let sent = |payload: Value| async move {
+            self.client
+                .post(self.api_url(method))
+                .json(&payload)
+                .send()
+                .await
+                .is_ok_and(|response| response.status().is_success())
+        };
+        if sent(payload.clone()).await {
+            return true;
+        }
+        payload["text"] = Value::String(format::to_plain(chunk));
+        if let Some(map) = payload.as_object_mut() {
+            map.remove("parse_mode");
+        }
+        sent(payload).await
+    }
+
+    /// Shows the partial answer of a live reply, sending its message on the
+    /// first update and editing it afterwards.
+    async fn send_partial(&self, msg: &OutboundMessage, id: &str) -> Result<()> {
+        let text = live::partial_text(&msg.content, format::TELEGRAM_CHUNK_CHARS);
+        if let Some(message_id) = self.live.get(id).and_then(|id| id.parse::<i64>().ok()) {
+            self.client
+                .post(self.api_url("editMessageText"))
+                .json(&json!({ "chat_id": msg.chat_id, "message_id": message_id, "text": text }))
+                .send()
+                .await?;
+            return Ok(());
+        }
+        let response: Value = self
+            .client
+            .post(self.api_url("sendMessage"))
+            .json(&json!({ "chat_id": msg.chat_id, "text": text }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(message_id) = response["result"]["message_id"].as_i64() {
+            self.live.insert(id, message_id.to_string());
+        }
+        Ok(())
+    }
+
+    /// Learns the bot's id and username, used to spot mentions in groups.
+    async fn identify(&self) {
+        let response = match self.client.post(self.api_url("getMe")).send().await {
+            Ok(response) => response.json::<Value>().await,
+            Err(err) => Err(err),
+        };
+        match response {
+            Ok(body) => {
+                let id = body["result"]["id"].as_i64().unwrap_or_default();
+                let username = body["result"]["username"].as_str().unwrap_or_default();
+                *self.me.lock().await = Some((id, username.to_string()));
+            }
+            Err(err) => warn!("failed to look up the Telegram bot: {err}"),
+        }
+    }
+
+    /// Whether a group message mentions the bot or replies to it; the text
+    /// without the mention.
+    async fn addressing(&self, message: &Value, text: &str) -> (bool, String) {
+        let Some((id, username)) = self.me.lock().await.clone() else {
+            return (false, text.trim().to_string());
+        };
+        let handle = format!("@{username}");
+        let mentioned = !username.is_empty()
+            && text
+                .to_ascii_lowercase()
+                .contains(&handle.to_ascii_lowercase());
+        let replied = message["reply_to_message"]["from"]["id"].as_i64() == Some(id);
+        (mentioned || replied, strip_mention(text, &handle))
+    }
+
+    /// Publishes the chat commands as the bot's command menu.
+    async fn register_commands(&self) {
+        let menu = COMMANDS
+            .iter()
+            .map(|spec| json!({ "command": spec.name, "description": tr(spec.description) }))
+            .collect::<Vec<_>>();
+        let result = self
+            .client
+            .post(self.api_url("setMyCommands"))
+            .json(&json!({ "commands": menu }))
+            .send()
+            .await;
+        if let Err(err) = result.and_then(|response| response.error_for_status()) {
+            warn!("failed to register Telegram commands: {err}");
+        }
+    }
+
+    async fn send_photo(&self, chat_id: &str, path: &Path) -> Result<()> {
+        let bytes = tokio::fs::read(path).await?;
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("image.png")
+            .to_string();
+        let form = Form::new()
+            .text("chat_id", chat_id.to_string())
+            .part("photo", Part::bytes(bytes).file_name(name));
+        self.client
+            .post(self.api_url("sendPhoto"))
+            .multipart(form)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
     async fn start_typing(&self, chat_id: &str) {
         self.stop_typing(chat_id).await;
         let api_url = self.api_url("sendChatAction");
@@ -271,7 +380,75 @@ impl TelegramChannel {
         }
     }
 
+    async fn handle_callback_query(&self, callback: &Value) -> Result<()> {
+        let user_id = callback
+            .get("from")
+            .and_then(|v| v.get("id"))
+            .and_then(Value::as_i64)
+            .unwrap_or_default();
+        let sender_id = match callback
+            .get("from")
+            .and_then(|v| v.get("username"))
+            .and_then(Value::as_str)
+        {
+            Some(username) => format!("{user_id}|{username}"),
+            None => user_id.to_string(),
+        };
+        let chat_id = callback
+            .get("message")
+            .and_then(|v| v.get("chat"))
+            .and_then(|v| v.get("id"))
+            .and_then(Value::as_i64)
+            .unwrap_or_default()
+            .to_string();
+        let data = callback.get("data").and_then(Value::as_str).unwrap_or("");
+        let answer = match parse_approval_callback(data) {
+            Some(_) if !self.is_allowed(&sender_id) => "Not allowed".to_string(),
+            Some((id, decision)) => {
+                let user = access::user_id(self.name(), &sender_id, "");
+                match PendingApprovals::global().resolve(
+                    &id,
+                    self.name(),
+                    &chat_id,
+                    &user,
+                    decision,
+                ) {
+                    Resolution::Answered => decision.label().to_string(),
+                    Resolution::NotAllowed => "Not allowed".to_string(),
+                    Resolution::Expired => "This request has expired".to_string(),
+                }
+            }
+            None => String::new(),
+        };
+        let _ = self
+            .client
+            .post(self.api_url("answerCallbackQuery"))
+            .json(&json!({
+                "callback_query_id": callback.get("id").cloned().unwrap_or(Value::Null),
+                "text": answer,
+            }))
+            .send()
+            .await;
+
+        if let Some(message) = callback.get("message") {
+            let _ = self
+                .client
+                .post(self.api_url("editMessageReplyMarkup"))
+                .json(&json!({
+                    "chat_id": message.get("chat").and_then(|v| v.get("id")).cloned().unwrap_or(Value::Null),
+                    "message_id": message.get("message_id").cloned().unwrap_or(Value::Null),
+                    "reply_markup": { "inline_keyboard": [] },
+                }))
+                .send()
+                .await;
+        }
+        Ok(())
+    }
+
     async fn handle_update(&self, update: &Value) -> Result<()> {
+        if let Some(callback) = update.get("callback_query") {
+            return self.handle_callback_query(callback).await;
+        }
         let Some(message) = update.get("message") else {
             return Ok(());
         };
@@ -331,14 +508,15 @@ impl TelegramChannel {
                         )
                         .await;
                     }
-                    "help" | "new" | "reset" => {
-                        let forwarded = if command == "reset" {
-                            "/new".to_string()
-                        } else {
-                            format!("/{command}")
-                        };
-                        self.handle_message(sender_id, chat_id, forwarded, Vec::new(), Map::new())
-                            .await?;
+                    _ if commands::parse(text).is_some() => {
+                        self.handle_message(
+                            sender_id,
+                            chat_id,
+                            text.to_string(),
+                            Vec::new(),
+                            Map::new(),
+                        )
+                        .await?;
                     }
                     _ => {}
                 }
@@ -349,6 +527,21 @@ impl TelegramChannel {
         if let Some(caption) = message.get("caption").and_then(Value::as_str) {
             content_parts.push(caption.to_string());
         }
+        let is_group = message["chat"]["type"]
+            .as_str()
+            .is_some_and(|chat_type| chat_type != "private");
+        if is_group {
+            let said = content_parts.join("\n");
+            let (mentioned, said) = self.addressing(message, &said).await;
+            let sender = user["first_name"]
+                .as_str()
+                .or(username)
+                .unwrap_or("someone");
+            let Some(content) = self.group.admit(&chat_id, sender, &said, mentioned) else {
+                return Ok(());
+            };
+            content_parts = vec![content];
+        }
 
         let mut media_file_id = None::<String>;
         let mut media_type = None::<String>;
@@ -433,17 +626,7 @@ impl TelegramChannel {
             "first_name".to_string(),
             user.get("first_name").cloned().unwrap_or(Value::Null),
         );
-        metadata.insert(
-            "is_group".to_string(),
-            Value::Bool(
-                message
-                    .get("chat")
-                    .and_then(|v| v.get("type"))
-                    .and_then(Value::as_str)
-                    .map(|t| t != "private")
-                    .unwrap_or(false),
-            ),
-        );
+        metadata.insert("is_group".to_string(), Value::Bool(is_group));
 
         self.start_typing(&chat_id).await;
 
@@ -485,6 +668,8 @@ impl Channel for TelegramChannel {
             return Ok(());
         }
         self.running.store(true, Ordering::Relaxed);
+        self.identify().await;
+        self.register_commands().await;
 
         while self.running.load(Ordering::Relaxed) {
             let offset = *self.offset.lock().await;
@@ -494,14 +679,14 @@ impl Channel for TelegramChannel {
                 .json(&json!({
                     "offset": if offset > 0 { Value::Number(offset.into()) } else { Value::Null },
                     "timeout": 20,
-                    "allowed_updates": ["message"]
+                    "allowed_updates": ["message", "callback_query"]
                 }))
                 .send()
                 .await;
 
             let Ok(response) = response else {
                 if let Err(err) = response {
-                    eprintln!("Telegram polling request error: {err}");
+                    error!("Telegram polling request error: {err}");
                 }
                 tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                 continue;
@@ -509,14 +694,14 @@ impl Channel for TelegramChannel {
             let body: Value = match response.json().await {
                 Ok(body) => body,
                 Err(err) => {
-                    eprintln!("Telegram polling decode error: {err}");
+                    error!("Telegram polling decode error: {err}");
                     tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                     continue;
                 }
             };
             if !body.get("ok").and_then(Value::as_bool).unwrap_or(false) {
                 if let Some(desc) = body.get("description").and_then(Value::as_str) {
-                    eprintln!("Telegram polling returned not ok: {desc}");
+                    warn!("Telegram polling returned not ok: {desc}");
                 }
                 tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                 continue;
@@ -528,7 +713,7 @@ impl Channel for TelegramChannel {
                         *self.offset.lock().await = update_id + 1;
                     }
                     if let Err(err) = self.handle_update(update).await {
-                        eprintln!("Telegram update handling error: {err}");
+                        error!("Telegram update handling error: {err}");
                     }
                 }
             }
@@ -546,25 +731,44 @@ impl Channel for TelegramChannel {
     }
 
     async fn send(&self, msg: &OutboundMessage) -> Result<()> {
-        self.stop_typing(&msg.chat_id).await;
-        let html = markdown_to_telegram_html(&msg.content);
-        let first_try = self
-            .client
-            .post(self.api_url("sendMessage"))
-            .json(&json!({
-                "chat_id": msg.chat_id,
-                "text": html,
-                "parse_mode": "HTML"
-            }))
-            .send()
-            .await?;
-
-        if first_try.status().is_success() {
+        let update = LiveUpdate::from_metadata(&msg.metadata);
+        if let Some(update) = update.as_ref().filter(|update| !update.done) {
+            if let Err(err) = self.send_partial(msg, &update.id).await {
+                debug!("failed to update live reply: {err:#}");
+            }
             return Ok(());
         }
-
-        self.send_text_message(&msg.chat_id, &msg.content, None)
-            .await;
+        self.stop_typing(&msg.chat_id).await;
+        let edit = update.and_then(|update| self.live.remove(&update.id));
+        self.send_content(msg, edit.as_deref()).await?;
+        for path in &msg.media {
+            if let Err(err) = self.send_photo(&msg.chat_id, Path::new(path)).await {
+                warn!("failed to send photo {path}: {err:#}");
+            }
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::approval_keyboard;
+    use crate::agent::approval::ApprovalRequest;
+    use serde_json::Map;
+
+    #[test]
+    fn approval_metadata_renders_inline_keyboard() {
+        let request = ApprovalRequest::new("exec", &Map::new(), "telegram", "42");
+        let mut metadata = Map::new();
+        metadata.insert("approval".to_string(), request.to_metadata());
+        let keyboard = approval_keyboard(&metadata).expect("keyboard");
+        let row = keyboard["inline_keyboard"][0].as_array().expect("row");
+        assert_eq!(row.len(), 3);
+        assert_eq!(row[0]["text"], "Approve");
+        assert_eq!(
+            row[1]["callback_data"],
+            format!("approval:{}:deny", request.id)
+        );
+        assert!(approval_keyboard(&Map::new()).is_none());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::bus::{MessageBus, OutboundMessage};
 use crate::channels::base::Channel;
+use crate::channels::format::formatter_for;
 use crate::config::WhatsAppConfig;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
@@ -9,6 +10,7 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::{Mutex, mpsc};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{error, info};
 
 pub struct WhatsAppChannel {
     config: WhatsAppConfig,
@@ -146,18 +148,18 @@ impl Channel for WhatsAppChannel {
                         let is_connected = status == "connected";
                         self.connected.store(is_connected, Ordering::Relaxed);
                         if !status.is_empty() {
-                            eprintln!("WhatsApp status: {status}");
+                            info!("WhatsApp status: {status}");
                         }
                     }
                     "qr" => {
-                        eprintln!("WhatsApp QR received. Scan the QR code in bridge terminal.");
+                        info!("WhatsApp QR received. Scan the QR code in bridge terminal.");
                     }
                     "error" => {
                         let err = data
                             .get("error")
                             .and_then(Value::as_str)
                             .unwrap_or("unknown bridge error");
-                        eprintln!("WhatsApp bridge error: {err}");
+                        error!("WhatsApp bridge error: {err}");
                     }
                     "sent" => {}
                     _ => {}
@@ -185,20 +187,22 @@ impl Channel for WhatsAppChannel {
         if !self.connected.load(Ordering::Relaxed) {
             return Err(anyhow!("WhatsApp bridge not connected"));
         }
-        let payload = json!({
-            "type": "send",
-            "to": msg.chat_id,
-            "text": msg.content
-        })
-        .to_string();
         let tx = self
             .outbound_tx
             .lock()
             .await
             .clone()
             .ok_or_else(|| anyhow!("WhatsApp bridge not connected"))?;
-        tx.send(payload)
-            .map_err(|err| anyhow!("failed to send bridge payload: {err}"))?;
+        for part in formatter_for("whatsapp").format(&msg.content) {
+            let payload = json!({
+                "type": "send",
+                "to": msg.chat_id,
+                "text": part.text
+            })
+            .to_string();
+            tx.send(payload)
+                .map_err(|err| anyhow!("failed to send bridge payload: {err}"))?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,182 @@
+const log = document.getElementById("log");
+const input = document.getElementById("input");
+const sendButton = document.getElementById("send");
+const sessionList = document.getElementById("sessions");
+
+let session = window.localStorage.getItem("nanobotChatSession") || "default";
+let busy = false;
+let apiKeyPrompted = false;
+
+function authHeaders() {
+  const key = window.localStorage.getItem("nanobotApiKey");
+  return key ? { Authorization: `Bearer ${key}` } : {};
+}
+
+// On the first 401 ask for an API key, remember it, and retry.
+async function apiFetch(url, options = {}) {
+  const send = () =>
+    fetch(url, { ...options, headers: { ...(options.headers || {}), ...authHeaders() } });
+  let response = await send();
+  if (response.status === 401 && !apiKeyPrompted) {
+    apiKeyPrompted = true;
+    const key = window.prompt("API key");
+    if (key) {
+      window.localStorage.setItem("nanobotApiKey", key.trim());
+      response = await send();
+    }
+  }
+  return response;
+}
+
+function scrollToEnd() {
+  log.scrollTop = log.scrollHeight;
+}
+
+function addMessage(role, text) {
+  const node = document.createElement("div");
+  node.className = `msg ${role}`;
+  node.textContent = text;
+  log.appendChild(node);
+  scrollToEnd();
+  return node;
+}
+
+function addTool(name, args) {
+  const node = document.createElement("div");
+  node.className = "tool";
+  const details = document.createElement("details");
+  const summary = document.createElement("summary");
+  summary.textContent = `⚙ ${name} …`;
+  const body = document.createElement("pre");
+  body.textContent = args;
+  details.append(summary, body);
+  node.appendChild(details);
+  log.appendChild(node);
+  scrollToEnd();
+  return { summary, body, name };
+}
+
+async function loadSessions() {
+  const response = await apiFetch("/api/sessions", { cache: "no-store" });
+  const payload = await response.json().catch(() => ({}));
+  const names = new Set(payload.sessions || []);
+  names.add(session);
+  sessionList.replaceChildren();
+  for (const name of [...names].sort()) {
+    const item = document.createElement("li");
+    item.textContent = name;
+    item.title = name;
+    item.classList.toggle("active", name === session);
+    item.addEventListener("click", () => selectSession(name));
+    sessionList.appendChild(item);
+  }
+}
+
+async function selectSession(name) {
+  session = name;
+  window.localStorage.setItem("nanobotChatSession", name);
+  log.replaceChildren();
+  const response = await apiFetch(`/api/history?session=${encodeURIComponent(name)}`, {
+    cache: "no-store",
+  });
+  const payload = await response.json().catch(() => ({}));
+  for (const message of payload.messages || []) {
+    addMessage(message.role, message.content);
+  }
+  await loadSessions();
+}
+
+// Parses `event:`/`data:` frames out of the streamed response body.
+async function readEvents(response, onEvent) {
+  const reader = response.body.getReader();
+  const decoder = new TextDecoder();
+  let buffer = "";
+  for (;;) {
+    const { value, done } = await reader.read();
+    if (done) break;
+    buffer += decoder.decode(value, { stream: true });
+    let split;
+    while ((split = buffer.indexOf("\n\n")) >= 0) {
+      const frame = buffer.slice(0, split);
+      buffer = buffer.slice(split + 2);
+      let event = "message";
+      let data = "";
+      for (const line of frame.split("\n")) {
+        if (line.startsWith("event:")) event = line.slice(6).trim();
+        if (line.startsWith("data:")) data += line.slice(5).trim();
+      }
+      onEvent(event, data ? JSON.parse(data) : {});
+    }
+  }
+}
+
+async function send(message) {
+  busy = true;
+  sendButton.disabled = true;
+  addMessage("user", message);
+  let answer = null;
+  const tools = [];
+  try {
+    const response = await apiFetch("/api/chat", {
+      method: "POST",
+      headers: { "Content-Type": "application/json", Accept: "text/event-stream" },
+      body: JSON.stringify({ message, session, stream: true }),
+    });
+    if (!response.ok) {
+      const payload = await response.json().catch(() => ({}));
+      throw new Error(payload.error || `HTTP ${response.status}`);
+    }
+    await readEvents(response, (event, data) => {
+      if (event === "delta") {
+        answer = answer || addMessage("assistant", "");
+        answer.textContent += data.text;
+        scrollToEnd();
+      } else if (event === "tool_start") {
+        // Text before a tool call is interim; the final answer starts fresh.
+        answer = null;
+        tools.push(addTool(data.name, data.arguments));
+      } else if (event === "tool_end") {
+        const tool = tools.find((t) => t.name === data.name && t.summary.textContent.endsWith("…"));
+        if (tool) {
+          tool.summary.textContent = `⚙ ${data.name}`;
+          tool.body.textContent += `\n→ ${data.preview}`;
+        }
+      } else if (event === "prompt_reloaded") {
+        addMessage("notice", `Reloaded ${data.files.join(", ")}`);
+      } else if (event === "done") {
+        answer = answer || addMessage("assistant", "");
+        answer.textContent = data.response;
+      } else if (event === "error") {
+        addMessage("error", data.error || "chat failed");
+      }
+    });
+  } catch (err) {
+    addMessage("error", String(err.message || err));
+  } finally {
+    busy = false;
+    sendButton.disabled = false;
+    loadSessions().catch(() => {});
+  }
+}
+
+document.getElementById("composer").addEventListener("submit", (event) => {
+  event.preventDefault();
+  const message = input.value.trim();
+  if (!message || busy) return;
+  input.value = "";
+  send(message);
+});
+
+input.addEventListener("keydown", (event) => {
+  if (event.key === "Enter" && !event.shiftKey) {
+    event.preventDefault();
+    document.getElementById("composer").requestSubmit();
+  }
+});
+
+document.getElementById("new-session").addEventListener("click", () => {
+  const name = window.prompt("Session name", `chat-${new Date().toISOString().slice(0, 10)}`);
+  if (name && name.trim()) selectSession(name.trim());
+});
+
+selectSession(session).catch((err) => addMessage("error", String(err.message || err)));
@@ -0,0 +1,318 @@
+//! Browser chat UI served by the gateway at `/`.
+//!
+//! Assets are embedded at build time. Chats run on the gateway's agent in
+//! `web:<name>` sessions (the key's own sessions for profile-bound API keys).
+//! Without an API key only loopback clients may use the API, and only from
+//! the UI's own origin.
+
+use crate::agent::events::AgentEvent;
+use crate::agent::{AgentLoop, error_reply};
+use crate::apikeys::{ApiKey, ApiKeyGuard};
+use crate::listener::{TrustedProxies, into_event_stream, is_json, write_event};
+use crate::session::SessionManager;
+use rust_embed::RustEmbed;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response};
+use tokio::runtime::Handle;
+use tokio::sync::{mpsc, oneshot};
+
+#[derive(RustEmbed)]
+#[folder = "src/chatui/assets/"]
+struct Assets;
+
+#[derive(Debug, Deserialize)]
+struct ChatPayload {
+    message: String,
+    #[serde(default)]
+    session: String,
+    #[serde(default)]
+    stream: bool,
+}
+
+fn respond_json(request: Request, status: u16, body: Value) {
+    let header = Header::from_bytes("Content-Type", "application/json; charset=utf-8")
+        .expect("static header is valid");
+    let _ = request.respond(
+        Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header),
+    );
+}
+
+fn session_name(name: &str) -> &str {
+    match name.trim() {
+        "" => "default",
+        name => name,
+    }
+}
+
+/// Stored session key for the chat `name`.
+fn session_key(key: Option<&ApiKey>, name: &str) -> String {
+    let name = session_name(name);
+    key.and_then(|key| key.scoped_session(Some(name)))
+        .unwrap_or_else(|| format!("web:{name}"))
+}
+
+/// Prefix of the session keys the caller may see.
+fn session_prefix(key: Option<&ApiKey>) -> String {
+    match key.and_then(|key| key.scoped_session(None)) {
+        Some(scope) => format!("{scope}:"),
+        None => "web:".to_string(),
+    }
+}
+
+/// User and assistant turns of a stored session, for display.
+fn visible_messages(messages: &[Value]) -> Vec<Value> {
+    messages
+        .iter()
+        .filter_map(|message| {
+            let role = message.get("role").and_then(Value::as_str)?;
+            let content = message.get("content").and_then(Value::as_str)?;
+            (matches!(role, "user" | "assistant") && !content.is_empty())
+                .then(|| json!({ "role": role, "content": content }))
+        })
+        .collect()
+}
+
+pub struct ChatUi {
+    agent: Arc<AgentLoop>,
+    sessions: Arc<SessionManager>,
+    runtime: Handle,
+    guard: ApiKeyGuard,
+    proxies: TrustedProxies,
+}
+
+impl ChatUi {
+    pub fn new(
+        agent: Arc<AgentLoop>,
+        sessions: Arc<SessionManager>,
+        runtime: Handle,
+        guard: ApiKeyGuard,
+        proxies: TrustedProxies,
+    ) -> Self {
+        Self {
+            agent,
+            sessions,
+            runtime,
+            guard,
+            proxies,
+        }
+    }
+
+    /// Serves the request if it belongs to the chat UI, otherwise hands it back.
+    pub fn handle(self: &Arc<Self>, request: Request) -> Option<Request> {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        if !path.starts_with("/api/") {
+            return self.serve_asset(request, path);
+        }
+        let key = match self.authorize(&request) {
+            Ok(key) => key,
+            Err((status, error)) => {
+                respond_json(request, status, json!({ "ok": false, "error": error }));
+                return None;
+            }
+        };
+        match (request.method(), path) {
+            (Method::Get, "/api/sessions") => self.list_sessions(request, key.as_ref()),
+            (Method::Get, "/api/history") => {
+                let name = url::form_urlencoded::parse(query.as_bytes())
+                    .find(|(name, _)| name == "session")
+                    .map(|(_, value)| value.into_owned())
+                    .unwrap_or_default();
+                self.history(request, key.as_ref(), &name);
+            }
+            (Method::Post, "/api/chat") if !is_json(&request) => respond_json(
+                request,
+                415,
+                json!({ "ok": false, "error": "send the message as application/json" }),
+            ),
+            (Method::Post, "/api/chat") => {
+                let ui = self.clone();
+                std::thread::spawn(move || ui.chat(request, key));
+            }
+            _ => return Some(request),
+        }
+        None
+    }
+
+    fn serve_asset(&self, request: Request, path: &str) -> Option<Request> {
+        if !matches!(request.method(), Method::Get | Method::Head) {
+            return Some(request);
+        }
+        let name = match path.trim_start_matches('/') {
+            "" => "index.html",
+            name => name,
+        };
+        let Some(asset) = Assets::get(name) else {
+            return Some(request);
+        };
+        let mime = mime_guess::from_path(name).first_or_octet_stream();
+        let header = Header::from_bytes("Content-Type", mime.essence_str())
+            .expect("mime type is a valid header");
+        let _ = request.respond(Response::from_data(asset.data.into_owned()).with_header(header));
+        None
+    }
+
+    fn authorize(&self, request: &Request) -> Result<Option<ApiKey>, (u16, String)> {
+        let key = self
+            .guard
+            .check(request)
+            .map_err(|err| (err.status_code(), err.to_string()))?;
+        if key.is_none()
+            && let Some((status, error)) = self.proxies.local_request_error(request)
+        {
+            return Err((status, error.to_string()));
+        }
+        Ok(key)
+    }
+
+    fn list_sessions(&self, request: Request, key: Option<&ApiKey>) {
+        let prefix = session_prefix(key);
+        let mut names = self
+            .sessions
+            .list_session_keys()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|session| session.strip_prefix(&prefix).map(ToOwned::to_owned))
+            .collect::<Vec<_>>();
+        names.sort();
+        respond_json(request, 200, json!({ "ok": true, "sessions": names }));
+    }
+
+    fn history(&self, request: Request, key: Option<&ApiKey>, name: &str) {
+        match self.sessions.get_or_create(&session_key(key, name)) {
+            Ok(session) => respond_json(
+                request,
+                200,
+                json!({ "ok": true, "messages": visible_messages(&session.messages) }),
+            ),
+            Err(err) => respond_json(
+                request,
+                500,
+                json!({ "ok": false, "error": error_reply(&err, "web") }),
+            ),
+        }
+    }
+
+    fn chat(&self, mut request: Request, key: Option<ApiKey>) {
+        let mut raw = String::new();
+        let _ = request.as_reader().read_to_string(&mut raw);
+        let payload = match serde_json::from_str::<ChatPayload>(&raw) {
+            Ok(payload) if !payload.message.trim().is_empty() => payload,
+            Ok(_) => {
+                respond_json(
+                    request,
+                    400,
+                    json!({ "ok": false, "error": "message cannot be empty" }),
+                );
+                return;
+            }
+            Err(err) => {
+                respond_json(
+                    request,
+                    400,
+                    json!({ "ok": false, "error": format!("invalid JSON body: {err}") }),
+                );
+                return;
+            }
+        };
+        let session = session_key(key.as_ref(), &payload.session);
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel::<AgentEvent>();
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let agent = self.agent.clone();
+        self.runtime.spawn(async move {
+            let answer = agent
+                .process_direct_streaming(&payload.message, Some(&session), None, None, events_tx)
+                .await;
+            let _ = reply_tx.send(answer);
+        });
+
+        if !payload.stream {
+            // Only the final answer is wanted; the event channel just drains.
+            while events_rx.blocking_recv().is_some() {}
+            match reply_rx.blocking_recv() {
+                Ok(Ok(answer)) => {
+                    respond_json(request, 200, json!({ "ok": true, "response": answer }))
+                }
+                Ok(Err(err)) => respond_json(
+                    request,
+                    500,
+                    json!({ "ok": false, "error": error_reply(&err, "web") }),
+                ),
+                Err(_) => respond_json(
+                    request,
+                    500,
+                    json!({ "ok": false, "error": "chat was interrupted" }),
+                ),
+            }
+            return;
+        }
+
+        let mut writer = into_event_stream(request);
+        while let Some(event) = events_rx.blocking_recv() {
+            if let Some(w) = writer.as_mut() {
+                let data = serde_json::to_value(&event).unwrap_or(Value::Null);
+                if !write_event(w.as_mut(), event.name(), &data) {
+                    writer = None;
+                }
+            }
+        }
+        let (name, data) = match reply_rx.blocking_recv() {
+            Ok(Ok(answer)) => ("done", json!({ "ok": true, "response": answer })),
+            Ok(Err(err)) => (
+                "error",
+                json!({ "ok": false, "error": error_reply(&err, "web") }),
+            ),
+            Err(_) => (
+                "error",
+                json!({ "ok": false, "error": "chat was interrupted" }),
+            ),
+        };
+        if let Some(w) = writer.as_mut() {
+            write_event(w.as_mut(), name, &data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_are_namespaced_per_key_scope() {
+        let profile_key: ApiKey = serde_json::from_value(json!({
+            "id": "abcd1234",
+            "name": "guest",
+            "hash": "",
+            "readOnly": false,
+            "profile": "research",
+            "admin": false,
+            "createdAt": "2026-01-01T00:00:00+00:00",
+            "expiresAt": null,
+            "revokedAt": null,
+        }))
+        .expect("key");
+        assert_eq!(session_key(None, " "), "web:default");
+        assert_eq!(session_key(None, "notes"), "web:notes");
+        assert_eq!(
+            session_key(Some(&profile_key), "notes"),
+            "api:research:notes"
+        );
+        assert_eq!(session_prefix(None), "web:");
+        assert_eq!(session_prefix(Some(&profile_key)), "api:research:");
+    }
+
+    #[test]
+    fn embeds_the_page_and_hides_tool_turns() {
+        assert!(Assets::get("index.html").is_some());
+        let messages = vec![
+            json!({"role": "user", "content": "hi"}),
+            json!({"role": "tool", "content": "{}"}),
+            json!({"role": "assistant", "content": "hello"}),
+        ];
+        assert_eq!(visible_messages(&messages).len(), 2);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::utils::{expand_tilde, get_data_path};
-use anyhow::{Context, Result};
+use crate::utils::{expand_tilde, get_data_path, resolve_workspace, set_data_dir_override};
+use anyhow::{Context, Result, anyhow};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default, rename_all = "camelCase")]
@@ -11,9 +13,35 @@ pub struct ProviderConfig {
     pub api_key: String,
     pub api_base: Option<String>,
     pub extra_headers: Option<HashMap<String, String>>,
+    /// Extra request fields for gateways that support them (OpenRouter).
+    pub gateway_options: Option<GatewayOptions>,
+    /// Use the provider's built-in web search (Zhipu GLM, DashScope Qwen).
+    pub native_search: bool,
+    /// Gemini `safetySettings`, sent as-is, e.g.
+    /// `[{"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_ONLY_HIGH"}]`.
+    pub safety_settings: Option<Value>,
+    /// AWS region for Bedrock; `AWS_REGION` or the AWS profile's when empty.
+    pub region: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// OpenRouter request options; ignored by other providers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GatewayOptions {
+    /// Provider preferences sent as-is, e.g. `{"order": ["anthropic"], "allow_fallbacks": false}`.
+    pub provider: Option<Value>,
+    /// Fallback models tried in order when the requested one fails.
+    pub models: Vec<String>,
+    /// Prompt transforms, e.g. `["middle-out"]`.
+    pub transforms: Vec<String>,
+    /// Ask for cost and token accounting in the response usage.
+    pub usage: bool,
+    /// App attribution (`HTTP-Referer` and `X-Title` headers).
+    pub app_url: String,
+    pub app_title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default, rename_all = "camelCase")]
 pub struct ProvidersConfig {
     pub anthropic: ProviderConfig,
@@ -30,25 +58,64 @@ pub struct ProvidersConfig {
     pub gemini: ProviderConfig,
     pub moonshot: ProviderConfig,
     pub minimax: ProviderConfig,
+    /// `bedrock/` models; signed with the standard AWS credentials unless
+    /// `apiKey` holds a Bedrock API key.
+    pub bedrock: ProviderConfig,
+    pub retry: RetryConfig,
+    /// Replay stored answers to repeated calls at temperature 0.
+    pub cache: bool,
+    /// Client-side limits keyed by model (`openai/gpt-4o`) or provider name
+    /// (`openai`); the model's entry wins.
+    pub rate_limits: HashMap<String, RateLimitConfig>,
+    /// Text embeddings for semantic memory and retrieval.
+    pub embeddings: EmbeddingsConfig,
+}
+
+/// Embedding model used by `providers::embeddings::build_embedding_provider`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EmbeddingsConfig {
+    /// `openai` (or any OpenAI-compatible endpoint), `gemini` or `ollama`;
+    /// empty turns embeddings off.
+    pub provider: String,
+    /// The backend's default model when empty.
+    pub model: String,
+    /// The matching `providers.openai` / `providers.gemini` key when empty.
+    pub api_key: String,
+    pub api_base: Option<String>,
+    /// Shorter vectors, for models that support it.
+    pub dimensions: Option<u32>,
+}
+
+/// Token buckets refilled continuously; 0 leaves a dimension unlimited.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    pub requests_per_minute: u32,
+    /// Prompt and completion tokens together.
+    pub tokens_per_minute: u64,
+}
+
+/// Retrying provider calls that failed for a passing reason.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetryConfig {
+    /// Tries per call, the first included; 1 turns retries off.
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled for each one after, with jitter.
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// HTTP statuses worth retrying. Connection failures are always retried.
+    pub retry_on: Vec<u16>,
 }
 
-impl Default for ProvidersConfig {
+impl Default for RetryConfig {
     fn default() -> Self {
         Self {
-            anthropic: ProviderConfig::default(),
-            openai: ProviderConfig::default(),
-            openrouter: ProviderConfig::default(),
-            aihubmix: ProviderConfig::default(),
-            siliconflow: ProviderConfig::default(),
-            volcengine: ProviderConfig::default(),
-            deepseek: ProviderConfig::default(),
-            groq: ProviderConfig::default(),
-            zhipu: ProviderConfig::default(),
-            dashscope: ProviderConfig::default(),
-            vllm: ProviderConfig::default(),
-            gemini: ProviderConfig::default(),
-            moonshot: ProviderConfig::default(),
-            minimax: ProviderConfig::default(),
+            max_attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 20_000,
+            retry_on: vec![408, 429, 500, 502, 503, 504, 529],
         }
     }
 }
@@ -62,6 +129,16 @@ pub struct AgentDefaults {
     pub temperature: f32,
     pub max_tool_iterations: u32,
     pub memory_window: usize,
+    /// Chats the gateway answers at once; each chat still gets one turn at
+    /// a time.
+    pub max_concurrent_turns: usize,
+    /// Text fetched on an interval and added to every system prompt.
+    pub context_sources: Vec<ContextSourceConfig>,
+    pub content_filter: ContentFilterConfig,
+    pub language: LanguageConfig,
+    pub idle_extraction: IdleExtractionConfig,
+    pub post_process: PostProcessConfig,
+    pub identity: IdentityConfig,
 }
 
 impl Default for AgentDefaults {
@@ -73,58 +150,220 @@ impl Default for AgentDefaults {
             temperature: 0.7,
             max_tool_iterations: 20,
             memory_window: 50,
+            max_concurrent_turns: crate::agent::DEFAULT_CONCURRENT_TURNS,
+            context_sources: Vec::new(),
+            content_filter: ContentFilterConfig::default(),
+            language: LanguageConfig::default(),
+            idle_extraction: IdleExtractionConfig::default(),
+            post_process: PostProcessConfig::default(),
+            identity: IdentityConfig::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Generation settings for the models matching a `models.<pattern>` glob
+/// (`kimi-k2.5*`, `openai/o*`); they replace what the agent asks for.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
-pub struct AgentsConfig {
-    pub defaults: AgentDefaults,
+pub struct ModelParams {
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub top_p: Option<f32>,
+    /// Stop sequences.
+    pub stop: Vec<String>,
+    /// `none`, `low`, `medium` or `high`, for reasoning models.
+    pub reasoning_effort: String,
+    /// Token budget for extended thinking; `0` turns thinking off.
+    pub thinking_budget_tokens: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ModelParams {
+    /// `other`'s settings on top of these.
+    pub fn merge(&mut self, other: &ModelParams) {
+        self.temperature = other.temperature.or(self.temperature);
+        self.max_tokens = other.max_tokens.or(self.max_tokens);
+        self.top_p = other.top_p.or(self.top_p);
+        if !other.stop.is_empty() {
+            self.stop = other.stop.clone();
+        }
+        if !other.reasoning_effort.trim().is_empty() {
+            self.reasoning_effort = other.reasoning_effort.trim().to_ascii_lowercase();
+        }
+        self.thinking_budget_tokens = other.thinking_budget_tokens.or(self.thinking_budget_tokens);
+    }
+}
+
+/// Distilling sessions that went quiet into `memory/FACTS.md` (gateway only).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
-pub struct PerplexitySearchConfig {
-    pub api_key: String,
-    pub base_url: Option<String>,
-    pub model: Option<String>,
+pub struct IdleExtractionConfig {
+    pub enabled: bool,
+    /// How long a session must be quiet before it is read.
+    pub idle_minutes: u64,
+    /// Fewer new messages than this are left for a later pass.
+    pub min_messages: usize,
 }
 
-impl Default for PerplexitySearchConfig {
+impl Default for IdleExtractionConfig {
     fn default() -> Self {
         Self {
-            api_key: String::new(),
-            base_url: None,
-            model: None,
+            enabled: false,
+            idle_minutes: 60,
+            min_messages: 4,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Transforms applied to replies before they are sent. A profile's own chain
+/// replaces the channel's, which replaces `default`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 #[serde(default, rename_all = "camelCase")]
-pub struct GrokSearchConfig {
-    pub api_key: String,
-    pub model: Option<String>,
-    pub inline_citations: bool,
+pub struct PostProcessConfig {
+    pub default: Vec<PostProcessStep>,
+    /// Chains by channel name, e.g. `sms`.
+    pub channels: HashMap<String, Vec<PostProcessStep>>,
+}
+
+/// Identity snippets, `<workspace>/identities/<name>.md`, that replace
+/// SOUL.md in the system prompt. A profile's own identity wins over the
+/// channel's, which wins over `default`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IdentityConfig {
+    pub default: Option<String>,
+    /// Identity names by channel name, e.g. `email`.
+    pub channels: HashMap<String, String>,
 }
 
-impl Default for GrokSearchConfig {
+/// One output transform: `strip_reasoning`, `max_length`, `signature`,
+/// `units` or `translate`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PostProcessStep {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// `max_length`: longest reply in characters.
+    pub max_chars: usize,
+    /// `signature`: appended on a line of its own.
+    pub text: String,
+    /// `units`: `metric` or `imperial`.
+    pub system: String,
+    /// `translate`: the language to translate into.
+    pub language: String,
+}
+
+/// Which language the agent answers in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LanguageConfig {
+    /// `auto` matches the language of each user message, `off` leaves it to
+    /// the model, anything else (e.g. `Chinese`) is always used.
+    pub reply: String,
+    /// Translate an answer that came back in a different script.
+    pub enforce: bool,
+}
+
+impl Default for LanguageConfig {
     fn default() -> Self {
         Self {
-            api_key: String::new(),
-            model: None,
-            inline_citations: false,
+            reply: "auto".to_string(),
+            enforce: true,
+        }
+    }
+}
+
+/// What to do when the provider's content filter blocks a completion.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ContentFilterConfig {
+    /// Retry once with `hint` added to the system prompt.
+    pub retry: bool,
+    pub hint: String,
+}
+
+impl Default for ContentFilterConfig {
+    fn default() -> Self {
+        Self {
+            retry: true,
+            hint: "The previous attempt was blocked by the provider's content filter. Answer \
+                   helpfully while avoiding graphic, explicit or hateful wording; paraphrase \
+                   quoted material instead of reproducing it, and decline briefly only the \
+                   parts you cannot help with."
+                .to_string(),
+        }
+    }
+}
+
+/// One of `url`, `path` (file or glob, relative to the workspace) or `command`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ContextSourceConfig {
+    pub name: String,
+    pub url: String,
+    pub path: String,
+    pub command: String,
+    pub refresh_s: u64,
+    /// Approximate cap; longer content is truncated.
+    pub max_tokens: usize,
+}
+
+impl Default for ContextSourceConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            url: String::new(),
+            path: String::new(),
+            command: String::new(),
+            refresh_s: 300,
+            max_tokens: 1000,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AgentsConfig {
+    pub defaults: AgentDefaults,
+    /// Named profiles; API keys bound to one chat in `api:<profile>` sessions.
+    pub profiles: HashMap<String, AgentProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AgentProfile {
+    /// Tools the profile may see and run; all tools when unset.
+    pub tools: Option<Vec<String>>,
+    /// Replaces the channel's reply transforms when set.
+    pub post_process: Option<Vec<PostProcessStep>>,
+    /// Replaces the channel's identity snippet when set.
+    pub identity: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PerplexitySearchConfig {
+    pub api_key: String,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GrokSearchConfig {
+    pub api_key: String,
+    pub model: Option<String>,
+    pub inline_citations: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct WebSearchConfig {
     pub provider: String,
     pub api_key: String,
     pub max_results: usize,
+    /// Top results whose pages are fetched for a lead paragraph (0: none).
+    pub lead_results: usize,
+    pub lead_chars: usize,
     pub perplexity: PerplexitySearchConfig,
     pub grok: GrokSearchConfig,
 }
@@ -135,6 +374,8 @@ impl Default for WebSearchConfig {
             provider: "brave".to_string(),
             api_key: String::new(),
             max_results: 5,
+            lead_results: 3,
+            lead_chars: 300,
             perplexity: PerplexitySearchConfig::default(),
             grok: GrokSearchConfig::default(),
         }
@@ -159,12 +400,551 @@ impl Default for ExecToolConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LogFileConfig {
+    pub enabled: bool,
+    pub path: String,
+    pub json: bool,
+    pub max_bytes: u64,
+    pub max_files: usize,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: String::new(),
+            json: true,
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LoggingConfig {
+    pub level: String,
+    pub modules: HashMap<String, String>,
+    pub file: LogFileConfig,
+    pub otlp: OtlpConfig,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            modules: HashMap::new(),
+            file: LogFileConfig::default(),
+            otlp: OtlpConfig::default(),
+        }
+    }
+}
+
+/// Masks secrets and PII before they reach logs or exported transcripts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Builtin detectors: api keys, tokens, private keys, emails, card numbers.
+    pub builtin: bool,
+    pub disabled_detectors: Vec<String>,
+    /// Extra regular expressions to mask.
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            builtin: true,
+            disabled_detectors: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// USD per million tokens for a model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input_per_million
+            + completion_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Spend thresholds; a limit of 0 disables that period.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BudgetConfig {
+    pub enabled: bool,
+    pub daily_usd: f64,
+    pub monthly_usd: f64,
+    /// Channel and chat that receive threshold alerts.
+    pub notify_channel: String,
+    pub notify_to: String,
+    /// Cheaper model (served by the same provider) used until the period resets.
+    pub fallback_model: String,
+    /// Keyed by model name without the provider prefix, e.g. `claude-opus-4-5`;
+    /// added to and overriding the built-in price table.
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+impl BudgetConfig {
+    /// The built-in price table with `prices` applied on top. A configured
+    /// key also replaces the built-in entries it is a prefix of, so
+    /// `claude-opus` prices every Opus model.
+    pub fn model_prices(&self) -> HashMap<String, ModelPrice> {
+        let mut prices: HashMap<String, ModelPrice> = crate::budget::BUILTIN_PRICES
+            .iter()
+            .filter(|(model, _, _)| {
+                !self
+                    .prices
+                    .keys()
+                    .any(|key| model.starts_with(key.as_str()))
+            })
+            .map(|(model, input, output)| {
+                (
+                    model.to_string(),
+                    ModelPrice {
+                        input_per_million: *input,
+                        output_per_million: *output,
+                    },
+                )
+            })
+            .collect();
+        prices.extend(self.prices.iter().map(|(k, v)| (k.clone(), *v)));
+        prices
+    }
+}
+
+/// Sends simple requests (greetings, short lookups) to a fast model and the
+/// rest to `agents.defaults.model`; background jobs use the model of their
+/// quality tier.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RouterConfig {
+    pub enabled: bool,
+    /// e.g. `groq/llama-3.1-8b-instant`; uses that provider's API key.
+    pub fast_model: String,
+    /// Optional tiny model, served by the fast model's provider, that decides
+    /// requests the local heuristic cannot.
+    pub scorer_model: String,
+    /// Longer messages always go to the main model.
+    pub max_chars: usize,
+    /// Model per quality tier (`cheap`, `premium`) for cron jobs, the
+    /// heartbeat and workflows; chat keeps the main model.
+    pub tiers: BTreeMap<String, String>,
+    /// Tier of background jobs that do not declare one; empty keeps them on
+    /// the main model.
+    pub background_tier: String,
+}
+
+impl RouterConfig {
+    /// The model for `tier`, or for `backgroundTier` when `tier` is empty.
+    pub fn tier_model(&self, tier: &str) -> Option<&str> {
+        let tier = match tier.trim() {
+            "" => self.background_tier.trim(),
+            tier => tier,
+        };
+        self.tiers
+            .get(tier)
+            .map(|model| model.trim())
+            .filter(|model| !model.is_empty())
+    }
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fast_model: String::new(),
+            scorer_model: String::new(),
+            max_chars: 120,
+            tiers: BTreeMap::new(),
+            background_tier: String::new(),
+        }
+    }
+}
+
+/// Who may do what when one gateway serves several people (`access`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AccessConfig {
+    pub enabled: bool,
+    /// Role of users not listed in `users`.
+    pub default_role: String,
+    /// Role per user: `telegram:12345` (channel and sender id), a whole
+    /// channel (`discord`), or an API key profile (`api:research`).
+    pub users: HashMap<String, String>,
+    pub roles: HashMap<String, RoleConfig>,
+}
+
+impl Default for AccessConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_role: "guest".to_string(),
+            users: HashMap::new(),
+            roles: HashMap::from([
+                ("owner".to_string(), RoleConfig::default()),
+                (
+                    "trusted".to_string(),
+                    RoleConfig {
+                        daily_usd: 1.0,
+                        ..RoleConfig::default()
+                    },
+                ),
+                (
+                    "guest".to_string(),
+                    RoleConfig {
+                        tools: Some(vec!["web_search".to_string(), "web_fetch".to_string()]),
+                        daily_usd: 0.2,
+                        ..RoleConfig::default()
+                    },
+                ),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RoleConfig {
+    /// Tools the role may see and run; all tools when unset.
+    pub tools: Option<Vec<String>>,
+    /// Models the role may pick with `/model`; any when empty.
+    pub models: Vec<String>,
+    /// Model the role is answered with; `agents.defaults.model` when empty.
+    pub model: String,
+    /// Spend per user and day, priced with `budget.prices`; 0 is unlimited.
+    pub daily_usd: f64,
+}
+
+/// OTLP/HTTP trace export; requires building with the `otel` feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OtlpConfig {
+    pub enabled: bool,
+    /// Traces endpoint; empty falls back to `OTEL_EXPORTER_OTLP_ENDPOINT`
+    /// or `http://localhost:4318/v1/traces`.
+    pub endpoint: String,
+    pub service_name: String,
+    pub headers: HashMap<String, String>,
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            service_name: "nanobot".to_string(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// `nanobot voice`; requires building with the `voice` feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct VoiceConfig {
+    /// OpenAI-compatible transcription endpoint; empty uses Groq with
+    /// `providers.groq.apiKey`.
+    pub stt_api_base: String,
+    pub stt_api_key: String,
+    pub stt_model: String,
+    /// OpenAI-compatible speech endpoint; empty uses `providers.openai`.
+    pub tts_api_base: String,
+    pub tts_api_key: String,
+    pub tts_model: String,
+    pub tts_voice: String,
+    /// Microphone level (RMS, 0-1) that counts as speech.
+    pub speech_threshold: f32,
+    /// Louder level required to interrupt a spoken reply.
+    pub barge_in_threshold: f32,
+    /// Silence that ends an utterance.
+    pub silence_ms: u64,
+    pub session: String,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            stt_api_base: String::new(),
+            stt_api_key: String::new(),
+            stt_model: "whisper-large-v3".to_string(),
+            tts_api_base: String::new(),
+            tts_api_key: String::new(),
+            tts_model: "gpt-4o-mini-tts".to_string(),
+            tts_voice: "alloy".to_string(),
+            speech_threshold: 0.02,
+            barge_in_threshold: 0.06,
+            silence_ms: 800,
+            session: "voice:direct".to_string(),
+        }
+    }
+}
+
+/// Nightly housekeeping run by the gateway; a `*Days` of 0 skips that task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MaintenanceConfig {
+    pub enabled: bool,
+    /// Local time of day, `HH:MM`.
+    pub time: String,
+    /// Delete rotated log files older than this.
+    pub log_days: u64,
+    /// Delete downloaded media and diagnostic bundles older than this.
+    pub cache_days: u64,
+    /// Archive sessions idle for this long.
+    pub session_days: u64,
+    /// Delete archived sessions older than this.
+    pub archive_days: u64,
+    /// Drop `usage.jsonl` records older than this.
+    pub usage_days: u64,
+    /// Zip config, cron jobs and memory into `~/.nanobot/backups/`.
+    pub backup: bool,
+    pub backup_keep: usize,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            time: "03:30".to_string(),
+            log_days: 14,
+            cache_days: 30,
+            session_days: 180,
+            archive_days: 365,
+            usage_days: 365,
+            backup: true,
+            backup_keep: 7,
+        }
+    }
+}
+
+/// Where reminders and the briefing go: the first channel in `prefer` the
+/// user was active on within `activeMinutes`, else the job's own target.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PresenceConfig {
+    pub enabled: bool,
+    pub active_minutes: u64,
+    /// Channels in order of preference; `desktop` is a desktop notification,
+    /// active while the CLI or WebUI is in use. Empty reads the `Notify:`
+    /// line of USER.md.
+    pub prefer: Vec<String>,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            active_minutes: 15,
+            prefer: Vec::new(),
+        }
+    }
+}
+
+/// Notes written from sessions by `nanobot sessions publish` and the
+/// `publish_note` tool.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PublishConfig {
+    /// Folder the notes go to, e.g. a folder of an Obsidian vault; defaults
+    /// to `<workspace>/notes`.
+    pub notes_dir: String,
+}
+
+/// Morning briefing sent by the gateway and printed by `nanobot brief`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct BriefingConfig {
+    /// Send the briefing every day at `time` (the gateway must be running).
+    pub enabled: bool,
+    /// Local time of day, `HH:MM`.
+    pub time: String,
+    pub channel: String,
+    pub to: String,
+    /// wttr.in location, e.g. `Berlin`; empty skips the weather.
+    pub weather_location: String,
+    /// iCalendar URLs or file paths.
+    pub calendars: Vec<String>,
+    /// RSS or Atom feed URLs.
+    pub feeds: Vec<String>,
+    pub max_feed_items: usize,
+    /// Extra guidance for writing the briefing (tone, length, language).
+    pub instructions: String,
+}
+
+impl Default for BriefingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time: "07:30".to_string(),
+            channel: String::new(),
+            to: String::new(),
+            weather_location: String::new(),
+            calendars: Vec::new(),
+            feeds: Vec::new(),
+            max_feed_items: 5,
+            instructions: String::new(),
+        }
+    }
+}
+
+/// Where the user is, for the prompt, weather and place reminders.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LocationConfig {
+    /// `static` (the fields below), `ip` (geolocate the machine's public
+    /// address) or `webhook` (`POST /location` from a phone); empty disables.
+    pub source: String,
+    /// Place name for `static`, e.g. `Berlin, Germany`.
+    pub name: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Named places (`home`, `office`) that place reminders refer to.
+    pub places: BTreeMap<String, PlaceConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PlaceConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// How close counts as being there, in meters.
+    pub radius_m: f64,
+}
+
+impl Default for PlaceConfig {
+    fn default() -> Self {
+        Self {
+            latitude: 0.0,
+            longitude: 0.0,
+            radius_m: 150.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ApprovalConfig {
+    pub enabled: bool,
+    pub tools: Vec<String>,
+    pub timeout: u64,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tools: vec![
+                "exec".to_string(),
+                "write_file".to_string(),
+                "edit_file".to_string(),
+            ],
+            timeout: 300,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default, rename_all = "camelCase")]
 pub struct ToolsConfig {
     pub web: WebToolsConfig,
     pub exec: ExecToolConfig,
     pub restrict_to_workspace: bool,
+    pub approval: ApprovalConfig,
+    pub stats: ToolStatsConfig,
+    pub verify: VerifyConfig,
+    /// Refuse tools that write files, run commands or act outside the chat.
+    pub read_only: bool,
+    pub selection: ToolSelectionConfig,
+}
+
+/// `tools.selection`: showing the model only the tools relevant to the
+/// message when many are registered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ToolSelectionConfig {
+    pub enabled: bool,
+    /// Selection only runs with more tools than this.
+    pub min_tools: usize,
+    pub top_k: usize,
+    /// Model that ranks the tools, served by the agent's provider; the
+    /// agent's model when empty.
+    pub model: String,
+    /// Tools offered on every turn.
+    pub always: Vec<String>,
+}
+
+impl Default for ToolSelectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_tools: 20,
+            top_k: 8,
+            model: String::new(),
+            always: vec!["message".to_string()],
+        }
+    }
+}
+
+/// `tools.verify`: the project check run after the agent edits files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct VerifyConfig {
+    /// Shell command, e.g. `cargo test`; empty turns verification off.
+    pub command: String,
+    /// Directory to run it in, relative to the workspace.
+    pub cwd: String,
+    pub timeout: u64,
+    /// Failed checks per turn before the agent is told to stop.
+    pub max_attempts: u32,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            command: String::new(),
+            cwd: String::new(),
+            timeout: 300,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// `tools.stats`: per-call records in `tool_stats.jsonl` and switching off
+/// tools that keep failing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ToolStatsConfig {
+    pub enabled: bool,
+    /// Disable a tool once every call in the last `window_minutes` failed.
+    pub auto_disable: bool,
+    pub window_minutes: u64,
+    /// Failed calls needed in the window before a tool is disabled.
+    pub min_calls: usize,
+}
+
+impl Default for ToolStatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            auto_disable: false,
+            window_minutes: 60,
+            min_calls: 5,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -172,6 +952,21 @@ pub struct ToolsConfig {
 pub struct GatewayConfig {
     pub host: String,
     pub port: u16,
+    /// Listen on this UNIX socket instead of host:port (unix only).
+    pub unix_socket: String,
+    pub tls: GatewayTlsConfig,
+    /// Proxy addresses or CIDRs whose X-Forwarded-For header is honoured.
+    pub trusted_proxies: Vec<String>,
+    /// Reject HTTP API requests without a valid key from `nanobot apikeys`.
+    pub require_api_key: bool,
+    /// Serve the browser chat UI at `/` (off by default).
+    pub web_chat: bool,
+    /// Multi-user mode: encrypt the sessions and memory of profile-bound API
+    /// keys with a per-profile key that each of the profile's API keys unlocks.
+    pub encrypt_user_data: bool,
+    /// On shutdown, how long in-flight turns may run before they are cut off.
+    pub drain_timeout_s: u64,
+    pub watchdog: WatchdogConfig,
 }
 
 impl Default for GatewayConfig {
@@ -179,6 +974,46 @@ impl Default for GatewayConfig {
         Self {
             host: "0.0.0.0".to_string(),
             port: 18790,
+            unix_socket: String::new(),
+            tls: GatewayTlsConfig::default(),
+            trusted_proxies: Vec::new(),
+            require_api_key: false,
+            web_chat: false,
+            encrypt_user_data: false,
+            drain_timeout_s: 30,
+            watchdog: WatchdogConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GatewayTlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain.
+    pub cert_path: String,
+    /// PEM private key (PKCS#8 or RSA).
+    pub key_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WatchdogConfig {
+    pub base_backoff_s: u64,
+    pub max_backoff_s: u64,
+    /// A subsystem running this long without failing has its backoff reset.
+    pub stable_after_s: u64,
+    /// Consecutive failures before a subsystem is reported as crash-looping.
+    pub crash_loop_threshold: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            base_backoff_s: 2,
+            max_backoff_s: 300,
+            stable_after_s: 120,
+            crash_loop_threshold: 5,
         }
     }
 }
@@ -204,6 +1039,30 @@ pub struct TelegramConfig {
     pub token: String,
     pub allow_from: Vec<String>,
     pub proxy: Option<String>,
+    pub group: GroupConfig,
+}
+
+/// When the bot answers in group chats; see `channels::group`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GroupConfig {
+    /// `mention`: only when mentioned, replied to or addressed with a
+    /// prefix; `open`: every message.
+    pub policy: String,
+    /// Leading words that address the bot, e.g. `nano` or `!ask`.
+    pub prefixes: Vec<String>,
+    /// Earlier unanswered messages passed along with an addressed one.
+    pub context_messages: usize,
+}
+
+impl Default for GroupConfig {
+    fn default() -> Self {
+        Self {
+            policy: "mention".to_string(),
+            prefixes: Vec::new(),
+            context_messages: 10,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -234,6 +1093,7 @@ pub struct DiscordConfig {
     pub allow_from: Vec<String>,
     pub gateway_url: String,
     pub intents: u32,
+    pub group: GroupConfig,
 }
 
 impl Default for DiscordConfig {
@@ -244,6 +1104,7 @@ impl Default for DiscordConfig {
             allow_from: Vec::new(),
             gateway_url: "wss://gateway.discord.gg/?v=10&encoding=json".to_string(),
             intents: 37377,
+            group: GroupConfig::default(),
         }
     }
 }
@@ -268,34 +1129,18 @@ pub struct DingTalkConfig {
     pub allow_from: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default, rename_all = "camelCase")]
 pub struct MochatMentionConfig {
     pub require_in_groups: bool,
 }
 
-impl Default for MochatMentionConfig {
-    fn default() -> Self {
-        Self {
-            require_in_groups: false,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default, rename_all = "camelCase")]
 pub struct MochatGroupRule {
     pub require_mention: bool,
 }
 
-impl Default for MochatGroupRule {
-    fn default() -> Self {
-        Self {
-            require_mention: false,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct MochatConfig {
@@ -475,6 +1320,46 @@ pub struct ChannelsConfig {
     pub email: EmailConfig,
     pub slack: SlackConfig,
     pub qq: QQConfig,
+    pub streaming: StreamingConfig,
+}
+
+/// Live replies: on channels that can edit messages, the answer is shown
+/// while it is written by editing one message at most every `intervalMs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StreamingConfig {
+    pub enabled: bool,
+    /// Channels to stream on; only telegram, discord and slack can edit.
+    pub channels: Vec<String>,
+    pub interval_ms: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channels: vec![
+                "telegram".to_string(),
+                "discord".to_string(),
+                "slack".to_string(),
+            ],
+            interval_ms: 1500,
+        }
+    }
+}
+
+/// Outbound HTTP for providers, web tools and channels (see `network`).
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NetworkConfig {
+    /// `http://` or `https://` proxy URL, optionally with `user:pass@`;
+    /// empty uses `HTTP(S)_PROXY` from the environment.
+    pub proxy: String,
+    /// Hosts that skip the proxy, comma-separated as in `NO_PROXY`.
+    pub no_proxy: String,
+    /// PEM file of extra root certificates to trust, for private endpoints
+    /// and TLS-inspecting proxies.
+    pub ca_bundle: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -486,11 +1371,136 @@ pub struct Config {
     pub gateway: GatewayConfig,
     pub service: ServiceConfig,
     pub tools: ToolsConfig,
+    pub logging: LoggingConfig,
+    pub redaction: RedactionConfig,
+    pub budget: BudgetConfig,
+    pub router: RouterConfig,
+    pub voice: VoiceConfig,
+    pub briefing: BriefingConfig,
+    pub location: LocationConfig,
+    /// Per-model generation settings, keyed by model glob.
+    pub models: BTreeMap<String, ModelParams>,
+    pub publish: PublishConfig,
+    pub maintenance: MaintenanceConfig,
+    pub presence: PresenceConfig,
+    pub access: AccessConfig,
+    pub network: NetworkConfig,
+    /// Named workspaces, each with its own memory, skills and sessions.
+    pub workspaces: BTreeMap<String, WorkspaceConfig>,
+    /// UI language (`en`, `zh`); empty or `auto` follows the system locale.
+    pub locale: String,
+    /// IANA time zone (`Asia/Shanghai`) for dates, memory files, cron and
+    /// reminders; empty follows the OS.
+    pub timezone: String,
+    /// The named workspace chosen with `--workspace`; never saved.
+    #[serde(skip)]
+    pub active_workspace: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkspaceConfig {
+    /// Defaults to `~/.nanobot/workspaces/<name>`.
+    pub path: String,
+    /// Channels (`slack`) or single chats (`telegram:123`) the gateway
+    /// answers from this workspace.
+    pub channels: Vec<String>,
+}
+
+impl WorkspaceConfig {
+    /// How closely this workspace claims a chat: 2 for the chat itself, 1
+    /// for its whole channel.
+    pub fn claims(&self, channel: &str, chat_id: &str) -> Option<u8> {
+        self.channels
+            .iter()
+            .filter_map(|entry| match entry.split_once(':') {
+                Some((c, id)) => (c == channel && id == chat_id).then_some(2),
+                None => (entry == channel).then_some(1),
+            })
+            .max()
+    }
 }
 
 impl Config {
     pub fn workspace_path(&self) -> PathBuf {
-        expand_tilde(&self.agents.defaults.workspace)
+        match &self.active_workspace {
+            Some(name) => self.named_workspace_path(name),
+            None => resolve_workspace(&self.agents.defaults.workspace),
+        }
+    }
+
+    fn named_workspace_path(&self, name: &str) -> PathBuf {
+        match self.workspaces.get(name) {
+            Some(workspace) if !workspace.path.trim().is_empty() => {
+                expand_tilde(workspace.path.trim())
+            }
+            _ => get_data_path()
+                .map(|dir| dir.join("workspaces").join(name))
+                .unwrap_or_else(|_| PathBuf::from("workspaces").join(name)),
+        }
+    }
+
+    /// Makes `name` the workspace for this process; `default` goes back to
+    /// `agents.defaults.workspace`.
+    pub fn select_workspace(&mut self, name: &str) -> Result<()> {
+        let name = name.trim();
+        if name == DEFAULT_WORKSPACE_NAME {
+            self.active_workspace = None;
+            return Ok(());
+        }
+        if self.workspaces.contains_key(name) {
+            self.active_workspace = Some(name.to_string());
+            return Ok(());
+        }
+        let known = self.workspaces.keys().cloned().collect::<Vec<_>>();
+        Err(anyhow!(
+            "unknown workspace '{name}' (configured: {})",
+            if known.is_empty() {
+                "none".to_string()
+            } else {
+                known.join(", ")
+            }
+        ))
+    }
+
+    /// A copy of this config with `name` selected.
+    pub fn for_workspace(&self, name: &str) -> Result<Config> {
+        let mut config = self.clone();
+        config.select_workspace(name)?;
+        Ok(config)
+    }
+
+    /// Where sessions are stored: `~/.nanobot/sessions` for the default
+    /// workspace, `~/.nanobot/workspaces/<name>/sessions` for a named one.
+    pub fn sessions_dir(&self) -> Result<PathBuf> {
+        sessions_dir_for(self.active_workspace.as_deref())
+    }
+
+    /// `tools.readOnly`, or `--read-only` for this process.
+    pub fn read_only(&self) -> bool {
+        self.tools.read_only || READ_ONLY_OVERRIDE.load(Ordering::Relaxed)
+    }
+
+    /// The named workspace the gateway routes `channel:chat_id` to, if any.
+    pub fn workspace_for_chat(&self, channel: &str, chat_id: &str) -> Option<&str> {
+        self.workspaces
+            .iter()
+            .filter_map(|(name, workspace)| {
+                workspace
+                    .claims(channel, chat_id)
+                    .map(|rank| (rank, name.as_str()))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, name)| name)
+    }
+
+    pub fn log_file_path(&self) -> PathBuf {
+        let configured = self.logging.file.path.trim();
+        if configured.is_empty() {
+            self.workspace_path().join("logs").join("nanobot.log")
+        } else {
+            expand_tilde(configured)
+        }
     }
 
     fn match_provider(
@@ -516,36 +1526,63 @@ impl Config {
         ];
 
         for (name, keywords) in mapping {
-            let provider = self.provider_by_name(name);
-            if keywords.iter().any(|kw| m.contains(kw)) && !provider.api_key.is_empty() {
-                return (Some(provider), Some(name));
+            if keywords.iter().any(|kw| m.contains(kw)) && self.provider_api_key(name).is_some() {
+                return (Some(self.provider_by_name(name)), Some(name));
             }
         }
 
-        for name in [
-            "openrouter",
-            "aihubmix",
-            "siliconflow",
-            "volcengine",
-            "anthropic",
-            "openai",
-            "deepseek",
-            "gemini",
-            "minimax",
-            "zhipu",
-            "dashscope",
-            "moonshot",
-            "vllm",
-            "groq",
-        ] {
-            let provider = self.provider_by_name(name);
-            if !provider.api_key.is_empty() {
-                return (Some(provider), Some(name));
+        for name in PROVIDER_NAMES {
+            if self.provider_api_key(name).is_some() {
+                return (Some(self.provider_by_name(name)), Some(name));
             }
         }
         (None, None)
     }
 
+    /// A model whose calls go through the provider that `key` sets up, for
+    /// checking a `providers.<name>.*` change: the default model when it uses
+    /// that provider, else the first router model that does. Keys shared by
+    /// all providers (`retry`, `cache`, ...) get the default model.
+    pub fn model_for_provider_key(&self, key: &str) -> Result<String> {
+        let default_model = &self.agents.defaults.model;
+        let name = key.split('.').nth(1).unwrap_or_default();
+        if name != "bedrock" && !PROVIDER_NAMES.contains(&name) {
+            return Ok(default_model.clone());
+        }
+        let router = &self.router;
+        let models = [default_model, &router.fast_model, &router.scorer_model]
+            .into_iter()
+            .chain(router.tiers.values());
+        let uses_provider = |model: &str| {
+            let bedrock = model
+                .strip_prefix("litellm/")
+                .unwrap_or(model)
+                .starts_with("bedrock/");
+            match name {
+                "bedrock" => bedrock,
+                _ => !bedrock && self.get_provider_name(Some(model)).as_deref() == Some(name),
+            }
+        };
+        models
+            .filter(|model| !model.trim().is_empty())
+            .find(|model| uses_provider(model))
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!("no configured model uses providers.{name}, so '{key}' cannot be checked")
+            })
+    }
+
+    /// Providers with an API key, in fallback order, with their settings and key.
+    pub fn configured_providers(&self) -> Vec<(&'static str, &ProviderConfig, String)> {
+        PROVIDER_NAMES
+            .into_iter()
+            .filter_map(|name| {
+                let key = self.provider_api_key(name)?;
+                Some((name, self.provider_by_name(name), key))
+            })
+            .collect()
+    }
+
     fn provider_by_name(&self, name: &str) -> &ProviderConfig {
         match name {
             "openrouter" => &self.providers.openrouter,
@@ -566,6 +1603,19 @@ impl Config {
         }
     }
 
+    /// API key for a provider, falling back to `NANOBOT_<NAME>_API_KEY` or
+    /// `<NAME>_API_KEY` from the environment or the service secrets file so
+    /// keys never have to be written to the shared config.
+    fn provider_api_key(&self, name: &str) -> Option<String> {
+        let provider = self.provider_by_name(name);
+        if !provider.api_key.is_empty() {
+            return Some(provider.api_key.clone());
+        }
+        let upper = name.to_ascii_uppercase();
+        crate::secrets::lookup(&format!("NANOBOT_{upper}_API_KEY"))
+            .or_else(|| crate::secrets::lookup(&format!("{upper}_API_KEY")))
+    }
+
     pub fn get_provider(&self, model: Option<&str>) -> Option<&ProviderConfig> {
         let (provider, _) = self.match_provider(model);
         provider
@@ -577,18 +1627,16 @@ impl Config {
     }
 
     pub fn get_api_key(&self, model: Option<&str>) -> Option<String> {
-        if let Some(provider) = self.get_provider(model) {
-            return Some(provider.api_key.clone());
-        }
-        None
+        let (_, name) = self.match_provider(model);
+        self.provider_api_key(name?)
     }
 
     pub fn get_api_base(&self, model: Option<&str>) -> Option<String> {
         let (provider, name) = self.match_provider(model);
-        if let Some(provider) = provider {
-            if provider.api_base.is_some() {
-                return provider.api_base.clone();
-            }
+        if let Some(provider) = provider
+            && provider.api_base.is_some()
+        {
+            return provider.api_base.clone();
         }
         match name {
             Some("openrouter") => Some(
@@ -631,7 +1679,74 @@ impl Config {
     }
 }
 
+/// Providers picked when the model name matches none, in this order.
+const PROVIDER_NAMES: [&str; 14] = [
+    "openrouter",
+    "aihubmix",
+    "siliconflow",
+    "volcengine",
+    "anthropic",
+    "openai",
+    "deepseek",
+    "gemini",
+    "minimax",
+    "zhipu",
+    "dashscope",
+    "moonshot",
+    "vllm",
+    "groq",
+];
+
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+static WORKSPACE_OVERRIDE: OnceLock<String> = OnceLock::new();
+static READ_ONLY_OVERRIDE: AtomicBool = AtomicBool::new(false);
+/// `--workspace default` selects `agents.defaults.workspace`.
+const DEFAULT_WORKSPACE_NAME: &str = "default";
+
+fn sessions_dir_for(workspace: Option<&str>) -> Result<PathBuf> {
+    let data = get_data_path()?;
+    Ok(match workspace {
+        Some(name) if name != DEFAULT_WORKSPACE_NAME => {
+            data.join("workspaces").join(name).join("sessions")
+        }
+        _ => data.join("sessions"),
+    })
+}
+
+/// Sessions directory of the workspace chosen with `--workspace`.
+pub fn default_sessions_dir() -> Result<PathBuf> {
+    sessions_dir_for(WORKSPACE_OVERRIDE.get().map(|name| name.trim()))
+}
+
+/// Selects a named workspace in every config loaded by this process
+/// (`--workspace`).
+pub fn set_workspace_override(name: String) {
+    let _ = WORKSPACE_OVERRIDE.set(name);
+}
+
+/// Forces read-only mode for this process (`--read-only`) without touching
+/// the saved config.
+pub fn set_read_only_override() {
+    READ_ONLY_OVERRIDE.store(true, Ordering::Relaxed);
+}
+
+/// Uses `path` as the config file for this process (`--config`). Its parent
+/// directory becomes the data directory so instances stay isolated.
+pub fn set_config_path(path: PathBuf) {
+    if let Some(parent) = path.parent() {
+        set_data_dir_override(parent.to_path_buf());
+    }
+    let _ = CONFIG_PATH_OVERRIDE.set(path);
+}
+
+pub fn config_path_override() -> Option<&'static Path> {
+    CONFIG_PATH_OVERRIDE.get().map(PathBuf::as_path)
+}
+
 pub fn get_config_path() -> Result<PathBuf> {
+    if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+        return Ok(path.clone());
+    }
     Ok(get_data_path()?.join("config.json"))
 }
 
@@ -641,16 +1756,19 @@ pub fn load_config(config_path: Option<&Path>) -> Result<Config> {
         None => get_config_path()?,
     };
 
-    if !path.exists() {
-        return Ok(Config::default());
+    let mut config = if path.exists() {
+        let raw = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read config: {}", path.display()))?;
+        let mut value: Value = serde_json::from_str(&raw)
+            .with_context(|| format!("invalid JSON in {}", path.display()))?;
+        migrate_config(&mut value);
+        serde_json::from_value::<Config>(value).context("failed to parse config structure")?
+    } else {
+        Config::default()
+    };
+    if let Some(name) = WORKSPACE_OVERRIDE.get() {
+        config.select_workspace(name)?;
     }
-
-    let raw = std::fs::read_to_string(&path)
-        .with_context(|| format!("failed to read config: {}", path.display()))?;
-    let mut value: Value = serde_json::from_str(&raw)
-        .with_context(|| format!("invalid JSON in {}", path.display()))?;
-    migrate_config(&mut value);
-    let config = serde_json::from_value(value).context("failed to parse config structure")?;
     Ok(config)
 }
 
@@ -667,6 +1785,89 @@ pub fn save_config(config: &Config, config_path: Option<&Path>) -> Result<()> {
     Ok(())
 }
 
+/// One value changed in the config file (`nanobot config set`): the file's
+/// JSON with the change, and the config it parses to.
+pub struct ConfigEdit {
+    raw: Value,
+    pub config: Config,
+}
+
+impl ConfigEdit {
+    /// Sets `key`, a dotted path of config names such as
+    /// `providers.openai.apiKey`, to `value` in the config file. The value is
+    /// read as JSON (`true`, `4`, `["a"]`) when that fits the field and as a
+    /// string otherwise.
+    pub fn new(key: &str, value: &str) -> Result<Self> {
+        let path = get_config_path()?;
+        let raw = if path.exists() {
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read config: {}", path.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("invalid JSON in {}", path.display()))?
+        } else {
+            serde_json::to_value(Config::default())?
+        };
+        Self::apply(raw, key, value)
+    }
+
+    fn apply(raw: Value, key: &str, value: &str) -> Result<Self> {
+        let segments = key.split('.').map(str::trim).collect::<Vec<_>>();
+        if segments.iter().any(|segment| segment.is_empty()) {
+            return Err(anyhow!("invalid config key '{key}'"));
+        }
+        let pointer = format!("/{}", segments.join("/"));
+        let candidates = serde_json::from_str::<Value>(value)
+            .ok()
+            .into_iter()
+            .chain([Value::String(value.to_string())]);
+        let mut last_error = None;
+        for candidate in candidates {
+            let mut edited = raw.clone();
+            let mut node = &mut edited;
+            for segment in &segments {
+                if !node.is_object() {
+                    *node = Value::Object(Map::new());
+                }
+                node = node
+                    .as_object_mut()
+                    .expect("just made an object")
+                    .entry(segment.to_string())
+                    .or_insert(Value::Null);
+            }
+            *node = candidate.clone();
+            let mut migrated = edited.clone();
+            migrate_config(&mut migrated);
+            match serde_json::from_value::<Config>(migrated) {
+                Ok(config) => {
+                    // Unknown names parse fine and are dropped; catch them here.
+                    if serde_json::to_value(&config)?.pointer(&pointer) != Some(&candidate) {
+                        return Err(anyhow!("unknown config key '{key}'"));
+                    }
+                    return Ok(Self {
+                        raw: edited,
+                        config,
+                    });
+                }
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(anyhow!(
+            "invalid value for {key}: {}",
+            last_error.map(|err| err.to_string()).unwrap_or_default()
+        ))
+    }
+
+    /// Writes the changed file, keeping everything else as it was.
+    pub fn save(&self) -> Result<()> {
+        let path = get_config_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&self.raw)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
 fn migrate_config(value: &mut Value) {
     let Some(root) = value.as_object_mut() else {
         return;
@@ -675,12 +1876,11 @@ fn migrate_config(value: &mut Value) {
         return;
     };
     let should_migrate = tools.get("restrictToWorkspace").is_none();
-    if should_migrate {
-        if let Some(exec) = tools.get_mut("exec").and_then(Value::as_object_mut) {
-            if let Some(v) = exec.remove("restrictToWorkspace") {
-                tools.insert("restrictToWorkspace".to_string(), v);
-            }
-        }
+    if should_migrate
+        && let Some(exec) = tools.get_mut("exec").and_then(Value::as_object_mut)
+        && let Some(v) = exec.remove("restrictToWorkspace")
+    {
+        tools.insert("restrictToWorkspace".to_string(), v);
     }
 }
 
@@ -744,3 +1944,102 @@ pub fn providers_status(config: &Config) -> Map<String, Value> {
     );
     map
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_workspaces_are_selected_and_routed() {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "workspaces": {
+                "personal": { "path": "/tmp/personal", "channels": ["telegram"] },
+                "work": { "channels": ["slack", "telegram:42"] }
+            }
+        }))
+        .expect("config");
+
+        assert_eq!(config.workspace_for_chat("telegram", "7"), Some("personal"));
+        assert_eq!(config.workspace_for_chat("telegram", "42"), Some("work"));
+        assert_eq!(config.workspace_for_chat("slack", "C1"), Some("work"));
+        assert_eq!(config.workspace_for_chat("discord", "1"), None);
+
+        config.select_workspace("personal").expect("select");
+        assert_eq!(config.workspace_path(), PathBuf::from("/tmp/personal"));
+        assert!(
+            config
+                .sessions_dir()
+                .expect("sessions")
+                .ends_with("workspaces/personal/sessions")
+        );
+        let work = config.for_workspace("work").expect("work");
+        assert!(work.workspace_path().ends_with("workspaces/work"));
+        assert!(config.select_workspace("holiday").is_err());
+        config.select_workspace("default").expect("default");
+        assert_eq!(config.active_workspace, None);
+        assert!(
+            config
+                .sessions_dir()
+                .expect("sessions")
+                .ends_with("sessions")
+        );
+        assert!(
+            !serde_json::to_string(&work)
+                .expect("json")
+                .contains("activeWorkspace")
+        );
+    }
+
+    #[test]
+    fn config_edits_keep_the_file_and_type_the_value() {
+        let raw =
+            serde_json::json!({ "providers": { "openai": { "apiKey": "old" } }, "custom": 1 });
+        let edit =
+            ConfigEdit::apply(raw.clone(), "providers.openai.apiKey", "sk-new").expect("key");
+        assert_eq!(edit.config.providers.openai.api_key, "sk-new");
+        assert_eq!(edit.raw["custom"], 1);
+
+        let edit = ConfigEdit::apply(raw.clone(), "agents.defaults.maxConcurrentTurns", "8")
+            .expect("number");
+        assert_eq!(edit.config.agents.defaults.max_concurrent_turns, 8);
+        // Digits still make a string field.
+        let edit =
+            ConfigEdit::apply(raw.clone(), "providers.openai.apiKey", "123").expect("digits");
+        assert_eq!(edit.config.providers.openai.api_key, "123");
+
+        assert!(ConfigEdit::apply(raw.clone(), "providers.openai.apiKye", "x").is_err());
+        assert!(ConfigEdit::apply(raw, "agents.defaults.maxConcurrentTurns", "many").is_err());
+    }
+
+    #[test]
+    fn provider_keys_are_checked_through_their_own_provider() {
+        let mut config = Config::default();
+        config.agents.defaults.model = "openai/gpt-4o".to_string();
+        config.providers.openai.api_key = "sk-openai".to_string();
+        config.providers.anthropic.api_key = "sk-ant".to_string();
+        assert_eq!(
+            config
+                .model_for_provider_key("providers.openai.apiKey")
+                .expect("default"),
+            "openai/gpt-4o"
+        );
+        assert!(
+            config
+                .model_for_provider_key("providers.anthropic.apiKey")
+                .is_err()
+        );
+        config.router.fast_model = "anthropic/claude-haiku".to_string();
+        assert_eq!(
+            config
+                .model_for_provider_key("providers.anthropic.apiKey")
+                .expect("router"),
+            "anthropic/claude-haiku"
+        );
+        assert_eq!(
+            config
+                .model_for_provider_key("providers.retry.maxAttempts")
+                .expect("shared"),
+            "openai/gpt-4o"
+        );
+    }
+}
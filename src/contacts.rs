@@ -0,0 +1,390 @@
+//! The user's contact book, one Markdown file per person in
+//! `<workspace>/contacts/`.
+//!
+//! ```text
+//! ---
+//! name: Anna Schmidt
+//! aliases:
+//! - Anna
+//! relationship: sister
+//! birthday: 1990-03-14
+//! emails:
+//! - anna@example.com
+//! updatedAt: 2026-10-15T20:14:03+08:00
+//! ---
+//! Allergic to peanuts. Lives in Hamburg.
+//! ```
+//!
+//! A birthday (`YYYY-MM-DD`, or `MM-DD` when the year is unknown) gets a
+//! yearly cron job that reminds the chat the contact was saved from;
+//! removing the contact or its birthday cancels it.
+
+use crate::cron::{CronSchedule, CronService};
+use crate::utils::with_path_lock;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Hour of the day birthday reminders are sent, local time.
+const BIRTHDAY_REMINDER_HOUR: u32 = 9;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub relationship: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub birthday: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emails: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phones: Vec<String>,
+    /// Cron job that sends the yearly birthday reminder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reminder_job: Option<String>,
+    pub updated_at: DateTime<Local>,
+    #[serde(skip)]
+    pub notes: String,
+}
+
+impl Contact {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            aliases: Vec::new(),
+            relationship: String::new(),
+            birthday: None,
+            emails: Vec::new(),
+            phones: Vec::new(),
+            reminder_job: None,
+            updated_at: Local::now(),
+            notes: String::new(),
+        }
+    }
+
+    /// Whether `name` is this contact's name or one of its aliases.
+    pub fn is_called(&self, name: &str) -> bool {
+        let name = name.trim();
+        self.name.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        [&self.name, &self.relationship, &self.notes]
+            .into_iter()
+            .chain(&self.aliases)
+            .chain(&self.emails)
+            .chain(&self.phones)
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+
+    /// The next birthday on or after `today`. February 29 is celebrated on
+    /// the 28th in other years.
+    pub fn next_birthday(&self, today: NaiveDate) -> Option<NaiveDate> {
+        let (_, month, day) = parse_birthday(self.birthday.as_deref()?).ok()?;
+        let on = |year: i32| {
+            NaiveDate::from_ymd_opt(year, month, day)
+                .or_else(|| NaiveDate::from_ymd_opt(year, month, day - 1))
+        };
+        on(today.year())
+            .filter(|date| *date >= today)
+            .or_else(|| on(today.year() + 1))
+    }
+
+    /// `Anna Schmidt (sister), birthday 1990-03-14, anna@example.com`
+    pub fn line(&self) -> String {
+        let mut line = self.name.clone();
+        if !self.relationship.is_empty() {
+            line.push_str(&format!(" ({})", self.relationship));
+        }
+        if !self.aliases.is_empty() {
+            line.push_str(&format!(", aka {}", self.aliases.join(", ")));
+        }
+        if let Some(birthday) = &self.birthday {
+            line.push_str(&format!(", birthday {birthday}"));
+        }
+        for contact in self.emails.iter().chain(&self.phones) {
+            line.push_str(&format!(", {contact}"));
+        }
+        line
+    }
+
+    /// `line()` followed by the notes.
+    pub fn details(&self) -> String {
+        match self.notes.trim() {
+            "" => self.line(),
+            notes => format!("{}\n{notes}", self.line()),
+        }
+    }
+
+    fn to_markdown(&self) -> Result<String> {
+        let front = serde_yaml::to_string(self)?;
+        let notes = self.notes.trim();
+        Ok(if notes.is_empty() {
+            format!("---\n{front}---\n")
+        } else {
+            format!("---\n{front}---\n{notes}\n")
+        })
+    }
+
+    fn from_markdown(text: &str) -> Result<Self> {
+        let rest = text
+            .strip_prefix("---\n")
+            .or_else(|| text.strip_prefix("---\r\n"))
+            .ok_or_else(|| anyhow!("missing front matter"))?;
+        let end = rest
+            .find("\n---")
+            .ok_or_else(|| anyhow!("unterminated front matter"))?;
+        let mut contact: Contact = serde_yaml::from_str(&rest[..end])?;
+        contact.notes = rest[end + 4..]
+            .split_once('\n')
+            .map(|(_, body)| body.trim().to_string())
+            .unwrap_or_default();
+        Ok(contact)
+    }
+}
+
+/// Accepts `YYYY-MM-DD`, `MM-DD` or `--MM-DD` (vCard style) and returns the
+/// year, if given, month and day.
+pub fn parse_birthday(raw: &str) -> Result<(Option<i32>, u32, u32)> {
+    let raw = raw.trim();
+    let invalid = || anyhow!("invalid birthday '{raw}': expected YYYY-MM-DD or MM-DD");
+    if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        return Ok((Some(date.year()), date.month(), date.day()));
+    }
+    let (month, day) = raw
+        .trim_start_matches("--")
+        .split_once('-')
+        .and_then(|(m, d)| Some((m.parse::<u32>().ok()?, d.parse::<u32>().ok()?)))
+        .ok_or_else(invalid)?;
+    // 2000 is a leap year, so February 29 passes.
+    NaiveDate::from_ymd_opt(2000, month, day).ok_or_else(invalid)?;
+    Ok((None, month, day))
+}
+
+/// `raw` as stored: `YYYY-MM-DD` or `MM-DD`.
+pub fn normalize_birthday(raw: &str) -> Result<String> {
+    Ok(match parse_birthday(raw)? {
+        (Some(year), month, day) => format!("{year:04}-{month:02}-{day:02}"),
+        (None, month, day) => format!("{month:02}-{day:02}"),
+    })
+}
+
+pub struct ContactStore {
+    dir: PathBuf,
+}
+
+impl ContactStore {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            dir: workspace.join("contacts"),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        let slug = name
+            .trim()
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '-' })
+            .collect::<String>();
+        let slug = slug
+            .split('-')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-");
+        self.dir.join(format!("{slug}.md"))
+    }
+
+    /// Every contact, by name.
+    pub fn list(&self) -> Vec<Contact> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut contacts = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .filter_map(|path| {
+                let text = std::fs::read_to_string(&path).ok()?;
+                match Contact::from_markdown(&text) {
+                    Ok(contact) => Some(contact),
+                    Err(err) => {
+                        tracing::warn!("skipping contact {}: {err:#}", path.display());
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        contacts.sort_by_key(|contact| contact.name.to_lowercase());
+        contacts
+    }
+
+    /// The contact called `name` (or with that alias).
+    pub fn get(&self, name: &str) -> Option<Contact> {
+        self.list()
+            .into_iter()
+            .find(|contact| contact.is_called(name))
+    }
+
+    /// Contacts with `query` in any field, notes included.
+    pub fn search(&self, query: &str) -> Vec<Contact> {
+        self.list()
+            .into_iter()
+            .filter(|contact| contact.matches(query))
+            .collect()
+    }
+
+    /// Contacts whose birthday falls within `days` of `today`, soonest first.
+    pub fn upcoming_birthdays(&self, today: NaiveDate, days: i64) -> Vec<(NaiveDate, Contact)> {
+        let mut upcoming = self
+            .list()
+            .into_iter()
+            .filter_map(|contact| Some((contact.next_birthday(today)?, contact)))
+            .filter(|(date, _)| (*date - today).num_days() <= days)
+            .collect::<Vec<_>>();
+        upcoming.sort_by_key(|(date, _)| *date);
+        upcoming
+    }
+
+    /// Writes `contact`, replacing the file of the contact it was loaded as
+    /// when `previous_name` differs (a rename).
+    pub fn save(&self, contact: &Contact, previous_name: Option<&str>) -> Result<()> {
+        if contact.name.trim().is_empty() {
+            bail!("contact name cannot be empty");
+        }
+        with_path_lock(&self.dir, || {
+            std::fs::create_dir_all(&self.dir)?;
+            let path = self.path(&contact.name);
+            if let Some(previous) = previous_name.map(|name| self.path(name))
+                && previous != path
+            {
+                let _ = std::fs::remove_file(previous);
+            }
+            std::fs::write(&path, contact.to_markdown()?)
+                .with_context(|| format!("failed to write {}", path.display()))
+        })
+    }
+
+    pub fn remove(&self, name: &str) -> Result<Contact> {
+        with_path_lock(&self.dir, || {
+            let contact = self
+                .get(name)
+                .ok_or_else(|| anyhow!("no contact named {name}"))?;
+            std::fs::remove_file(self.path(&contact.name))?;
+            Ok(contact)
+        })
+    }
+}
+
+/// Schedules the yearly birthday reminder, delivered to `channel`/`to`.
+/// Contacts without a birthday get none.
+pub async fn schedule_birthday_reminder(
+    cron: &CronService,
+    contact: &mut Contact,
+    channel: &str,
+    to: &str,
+) -> Result<()> {
+    let Some(birthday) = contact.birthday.as_deref() else {
+        return Ok(());
+    };
+    let (year, month, day) = parse_birthday(birthday)?;
+    // Every year, so February 29 birthdays are reminded on the 28th.
+    let day = if (month, day) == (2, 29) { 28 } else { day };
+    let schedule = CronSchedule {
+        kind: "cron".to_string(),
+        expr: Some(format!("0 0 {BIRTHDAY_REMINDER_HOUR} {day} {month} *")),
+        tz: Some("local".to_string()),
+        ..Default::default()
+    };
+    let born = year
+        .map(|year| format!(" (born {year})"))
+        .unwrap_or_default();
+    let relationship = match contact.relationship.as_str() {
+        "" => String::new(),
+        relationship => format!(", the user's {relationship},"),
+    };
+    let message = format!(
+        "Remind the user that today is {}'s birthday{born}. {}{relationship} may appreciate a message; offer to help write one.",
+        contact.name, contact.name
+    );
+    let job = cron
+        .add_job(
+            format!("birthday: {}", contact.name),
+            schedule,
+            message,
+            true,
+            Some(channel.to_string()),
+            Some(to.to_string()),
+            false,
+        )
+        .await?;
+    contact.reminder_job = Some(job.id);
+    Ok(())
+}
+
+pub async fn cancel_birthday_reminder(cron: &CronService, contact: &mut Contact) -> Result<()> {
+    if let Some(job) = contact.reminder_job.take() {
+        cron.remove_job(&job).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contacts_round_trip_and_find_birthdays() {
+        let dir = std::env::temp_dir().join(format!("nanobot-contacts-{}", uuid::Uuid::new_v4()));
+        let store = ContactStore::new(&dir);
+        let mut anna = Contact::new("Anna Schmidt");
+        anna.aliases = vec!["Anna".to_string()];
+        anna.relationship = "sister".to_string();
+        anna.birthday = Some(normalize_birthday("1990-3-14").expect("birthday"));
+        anna.emails = vec!["anna@example.com".to_string()];
+        anna.notes = "Allergic to peanuts.".to_string();
+        store.save(&anna, None).expect("save");
+        let mut leap = Contact::new("Leo");
+        leap.birthday = Some(normalize_birthday("--02-29").expect("birthday"));
+        store.save(&leap, None).expect("save");
+
+        let loaded = store.get("anna").expect("by alias");
+        assert_eq!(loaded.birthday.as_deref(), Some("1990-03-14"));
+        assert_eq!(loaded.notes, "Allergic to peanuts.");
+        assert_eq!(
+            loaded.line(),
+            "Anna Schmidt (sister), aka Anna, birthday 1990-03-14, anna@example.com"
+        );
+        assert_eq!(store.search("PEANUT").len(), 1);
+
+        let today = NaiveDate::from_ymd_opt(2027, 2, 20).unwrap();
+        let upcoming = store.upcoming_birthdays(today, 14);
+        let dates = upcoming
+            .iter()
+            .map(|(date, contact)| (date.to_string(), contact.name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(dates, [("2027-02-28".to_string(), "Leo")]);
+        let after = NaiveDate::from_ymd_opt(2027, 3, 15).unwrap();
+        assert_eq!(
+            loaded.next_birthday(after),
+            NaiveDate::from_ymd_opt(2028, 3, 14)
+        );
+
+        let mut renamed = loaded.clone();
+        renamed.name = "Anna Meyer".to_string();
+        store.save(&renamed, Some(&loaded.name)).expect("rename");
+        assert_eq!(store.list().len(), 2);
+        store.remove("Anna Meyer").expect("remove");
+        assert!(store.get("anna").is_none());
+        assert!(normalize_birthday("13-01").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::cron::types::{CronJob, CronJobState, CronPayload, CronSchedule, CronStore};
+use crate::router;
 use anyhow::Result;
-use chrono::{TimeZone, Utc};
+use chrono::{Local, TimeZone, Utc};
 use cron::Schedule;
 use futures_util::future::BoxFuture;
 use std::str::FromStr;
@@ -31,6 +32,11 @@ fn compute_next_run(schedule: &CronSchedule, now_ms: i64) -> Option<i64> {
         "cron" => {
             let expr = schedule.expr.as_ref()?;
             let parsed = Schedule::from_str(expr).ok()?;
+            // Expressions are UTC unless the schedule asks for local time.
+            if schedule.tz.as_deref() == Some("local") {
+                let now = Local.timestamp_millis_opt(now_ms).single()?;
+                return parsed.after(&now).next().map(|dt| dt.timestamp_millis());
+            }
             let now = Utc.timestamp_millis_opt(now_ms).single()?;
             parsed.after(&now).next().map(|dt| dt.timestamp_millis())
         }
@@ -101,7 +107,7 @@ impl CronService {
                         let callback = on_job.lock().await.clone();
                         let result = if let Some(callback) = callback {
                             let fut = callback(job.clone());
-                            fut.await
+                            router::with_job_tier(job.payload.tier.as_deref(), fut).await
                         } else {
                             Ok(None)
                         };
@@ -150,6 +156,18 @@ impl CronService {
         }
     }
 
+    /// False when the service was started but its runner task has died.
+    pub async fn is_alive(&self) -> bool {
+        if !self.running.load(Ordering::Relaxed) {
+            return true;
+        }
+        self.runner
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
     async fn recompute_next_runs(&self) {
         let mut store = self.store.lock().await;
         let now = now_ms();
@@ -187,6 +205,19 @@ impl CronService {
         jobs
     }
 
+    /// Every job, disabled ones included, soonest first. A service that is
+    /// not running reads the store file first, as a gateway elsewhere may
+    /// have changed it.
+    pub async fn current_jobs(&self) -> Vec<CronJob> {
+        if !self.running.load(Ordering::Relaxed)
+            && let Err(err) = self.load_store().await
+        {
+            tracing::warn!("failed to read the cron store: {err:#}");
+        }
+        self.list_jobs(true).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn add_job(
         &self,
         name: String,
@@ -196,6 +227,25 @@ impl CronService {
         channel: Option<String>,
         to: Option<String>,
         delete_after_run: bool,
+    ) -> Result<CronJob> {
+        let payload = CronPayload {
+            kind: "agent_turn".to_string(),
+            message,
+            deliver,
+            channel,
+            to,
+            tier: None,
+        };
+        self.add_job_with_payload(name, schedule, payload, delete_after_run)
+            .await
+    }
+
+    pub async fn add_job_with_payload(
+        &self,
+        name: String,
+        schedule: CronSchedule,
+        payload: CronPayload,
+        delete_after_run: bool,
     ) -> Result<CronJob> {
         let now = now_ms();
         let job = CronJob {
@@ -203,13 +253,7 @@ impl CronService {
             name,
             enabled: true,
             schedule: schedule.clone(),
-            payload: CronPayload {
-                kind: "agent_turn".to_string(),
-                message,
-                deliver,
-                channel,
-                to,
-            },
+            payload,
             state: CronJobState {
                 next_run_at_ms: compute_next_run(&schedule, now),
                 ..Default::default()
@@ -273,7 +317,7 @@ impl CronService {
 
         let callback = self.on_job.lock().await.clone();
         let result = if let Some(callback) = callback {
-            callback(job.clone()).await
+            router::with_job_tier(job.payload.tier.as_deref(), callback(job.clone())).await
         } else {
             Ok(None)
         };
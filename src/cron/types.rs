@@ -7,6 +7,8 @@ pub struct CronSchedule {
     pub at_ms: Option<i64>,
     pub every_ms: Option<i64>,
     pub expr: Option<String>,
+    /// `local` evaluates `expr` in the configured time zone instead of UTC;
+    /// jobs added from the CLI, the cron tool and workflows use it.
     pub tz: Option<String>,
 }
 
@@ -30,6 +32,9 @@ pub struct CronPayload {
     pub deliver: bool,
     pub channel: Option<String>,
     pub to: Option<String>,
+    /// Quality tier (`router.tiers`) the job's turns run on.
+    #[serde(default)]
+    pub tier: Option<String>,
 }
 
 impl Default for CronPayload {
@@ -40,6 +45,7 @@ impl Default for CronPayload {
             deliver: false,
             channel: None,
             to: None,
+            tier: None,
         }
     }
 }
@@ -0,0 +1,184 @@
+use crate::VERSION;
+use crate::config::Config;
+use crate::logging::recent_log_lines;
+use crate::redact::redact;
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde_json::Value;
+use std::backtrace::Backtrace;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use zip::write::SimpleFileOptions;
+
+/// Minimum gap between bundles so a crash-looping task cannot fill the disk.
+const MIN_BUNDLE_INTERVAL_S: i64 = 600;
+
+const SECRET_KEY_HINTS: &[&str] = &["key", "token", "secret", "password", "credential"];
+
+static LAST_BUNDLE_AT: AtomicI64 = AtomicI64::new(0);
+
+fn is_secret_key(key: &str) -> bool {
+    let normalized = key.to_ascii_lowercase().replace(['_', '-'], "");
+    SECRET_KEY_HINTS
+        .iter()
+        .any(|hint| normalized.ends_with(hint))
+}
+
+/// Replaces non-empty values under secret-looking keys with `[REDACTED]`.
+pub fn strip_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let is_set = match child {
+                    Value::String(s) => !s.is_empty(),
+                    Value::Null => false,
+                    _ => true,
+                };
+                if is_secret_key(key) && is_set && !child.is_object() {
+                    *child = Value::String("[REDACTED]".to_string());
+                } else {
+                    strip_secrets(child);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_secrets),
+        _ => {}
+    }
+}
+
+fn versions_text() -> String {
+    format!(
+        "nanobot {VERSION}\nos: {} {}\nfamily: {}\npid: {}\nargs: {}\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::FAMILY,
+        std::process::id(),
+        std::env::args().collect::<Vec<_>>().join(" "),
+    )
+}
+
+/// Writes `diagnostics-<timestamp>.zip` with the failure reason, backtrace,
+/// recent log lines, sanitized config and version info.
+pub fn write_bundle(dir: &Path, reason: &str, backtrace: &str, config: &Config) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(format!(
+        "diagnostics-{}.zip",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let file = std::fs::File::create(&path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+
+    let mut config_json = serde_json::to_value(config)?;
+    strip_secrets(&mut config_json);
+    let entries = [
+        ("reason.txt", redact(reason).into_owned()),
+        ("backtrace.txt", backtrace.to_string()),
+        ("recent.log", recent_log_lines().join("\n")),
+        (
+            "config.json",
+            redact(&serde_json::to_string_pretty(&config_json)?).into_owned(),
+        ),
+        ("versions.txt", versions_text()),
+    ];
+
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+    for (name, content) in entries {
+        zip.start_file(name, options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(path)
+}
+
+fn claim_bundle_slot() -> bool {
+    let now = Local::now().timestamp();
+    let last = LAST_BUNDLE_AT.load(Ordering::Relaxed);
+    now - last >= MIN_BUNDLE_INTERVAL_S
+        && LAST_BUNDLE_AT
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+}
+
+/// Writes a bundle for a fatal error and reports where it went.
+pub fn report_fatal(dir: &Path, reason: &str, config: &Config) {
+    if !claim_bundle_slot() {
+        return;
+    }
+    let backtrace = Backtrace::force_capture().to_string();
+    match write_bundle(dir, reason, &backtrace, config) {
+        Ok(path) => eprintln!("Diagnostic bundle written to {}", path.display()),
+        Err(err) => eprintln!("Failed to write diagnostic bundle: {err}"),
+    }
+}
+
+/// Chains a panic hook that writes a diagnostic bundle after the default
+/// panic message.
+pub fn install_panic_hook(dir: PathBuf, config: Config) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        let thread = std::thread::current();
+        let reason = format!(
+            "panic in thread '{}': {info}",
+            thread.name().unwrap_or("<unnamed>")
+        );
+        tracing::error!("{reason}");
+        report_fatal(&dir, &reason, &config);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Read;
+
+    #[test]
+    fn strips_secret_fields_recursively() {
+        let mut value = json!({
+            "providers": {"openai": {"apiKey": "sk-live", "apiBase": "https://x"}},
+            "channels": {"telegram": {"token": "123:abc", "allowFrom": ["me"]}},
+            "email": {"imapPassword": "", "smtpPassword": "pw"},
+        });
+        strip_secrets(&mut value);
+        assert_eq!(value["providers"]["openai"]["apiKey"], "[REDACTED]");
+        assert_eq!(value["providers"]["openai"]["apiBase"], "https://x");
+        assert_eq!(value["channels"]["telegram"]["token"], "[REDACTED]");
+        assert_eq!(value["channels"]["telegram"]["allowFrom"][0], "me");
+        assert_eq!(value["email"]["imapPassword"], "");
+        assert_eq!(value["email"]["smtpPassword"], "[REDACTED]");
+    }
+
+    #[test]
+    fn bundle_contains_expected_entries() {
+        let dir = std::env::temp_dir().join(format!("nanobot-diag-{}", uuid::Uuid::new_v4()));
+        let mut config = Config::default();
+        config.providers.openai.api_key = "sk-secret-value".to_string();
+        let path = write_bundle(&dir, "boom", "frame 0", &config).expect("bundle");
+
+        let mut archive =
+            zip::ZipArchive::new(std::fs::File::open(&path).expect("open")).expect("zip");
+        let mut names = archive.file_names().map(String::from).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "backtrace.txt",
+                "config.json",
+                "reason.txt",
+                "recent.log",
+                "versions.txt"
+            ]
+        );
+        let mut config_text = String::new();
+        archive
+            .by_name("config.json")
+            .expect("config entry")
+            .read_to_string(&mut config_text)
+            .expect("read");
+        assert!(!config_text.contains("sk-secret-value"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,174 @@
+//! High-level API for running the assistant inside another application.
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! let bot = nanobot::Nanobot::from_config("/etc/mybot/config.json")?;
+//! let answer = bot.chat("alice", "What's on my calendar?").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Provider selection, model routing, budgets, tools, memory and sessions are
+//! set up from the config the same way `nanobot agent` does.
+
+use crate::agent::events::EventSender;
+use crate::agent::{AgentLoop, AgentLoopBuilder};
+use crate::bus::{MessageBus, OutboundMessage};
+use crate::config::{Config, load_config};
+use crate::providers::base::LLMProvider;
+use crate::providers::build_provider;
+use crate::router;
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use std::sync::Arc;
+
+const EMBED_CHANNEL: &str = "sdk";
+
+pub struct Nanobot {
+    config: Config,
+    bus: Arc<MessageBus>,
+    agent: Arc<AgentLoop>,
+}
+
+impl Nanobot {
+    /// Loads `path` (missing file: defaults) and builds the assistant from it.
+    pub fn from_config(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(load_config(Some(path.as_ref()))?)
+    }
+
+    /// Uses `~/.nanobot/config.json`, like the CLI.
+    pub fn from_default_config() -> Result<Self> {
+        Self::new(load_config(None)?)
+    }
+
+    pub fn new(config: Config) -> Result<Self> {
+        Self::new_with(config, |builder| builder)
+    }
+
+    /// Like `new`, letting `customize` adjust the agent (extra tools, an
+    /// approval gate, a different session store) before it is built.
+    pub fn new_with(
+        config: Config,
+        customize: impl FnOnce(AgentLoopBuilder) -> AgentLoopBuilder,
+    ) -> Result<Self> {
+        let model = config.agents.defaults.model.clone();
+        let normalized = model.strip_prefix("litellm/").unwrap_or(&model);
+        let api_key = match config.get_api_key(Some(&model)) {
+            Some(key) => key,
+            None if normalized.starts_with("bedrock/") => "dummy".to_string(),
+            None => return Err(anyhow!("no API key configured for {model}")),
+        };
+        let provider =
+            router::wrap_provider(build_provider(&config, &model, api_key), &config, |fast| {
+                let key = config.get_api_key(Some(fast)).unwrap_or_default();
+                build_provider(&config, fast, key)
+            });
+        Self::with_provider(config, provider, customize)
+    }
+
+    /// Builds the assistant around an already constructed provider.
+    pub fn with_provider(
+        config: Config,
+        provider: Arc<dyn LLMProvider>,
+        customize: impl FnOnce(AgentLoopBuilder) -> AgentLoopBuilder,
+    ) -> Result<Self> {
+        let bus = Arc::new(MessageBus::new(1024));
+        let builder = AgentLoopBuilder::from_config(provider, &config).bus(bus.clone());
+        let agent = Arc::new(customize(builder).build()?);
+        Ok(Self { config, bus, agent })
+    }
+
+    /// Sends `message` in `session` and returns the reply. Sessions are
+    /// created on first use; a plain name is kept under the `sdk` channel.
+    pub async fn chat(&self, session: &str, message: &str) -> Result<String> {
+        let key = session_key(session);
+        self.agent
+            .process_direct(message, Some(&key), None, None)
+            .await
+    }
+
+    /// `chat`, reporting deltas and tool progress to `events`.
+    pub async fn chat_streaming(
+        &self,
+        session: &str,
+        message: &str,
+        events: EventSender,
+    ) -> Result<String> {
+        let key = session_key(session);
+        self.agent
+            .process_direct_streaming(message, Some(&key), None, None, events)
+            .await
+    }
+
+    /// The next message the agent sent on its own (the `message` tool,
+    /// subagent results, generated images).
+    pub async fn next_outbound(&self) -> Option<OutboundMessage> {
+        self.bus.consume_outbound().await
+    }
+
+    /// The agent's bus, for subscribing to its topics.
+    pub fn bus(&self) -> &Arc<MessageBus> {
+        &self.bus
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The underlying agent, for anything the facade does not cover.
+    pub fn agent(&self) -> &Arc<AgentLoop> {
+        &self.agent
+    }
+}
+
+fn session_key(session: &str) -> String {
+    if session.contains(':') {
+        session.to_string()
+    } else {
+        format!("{EMBED_CHANNEL}:{session}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Cassette, EvalProvider};
+    use crate::providers::base::LLMResponse;
+    use crate::session::SessionManager;
+
+    #[tokio::test]
+    async fn chats_through_the_configured_agent() {
+        let dir = std::env::temp_dir().join(format!("nanobot-embed-{}", uuid::Uuid::new_v4()));
+        let mut cassette = Cassette::default();
+        cassette.cases.insert(
+            String::new(),
+            vec![LLMResponse {
+                content: Some("hello there".to_string()),
+                tool_calls: Vec::new(),
+                finish_reason: "stop".to_string(),
+                usage: Default::default(),
+                reasoning_content: None,
+                images: Vec::new(),
+            }],
+        );
+        let provider = Arc::new(EvalProvider::replay(cassette, "test-model"));
+        let mut config = Config::default();
+        config.agents.defaults.workspace = dir.join("workspace").display().to_string();
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let bot = Nanobot::with_provider(config, provider, |builder| {
+            builder.sessions(sessions.clone())
+        })
+        .expect("bot");
+
+        assert_eq!(bot.chat("alice", "hi").await.expect("reply"), "hello there");
+        assert!(
+            sessions
+                .get_or_create("sdk:alice")
+                .expect("session")
+                .messages
+                .len()
+                >= 2
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,477 @@
+//! Prompt regression suites for `nanobot eval`.
+//!
+//! A suite is a YAML file of cases, each a prompt plus assertions on the
+//! final answer and the tools the agent called. LLM responses can be
+//! recorded to a cassette and replayed later for deterministic runs.
+
+use crate::agent::AgentLoop;
+use crate::config::ModelPrice;
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EvalSuite {
+    pub name: String,
+    pub model: Option<String>,
+    /// Used to estimate the cost of a run.
+    pub pricing: Option<ModelPrice>,
+    pub cases: Vec<EvalCase>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct EvalCase {
+    pub name: String,
+    pub prompt: String,
+    pub expect: Vec<Assertion>,
+}
+
+/// One expectation; every field that is set must hold.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Assertion {
+    pub contains: Option<String>,
+    pub not_contains: Option<String>,
+    pub regex: Option<String>,
+    pub json_path: Option<String>,
+    pub equals: Option<Value>,
+    pub called_tool: Option<String>,
+}
+
+pub fn load_suite(path: &Path) -> Result<EvalSuite> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let mut suite: EvalSuite = serde_yaml::from_str(&text)
+        .with_context(|| format!("invalid eval suite {}", path.display()))?;
+    if suite.name.is_empty() {
+        suite.name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "eval".to_string());
+    }
+    for (index, case) in suite.cases.iter_mut().enumerate() {
+        if case.name.is_empty() {
+            case.name = format!("case-{}", index + 1);
+        }
+    }
+    Ok(suite)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    fn add_response(&mut self, response: &LLMResponse) {
+        let (prompt, completion) = response.token_usage();
+        self.prompt_tokens += prompt;
+        self.completion_tokens += completion;
+    }
+
+    pub fn add(&mut self, other: TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    pub fn cost(&self, pricing: &ModelPrice) -> f64 {
+        pricing.cost(self.prompt_tokens, self.completion_tokens)
+    }
+}
+
+/// Recorded LLM responses keyed by case name, replayed in order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cassette {
+    pub cases: BTreeMap<String, Vec<LLMResponse>>,
+}
+
+impl Cassette {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read cassette {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("invalid cassette {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write cassette {}", path.display()))
+    }
+}
+
+#[derive(Default)]
+struct EvalState {
+    case: String,
+    replay: BTreeMap<String, VecDeque<LLMResponse>>,
+    recorded: BTreeMap<String, Vec<LLMResponse>>,
+    usage: TokenUsage,
+    tools_called: Vec<String>,
+}
+
+/// Provider wrapper that tracks usage and tool calls per case, and either
+/// forwards to a live provider (recording responses) or replays a cassette.
+pub struct EvalProvider {
+    inner: Option<Arc<dyn LLMProvider>>,
+    default_model: String,
+    state: Mutex<EvalState>,
+}
+
+impl EvalProvider {
+    pub fn live(inner: Arc<dyn LLMProvider>) -> Self {
+        Self {
+            default_model: inner.default_model().to_string(),
+            inner: Some(inner),
+            state: Mutex::new(EvalState::default()),
+        }
+    }
+
+    pub fn replay(cassette: Cassette, default_model: &str) -> Self {
+        let replay = cassette
+            .cases
+            .into_iter()
+            .map(|(case, responses)| (case, responses.into_iter().collect()))
+            .collect();
+        Self {
+            inner: None,
+            default_model: default_model.to_string(),
+            state: Mutex::new(EvalState {
+                replay,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, EvalState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn begin_case(&self, name: &str) {
+        let mut state = self.state();
+        state.case = name.to_string();
+        state.usage = TokenUsage::default();
+        state.tools_called.clear();
+        state.recorded.remove(name);
+    }
+
+    /// Usage and tool names seen since the last `begin_case`.
+    pub fn case_trace(&self) -> (TokenUsage, Vec<String>) {
+        let state = self.state();
+        (state.usage, state.tools_called.clone())
+    }
+
+    pub fn cassette(&self) -> Cassette {
+        Cassette {
+            cases: self.state().recorded.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for EvalProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        let response = match &self.inner {
+            Some(inner) => {
+                inner
+                    .chat_with_options(messages, tools, model, max_tokens, temperature, options)
+                    .await?
+            }
+            None => {
+                let mut state = self.state();
+                let case = state.case.clone();
+                state
+                    .replay
+                    .get_mut(&case)
+                    .and_then(VecDeque::pop_front)
+                    .ok_or_else(|| anyhow!("cassette has no more responses for case '{case}'"))?
+            }
+        };
+        let mut state = self.state();
+        state.usage.add_response(&response);
+        let names = response.tool_calls.iter().map(|call| call.name.clone());
+        state.tools_called.extend(names);
+        let case = state.case.clone();
+        state
+            .recorded
+            .entry(case)
+            .or_default()
+            .push(response.clone());
+        Ok(response)
+    }
+
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        match &self.inner {
+            Some(inner) => inner.capabilities(model),
+            None => Capabilities::for_model(model),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub name: String,
+    pub response: String,
+    pub failures: Vec<String>,
+    pub usage: TokenUsage,
+    pub tools_called: Vec<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Resolves `$.a.b[0]` (or `a.b.0`) against a JSON value.
+pub fn json_path_lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+    for segment in path.replace('[', ".").replace(']', "").split('.') {
+        if segment.is_empty() {
+            continue;
+        }
+        current = match current {
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            Value::Object(map) => map.get(segment)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Parses the answer as JSON, falling back to a fenced block or the
+/// outermost braces so chatty answers around a JSON payload still work.
+fn parse_json_answer(answer: &str) -> Option<Value> {
+    let trimmed = answer.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Some(value);
+    }
+    if let Some(start) = trimmed.find("```") {
+        let body = &trimmed[start + 3..];
+        let body = body.split_once('\n').map(|(_, rest)| rest).unwrap_or(body);
+        if let Some(end) = body.find("```")
+            && let Ok(value) = serde_json::from_str(body[..end].trim())
+        {
+            return Some(value);
+        }
+    }
+    let start = trimmed.find(['{', '['])?;
+    let end = trimmed.rfind(['}', ']'])?;
+    serde_json::from_str(trimmed.get(start..=end)?).ok()
+}
+
+pub fn check_assertion(
+    assertion: &Assertion,
+    answer: &str,
+    tools_called: &[String],
+) -> Vec<String> {
+    let mut failures = Vec::new();
+    if let Some(needle) = &assertion.contains
+        && !answer.contains(needle.as_str())
+    {
+        failures.push(format!("expected answer to contain {needle:?}"));
+    }
+    if let Some(needle) = &assertion.not_contains
+        && answer.contains(needle.as_str())
+    {
+        failures.push(format!("expected answer not to contain {needle:?}"));
+    }
+    if let Some(pattern) = &assertion.regex {
+        match Regex::new(pattern) {
+            Ok(regex) if regex.is_match(answer) => {}
+            Ok(_) => failures.push(format!("expected answer to match /{pattern}/")),
+            Err(err) => failures.push(format!("invalid regex /{pattern}/: {err}")),
+        }
+    }
+    if let Some(path) = &assertion.json_path {
+        match parse_json_answer(answer) {
+            None => failures.push(format!("{path}: answer is not JSON")),
+            Some(json) => match (json_path_lookup(&json, path), &assertion.equals) {
+                (None, _) => failures.push(format!("{path}: not found")),
+                (Some(actual), Some(expected)) if actual != expected => {
+                    failures.push(format!("{path}: expected {expected}, got {actual}"))
+                }
+                _ => {}
+            },
+        }
+    }
+    if let Some(tool) = &assertion.called_tool
+        && !tools_called.iter().any(|name| name == tool)
+    {
+        failures.push(format!("expected tool '{tool}' to be called"));
+    }
+    failures
+}
+
+/// Runs every case in a fresh session and collects the results.
+pub async fn run_suite(
+    suite: &EvalSuite,
+    agent: &AgentLoop,
+    provider: &EvalProvider,
+) -> Vec<CaseResult> {
+    let mut results = Vec::new();
+    for (index, case) in suite.cases.iter().enumerate() {
+        provider.begin_case(&case.name);
+        let session_key = format!("eval:{}-{}", index + 1, uuid::Uuid::new_v4());
+        let outcome = agent
+            .process_direct(&case.prompt, Some(&session_key), None, None)
+            .await;
+        let (usage, tools_called) = provider.case_trace();
+        let (response, failures) = match outcome {
+            Ok(response) => {
+                let failures = case
+                    .expect
+                    .iter()
+                    .flat_map(|assertion| check_assertion(assertion, &response, &tools_called))
+                    .collect();
+                (response, failures)
+            }
+            Err(err) => (String::new(), vec![format!("agent error: {err}")]),
+        };
+        results.push(CaseResult {
+            name: case.name.clone(),
+            response,
+            failures,
+            usage,
+            tools_called,
+        });
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::ToolCallRequest;
+    use serde_json::{Map, json};
+
+    #[test]
+    fn suite_parses_yaml_assertions() {
+        let suite: EvalSuite = serde_yaml::from_str(
+            r#"
+name: smoke
+pricing: { inputPerMillion: 3, outputPerMillion: 15 }
+cases:
+  - name: weather
+    prompt: "What's the weather?"
+    expect:
+      - calledTool: web_search
+      - regex: "(?i)sunny|rain"
+      - jsonPath: $.temp
+        equals: 21
+"#,
+        )
+        .expect("suite");
+        assert_eq!(suite.cases.len(), 1);
+        let expect = &suite.cases[0].expect;
+        assert_eq!(expect[0].called_tool.as_deref(), Some("web_search"));
+        assert_eq!(expect[2].equals, Some(json!(21)));
+        assert_eq!(suite.pricing.expect("pricing").output_per_million, 15.0);
+    }
+
+    #[test]
+    fn json_path_and_assertions() {
+        let value = json!({"items": [{"id": 7}], "ok": true});
+        assert_eq!(json_path_lookup(&value, "$.items[0].id"), Some(&json!(7)));
+        assert_eq!(json_path_lookup(&value, "ok"), Some(&json!(true)));
+        assert_eq!(json_path_lookup(&value, "$.items[3]"), None);
+
+        let answer = "Here you go:\n```json\n{\"status\": \"done\"}\n```";
+        let assertion = Assertion {
+            json_path: Some("$.status".to_string()),
+            equals: Some(json!("done")),
+            contains: Some("go".to_string()),
+            called_tool: Some("exec".to_string()),
+            ..Default::default()
+        };
+        assert!(check_assertion(&assertion, answer, &["exec".to_string()]).is_empty());
+        let failures = check_assertion(&assertion, answer, &[]);
+        assert_eq!(failures, vec!["expected tool 'exec' to be called"]);
+    }
+
+    #[tokio::test]
+    async fn replay_provider_tracks_usage_and_tools() {
+        let mut usage = Map::new();
+        usage.insert("prompt_tokens".to_string(), json!(100));
+        usage.insert("completion_tokens".to_string(), json!(20));
+        let response = LLMResponse {
+            content: None,
+            tool_calls: vec![ToolCallRequest {
+                id: "1".to_string(),
+                name: "read_file".to_string(),
+                arguments: Map::new(),
+            }],
+            finish_reason: "tool_calls".to_string(),
+            usage,
+            reasoning_content: None,
+            images: Vec::new(),
+        };
+        let cassette = Cassette {
+            cases: BTreeMap::from([("a".to_string(), vec![response])]),
+        };
+        let provider = EvalProvider::replay(cassette, "test-model");
+        provider.begin_case("a");
+        provider
+            .chat(&[], None, None, 100, 0.0)
+            .await
+            .expect("replay");
+        assert!(provider.chat(&[], None, None, 100, 0.0).await.is_err());
+
+        let (usage, tools) = provider.case_trace();
+        assert_eq!(usage.total(), 120);
+        assert_eq!(tools, vec!["read_file"]);
+        let pricing = ModelPrice {
+            input_per_million: 1_000_000.0,
+            output_per_million: 0.0,
+        };
+        assert_eq!(usage.cost(&pricing), 100.0);
+        assert_eq!(provider.cassette().cases["a"].len(), 1);
+    }
+}
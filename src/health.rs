@@ -437,7 +437,7 @@ pub async fn check_update(crate_name: &str) -> Result<UpdateReport> {
     let mut latest_version = None::<String>;
     let mut registry_error = None::<String>;
 
-    let response = reqwest::Client::new()
+    let response = crate::network::client()
         .get(url)
         .header("User-Agent", "nanobot-rs-update-check")
         .send()
@@ -1,3 +1,4 @@
+use crate::router;
 use futures_util::future::BoxFuture;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -83,7 +84,8 @@ impl HeartbeatService {
 
                 let callback = on_heartbeat.lock().await.clone();
                 if let Some(callback) = callback {
-                    let response = callback(HEARTBEAT_PROMPT.to_string()).await;
+                    let response =
+                        router::with_job_tier(None, callback(HEARTBEAT_PROMPT.to_string())).await;
                     let normalized = response.to_uppercase().replace('_', "");
                     let ok = HEARTBEAT_OK_TOKEN.to_uppercase().replace('_', "");
                     if normalized.contains(&ok) {
@@ -104,10 +106,22 @@ impl HeartbeatService {
         }
     }
 
+    /// False when the service was started but its loop task has died.
+    pub async fn is_alive(&self) -> bool {
+        if !self.running.load(Ordering::Relaxed) {
+            return true;
+        }
+        self.task
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
     pub async fn trigger_now(&self) -> Option<String> {
         let callback = self.on_heartbeat.lock().await.clone();
         match callback {
-            Some(cb) => Some(cb(HEARTBEAT_PROMPT.to_string()).await),
+            Some(cb) => Some(router::with_job_tier(None, cb(HEARTBEAT_PROMPT.to_string())).await),
             None => None,
         }
     }
@@ -0,0 +1,67 @@
+# Agent replies
+tools-none = No tools are registered in this runtime.
+tools-available =
+    Tools available in this runtime:
+    - { $tools }
+    To browse the web, run commands, work with files, or schedule tasks, just tell me the goal.
+session-new = 🐈 New session started. Memory consolidated.
+session-compacted = 🐈 Compacted { $messages } messages ({ $before } → { $after } characters). Undo with: nanobot sessions compact { $session } --undo
+session-compact-failed = Could not compact this session: { $error }
+pending-done =
+    ✅ Ran `{ $tool }`:
+    { $result }
+pending-cancelled = Cancelled: `{ $tool }` was not run.
+content-filtered = ⚠️ The model provider's content filter blocked this response. Try rephrasing the request.
+error-auth = 🔑 I can't reach the AI model right now because its API key was rejected. Please let the operator know.
+error-quota = 💳 The AI provider account is out of credit or over its spending limit. Please let the operator know.
+error-rate-limited = ⏳ The AI provider is getting too many requests. Please try again in a minute.
+error-context-overflow = 📚 This conversation is too long for the model. Start fresh with /new or shrink it with /compact.
+error-content-filter = ⚠️ The model provider's content filter blocked this request. Try rephrasing it.
+error-network = 🌐 I couldn't reach the AI provider. Please try again shortly.
+error-invalid-request = ⚙️ The AI provider rejected the request, which usually means a model or settings problem. Please let the operator know.
+error-server = 🛠️ The AI provider is having trouble on its side. Please try again later.
+error-timeout = ⏱️ That took too long and was stopped. Try again, or ask for something smaller.
+error-internal = ⚠️ Something went wrong on my side and I couldn't answer. Please try again.
+error-reference = Reference: { $ref }
+help-header = 🐈 nanobot commands:
+command-new = Start a new conversation
+command-compact = Summarize the conversation so far to keep prompts small
+command-status = Show the model, session and runtime state
+command-model = Show or switch the model for this chat
+command-usage = Show token usage and cost for this chat and today
+command-help = Show available commands
+status-summary =
+    🐈 nanobot { $version }
+    Model: { $model }
+    Session: { $session } ({ $messages } messages)
+    Tools: { $tools } available
+    Turns in progress: { $active }
+model-current = Model for this chat: { $model }. Switch with /model <name>, go back with /model default.
+model-set = 🐈 This chat now uses { $model }.
+model-reset = 🐈 This chat is back on the default model, { $model }.
+model-not-allowed = The { $role } role can't use { $model }. Available: { $models }.
+access-budget-exceeded = You've used today's allowance (${ $spent } of ${ $limit }). Try again tomorrow.
+usage-summary =
+    📊 This chat: { $session_calls } calls, { $session_tokens } tokens{ $session_cost }
+    Today, all chats: { $today_calls } calls, { $today_tokens } tokens{ $today_cost }
+
+# CLI
+onboard-config-exists = Config already exists at { $path }
+onboard-created-config = Created config at { $path }
+onboard-created-workspace = Created workspace at { $path }
+onboard-created-file = Created { $path }
+onboard-ready = nanobot-rs is ready.
+onboard-next-steps = Next steps:
+onboard-step-api-key = 1. Add your API key to { $path }
+onboard-step-chat = 2. Chat: nanobot-rs agent -m "Hello!"
+no-api-key = No API key configured.
+no-api-key-hint = Set one in ~/.nanobot/config.json under providers.*.apiKey
+interactive-banner = nanobot-rs interactive mode (type exit/quit or Ctrl+C to exit)
+goodbye = Goodbye!
+thinking = Thinking...
+prompt-reloaded = Reloaded workspace instructions: { $files }
+gateway-started = Gateway started on { $url }
+shutting-down = Shutting down...
+drain-notice = I'm restarting and can't take new requests right now. Please try again in a minute.
+draining = Finishing { $count } in-flight turn(s), waiting up to { $seconds }s (press Ctrl+C again to stop now)...
+drain-timeout = Stopped before these turns finished: { $sessions }
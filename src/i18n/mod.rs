@@ -0,0 +1,160 @@
+//! Localized user-facing strings (Fluent).
+//!
+//! The locale comes from `locale` in config; `auto` follows `LC_ALL`,
+//! `LC_MESSAGES` or `LANG`. Messages missing from a locale fall back to
+//! English.
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+pub const SUPPORTED_LOCALES: &[&str] = &["en", "zh"];
+
+const EN_FTL: &str = include_str!("en.ftl");
+const ZH_FTL: &str = include_str!("zh.ftl");
+
+static LOCALE: OnceLock<&'static str> = OnceLock::new();
+static BUNDLES: OnceLock<Vec<(&'static str, FluentBundle<FluentResource>)>> = OnceLock::new();
+
+fn build_bundle(locale: &str, source: &str) -> FluentBundle<FluentResource> {
+    let langid: LanguageIdentifier = locale.parse().expect("builtin locale id is valid");
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // Isolation marks around arguments show up as stray characters in terminals.
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(source.to_string()).expect("builtin translations parse");
+    bundle
+        .add_resource(resource)
+        .expect("builtin translations have unique ids");
+    bundle
+}
+
+fn bundles() -> &'static [(&'static str, FluentBundle<FluentResource>)] {
+    BUNDLES.get_or_init(|| {
+        vec![
+            ("en", build_bundle("en", EN_FTL)),
+            ("zh", build_bundle("zh", ZH_FTL)),
+        ]
+    })
+}
+
+/// Maps a setting or POSIX locale (`zh_CN.UTF-8`, `en-US`) to a supported
+/// locale, or `None` when unsupported.
+pub fn normalize_locale(value: &str) -> Option<&'static str> {
+    let language = value
+        .split(['.', '@'])
+        .next()
+        .unwrap_or("")
+        .split(['_', '-'])
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    SUPPORTED_LOCALES
+        .iter()
+        .copied()
+        .find(|locale| *locale == language)
+}
+
+pub fn detect_locale() -> &'static str {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|value| !value.trim().is_empty())
+        .and_then(|value| normalize_locale(&value))
+        .unwrap_or("en")
+}
+
+/// Selects the locale once at startup; later calls are ignored.
+pub fn init(setting: &str) {
+    let setting = setting.trim();
+    let locale = if setting.is_empty() || setting.eq_ignore_ascii_case("auto") {
+        detect_locale()
+    } else {
+        normalize_locale(setting).unwrap_or_else(|| {
+            tracing::warn!("unsupported locale '{setting}', using English");
+            "en"
+        })
+    };
+    let _ = LOCALE.set(locale);
+}
+
+pub fn current_locale() -> &'static str {
+    LOCALE.get().copied().unwrap_or("en")
+}
+
+fn format_in(locale: &str, id: &str, args: Option<&FluentArgs>) -> Option<String> {
+    let (_, bundle) = bundles().iter().find(|(name, _)| *name == locale)?;
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    Some(
+        bundle
+            .format_pattern(pattern, args, &mut errors)
+            .into_owned(),
+    )
+}
+
+fn lookup(id: &str, args: Option<&FluentArgs>) -> String {
+    format_in(current_locale(), id, args)
+        .or_else(|| format_in("en", id, args))
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// The message `id` in the current locale.
+pub fn tr(id: &str) -> String {
+    lookup(id, None)
+}
+
+/// The message `id` with `{ $name }` placeholders filled from `args`.
+pub fn tr_args(id: &str, args: &[(&str, &str)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.to_string());
+    }
+    lookup(id, Some(&fluent_args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(source: &str) -> Vec<String> {
+        let mut ids = source
+            .lines()
+            .filter(|line| !line.starts_with([' ', '#']) && line.contains(" ="))
+            .map(|line| line.split(" =").next().unwrap_or("").to_string())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn locales_define_the_same_messages() {
+        assert_eq!(ids(EN_FTL), ids(ZH_FTL));
+        for id in ids(EN_FTL) {
+            assert!(format_in("zh", &id, None).is_some(), "zh missing {id}");
+        }
+    }
+
+    #[test]
+    fn normalizes_posix_and_bcp47_locales() {
+        assert_eq!(normalize_locale("zh_CN.UTF-8"), Some("zh"));
+        assert_eq!(normalize_locale("en-US"), Some("en"));
+        assert_eq!(normalize_locale("ZH"), Some("zh"));
+        assert_eq!(normalize_locale("fr_FR"), None);
+        assert_eq!(normalize_locale("C"), None);
+    }
+
+    #[test]
+    fn formats_arguments_without_isolation_marks() {
+        let mut args = FluentArgs::new();
+        args.set("url", "http://127.0.0.1:18790");
+        assert_eq!(
+            format_in("en", "gateway-started", Some(&args)).as_deref(),
+            Some("Gateway started on http://127.0.0.1:18790")
+        );
+        let mut args = FluentArgs::new();
+        args.set("tools", "exec\n- read_file");
+        let text = format_in("zh", "tools-available", Some(&args)).expect("message");
+        assert!(text.starts_with("当前运行时可用工具：\n- exec\n- read_file\n"));
+    }
+}
@@ -0,0 +1,67 @@
+# Agent replies
+tools-none = 当前运行时未注册任何工具。
+tools-available =
+    当前运行时可用工具：
+    - { $tools }
+    如需执行网络访问、命令执行、文件操作或定时任务，请直接给出目标。
+session-new = 🐈 已开始新会话，记忆已整理。
+session-compacted = 🐈 已压缩 { $messages } 条消息（{ $before } → { $after } 字符）。撤销：nanobot sessions compact { $session } --undo
+session-compact-failed = 无法压缩此会话：{ $error }
+pending-done =
+    ✅ 已执行 `{ $tool }`：
+    { $result }
+pending-cancelled = 已取消：`{ $tool }` 未执行。
+content-filtered = ⚠️ 模型服务商的内容过滤拦截了此回复，请换一种说法再试。
+error-auth = 🔑 暂时无法连接 AI 模型：API 密钥被拒绝。请联系管理员。
+error-quota = 💳 AI 服务商账户余额不足或已超出消费上限。请联系管理员。
+error-rate-limited = ⏳ AI 服务商当前请求过多，请稍后再试。
+error-context-overflow = 📚 当前对话对模型来说太长了。用 /new 开始新对话，或用 /compact 压缩。
+error-content-filter = ⚠️ 模型服务商的内容过滤拦截了此请求，请换一种说法再试。
+error-network = 🌐 无法连接 AI 服务商，请稍后再试。
+error-invalid-request = ⚙️ AI 服务商拒绝了请求，通常是模型或配置问题。请联系管理员。
+error-server = 🛠️ AI 服务商出现故障，请稍后再试。
+error-timeout = ⏱️ 处理时间过长，已停止。请重试，或把请求拆小一些。
+error-internal = ⚠️ 我这边出了点问题，没能回答。请再试一次。
+error-reference = 参考编号：{ $ref }
+help-header = 🐈 nanobot 命令：
+command-new = 开始新的对话
+command-compact = 将目前的对话总结为摘要，缩小提示词
+command-status = 查看模型、会话与运行状态
+command-model = 查看或切换本会话使用的模型
+command-usage = 查看本会话及今日的 token 用量与费用
+command-help = 显示可用命令
+status-summary =
+    🐈 nanobot { $version }
+    模型：{ $model }
+    会话：{ $session }（{ $messages } 条消息）
+    工具：{ $tools } 个可用
+    进行中的对话：{ $active }
+model-current = 本会话使用的模型：{ $model }。用 /model <名称> 切换，/model default 恢复默认。
+model-set = 🐈 本会话已切换为 { $model }。
+model-reset = 🐈 本会话已恢复默认模型 { $model }。
+model-not-allowed = { $role } 角色不能使用 { $model }。可用模型：{ $models }。
+access-budget-exceeded = 你今天的额度已用完（已用 ${ $spent }，上限 ${ $limit }），请明天再试。
+usage-summary =
+    📊 本会话：{ $session_calls } 次调用，{ $session_tokens } tokens{ $session_cost }
+    今日全部会话：{ $today_calls } 次调用，{ $today_tokens } tokens{ $today_cost }
+
+# CLI
+onboard-config-exists = 配置文件已存在：{ $path }
+onboard-created-config = 已创建配置文件：{ $path }
+onboard-created-workspace = 已创建工作区：{ $path }
+onboard-created-file = 已创建 { $path }
+onboard-ready = nanobot-rs 已就绪。
+onboard-next-steps = 下一步：
+onboard-step-api-key = 1. 在 { $path } 中填写 API Key
+onboard-step-chat = 2. 开始对话：nanobot-rs agent -m "你好！"
+no-api-key = 未配置 API Key。
+no-api-key-hint = 请在 ~/.nanobot/config.json 的 providers.*.apiKey 中设置
+interactive-banner = nanobot-rs 交互模式（输入 exit/quit 或按 Ctrl+C 退出）
+goodbye = 再见！
+thinking = 思考中...
+prompt-reloaded = 已重新加载工作区指令：{ $files }
+gateway-started = 网关已启动：{ $url }
+shutting-down = 正在关闭...
+drain-notice = 我正在重启，暂时无法处理新的请求，请稍后再试。
+draining = 正在等待 { $count } 个进行中的对话完成，最多 { $seconds } 秒（再次按 Ctrl+C 立即退出）...
+drain-timeout = 以下对话未完成即已停止：{ $sessions }
@@ -1,18 +1,58 @@
+pub mod access;
+pub mod admin;
 pub mod agent;
+pub mod apikeys;
+pub mod artifacts;
+pub mod attachments;
+pub mod bookmarks;
+pub mod briefing;
+pub mod budget;
 pub mod bus;
 pub mod channels;
+pub mod chatui;
 pub mod config;
+pub mod contacts;
 pub mod cron;
+pub mod diagnostics;
+pub mod embed;
+pub mod eval;
 pub mod health;
 pub mod heartbeat;
+pub mod i18n;
+pub mod listener;
+pub mod location;
+pub mod logbook;
+pub mod logging;
+pub mod maintenance;
+pub mod markdown;
 pub mod memory;
+pub mod network;
+pub mod outbox;
 pub mod pairing;
+pub mod presence;
+pub mod prompts;
 pub mod providers;
+pub mod publish;
+pub mod redact;
+pub mod router;
+pub mod secrets;
 pub mod service;
 pub mod session;
 pub mod skills;
+pub mod tasks;
+pub mod telemetry;
+pub mod terminal;
+pub mod tool_stats;
 pub mod tools;
+pub mod transcript;
+pub mod usage;
 pub mod utils;
+pub mod vault;
+pub mod voice;
+pub mod watchdog;
 pub mod webui;
+pub mod workflows;
+
+pub use embed::Nanobot;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
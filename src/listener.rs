@@ -0,0 +1,369 @@
+use crate::config::GatewayConfig;
+use crate::utils::expand_tilde;
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tiny_http::{Request, Server, SslConfig};
+use tracing::warn;
+
+/// Binds the gateway HTTP listener: a UNIX socket when `unixSocket` is set,
+/// otherwise host:port, over TLS when `tls.enabled`.
+pub fn bind_gateway_server(config: &GatewayConfig) -> Result<Server> {
+    let socket = config.unix_socket.trim();
+    if !socket.is_empty() {
+        return bind_unix(socket);
+    }
+    let addr = format!("{}:{}", config.host, config.port);
+    let server = if config.tls.enabled {
+        Server::https(&addr, load_tls(config)?)
+    } else {
+        Server::http(&addr)
+    };
+    server.map_err(|err| anyhow!("failed to bind gateway on {addr}: {err}"))
+}
+
+#[cfg(unix)]
+fn bind_unix(socket: &str) -> Result<Server> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    let path = expand_tilde(socket);
+    // A socket left behind by an unclean shutdown would make bind fail;
+    // anything else at the path is not ours to delete.
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if !metadata.file_type().is_socket() {
+            return Err(anyhow!(
+                "gateway.unixSocket {} exists and is not a socket",
+                path.display()
+            ));
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let server = Server::http_unix(&path)
+        .map_err(|err| anyhow!("failed to bind gateway on {}: {err}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("failed to restrict {}", path.display()))?;
+    Ok(server)
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_socket: &str) -> Result<Server> {
+    Err(anyhow!(
+        "gateway.unixSocket is only supported on unix platforms"
+    ))
+}
+
+fn load_tls(config: &GatewayConfig) -> Result<SslConfig> {
+    let read = |label: &str, path: &str| -> Result<Vec<u8>> {
+        if path.trim().is_empty() {
+            return Err(anyhow!("gateway.tls.enabled requires gateway.tls.{label}"));
+        }
+        let path = expand_tilde(path.trim());
+        std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+    };
+    Ok(SslConfig {
+        certificate: read("certPath", &config.tls.cert_path)?,
+        private_key: read("keyPath", &config.tls.key_path)?,
+    })
+}
+
+pub fn listen_url(config: &GatewayConfig) -> String {
+    let socket = config.unix_socket.trim();
+    if !socket.is_empty() {
+        return format!("unix:{}", expand_tilde(socket).display());
+    }
+    let scheme = if config.tls.enabled { "https" } else { "http" };
+    format!("{scheme}://{}:{}", config.host, config.port)
+}
+
+/// A group of HTTP routes mounted on the gateway listener; hands the request
+/// back when it is not one of them.
+pub type RouteHandler = Arc<dyn Fn(Request) -> Option<Request> + Send + Sync>;
+
+/// Takes over the connection to answer with `text/event-stream`; `None`
+/// when the client is already gone.
+pub fn into_event_stream(request: Request) -> Option<Box<dyn Write + Send + 'static>> {
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+        Cache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    (writer.write_all(head.as_bytes()).is_ok() && writer.flush().is_ok()).then_some(writer)
+}
+
+/// Writes one server-sent event; false once the client has disconnected.
+pub fn write_event(writer: &mut dyn Write, event: &str, data: &Value) -> bool {
+    writer
+        .write_all(format!("event: {event}\ndata: {data}\n\n").as_bytes())
+        .and_then(|_| writer.flush())
+        .is_ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    fn parse(value: &str) -> Option<Self> {
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+            None => (value.parse::<IpAddr>().ok()?, None),
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Self {
+            network: addr,
+            prefix,
+        })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            other => other,
+        };
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Proxies allowed to report the real client address via X-Forwarded-For.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    pub fn new(entries: &[String]) -> Self {
+        let mut ranges = Vec::new();
+        for entry in entries.iter().map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            match Cidr::parse(entry) {
+                Some(cidr) => ranges.push(cidr),
+                None => warn!("ignoring invalid trusted proxy '{entry}'"),
+            }
+        }
+        Self { ranges }
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// The originating client: the peer itself unless it is a trusted proxy,
+    /// in which case the right-most untrusted X-Forwarded-For hop. Peers on a
+    /// UNIX socket (`None`) are local proxies and always trusted.
+    pub fn client_ip(&self, peer: Option<IpAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        if let Some(peer) = peer
+            && !self.contains(peer)
+        {
+            return Some(peer);
+        }
+        let hops = forwarded_for
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|hop| hop.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+        hops.iter()
+            .rev()
+            .find(|hop| !self.contains(**hop))
+            .or(hops.first())
+            .copied()
+            .or(peer)
+    }
+
+    pub fn request_client_ip(&self, request: &Request) -> Option<IpAddr> {
+        let forwarded_for = header(request, "X-Forwarded-For");
+        self.client_ip(request.remote_addr().map(|addr| addr.ip()), forwarded_for)
+    }
+
+    /// Why a request without an API key may not act for the owner, with the
+    /// status to answer: it must come from loopback or the UNIX socket,
+    /// address a loopback host so a rebound DNS name cannot reach it, and
+    /// carry no `Origin` but its own so other web pages cannot post to it.
+    pub fn local_request_error(&self, request: &Request) -> Option<(u16, &'static str)> {
+        let client = self.request_client_ip(request);
+        if client.is_some_and(|client| !client.is_loopback()) {
+            return Some((
+                401,
+                "remote clients need an API key (see `nanobot apikeys create`)",
+            ));
+        }
+        let host = header(request, "Host").unwrap_or_default();
+        if client.is_some() && !is_loopback_host(host) {
+            return Some((
+                403,
+                "requests without an API key must address a loopback host",
+            ));
+        }
+        if let Some(origin) = header(request, "Origin")
+            && !origin
+                .split_once("://")
+                .is_some_and(|(_, authority)| authority.eq_ignore_ascii_case(host))
+        {
+            return Some((403, "cross-origin requests need an API key"));
+        }
+        None
+    }
+}
+
+fn header<'r>(request: &'r Request, name: &'static str) -> Option<&'r str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str().trim())
+}
+
+/// Whether a `Host` header names this machine: `localhost` or a loopback
+/// address, with or without a port.
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Whether the request body is declared as JSON. Browsers only send other
+/// pages' cross-site posts without a preflight as form or plain-text
+/// bodies, so JSON routes refuse those.
+pub fn is_json(request: &Request) -> bool {
+    header(request, "Content-Type").is_some_and(|value| {
+        value
+            .split(';')
+            .next()
+            .is_some_and(|essence| essence.trim().eq_ignore_ascii_case("application/json"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().expect("ip")
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_sockets_replace_only_stale_sockets() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("nanobot-socket-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("dir");
+        let file = dir.join("notes.txt");
+        std::fs::write(&file, "keep me").expect("write");
+        assert!(bind_unix(&file.display().to_string()).is_err());
+        assert_eq!(std::fs::read_to_string(&file).expect("read"), "keep me");
+
+        let socket = dir.join("gateway.sock").display().to_string();
+        drop(bind_unix(&socket).expect("bind"));
+        let server = bind_unix(&socket).expect("rebind");
+        let mode = std::fs::metadata(&socket)
+            .expect("metadata")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        drop(server);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn keyless_requests_must_be_local_and_same_origin() {
+        use tiny_http::{Header, TestRequest};
+        let request = |remote: &str, headers: &[(&str, &str)]| -> Request {
+            headers
+                .iter()
+                .fold(
+                    TestRequest::new().with_remote_addr(remote.parse().expect("addr")),
+                    |request, (field, value)| {
+                        request.with_header(Header::from_bytes(*field, *value).expect("header"))
+                    },
+                )
+                .into()
+        };
+        let proxies = TrustedProxies::default();
+        let local = "127.0.0.1:5000";
+        let page = [
+            ("Host", "127.0.0.1:18790"),
+            ("Origin", "http://127.0.0.1:18790"),
+        ];
+        assert_eq!(proxies.local_request_error(&request(local, &page)), None);
+        let curl = [("Host", "localhost:18790")];
+        assert_eq!(proxies.local_request_error(&request(local, &curl)), None);
+
+        let remote = proxies.local_request_error(&request("203.0.113.9:5000", &curl));
+        assert_eq!(remote.map(|(status, _)| status), Some(401));
+        let rebound = [("Host", "evil.example:18790")];
+        assert!(
+            proxies
+                .local_request_error(&request(local, &rebound))
+                .is_some()
+        );
+        let cross_site = [
+            ("Host", "127.0.0.1:18790"),
+            ("Origin", "https://evil.example"),
+        ];
+        assert!(
+            proxies
+                .local_request_error(&request(local, &cross_site))
+                .is_some()
+        );
+
+        assert!(is_json(&request(
+            local,
+            &[("Content-Type", "application/json; charset=utf-8")]
+        )));
+        assert!(!is_json(&request(local, &[("Content-Type", "text/plain")])));
+        assert!(!is_json(&request(local, &[])));
+    }
+
+    #[test]
+    fn cidr_matching() {
+        let proxies = TrustedProxies::new(&[
+            "10.0.0.0/8".to_string(),
+            "::1".to_string(),
+            "bogus".to_string(),
+        ]);
+        assert!(proxies.contains(ip("10.1.2.3")));
+        assert!(proxies.contains(ip("::ffff:10.0.0.1")));
+        assert!(proxies.contains(ip("::1")));
+        assert!(!proxies.contains(ip("11.0.0.1")));
+        assert!(!proxies.contains(ip("::2")));
+    }
+
+    #[test]
+    fn forwarded_for_only_honoured_from_trusted_peers() {
+        let proxies = TrustedProxies::new(&["127.0.0.1".to_string(), "10.0.0.0/8".to_string()]);
+        let xff = Some("203.0.113.9, 198.51.100.7, 10.0.0.2");
+        assert_eq!(
+            proxies.client_ip(Some(ip("127.0.0.1")), xff),
+            Some(ip("198.51.100.7"))
+        );
+        assert_eq!(
+            proxies.client_ip(Some(ip("192.0.2.1")), xff),
+            Some(ip("192.0.2.1"))
+        );
+        assert_eq!(
+            proxies.client_ip(None, Some("10.0.0.5")),
+            Some(ip("10.0.0.5"))
+        );
+        assert_eq!(
+            proxies.client_ip(Some(ip("127.0.0.1")), None),
+            Some(ip("127.0.0.1"))
+        );
+    }
+}
@@ -0,0 +1,608 @@
+//! Where the user is (`location` in config.json): a fixed place, the
+//! machine's IP geolocation, or the last position a phone posted to the
+//! gateway's `POST /location` (OwnTracks, or any `{"lat": .., "lon": ..}`).
+//!
+//! The current location goes into the prompt every turn and stands in for
+//! the briefing's weather location. Place reminders fire when a posted
+//! position enters or leaves one of the named `location.places`; they and
+//! the last position live in `~/.nanobot/`.
+
+use crate::apikeys::ApiKeyGuard;
+use crate::bus::{MessageBus, OutboundMessage};
+use crate::config::{LocationConfig, PlaceConfig};
+use crate::listener::TrustedProxies;
+use crate::utils::get_data_path;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response};
+use tokio::runtime::Handle;
+
+pub const ROUTE: &str = "/location";
+
+const IP_LOOKUP_URL: &str = "https://ipapi.co/json/";
+const IP_REFRESH: Duration = Duration::from_secs(3600);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy_m: Option<f64>,
+    /// `static`, `ip` or `webhook`.
+    pub source: String,
+    pub updated_at: DateTime<Local>,
+}
+
+impl Location {
+    pub fn coordinates(&self) -> Option<(f64, f64)> {
+        self.latitude.zip(self.longitude)
+    }
+
+    /// What to ask a weather service for: the place name, else `lat,lon`.
+    pub fn weather_query(&self) -> Option<String> {
+        if !self.name.is_empty() {
+            return Some(self.name.clone());
+        }
+        self.coordinates()
+            .map(|(lat, lon)| format!("{lat:.4},{lon:.4}"))
+    }
+
+    /// `Berlin, Germany (52.5200, 13.4050), at home; from the phone at 18:02`
+    pub fn describe(&self, places: &BTreeMap<String, PlaceConfig>) -> String {
+        let mut text = self.name.clone();
+        if let Some((lat, lon)) = self.coordinates() {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&format!("({lat:.4}, {lon:.4})"));
+            if let Some(place) = place_at(places, (lat, lon)) {
+                text.push_str(&format!(", at {place}"));
+            }
+        }
+        if self.source == "webhook" {
+            text.push_str(&format!(
+                "; from the phone at {}",
+                self.updated_at.format("%Y-%m-%d %H:%M")
+            ));
+        } else if self.source == "ip" {
+            text.push_str("; approximate, from the IP address");
+        }
+        text
+    }
+}
+
+/// Great-circle distance between two `(lat, lon)` points.
+pub fn distance_m(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.1 - a.1).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// The closest named place whose radius covers `point`.
+pub fn place_at(places: &BTreeMap<String, PlaceConfig>, point: (f64, f64)) -> Option<&str> {
+    places
+        .iter()
+        .map(|(name, place)| {
+            let distance = distance_m(point, (place.latitude, place.longitude));
+            (name, place, distance)
+        })
+        .filter(|(_, place, distance)| *distance <= place.radius_m)
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(name, ..)| name.as_str())
+}
+
+/// Reads a position report: OwnTracks (`_type: location`, `lat`, `lon`,
+/// `acc`) or plain `latitude`/`longitude`, with an optional `name`. `None`
+/// for OwnTracks messages that are not positions.
+pub fn parse_report(body: &Value) -> Result<Option<Location>> {
+    if body
+        .get("_type")
+        .and_then(Value::as_str)
+        .is_some_and(|kind| kind != "location")
+    {
+        return Ok(None);
+    }
+    let number = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| body.get(*key).and_then(Value::as_f64))
+    };
+    let latitude = number(&["lat", "latitude"]).context("missing lat")?;
+    let longitude = number(&["lon", "lng", "longitude"]).context("missing lon")?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        bail!("coordinates out of range");
+    }
+    Ok(Some(Location {
+        name: body
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        latitude: Some(latitude),
+        longitude: Some(longitude),
+        accuracy_m: number(&["acc", "accuracy"]),
+        source: "webhook".to_string(),
+        updated_at: Local::now(),
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaceEvent {
+    Arrive,
+    Leave,
+}
+
+impl PlaceEvent {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "arrive" | "arrival" | "enter" => Ok(Self::Arrive),
+            "leave" | "exit" | "depart" => Ok(Self::Leave),
+            other => bail!("unknown place event '{other}': use arrive or leave"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceReminder {
+    pub id: String,
+    pub place: String,
+    pub on: PlaceEvent,
+    pub message: String,
+    pub channel: String,
+    pub chat_id: String,
+    pub created_at: DateTime<Local>,
+}
+
+impl PlaceReminder {
+    /// `3f2a9c1e  when arriving at home: buy milk`
+    pub fn line(&self) -> String {
+        let on = match self.on {
+            PlaceEvent::Arrive => "arriving at",
+            PlaceEvent::Leave => "leaving",
+        };
+        format!("{}  when {on} {}: {}", self.id, self.place, self.message)
+    }
+}
+
+/// The current location and the one-shot place reminders.
+pub struct LocationService {
+    config: LocationConfig,
+    dir: PathBuf,
+    ip_cache: tokio::sync::Mutex<Option<(Instant, Location)>>,
+    /// Serializes read-modify-write of the state files.
+    write_lock: std::sync::Mutex<()>,
+}
+
+impl LocationService {
+    /// Keeps state in `dir`; see `from_config`.
+    pub fn new(config: LocationConfig, dir: PathBuf) -> Self {
+        Self {
+            config,
+            dir,
+            ip_cache: tokio::sync::Mutex::new(None),
+            write_lock: std::sync::Mutex::new(()),
+        }
+    }
+
+    /// `None` when `location.source` is empty or unknown.
+    pub fn from_config(config: &LocationConfig) -> Option<Arc<Self>> {
+        let source = config.source.trim();
+        match source {
+            "" => return None,
+            "static" | "ip" | "webhook" => {}
+            other => {
+                tracing::warn!("unknown location.source '{other}'; location is disabled");
+                return None;
+            }
+        }
+        let dir = match get_data_path() {
+            Ok(dir) => dir,
+            Err(err) => {
+                tracing::warn!("location disabled: {err}");
+                return None;
+            }
+        };
+        Some(Arc::new(Self::new(config.clone(), dir)))
+    }
+
+    pub fn source(&self) -> &str {
+        self.config.source.trim()
+    }
+
+    pub fn places(&self) -> &BTreeMap<String, PlaceConfig> {
+        &self.config.places
+    }
+
+    fn last_path(&self) -> PathBuf {
+        self.dir.join("location.json")
+    }
+
+    fn reminders_path(&self) -> PathBuf {
+        self.dir.join("place_reminders.json")
+    }
+
+    /// Where the user is now, per the configured source.
+    pub async fn current(&self) -> Result<Option<Location>> {
+        match self.source() {
+            "static" => {
+                let location = Location {
+                    name: self.config.name.trim().to_string(),
+                    latitude: self.config.latitude,
+                    longitude: self.config.longitude,
+                    accuracy_m: None,
+                    source: "static".to_string(),
+                    updated_at: Local::now(),
+                };
+                Ok(
+                    (!location.name.is_empty() || location.coordinates().is_some())
+                        .then_some(location),
+                )
+            }
+            "ip" => {
+                let mut cache = self.ip_cache.lock().await;
+                if let Some((at, location)) = cache.as_ref()
+                    && at.elapsed() < IP_REFRESH
+                {
+                    return Ok(Some(location.clone()));
+                }
+                let location = lookup_ip().await?;
+                *cache = Some((Instant::now(), location.clone()));
+                Ok(Some(location))
+            }
+            "webhook" => Ok(self.last_reported()),
+            _ => Ok(None),
+        }
+    }
+
+    /// The current location for the system prompt; empty when unknown.
+    pub async fn prompt_section(&self) -> String {
+        match self.current().await {
+            Ok(Some(location)) => location.describe(self.places()),
+            Ok(None) => String::new(),
+            Err(err) => {
+                tracing::warn!("location lookup failed: {err:#}");
+                String::new()
+            }
+        }
+    }
+
+    pub fn last_reported(&self) -> Option<Location> {
+        let text = std::fs::read_to_string(self.last_path()).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Stores a posted position and returns the place reminders it sets
+    /// off, which are removed.
+    pub fn report(&self, location: &Location) -> Result<Vec<PlaceReminder>> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let here = |location: Option<&Location>| {
+            location
+                .and_then(Location::coordinates)
+                .and_then(|point| place_at(self.places(), point))
+                .map(ToOwned::to_owned)
+        };
+        let before = here(self.last_reported().as_ref());
+        let after = here(Some(location));
+        write_json(&self.last_path(), location)?;
+        if before == after {
+            return Ok(Vec::new());
+        }
+        let (due, pending): (Vec<_>, Vec<_>) =
+            self.reminders()
+                .into_iter()
+                .partition(|reminder| match reminder.on {
+                    PlaceEvent::Arrive => after.as_deref() == Some(reminder.place.as_str()),
+                    PlaceEvent::Leave => before.as_deref() == Some(reminder.place.as_str()),
+                });
+        if !due.is_empty() {
+            write_json(&self.reminders_path(), &pending)?;
+        }
+        Ok(due)
+    }
+
+    pub fn reminders(&self) -> Vec<PlaceReminder> {
+        std::fs::read_to_string(self.reminders_path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn add_reminder(
+        &self,
+        place: &str,
+        on: PlaceEvent,
+        message: &str,
+        channel: &str,
+        chat_id: &str,
+    ) -> Result<PlaceReminder> {
+        if self.source() != "webhook" {
+            bail!(
+                "place reminders need location.source = \"webhook\" so the phone reports where it is"
+            );
+        }
+        let place = self
+            .places()
+            .keys()
+            .find(|name| name.eq_ignore_ascii_case(place.trim()))
+            .ok_or_else(|| {
+                let known = self.places().keys().cloned().collect::<Vec<_>>();
+                anyhow!(
+                    "unknown place '{place}'; configured places: {}",
+                    if known.is_empty() {
+                        "none (add them under location.places)".to_string()
+                    } else {
+                        known.join(", ")
+                    }
+                )
+            })?;
+        if message.trim().is_empty() {
+            bail!("message cannot be empty");
+        }
+        let reminder = PlaceReminder {
+            id: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+            place: place.clone(),
+            on,
+            message: message.trim().to_string(),
+            channel: channel.to_string(),
+            chat_id: chat_id.to_string(),
+            created_at: Local::now(),
+        };
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut reminders = self.reminders();
+        reminders.push(reminder.clone());
+        write_json(&self.reminders_path(), &reminders)?;
+        Ok(reminder)
+    }
+
+    pub fn remove_reminder(&self, id: &str) -> Result<PlaceReminder> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut reminders = self.reminders();
+        let index = reminders
+            .iter()
+            .position(|reminder| reminder.id == id.trim())
+            .ok_or_else(|| anyhow!("no place reminder with id '{id}'"))?;
+        let reminder = reminders.remove(index);
+        write_json(&self.reminders_path(), &reminders)?;
+        Ok(reminder)
+    }
+}
+
+fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+async fn lookup_ip() -> Result<Location> {
+    let data: Value = crate::network::client()
+        .get(IP_LOOKUP_URL)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if data.get("error").and_then(Value::as_bool) == Some(true) {
+        bail!(
+            "IP lookup failed: {}",
+            data.get("reason")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown")
+        );
+    }
+    let name = ["city", "region", "country_name"]
+        .iter()
+        .filter_map(|key| data.get(*key).and_then(Value::as_str))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(Location {
+        name,
+        latitude: data.get("latitude").and_then(Value::as_f64),
+        longitude: data.get("longitude").and_then(Value::as_f64),
+        accuracy_m: None,
+        source: "ip".to_string(),
+        updated_at: Local::now(),
+    })
+}
+
+fn respond_json(request: Request, status: u16, body: Value) {
+    let header = Header::from_bytes("Content-Type", "application/json; charset=utf-8")
+        .expect("static header is valid");
+    let _ = request.respond(
+        Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header),
+    );
+}
+
+/// `POST /location` records the phone's position and sends the place
+/// reminders it sets off. Answers `[]`, which OwnTracks expects.
+pub struct LocationWebhook {
+    service: Arc<LocationService>,
+    bus: Arc<MessageBus>,
+    runtime: Handle,
+    guard: ApiKeyGuard,
+    proxies: TrustedProxies,
+}
+
+impl LocationWebhook {
+    pub fn new(
+        service: Arc<LocationService>,
+        bus: Arc<MessageBus>,
+        runtime: Handle,
+        guard: ApiKeyGuard,
+        proxies: TrustedProxies,
+    ) -> Self {
+        Self {
+            service,
+            bus,
+            runtime,
+            guard,
+            proxies,
+        }
+    }
+
+    pub fn handle(&self, mut request: Request) -> Option<Request> {
+        let path = request.url().split('?').next().unwrap_or_default();
+        if path != ROUTE || request.method() != &Method::Post {
+            return Some(request);
+        }
+        match self.guard.check(&request) {
+            Err(err) => {
+                respond_json(
+                    request,
+                    err.status_code(),
+                    json!({ "ok": false, "error": err.to_string() }),
+                );
+                return None;
+            }
+            Ok(None) => {
+                if let Some((status, error)) = self.proxies.local_request_error(&request) {
+                    respond_json(request, status, json!({ "ok": false, "error": error }));
+                    return None;
+                }
+            }
+            Ok(Some(_)) => {}
+        }
+        let mut body = String::new();
+        let _ = request.as_reader().read_to_string(&mut body);
+        let report = serde_json::from_str::<Value>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|body| parse_report(&body));
+        let location = match report {
+            Ok(Some(location)) => location,
+            Ok(None) => {
+                respond_json(request, 200, json!([]));
+                return None;
+            }
+            Err(err) => {
+                respond_json(
+                    request,
+                    400,
+                    json!({ "ok": false, "error": format!("invalid location: {err:#}") }),
+                );
+                return None;
+            }
+        };
+        match self.service.report(&location) {
+            Ok(due) => {
+                for reminder in due {
+                    let bus = self.bus.clone();
+                    self.runtime.spawn(async move {
+                        let message = OutboundMessage::new(
+                            reminder.channel.clone(),
+                            reminder.chat_id.clone(),
+                            format!("📍 {}", reminder.message),
+                        );
+                        if let Err(err) = bus.publish_outbound(message).await {
+                            tracing::warn!(id = %reminder.id, "place reminder failed: {err:#}");
+                        }
+                    });
+                }
+                respond_json(request, 200, json!([]));
+            }
+            Err(err) => respond_json(
+                request,
+                500,
+                json!({ "ok": false, "error": format!("{err:#}") }),
+            ),
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_reminders_fire_on_arrival_and_departure() {
+        let dir = std::env::temp_dir().join(format!("nanobot-location-{}", uuid::Uuid::new_v4()));
+        let mut places = BTreeMap::new();
+        places.insert(
+            "home".to_string(),
+            PlaceConfig {
+                latitude: 52.5200,
+                longitude: 13.4050,
+                radius_m: 150.0,
+            },
+        );
+        let service = LocationService::new(
+            LocationConfig {
+                source: "webhook".to_string(),
+                places,
+                ..Default::default()
+            },
+            dir.clone(),
+        );
+        let report = |lat: f64, lon: f64| {
+            parse_report(&json!({"_type": "location", "lat": lat, "lon": lon, "acc": 12}))
+                .unwrap()
+                .unwrap()
+        };
+        assert!(
+            parse_report(&json!({"_type": "transition"}))
+                .unwrap()
+                .is_none()
+        );
+        assert!(parse_report(&json!({"lat": 120.0, "lon": 0.0})).is_err());
+
+        let arrive = service
+            .add_reminder("Home", PlaceEvent::Arrive, "buy milk", "telegram", "42")
+            .expect("add");
+        service
+            .add_reminder("home", PlaceEvent::Leave, "take the keys", "telegram", "42")
+            .expect("add");
+        assert!(
+            service
+                .add_reminder("gym", PlaceEvent::Arrive, "stretch", "telegram", "42")
+                .is_err()
+        );
+
+        // About 2 km away, then 50 m from home, then still home.
+        assert!(
+            service
+                .report(&report(52.5380, 13.4050))
+                .unwrap()
+                .is_empty()
+        );
+        let due = service.report(&report(52.5204, 13.4052)).unwrap();
+        assert_eq!(due, vec![arrive]);
+        assert!(
+            service
+                .report(&report(52.5201, 13.4049))
+                .unwrap()
+                .is_empty()
+        );
+        let due = service.report(&report(52.5380, 13.4050)).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].message, "take the keys");
+        assert!(service.reminders().is_empty());
+
+        let last = service.last_reported().expect("stored");
+        assert_eq!(last.accuracy_m, Some(12.0));
+        assert_eq!(last.weather_query().as_deref(), Some("52.5380,13.4050"));
+        assert!((distance_m((52.5200, 13.4050), (52.5380, 13.4050)) - 2001.0).abs() < 5.0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
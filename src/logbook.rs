@@ -0,0 +1,340 @@
+//! Quick-capture log, `<workspace>/logbook.csv`: what the user spent, ate,
+//! weighed or wants to remember, one row per entry.
+//!
+//! ```text
+//! time,category,amount,currency,text
+//! 2026-10-15T12:31:09+02:00,food,12.50,EUR,lunch with Tom
+//! 2026-10-15T18:02:44+02:00,idea,,,"bookmarklet for saving recipes"
+//! ```
+//!
+//! The file opens in any spreadsheet; `log_summary` and `nanobot log
+//! summary` total a month by category and currency.
+
+use crate::usage::csv_field;
+use crate::utils::with_path_lock;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDate};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "time,category,amount,currency,text";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub category: String,
+    pub amount: Option<f64>,
+    pub currency: String,
+    pub text: String,
+}
+
+impl LogEntry {
+    /// `2026-10-15 12:31 food 12.50 EUR lunch with Tom`
+    pub fn line(&self) -> String {
+        let mut line = format!("{} {}", self.time.format("%Y-%m-%d %H:%M"), self.category);
+        if let Some(amount) = self.amount {
+            line.push_str(&format!(" {amount:.2}"));
+            if !self.currency.is_empty() {
+                line.push_str(&format!(" {}", self.currency));
+            }
+        }
+        if !self.text.is_empty() {
+            line.push_str(&format!(" {}", self.text));
+        }
+        line
+    }
+
+    fn to_row(&self) -> String {
+        format!(
+            "{},{},{},{},{}\n",
+            self.time.to_rfc3339(),
+            csv_field(&self.category),
+            self.amount
+                .map(|amount| format!("{amount:.2}"))
+                .unwrap_or_default(),
+            csv_field(&self.currency),
+            csv_field(&self.text),
+        )
+    }
+
+    fn from_fields(fields: &[String]) -> Result<Self> {
+        let [time, category, amount, currency, text] = fields else {
+            bail!("expected 5 fields, found {}", fields.len());
+        };
+        Ok(Self {
+            time: DateTime::parse_from_rfc3339(time)?.with_timezone(&Local),
+            category: category.clone(),
+            amount: match amount.trim() {
+                "" => None,
+                amount => Some(amount.parse()?),
+            },
+            currency: currency.clone(),
+            text: text.clone(),
+        })
+    }
+}
+
+/// Splits CSV text into records, honoring quoted fields with `""` escapes
+/// and line breaks.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// Reads an amount as typed in chat: `12.5`, `12,50 €`, `$7`, `30 usd`.
+/// Returns the amount and the currency code, if one was given.
+pub fn parse_amount(raw: &str) -> Result<(f64, Option<String>)> {
+    let raw = raw.trim();
+    let symbols = [('$', "USD"), ('€', "EUR"), ('£', "GBP"), ('¥', "CNY")];
+    let mut currency = symbols
+        .iter()
+        .find(|(symbol, _)| raw.contains(*symbol))
+        .map(|(_, code)| code.to_string());
+    let mut number = raw
+        .chars()
+        .filter(|c| !symbols.iter().any(|(symbol, _)| symbol == c))
+        .collect::<String>();
+    let letters = number
+        .chars()
+        .filter(char::is_ascii_alphabetic)
+        .collect::<String>();
+    if !letters.is_empty() {
+        if letters.len() != 3 {
+            bail!("invalid amount '{raw}'");
+        }
+        currency = Some(letters.to_ascii_uppercase());
+        number.retain(|c| !c.is_ascii_alphabetic());
+    }
+    let number = number.trim().replace(' ', "");
+    // A lone comma is the decimal separator (12,50); otherwise commas group
+    // thousands (1,200.00).
+    let number =
+        if number.contains(',') && !number.contains('.') && number.matches(',').count() == 1 {
+            number.replace(',', ".")
+        } else {
+            number.replace(',', "")
+        };
+    let amount = number
+        .parse::<f64>()
+        .ok()
+        .filter(|amount| amount.is_finite())
+        .ok_or_else(|| anyhow!("invalid amount '{raw}'"))?;
+    Ok((amount, currency))
+}
+
+/// Totals for one month.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MonthSummary {
+    pub month: String,
+    pub entries: usize,
+    /// (category, currency) -> (total, entries)
+    pub totals: BTreeMap<(String, String), (f64, usize)>,
+    /// Entries without an amount, by category.
+    pub notes: BTreeMap<String, usize>,
+}
+
+impl MonthSummary {
+    pub fn render(&self) -> String {
+        if self.entries == 0 {
+            return format!("{}: no entries.", self.month);
+        }
+        let mut lines = vec![format!("{}: {} entries", self.month, self.entries)];
+        let mut grand: BTreeMap<&str, f64> = BTreeMap::new();
+        for ((category, currency), (total, count)) in &self.totals {
+            let currency = if currency.is_empty() {
+                String::new()
+            } else {
+                format!(" {currency}")
+            };
+            lines.push(format!("- {category}: {total:.2}{currency} ({count})"));
+        }
+        for ((_, currency), (total, _)) in &self.totals {
+            *grand.entry(currency.as_str()).or_default() += total;
+        }
+        for (currency, total) in grand {
+            let currency = if currency.is_empty() {
+                String::new()
+            } else {
+                format!(" {currency}")
+            };
+            lines.push(format!("Total: {total:.2}{currency}"));
+        }
+        for (category, count) in &self.notes {
+            lines.push(format!("- {category}: {count} without amount"));
+        }
+        lines.join("\n")
+    }
+}
+
+pub struct Logbook {
+    path: PathBuf,
+}
+
+impl Logbook {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            path: workspace.join("logbook.csv"),
+        }
+    }
+
+    /// Every entry in file order; rows that do not parse are skipped.
+    pub fn entries(&self) -> Vec<LogEntry> {
+        let Ok(text) = std::fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+        parse_csv(&text)
+            .into_iter()
+            .skip(1)
+            .filter(|fields| fields.iter().any(|field| !field.is_empty()))
+            .filter_map(|fields| match LogEntry::from_fields(&fields) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    tracing::warn!("skipping logbook row {fields:?}: {err:#}");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn append(&self, entry: &LogEntry) -> Result<()> {
+        if entry.category.trim().is_empty() {
+            bail!("category cannot be empty");
+        }
+        if entry.amount.is_none() && entry.text.trim().is_empty() {
+            bail!("an entry needs an amount or a text");
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        with_path_lock(&self.path, || {
+            let new = !self.path.exists();
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .with_context(|| format!("failed to open {}", self.path.display()))?;
+            if new {
+                writeln!(file, "{HEADER}")?;
+            }
+            file.write_all(entry.to_row().as_bytes())?;
+            Ok(())
+        })
+    }
+
+    /// Totals for `month` (`YYYY-MM`), optionally for one category.
+    pub fn summarize(&self, month: &str, category: Option<&str>) -> Result<MonthSummary> {
+        NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+            .map_err(|_| anyhow!("invalid month '{month}': expected YYYY-MM"))?;
+        let mut summary = MonthSummary {
+            month: month.to_string(),
+            ..Default::default()
+        };
+        for entry in self.entries() {
+            if entry.time.format("%Y-%m").to_string() != month
+                || category.is_some_and(|category| !entry.category.eq_ignore_ascii_case(category))
+            {
+                continue;
+            }
+            summary.entries += 1;
+            match entry.amount {
+                Some(amount) => {
+                    let total = summary
+                        .totals
+                        .entry((entry.category, entry.currency))
+                        .or_default();
+                    total.0 += amount;
+                    total.1 += 1;
+                }
+                None => *summary.notes.entry(entry.category).or_default() += 1,
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// The current month as `YYYY-MM`.
+pub fn this_month() -> String {
+    Local::now().format("%Y-%m").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn logs_rows_and_totals_a_month() {
+        let dir = std::env::temp_dir().join(format!("nanobot-logbook-{}", uuid::Uuid::new_v4()));
+        let book = Logbook::new(&dir);
+        let at = |day: u32| Local.with_ymd_and_hms(2026, 10, day, 12, 0, 0).unwrap();
+        let entry = |day, category: &str, amount: Option<f64>, text: &str| LogEntry {
+            time: at(day),
+            category: category.to_string(),
+            amount,
+            currency: if amount.is_some() { "EUR" } else { "" }.to_string(),
+            text: text.to_string(),
+        };
+        book.append(&entry(1, "food", Some(12.5), "lunch, with \"Tom\""))
+            .expect("append");
+        book.append(&entry(2, "food", Some(7.5), ""))
+            .expect("append");
+        book.append(&entry(3, "idea", None, "multi\nline"))
+            .expect("append");
+        assert!(book.append(&entry(3, "idea", None, "")).is_err());
+
+        let entries = book.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].text, "lunch, with \"Tom\"");
+        assert_eq!(entries[2].text, "multi\nline");
+
+        let summary = book.summarize("2026-10", None).expect("summary");
+        assert_eq!(
+            summary.render(),
+            "2026-10: 3 entries\n- food: 20.00 EUR (2)\nTotal: 20.00 EUR\n- idea: 1 without amount"
+        );
+        assert_eq!(book.summarize("2026-09", None).unwrap().entries, 0);
+        assert!(book.summarize("October", None).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn parses_amounts_as_typed() {
+        assert_eq!(parse_amount("12.5").unwrap(), (12.5, None));
+        assert_eq!(
+            parse_amount("12,50 €").unwrap(),
+            (12.5, Some("EUR".to_string()))
+        );
+        assert_eq!(parse_amount("$7").unwrap(), (7.0, Some("USD".to_string())));
+        assert_eq!(
+            parse_amount("1,200.00 chf").unwrap(),
+            (1200.0, Some("CHF".to_string()))
+        );
+        assert!(parse_amount("lots").is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use crate::config::LoggingConfig;
+use crate::redact::Redacting;
+use anyhow::{Result, anyhow};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn open(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files: max_files.max(1),
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let _ = std::fs::remove_file(self.rotated_path(self.max_files));
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_bytes > 0 && self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+const RECENT_LINE_CAPACITY: usize = 500;
+
+static RECENT_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// In-memory tail of recent log lines, included in diagnostic bundles.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecentLines;
+
+impl Write for RecentLines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Ok(mut lines) = RECENT_LINES.lock() {
+            for line in String::from_utf8_lossy(buf).lines() {
+                if lines.len() >= RECENT_LINE_CAPACITY {
+                    lines.pop_front();
+                }
+                lines.push_back(line.to_string());
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn recent_log_lines() -> Vec<String> {
+    RECENT_LINES
+        .lock()
+        .map(|lines| lines.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// The most recent WARN/ERROR lines, oldest first.
+pub fn recent_error_lines(limit: usize) -> Vec<String> {
+    let mut errors = recent_log_lines()
+        .into_iter()
+        .rev()
+        .filter(|line| matches!(line.split_whitespace().nth(1), Some("WARN" | "ERROR")))
+        .take(limit)
+        .collect::<Vec<_>>();
+    errors.reverse();
+    errors
+}
+
+pub fn effective_level(configured: &str, verbose: u8, quiet: bool) -> String {
+    if quiet {
+        return "warn".to_string();
+    }
+    match verbose {
+        0 => {
+            let configured = configured.trim();
+            if configured.is_empty() {
+                "info".to_string()
+            } else {
+                configured.to_string()
+            }
+        }
+        1 => "debug".to_string(),
+        _ => "trace".to_string(),
+    }
+}
+
+pub fn build_filter_directives(config: &LoggingConfig, verbose: u8, quiet: bool) -> String {
+    let mut directives = vec![effective_level(&config.level, verbose, quiet)];
+    let mut modules = config.modules.iter().collect::<Vec<_>>();
+    modules.sort();
+    for (module, level) in modules {
+        let module = module.trim();
+        let level = level.trim();
+        if !module.is_empty() && !level.is_empty() {
+            directives.push(format!("{module}={level}"));
+        }
+    }
+    directives.join(",")
+}
+
+pub fn init_logging(
+    config: &LoggingConfig,
+    log_path: &Path,
+    verbose: u8,
+    quiet: bool,
+) -> Result<()> {
+    // An explicit RUST_LOG wins unless the user asked for -v/-q on the command line.
+    let directives = match std::env::var("RUST_LOG") {
+        Ok(env) if !env.trim().is_empty() && verbose == 0 && !quiet => env,
+        _ => build_filter_directives(config, verbose, quiet),
+    };
+    let filter = EnvFilter::try_new(&directives)
+        .map_err(|err| anyhow!("invalid log filter '{directives}': {err}"))?;
+
+    let console = tracing_subscriber::fmt::layer()
+        .with_writer(Redacting(io::stderr))
+        .with_ansi(crate::terminal::color_stderr())
+        .with_target(verbose > 0)
+        .compact();
+    let recent = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(Redacting(|| RecentLines));
+
+    let (json_file, plain_file) = if config.file.enabled {
+        let writer = Redacting(Mutex::new(RotatingFileWriter::open(
+            log_path,
+            config.file.max_bytes,
+            config.file.max_files,
+        )?));
+        if config.file.json {
+            let layer = tracing_subscriber::fmt::layer()
+                .json()
+                .with_ansi(false)
+                .with_writer(writer);
+            (Some(layer), None)
+        } else {
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer);
+            (None, Some(layer))
+        }
+    } else {
+        (None, None)
+    };
+
+    #[cfg(feature = "otel")]
+    let otel = crate::telemetry::init_tracer(&config.otlp)?
+        .map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
+    #[cfg(not(feature = "otel"))]
+    let otel: Option<tracing_subscriber::layer::Identity> = None;
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(recent)
+        .with(json_file)
+        .with(plain_file)
+        .with(otel)
+        .try_init()
+        .map_err(|err| anyhow!("failed to initialize logging: {err}"))?;
+    if let Some(warning) = crate::telemetry::check_supported(&config.otlp) {
+        tracing::warn!("{warning}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn effective_level_prefers_cli_flags() {
+        assert_eq!(effective_level("info", 0, false), "info");
+        assert_eq!(effective_level("", 0, false), "info");
+        assert_eq!(effective_level("info", 1, false), "debug");
+        assert_eq!(effective_level("info", 3, false), "trace");
+        assert_eq!(effective_level("debug", 2, true), "warn");
+    }
+
+    #[test]
+    fn filter_directives_include_sorted_module_levels() {
+        let config = LoggingConfig {
+            level: "info".to_string(),
+            modules: HashMap::from([
+                ("nanobot::tools".to_string(), "trace".to_string()),
+                ("nanobot::agent".to_string(), "debug".to_string()),
+                ("".to_string(), "debug".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_filter_directives(&config, 0, false),
+            "info,nanobot::agent=debug,nanobot::tools=trace"
+        );
+    }
+
+    #[test]
+    fn rotating_writer_shifts_old_files() {
+        let dir = std::env::temp_dir().join(format!("nanobot-log-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("nanobot.log");
+        let mut writer = RotatingFileWriter::open(&path, 10, 2).expect("open writer");
+        for line in ["first-line\n", "second-line\n", "third-line\n", "fourth\n"] {
+            writer.write_all(line.as_bytes()).expect("write");
+        }
+        writer.flush().expect("flush");
+
+        let read = |p: PathBuf| std::fs::read_to_string(p).unwrap_or_default();
+        assert_eq!(read(path.clone()), "fourth\n");
+        assert_eq!(read(dir.join("nanobot.log.1")), "third-line\n");
+        assert_eq!(read(dir.join("nanobot.log.2")), "second-line\n");
+        assert!(!dir.join("nanobot.log.3").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,22 +1,79 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
 use clap::{ArgAction, Parser, Subcommand};
 use nanobot::VERSION;
-use nanobot::agent::AgentLoop;
-use nanobot::bus::{MessageBus, OutboundMessage};
+use nanobot::admin::{AdminApi, issue_admin_token, read_admin_token};
+use nanobot::agent::approval::{
+    ApprovalDecision, ApprovalGate, ApprovalPrompt, ApprovalRequest, ChannelApprovalPrompt,
+};
+use nanobot::agent::{AgentLoop, AgentLoopBuilder, error_reply};
+use nanobot::apikeys::{ApiKeyGuard, ApiKeyStore, apikeys_path};
+use nanobot::attachments::{
+    DEFAULT_MAX_ATTACHMENT_BYTES, check_image_source, format_with_attachments,
+    load_file_attachment, read_stdin_attachment,
+};
+use nanobot::briefing;
+use nanobot::budget;
+use nanobot::bus::{InboundMessage, MessageBus, OutboundMessage, SystemEvent};
 use nanobot::channels::manager::ChannelManager;
-use nanobot::config::{Config, get_config_path, load_config, providers_status, save_config};
-use nanobot::cron::{CronSchedule, CronService};
+use nanobot::chatui::ChatUi;
+use nanobot::config::{
+    Config, ConfigEdit, GatewayConfig, WorkspaceConfig, config_path_override, get_config_path,
+    load_config, providers_status, save_config, set_config_path, set_read_only_override,
+    set_workspace_override,
+};
+use nanobot::contacts::{ContactStore, cancel_birthday_reminder};
+use nanobot::cron::{CronPayload, CronSchedule, CronService};
+use nanobot::diagnostics;
+use nanobot::eval::{Cassette, EvalProvider, TokenUsage, load_suite, run_suite};
 use nanobot::health::{CheckLevel, HealthReport, check_update, collect_health, run_doctor};
 use nanobot::heartbeat::{DEFAULT_HEARTBEAT_INTERVAL_S, HeartbeatService};
+use nanobot::i18n::{self, tr, tr_args};
+use nanobot::listener::{RouteHandler, TrustedProxies, listen_url};
+use nanobot::location::{LocationService, LocationWebhook};
+use nanobot::logbook::{Logbook, this_month};
+use nanobot::logging::init_logging;
+use nanobot::maintenance::{self, MaintenancePaths};
+use nanobot::markdown::{highlight_code, render_terminal};
+use nanobot::network;
+use nanobot::outbox::{self, Outbox};
 use nanobot::pairing::{approve_pairing, list_pending, reject_pairing};
+use nanobot::presence::{self, Target};
+use nanobot::prompts::{list_templates, load_template, parse_vars};
 use nanobot::providers::base::LLMProvider;
-use nanobot::providers::litellm::LiteLLMProvider;
+use nanobot::providers::build_provider;
+use nanobot::providers::embeddings::build_embedding_provider;
+use nanobot::providers::error::ProviderError;
+use nanobot::providers::mock;
+use nanobot::providers::models::list_models;
+use nanobot::providers::swap::{self, SwappableProvider};
+use nanobot::redact;
+use nanobot::router;
+use nanobot::secrets::{check_secrets_file_permissions, parse_env_assignment};
+use nanobot::service::registry::{
+    ServiceInstance, forget_instance, list_instances, record_instance,
+};
 use nanobot::service::{self, ServiceAccount, ServiceInstallOptions};
 use nanobot::session::SessionManager;
-use nanobot::utils::{get_data_path, get_workspace_path};
+use nanobot::tasks::{NewTask, TaskStore, cancel_reminder, parse_due, schedule_reminder};
+use nanobot::telemetry;
+use nanobot::terminal::{self, Spinner};
+use nanobot::tool_stats;
+use nanobot::transcript::{TranscriptOptions, render_transcript};
+use nanobot::usage;
+use nanobot::utils::{get_data_path, get_workspace_path, set_timezone};
+use nanobot::vault::Vault;
+use nanobot::watchdog::{
+    SharedHealth, Watchdog, health_file_path, read_health_snapshot, spawn_gateway_http,
+    write_health_snapshot,
+};
 use nanobot::webui::run_webui_server;
+use nanobot::workflows::{
+    CRON_PAYLOAD_KIND, RunStatus, WorkflowRun, WorkflowRunner, WorkflowWebhook, load_workflows,
+    sync_cron_triggers, workflows_dir,
+};
 use std::fs;
-use std::io::BufRead;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::process::Command;
@@ -28,6 +85,27 @@ use which::which;
     about = "nanobot: Rust port of the lightweight personal AI assistant"
 )]
 struct Cli {
+    /// Config file to use instead of ~/.nanobot/config.json; its directory
+    /// also holds this instance's sessions, logs and default workspace
+    #[arg(long, global = true, env = "NANOBOT_CONFIG")]
+    config: Option<PathBuf>,
+    /// Named workspace from `workspaces` in config (memory, skills, sessions)
+    #[arg(long, global = true, env = "NANOBOT_WORKSPACE")]
+    workspace: Option<String>,
+    /// Refuse tools that write files, run commands or act outside the chat
+    #[arg(long, global = true, default_value_t = false)]
+    read_only: bool,
+    /// Answer model calls from a recorded fixture instead of the provider
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "record")]
+    mock: Option<PathBuf>,
+    /// Record every model response to a fixture file for `--mock`
+    /// (a cassette for `eval`)
+    #[arg(long, global = true, value_name = "FILE")]
+    record: Option<PathBuf>,
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    #[arg(short, long, global = true, default_value_t = false)]
+    quiet: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -53,18 +131,49 @@ enum Commands {
         port: u16,
     },
     Gateway {
-        #[arg(short, long, default_value_t = 18790)]
-        port: u16,
-        #[arg(short, long, default_value_t = false)]
-        verbose: bool,
+        /// Bind address; defaults to gateway.host
+        #[arg(long)]
+        host: Option<String>,
+        /// Defaults to gateway.port
+        #[arg(short, long)]
+        port: Option<u16>,
+    },
+    Serve {
+        /// Bind address; defaults to gateway.host
+        #[arg(long)]
+        host: Option<String>,
+        /// Defaults to gateway.port
+        #[arg(short, long)]
+        port: Option<u16>,
+        #[arg(long, default_value_t = 5)]
+        check_interval: u64,
     },
     Agent {
         #[arg(short, long)]
         message: Option<String>,
         #[arg(short, long, default_value = "cli:direct")]
         session: String,
+        #[arg(short = 'f', long = "file")]
+        files: Vec<PathBuf>,
+        /// Image file or http(s) URL for vision models; repeatable
+        #[arg(short = 'i', long = "image")]
+        images: Vec<String>,
+        #[arg(long, default_value_t = DEFAULT_MAX_ATTACHMENT_BYTES)]
+        max_attach_bytes: usize,
+        #[arg(long, default_value_t = false)]
+        plain: bool,
+        #[arg(long, default_value_t = false)]
+        raw: bool,
     },
     Status,
+    /// Models the configured providers serve, with their capabilities
+    Models {
+        /// Only this provider (e.g. openai, openrouter)
+        provider: Option<String>,
+        /// Ignore the cached lists (kept for a day)
+        #[arg(long, default_value_t = false)]
+        refresh: bool,
+    },
     Version,
     Channels {
         #[command(subcommand)]
@@ -74,10 +183,45 @@ enum Commands {
         #[command(subcommand)]
         command: PairingCommand,
     },
+    /// Manage scoped API keys for the HTTP endpoints
+    #[command(name = "apikeys")]
+    ApiKeys {
+        #[command(subcommand)]
+        command: ApiKeysCommand,
+    },
+    /// Change config.json; provider keys apply to a running gateway at once
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Inspect and adjust a running gateway
+    Admin {
+        /// Gateway base URL (default: from gateway config)
+        #[arg(long)]
+        url: Option<String>,
+        /// Admin API key (default: the running service's admin token)
+        #[arg(long, env = "NANOBOT_ADMIN_KEY", hide_env_values = true)]
+        key: Option<String>,
+        #[command(subcommand)]
+        command: AdminCommand,
+    },
     Sessions {
         #[command(subcommand)]
         command: SessionCommand,
     },
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommand,
+    },
+    Show {
+        session: String,
+        #[arg(long, default_value_t = false)]
+        expand_tools: bool,
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        #[arg(long, default_value_t = false)]
+        no_pager: bool,
+    },
     Cron {
         #[command(subcommand)]
         command: CronCommand,
@@ -86,6 +230,76 @@ enum Commands {
         #[command(subcommand)]
         command: ServiceCommand,
     },
+    /// Run saved prompt templates from <workspace>/prompts
+    Prompt {
+        #[command(subcommand)]
+        command: PromptCommand,
+    },
+    /// Write the morning briefing now (see `briefing` in config)
+    Brief {
+        /// Print the gathered material without asking the model
+        #[arg(long, default_value_t = false)]
+        sources: bool,
+        /// Also send it to briefing.channel / briefing.to
+        #[arg(long, default_value_t = false)]
+        deliver: bool,
+    },
+    /// Summarize model calls recorded in ~/.nanobot/usage.jsonl
+    Usage {
+        /// Only calls from the last N days
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+        /// Only calls since a span ago (30m, 24h, 7d, 2w) or a date (2025-01-31); overrides --days
+        #[arg(long)]
+        since: Option<String>,
+        /// Show time-to-first-token, total latency and tokens/s percentiles
+        #[arg(long, default_value_t = false)]
+        perf: bool,
+        #[command(subcommand)]
+        command: Option<UsageCommand>,
+    },
+    /// Rotate logs, clear caches, archive sessions and back up (see `maintenance` in config)
+    Maintenance {
+        /// Report what would be removed without touching anything
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Manage the todo list in <workspace>/tasks
+    Tasks {
+        #[command(subcommand)]
+        command: TasksCommand,
+    },
+    /// Read the quick-capture log in <workspace>/logbook.csv
+    Log {
+        #[command(subcommand)]
+        command: LogCommand,
+    },
+    /// Show or remove people in <workspace>/contacts
+    Contacts {
+        #[command(subcommand)]
+        command: ContactsCommand,
+    },
+    /// Talk to the agent through the microphone and speakers
+    Voice {
+        /// Session to continue (defaults to voice.session)
+        #[arg(short, long)]
+        session: Option<String>,
+    },
+    /// List, run and resume workflows from the workspace
+    Workflows {
+        #[command(subcommand)]
+        command: WorkflowsCommand,
+    },
+    /// Run a YAML suite of prompts and assertions against the agent
+    Eval {
+        suite: PathBuf,
+        /// The global `--record`, saving every LLM response as a cassette
+        #[arg(skip)]
+        record: Option<PathBuf>,
+        /// Answer from a recorded cassette instead of calling the provider
+        #[arg(long)]
+        replay: Option<PathBuf>,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -101,6 +315,71 @@ enum PairingCommand {
     Reject { channel: String, code: String },
 }
 
+#[derive(Debug, Subcommand)]
+enum AdminCommand {
+    /// Stored sessions and turns in progress
+    Sessions,
+    /// Message bus queue depths
+    Queues,
+    /// Recent warnings and errors
+    Errors {
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// Tools and whether they are enabled
+    Tools,
+    EnableTool {
+        name: String,
+    },
+    DisableTool {
+        name: String,
+    },
+    /// Archive a session's older messages into memory now
+    Consolidate {
+        session: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Set a value by its dotted name, e.g. providers.openai.apiKey
+    Set {
+        key: String,
+        value: String,
+        /// Skip the validation call for provider settings (no gateway running)
+        #[arg(long)]
+        no_verify: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ApiKeysCommand {
+    /// Issue a key; the token is printed once
+    Create {
+        name: String,
+        /// Only allow GET/HEAD requests
+        #[arg(long, default_value_t = false)]
+        read_only: bool,
+        /// Allow the /admin routes
+        #[arg(long, default_value_t = false)]
+        admin: bool,
+        /// Confine the key to an agent profile
+        #[arg(long)]
+        profile: Option<String>,
+        #[arg(long)]
+        expires_in_days: Option<i64>,
+        /// An existing key of the profile, so the new key can read its
+        /// encrypted data (gateway.encryptUserData)
+        #[arg(long, env = "NANOBOT_UNLOCK_KEY", hide_env_values = true)]
+        unlock_with: Option<String>,
+    },
+    List,
+    Revoke {
+        /// Key id or name
+        key: String,
+    },
+}
+
 #[derive(Debug, Subcommand)]
 enum SessionCommand {
     List,
@@ -112,6 +391,60 @@ enum SessionCommand {
     Delete {
         session: String,
     },
+    /// Summarize a session into a synopsis to shrink future prompts
+    Compact {
+        session: String,
+        /// Restore the transcript saved by the last compaction
+        #[arg(long, default_value_t = false)]
+        undo: bool,
+    },
+    /// Write a session up as a Markdown note in the notes folder
+    Publish {
+        session: String,
+        /// Note title; the model picks one otherwise
+        #[arg(long)]
+        title: Option<String>,
+        /// Folder to write to instead of publish.notesDir
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum UsageCommand {
+    /// Aggregate a month of usage by day, model, channel and session
+    Export {
+        #[arg(long, default_value = "csv", value_parser = ["csv", "json"])]
+        format: String,
+        /// Month to export as YYYY-MM; defaults to the current month
+        #[arg(long)]
+        month: Option<String>,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ToolsCommand {
+    List {
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+    Describe {
+        name: String,
+    },
+    Test {
+        name: String,
+        #[arg(default_value = "{}")]
+        args: String,
+    },
+    /// Calls, error rates and durations from ~/.nanobot/tool_stats.jsonl
+    Stats {
+        /// Only calls from the last N days
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -137,6 +470,9 @@ enum CronCommand {
         to: Option<String>,
         #[arg(long)]
         channel: Option<String>,
+        /// Quality tier from `router.tiers` the job runs on
+        #[arg(long)]
+        tier: Option<String>,
     },
     Remove {
         job_id: String,
@@ -153,6 +489,114 @@ enum CronCommand {
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum PromptCommand {
+    List,
+    /// Print a template filled in, without running it
+    Show {
+        name: String,
+        #[arg(long = "var", value_name = "KEY=VALUE")]
+        vars: Vec<String>,
+    },
+    /// Fill in a template and send it to the agent
+    Run {
+        name: String,
+        #[arg(long = "var", value_name = "KEY=VALUE")]
+        vars: Vec<String>,
+        #[arg(short, long, default_value = "cli:direct")]
+        session: String,
+        #[arg(long, default_value_t = false)]
+        plain: bool,
+        #[arg(long, default_value_t = false)]
+        raw: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum TasksCommand {
+    /// Open tasks, soonest due first
+    List {
+        /// Include finished tasks
+        #[arg(short, long, default_value_t = false)]
+        all: bool,
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    Add {
+        title: String,
+        /// YYYY-MM-DD or YYYY-MM-DD HH:MM (local time)
+        #[arg(long)]
+        due: Option<String>,
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long)]
+        notes: Option<String>,
+        /// Channel for the due-date reminder (needs --to)
+        #[arg(long)]
+        channel: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
+    },
+    Done {
+        id: u64,
+    },
+    Remove {
+        id: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum LogCommand {
+    /// Entries of a month, oldest first
+    List {
+        /// YYYY-MM (defaults to this month)
+        #[arg(long)]
+        month: Option<String>,
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Totals per category and currency for a month
+    Summary {
+        /// YYYY-MM (defaults to this month)
+        #[arg(long)]
+        month: Option<String>,
+        #[arg(long)]
+        category: Option<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ContactsCommand {
+    /// Everyone, or those matching a query
+    List { query: Option<String> },
+    /// Birthdays in the next N days
+    Birthdays {
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+    },
+    /// Remove a contact and its birthday reminder
+    Remove { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum WorkflowsCommand {
+    /// Workflows defined in <workspace>/workflows
+    List,
+    Run {
+        name: String,
+        /// JSON object available to steps as {{input.*}}
+        #[arg(long)]
+        input: Option<String>,
+    },
+    /// Recent runs, newest first
+    Runs {
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Continue a failed or interrupted run after its last finished step
+    Resume { run_id: String },
+}
+
 #[derive(Debug, Subcommand)]
 enum ServiceCommand {
     Install {
@@ -160,10 +604,12 @@ enum ServiceCommand {
         name: Option<String>,
         #[arg(long)]
         bin: Option<PathBuf>,
-        #[arg(long, default_value = "gateway")]
+        #[arg(long, default_value = "serve")]
         args: String,
         #[arg(long)]
         workdir: Option<PathBuf>,
+        #[arg(long)]
+        log_dir: Option<PathBuf>,
         #[arg(long, action = ArgAction::SetTrue)]
         system: bool,
         #[arg(long, action = ArgAction::SetTrue)]
@@ -174,6 +620,12 @@ enum ServiceCommand {
         auto_install_nssm: bool,
         #[arg(long, default_value_t = true, action = ArgAction::Set)]
         autostart: bool,
+        /// Extra environment variable for the service (KEY=VALUE, repeatable)
+        #[arg(long = "env", value_parser = parse_env_arg)]
+        env: Vec<(String, String)>,
+        /// KEY=VALUE file the service loads API keys from (must be private to the owner)
+        #[arg(long)]
+        secrets_file: Option<PathBuf>,
     },
     Remove {
         #[arg(long)]
@@ -195,26 +647,148 @@ enum ServiceCommand {
         #[arg(long)]
         name: Option<String>,
     },
+    /// List all installed nanobot service instances
+    List,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
-    match cli.command {
+    let mut cli = Cli::parse();
+    terminal::init();
+    if let Some(path) = &cli.config {
+        set_config_path(std::path::absolute(path)?);
+    }
+    if let Some(name) = &cli.workspace {
+        set_workspace_override(name.clone());
+        load_config(None)?;
+    }
+    if cli.read_only {
+        set_read_only_override();
+    }
+    if let Some(path) = &cli.mock {
+        mock::set_replay(path)?;
+    }
+    if let Commands::Eval { record, .. } = &mut cli.command {
+        *record = cli.record.take();
+    }
+    if let Some(path) = &cli.record {
+        mock::set_record(path)?;
+    }
+    let log_config = load_config(None).unwrap_or_default();
+    redact::install(&log_config.redaction);
+    i18n::init(&log_config.locale);
+    let timezone = set_timezone(&log_config.timezone);
+    if let Err(err) = init_logging(
+        &log_config.logging,
+        &log_config.log_file_path(),
+        cli.verbose,
+        // Raw agent output is meant for pipelines, so keep stderr to warnings too.
+        cli.quiet
+            || matches!(
+                cli.command,
+                Commands::Agent { raw: true, .. }
+                    | Commands::Prompt {
+                        command: PromptCommand::Run { raw: true, .. }
+                    }
+            ),
+    ) {
+        eprintln!("Warning: {err}");
+    }
+    if let Err(err) = timezone {
+        tracing::warn!("{err:#}; using the system time zone");
+    }
+    if let Err(err) = network::configure(&log_config.network) {
+        tracing::warn!("{err:#}; using the default network settings");
+    }
+    let diagnostics_dir = log_config.workspace_path().join("diagnostics");
+    diagnostics::install_panic_hook(diagnostics_dir.clone(), log_config.clone());
+    let is_service = matches!(
+        cli.command,
+        Commands::Serve { .. } | Commands::Gateway { .. }
+    );
+
+    let result = dispatch(cli.command).await;
+    if is_service && let Err(err) = &result {
+        diagnostics::report_fatal(&diagnostics_dir, &format!("{err:#}"), &log_config);
+    }
+    telemetry::shutdown();
+    result
+}
+
+async fn dispatch(command: Commands) -> Result<()> {
+    match command {
         Commands::Onboard => cmd_onboard()?,
         Commands::Health { json } => cmd_health(json)?,
         Commands::Doctor { fix, json } => cmd_doctor(fix, json)?,
         Commands::Update => cmd_update().await?,
         Commands::Webui { host, port } => cmd_webui(&host, port)?,
         Commands::Status => cmd_status()?,
+        Commands::Models { provider, refresh } => cmd_models(provider, refresh).await?,
         Commands::Version => println!("nanobot-rs v{VERSION}"),
-        Commands::Gateway { port, verbose } => cmd_gateway(port, verbose).await?,
-        Commands::Agent { message, session } => cmd_agent(message, &session).await?,
+        Commands::Gateway { host, port } => cmd_gateway(host, port).await?,
+        Commands::Serve {
+            host,
+            port,
+            check_interval,
+        } => cmd_serve(host, port, check_interval).await?,
+        Commands::Agent {
+            message,
+            session,
+            files,
+            images,
+            max_attach_bytes,
+            plain,
+            raw,
+        } => {
+            let style = if raw {
+                OutputStyle::Raw
+            } else if plain {
+                OutputStyle::Plain
+            } else {
+                OutputStyle::Rendered
+            };
+            cmd_agent(message, &session, &files, images, max_attach_bytes, style).await?
+        }
         Commands::Channels { command } => cmd_channels(command).await?,
         Commands::Pairing { command } => cmd_pairing(command)?,
-        Commands::Sessions { command } => cmd_sessions(command)?,
+        Commands::ApiKeys { command } => cmd_apikeys(command)?,
+        Commands::Config { command } => cmd_config(command).await?,
+        Commands::Admin { url, key, command } => cmd_admin(url, key, command).await?,
+        Commands::Sessions { command } => cmd_sessions(command).await?,
+        Commands::Tools { command } => cmd_tools(command).await?,
+        Commands::Show {
+            session,
+            expand_tools,
+            since,
+            no_pager,
+        } => cmd_show(&session, expand_tools, since, no_pager)?,
         Commands::Cron { command } => cmd_cron(command).await?,
         Commands::Service { command } => cmd_service(command)?,
+        Commands::Prompt { command } => cmd_prompt(command).await?,
+        Commands::Brief { sources, deliver } => cmd_brief(sources, deliver).await?,
+        Commands::Tasks { command } => cmd_tasks(command).await?,
+        Commands::Contacts { command } => cmd_contacts(command).await?,
+        Commands::Log { command } => cmd_log(command)?,
+        Commands::Usage {
+            command:
+                Some(UsageCommand::Export {
+                    format,
+                    month,
+                    output,
+                }),
+            ..
+        } => cmd_usage_export(&format, month, output)?,
+        Commands::Usage {
+            days, since, perf, ..
+        } => cmd_usage(days, since.as_deref(), perf)?,
+        Commands::Maintenance { dry_run } => cmd_maintenance(dry_run)?,
+        Commands::Voice { session } => cmd_voice(session).await?,
+        Commands::Workflows { command } => cmd_workflows(command).await?,
+        Commands::Eval {
+            suite,
+            record,
+            replay,
+        } => cmd_eval(&suite, record.as_deref(), replay.as_deref()).await?,
     }
     Ok(())
 }
@@ -222,16 +796,34 @@ async fn main() -> Result<()> {
 fn cmd_onboard() -> Result<()> {
     let config_path = get_config_path()?;
     if config_path.exists() {
-        println!("Config already exists at {}", config_path.display());
+        println!(
+            "{}",
+            tr_args(
+                "onboard-config-exists",
+                &[("path", &config_path.display().to_string())]
+            )
+        );
         return Ok(());
     }
 
     let config = Config::default();
     save_config(&config, Some(&config_path))?;
-    println!("Created config at {}", config_path.display());
+    println!(
+        "{}",
+        tr_args(
+            "onboard-created-config",
+            &[("path", &config_path.display().to_string())]
+        )
+    );
 
     let workspace = get_workspace_path(Some(&config.agents.defaults.workspace))?;
-    println!("Created workspace at {}", workspace.display());
+    println!(
+        "{}",
+        tr_args(
+            "onboard-created-workspace",
+            &[("path", &workspace.display().to_string())]
+        )
+    );
 
     let templates = [
         (
@@ -255,7 +847,13 @@ fn cmd_onboard() -> Result<()> {
         let path = workspace.join(name);
         if !path.exists() {
             std::fs::write(&path, content)?;
-            println!("Created {}", path.display());
+            println!(
+                "{}",
+                tr_args(
+                    "onboard-created-file",
+                    &[("path", &path.display().to_string())]
+                )
+            );
         }
     }
 
@@ -267,21 +865,39 @@ fn cmd_onboard() -> Result<()> {
             &memory_file,
             "# Long-term Memory\n\nThis file stores important information across sessions.\n",
         )?;
-        println!("Created {}", memory_file.display());
+        println!(
+            "{}",
+            tr_args(
+                "onboard-created-file",
+                &[("path", &memory_file.display().to_string())]
+            )
+        );
     }
     let history_file = memory_dir.join("HISTORY.md");
     if !history_file.exists() {
         std::fs::write(&history_file, "")?;
-        println!("Created {}", history_file.display());
+        println!(
+            "{}",
+            tr_args(
+                "onboard-created-file",
+                &[("path", &history_file.display().to_string())]
+            )
+        );
     }
 
     let skills_dir = workspace.join("skills");
     std::fs::create_dir_all(&skills_dir)?;
 
-    println!("nanobot-rs is ready.");
-    println!("Next steps:");
-    println!("1. Add your API key to {}", config_path.display());
-    println!("2. Chat: nanobot-rs agent -m \"Hello!\"");
+    println!("{}", tr("onboard-ready"));
+    println!("{}", tr("onboard-next-steps"));
+    println!(
+        "{}",
+        tr_args(
+            "onboard-step-api-key",
+            &[("path", &config_path.display().to_string())]
+        )
+    );
+    println!("{}", tr("onboard-step-chat"));
     Ok(())
 }
 
@@ -378,6 +994,31 @@ fn cmd_webui(host: &str, port: u16) -> Result<()> {
     run_webui_server(host, port)
 }
 
+async fn cmd_models(provider: Option<String>, refresh: bool) -> Result<()> {
+    let config = load_config(None).unwrap_or_default();
+    let listed = list_models(&config, provider.as_deref(), refresh).await?;
+    for (i, entry) in listed.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        if let Some(error) = &entry.error {
+            println!("{}: unavailable ({error})", entry.provider);
+            continue;
+        }
+        println!(
+            "{} ({} models, as of {})",
+            entry.provider,
+            entry.models.len(),
+            entry.fetched_at.format("%Y-%m-%d %H:%M")
+        );
+        let width = entry.models.iter().map(|m| m.id.len()).max().unwrap_or(0);
+        for model in &entry.models {
+            println!("  {}", model.line(width));
+        }
+    }
+    Ok(())
+}
+
 fn cmd_status() -> Result<()> {
     let config_path = get_config_path()?;
     let config = load_config(Some(&config_path)).unwrap_or_default();
@@ -399,6 +1040,54 @@ fn cmd_status() -> Result<()> {
         if workspace.exists() { "OK" } else { "MISSING" }
     );
     println!("Model: {}", config.agents.defaults.model);
+    match build_embedding_provider(&config) {
+        Ok(Some(embeddings)) => println!(
+            "Embeddings: {}/{}",
+            config
+                .providers
+                .embeddings
+                .provider
+                .trim()
+                .to_ascii_lowercase(),
+            embeddings.model()
+        ),
+        Ok(None) => println!("Embeddings: off"),
+        Err(err) => println!("Embeddings: {err:#}"),
+    }
+    match config.location.source.trim() {
+        "" => println!("Location: off"),
+        source => println!(
+            "Location: {source} ({} named places)",
+            config.location.places.len()
+        ),
+    }
+    println!(
+        "Time zone: {} ({})",
+        match config.timezone.trim() {
+            "" => "system",
+            timezone => timezone,
+        },
+        chrono::Local::now().format("%:z")
+    );
+    if config.budget.enabled {
+        let tracker =
+            budget::BudgetTracker::new(config.budget.clone(), budget::budget_state_path().ok());
+        let spend = tracker.snapshot(chrono::Local::now());
+        let limit = |usd: f64| {
+            if usd > 0.0 {
+                format!("${usd:.2}")
+            } else {
+                "no limit".to_string()
+            }
+        };
+        println!(
+            "Budget: ${:.2} today ({}), ${:.2} this month ({})",
+            spend.day_usd,
+            limit(config.budget.daily_usd),
+            spend.month_usd,
+            limit(config.budget.monthly_usd)
+        );
+    }
 
     let status = providers_status(&config);
     println!(
@@ -501,84 +1190,450 @@ fn cmd_status() -> Result<()> {
     Ok(())
 }
 
-fn build_provider(config: &Config, model: &str, api_key: String) -> Arc<dyn LLMProvider> {
-    let api_base = config.get_api_base(Some(model));
-    let extra_headers = config
-        .get_provider(Some(model))
-        .and_then(|p| p.extra_headers.clone());
-    let provider_name = config.get_provider_name(Some(model));
-    Arc::new(LiteLLMProvider::new(
-        api_key,
-        api_base,
-        model.to_string(),
-        extra_headers,
-        provider_name.as_deref(),
-    ))
+/// `build_provider` plus model routing.
+fn routed_provider(config: &Config, model: &str, api_key: String) -> Arc<dyn LLMProvider> {
+    router::wrap_provider(build_provider(config, model, api_key), config, |fast| {
+        let key = config.get_api_key(Some(fast)).unwrap_or_default();
+        build_provider(config, fast, key)
+    })
 }
 
-async fn cmd_gateway(port: u16, _verbose: bool) -> Result<()> {
-    let config = load_config(None).unwrap_or_default();
+/// The default model's provider for `config`, as the gateway builds it;
+/// fails when the model has no API key.
+fn gateway_provider(config: &Config) -> Result<Arc<dyn LLMProvider>> {
     let model = config.agents.defaults.model.clone();
     let normalized_model = model.strip_prefix("litellm/").unwrap_or(&model);
     let is_bedrock = normalized_model.starts_with("bedrock/");
     let api_key = config.get_api_key(Some(&model));
-    if api_key.is_none() && !is_bedrock {
-        return Err(anyhow!("No API key configured."));
+    if api_key.is_none() && !is_bedrock && !mock::replaying() {
+        return Err(anyhow!(tr("no-api-key")));
     }
-
-    let bus = Arc::new(MessageBus::new(1024));
-    let provider = build_provider(
-        &config,
+    Ok(routed_provider(
+        config,
         &model,
         api_key.unwrap_or_else(|| "dummy".to_string()),
-    );
-    let session_manager = Arc::new(SessionManager::new()?);
+    ))
+}
 
-    let cron_store_path = get_data_path()?.join("cron").join("jobs.json");
-    let cron = Arc::new(CronService::new(cron_store_path));
+/// Routes for the named workspaces that claim channels. Each other workspace
+/// gets its own loop from its own config, sharing the gateway's provider,
+/// bus, cron service, location and approval gate.
+fn workspace_routes(
+    config: &Config,
+    provider: &Arc<dyn LLMProvider>,
+    bus: &Arc<MessageBus>,
+    cron: &Arc<CronService>,
+    location: Option<&Arc<LocationService>>,
+    gate: Option<&Arc<ApprovalGate>>,
+    vault: Option<&Arc<Vault>>,
+) -> Result<Vec<(WorkspaceConfig, Option<Arc<AgentLoop>>)>> {
+    AgentLoopBuilder::routes_from_config(provider, config, |ws_config, builder| {
+        let sessions = SessionManager::with_dir(ws_config.sessions_dir()?)?;
+        let mut builder = builder
+            .without_budget()
+            .bus(bus.clone())
+            .cron(cron.clone())
+            .sessions(Arc::new(sessions.with_vault(vault.cloned())));
+        if let Some(location) = location {
+            builder = builder.location(location.clone());
+        }
+        if let Some(gate) = gate {
+            builder = builder.approval_gate(gate.clone());
+        }
+        Ok(builder)
+    })
+}
 
-    let agent = Arc::new(AgentLoop::new(
-        bus.clone(),
-        provider,
-        config.workspace_path(),
-        Some(model.clone()),
-        config.agents.defaults.max_tool_iterations,
-        config.agents.defaults.memory_window,
-        config.tools.web.search.clone(),
-        config.tools.exec.timeout,
-        config.tools.restrict_to_workspace,
-        Some(cron.clone()),
-        Some(session_manager.clone()),
-    )?);
+struct GatewayRuntime {
+    agent: Arc<AgentLoop>,
+    bus: Arc<MessageBus>,
+    drain_timeout: std::time::Duration,
+    cron: Arc<CronService>,
+    heartbeat: Arc<HeartbeatService>,
+    channels: Arc<ChannelManager>,
+    /// HTTP routes mounted on the gateway listener.
+    routes: Vec<RouteHandler>,
+    agent_task: tokio::task::JoinHandle<()>,
+    channels_task: tokio::task::JoinHandle<()>,
+}
 
-    let bus_for_cron = bus.clone();
-    let agent_for_cron = agent.clone();
-    cron.set_on_job(Arc::new(move |job| {
-        let bus = bus_for_cron.clone();
-        let agent = agent_for_cron.clone();
-        Box::pin(async move {
-            let response = agent
-                .process_direct(
-                    &job.payload.message,
-                    Some(&format!("cron:{}", job.id)),
-                    job.payload.channel.as_deref(),
-                    job.payload.to.as_deref(),
-                )
-                .await?;
+impl GatewayRuntime {
+    fn spawn_agent(agent: Arc<AgentLoop>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Err(err) = agent.run().await {
+                tracing::error!("agent loop exited with error: {err}");
+            }
+        })
+    }
 
-            if job.payload.deliver {
-                if let (Some(channel), Some(to)) =
-                    (job.payload.channel.clone(), job.payload.to.clone())
-                {
-                    bus.publish_outbound(OutboundMessage::new(channel, to, response.clone()))
-                        .await?;
+    async fn supervise(&mut self, watchdog: &mut Watchdog) {
+        let now = std::time::Instant::now();
+        if watchdog.observe("agent", !self.agent_task.is_finished(), now) {
+            self.agent_task = Self::spawn_agent(self.agent.clone());
+        }
+
+        let enabled_channels = self.channels.enabled_channels();
+        let manager_alive = enabled_channels.is_empty()
+            || (!self.channels_task.is_finished() && self.channels.dispatcher_alive().await);
+        if watchdog.observe("channels", manager_alive, now) {
+            self.channels.stop_all().await;
+            let channels = self.channels.clone();
+            self.channels_task = tokio::spawn(async move {
+                channels.start_all().await;
+            });
+        } else if manager_alive {
+            let exited = self.channels.exited_channels().await;
+            for name in enabled_channels {
+                let alive = !exited.contains(&name);
+                if watchdog.observe(&format!("channel:{name}"), alive, now) {
+                    self.channels.restart_channel(&name).await;
                 }
             }
+        }
+
+        if watchdog.observe("cron", self.cron.is_alive().await, now)
+            && let Err(err) = self.cron.start().await
+        {
+            tracing::error!("failed to restart cron service: {err}");
+        }
+        if watchdog.observe("heartbeat", self.heartbeat.is_alive().await, now) {
+            self.heartbeat.start().await;
+        }
+    }
+
+    /// Lets in-flight turns finish and their replies go out, answering new
+    /// messages with a notice, until the drain deadline or a second signal.
+    async fn shutdown(self) {
+        println!("{}", tr("shutting-down"));
+        self.agent.begin_drain();
+        self.heartbeat.stop().await;
+        self.cron.stop().await;
+
+        let in_flight = self.agent.active_sessions().len();
+        if in_flight > 0 {
+            println!(
+                "{}",
+                tr_args(
+                    "draining",
+                    &[
+                        ("count", &in_flight.to_string()),
+                        ("seconds", &self.drain_timeout.as_secs().to_string()),
+                    ],
+                )
+            );
+        }
+        let deadline = tokio::time::Instant::now() + self.drain_timeout;
+        let drained = tokio::select! {
+            drained = self.drain(deadline) => drained,
+            _ = shutdown_signal() => false,
+        };
+        if !drained {
+            let sessions = self
+                .agent
+                .active_sessions()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect::<Vec<_>>();
+            if !sessions.is_empty() {
+                tracing::warn!(
+                    "{}",
+                    tr_args("drain-timeout", &[("sessions", &sessions.join(", "))])
+                );
+            }
+        }
+
+        self.agent.stop();
+        self.channels.stop_all().await;
+        self.agent_task.abort();
+        self.channels_task.abort();
+    }
+
+    async fn drain(&self, deadline: tokio::time::Instant) -> bool {
+        if !self.agent.wait_idle(deadline).await {
+            return false;
+        }
+        // Give channels a moment to deliver replies of the finished turns.
+        while self.bus.outbound_size() > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        true
+    }
+}
+
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows::{ctrl_close, ctrl_shutdown};
+        let mut close = ctrl_close()?;
+        let mut shutdown = ctrl_shutdown()?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res?,
+            _ = close.recv() => {}
+            _ = shutdown.recv() => {}
+        }
+    }
+    #[cfg(not(any(unix, windows)))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Gateway listener settings from config with CLI overrides applied.
+fn gateway_listen_config(host: Option<String>, port: Option<u16>) -> GatewayConfig {
+    let mut gateway = load_config(None).unwrap_or_default().gateway;
+    if let Some(host) = host {
+        gateway.host = host;
+    }
+    if let Some(port) = port {
+        gateway.port = port;
+    }
+    gateway
+}
+
+async fn cmd_gateway(host: Option<String>, port: Option<u16>) -> Result<()> {
+    let gateway = gateway_listen_config(host, port);
+    let runtime = start_gateway_runtime(&gateway).await?;
+    if !runtime.routes.is_empty()
+        && let Err(err) = spawn_gateway_http(&gateway, None, runtime.routes.clone())
+    {
+        tracing::warn!("{err}");
+    }
+    shutdown_signal().await?;
+    runtime.shutdown().await;
+    Ok(())
+}
+
+async fn cmd_serve(host: Option<String>, port: Option<u16>, check_interval_s: u64) -> Result<()> {
+    let gateway = gateway_listen_config(host, port);
+    let check_interval = std::time::Duration::from_secs(check_interval_s.max(1));
+    let mut watchdog = Watchdog::new(&gateway.watchdog, check_interval);
+    let health: SharedHealth = Arc::new(std::sync::RwLock::new(None));
+    let mut runtime = start_gateway_runtime(&gateway).await?;
+    if let Err(err) = spawn_gateway_http(&gateway, Some(health.clone()), runtime.routes.clone()) {
+        tracing::warn!("{err}");
+    }
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut ticker = tokio::time::interval(check_interval);
+    loop {
+        tokio::select! {
+            res = &mut shutdown => {
+                res?;
+                break;
+            }
+            _ = ticker.tick() => {
+                runtime.supervise(&mut watchdog).await;
+                let snapshot = watchdog.snapshot();
+                if let Err(err) = write_health_snapshot(&snapshot) {
+                    tracing::debug!("failed to write health snapshot: {err}");
+                }
+                if let Ok(mut guard) = health.write() {
+                    *guard = Some(snapshot);
+                }
+            }
+        }
+    }
+    runtime.shutdown().await;
+    if let Ok(path) = health_file_path() {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
+
+/// How often the gateway looks for sessions that went idle.
+const IDLE_EXTRACTION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(300);
+
+async fn start_gateway_runtime(gateway: &GatewayConfig) -> Result<GatewayRuntime> {
+    let config = load_config(None).unwrap_or_default();
+    let bus = Arc::new(MessageBus::new(1024));
+    // Swappable so `nanobot config set providers.…` applies without a restart.
+    let live_provider = Arc::new(SwappableProvider::new(gateway_provider(&config)?));
+    let provider = budget::wrap_provider(live_provider.clone(), &config, bus.clone());
+    let vault = gateway.encrypt_user_data.then(|| Arc::new(Vault::new()));
+    let session_manager = Arc::new(SessionManager::new()?.with_vault(vault.clone()));
+
+    let cron_store_path = get_data_path()?.join("cron").join("jobs.json");
+    let cron = Arc::new(CronService::new(cron_store_path));
+
+    let approval_gate = config.tools.approval.enabled.then(|| {
+        let timeout = std::time::Duration::from_secs(config.tools.approval.timeout);
+        let prompt: Arc<dyn ApprovalPrompt> =
+            Arc::new(ChannelApprovalPrompt::new(bus.clone(), timeout));
+        let gate = ApprovalGate::new(config.tools.approval.tools.clone())
+            .with_prompt("telegram", prompt.clone())
+            .with_prompt("discord", prompt);
+        Arc::new(gate)
+    });
+    let location = LocationService::from_config(&config.location);
+    let workspace_routes = workspace_routes(
+        &config,
+        &provider,
+        &bus,
+        &cron,
+        location.as_ref(),
+        approval_gate.as_ref(),
+        vault.as_ref(),
+    )?;
+    let approval_timeout = std::time::Duration::from_secs(config.tools.approval.timeout);
+    let workflows = Arc::new(
+        WorkflowRunner::new(config.workspace_path(), workflow_runs_dir()?)
+            .with_bus(bus.clone())
+            .with_approval(Arc::new(ChannelApprovalPrompt::new(
+                bus.clone(),
+                approval_timeout,
+            ))),
+    );
+    // The provider already tracks the budget for every workspace's loop.
+    let mut builder = AgentLoopBuilder::from_config(provider.clone(), &config)
+        .without_budget()
+        .bus(bus.clone())
+        .cron(cron.clone())
+        .sessions(session_manager.clone())
+        .workspace_routes(workspace_routes)
+        .workflows(workflows.clone());
+    if let Some(location) = &location {
+        builder = builder.location(location.clone());
+    }
+    if let Some(gate) = &approval_gate {
+        builder = builder.approval_gate(gate.clone());
+    }
+    let agent = Arc::new(builder.build()?);
+    workflows.attach(&agent);
+    let interrupted = workflows.mark_interrupted();
+    if interrupted > 0 {
+        tracing::warn!(
+            count = interrupted,
+            "workflow runs were interrupted; see `nanobot workflows runs`"
+        );
+    }
+    let guard = ApiKeyGuard::new(
+        apikeys_path(&config.workspace_path())?,
+        gateway.require_api_key,
+    )
+    .with_vault(vault);
+    let mut routes: Vec<RouteHandler> = Vec::new();
+    match issue_admin_token() {
+        Ok(token) => {
+            let admin = Arc::new(
+                AdminApi::new(
+                    agent.clone(),
+                    bus.clone(),
+                    session_manager.clone(),
+                    tokio::runtime::Handle::current(),
+                    guard.clone(),
+                    token,
+                )
+                .with_provider_reload(live_provider.clone(), Arc::new(gateway_provider)),
+            );
+            routes.push(Arc::new(move |request| admin.handle(request)));
+        }
+        Err(err) => tracing::warn!("admin API disabled: {err:#}"),
+    }
+    if gateway.web_chat {
+        let chat_ui = Arc::new(ChatUi::new(
+            agent.clone(),
+            session_manager.clone(),
+            tokio::runtime::Handle::current(),
+            guard.clone(),
+            TrustedProxies::new(&gateway.trusted_proxies),
+        ));
+        routes.push(Arc::new(move |request| chat_ui.handle(request)));
+    }
+    let webhook = Arc::new(WorkflowWebhook::new(
+        workflows.clone(),
+        tokio::runtime::Handle::current(),
+        guard.clone(),
+        TrustedProxies::new(&gateway.trusted_proxies),
+    ));
+    routes.push(Arc::new(move |request| webhook.handle(request)));
+    if let Some(location) = location.filter(|location| location.source() == "webhook") {
+        let location_webhook = Arc::new(LocationWebhook::new(
+            location,
+            bus.clone(),
+            tokio::runtime::Handle::current(),
+            guard.clone(),
+            TrustedProxies::new(&gateway.trusted_proxies),
+        ));
+        routes.push(Arc::new(move |request| location_webhook.handle(request)));
+    }
+
+    let channels = Arc::new(ChannelManager::new(&config, bus.clone()));
+    let bus_for_cron = bus.clone();
+    let agent_for_cron = agent.clone();
+    let channels_for_cron = channels.clone();
+    let workflows_for_cron = workflows.clone();
+    let config_for_cron = Arc::new(config.clone());
+    cron.set_on_job(Arc::new(move |job| {
+        let config = config_for_cron.clone();
+        let bus = bus_for_cron.clone();
+        let agent = agent_for_cron.clone();
+        let channels = channels_for_cron.clone();
+        let workflows = workflows_for_cron.clone();
+        Box::pin(async move {
+            bus.publish(SystemEvent::new("cron_job", &job.id, &job.name));
+            if job.payload.kind == maintenance::CRON_PAYLOAD_KIND {
+                return maintenance::run_scheduled(&config).await;
+            }
+            if job.payload.kind == CRON_PAYLOAD_KIND {
+                return run_workflow_job(&workflows, &job.payload.message).await;
+            }
+            let response = if job.payload.kind == briefing::CRON_PAYLOAD_KIND {
+                run_briefing(
+                    &agent,
+                    job.payload.channel.as_deref(),
+                    job.payload.to.as_deref(),
+                )
+                .await?
+            } else {
+                agent
+                    .process_direct(
+                        &job.payload.message,
+                        Some(&format!("cron:{}", job.id)),
+                        job.payload.channel.as_deref(),
+                        job.payload.to.as_deref(),
+                    )
+                    .await?
+            };
+
+            if job.payload.deliver
+                && let (Some(channel), Some(to)) = (&job.payload.channel, &job.payload.to)
+            {
+                deliver_job_output(
+                    &config,
+                    Some(&channels),
+                    &bus,
+                    &outbox::job_key(&job),
+                    channel,
+                    to,
+                    &response,
+                )
+                .await?;
+            }
             Ok(Some(response))
         })
     }))
     .await;
     cron.start().await?;
+    if let Err(err) = sync_cron_triggers(&cron, &load_workflows(&config.workspace_path())).await {
+        tracing::warn!("failed to schedule workflows: {err:#}");
+    }
+    if let Err(err) = briefing::sync_briefing_job(&cron, &config.briefing).await {
+        tracing::warn!("failed to schedule the briefing: {err:#}");
+    }
+    if let Err(err) = maintenance::sync_maintenance_job(&cron, &config.maintenance).await {
+        tracing::warn!("failed to schedule maintenance: {err:#}");
+    }
 
     let heartbeat = Arc::new(HeartbeatService::new(
         config.workspace_path(),
@@ -593,59 +1648,140 @@ async fn cmd_gateway(port: u16, _verbose: bool) -> Result<()> {
                 agent
                     .process_direct(&prompt, Some("heartbeat"), None, None)
                     .await
-                    .unwrap_or_default()
+                    .unwrap_or_else(|err| {
+                        tracing::warn!("heartbeat turn failed: {err:#}");
+                        String::new()
+                    })
             })
         }))
         .await;
     heartbeat.start().await;
 
-    let channels = Arc::new(ChannelManager::new(&config, bus.clone()));
     let enabled_channels = channels.enabled_channels();
     if enabled_channels.is_empty() {
         println!("Warning: No channels enabled");
     } else {
         println!("Channels enabled: {}", enabled_channels.join(", "));
     }
-    println!("Gateway started on port {port}");
+    println!(
+        "{}",
+        tr_args("gateway-started", &[("url", &listen_url(gateway))])
+    );
 
-    let agent_task = {
-        let agent = agent.clone();
+    if config.presence.enabled {
+        let mut inbound = bus.subscribe_filtered::<InboundMessage>(|msg| msg.channel != "system");
+        let presence = config.presence.clone();
         tokio::spawn(async move {
-            let _ = agent.run().await;
-        })
-    };
+            while let Some(msg) = inbound.recv().await {
+                presence::touch(&presence, &msg.channel, &msg.chat_id);
+            }
+        });
+    }
+
+    let agent_task = GatewayRuntime::spawn_agent(agent.clone());
     let channels_task = {
         let channels = channels.clone();
         tokio::spawn(async move {
             channels.start_all().await;
         })
     };
+    match Outbox::open_default() {
+        Ok(outbox) => {
+            let channels = channels.clone();
+            tokio::spawn(async move {
+                // Channels connect first; queued output from earlier runs
+                // goes out on the first tick.
+                let start = tokio::time::Instant::now() + outbox::RETRY_INTERVAL;
+                let mut ticker = tokio::time::interval_at(start, outbox::RETRY_INTERVAL);
+                loop {
+                    ticker.tick().await;
+                    if let Err(err) = flush_outbox(&outbox, &channels).await {
+                        tracing::warn!("failed to retry queued output: {err:#}");
+                    }
+                }
+            });
+        }
+        Err(err) => tracing::warn!("outbox retries disabled: {err:#}"),
+    }
+    let idle_extraction = config.agents.defaults.idle_extraction.clone();
+    if idle_extraction.enabled {
+        let agent = agent.clone();
+        tokio::spawn(async move {
+            let idle = std::time::Duration::from_secs(idle_extraction.idle_minutes * 60);
+            let every = IDLE_EXTRACTION_INTERVAL.min(idle.max(std::time::Duration::from_secs(60)));
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+            loop {
+                ticker.tick().await;
+                if let Err(err) = agent
+                    .extract_idle_sessions(idle, idle_extraction.min_messages)
+                    .await
+                {
+                    tracing::warn!("idle session extraction failed: {err:#}");
+                }
+            }
+        });
+    }
 
-    tokio::signal::ctrl_c().await?;
-    println!("Shutting down...");
-    agent.stop();
-    heartbeat.stop().await;
-    cron.stop().await;
-    channels.stop_all().await;
-    agent_task.abort();
-    channels_task.abort();
-    Ok(())
+    Ok(GatewayRuntime {
+        agent,
+        bus,
+        drain_timeout: std::time::Duration::from_secs(gateway.drain_timeout_s),
+        cron,
+        heartbeat,
+        channels,
+        routes,
+        agent_task,
+        channels_task,
+    })
 }
 
-async fn cmd_agent(message: Option<String>, session: &str) -> Result<()> {
+async fn cmd_agent(
+    message: Option<String>,
+    session: &str,
+    files: &[PathBuf],
+    mut images: Vec<String>,
+    max_attach_bytes: usize,
+    style: OutputStyle,
+) -> Result<()> {
+    for image in &images {
+        check_image_source(image)?;
+    }
+    let mut attachments = files
+        .iter()
+        .map(|path| load_file_attachment(path, max_attach_bytes))
+        .collect::<Result<Vec<_>>>()?;
+    let stdin_attachment = read_stdin_attachment(max_attach_bytes)?;
+    // Piped stdin without -m becomes the message itself; with -m it is context.
+    let message = match (message, stdin_attachment) {
+        (Some(message), Some(stdin)) => {
+            attachments.push(stdin);
+            Some(message)
+        }
+        (None, Some(stdin)) => Some(stdin.content),
+        (message, None) => message,
+    };
+    for attachment in attachments.iter().filter(|a| a.truncated) {
+        eprintln!(
+            "Warning: {} truncated to {} of {} bytes",
+            attachment.label,
+            attachment.content.len(),
+            attachment.original_bytes
+        );
+    }
+
     let config = load_config(None).unwrap_or_default();
     let model = config.agents.defaults.model.clone();
     let normalized_model = model.strip_prefix("litellm/").unwrap_or(&model);
     let is_bedrock = normalized_model.starts_with("bedrock/");
     let api_key = config.get_api_key(Some(&model));
-    if api_key.is_none() && !is_bedrock {
-        println!("Error: No API key configured.");
-        println!("Set one in ~/.nanobot/config.json under providers.*.apiKey");
+    if api_key.is_none() && !is_bedrock && !mock::replaying() {
+        eprintln!("Error: {}", tr("no-api-key"));
+        eprintln!("{}", tr("no-api-key-hint"));
         return Ok(());
     }
 
     let bus = Arc::new(MessageBus::new(1024));
-    let provider = build_provider(
+    let provider = routed_provider(
         &config,
         &model,
         api_key.unwrap_or_else(|| "dummy".to_string()),
@@ -655,49 +1791,71 @@ async fn cmd_agent(message: Option<String>, session: &str) -> Result<()> {
     let cron = Arc::new(CronService::new(cron_store_path));
     let channels = Arc::new(ChannelManager::new(&config, bus.clone()));
 
-    let agent_loop = Arc::new(AgentLoop::new(
-        bus.clone(),
-        provider,
-        config.workspace_path(),
-        Some(model.clone()),
-        config.agents.defaults.max_tool_iterations,
-        config.agents.defaults.memory_window,
-        config.tools.web.search.clone(),
-        config.tools.exec.timeout,
-        config.tools.restrict_to_workspace,
-        Some(cron.clone()),
-        Some(session_manager.clone()),
-    )?);
+    let workflows = Arc::new(
+        WorkflowRunner::new(config.workspace_path(), workflow_runs_dir()?)
+            .with_approval(Arc::new(CliApprovalPrompt)),
+    );
+    let mut builder = AgentLoopBuilder::from_config(provider, &config)
+        .bus(bus.clone())
+        .cron(cron.clone())
+        .sessions(session_manager.clone())
+        .workflows(workflows.clone());
+    if config.tools.approval.enabled {
+        let gate = ApprovalGate::new(config.tools.approval.tools.clone())
+            .with_prompt("cli", Arc::new(CliApprovalPrompt));
+        builder = builder.approval_gate(Arc::new(gate));
+    }
+    let agent_loop = Arc::new(builder.build()?);
+    workflows.attach(&agent_loop);
 
     let bus_for_cron = bus.clone();
     let agent_for_cron = agent_loop.clone();
     let channels_for_cron = channels.clone();
+    let config_for_cron = Arc::new(config.clone());
     cron.set_on_job(Arc::new(move |job| {
+        let config = config_for_cron.clone();
         let bus = bus_for_cron.clone();
         let agent = agent_for_cron.clone();
         let channels = channels_for_cron.clone();
+        let workflows = workflows.clone();
         Box::pin(async move {
-            let response = agent
-                .process_direct(
-                    &job.payload.message,
-                    Some(&format!("cron:{}", job.id)),
+            if job.payload.kind == maintenance::CRON_PAYLOAD_KIND {
+                return maintenance::run_scheduled(&config).await;
+            }
+            if job.payload.kind == CRON_PAYLOAD_KIND {
+                return run_workflow_job(&workflows, &job.payload.message).await;
+            }
+            let response = if job.payload.kind == briefing::CRON_PAYLOAD_KIND {
+                run_briefing(
+                    &agent,
                     job.payload.channel.as_deref(),
                     job.payload.to.as_deref(),
                 )
-                .await?;
+                .await?
+            } else {
+                agent
+                    .process_direct(
+                        &job.payload.message,
+                        Some(&format!("cron:{}", job.id)),
+                        job.payload.channel.as_deref(),
+                        job.payload.to.as_deref(),
+                    )
+                    .await?
+            };
 
             if job.payload.deliver
-                && let (Some(channel), Some(to)) =
-                    (job.payload.channel.clone(), job.payload.to.clone())
+                && let (Some(channel), Some(to)) = (&job.payload.channel, &job.payload.to)
             {
-                let outbound = OutboundMessage::new(channel.clone(), to, response.clone());
-                if channel == "cli" {
-                    println!("nanobot-rs[cron]: {response}");
-                } else if let Some(adapter) = channels.get_channel(&channel) {
-                    adapter.send(&outbound).await?;
-                } else {
-                    bus.publish_outbound(outbound).await?;
-                }
+                deliver_job_output(
+                    &config,
+                    Some(&channels),
+                    &bus,
+                    &outbox::job_key(&job),
+                    channel,
+                    to,
+                    &response,
+                )
+                .await?;
             }
             Ok(Some(response))
         })
@@ -706,15 +1864,32 @@ async fn cmd_agent(message: Option<String>, session: &str) -> Result<()> {
     cron.start().await?;
 
     if let Some(content) = message {
+        let content = format_with_attachments(&content, &attachments);
+        presence::touch(&config.presence, presence::DESKTOP, session);
+        let spinner = style.spinner();
         let response = agent_loop
-            .process_direct(&content, Some(session), None, None)
-            .await?;
-        println!("nanobot-rs: {response}");
+            .process_direct_with_media(&content, images, Some(session), None, None)
+            .await;
+        drop(spinner);
+        let response = response.inspect_err(|err| {
+            if let Some(provider_err) = ProviderError::find(err) {
+                eprintln!("{}", provider_err.hint());
+            }
+        })?;
+        style.print_response(&response);
     } else {
-        println!("nanobot-rs interactive mode (type exit/quit or Ctrl+C to exit)");
+        if style != OutputStyle::Raw {
+            println!("{}", tr("interactive-banner"));
+        }
+        // Read line by line without holding the stdin lock, so approval
+        // prompts can read their answer while a turn is in progress.
         let stdin = std::io::stdin();
-        for line in stdin.lock().lines() {
-            let input = line?;
+        loop {
+            let mut input = String::new();
+            if stdin.read_line(&mut input)? == 0 {
+                break;
+            }
+            let input = input.trim_end_matches(['\r', '\n']).to_string();
             let command = input.trim();
             if command.is_empty() {
                 continue;
@@ -722,17 +1897,104 @@ async fn cmd_agent(message: Option<String>, session: &str) -> Result<()> {
             if is_exit_command(command) {
                 break;
             }
+            presence::touch(&config.presence, presence::DESKTOP, session);
+            // File attachments and images ride along with the first interactive message.
+            let input = format_with_attachments(&input, &std::mem::take(&mut attachments));
+            let media = std::mem::take(&mut images);
+            let reloaded = agent_loop.reload_prompt();
+            if !reloaded.is_empty() && style != OutputStyle::Raw {
+                eprintln!(
+                    "{}",
+                    tr_args("prompt-reloaded", &[("files", &reloaded.join(", "))])
+                );
+            }
+            let spinner = style.spinner();
             let response = agent_loop
-                .process_direct(&input, Some(session), None, None)
-                .await?;
-            println!("nanobot-rs: {response}");
+                .process_direct_with_media(&input, media, Some(session), None, None)
+                .await;
+            drop(spinner);
+            // Provider failures end the turn, not the session.
+            let response = match response {
+                Err(err) if ProviderError::find(&err).is_some() => {
+                    eprintln!("{}", error_reply(&err, "cli"));
+                    continue;
+                }
+                response => response?,
+            };
+            style.print_response(&response);
+        }
+        if style != OutputStyle::Raw {
+            println!("{}", tr("goodbye"));
         }
-        println!("Goodbye!");
     }
     cron.stop().await;
     Ok(())
 }
 
+struct CliApprovalPrompt;
+
+#[async_trait]
+impl ApprovalPrompt for CliApprovalPrompt {
+    async fn ask(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        if !std::io::stdin().is_terminal() {
+            return ApprovalDecision::Deny;
+        }
+        let arguments = request.arguments_pretty();
+        let _pause = terminal::pause_spinner();
+        let arguments = if terminal::color_stderr() {
+            highlight_code(&arguments, "json")
+        } else {
+            arguments
+        };
+        eprintln!("\nTool `{}` requires approval:\n{arguments}", request.tool);
+        loop {
+            eprint!("Allow? [y]es / [n]o / [a]lways for this tool: ");
+            let answer = tokio::task::spawn_blocking(|| {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line).map(|_| line)
+            })
+            .await;
+            match answer {
+                Ok(Ok(line)) if line.is_empty() => return ApprovalDecision::Deny,
+                Ok(Ok(line)) => {
+                    if let Some(decision) = ApprovalDecision::parse(&line) {
+                        return decision;
+                    }
+                }
+                _ => return ApprovalDecision::Deny,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputStyle {
+    Rendered,
+    Plain,
+    Raw,
+}
+
+impl OutputStyle {
+    /// A spinner while the agent works; only rendered output gets one.
+    fn spinner(self) -> Option<Spinner> {
+        (self == OutputStyle::Rendered).then(|| Spinner::start(&tr("thinking")))
+    }
+
+    fn print_response(self, response: &str) {
+        match self {
+            OutputStyle::Raw => println!("{response}"),
+            OutputStyle::Plain => println!("nanobot-rs: {response}"),
+            OutputStyle::Rendered => {
+                if terminal::color_stdout() {
+                    println!("nanobot-rs: {}", render_terminal(response));
+                } else {
+                    println!("nanobot-rs: {response}");
+                }
+            }
+        }
+    }
+}
+
 fn is_exit_command(command: &str) -> bool {
     matches!(
         command.to_ascii_lowercase().as_str(),
@@ -767,7 +2029,12 @@ fn persist_service_name_if_overridden(config: &mut Config, name: Option<&str>) -
         if normalized.is_empty() {
             return Err(anyhow!("service name cannot be empty"));
         }
-        if config.service.name != normalized {
+        // Addressing another instance by name must not rebind this config.
+        let belongs_elsewhere = list_instances()?.iter().any(|instance| {
+            instance.name.eq_ignore_ascii_case(normalized)
+                && instance.config_path.as_deref() != config_path_override()
+        });
+        if config.service.name != normalized && !belongs_elsewhere {
             config.service.name = normalized.to_string();
             save_config(config, None)?;
             println!(
@@ -780,6 +2047,10 @@ fn persist_service_name_if_overridden(config: &mut Config, name: Option<&str>) -
     Ok(())
 }
 
+fn parse_env_arg(value: &str) -> Result<(String, String), String> {
+    parse_env_assignment(value).ok_or_else(|| format!("expected KEY=VALUE, got '{value}'"))
+}
+
 fn current_user_for_service() -> Result<String> {
     let username = std::env::var("USERNAME")
         .ok()
@@ -837,11 +2108,14 @@ fn cmd_service(command: ServiceCommand) -> Result<()> {
             bin,
             args,
             workdir,
+            log_dir,
             system,
             use_current_user,
             password,
             auto_install_nssm,
             autostart,
+            env,
+            secrets_file,
         } => {
             let resolved_name = resolve_service_name(&config, name.as_deref())?;
             persist_service_name_if_overridden(&mut config, name.as_deref())?;
@@ -853,18 +2127,46 @@ fn cmd_service(command: ServiceCommand) -> Result<()> {
                 Some(path) => path,
                 None => std::env::current_dir()?,
             };
+            let log_directory = match log_dir {
+                Some(path) => path,
+                None => get_data_path()?.join("logs"),
+            };
             let account = resolve_install_account(system, use_current_user, password)?;
+            let secrets_file = match secrets_file {
+                Some(path) => {
+                    let path = std::fs::canonicalize(&path)
+                        .with_context(|| format!("secrets file not found: {}", path.display()))?;
+                    check_secrets_file_permissions(&path)?;
+                    Some(path)
+                }
+                None => None,
+            };
+            let config_path = config_path_override().map(Path::to_path_buf);
+            let arguments = match &config_path {
+                Some(path) => format!(
+                    "--config {} {}",
+                    service::quote_argument(&path.display().to_string()),
+                    args.trim()
+                ),
+                None => args,
+            };
+            let instance = ServiceInstance::new(&resolved_name, config_path, log_directory.clone());
             let options = ServiceInstallOptions {
                 name: resolved_name.clone(),
                 binary_path,
-                arguments: args,
+                arguments,
                 working_directory,
-                log_directory: get_data_path()?.join("logs"),
+                log_directory,
                 account,
                 auto_install_nssm,
                 autostart,
+                environment: env,
+                secrets_file,
+                // Leave room for the shutdown drain before being killed.
+                stop_timeout_s: config.gateway.drain_timeout_s + 10,
             };
             service::install_service(&options)?;
+            record_instance(instance)?;
             println!("Service '{}' configured successfully.", resolved_name);
             println!("Use `nanobot-rs service start` to start it.");
         }
@@ -872,6 +2174,7 @@ fn cmd_service(command: ServiceCommand) -> Result<()> {
             let resolved_name = resolve_service_name(&config, name.as_deref())?;
             persist_service_name_if_overridden(&mut config, name.as_deref())?;
             service::remove_service(&resolved_name)?;
+            forget_instance(&resolved_name)?;
             println!("Service '{}' removed.", resolved_name);
         }
         ServiceCommand::Start { name } => {
@@ -904,12 +2207,56 @@ fn cmd_service(command: ServiceCommand) -> Result<()> {
                     resolved_name,
                     status.state.unwrap_or_else(|| "UNKNOWN".to_string())
                 );
+                print_service_health();
+            }
+        }
+        ServiceCommand::List => {
+            let instances = list_instances()?;
+            if instances.is_empty() {
+                println!("No nanobot services installed.");
+            }
+            for instance in instances {
+                let state = match service::status_service(&instance.name) {
+                    Ok(status) if !status.exists => "NOT_INSTALLED".to_string(),
+                    Ok(status) => status.state.unwrap_or_else(|| "UNKNOWN".to_string()),
+                    Err(err) => format!("ERROR ({err})"),
+                };
+                let config = instance
+                    .config_path
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|| "default".to_string());
+                println!("{:<24} {:<20} config: {config}", instance.name, state);
             }
         }
     }
     Ok(())
 }
 
+fn print_service_health() {
+    let Some(snapshot) = read_health_snapshot() else {
+        return;
+    };
+    if snapshot.is_stale(chrono::Local::now()) {
+        println!("Health: unknown (last report {})", snapshot.updated_at);
+        return;
+    }
+    println!(
+        "Health: {} (pid {}, up since {})",
+        if snapshot.healthy { "ok" } else { "degraded" },
+        snapshot.pid,
+        snapshot.started_at
+    );
+    for subsystem in snapshot.unhealthy_subsystems() {
+        println!(
+            "  {}: {:?}, {} failure(s), next retry {}",
+            subsystem.name,
+            subsystem.status,
+            subsystem.consecutive_failures,
+            subsystem.next_retry_at.as_deref().unwrap_or("-")
+        );
+    }
+}
+
 async fn cmd_channels(command: ChannelCommand) -> Result<()> {
     match command {
         ChannelCommand::Status => {
@@ -1064,63 +2411,457 @@ async fn cmd_channels(command: ChannelCommand) -> Result<()> {
     Ok(())
 }
 
-fn cmd_pairing(command: PairingCommand) -> Result<()> {
+fn cmd_apikeys(command: ApiKeysCommand) -> Result<()> {
+    let config = load_config(None).unwrap_or_default();
+    let mut store = ApiKeyStore::load(&apikeys_path(&config.workspace_path())?)?;
     match command {
-        PairingCommand::List => {
-            let pending = list_pending()?;
-            if pending.is_empty() {
-                println!("No pending pairing requests.");
-                return Ok(());
+        ApiKeysCommand::Create {
+            name,
+            read_only,
+            admin,
+            profile,
+            expires_in_days,
+            unlock_with,
+        } => {
+            let ttl = expires_in_days.map(chrono::Duration::days);
+            let (key, token) = store.create(&name, read_only, admin, profile, ttl)?;
+            if config.gateway.encrypt_user_data && key.profile.is_some() {
+                store.issue_data_key(&token, unlock_with.as_deref())?;
             }
-            println!("Pending pairing requests:");
-            for entry in pending {
+            store.save()?;
+            println!("Created API key '{}' (id {})", key.name, key.id);
+            println!("{token}");
+            println!("Store it now; it cannot be shown again.");
+            if !config.gateway.require_api_key {
                 println!(
-                    "- channel={} sender={} chat={} code={} requests={} last_seen_ms={}",
-                    entry.channel,
-                    entry.sender_id,
-                    entry.chat_id,
-                    entry.code,
-                    entry.request_count,
-                    entry.last_seen_at_ms
+                    "Note: gateway.requireApiKey is off, so requests without a key are still accepted."
                 );
             }
         }
-        PairingCommand::Approve { channel, code } => {
-            let approved = approve_pairing(&channel, &code)?;
-            println!(
-                "Approved pairing: channel={} sender={} chat={} code={}",
-                approved.channel, approved.sender_id, approved.chat_id, approved.code
-            );
-            println!(
-                "Sender added to allowlist. You can verify with `nanobot-rs channels status` or config.json."
-            );
+        ApiKeysCommand::List => {
+            if store.keys.is_empty() {
+                println!("No API keys.");
+                return Ok(());
+            }
+            let now = chrono::Local::now();
+            for key in &store.keys {
+                let access = match (key.read_only, key.admin) {
+                    (true, true) => "admin-read-only",
+                    (false, true) => "admin",
+                    (true, false) => "read-only",
+                    (false, false) => "full",
+                };
+                let expires = key
+                    .expires_at
+                    .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "never".to_string());
+                println!(
+                    "- {} {} [{}] access={access} profile={} expires={expires}",
+                    key.id,
+                    key.name,
+                    key.status(now),
+                    key.profile.as_deref().unwrap_or("-"),
+                );
+            }
         }
-        PairingCommand::Reject { channel, code } => {
-            if reject_pairing(&channel, &code)? {
-                println!("Rejected pairing request: channel={channel} code={code}");
-            } else {
-                println!("No pending pairing request found for channel={channel} code={code}");
+        ApiKeysCommand::Revoke { key } => {
+            if !store.revoke(&key) {
+                return Err(anyhow!("no API key with id or name '{key}'"));
             }
+            store.save()?;
+            println!("Revoked API key '{key}'");
         }
     }
     Ok(())
 }
 
-fn cmd_sessions(command: SessionCommand) -> Result<()> {
-    let sessions = SessionManager::new()?;
-    match command {
-        SessionCommand::List => {
-            let keys = sessions.list_session_keys()?;
-            if keys.is_empty() {
-                println!("No sessions found.");
-            } else {
-                println!("Sessions:");
-                for key in keys {
-                    println!("- {key}");
-                }
-            }
+/// Sets one config value, through the running gateway when there is one.
+async fn cmd_config(command: ConfigCommand) -> Result<()> {
+    let ConfigCommand::Set {
+        key,
+        value,
+        no_verify,
+    } = command;
+    if let Some(applied) = set_config_on_gateway(&key, &value).await? {
+        if applied {
+            println!("Set {key}; the running gateway now uses it");
+        } else {
+            println!("Set {key}; restart the gateway to apply it");
         }
-        SessionCommand::Show { session, limit } => {
+        return Ok(());
+    }
+    let edit = ConfigEdit::new(&key, &value)?;
+    if key.starts_with("providers.") && !no_verify {
+        swap::verify_setting(&edit.config, &key).await?;
+    }
+    edit.save()?;
+    println!("Set {key}");
+    Ok(())
+}
+
+/// Sends the change to a running gateway, which checks provider settings
+/// before saving them. `None` when no gateway answers.
+async fn set_config_on_gateway(key: &str, value: &str) -> Result<Option<bool>> {
+    let (Ok(base), Ok(token)) = (admin_base_url(), read_admin_token()) else {
+        return Ok(None);
+    };
+    let client = network::client_builder()
+        .danger_accept_invalid_certs(base.starts_with("https://127.0.0.1"))
+        .build()?;
+    let response = client
+        .post(format!("{base}/admin/config"))
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "key": key, "value": value }))
+        .send()
+        .await;
+    // A token left behind by a gateway that has stopped.
+    let Ok(response) = response else {
+        return Ok(None);
+    };
+    let payload: serde_json::Value = response.json().await?;
+    if payload.get("ok").and_then(serde_json::Value::as_bool) != Some(true) {
+        let error = payload
+            .get("error")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or("unknown error");
+        return Err(anyhow!("the gateway refused the change: {error}"));
+    }
+    Ok(Some(
+        payload
+            .get("applied")
+            .and_then(serde_json::Value::as_bool)
+            .unwrap_or(false),
+    ))
+}
+
+/// Base URL of the local gateway listener.
+fn admin_base_url() -> Result<String> {
+    let gateway = gateway_listen_config(None, None);
+    if !gateway.unix_socket.trim().is_empty() {
+        return Err(anyhow!(
+            "the gateway listens on a UNIX socket; pass --url for a TCP endpoint"
+        ));
+    }
+    let host = match gateway.host.as_str() {
+        "0.0.0.0" | "" => "127.0.0.1",
+        "::" => "[::1]",
+        host => host,
+    };
+    let scheme = if gateway.tls.enabled { "https" } else { "http" };
+    Ok(format!("{scheme}://{host}:{}", gateway.port))
+}
+
+async fn cmd_admin(url: Option<String>, key: Option<String>, command: AdminCommand) -> Result<()> {
+    let base = match url {
+        Some(url) => url.trim_end_matches('/').to_string(),
+        None => admin_base_url()?,
+    };
+    let token = match key {
+        Some(key) => key,
+        None => read_admin_token()?,
+    };
+    // The local listener's certificate rarely names 127.0.0.1.
+    let client = network::client_builder()
+        .danger_accept_invalid_certs(base.starts_with("https://127.0.0.1"))
+        .build()?;
+    let (method, path, body) = match &command {
+        AdminCommand::Sessions => (reqwest::Method::GET, "/admin/sessions".to_string(), None),
+        AdminCommand::Queues => (reqwest::Method::GET, "/admin/queues".to_string(), None),
+        AdminCommand::Errors { limit } => (
+            reqwest::Method::GET,
+            format!("/admin/errors?limit={limit}"),
+            None,
+        ),
+        AdminCommand::Tools => (reqwest::Method::GET, "/admin/tools".to_string(), None),
+        AdminCommand::EnableTool { name } | AdminCommand::DisableTool { name } => (
+            reqwest::Method::POST,
+            "/admin/tools".to_string(),
+            Some(serde_json::json!({
+                "name": name,
+                "enabled": matches!(command, AdminCommand::EnableTool { .. }),
+            })),
+        ),
+        AdminCommand::Consolidate { session } => (
+            reqwest::Method::POST,
+            "/admin/consolidate".to_string(),
+            Some(serde_json::json!({ "session": session })),
+        ),
+    };
+    let mut request = client
+        .request(method, format!("{base}{path}"))
+        .bearer_auth(token);
+    if let Some(body) = body {
+        request = request.json(&body);
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("failed to reach the gateway at {base}"))?;
+    let payload: serde_json::Value = response.json().await?;
+    if payload.get("ok").and_then(serde_json::Value::as_bool) != Some(true) {
+        let error = payload
+            .get("error")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or("unknown error");
+        return Err(anyhow!("admin request failed: {error}"));
+    }
+
+    let items = |key: &str| {
+        payload
+            .get(key)
+            .and_then(serde_json::Value::as_array)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let text = |value: &serde_json::Value, key: &str| {
+        value
+            .get(key)
+            .map(|v| v.as_str().map(ToOwned::to_owned).unwrap_or(v.to_string()))
+            .unwrap_or_default()
+    };
+    match command {
+        AdminCommand::Sessions => {
+            let active = items("active");
+            println!("Active turns: {}", active.len());
+            for turn in &active {
+                println!("  - {} (since {})", text(turn, "key"), text(turn, "since"));
+            }
+            println!("Sessions:");
+            for session in items("sessions") {
+                println!(
+                    "  - {} messages={} updated={}",
+                    text(&session, "key"),
+                    text(&session, "messages"),
+                    text(&session, "updatedAt")
+                );
+            }
+        }
+        AdminCommand::Queues => {
+            for key in ["inbound", "outbound", "activeTurns", "subagents"] {
+                println!("{key}: {}", text(&payload, key));
+            }
+        }
+        AdminCommand::Errors { .. } => {
+            let errors = items("errors");
+            if errors.is_empty() {
+                println!("No recent warnings or errors.");
+            }
+            for line in errors {
+                println!("{}", line.as_str().unwrap_or_default());
+            }
+        }
+        AdminCommand::Tools => {
+            for tool in items("tools") {
+                let state = if tool.get("enabled") == Some(&serde_json::Value::Bool(true)) {
+                    "enabled"
+                } else {
+                    "disabled"
+                };
+                println!("- {} [{state}]", text(&tool, "name"));
+            }
+        }
+        AdminCommand::EnableTool { name } => println!("Enabled tool '{name}'"),
+        AdminCommand::DisableTool { name } => println!("Disabled tool '{name}'"),
+        AdminCommand::Consolidate { session } => println!(
+            "Consolidated '{session}': {} -> {} messages",
+            text(&payload, "before"),
+            text(&payload, "after")
+        ),
+    }
+    Ok(())
+}
+
+fn cmd_pairing(command: PairingCommand) -> Result<()> {
+    match command {
+        PairingCommand::List => {
+            let pending = list_pending()?;
+            if pending.is_empty() {
+                println!("No pending pairing requests.");
+                return Ok(());
+            }
+            println!("Pending pairing requests:");
+            for entry in pending {
+                println!(
+                    "- channel={} sender={} chat={} code={} requests={} last_seen_ms={}",
+                    entry.channel,
+                    entry.sender_id,
+                    entry.chat_id,
+                    entry.code,
+                    entry.request_count,
+                    entry.last_seen_at_ms
+                );
+            }
+        }
+        PairingCommand::Approve { channel, code } => {
+            let approved = approve_pairing(&channel, &code)?;
+            println!(
+                "Approved pairing: channel={} sender={} chat={} code={}",
+                approved.channel, approved.sender_id, approved.chat_id, approved.code
+            );
+            println!(
+                "Sender added to allowlist. You can verify with `nanobot-rs channels status` or config.json."
+            );
+        }
+        PairingCommand::Reject { channel, code } => {
+            if reject_pairing(&channel, &code)? {
+                println!("Rejected pairing request: channel={channel} code={code}");
+            } else {
+                println!("No pending pairing request found for channel={channel} code={code}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_maintenance(dry_run: bool) -> Result<()> {
+    let config = load_config(None)?;
+    let paths = MaintenancePaths::from_config(&config)?;
+    let report = maintenance::run(&config.maintenance, &paths, dry_run);
+    println!("{}", report.to_string().trim_end());
+    Ok(())
+}
+
+fn cmd_usage_export(format: &str, month: Option<String>, output: Option<PathBuf>) -> Result<()> {
+    let config = load_config(None).unwrap_or_default();
+    let month = month.unwrap_or_else(|| chrono::Local::now().format("%Y-%m").to_string());
+    let (start, end) = usage::month_range(&month)?;
+    let records = usage::load_records(&usage::usage_path()?, Some(start))
+        .into_iter()
+        .filter(|record| record.at < end)
+        .collect::<Vec<_>>();
+    let rows = usage::aggregate(&records, &config.budget.model_prices());
+    let text = match format {
+        "json" => format!("{}\n", serde_json::to_string_pretty(&rows)?),
+        _ => usage::rows_to_csv(&rows),
+    };
+    match &output {
+        Some(path) => {
+            fs::write(path, text)?;
+            eprintln!(
+                "Wrote {} rows for {month} to {}",
+                rows.len(),
+                path.display()
+            );
+        }
+        None => print!("{text}"),
+    }
+    let unpriced = usage::unpriced_models(&rows);
+    if !unpriced.is_empty() {
+        eprintln!(
+            "No price for {}; add one to budget.prices. Their cost is left empty.",
+            unpriced.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Rows per table in `nanobot usage`.
+const USAGE_ROWS: usize = 20;
+
+fn cmd_usage(days: i64, since: Option<&str>, perf: bool) -> Result<()> {
+    let now = chrono::Local::now();
+    let (start, window) = match since {
+        Some(since) => (usage::parse_since(since, now)?, format!("since {since}")),
+        None => (
+            now - chrono::Duration::days(days.max(0)),
+            format!("in the last {days} days"),
+        ),
+    };
+    let records = usage::load_records(&usage::usage_path()?, Some(start));
+    if records.is_empty() {
+        println!("No model calls recorded {window}.");
+        return Ok(());
+    }
+    if perf {
+        let stats = usage::summarize(&records);
+        println!(
+            "{:<36} {:>6} {:>10} {:>10} {:>10} {:>10} {:>8}",
+            "MODEL", "CALLS", "TTFT p50", "TTFT p95", "TOTAL p50", "TOTAL p95", "TOK/S"
+        );
+        for model in stats {
+            let tps = model
+                .tokens_per_second_p50
+                .map(|tps| format!("{tps:.1}"))
+                .unwrap_or_else(|| "-".to_string());
+            println!(
+                "{:<36} {:>6} {:>8}ms {:>8}ms {:>8}ms {:>8}ms {:>8}",
+                model.model,
+                model.calls,
+                model.ttft_p50_ms,
+                model.ttft_p95_ms,
+                model.total_p50_ms,
+                model.total_p95_ms,
+                tps
+            );
+        }
+        return Ok(());
+    }
+
+    let config = load_config(None).unwrap_or_default();
+    let prices = config.budget.model_prices();
+    let print_spend = |title: &str, spends: &[usage::Spend]| {
+        println!(
+            "{:<36} {:>6} {:>14} {:>14} {:>10}",
+            title, "CALLS", "PROMPT TOKENS", "OUTPUT TOKENS", "COST"
+        );
+        for spend in spends.iter().take(USAGE_ROWS) {
+            let cost = if spend.unpriced_calls == spend.calls {
+                "-".to_string()
+            } else {
+                format!("${:.4}", spend.cost_usd)
+            };
+            let key = if spend.key.is_empty() {
+                "(outside a session)"
+            } else {
+                &spend.key
+            };
+            println!(
+                "{:<36} {:>6} {:>14} {:>14} {:>10}",
+                key, spend.calls, spend.prompt_tokens, spend.completion_tokens, cost
+            );
+        }
+        if spends.len() > USAGE_ROWS {
+            println!("… {} more", spends.len() - USAGE_ROWS);
+        }
+    };
+    let by_model = usage::spend_by(&records, &prices, |r| r.model.as_str());
+    print_spend("MODEL", &by_model);
+    println!();
+    print_spend(
+        "SESSION",
+        &usage::spend_by(&records, &prices, |r| r.session.as_str()),
+    );
+    let total: f64 = by_model.iter().map(|s| s.cost_usd).sum();
+    println!("\nTotal {window}: ${total:.4} over {} calls", records.len());
+    let unpriced: Vec<&str> = by_model
+        .iter()
+        .filter(|s| s.unpriced_calls > 0)
+        .map(|s| s.key.as_str())
+        .collect();
+    if !unpriced.is_empty() {
+        println!(
+            "No price for {}; add one to budget.prices.",
+            unpriced.join(", ")
+        );
+    }
+    Ok(())
+}
+
+async fn cmd_sessions(command: SessionCommand) -> Result<()> {
+    let sessions = SessionManager::new()?;
+    match command {
+        SessionCommand::List => {
+            let keys = sessions.list_session_keys()?;
+            if keys.is_empty() {
+                println!("No sessions found.");
+            } else {
+                println!("Sessions:");
+                for key in keys {
+                    println!("- {key}");
+                }
+            }
+        }
+        SessionCommand::Show { session, limit } => {
             let loaded = sessions.load_session(&session)?;
             let start = loaded.messages.len().saturating_sub(limit);
             println!("Session: {}", loaded.key);
@@ -1130,26 +2871,768 @@ fn cmd_sessions(command: SessionCommand) -> Result<()> {
                     .get("role")
                     .and_then(|v| v.as_str())
                     .unwrap_or("unknown");
-                let content = msg
-                    .get("content")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .replace('\n', " ");
+                let content =
+                    redact::redact(msg.get("content").and_then(|v| v.as_str()).unwrap_or(""))
+                        .replace('\n', " ");
                 let ts = msg.get("timestamp").and_then(|v| v.as_str()).unwrap_or("-");
                 println!("[{}] {}: {}", ts, role, content);
             }
         }
         SessionCommand::Delete { session } => {
             if sessions.delete(&session) {
+                let workspace = load_config(None).unwrap_or_default().workspace_path();
+                if let Err(err) = nanobot::tools::shell::remove_sandbox(&workspace, &session) {
+                    eprintln!("Failed to remove the exec sandbox of {session}: {err}");
+                }
                 println!("Deleted session {session}");
             } else {
                 println!("Session not found: {session}");
             }
         }
+        SessionCommand::Compact { session, undo } => {
+            if undo {
+                let restored = sessions.restore_archive(&session)?;
+                println!(
+                    "Restored {} messages to session {session}",
+                    restored.messages.len()
+                );
+                return Ok(());
+            }
+            let config = load_config(None)?;
+            let (agent, _) = workflow_cli_runner(&config)?;
+            let done = agent.compact_session(&session).await?;
+            println!(
+                "Compacted {} messages of {session}: {} → {} characters",
+                done.messages, done.chars_before, done.chars_after
+            );
+            println!("Original transcript: {}", done.archive.display());
+        }
+        SessionCommand::Publish {
+            session,
+            title,
+            dir,
+        } => {
+            let config = load_config(None)?;
+            let dir = dir.unwrap_or_else(|| {
+                nanobot::publish::notes_dir(&config.publish, &config.workspace_path())
+            });
+            let (agent, _) = workflow_cli_runner(&config)?;
+            let path = agent
+                .publish_session(&session, &dir, title.as_deref())
+                .await?;
+            println!("Published {}", path.display());
+        }
     }
     Ok(())
 }
 
+async fn cmd_prompt(command: PromptCommand) -> Result<()> {
+    let workspace = load_config(None).unwrap_or_default().workspace_path();
+    match command {
+        PromptCommand::List => {
+            let templates = list_templates(&workspace);
+            if templates.is_empty() {
+                println!(
+                    "No prompt templates in {}",
+                    nanobot::prompts::prompts_dir(&workspace).display()
+                );
+            }
+            for template in templates {
+                let variables = template
+                    .variables
+                    .iter()
+                    .map(|v| match &v.default {
+                        Some(default) => format!("{}={default}", v.name),
+                        None => v.name.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}  {}", template.name, variables.join(" "));
+                if !template.description.is_empty() {
+                    println!("    {}", template.description);
+                }
+            }
+        }
+        PromptCommand::Show { name, vars } => {
+            let prompt = load_template(&workspace, &name)?.render(&parse_vars(&vars)?)?;
+            println!("{prompt}");
+        }
+        PromptCommand::Run {
+            name,
+            vars,
+            session,
+            plain,
+            raw,
+        } => {
+            let prompt = load_template(&workspace, &name)?.render(&parse_vars(&vars)?)?;
+            let style = if raw {
+                OutputStyle::Raw
+            } else if plain {
+                OutputStyle::Plain
+            } else {
+                OutputStyle::Rendered
+            };
+            cmd_agent(
+                Some(prompt),
+                &session,
+                &[],
+                Vec::new(),
+                DEFAULT_MAX_ATTACHMENT_BYTES,
+                style,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+fn workflow_runs_dir() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("workflows").join("runs"))
+}
+
+/// Cron callback body for jobs created by `sync_cron_triggers`.
+/// Re-reads the config so source changes apply without a restart.
+/// Sends a cron job's output to `channel`/`to`, or wherever presence routing
+/// sends notifications. `channels` delivers directly when no gateway is
+/// dispatching the bus.
+async fn deliver_job_output(
+    config: &Config,
+    channels: Option<&ChannelManager>,
+    bus: &MessageBus,
+    key: &str,
+    channel: &str,
+    to: &str,
+    response: &str,
+) -> Result<()> {
+    let (channel, to) = match presence::route(config, channel, to) {
+        Target::Desktop => match presence::notify_desktop("nanobot", response).await {
+            Ok(()) => return Ok(()),
+            Err(err) => {
+                tracing::warn!("desktop notification failed: {err:#}");
+                (channel.to_string(), to.to_string())
+            }
+        },
+        Target::Chat { channel, to } => (channel, to),
+    };
+    if channel == presence::DESKTOP {
+        return Ok(());
+    }
+    let outbound = OutboundMessage::new(channel.clone(), to, response.to_string());
+    match channels {
+        Some(_) if channel == "cli" => println!("nanobot-rs[cron]: {response}"),
+        Some(channels) if let Some(adapter) = channels.get_channel(&channel) => {
+            // Queued first, so output the channel cannot take now survives
+            // for the gateway to retry.
+            let outbox = Outbox::open_default()?;
+            if !outbox.enqueue(key, &outbound)? {
+                tracing::debug!(key, "scheduled output already queued or delivered");
+                return Ok(());
+            }
+            match adapter.send(&outbound).await {
+                Ok(()) => outbox.ack(key)?,
+                Err(err) => {
+                    tracing::warn!(key, %channel, "delivery failed, queued for retry: {err:#}");
+                    outbox.fail(key, &format!("{err:#}"))?;
+                }
+            }
+        }
+        _ => bus.publish_outbound(outbound).await?,
+    }
+    Ok(())
+}
+
+/// Resends queued scheduled output that is due, skipping channels whose
+/// receive task is down until they are back.
+async fn flush_outbox(outbox: &Outbox, channels: &ChannelManager) -> Result<()> {
+    let entries = outbox.due()?;
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let down = channels.exited_channels().await;
+    for entry in entries {
+        let channel = &entry.message.channel;
+        let Some(adapter) = channels
+            .get_channel(channel)
+            .filter(|_| !down.contains(channel))
+        else {
+            continue;
+        };
+        match adapter.send(&entry.message).await {
+            Ok(()) => {
+                tracing::info!(key = %entry.key, %channel, "delivered queued scheduled output");
+                outbox.ack(&entry.key)?;
+            }
+            Err(err) => {
+                tracing::debug!(key = %entry.key, %channel, "retry failed: {err:#}");
+                outbox.fail(&entry.key, &format!("{err:#}"))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_briefing(
+    agent: &AgentLoop,
+    channel: Option<&str>,
+    to: Option<&str>,
+) -> Result<String> {
+    let config = load_config(None).unwrap_or_default();
+    let cron_store = get_data_path()?.join("cron").join("jobs.json");
+    let briefing_config = briefing::with_location_weather(&config.briefing, &config.location).await;
+    briefing::generate(
+        agent,
+        &briefing_config,
+        &config.workspace_path(),
+        &cron_store,
+        channel,
+        to,
+    )
+    .await
+}
+
+async fn run_workflow_job(workflows: &WorkflowRunner, name: &str) -> Result<Option<String>> {
+    let run = workflows.run(name, serde_json::json!({}), "cron").await?;
+    if run.status == RunStatus::Failed {
+        return Err(anyhow!(run.summary()));
+    }
+    Ok(Some(run.summary()))
+}
+
+fn print_workflow_run(run: &WorkflowRun) {
+    println!(
+        "{}  {}  {}  {}  [{}]",
+        run.id,
+        run.workflow,
+        run.status.as_str(),
+        run.started_at.format("%Y-%m-%d %H:%M:%S"),
+        run.trigger
+    );
+}
+
+/// Agent for running workflows from the CLI; the runner only holds a weak
+/// reference, so keep the returned agent alive while it runs.
+fn workflow_cli_runner(config: &Config) -> Result<(Arc<AgentLoop>, Arc<WorkflowRunner>)> {
+    let model = config.agents.defaults.model.clone();
+    let api_key = config
+        .get_api_key(Some(&model))
+        .or_else(|| mock::replaying().then(|| "dummy".to_string()))
+        .ok_or_else(|| anyhow!(tr("no-api-key")))?;
+    let provider = routed_provider(config, &model, api_key);
+    let runner = Arc::new(
+        WorkflowRunner::new(config.workspace_path(), workflow_runs_dir()?)
+            .with_approval(Arc::new(CliApprovalPrompt)),
+    );
+    let mut builder = AgentLoopBuilder::from_config(provider, config)
+        .sessions(Arc::new(SessionManager::new()?))
+        .workflows(runner.clone());
+    if config.tools.approval.enabled {
+        let gate = ApprovalGate::new(config.tools.approval.tools.clone())
+            .with_prompt("cli", Arc::new(CliApprovalPrompt));
+        builder = builder.approval_gate(Arc::new(gate));
+    }
+    let agent = Arc::new(builder.build()?);
+    runner.attach(&agent);
+    Ok((agent, runner))
+}
+
+fn finish_workflow_run(run: &WorkflowRun) -> Result<()> {
+    print_workflow_run(run);
+    println!("{}", run.summary());
+    if run.status != RunStatus::Succeeded {
+        return Err(anyhow!("workflow run {} {}", run.id, run.status.as_str()));
+    }
+    Ok(())
+}
+
+async fn cmd_brief(sources: bool, deliver: bool) -> Result<()> {
+    let config = load_config(None).unwrap_or_default();
+    let cron_store = get_data_path()?.join("cron").join("jobs.json");
+    if sources {
+        let briefing_config =
+            briefing::with_location_weather(&config.briefing, &config.location).await;
+        let sections =
+            briefing::gather(&briefing_config, &config.workspace_path(), &cron_store).await;
+        println!("{}", briefing::render_sections(&sections));
+        return Ok(());
+    }
+    let target = (!config.briefing.channel.is_empty() && !config.briefing.to.is_empty())
+        .then(|| (config.briefing.channel.clone(), config.briefing.to.clone()));
+    if deliver && target.is_none() {
+        return Err(anyhow!(
+            "set briefing.channel and briefing.to to deliver the briefing"
+        ));
+    }
+    let (agent, _) = workflow_cli_runner(&config)?;
+    let text = run_briefing(
+        &agent,
+        target.as_ref().map(|(channel, _)| channel.as_str()),
+        target.as_ref().map(|(_, to)| to.as_str()),
+    )
+    .await?;
+    println!("{text}");
+    if deliver && let Some((channel, to)) = target {
+        let bus = Arc::new(MessageBus::new(16));
+        let adapter = ChannelManager::new(&config, bus)
+            .get_channel(&channel)
+            .ok_or_else(|| anyhow!("channel '{channel}' is not enabled"))?;
+        adapter
+            .send(&OutboundMessage::new(channel, to, text))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn cmd_tasks(command: TasksCommand) -> Result<()> {
+    let store = TaskStore::new(&load_config(None).unwrap_or_default().workspace_path());
+    let cron = || async {
+        let cron = CronService::new(get_data_path()?.join("cron").join("jobs.json"));
+        cron.start().await?;
+        anyhow::Ok(cron)
+    };
+    match command {
+        TasksCommand::List { all, tag } => {
+            let tasks = store
+                .list()
+                .into_iter()
+                .filter(|task| all || task.is_open())
+                .filter(|task| {
+                    tag.as_deref()
+                        .is_none_or(|tag| task.tags.iter().any(|t| t == tag))
+                })
+                .collect::<Vec<_>>();
+            if tasks.is_empty() {
+                println!("No tasks.");
+            }
+            for task in tasks {
+                println!("{}", task.line());
+            }
+        }
+        TasksCommand::Add {
+            title,
+            due,
+            tags,
+            notes,
+            channel,
+            to,
+        } => {
+            let due = due.as_deref().map(parse_due).transpose()?;
+            let mut task = store.add(NewTask {
+                title,
+                due,
+                tags,
+                notes: notes.unwrap_or_default(),
+            })?;
+            if let (Some(channel), Some(to)) = (channel, to) {
+                let cron = cron().await?;
+                schedule_reminder(&cron, &mut task, &channel, &to).await?;
+                cron.stop().await;
+                store.save(&task)?;
+            }
+            println!("Added {}", task.line());
+        }
+        TasksCommand::Done { id } => {
+            let mut task = store.complete(id)?;
+            if task.reminder_job.is_some() {
+                let cron = cron().await?;
+                cancel_reminder(&cron, &mut task).await?;
+                cron.stop().await;
+                store.save(&task)?;
+            }
+            println!("Completed {}", task.line());
+        }
+        TasksCommand::Remove { id } => {
+            let mut task = store.remove(id)?;
+            if task.reminder_job.is_some() {
+                let cron = cron().await?;
+                cancel_reminder(&cron, &mut task).await?;
+                cron.stop().await;
+            }
+            println!("Removed {}", task.line());
+        }
+    }
+    Ok(())
+}
+
+fn cmd_log(command: LogCommand) -> Result<()> {
+    let book = Logbook::new(&load_config(None).unwrap_or_default().workspace_path());
+    match command {
+        LogCommand::List { month, category } => {
+            let month = month.unwrap_or_else(this_month);
+            let entries = book
+                .entries()
+                .into_iter()
+                .filter(|entry| entry.time.format("%Y-%m").to_string() == month)
+                .filter(|entry| {
+                    category
+                        .as_deref()
+                        .is_none_or(|category| entry.category.eq_ignore_ascii_case(category))
+                })
+                .collect::<Vec<_>>();
+            if entries.is_empty() {
+                println!("No entries in {month}.");
+            }
+            for entry in entries {
+                println!("{}", entry.line());
+            }
+        }
+        LogCommand::Summary { month, category } => {
+            let month = month.unwrap_or_else(this_month);
+            println!("{}", book.summarize(&month, category.as_deref())?.render());
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_contacts(command: ContactsCommand) -> Result<()> {
+    let store = ContactStore::new(&load_config(None).unwrap_or_default().workspace_path());
+    match command {
+        ContactsCommand::List { query } => {
+            let contacts = match query.as_deref() {
+                Some(query) => store.search(query),
+                None => store.list(),
+            };
+            if contacts.is_empty() {
+                println!("No contacts.");
+            }
+            for contact in contacts {
+                println!("{}", contact.line());
+            }
+        }
+        ContactsCommand::Birthdays { days } => {
+            let upcoming = store.upcoming_birthdays(chrono::Local::now().date_naive(), days);
+            if upcoming.is_empty() {
+                println!("No birthdays in the next {days} days.");
+            }
+            for (date, contact) in upcoming {
+                println!("{} {}", date.format("%Y-%m-%d"), contact.line());
+            }
+        }
+        ContactsCommand::Remove { name } => {
+            let mut contact = store.remove(&name)?;
+            if contact.reminder_job.is_some() {
+                let cron = CronService::new(get_data_path()?.join("cron").join("jobs.json"));
+                cron.start().await?;
+                cancel_birthday_reminder(&cron, &mut contact).await?;
+                cron.stop().await;
+            }
+            println!("Removed {}", contact.line());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "voice")]
+async fn cmd_voice(session: Option<String>) -> Result<()> {
+    let config = load_config(None).unwrap_or_default();
+    let session = session.unwrap_or_else(|| config.voice.session.clone());
+    let (agent, _) = workflow_cli_runner(&config)?;
+    nanobot::voice::run_voice(&config, &agent, &session).await
+}
+
+#[cfg(not(feature = "voice"))]
+async fn cmd_voice(_session: Option<String>) -> Result<()> {
+    Err(anyhow!(
+        "voice mode is not compiled in; rebuild with `cargo build --release --features voice`"
+    ))
+}
+
+async fn cmd_workflows(command: WorkflowsCommand) -> Result<()> {
+    let config = load_config(None).unwrap_or_default();
+    let workspace = config.workspace_path();
+    match command {
+        WorkflowsCommand::List => {
+            let workflows = load_workflows(&workspace);
+            if workflows.is_empty() {
+                println!("No workflows in {}", workflows_dir(&workspace).display());
+            }
+            for workflow in workflows {
+                let mut triggers = Vec::new();
+                if let Some(expr) = &workflow.trigger.cron {
+                    triggers.push(format!("cron {expr}"));
+                }
+                if let Some(seconds) = workflow.trigger.every_seconds {
+                    triggers.push(format!("every {seconds}s"));
+                }
+                if workflow.trigger.webhook {
+                    triggers.push(format!("POST /workflows/{}", workflow.name));
+                }
+                println!(
+                    "{}  ({} steps)  {}",
+                    workflow.name,
+                    workflow.steps.len(),
+                    triggers.join(", ")
+                );
+                if !workflow.description.is_empty() {
+                    println!("    {}", workflow.description);
+                }
+            }
+        }
+        WorkflowsCommand::Runs { limit } => {
+            let runner = WorkflowRunner::new(workspace, workflow_runs_dir()?);
+            let runs = runner.list_runs(limit);
+            if runs.is_empty() {
+                println!("No workflow runs yet.");
+            }
+            for run in &runs {
+                print_workflow_run(run);
+                if let Some(error) = &run.error {
+                    println!("    {error}");
+                }
+            }
+        }
+        WorkflowsCommand::Run { name, input } => {
+            let input = match input {
+                Some(raw) => serde_json::from_str::<serde_json::Value>(&raw)
+                    .context("--input must be a JSON object")?,
+                None => serde_json::json!({}),
+            };
+            let (_agent, runner) = workflow_cli_runner(&config)?;
+            finish_workflow_run(&runner.run(&name, input, "manual").await?)?;
+        }
+        WorkflowsCommand::Resume { run_id } => {
+            let (_agent, runner) = workflow_cli_runner(&config)?;
+            finish_workflow_run(&runner.resume(&run_id).await?)?;
+        }
+    }
+    Ok(())
+}
+
+async fn cmd_eval(suite_path: &Path, record: Option<&Path>, replay: Option<&Path>) -> Result<()> {
+    if record.is_some() && replay.is_some() {
+        return Err(anyhow!("--record cannot be used with --replay"));
+    }
+    let suite = load_suite(suite_path)?;
+    let config = load_config(None).unwrap_or_default();
+    let model = suite
+        .model
+        .clone()
+        .unwrap_or_else(|| config.agents.defaults.model.clone());
+    let provider = match replay {
+        Some(path) => Arc::new(EvalProvider::replay(Cassette::load(path)?, &model)),
+        None => {
+            let api_key = config
+                .get_api_key(Some(&model))
+                .ok_or_else(|| anyhow!("no API key configured for {model}; use --replay"))?;
+            Arc::new(EvalProvider::live(build_provider(&config, &model, api_key)))
+        }
+    };
+
+    // Eval sessions live in a scratch directory so they never mix with real chats.
+    let scratch = std::env::temp_dir().join(format!("nanobot-eval-{}", uuid::Uuid::new_v4()));
+    let sessions = SessionManager::with_dir(scratch.join("sessions"))?;
+    // Eval runs leave the budget alone; the suite reports its own cost.
+    let mut builder = AgentLoopBuilder::from_config(provider.clone(), &config)
+        .without_budget()
+        .bus(Arc::new(MessageBus::new(16)))
+        .model(model.clone())
+        .sessions(Arc::new(sessions));
+    if replay.is_some() {
+        // Replayed tool calls run for real, so keep them off the user's files.
+        let workspace = scratch.join("workspace");
+        fs::create_dir_all(&workspace)?;
+        builder = builder.workspace(workspace).restrict_to_workspace(true);
+    }
+    let agent = builder.build()?;
+
+    println!(
+        "Suite {} ({} cases, model {model})",
+        suite.name,
+        suite.cases.len()
+    );
+    let results = run_suite(&suite, &agent, &provider).await;
+    let _ = fs::remove_dir_all(&scratch);
+
+    let mut total = TokenUsage::default();
+    for result in &results {
+        total.add(result.usage);
+        let status = if result.passed() { "PASS" } else { "FAIL" };
+        println!(
+            "  {status}  {}  ({} tokens)",
+            result.name,
+            result.usage.total()
+        );
+        for failure in &result.failures {
+            println!("        - {failure}");
+        }
+        if !result.passed() && !result.response.is_empty() {
+            let preview = result.response.chars().take(200).collect::<String>();
+            println!("        answer: {}", preview.replace('\n', " "));
+        }
+    }
+    let failed = results.iter().filter(|r| !r.passed()).count();
+    println!(
+        "{} passed, {failed} failed; tokens: {} prompt + {} completion",
+        results.len() - failed,
+        total.prompt_tokens,
+        total.completion_tokens
+    );
+    if let Some(pricing) = &suite.pricing {
+        println!("Estimated cost: ${:.4}", total.cost(pricing));
+    }
+
+    if let Some(path) = record {
+        provider.cassette().save(path)?;
+        println!("Recorded responses to {}", path.display());
+    }
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} eval cases failed", results.len()));
+    }
+    Ok(())
+}
+
+async fn cmd_tools(command: ToolsCommand) -> Result<()> {
+    let config = load_config(None).unwrap_or_default();
+    let model = config.agents.defaults.model.clone();
+    let api_key = config
+        .get_api_key(Some(&model))
+        .unwrap_or_else(|| "dummy".to_string());
+    let provider = build_provider(&config, &model, api_key);
+    let cron_store_path = get_data_path()?.join("cron").join("jobs.json");
+    let mut builder = AgentLoopBuilder::from_config(provider, &config)
+        .cron(Arc::new(CronService::new(cron_store_path)));
+    if config.tools.approval.enabled {
+        let gate = ApprovalGate::new(config.tools.approval.tools.clone())
+            .with_prompt("cli", Arc::new(CliApprovalPrompt));
+        builder = builder.approval_gate(Arc::new(gate));
+    }
+    let agent = builder.build()?;
+    let tools = agent.tools();
+
+    match command {
+        ToolsCommand::List { json } => {
+            let mut names = tools.tool_names();
+            names.sort();
+            if json {
+                let schemas = names
+                    .iter()
+                    .filter_map(|name| tools.get(name))
+                    .map(|tool| tool.to_schema())
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&schemas)?);
+                return Ok(());
+            }
+            let width = names.iter().map(String::len).max().unwrap_or(0);
+            for info in tools.list() {
+                let summary = info.description.lines().next().unwrap_or("");
+                let tags = if info.tags.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", info.tags.join(", "))
+                };
+                println!("{:<width$}  {summary}{tags}", info.name);
+            }
+        }
+        ToolsCommand::Describe { name } => {
+            let tool = tools
+                .get(&name)
+                .ok_or_else(|| anyhow!("unknown tool: {name}"))?;
+            println!("{}", tool.name());
+            println!();
+            println!("{}", tool.description());
+            println!();
+            println!("Parameters:");
+            println!("{}", serde_json::to_string_pretty(&tool.parameters())?);
+        }
+        ToolsCommand::Test { name, args } => {
+            if !tools.has(&name) {
+                return Err(anyhow!("unknown tool: {name}"));
+            }
+            let params = match serde_json::from_str::<serde_json::Value>(&args)? {
+                serde_json::Value::Object(map) => map,
+                _ => return Err(anyhow!("tool arguments must be a JSON object")),
+            };
+            println!("{}", agent.run_tool("cli", "direct", &name, &params).await);
+        }
+        ToolsCommand::Stats { days } => cmd_tool_stats(days)?,
+    }
+    Ok(())
+}
+
+fn cmd_tool_stats(days: i64) -> Result<()> {
+    let since = chrono::Local::now() - chrono::Duration::days(days.max(0));
+    let records = tool_stats::load_records(&tool_stats::tool_stats_path()?, Some(since));
+    if records.is_empty() {
+        println!("No tool calls recorded in the last {days} days.");
+        return Ok(());
+    }
+    let stats = tool_stats::summarize(&records);
+    let width = stats.iter().map(|s| s.tool.len()).max().unwrap_or(4).max(4);
+    println!(
+        "{:<width$} {:>6} {:>6} {:>7} {:>8} {:>8}  LAST ERROR",
+        "TOOL", "CALLS", "FAILED", "ERRORS", "p50", "p95"
+    );
+    for tool in stats {
+        println!(
+            "{:<width$} {:>6} {:>6} {:>6.0}% {:>6}ms {:>6}ms  {}",
+            tool.tool,
+            tool.calls,
+            tool.failures,
+            tool.error_rate() * 100.0,
+            tool.p50_ms,
+            tool.p95_ms,
+            tool.last_error.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+fn cmd_show(
+    session: &str,
+    expand_tools: bool,
+    since: Option<chrono::NaiveDate>,
+    no_pager: bool,
+) -> Result<()> {
+    let sessions = SessionManager::new()?;
+    if !sessions
+        .list_session_keys()?
+        .iter()
+        .any(|key| key == session)
+    {
+        return Err(anyhow!("session not found: {session}"));
+    }
+    let loaded = sessions.load_session(session)?;
+    let interactive = std::io::stdout().is_terminal();
+    let options = TranscriptOptions {
+        color: terminal::color_stdout(),
+        expand_tools,
+        since,
+    };
+    let text = render_transcript(&loaded, &options);
+    if no_pager || !interactive || !page_output(&text) {
+        println!("{text}");
+    }
+    Ok(())
+}
+
+/// Pipes text through `$PAGER` (default `less -R`, `more` on Windows).
+/// Returns false when no pager could be started.
+fn page_output(text: &str) -> bool {
+    use std::io::Write;
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| {
+        if cfg!(windows) {
+            "more".to_string()
+        } else {
+            "less -R".to_string()
+        }
+    });
+    let mut parts = pager.split_whitespace();
+    let Some(program) = parts.next() else {
+        return false;
+    };
+    let Ok(mut child) = std::process::Command::new(program)
+        .args(parts)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+    else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's fine.
+        let _ = writeln!(stdin, "{text}");
+    }
+    let _ = child.wait();
+    true
+}
+
 async fn cmd_channels_login() -> Result<()> {
     let config = load_config(None).unwrap_or_default();
     let bridge_dir = prepare_bridge_dir().await?;
@@ -1302,6 +3785,7 @@ async fn cmd_cron(command: CronCommand) -> Result<()> {
             deliver,
             to,
             channel,
+            tier,
         } => {
             let schedule = if let Some(every) = every {
                 CronSchedule {
@@ -1313,6 +3797,7 @@ async fn cmd_cron(command: CronCommand) -> Result<()> {
                 CronSchedule {
                     kind: "cron".to_string(),
                     expr: Some(expr),
+                    tz: Some("local".to_string()),
                     ..Default::default()
                 }
             } else if let Some(at) = at {
@@ -1327,8 +3812,16 @@ async fn cmd_cron(command: CronCommand) -> Result<()> {
                 return Err(anyhow!("Must specify --every, --cron, or --at"));
             };
 
+            let payload = CronPayload {
+                message,
+                deliver,
+                channel,
+                to,
+                tier,
+                ..Default::default()
+            };
             let job = cron
-                .add_job(name, schedule, message, deliver, channel, to, false)
+                .add_job_with_payload(name, schedule, payload, false)
                 .await?;
             println!("Added job '{}' ({})", job.name, job.id);
         }
@@ -1357,7 +3850,7 @@ async fn cmd_cron(command: CronCommand) -> Result<()> {
             let normalized_model = model.strip_prefix("litellm/").unwrap_or(&model);
             let is_bedrock = normalized_model.starts_with("bedrock/");
             let api_key = config.get_api_key(Some(&model));
-            if api_key.is_none() && !is_bedrock {
+            if api_key.is_none() && !is_bedrock && !mock::replaying() {
                 return Err(anyhow!(
                     "No API key configured. Set one in ~/.nanobot/config.json under providers.*.apiKey"
                 ));
@@ -1371,49 +3864,68 @@ async fn cmd_cron(command: CronCommand) -> Result<()> {
             );
             let session_manager = Arc::new(SessionManager::new()?);
             let channels = Arc::new(ChannelManager::new(&config, bus.clone()));
-            let agent = Arc::new(AgentLoop::new(
-                bus.clone(),
-                provider,
-                config.workspace_path(),
-                Some(model),
-                config.agents.defaults.max_tool_iterations,
-                config.agents.defaults.memory_window,
-                config.tools.web.search.clone(),
-                config.tools.exec.timeout,
-                config.tools.restrict_to_workspace,
-                Some(cron.clone()),
-                Some(session_manager),
-            )?);
+            let workflows = Arc::new(
+                WorkflowRunner::new(config.workspace_path(), workflow_runs_dir()?)
+                    .with_approval(Arc::new(CliApprovalPrompt)),
+            );
+            let agent = Arc::new(
+                AgentLoopBuilder::from_config(provider, &config)
+                    .bus(bus.clone())
+                    .cron(cron.clone())
+                    .sessions(session_manager)
+                    .workflows(workflows.clone())
+                    .build()?,
+            );
+            workflows.attach(&agent);
 
             let bus_for_cron = bus.clone();
             let agent_for_cron = agent.clone();
             let channels_for_cron = channels.clone();
+            let config_for_cron = Arc::new(config.clone());
             cron.set_on_job(Arc::new(move |job| {
+                let config = config_for_cron.clone();
                 let bus = bus_for_cron.clone();
                 let agent = agent_for_cron.clone();
                 let channels = channels_for_cron.clone();
+                let workflows = workflows.clone();
                 Box::pin(async move {
-                    let response = agent
-                        .process_direct(
-                            &job.payload.message,
-                            Some(&format!("cron:{}", job.id)),
+                    if job.payload.kind == maintenance::CRON_PAYLOAD_KIND {
+                        return maintenance::run_scheduled(&config).await;
+                    }
+                    if job.payload.kind == CRON_PAYLOAD_KIND {
+                        return run_workflow_job(&workflows, &job.payload.message).await;
+                    }
+                    let response = if job.payload.kind == briefing::CRON_PAYLOAD_KIND {
+                        run_briefing(
+                            &agent,
                             job.payload.channel.as_deref(),
                             job.payload.to.as_deref(),
                         )
-                        .await?;
+                        .await?
+                    } else {
+                        agent
+                            .process_direct(
+                                &job.payload.message,
+                                Some(&format!("cron:{}", job.id)),
+                                job.payload.channel.as_deref(),
+                                job.payload.to.as_deref(),
+                            )
+                            .await?
+                    };
 
                     if job.payload.deliver
-                        && let (Some(channel), Some(to)) =
-                            (job.payload.channel.clone(), job.payload.to.clone())
+                        && let (Some(channel), Some(to)) = (&job.payload.channel, &job.payload.to)
                     {
-                        let outbound = OutboundMessage::new(channel.clone(), to, response.clone());
-                        if channel == "cli" {
-                            println!("nanobot-rs[cron]: {response}");
-                        } else if let Some(adapter) = channels.get_channel(&channel) {
-                            adapter.send(&outbound).await?;
-                        } else {
-                            bus.publish_outbound(outbound).await?;
-                        }
+                        deliver_job_output(
+                            &config,
+                            Some(&channels),
+                            &bus,
+                            &outbox::job_key(&job),
+                            channel,
+                            to,
+                            &response,
+                        )
+                        .await?;
                     }
                     Ok(Some(response))
                 })
@@ -0,0 +1,598 @@
+//! Nightly housekeeping so a long-running install does not fill the disk:
+//! old rotated logs, cached media and diagnostic bundles are deleted, idle
+//! sessions are archived and old archives dropped, exec scratch directories
+//! of gone or idle sessions are removed, usage records are trimmed, and the
+//! config, cron jobs and memory are backed up.
+//!
+//! The gateway schedules it as a `maintenance` cron job at `maintenance.time`
+//! (local time); `nanobot maintenance` runs it on demand.
+
+use crate::briefing::daily_schedule;
+use crate::config::{Config, MaintenanceConfig, get_config_path};
+use crate::cron::{CronPayload, CronService};
+use crate::utils::{get_data_path, get_home_data_path};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+use zip::write::SimpleFileOptions;
+
+pub const CRON_PAYLOAD_KIND: &str = "maintenance";
+
+const DAY: Duration = Duration::from_secs(86_400);
+
+/// Where the maintained files live.
+#[derive(Debug, Clone)]
+pub struct MaintenancePaths {
+    pub data_dir: PathBuf,
+    pub config_file: PathBuf,
+    pub workspace: PathBuf,
+    pub log_file: PathBuf,
+    pub media_dir: PathBuf,
+}
+
+impl MaintenancePaths {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self {
+            data_dir: get_data_path()?,
+            config_file: get_config_path()?,
+            workspace: config.workspace_path(),
+            log_file: config.log_file_path(),
+            media_dir: get_home_data_path()?.join("media"),
+        })
+    }
+
+    fn sessions_dir(&self) -> PathBuf {
+        self.data_dir.join("sessions")
+    }
+
+    fn backups_dir(&self) -> PathBuf {
+        self.data_dir.join("backups")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TaskReport {
+    pub name: &'static str,
+    /// Files deleted, archived or records dropped.
+    pub affected: usize,
+    pub freed_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MaintenanceReport {
+    pub dry_run: bool,
+    pub tasks: Vec<TaskReport>,
+}
+
+impl fmt::Display for MaintenanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for task in &self.tasks {
+            match &task.error {
+                Some(error) => writeln!(f, "{:<9} failed: {error}", task.name)?,
+                None => writeln!(
+                    f,
+                    "{:<9} {} item(s), {:.1} MB",
+                    task.name,
+                    task.affected,
+                    task.freed_bytes as f64 / 1_048_576.0
+                )?,
+            }
+        }
+        if self.dry_run {
+            write!(f, "(dry run: nothing was changed)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every task enabled in `config`; a failing task does not stop the rest.
+pub fn run(
+    config: &MaintenanceConfig,
+    paths: &MaintenancePaths,
+    dry_run: bool,
+) -> MaintenanceReport {
+    let mut tasks = Vec::new();
+    let mut record = |name: &'static str, result: Result<(usize, u64)>| {
+        let report = match result {
+            Ok((affected, freed_bytes)) => TaskReport {
+                name,
+                affected,
+                freed_bytes,
+                error: None,
+            },
+            Err(err) => {
+                warn!(task = name, "maintenance task failed: {err:#}");
+                TaskReport {
+                    name,
+                    error: Some(format!("{err:#}")),
+                    ..Default::default()
+                }
+            }
+        };
+        tasks.push(report);
+    };
+    if config.log_days > 0 {
+        record("logs", prune_logs(paths, config.log_days, dry_run));
+    }
+    if config.cache_days > 0 {
+        record("caches", prune_caches(paths, config.cache_days, dry_run));
+    }
+    if config.session_days > 0 || config.archive_days > 0 {
+        record(
+            "sessions",
+            retain_sessions(paths, config.session_days, config.archive_days, dry_run),
+        );
+    }
+    record(
+        "sandboxes",
+        prune_sandboxes(paths, config.session_days, dry_run),
+    );
+    if config.usage_days > 0 {
+        record("usage", compact_usage(paths, config.usage_days, dry_run));
+    }
+    if config.backup {
+        record("backup", backup(paths, config.backup_keep, dry_run));
+    }
+    let report = MaintenanceReport { dry_run, tasks };
+    info!(dry_run, "maintenance finished");
+    report
+}
+
+fn older_than(path: &Path, days: u64) -> bool {
+    let cutoff = SystemTime::now()
+        .checked_sub(DAY * days as u32)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|modified| modified < cutoff)
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Deletes `paths`, returning how many went and the bytes freed.
+fn remove_files(paths: Vec<PathBuf>, dry_run: bool) -> Result<(usize, u64)> {
+    let mut freed = 0;
+    for path in &paths {
+        freed += file_size(path);
+        if !dry_run {
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to delete {}", path.display()))?;
+        }
+    }
+    Ok((paths.len(), freed))
+}
+
+/// Rotated log files (`nanobot.log.1`, ...) not written to for `days`.
+fn prune_logs(paths: &MaintenancePaths, days: u64, dry_run: bool) -> Result<(usize, u64)> {
+    let Some(dir) = paths.log_file.parent() else {
+        return Ok((0, 0));
+    };
+    let Some(name) = paths.log_file.file_name().and_then(|n| n.to_str()) else {
+        return Ok((0, 0));
+    };
+    let prefix = format!("{name}.");
+    let old = files_in(dir)
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix(&prefix))
+                .is_some_and(|index| index.chars().all(|c| c.is_ascii_digit()))
+        })
+        .filter(|path| older_than(path, days))
+        .collect();
+    remove_files(old, dry_run)
+}
+
+/// Downloaded channel media, diagnostic bundles and cached responses older
+/// than `days`.
+fn prune_caches(paths: &MaintenancePaths, days: u64, dry_run: bool) -> Result<(usize, u64)> {
+    let old = [
+        paths.media_dir.clone(),
+        paths.workspace.join("diagnostics"),
+        paths.data_dir.join("cache").join("responses"),
+    ]
+    .iter()
+    .flat_map(|dir| files_in(dir))
+    .filter(|path| older_than(path, days))
+    .collect();
+    remove_files(old, dry_run)
+}
+
+/// Moves sessions idle for `session_days` to `sessions/archive/` and deletes
+/// archived transcripts older than `archive_days` (0 keeps them).
+fn retain_sessions(
+    paths: &MaintenancePaths,
+    session_days: u64,
+    archive_days: u64,
+    dry_run: bool,
+) -> Result<(usize, u64)> {
+    let sessions = paths.sessions_dir();
+    let archive = sessions.join("archive");
+    let mut affected = 0;
+    let mut freed = 0;
+    if archive_days > 0 {
+        let old = files_in(&archive)
+            .into_iter()
+            .filter(|path| older_than(path, archive_days))
+            .collect();
+        (affected, freed) = remove_files(old, dry_run)?;
+    }
+    if session_days > 0 {
+        let idle = files_in(&sessions)
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .filter(|path| older_than(path, session_days))
+            .collect::<Vec<_>>();
+        affected += idle.len();
+        if dry_run || idle.is_empty() {
+            return Ok((affected, freed));
+        }
+        std::fs::create_dir_all(&archive)?;
+        let stamp = Local::now().format("%Y%m%d%H%M%S%3f");
+        for path in idle {
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("session");
+            let target = archive.join(format!("{stem}.{stamp}.jsonl"));
+            std::fs::rename(&path, &target)
+                .with_context(|| format!("failed to archive {}", path.display()))?;
+            // Archive retention counts from the move, not the last message.
+            std::fs::File::options()
+                .write(true)
+                .open(&target)
+                .and_then(|file| file.set_modified(SystemTime::now()))?;
+        }
+    }
+    Ok((affected, freed))
+}
+
+/// Total size and latest modification of the files under `dir`.
+fn tree_stats(dir: &Path) -> (u64, SystemTime) {
+    let mut size = 0;
+    let mut newest = std::fs::metadata(dir)
+        .and_then(|meta| meta.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let (entry_size, modified) = if path.is_dir() {
+            tree_stats(&path)
+        } else {
+            let meta = entry.metadata().ok();
+            (
+                meta.as_ref().map_or(0, |meta| meta.len()),
+                meta.and_then(|meta| meta.modified().ok())
+                    .unwrap_or(SystemTime::UNIX_EPOCH),
+            )
+        };
+        size += entry_size;
+        newest = newest.max(modified);
+    }
+    (size, newest)
+}
+
+/// Deletes `exec` scratch directories (`<workspace>/sandbox/<session>/`)
+/// untouched for `session_days`, or for a day once their session is gone.
+fn prune_sandboxes(
+    paths: &MaintenancePaths,
+    session_days: u64,
+    dry_run: bool,
+) -> Result<(usize, u64)> {
+    let sessions = paths.sessions_dir();
+    let (mut affected, mut freed) = (0, 0);
+    let dirs = std::fs::read_dir(paths.workspace.join(crate::tools::shell::SANDBOX_DIR))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir());
+    for dir in dirs {
+        let Some(name) = dir.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let (size, modified) = tree_stats(&dir);
+        let idle_days = SystemTime::now()
+            .duration_since(modified)
+            .map_or(0, |idle| idle.as_secs() / DAY.as_secs());
+        let orphaned = !sessions.join(format!("{name}.jsonl")).exists();
+        if !((orphaned && idle_days >= 1) || (session_days > 0 && idle_days >= session_days)) {
+            continue;
+        }
+        affected += 1;
+        freed += size;
+        if !dry_run {
+            std::fs::remove_dir_all(&dir)
+                .with_context(|| format!("failed to delete {}", dir.display()))?;
+        }
+    }
+    Ok((affected, freed))
+}
+
+/// The timestamp every `usage.jsonl` and `tool_stats.jsonl` record carries.
+#[derive(Deserialize)]
+struct Stamped {
+    at: DateTime<Local>,
+}
+
+/// Drops `usage.jsonl` and `tool_stats.jsonl` records older than `days`.
+fn compact_usage(paths: &MaintenancePaths, days: u64, dry_run: bool) -> Result<(usize, u64)> {
+    let since = Local::now() - ChronoDuration::days(days as i64);
+    let (mut dropped, mut freed) = (0, 0);
+    for name in ["usage.jsonl", "tool_stats.jsonl"] {
+        let path = paths.data_dir.join(name);
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let kept = text
+            .lines()
+            .filter(|line| {
+                serde_json::from_str::<Stamped>(line).is_ok_and(|record| record.at >= since)
+            })
+            .collect::<Vec<_>>();
+        let removed = text.lines().count() - kept.len();
+        if removed == 0 {
+            continue;
+        }
+        let mut compacted = kept.join("\n");
+        if !compacted.is_empty() {
+            compacted.push('\n');
+        }
+        dropped += removed;
+        freed += (text.len() - compacted.len()) as u64;
+        if !dry_run {
+            let tmp = path.with_extension("jsonl.tmp");
+            std::fs::write(&tmp, compacted)?;
+            std::fs::rename(&tmp, &path)?;
+        }
+    }
+    Ok((dropped, freed))
+}
+
+/// Zips the config, cron jobs, API keys and the workspace's memory, tasks
+/// and instruction files into `backups/`, keeping the newest `keep`.
+fn backup(paths: &MaintenancePaths, keep: usize, dry_run: bool) -> Result<(usize, u64)> {
+    let mut entries: Vec<(String, PathBuf)> = Vec::new();
+    if paths.config_file.is_file() {
+        entries.push(("config.json".to_string(), paths.config_file.clone()));
+    }
+    for name in ["apikeys.json", "cron/jobs.json"] {
+        let path = paths.data_dir.join(name);
+        if path.is_file() {
+            entries.push((name.to_string(), path));
+        }
+    }
+    for path in files_in(&paths.workspace) {
+        if path.extension().is_some_and(|ext| ext == "md") {
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            entries.push((format!("workspace/{name}"), path.clone()));
+        }
+    }
+    for dir in ["memory", "tasks"] {
+        for path in walk(&paths.workspace.join(dir)) {
+            let relative = path
+                .strip_prefix(&paths.workspace)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            entries.push((format!("workspace/{relative}"), path));
+        }
+    }
+
+    let dir = paths.backups_dir();
+    let mut backups = files_in(&dir)
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("backup-") && n.ends_with(".zip"))
+        })
+        .collect::<Vec<_>>();
+    backups.sort();
+    if dry_run {
+        return Ok((entries.len(), 0));
+    }
+
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "backup-{}.zip",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let file = std::fs::File::create(&path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+    for (name, source) in &entries {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(&std::fs::read(source)?)?;
+    }
+    zip.finish()?;
+    backups.push(path);
+
+    let excess = backups.len().saturating_sub(keep.max(1));
+    let (_, freed) = remove_files(backups.drain(..excess).collect(), false)?;
+    Ok((entries.len(), freed))
+}
+
+fn walk(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(walk(&path));
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    files
+}
+
+/// Keeps exactly one `maintenance` cron job at `config.time`, or none when disabled.
+pub async fn sync_maintenance_job(cron: &CronService, config: &MaintenanceConfig) -> Result<()> {
+    let wanted = if config.enabled {
+        Some(daily_schedule(&config.time)?)
+    } else {
+        None
+    };
+    let mut found = false;
+    for job in cron.list_jobs(true).await {
+        if job.payload.kind != CRON_PAYLOAD_KIND {
+            continue;
+        }
+        let current = wanted.as_ref().is_some_and(|schedule| {
+            !found && job.schedule.expr == schedule.expr && job.schedule.tz == schedule.tz
+        });
+        if current {
+            found = true;
+        } else {
+            cron.remove_job(&job.id).await?;
+        }
+    }
+    if let (Some(schedule), false) = (wanted, found) {
+        let payload = CronPayload {
+            kind: CRON_PAYLOAD_KIND.to_string(),
+            ..Default::default()
+        };
+        cron.add_job_with_payload("maintenance".to_string(), schedule, payload, false)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Cron callback body for the `maintenance` job.
+pub async fn run_scheduled(config: &Config) -> Result<Option<String>> {
+    let paths = MaintenancePaths::from_config(config)?;
+    let maintenance = config.maintenance.clone();
+    let report = tokio::task::spawn_blocking(move || run(&maintenance, &paths, false)).await?;
+    Ok(Some(report.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::UsageRecord;
+
+    fn age(path: &Path, days: u64) {
+        let file = std::fs::File::options()
+            .write(true)
+            .open(path)
+            .expect("open");
+        file.set_modified(SystemTime::now() - DAY * days as u32)
+            .expect("set mtime");
+    }
+
+    #[test]
+    fn prunes_archives_and_backs_up() {
+        let root = std::env::temp_dir().join(format!("nanobot-maint-{}", uuid::Uuid::new_v4()));
+        let paths = MaintenancePaths {
+            data_dir: root.join("data"),
+            config_file: root.join("data/config.json"),
+            workspace: root.join("workspace"),
+            log_file: root.join("workspace/logs/nanobot.log"),
+            media_dir: root.join("media"),
+        };
+        for dir in [
+            "data/sessions",
+            "workspace/logs",
+            "workspace/memory",
+            "media",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).expect("dir");
+        }
+        let write = |rel: &str, days: u64| {
+            let path = root.join(rel);
+            std::fs::write(&path, "x".repeat(100)).expect("write");
+            age(&path, days);
+            path
+        };
+        write("workspace/logs/nanobot.log", 30);
+        let old_log = write("workspace/logs/nanobot.log.1", 30);
+        let new_log = write("workspace/logs/nanobot.log.2", 1);
+        let old_media = write("media/photo.jpg", 60);
+        let idle = write("data/sessions/telegram_1.jsonl", 400);
+        let active = write("data/sessions/telegram_2.jsonl", 1);
+        write("data/config.json", 0);
+        write("workspace/memory/MEMORY.md", 0);
+        std::fs::create_dir_all(root.join("workspace/sandbox/telegram_1")).expect("dir");
+        std::fs::create_dir_all(root.join("workspace/sandbox/telegram_2")).expect("dir");
+        write("workspace/sandbox/telegram_1/out.csv", 400);
+        write("workspace/sandbox/telegram_2/out.csv", 1);
+        std::fs::File::open(root.join("workspace/sandbox/telegram_1"))
+            .and_then(|dir| dir.set_modified(SystemTime::now() - DAY * 400))
+            .expect("set mtime");
+        let old = UsageRecord {
+            at: Local::now() - ChronoDuration::days(500),
+            model: "m".to_string(),
+            prompt_tokens: 1,
+            completion_tokens: 1,
+            ttft_ms: 1,
+            total_ms: 1,
+            session: String::new(),
+            channel: String::new(),
+            user: String::new(),
+            cost_usd: None,
+        };
+        let recent = UsageRecord {
+            at: Local::now(),
+            ..old.clone()
+        };
+        std::fs::write(
+            paths.data_dir.join("usage.jsonl"),
+            format!(
+                "{}\n{}\n",
+                serde_json::to_string(&old).unwrap(),
+                serde_json::to_string(&recent).unwrap()
+            ),
+        )
+        .expect("usage");
+
+        let config = MaintenanceConfig::default();
+        let dry = run(&config, &paths, true);
+        assert!(dry.tasks.iter().all(|task| task.error.is_none()));
+        assert!(old_log.exists() && idle.exists());
+
+        let report = run(&config, &paths, false);
+        let affected = |name: &str| {
+            report
+                .tasks
+                .iter()
+                .find(|task| task.name == name)
+                .map(|task| task.affected)
+        };
+        assert_eq!(affected("logs"), Some(1));
+        assert_eq!(affected("caches"), Some(1));
+        assert_eq!(affected("sessions"), Some(1));
+        assert_eq!(affected("usage"), Some(1));
+        assert_eq!(affected("sandboxes"), Some(1));
+        assert_eq!(affected("backup"), Some(2));
+        assert!(!old_log.exists() && new_log.exists() && !old_media.exists());
+        assert!(!idle.exists() && active.exists());
+        assert!(!root.join("workspace/sandbox/telegram_1").exists());
+        assert!(root.join("workspace/sandbox/telegram_2").exists());
+        assert_eq!(files_in(&paths.sessions_dir().join("archive")).len(), 1);
+        assert_eq!(files_in(&paths.backups_dir()).len(), 1);
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
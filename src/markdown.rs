@@ -0,0 +1,182 @@
+use regex::Regex;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{LinesWithEndings, as_24_bit_terminal_escaped};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        themes
+            .remove("base16-ocean.dark")
+            .or_else(|| themes.into_values().next())
+            .unwrap_or_default()
+    })
+}
+
+struct InlinePatterns {
+    code: Regex,
+    bold: Regex,
+    italic: Regex,
+}
+
+fn inline_patterns() -> &'static InlinePatterns {
+    static PATTERNS: OnceLock<InlinePatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| InlinePatterns {
+        code: Regex::new(r"`([^`]+)`").expect("code regex"),
+        bold: Regex::new(r"\*\*([^*]+)\*\*|__([^_]+)__").expect("bold regex"),
+        italic: Regex::new(r"(^|[^*\w])\*([^*\s][^*]*)\*").expect("italic regex"),
+    })
+}
+
+fn render_inline(line: &str) -> String {
+    let patterns = inline_patterns();
+    // Inline code is swapped out first so emphasis markers inside it survive.
+    let mut spans = Vec::new();
+    let out = patterns.code.replace_all(line, |caps: &regex::Captures| {
+        spans.push(format!("{CYAN}{}{RESET}", &caps[1]));
+        format!("\u{0}{}\u{0}", spans.len() - 1)
+    });
+    let out = patterns.bold.replace_all(&out, |caps: &regex::Captures| {
+        let inner = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .map_or("", |m| m.as_str());
+        format!("{BOLD}{inner}{RESET}")
+    });
+    let mut out = patterns
+        .italic
+        .replace_all(&out, format!("${{1}}{ITALIC}${{2}}{RESET}").as_str())
+        .into_owned();
+    for (idx, span) in spans.iter().enumerate() {
+        out = out.replace(&format!("\u{0}{idx}\u{0}"), span);
+    }
+    out
+}
+
+pub fn highlight_code(code: &str, lang: &str) -> String {
+    let syntaxes = syntax_set();
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, theme());
+    let mut out = String::new();
+    for line in LinesWithEndings::from(code) {
+        match highlighter.highlight_line(line, syntaxes) {
+            Ok(ranges) => out.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => out.push_str(line),
+        }
+    }
+    let mut out = out.trim_end_matches('\n').to_string();
+    out.push_str(RESET);
+    out
+}
+
+/// Renders markdown for an ANSI terminal: headings, emphasis, inline code,
+/// lists, quotes and syntax-highlighted fenced code blocks.
+pub fn render_terminal(text: &str) -> String {
+    let mut out = Vec::new();
+    // (fence marker, language tag, buffered code)
+    let mut fence: Option<(&str, String, String)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if let Some((marker, lang, code)) = fence.as_mut() {
+            if trimmed.starts_with(*marker) {
+                out.push(highlight_code(code, lang));
+                fence = None;
+            } else {
+                code.push_str(line);
+                code.push('\n');
+            }
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            let lang = trimmed.trim_start_matches(['`', '~']).trim().to_string();
+            fence = Some((marker, lang, String::new()));
+            continue;
+        }
+
+        let heading_level = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+            let title = trimmed[heading_level..].trim();
+            out.push(format!("{BOLD}{UNDERLINE}{}{RESET}", render_inline(title)));
+            continue;
+        }
+        if let Some(rest) = trimmed.strip_prefix('>') {
+            let rest = rest.strip_prefix(' ').unwrap_or(rest);
+            out.push(format!("{DIM}│ {}{RESET}", render_inline(rest)));
+            continue;
+        }
+        if matches!(trimmed, "---" | "***" | "___") {
+            out.push(format!("{DIM}{}{RESET}", "─".repeat(40)));
+            continue;
+        }
+        let indent = &line[..line.len() - trimmed.len()];
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .or_else(|| trimmed.strip_prefix("+ "))
+        {
+            out.push(format!("{indent}  • {}", render_inline(item)));
+            continue;
+        }
+        out.push(render_inline(line));
+    }
+
+    // An unterminated fence still gets highlighted rather than dropped.
+    if let Some((_, lang, code)) = fence {
+        out.push(highlight_code(&code, &lang));
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_ansi(text: &str) -> String {
+        Regex::new(r"\x1b\[[0-9;]*m")
+            .expect("ansi regex")
+            .replace_all(text, "")
+            .into_owned()
+    }
+
+    #[test]
+    fn renders_emphasis_and_inline_code() {
+        let rendered = render_terminal("use **bold**, *italic* and `a*b*c`");
+        assert!(rendered.contains(&format!("{BOLD}bold{RESET}")));
+        assert!(rendered.contains(&format!("{ITALIC}italic{RESET}")));
+        assert!(rendered.contains(&format!("{CYAN}a*b*c{RESET}")));
+        assert_eq!(strip_ansi(&rendered), "use bold, italic and a*b*c");
+    }
+
+    #[test]
+    fn renders_headings_lists_and_quotes() {
+        let rendered = render_terminal("# Title\n- one\n  * two\n> note");
+        assert_eq!(strip_ansi(&rendered), "Title\n  • one\n    • two\n│ note");
+    }
+
+    #[test]
+    fn highlights_fenced_code_and_drops_fences() {
+        let rendered = render_terminal("before\n```rust\nfn main() {}\n```\nafter");
+        let plain = strip_ansi(&rendered);
+        assert_eq!(plain, "before\nfn main() {}\nafter");
+        assert!(rendered.contains("\x1b[38;2;"));
+        assert!(!plain.contains("```"));
+    }
+}
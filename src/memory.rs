@@ -1,11 +1,22 @@
-use crate::utils::ensure_dir;
-use std::path::PathBuf;
+use crate::utils::{ensure_dir, safe_filename, with_path_lock};
+use crate::vault::UserKey;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+tokio::task_local! {
+    /// Memory of the user the current turn runs for.
+    static USER_MEMORY: MemoryStore;
+}
 
 #[derive(Debug, Clone)]
 pub struct MemoryStore {
     pub memory_dir: PathBuf,
     pub memory_file: PathBuf,
     pub history_file: PathBuf,
+    /// Facts extracted from idle sessions, grouped by kind.
+    pub facts_file: PathBuf,
+    /// Seals the files for a multi-user session.
+    key: Option<UserKey>,
 }
 
 impl MemoryStore {
@@ -13,34 +24,292 @@ impl MemoryStore {
         let memory_dir = ensure_dir(&workspace.join("memory"))?;
         let memory_file = memory_dir.join("MEMORY.md");
         let history_file = memory_dir.join("HISTORY.md");
+        let facts_file = memory_dir.join("FACTS.md");
         Ok(Self {
             memory_dir,
             memory_file,
             history_file,
+            facts_file,
+            key: None,
+        })
+    }
+
+    /// The encrypted memory of user `scope` (`api:<profile>`), kept apart
+    /// from the workspace memory under `memory/users/`.
+    pub fn for_user(workspace: PathBuf, scope: &str, key: UserKey) -> std::io::Result<Self> {
+        let name = safe_filename(&scope.replace(':', "_"));
+        let memory_dir = ensure_dir(&workspace.join("memory").join("users").join(name))?;
+        Ok(Self {
+            memory_file: memory_dir.join("MEMORY.md.enc"),
+            history_file: memory_dir.join("HISTORY.md.enc"),
+            facts_file: memory_dir.join("FACTS.md.enc"),
+            memory_dir,
+            key: Some(key),
         })
     }
 
+    fn read(&self, path: &Path) -> String {
+        let raw = std::fs::read_to_string(path).unwrap_or_default();
+        match &self.key {
+            Some(key) if !raw.is_empty() => match key.open(&raw) {
+                Ok(plain) => String::from_utf8_lossy(&plain).into_owned(),
+                Err(err) => {
+                    tracing::warn!(path = %path.display(), "cannot read user memory: {err}");
+                    String::new()
+                }
+            },
+            _ => raw,
+        }
+    }
+
+    fn write(&self, path: &Path, content: &str) -> std::io::Result<()> {
+        match &self.key {
+            Some(key) => {
+                let sealed = key
+                    .seal(content.as_bytes())
+                    .map_err(std::io::Error::other)?;
+                std::fs::write(path, sealed)
+            }
+            None => std::fs::write(path, content),
+        }
+    }
+
     pub fn read_long_term(&self) -> String {
-        std::fs::read_to_string(&self.memory_file).unwrap_or_default()
+        self.read(&self.memory_file)
     }
 
     pub fn write_long_term(&self, content: &str) -> std::io::Result<()> {
-        std::fs::write(&self.memory_file, content)
+        with_path_lock(&self.memory_file, || self.write(&self.memory_file, content))
     }
 
     pub fn append_history(&self, entry: &str) -> std::io::Result<()> {
-        let mut existing = std::fs::read_to_string(&self.history_file).unwrap_or_default();
-        existing.push_str(entry.trim_end());
-        existing.push_str("\n\n");
-        std::fs::write(&self.history_file, existing)
+        with_path_lock(&self.history_file, || {
+            let mut existing = self.read(&self.history_file);
+            existing.push_str(entry.trim_end());
+            existing.push_str("\n\n");
+            self.write(&self.history_file, &existing)
+        })
+    }
+
+    pub fn read_facts(&self) -> String {
+        self.read(&self.facts_file)
+    }
+
+    /// Adds `facts` under their kind's heading, skipping any already recorded
+    /// (case-insensitively). Returns how many were new.
+    pub fn add_facts(&self, facts: &[Fact]) -> std::io::Result<usize> {
+        with_path_lock(&self.facts_file, || self.add_facts_locked(facts))
+    }
+
+    fn add_facts_locked(&self, facts: &[Fact]) -> std::io::Result<usize> {
+        let mut sections: Vec<(String, Vec<String>)> = FACT_KINDS
+            .iter()
+            .map(|(_, heading)| (heading.to_string(), Vec::new()))
+            .collect();
+        let mut current = None;
+        for line in self.read_facts().lines() {
+            if let Some(heading) = line.strip_prefix("## ") {
+                let heading = heading.trim();
+                current = Some(match sections.iter().position(|(h, _)| h == heading) {
+                    Some(idx) => idx,
+                    None => {
+                        sections.push((heading.to_string(), Vec::new()));
+                        sections.len() - 1
+                    }
+                });
+            } else if line.starts_with("- ")
+                && let Some(idx) = current
+            {
+                sections[idx].1.push(line.to_string());
+            }
+        }
+
+        let known = |sections: &[(String, Vec<String>)], text: &str| {
+            let text = text.to_lowercase();
+            sections
+                .iter()
+                .flat_map(|(_, entries)| entries)
+                .any(|entry| fact_text(entry).to_lowercase() == text)
+        };
+        let mut added = 0;
+        for fact in facts {
+            let text = fact.text.trim().replace('\n', " ");
+            if text.is_empty() || known(&sections, &text) {
+                continue;
+            }
+            let heading = FACT_KINDS
+                .iter()
+                .find(|(kind, _)| *kind == fact.kind)
+                .map_or("Facts", |(_, heading)| heading);
+            let idx = sections.iter().position(|(h, _)| h == heading).unwrap_or(1);
+            sections[idx]
+                .1
+                .push(format!("- {text} _({}, {})_", fact.source, fact.at));
+            added += 1;
+        }
+        if added == 0 {
+            return Ok(0);
+        }
+
+        let rendered = sections
+            .iter()
+            .filter(|(_, entries)| !entries.is_empty())
+            .map(|(heading, entries)| format!("## {heading}\n{}\n", entries.join("\n")))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.write(&self.facts_file, &rendered)?;
+        Ok(added)
     }
 
     pub fn get_memory_context(&self) -> String {
         let long_term = self.read_long_term();
-        if long_term.is_empty() {
-            String::new()
-        } else {
-            format!("## Long-term Memory\n{}", long_term)
+        let facts = self.read_facts();
+        let mut parts = Vec::new();
+        if !long_term.is_empty() {
+            parts.push(format!("## Long-term Memory\n{}", long_term));
+        }
+        if !facts.trim().is_empty() {
+            let facts = facts
+                .trim()
+                .lines()
+                .map(|line| match line.strip_prefix("## ") {
+                    Some(heading) => format!("### {heading}"),
+                    None => line.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            parts.push(format!("## Known Facts\n{facts}"));
+        }
+        parts.join("\n\n")
+    }
+}
+
+/// Fact kinds and the `FACTS.md` headings they are filed under.
+pub const FACT_KINDS: &[(&str, &str)] = &[
+    ("preference", "Preferences"),
+    ("fact", "Facts"),
+    ("decision", "Decisions"),
+];
+
+/// A durable fact, with where and when it was learned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fact {
+    /// One of the [`FACT_KINDS`]; anything else is filed as a fact.
+    pub kind: String,
+    pub text: String,
+    /// The session it came from.
+    pub source: String,
+    /// When it was said, `YYYY-MM-DD HH:MM`.
+    pub at: String,
+}
+
+/// The text of a `FACTS.md` entry without its provenance suffix.
+fn fact_text(entry: &str) -> &str {
+    let entry = entry.trim_start_matches("- ");
+    match entry.rfind(" _(") {
+        Some(idx) if entry.ends_with(")_") => &entry[..idx],
+        _ => entry,
+    }
+    .trim()
+}
+
+/// Runs `fut` with `store`, when set, as the memory the prompt shows.
+pub async fn with_user_memory<F: Future>(store: Option<MemoryStore>, fut: F) -> F::Output {
+    match store {
+        Some(store) => USER_MEMORY.scope(store, fut).await,
+        None => fut.await,
+    }
+}
+
+/// The memory of the user the current turn runs for, if any.
+pub fn user_memory() -> Option<MemoryStore> {
+    USER_MEMORY.try_with(Clone::clone).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facts_are_grouped_deduplicated_and_attributed() {
+        let workspace =
+            std::env::temp_dir().join(format!("nanobot-facts-{}", uuid::Uuid::new_v4()));
+        let store = MemoryStore::new(workspace.clone()).unwrap();
+        let fact = |kind: &str, text: &str| Fact {
+            kind: kind.to_string(),
+            text: text.to_string(),
+            source: "telegram:42".to_string(),
+            at: "2026-10-15 09:30".to_string(),
+        };
+
+        let added = store
+            .add_facts(&[
+                fact("preference", "Prefers metric units"),
+                fact("decision", "Deploys go out on Tuesdays"),
+                fact("fact", "Has a dog named Rex"),
+            ])
+            .unwrap();
+        assert_eq!(added, 3);
+        let again = store
+            .add_facts(&[
+                fact("preference", "prefers METRIC units"),
+                fact("unknown", "Lives in Lisbon"),
+            ])
+            .unwrap();
+        assert_eq!(again, 1);
+
+        let facts = store.read_facts();
+        assert!(facts.starts_with(
+            "## Preferences\n- Prefers metric units _(telegram:42, 2026-10-15 09:30)_"
+        ));
+        assert!(facts.contains(
+            "## Facts\n- Has a dog named Rex _(telegram:42, 2026-10-15 09:30)_\n- Lives in Lisbon"
+        ));
+        assert!(facts.contains("## Decisions\n- Deploys go out on Tuesdays"));
+        assert!(
+            store
+                .get_memory_context()
+                .starts_with("## Known Facts\n### Preferences\n")
+        );
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn concurrent_sessions_keep_each_others_writes() {
+        let workspace =
+            std::env::temp_dir().join(format!("nanobot-memory-race-{}", uuid::Uuid::new_v4()));
+        let sessions = ["telegram:1", "slack:2"].map(|session| {
+            // Each session opens its own store, as each turn does.
+            let store = MemoryStore::new(workspace.clone()).unwrap();
+            std::thread::spawn(move || {
+                for n in 0..25 {
+                    store
+                        .append_history(&format!("[{session}] entry {n}"))
+                        .unwrap();
+                    store
+                        .add_facts(&[Fact {
+                            kind: "fact".to_string(),
+                            text: format!("{session} fact {n}"),
+                            source: session.to_string(),
+                            at: "2026-10-15 09:30".to_string(),
+                        }])
+                        .unwrap();
+                }
+            })
+        });
+        for session in sessions {
+            session.join().unwrap();
+        }
+
+        let store = MemoryStore::new(workspace.clone()).unwrap();
+        let history = std::fs::read_to_string(&store.history_file).unwrap();
+        let facts = store.read_facts();
+        for session in ["telegram:1", "slack:2"] {
+            for n in 0..25 {
+                assert!(history.contains(&format!("[{session}] entry {n}\n")));
+                assert!(facts.contains(&format!("- {session} fact {n} ")));
+            }
         }
+        let _ = std::fs::remove_dir_all(workspace);
     }
 }
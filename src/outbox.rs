@@ -0,0 +1,218 @@
+//! Persistent outbox for scheduled output (cron jobs, the briefing).
+//!
+//! A scheduled reply is written to `outbox.json` under the data directory
+//! before it is sent and removed once the channel accepted it. Replies a
+//! channel could not take are retried with backoff by the gateway, so an
+//! outage delays the morning briefing instead of losing it. Every entry has
+//! a dedup key naming the run that produced it; keys delivered recently are
+//! remembered, so a job that fires again for the same run is not sent twice.
+
+use crate::bus::OutboundMessage;
+use crate::cron::CronJob;
+use crate::utils::get_data_path;
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often the gateway looks for due entries.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// Delay before the first retry; doubled per attempt up to [`MAX_BACKOFF_MS`].
+const BASE_BACKOFF_MS: i64 = 30 * 1000;
+const MAX_BACKOFF_MS: i64 = 30 * 60 * 1000;
+/// Entries still undelivered after this long are dropped.
+const EXPIRE_MS: i64 = 24 * 60 * 60 * 1000;
+/// Delivered keys remembered for dedup.
+const DELIVERED_KEYS: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxEntry {
+    pub key: String,
+    pub message: OutboundMessage,
+    pub attempts: u32,
+    pub created_at_ms: i64,
+    pub next_attempt_at_ms: i64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct OutboxStore {
+    pending: Vec<OutboxEntry>,
+    delivered: Vec<String>,
+}
+
+pub struct Outbox {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+fn now_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+fn backoff_ms(attempts: u32) -> i64 {
+    BASE_BACKOFF_MS
+        .saturating_mul(1 << attempts.saturating_sub(1).min(16))
+        .min(MAX_BACKOFF_MS)
+}
+
+/// Dedup key for the output of one run of `job`: its scheduled time, or the
+/// current time for a run started by hand.
+pub fn job_key(job: &CronJob) -> String {
+    let now = now_ms();
+    let run = job
+        .state
+        .next_run_at_ms
+        .filter(|at| *at <= now)
+        .unwrap_or(now);
+    format!("cron:{}:{run}", job.id)
+}
+
+impl Outbox {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// The outbox at `<data dir>/outbox.json`.
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(get_data_path()?.join("outbox.json")))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queues `message` under `key`. Returns false, queuing nothing, when the
+    /// key is already pending or was delivered.
+    pub fn enqueue(&self, key: &str, message: &OutboundMessage) -> Result<bool> {
+        self.update(|store| {
+            if store.delivered.iter().any(|k| k == key)
+                || store.pending.iter().any(|entry| entry.key == key)
+            {
+                return false;
+            }
+            let now = now_ms();
+            store.pending.push(OutboxEntry {
+                key: key.to_string(),
+                message: message.clone(),
+                attempts: 0,
+                created_at_ms: now,
+                next_attempt_at_ms: now,
+                last_error: None,
+            });
+            true
+        })
+    }
+
+    /// Marks `key` delivered and drops it from the queue.
+    pub fn ack(&self, key: &str) -> Result<()> {
+        self.update(|store| {
+            store.pending.retain(|entry| entry.key != key);
+            store.delivered.retain(|k| k != key);
+            store.delivered.push(key.to_string());
+            let excess = store.delivered.len().saturating_sub(DELIVERED_KEYS);
+            store.delivered.drain(..excess);
+        })
+    }
+
+    /// Records a failed attempt for `key` and schedules the next one.
+    pub fn fail(&self, key: &str, error: &str) -> Result<()> {
+        self.update(|store| {
+            if let Some(entry) = store.pending.iter_mut().find(|entry| entry.key == key) {
+                entry.attempts += 1;
+                entry.next_attempt_at_ms = now_ms() + backoff_ms(entry.attempts);
+                entry.last_error = Some(error.to_string());
+            }
+        })
+    }
+
+    /// Entries whose next attempt is due, oldest first. Entries past
+    /// [`EXPIRE_MS`] are dropped with a warning.
+    pub fn due(&self) -> Result<Vec<OutboxEntry>> {
+        let now = now_ms();
+        self.update(|store| {
+            store.pending.retain(|entry| {
+                let keep = now - entry.created_at_ms < EXPIRE_MS;
+                if !keep {
+                    tracing::warn!(
+                        key = %entry.key,
+                        channel = %entry.message.channel,
+                        attempts = entry.attempts,
+                        "dropping undelivered scheduled output"
+                    );
+                }
+                keep
+            });
+            store
+                .pending
+                .iter()
+                .filter(|entry| entry.next_attempt_at_ms <= now)
+                .cloned()
+                .collect()
+        })
+    }
+
+    pub fn pending(&self) -> Vec<OutboxEntry> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.load().pending
+    }
+
+    fn load(&self) -> OutboxStore {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut OutboxStore) -> T) -> Result<T> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut store = self.load();
+        let out = f(&mut store);
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&store)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_failed_output_until_acked_and_dedups_keys() {
+        let dir = std::env::temp_dir().join(format!("nanobot-outbox-{}", uuid::Uuid::new_v4()));
+        let outbox = Outbox::new(dir.join("outbox.json"));
+        let message = OutboundMessage::new("telegram", "42", "Good morning");
+
+        assert!(outbox.enqueue("briefing:1", &message).unwrap());
+        assert!(!outbox.enqueue("briefing:1", &message).unwrap());
+        assert_eq!(outbox.due().unwrap().len(), 1);
+
+        outbox.fail("briefing:1", "network down").unwrap();
+        assert!(outbox.due().unwrap().is_empty());
+        let reopened = Outbox::new(outbox.path());
+        let pending = reopened.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+        assert_eq!(pending[0].message.content, "Good morning");
+
+        reopened.ack("briefing:1").unwrap();
+        assert!(reopened.pending().is_empty());
+        assert!(!reopened.enqueue("briefing:1", &message).unwrap());
+        assert_eq!(backoff_ms(3), 4 * BASE_BACKOFF_MS);
+        assert_eq!(backoff_ms(30), MAX_BACKOFF_MS);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -120,7 +120,7 @@ pub fn list_pending() -> Result<Vec<PendingPairing>> {
     save_store(&store)?;
     store
         .pending
-        .sort_by(|a, b| b.last_seen_at_ms.cmp(&a.last_seen_at_ms));
+        .sort_by_key(|entry| std::cmp::Reverse(entry.last_seen_at_ms));
     Ok(store.pending)
 }
 
@@ -0,0 +1,277 @@
+//! Where the user was last seen, and where notifications should go.
+//!
+//! The gateway records every inbound chat in `~/.nanobot/presence.json` and
+//! the CLI and WebUI record `desktop`. With `presence.enabled`, reminders and
+//! the briefing go to the first preferred channel the user was active on
+//! recently (a desktop notification for `desktop`), falling back to the
+//! job's own target.
+
+use crate::config::{Config, PresenceConfig};
+use crate::utils::get_data_path;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Duration, Local};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The channel standing for the machine nanobot runs on.
+pub const DESKTOP: &str = "desktop";
+
+/// A chat seen again within this many seconds is not written again.
+const RECORD_INTERVAL_SECS: i64 = 60;
+/// Longest notification body; the full text stays in the session.
+const NOTIFICATION_CHARS: usize = 240;
+
+pub fn presence_path() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("presence.json"))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastSeen {
+    pub chat_id: String,
+    pub at: DateTime<Local>,
+}
+
+/// The latest chat per channel.
+pub type Presence = BTreeMap<String, LastSeen>;
+
+pub fn load(path: &Path) -> Presence {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Marks the user as active on `channel` in `chat_id` now.
+pub fn record(path: &Path, channel: &str, chat_id: &str) -> Result<()> {
+    let now = Local::now();
+    let mut presence = load(path);
+    if presence.get(channel).is_some_and(|seen| {
+        seen.chat_id == chat_id && now - seen.at < Duration::seconds(RECORD_INTERVAL_SECS)
+    }) {
+        return Ok(());
+    }
+    presence.insert(
+        channel.to_string(),
+        LastSeen {
+            chat_id: chat_id.to_string(),
+            at: now,
+        },
+    );
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(&presence)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// `record` at the default path when presence is enabled; failures are only
+/// logged.
+pub fn touch(config: &PresenceConfig, channel: &str, chat_id: &str) {
+    if !config.enabled {
+        return;
+    }
+    if let Err(err) = presence_path().and_then(|path| record(&path, channel, chat_id)) {
+        tracing::debug!("failed to record presence on {channel}: {err:#}");
+    }
+}
+
+/// The channels listed on a `Notify:` line of USER.md, e.g.
+/// `Notify: desktop, telegram`.
+pub fn user_preferences(workspace: &Path) -> Vec<String> {
+    static LINE: OnceLock<Regex> = OnceLock::new();
+    let line = LINE.get_or_init(|| {
+        Regex::new(r"(?im)^[\s*-]*notify(?:\s+via)?\s*:\s*(.+)$").expect("valid notify regex")
+    });
+    let Ok(text) = std::fs::read_to_string(workspace.join("USER.md")) else {
+        return Vec::new();
+    };
+    line.captures(&text)
+        .map(|caps| {
+            caps[1]
+                .split([',', '>'])
+                .map(|channel| channel.trim().trim_matches('`').to_lowercase())
+                .filter(|channel| !channel.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Desktop,
+    Chat { channel: String, to: String },
+}
+
+impl Target {
+    fn of(channel: &str, to: &str) -> Self {
+        if channel == DESKTOP {
+            Self::Desktop
+        } else {
+            Self::Chat {
+                channel: channel.to_string(),
+                to: to.to_string(),
+            }
+        }
+    }
+}
+
+/// The first channel in `prefer` seen within `active_minutes` of `now`; the
+/// job's own `channel`/`to` when there is none. A preferred channel other
+/// than the job's is reached in the chat the user was last seen in.
+pub fn choose(
+    active_minutes: u64,
+    prefer: &[String],
+    presence: &Presence,
+    now: DateTime<Local>,
+    channel: &str,
+    to: &str,
+) -> Target {
+    let cutoff = now - Duration::minutes(active_minutes as i64);
+    prefer
+        .iter()
+        .find_map(|preferred| {
+            let seen = presence.get(preferred).filter(|seen| seen.at >= cutoff)?;
+            let to = if preferred == channel {
+                to
+            } else {
+                &seen.chat_id
+            };
+            Some(Target::of(preferred, to))
+        })
+        .unwrap_or_else(|| Target::of(channel, to))
+}
+
+/// Where a notification for `channel`/`to` should go under `config`.
+pub fn route(config: &Config, channel: &str, to: &str) -> Target {
+    if !config.presence.enabled {
+        return Target::of(channel, to);
+    }
+    let prefer = if config.presence.prefer.is_empty() {
+        user_preferences(&config.workspace_path())
+    } else {
+        config.presence.prefer.clone()
+    };
+    let presence = presence_path().map(|path| load(&path)).unwrap_or_default();
+    let target = choose(
+        config.presence.active_minutes,
+        &prefer,
+        &presence,
+        Local::now(),
+        channel,
+        to,
+    );
+    tracing::debug!(?target, "routed notification for {channel}:{to}");
+    target
+}
+
+/// Shows `body` as a desktop notification.
+pub async fn notify_desktop(title: &str, body: &str) -> Result<()> {
+    let body = crate::channels::format::to_plain(body);
+    let mut body = body.trim().to_string();
+    if body.chars().count() > NOTIFICATION_CHARS {
+        body = body
+            .chars()
+            .take(NOTIFICATION_CHARS - 1)
+            .collect::<String>()
+            + "…";
+    }
+    let mut command = if cfg!(target_os = "macos") {
+        let quote = |text: &str| text.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut command = tokio::process::Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification \"{}\" with title \"{}\"",
+            quote(&body),
+            quote(title)
+        ));
+        command
+    } else if cfg!(windows) {
+        let quote = |text: &str| text.replace('\'', "''");
+        let mut command = tokio::process::Command::new("powershell");
+        command.args(["-NoProfile", "-Command"]).arg(format!(
+            "Add-Type -AssemblyName System.Windows.Forms; \
+             $n = New-Object System.Windows.Forms.NotifyIcon; \
+             $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+             $n.ShowBalloonTip(10000, '{}', '{}', 'Info'); Start-Sleep -Seconds 10; $n.Dispose()",
+            quote(title),
+            quote(&body)
+        ));
+        command
+    } else {
+        let mut command = tokio::process::Command::new("notify-send");
+        command.args(["--app-name=nanobot", title, &body]);
+        command
+    };
+    let status = command.status().await?;
+    if !status.success() {
+        bail!("desktop notification exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose_prefers_recently_active_channels_in_order() {
+        let now = Local::now();
+        let mut presence = Presence::new();
+        presence.insert(
+            DESKTOP.to_string(),
+            LastSeen {
+                chat_id: "direct".to_string(),
+                at: now - Duration::minutes(40),
+            },
+        );
+        presence.insert(
+            "discord".to_string(),
+            LastSeen {
+                chat_id: "chan-9".to_string(),
+                at: now - Duration::minutes(5),
+            },
+        );
+        let prefer = ["desktop".to_string(), "discord".to_string()];
+
+        assert_eq!(
+            choose(15, &prefer, &presence, now, "telegram", "42"),
+            Target::Chat {
+                channel: "discord".to_string(),
+                to: "chan-9".to_string()
+            }
+        );
+        assert_eq!(
+            choose(60, &prefer, &presence, now, "telegram", "42"),
+            Target::Desktop
+        );
+        assert_eq!(
+            choose(1, &prefer, &presence, now, "telegram", "42"),
+            Target::Chat {
+                channel: "telegram".to_string(),
+                to: "42".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn records_and_reads_preferences() {
+        let dir = std::env::temp_dir().join(format!("nanobot-presence-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("presence.json");
+        record(&path, "telegram", "42").expect("record");
+        record(&path, "telegram", "42").expect("record again");
+        assert_eq!(load(&path)["telegram"].chat_id, "42");
+
+        std::fs::write(
+            dir.join("USER.md"),
+            "# User\n\n- Name: Ada\n- Notify via: Desktop, `telegram`\n",
+        )
+        .expect("write user");
+        assert_eq!(user_preferences(&dir), ["desktop", "telegram"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
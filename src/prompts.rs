@@ -0,0 +1,232 @@
+//! Named prompt templates in `<workspace>/prompts/`.
+//!
+//! A template is a Markdown file whose body may contain `{{variable}}`
+//! placeholders, with optional YAML front matter describing it:
+//!
+//! ```text
+//! ---
+//! description: Weekly status report
+//! variables:
+//!   - name: team
+//!   - name: tone
+//!     default: concise
+//! ---
+//! Write the weekly report for {{team}} as of {{date}}, {{tone}}.
+//! ```
+//!
+//! `{{date}}` is always available. Placeholders without a value or default
+//! are an error rather than silently left blank.
+
+use crate::utils::today_date;
+use crate::workflows::render;
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const EXTENSIONS: [&str; 2] = ["md", "txt"];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PromptVariable {
+    pub name: String,
+    pub description: String,
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct FrontMatter {
+    description: String,
+    variables: Vec<PromptVariable>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub description: String,
+    pub variables: Vec<PromptVariable>,
+    pub body: String,
+    pub path: PathBuf,
+}
+
+impl PromptTemplate {
+    pub fn parse(name: &str, path: &Path, text: &str) -> Result<Self> {
+        let (meta, body) = match split_front_matter(text) {
+            Some((yaml, body)) => (
+                serde_yaml::from_str::<FrontMatter>(yaml)
+                    .with_context(|| format!("invalid front matter in {}", path.display()))?,
+                body,
+            ),
+            None => (FrontMatter::default(), text),
+        };
+        let mut variables = meta.variables;
+        for placeholder in placeholders(body) {
+            if placeholder != "date" && !variables.iter().any(|v| v.name == placeholder) {
+                variables.push(PromptVariable {
+                    name: placeholder,
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            description: meta.description,
+            variables,
+            body: body.trim().to_string(),
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Fills the placeholders; fails listing every variable left without a value.
+    pub fn render(&self, vars: &BTreeMap<String, String>) -> Result<String> {
+        let mut scope = Map::new();
+        scope.insert("date".to_string(), Value::String(today_date()));
+        let mut missing = Vec::new();
+        for variable in &self.variables {
+            match vars.get(&variable.name).or(variable.default.as_ref()) {
+                Some(value) => {
+                    scope.insert(variable.name.clone(), Value::String(value.clone()));
+                }
+                None => missing.push(variable.name.as_str()),
+            }
+        }
+        if !missing.is_empty() {
+            bail!(
+                "prompt '{}' needs values for: {}",
+                self.name,
+                missing.join(", ")
+            );
+        }
+        Ok(render(&self.body, &Value::Object(scope)))
+    }
+}
+
+fn split_front_matter(text: &str) -> Option<(&str, &str)> {
+    let rest = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))?;
+    let end = rest.find("\n---")?;
+    let body = &rest[end + 4..];
+    let body = body.split_once('\n').map(|(_, body)| body).unwrap_or("");
+    Some((&rest[..end], body))
+}
+
+/// Placeholder names in order of first appearance.
+fn placeholders(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim().to_string();
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    names
+}
+
+pub fn prompts_dir(workspace: &Path) -> PathBuf {
+    workspace.join("prompts")
+}
+
+fn template_name(path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_str()?;
+    EXTENSIONS
+        .contains(&ext)
+        .then(|| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .flatten()
+}
+
+pub fn load_template(workspace: &Path, name: &str) -> Result<PromptTemplate> {
+    let dir = prompts_dir(workspace);
+    let path = EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{name}.{ext}")))
+        .find(|path| path.is_file())
+        .ok_or_else(|| anyhow!("prompt '{name}' not found in {}", dir.display()))?;
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    PromptTemplate::parse(name, &path, &text)
+}
+
+/// Every template in the workspace, sorted by name; unreadable files are skipped.
+pub fn list_templates(workspace: &Path) -> Vec<PromptTemplate> {
+    let Ok(entries) = std::fs::read_dir(prompts_dir(workspace)) else {
+        return Vec::new();
+    };
+    let mut templates = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| {
+            let name = template_name(&path)?;
+            let text = std::fs::read_to_string(&path).ok()?;
+            match PromptTemplate::parse(&name, &path, &text) {
+                Ok(template) => Some(template),
+                Err(err) => {
+                    tracing::warn!("skipping prompt template: {err:#}");
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    templates
+}
+
+/// Parses `key=value` pairs from the command line.
+pub fn parse_vars(pairs: &[String]) -> Result<BTreeMap<String, String>> {
+    pairs
+        .iter()
+        .map(|pair| {
+            pair.split_once('=')
+                .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| anyhow!("expected KEY=VALUE, got '{pair}'"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_with_defaults_and_reports_missing_variables() {
+        let text = "---\ndescription: Standup\nvariables:\n  - name: tone\n    default: brief\n---\nSummarize {{team}}'s standup on {{date}}, {{tone}}.\n";
+        let template =
+            PromptTemplate::parse("standup", Path::new("standup.md"), text).expect("parse");
+        assert_eq!(template.description, "Standup");
+        let names = template
+            .variables
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["tone", "team"]);
+
+        let err = template.render(&BTreeMap::new()).expect_err("team missing");
+        assert!(err.to_string().contains("team"));
+
+        let vars = parse_vars(&["team=core".to_string()]).expect("vars");
+        let rendered = template.render(&vars).expect("render");
+        assert_eq!(
+            rendered,
+            format!("Summarize core's standup on {}, brief.", today_date())
+        );
+        assert!(parse_vars(&["novalue".to_string()]).is_err());
+    }
+
+    #[test]
+    fn templates_without_front_matter_use_the_whole_file() {
+        let template =
+            PromptTemplate::parse("plain", Path::new("plain.txt"), "Just do it.").expect("parse");
+        assert!(template.variables.is_empty());
+        assert_eq!(
+            template.render(&BTreeMap::new()).expect("render"),
+            "Just do it."
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! AWS credentials and Signature Version 4 request signing, for Bedrock.
+//!
+//! Credentials come from the standard chain, first match wins: the
+//! `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY`/`AWS_SESSION_TOKEN`
+//! variables, the `AWS_PROFILE` (or `default`) profile of
+//! `~/.aws/credentials` and `~/.aws/config` (static keys or
+//! `credential_process`), the ECS container endpoint, and EC2 instance
+//! metadata (IMDSv2). SSO and role assumption are not supported; run them
+//! through `credential_process` instead.
+
+use crate::secrets;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::debug;
+use url::Url;
+
+const ECS_HOST: &str = "http://169.254.170.2";
+const IMDS_HOST: &str = "http://169.254.169.254";
+const METADATA_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    /// When temporary credentials stop working.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AwsCredentials {
+    /// Whether the credentials expire within `margin` of `now`.
+    pub fn expiring(&self, now: DateTime<Utc>, margin: chrono::Duration) -> bool {
+        self.expires_at.is_some_and(|at| at - margin <= now)
+    }
+
+    /// Credentials in the JSON shape of the container endpoint, instance
+    /// metadata and `credential_process`.
+    fn from_json(value: &Value) -> Option<Self> {
+        let read = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+        Some(Self {
+            access_key_id: read("AccessKeyId")?,
+            secret_access_key: read("SecretAccessKey")?,
+            session_token: read("Token").or_else(|| read("SessionToken")),
+            expires_at: read("Expiration")
+                .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                .map(|at| at.with_timezone(&Utc)),
+        })
+    }
+}
+
+fn profile_name() -> String {
+    secrets::lookup("AWS_PROFILE").unwrap_or_else(|| "default".to_string())
+}
+
+fn aws_file(env: &str, name: &str) -> Option<PathBuf> {
+    secrets::lookup(env)
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".aws").join(name)))
+}
+
+/// The `key = value` pairs of `[section]` in an INI file.
+fn ini_section(text: &str, section: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut inside = false;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            inside = name.trim() == section;
+        } else if inside && let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    values
+}
+
+/// Settings of the active profile: `~/.aws/credentials` over `~/.aws/config`.
+fn profile_settings() -> HashMap<String, String> {
+    let profile = profile_name();
+    let read = |env: &str, name: &str| {
+        aws_file(env, name).and_then(|path| std::fs::read_to_string(path).ok())
+    };
+    let mut settings = HashMap::new();
+    if let Some(text) = read("AWS_CONFIG_FILE", "config") {
+        let section = if profile == "default" {
+            profile.clone()
+        } else {
+            format!("profile {profile}")
+        };
+        settings.extend(ini_section(&text, &section));
+    }
+    if let Some(text) = read("AWS_SHARED_CREDENTIALS_FILE", "credentials") {
+        settings.extend(ini_section(&text, &profile));
+    }
+    settings
+}
+
+/// The region from `AWS_REGION`, `AWS_DEFAULT_REGION` or the profile.
+pub fn default_region() -> Option<String> {
+    secrets::lookup("AWS_REGION")
+        .or_else(|| secrets::lookup("AWS_DEFAULT_REGION"))
+        .or_else(|| profile_settings().remove("region"))
+        .filter(|region| !region.trim().is_empty())
+}
+
+/// Resolves credentials through the standard chain.
+pub async fn load_credentials(client: &Client) -> Result<AwsCredentials> {
+    if let (Some(access_key_id), Some(secret_access_key)) = (
+        secrets::lookup("AWS_ACCESS_KEY_ID"),
+        secrets::lookup("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        debug!("using AWS credentials from the environment");
+        return Ok(AwsCredentials {
+            access_key_id,
+            secret_access_key,
+            session_token: secrets::lookup("AWS_SESSION_TOKEN"),
+            expires_at: None,
+        });
+    }
+    let settings = profile_settings();
+    if let (Some(access_key_id), Some(secret_access_key)) = (
+        settings.get("aws_access_key_id"),
+        settings.get("aws_secret_access_key"),
+    ) {
+        debug!(profile = %profile_name(), "using AWS credentials from the shared profile");
+        return Ok(AwsCredentials {
+            access_key_id: access_key_id.clone(),
+            secret_access_key: secret_access_key.clone(),
+            session_token: settings.get("aws_session_token").cloned(),
+            expires_at: None,
+        });
+    }
+    if let Some(command) = settings.get("credential_process") {
+        return credential_process(command).await;
+    }
+    if let Some(credentials) = container_credentials(client).await? {
+        return Ok(credentials);
+    }
+    instance_credentials(client).await.context(
+        "no AWS credentials found: set AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY, \
+configure a profile in ~/.aws, or run on AWS with a role attached",
+    )
+}
+
+async fn credential_process(command: &str) -> Result<AwsCredentials> {
+    debug!("using AWS credentials from credential_process");
+    let output = if cfg!(windows) {
+        tokio::process::Command::new("cmd")
+            .args(["/C", command])
+            .output()
+            .await
+    } else {
+        tokio::process::Command::new("sh")
+            .args(["-c", command])
+            .output()
+            .await
+    }
+    .context("failed to run credential_process")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "credential_process failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let value: Value = serde_json::from_slice(&output.stdout)
+        .context("credential_process did not print JSON credentials")?;
+    AwsCredentials::from_json(&value).context("credential_process output lacks credentials")
+}
+
+/// Credentials of an ECS task role, when running in a container that has one.
+async fn container_credentials(client: &Client) -> Result<Option<AwsCredentials>> {
+    let url = if let Some(relative) = secrets::lookup("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+        format!("{ECS_HOST}{relative}")
+    } else if let Some(full) = secrets::lookup("AWS_CONTAINER_CREDENTIALS_FULL_URI") {
+        full
+    } else {
+        return Ok(None);
+    };
+    debug!("using AWS credentials from the container endpoint");
+    let mut req = client.get(url).timeout(METADATA_TIMEOUT);
+    if let Some(token) = secrets::lookup("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+        req = req.header("Authorization", token);
+    }
+    let value: Value = req.send().await?.error_for_status()?.json().await?;
+    AwsCredentials::from_json(&value)
+        .map(Some)
+        .context("container endpoint returned no credentials")
+}
+
+/// Credentials of the instance profile, through IMDSv2.
+async fn instance_credentials(client: &Client) -> Result<AwsCredentials> {
+    if secrets::lookup("AWS_EC2_METADATA_DISABLED").is_some_and(|v| v.eq_ignore_ascii_case("true"))
+    {
+        return Err(anyhow!("instance metadata is disabled"));
+    }
+    let token = client
+        .put(format!("{IMDS_HOST}/latest/api/token"))
+        .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+        .timeout(METADATA_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let get = |path: String| {
+        client
+            .get(format!(
+                "{IMDS_HOST}/latest/meta-data/iam/security-credentials/{path}"
+            ))
+            .header("X-aws-ec2-metadata-token", &token)
+            .timeout(METADATA_TIMEOUT)
+            .send()
+    };
+    let roles = get(String::new()).await?.error_for_status()?.text().await?;
+    let role = roles
+        .lines()
+        .next()
+        .filter(|role| !role.is_empty())
+        .context("the instance has no IAM role")?;
+    let value: Value = get(role.to_string())
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    debug!(role, "using AWS credentials from instance metadata");
+    AwsCredentials::from_json(&value).context("instance metadata returned no credentials")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// RFC 3986 encoding of everything but unreserved characters.
+pub fn uri_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The headers that sign a `method` request to `url` with `body`: `host`,
+/// `x-amz-date`, the session token if any, and `authorization`. Path
+/// segments are encoded once more for the canonical request, as every
+/// service but S3 expects.
+pub fn sign(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    method: &str,
+    url: &Url,
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> Vec<(String, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let mut headers = vec![
+        ("host".to_string(), host),
+        ("x-amz-date".to_string(), amz_date.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+
+    let canonical_uri = url
+        .path()
+        .split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/");
+    let mut query = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+        .collect::<Vec<_>>();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    let canonical_headers = headers
+        .iter()
+        .map(|(k, v)| format!("{k}:{}\n", v.trim()))
+        .collect::<String>();
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{method}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{}",
+        hex(&Sha256::digest(body))
+    );
+
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = hmac(
+        format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+        &date,
+    );
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    let key = hmac(&key, "aws4_request");
+    let signature = hex(&hmac(&key, &string_to_sign));
+    headers.push((
+        "authorization".to_string(),
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        ),
+    ));
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn signs_requests_like_the_sigv4_test_suite() {
+        // `get-vanilla` from the AWS Signature Version 4 test suite.
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+            expires_at: None,
+        };
+        let now = Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap();
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = sign(&credentials, "us-east-1", "service", "GET", &url, b"", now);
+        let authorization = &headers.last().unwrap().1;
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+SignedHeaders=host;x-amz-date, \
+Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+
+        let config =
+            "[default]\nregion = eu-west-1\n\n[profile work]\n# comment\nregion=us-west-2\n";
+        assert_eq!(
+            ini_section(config, "profile work").get("region").unwrap(),
+            "us-west-2"
+        );
+        assert_eq!(uri_encode("v1:0"), "v1%3A0");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::providers::capabilities::Capabilities;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
@@ -12,16 +13,96 @@ pub struct ToolCallRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMResponse {
     pub content: Option<String>,
+    #[serde(default)]
     pub tool_calls: Vec<ToolCallRequest>,
+    #[serde(default)]
     pub finish_reason: String,
+    #[serde(default)]
     pub usage: Map<String, Value>,
     pub reasoning_content: Option<String>,
+    /// Generated images as `data:` or `http(s)` URLs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
 }
 
 impl LLMResponse {
     pub fn has_tool_calls(&self) -> bool {
         !self.tool_calls.is_empty()
     }
+
+    /// Moves a leading `<think>...</think>` block out of the content into
+    /// `reasoning_content`, for models that inline their reasoning instead of
+    /// returning it in a separate field.
+    pub fn split_reasoning(mut self) -> Self {
+        if self.reasoning_content.is_some() {
+            return self;
+        }
+        let Some(content) = self.content.as_deref() else {
+            return self;
+        };
+        let Some(rest) = content.trim_start().strip_prefix("<think>") else {
+            return self;
+        };
+        let Some((reasoning, answer)) = rest.split_once("</think>") else {
+            return self;
+        };
+        let reasoning = reasoning.trim().to_string();
+        let answer = answer.trim_start().to_string();
+        self.reasoning_content = (!reasoning.is_empty()).then_some(reasoning);
+        self.content = (!answer.is_empty()).then_some(answer);
+        self
+    }
+
+    /// `(prompt_tokens, completion_tokens)` from the usage block, 0 when absent.
+    pub fn token_usage(&self) -> (u64, u64) {
+        let read = |key: &str| self.usage.get(key).and_then(Value::as_u64).unwrap_or(0);
+        (read("prompt_tokens"), read("completion_tokens"))
+    }
+
+    /// `(cache_read_tokens, cache_write_tokens)` of the prompt, 0 when the
+    /// provider reported none (see `providers::cache`).
+    pub fn cache_usage(&self) -> (u64, u64) {
+        let read = |key: &str| self.usage.get(key).and_then(Value::as_u64).unwrap_or(0);
+        (read("cache_read_tokens"), read("cache_write_tokens"))
+    }
+}
+
+/// How the model may use the tools offered with a request.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ToolChoice {
+    /// The model decides.
+    #[default]
+    Auto,
+    /// Answer in text even though tools are listed.
+    None,
+    /// Call at least one tool.
+    Required,
+    /// Call this tool.
+    Tool(String),
+}
+
+impl ToolChoice {
+    /// The OpenAI `tool_choice` value.
+    pub fn to_value(&self) -> Value {
+        match self {
+            ToolChoice::Auto => Value::String("auto".to_string()),
+            ToolChoice::None => Value::String("none".to_string()),
+            ToolChoice::Required => Value::String("required".to_string()),
+            ToolChoice::Tool(name) => serde_json::json!({
+                "type": "function",
+                "function": { "name": name },
+            }),
+        }
+    }
+}
+
+/// Request settings beyond the plain `chat` arguments. Unset fields keep the
+/// provider's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChatOptions {
+    pub tool_choice: Option<ToolChoice>,
+    /// Whether the model may return several tool calls in one response.
+    pub parallel_tool_calls: Option<bool>,
 }
 
 #[async_trait]
@@ -35,5 +116,47 @@ pub trait LLMProvider: Send + Sync {
         temperature: f32,
     ) -> anyhow::Result<LLMResponse>;
 
+    /// Like `chat`, with `options` constraining tool use. Providers that cannot
+    /// honour them ignore them.
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        let _ = options;
+        self.chat(messages, tools, model, max_tokens, temperature)
+            .await
+    }
+
+    /// Like `chat`, reporting content deltas to `on_delta` as they arrive.
+    /// Providers without streaming report the whole content once.
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> anyhow::Result<LLMResponse> {
+        let response = self
+            .chat(messages, tools, model, max_tokens, temperature)
+            .await?;
+        if let Some(content) = response.content.as_deref().filter(|c| !c.is_empty()) {
+            on_delta(content);
+        }
+        Ok(response)
+    }
+
     fn default_model(&self) -> &str;
+
+    /// What `model` supports through this provider. Callers use it to adapt
+    /// requests, e.g. leaving out images for text-only models.
+    fn capabilities(&self, model: &str) -> Capabilities {
+        Capabilities::for_model(model)
+    }
 }
@@ -0,0 +1,579 @@
+//! Amazon Bedrock through the Converse API (`bedrock/` models).
+//!
+//! Requests are converted from the OpenAI-style history the agent keeps:
+//! system messages become `system` blocks, assistant tool calls become
+//! `toolUse` blocks, tool results become `toolResult` blocks in a user turn,
+//! and the tool list becomes `toolConfig`. Requests are signed with SigV4
+//! using the standard AWS credential chain, or sent with a Bedrock API key
+//! (`providers.bedrock.apiKey` or `AWS_BEARER_TOKEN_BEDROCK`) when one is set.
+
+use crate::providers::aws::{self, AwsCredentials};
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse, ToolCallRequest, ToolChoice};
+use crate::providers::cache;
+use crate::providers::error::ProviderError;
+use crate::providers::params::ModelRules;
+use crate::secrets;
+use anyhow::{Context, anyhow};
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::Client;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+use url::Url;
+
+/// Temporary credentials are refreshed this long before they expire.
+const REFRESH_MARGIN_MINUTES: i64 = 5;
+
+pub struct BedrockProvider {
+    region: Option<String>,
+    api_base: Option<String>,
+    api_key: Option<String>,
+    default_model: String,
+    extra_headers: HashMap<String, String>,
+    model_params: ModelRules,
+    credentials: Mutex<Option<AwsCredentials>>,
+    client: Client,
+}
+
+impl BedrockProvider {
+    /// `region` falls back to the AWS environment and profile; `api_base`
+    /// overrides `https://bedrock-runtime.<region>.amazonaws.com`.
+    pub fn new(
+        region: Option<String>,
+        api_base: Option<String>,
+        default_model: impl Into<String>,
+        extra_headers: Option<HashMap<String, String>>,
+    ) -> Self {
+        Self {
+            region: region.filter(|r| !r.trim().is_empty()),
+            api_base,
+            api_key: None,
+            default_model: default_model.into(),
+            extra_headers: extra_headers.unwrap_or_default(),
+            model_params: ModelRules::builtin(),
+            credentials: Mutex::new(None),
+            client: crate::network::client(),
+        }
+    }
+
+    /// A Bedrock API key, sent as a bearer token instead of signing.
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key
+            .filter(|key| !key.trim().is_empty())
+            .or_else(|| secrets::lookup("AWS_BEARER_TOKEN_BEDROCK"));
+        self
+    }
+
+    /// Per-model settings (`models.<pattern>`) applied to every request.
+    pub fn with_model_params(mut self, rules: ModelRules) -> Self {
+        self.model_params = rules;
+        self
+    }
+
+    fn region(&self) -> anyhow::Result<String> {
+        self.region
+            .clone()
+            .or_else(aws::default_region)
+            .ok_or_else(|| {
+                anyhow!("no AWS region for Bedrock: set providers.bedrock.region or AWS_REGION")
+            })
+    }
+
+    async fn credentials(&self) -> anyhow::Result<AwsCredentials> {
+        let mut cached = self.credentials.lock().await;
+        let margin = chrono::Duration::minutes(REFRESH_MARGIN_MINUTES);
+        if let Some(credentials) = cached.as_ref().filter(|c| !c.expiring(Utc::now(), margin)) {
+            return Ok(credentials.clone());
+        }
+        let credentials =
+            aws::load_credentials(&self.client)
+                .await
+                .map_err(|err| ProviderError::Auth {
+                    message: format!("{err:#}"),
+                })?;
+        *cached = Some(credentials.clone());
+        Ok(credentials)
+    }
+
+    async fn send(&self, model: &str, body: &Value) -> anyhow::Result<Value> {
+        let region = self.region()?;
+        let base = match &self.api_base {
+            Some(base) => base.trim_end_matches('/').to_string(),
+            None => format!("https://bedrock-runtime.{region}.amazonaws.com"),
+        };
+        let url = Url::parse(&format!(
+            "{base}/model/{}/converse",
+            aws::uri_encode(model_id(model))
+        ))
+        .context("invalid Bedrock endpoint")?;
+        let payload = serde_json::to_vec(body)?;
+
+        let mut req = self
+            .client
+            .post(url.clone())
+            .header("content-type", "application/json");
+        if let Some(key) = &self.api_key {
+            req = req.bearer_auth(key);
+        } else {
+            let credentials = self.credentials().await?;
+            for (name, value) in aws::sign(
+                &credentials,
+                &region,
+                "bedrock",
+                "POST",
+                &url,
+                &payload,
+                Utc::now(),
+            ) {
+                if name != "host" {
+                    req = req.header(name, value);
+                }
+            }
+        }
+        for (k, v) in &self.extra_headers {
+            req = req.header(k, v);
+        }
+        let response = req
+            .body(payload)
+            .send()
+            .await
+            .map_err(|err| ProviderError::Network {
+                message: err.to_string(),
+            })?;
+        let status = response.status();
+        if status.is_success() {
+            return response
+                .json()
+                .await
+                .context("failed to parse Bedrock response as JSON");
+        }
+        if status.as_u16() == 403 {
+            // Signed with stale temporary credentials; load them again next time.
+            *self.credentials.lock().await = None;
+        }
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+        let payload = response.text().await.unwrap_or_default();
+        let err = ProviderError::from_status(status.as_u16(), retry_after.as_deref(), &payload);
+        warn!(status = status.as_u16(), "Bedrock request failed: {err}");
+        Err(err.into())
+    }
+}
+
+/// The Bedrock model id of `model` (`bedrock/anthropic.claude-...`).
+fn model_id(model: &str) -> &str {
+    let model = model.strip_prefix("litellm/").unwrap_or(model);
+    model.strip_prefix("bedrock/").unwrap_or(model)
+}
+
+fn request_body(
+    messages: &[Value],
+    tools: Option<&[Value]>,
+    max_tokens: u32,
+    temperature: f32,
+    options: &ChatOptions,
+    cache_point: bool,
+) -> Value {
+    let (system, messages) = convert_messages(messages, cache_point);
+    let mut body = json!({
+        "messages": messages,
+        "inferenceConfig": { "maxTokens": max_tokens, "temperature": temperature },
+    });
+    if !system.is_empty() {
+        body["system"] = Value::Array(system);
+    }
+    if let Some(tools) = tools.filter(|tools| !tools.is_empty()) {
+        let mut config = json!({ "tools": tool_specs(tools) });
+        // Converse has no way to forbid tool use; `None` stays on auto.
+        match options.tool_choice.as_ref() {
+            Some(ToolChoice::Required) => config["toolChoice"] = json!({ "any": {} }),
+            Some(ToolChoice::Tool(name)) => {
+                config["toolChoice"] = json!({ "tool": { "name": name } })
+            }
+            _ => {}
+        }
+        body["toolConfig"] = config;
+    }
+    body
+}
+
+/// The `system` blocks and `messages` for an OpenAI-style history.
+/// Consecutive messages of one role are merged, as Converse expects turns to
+/// alternate. With `cache_point`, the first system message ends in a
+/// `cachePoint` (see `providers::cache`).
+fn convert_messages(messages: &[Value], cache_point: bool) -> (Vec<Value>, Vec<Value>) {
+    let mut system = Vec::new();
+    let mut cache_point = cache_point;
+    let mut turns: Vec<(&str, Vec<Value>)> = Vec::new();
+    for message in messages {
+        let role = message
+            .get("role")
+            .and_then(Value::as_str)
+            .unwrap_or("user");
+        let (role, blocks) = match role {
+            "system" => {
+                system.extend(
+                    content_blocks(message.get("content"))
+                        .into_iter()
+                        .filter(|block| block.get("text").is_some()),
+                );
+                if cache_point && !system.is_empty() {
+                    system.push(json!({ "cachePoint": { "type": "default" } }));
+                    cache_point = false;
+                }
+                continue;
+            }
+            "assistant" => {
+                let mut blocks = content_blocks(message.get("content"));
+                for call in message
+                    .get("tool_calls")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    let function = call.get("function").cloned().unwrap_or_default();
+                    let input = match function.get("arguments") {
+                        Some(Value::String(raw)) => {
+                            serde_json::from_str(raw).unwrap_or_else(|_| json!({}))
+                        }
+                        Some(value) => value.clone(),
+                        None => json!({}),
+                    };
+                    blocks.push(json!({
+                        "toolUse": {
+                            "toolUseId": call.get("id").and_then(Value::as_str).unwrap_or_default(),
+                            "name": function.get("name").and_then(Value::as_str).unwrap_or_default(),
+                            "input": input,
+                        }
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            "tool" => {
+                let mut content = content_blocks(message.get("content"));
+                if content.is_empty() {
+                    content.push(json!({ "text": "(no output)" }));
+                }
+                let block = json!({
+                    "toolResult": {
+                        "toolUseId": message.get("tool_call_id").and_then(Value::as_str).unwrap_or_default(),
+                        "content": content,
+                    }
+                });
+                ("user", vec![block])
+            }
+            _ => ("user", content_blocks(message.get("content"))),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        match turns.last_mut() {
+            Some((last, existing)) if *last == role => existing.extend(blocks),
+            _ => turns.push((role, blocks)),
+        }
+    }
+    let messages = turns
+        .into_iter()
+        .map(|(role, content)| json!({ "role": role, "content": content }))
+        .collect();
+    (system, messages)
+}
+
+/// Converse blocks for a string or parts `content`. Inline images become
+/// `image` blocks; remote image URLs are left as text, since Converse only
+/// takes image bytes. Empty text is dropped, as Converse rejects it.
+fn content_blocks(content: Option<&Value>) -> Vec<Value> {
+    match content {
+        Some(Value::String(text)) if !text.trim().is_empty() => vec![json!({ "text": text })],
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(Value::as_str) {
+                Some("text") => part
+                    .get("text")
+                    .and_then(Value::as_str)
+                    .filter(|text| !text.trim().is_empty())
+                    .map(|text| json!({ "text": text })),
+                Some("image_url") => {
+                    let image = part.get("image_url")?;
+                    let url = image.get("url").or(Some(image))?.as_str()?;
+                    Some(match image_block(url) {
+                        Some(block) => block,
+                        None => json!({ "text": format!("[image: {url}]") }),
+                    })
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// An `image` block for a `data:` URL in a format Converse reads.
+fn image_block(url: &str) -> Option<Value> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let format = match header.strip_suffix(";base64")? {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpeg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => return None,
+    };
+    Some(json!({ "image": { "format": format, "source": { "bytes": data } } }))
+}
+
+fn tool_specs(tools: &[Value]) -> Vec<Value> {
+    tools
+        .iter()
+        .filter_map(|tool| {
+            let function = tool.get("function")?;
+            let schema = function
+                .get("parameters")
+                .cloned()
+                .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+            Some(json!({
+                "toolSpec": {
+                    "name": function.get("name")?,
+                    "description": function.get("description").cloned().unwrap_or(json!("")),
+                    "inputSchema": { "json": schema },
+                }
+            }))
+        })
+        .collect()
+}
+
+/// The response for a `ConverseResponse`.
+fn parse_response(payload: &Value) -> anyhow::Result<LLMResponse> {
+    let mut content = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls = Vec::new();
+    for block in payload
+        .pointer("/output/message/content")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(text) = block.get("text").and_then(Value::as_str) {
+            content.push_str(text);
+        } else if let Some(tool_use) = block.get("toolUse") {
+            tool_calls.push(ToolCallRequest {
+                id: tool_use
+                    .get("toolUseId")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                name: tool_use
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                arguments: tool_use
+                    .get("input")
+                    .and_then(Value::as_object)
+                    .cloned()
+                    .unwrap_or_default(),
+            });
+        } else if let Some(text) = block
+            .pointer("/reasoningContent/reasoningText/text")
+            .and_then(Value::as_str)
+        {
+            reasoning.push_str(text);
+        }
+    }
+    let stop_reason = payload
+        .get("stopReason")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    if matches!(stop_reason, "guardrail_intervened" | "content_filtered") && content.is_empty() {
+        return Err(ProviderError::ContentFilter {
+            message: format!("Bedrock stopped the response: {stop_reason}"),
+        }
+        .into());
+    }
+    let finish_reason = if !tool_calls.is_empty() {
+        "tool_calls"
+    } else {
+        match stop_reason {
+            "max_tokens" => "length",
+            "guardrail_intervened" | "content_filtered" => "content_filter",
+            _ => "stop",
+        }
+    };
+    let usage = payload.get("usage");
+    let read = |key: &str| {
+        usage
+            .and_then(|usage| usage.get(key))
+            .and_then(Value::as_u64)
+            .unwrap_or(0)
+    };
+    let mut usage = Map::new();
+    usage.insert("prompt_tokens".to_string(), json!(read("inputTokens")));
+    usage.insert("completion_tokens".to_string(), json!(read("outputTokens")));
+    usage.insert("total_tokens".to_string(), json!(read("totalTokens")));
+    usage.insert(
+        "cache_read_tokens".to_string(),
+        json!(read("cacheReadInputTokens")),
+    );
+    usage.insert(
+        "cache_write_tokens".to_string(),
+        json!(read("cacheWriteInputTokens")),
+    );
+    Ok(LLMResponse {
+        content: (!content.is_empty()).then_some(content),
+        tool_calls,
+        finish_reason: finish_reason.to_string(),
+        usage,
+        reasoning_content: (!reasoning.is_empty()).then_some(reasoning),
+        images: Vec::new(),
+    })
+}
+
+#[async_trait]
+impl LLMProvider for BedrockProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> anyhow::Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        let model_name = model.unwrap_or(&self.default_model);
+        let mut body = request_body(
+            messages,
+            tools,
+            max_tokens,
+            temperature,
+            options,
+            cache::takes_breakpoints(model_name),
+        );
+        self.model_params
+            .resolve(model_name)
+            .apply_bedrock(&mut body["inferenceConfig"]);
+        debug!(model = %model_name, messages = messages.len(), "sending Bedrock request");
+        let payload = self.send(model_name, &body).await?;
+        parse_response(&payload)
+    }
+
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_history_and_tools_to_converse_requests() {
+        let messages = vec![
+            json!({ "role": "system", "content": "Be brief." }),
+            json!({ "role": "user", "content": [
+                { "type": "text", "text": "What is in this picture?" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,iVBOR" } },
+            ]}),
+            json!({ "role": "assistant", "content": "", "tool_calls": [{
+                "id": "tooluse_1",
+                "type": "function",
+                "function": { "name": "read_file", "arguments": "{\"path\":\"a.txt\"}" },
+            }]}),
+            json!({ "role": "tool", "tool_call_id": "tooluse_1", "name": "read_file", "content": "hello" }),
+            json!({ "role": "user", "content": "Thanks" }),
+        ];
+        let tools = vec![json!({
+            "type": "function",
+            "function": {
+                "name": "read_file",
+                "description": "Read a file",
+                "parameters": { "type": "object", "properties": { "path": { "type": "string" } } },
+            },
+        })];
+        let options = ChatOptions {
+            tool_choice: Some(ToolChoice::Tool("read_file".to_string())),
+            ..ChatOptions::default()
+        };
+        let body = request_body(&messages, Some(&tools), 512, 0.2, &options, false);
+
+        assert_eq!(body["system"], json!([{ "text": "Be brief." }]));
+        let cached = request_body(&messages, None, 512, 0.2, &options, true);
+        assert_eq!(
+            cached["system"],
+            json!([{ "text": "Be brief." }, { "cachePoint": { "type": "default" } }])
+        );
+        let turns = body["messages"].as_array().unwrap();
+        assert_eq!(turns.len(), 3);
+        assert_eq!(
+            turns[0]["content"][1],
+            json!({ "image": { "format": "png", "source": { "bytes": "iVBOR" } } })
+        );
+        assert_eq!(
+            turns[1],
+            json!({ "role": "assistant", "content": [{ "toolUse": {
+                "toolUseId": "tooluse_1", "name": "read_file", "input": { "path": "a.txt" }
+            }}]})
+        );
+        assert_eq!(turns[2]["role"], "user");
+        assert_eq!(
+            turns[2]["content"][0]["toolResult"]["content"],
+            json!([{ "text": "hello" }])
+        );
+        assert_eq!(turns[2]["content"][1], json!({ "text": "Thanks" }));
+        assert_eq!(
+            body["toolConfig"]["tools"][0]["toolSpec"]["inputSchema"]["json"]["properties"]["path"]
+                ["type"],
+            "string"
+        );
+        assert_eq!(
+            body["toolConfig"]["toolChoice"],
+            json!({ "tool": { "name": "read_file" } })
+        );
+        assert_eq!(body["inferenceConfig"]["maxTokens"], 512);
+        assert_eq!(
+            model_id("bedrock/anthropic.claude-3-5-sonnet-20240620-v1:0"),
+            "anthropic.claude-3-5-sonnet-20240620-v1:0"
+        );
+
+        let response = parse_response(&json!({
+            "output": { "message": { "role": "assistant", "content": [
+                { "text": "Let me check." },
+                { "toolUse": { "toolUseId": "tooluse_2", "name": "list_dir", "input": { "path": "." } } },
+            ]}},
+            "stopReason": "tool_use",
+            "usage": { "inputTokens": 30, "outputTokens": 12, "totalTokens": 42 },
+        }))
+        .unwrap();
+        assert_eq!(response.content.as_deref(), Some("Let me check."));
+        assert_eq!(response.tool_calls[0].id, "tooluse_2");
+        assert_eq!(response.tool_calls[0].arguments["path"], ".");
+        assert_eq!(response.finish_reason, "tool_calls");
+        assert_eq!(response.token_usage(), (30, 12));
+        assert!(
+            parse_response(&json!({ "output": { "message": { "content": [] } }, "stopReason": "guardrail_intervened" }))
+                .is_err()
+        );
+    }
+}
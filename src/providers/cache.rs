@@ -0,0 +1,116 @@
+//! Prompt caching.
+//!
+//! OpenAI, DeepSeek and Gemini reuse long prompt prefixes on their own.
+//! Anthropic models only cache up to explicit breakpoints, so requests for
+//! them mark the first system message (identity, instruction files, memory,
+//! skills; kept identical between turns by `ContextBuilder`) and the last
+//! message, which lets the tool rounds of a turn reuse each other's prefix.
+//!
+//! Whatever the provider calls them, cache hits and writes are reported in
+//! `LLMResponse.usage` as `cache_read_tokens` and `cache_write_tokens`.
+
+use serde_json::{Map, Value, json};
+
+/// Whether `model` only caches at `cache_control` breakpoints.
+pub fn takes_breakpoints(model: &str) -> bool {
+    let model = model.to_ascii_lowercase();
+    model.contains("claude") || model.contains("anthropic/")
+}
+
+/// `messages` with `cache_control` breakpoints on the first system message
+/// and on the last message.
+pub fn with_breakpoints(messages: &[Value]) -> Vec<Value> {
+    let mut marked = messages.to_vec();
+    if let Some(system) = marked
+        .iter_mut()
+        .find(|m| m.get("role").and_then(Value::as_str) == Some("system"))
+    {
+        mark(system);
+    }
+    if marked.len() > 1
+        && let Some(last) = marked.last_mut()
+    {
+        mark(last);
+    }
+    marked
+}
+
+/// Turns `message`'s content into parts, the last one carrying the breakpoint.
+fn mark(message: &mut Value) {
+    let mut parts = match message.get("content") {
+        Some(Value::String(text)) if !text.is_empty() => {
+            vec![json!({ "type": "text", "text": text })]
+        }
+        Some(Value::Array(parts)) if !parts.is_empty() => parts.clone(),
+        _ => return,
+    };
+    if let Some(last) = parts.last_mut() {
+        last["cache_control"] = json!({ "type": "ephemeral" });
+    }
+    message["content"] = Value::Array(parts);
+}
+
+/// Adds `cache_read_tokens` and `cache_write_tokens` to an OpenAI-style usage
+/// block from the fields OpenAI (`prompt_tokens_details`) and Anthropic
+/// (`cache_*_input_tokens`) report them in.
+pub fn normalize_usage(usage: &mut Map<String, Value>) {
+    let detail = |key: &str| {
+        usage
+            .get("prompt_tokens_details")
+            .and_then(|details| details.get(key))
+            .and_then(Value::as_u64)
+    };
+    let top = |key: &str| usage.get(key).and_then(Value::as_u64);
+    let read = detail("cached_tokens").or_else(|| top("cache_read_input_tokens"));
+    let write = detail("cache_write_tokens").or_else(|| top("cache_creation_input_tokens"));
+    if let Some(read) = read {
+        usage.insert("cache_read_tokens".to_string(), json!(read));
+    }
+    if let Some(write) = write {
+        usage.insert("cache_write_tokens".to_string(), json!(write));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_the_stable_system_prompt_and_the_last_message() {
+        let messages = vec![
+            json!({"role": "system", "content": "You are nanobot."}),
+            json!({"role": "system", "content": "## Current Time\n2026-10-15 09:00"}),
+            json!({"role": "user", "content": "hi"}),
+        ];
+        assert!(takes_breakpoints("openrouter/anthropic/claude-sonnet-4"));
+        assert!(!takes_breakpoints("openai/gpt-4o"));
+
+        let marked = with_breakpoints(&messages);
+        assert_eq!(
+            marked[0]["content"],
+            json!([{"type": "text", "text": "You are nanobot.", "cache_control": {"type": "ephemeral"}}])
+        );
+        assert_eq!(marked[1], messages[1]);
+        assert_eq!(
+            marked[2]["content"][0]["cache_control"]["type"],
+            "ephemeral"
+        );
+    }
+
+    #[test]
+    fn reports_cache_tokens_under_common_keys() {
+        let mut openai =
+            json!({"prompt_tokens": 2000, "prompt_tokens_details": {"cached_tokens": 1536}});
+        let openai = openai.as_object_mut().expect("object");
+        normalize_usage(openai);
+        assert_eq!(openai["cache_read_tokens"], 1536);
+        assert!(!openai.contains_key("cache_write_tokens"));
+
+        let mut anthropic =
+            json!({"cache_read_input_tokens": 0, "cache_creation_input_tokens": 1800});
+        let anthropic = anthropic.as_object_mut().expect("object");
+        normalize_usage(anthropic);
+        assert_eq!(anthropic["cache_read_tokens"], 0);
+        assert_eq!(anthropic["cache_write_tokens"], 1800);
+    }
+}
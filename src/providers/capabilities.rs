@@ -0,0 +1,108 @@
+//! What a model can do, so callers can adapt requests instead of failing.
+//!
+//! Values come from a table of known model families matched by name. Unknown
+//! models are assumed to be text-only with native tool calls and a modest
+//! context window, which suits most OpenAI-compatible servers.
+
+/// Features of one model behind one provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Native function calling.
+    pub tools: bool,
+    /// Image inputs.
+    pub vision: bool,
+    /// Incremental responses from `chat_stream`.
+    pub streaming: bool,
+    /// `response_format: json_object`.
+    pub json_mode: bool,
+    /// Context window in tokens.
+    pub max_context: u32,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            vision: false,
+            streaming: false,
+            json_mode: false,
+            max_context: 32_768,
+        }
+    }
+}
+
+/// (name fragment, tools, vision, json mode, context window); first match wins,
+/// so more specific fragments come first.
+const KNOWN_MODELS: &[(&str, bool, bool, bool, u32)] = &[
+    ("deepseek-reasoner", false, false, false, 65_536),
+    ("deepseek-r1", false, false, false, 65_536),
+    ("deepseek", true, false, true, 65_536),
+    ("o1-mini", false, false, false, 128_000),
+    ("gpt-4.1", true, true, true, 1_047_576),
+    ("gpt-4o", true, true, true, 128_000),
+    ("gpt-5", true, true, true, 400_000),
+    ("gpt-3.5", true, false, true, 16_385),
+    ("o1", true, true, true, 200_000),
+    ("o3", true, true, true, 200_000),
+    ("o4", true, true, true, 200_000),
+    ("claude", true, true, false, 200_000),
+    ("gemini", true, true, true, 1_048_576),
+    ("qwen-vl", true, true, true, 32_768),
+    ("qwen", true, false, true, 131_072),
+    ("glm-4v", true, true, false, 8_192),
+    ("glm", true, false, true, 128_000),
+    ("moonshot", true, false, true, 128_000),
+    ("kimi", true, true, true, 131_072),
+    ("llava", false, true, false, 4_096),
+    ("gemma", false, false, false, 8_192),
+    ("phi", false, false, false, 16_384),
+    ("llama", true, false, false, 131_072),
+    ("mistral", true, false, true, 32_768),
+];
+
+impl Capabilities {
+    /// Best guess for `model` (with or without a `provider/` prefix).
+    pub fn for_model(model: &str) -> Self {
+        let name = model
+            .rsplit('/')
+            .next()
+            .unwrap_or(model)
+            .to_ascii_lowercase();
+        KNOWN_MODELS
+            .iter()
+            .find(|(fragment, ..)| name.contains(fragment))
+            .map(|&(_, tools, vision, json_mode, max_context)| Self {
+                tools,
+                vision,
+                json_mode,
+                max_context,
+                ..Self::default()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_families_match_by_name() {
+        let gpt = Capabilities::for_model("openai/gpt-4o-mini");
+        assert!(gpt.tools && gpt.vision && gpt.json_mode);
+        assert_eq!(gpt.max_context, 128_000);
+
+        let reasoner = Capabilities::for_model("deepseek/deepseek-reasoner");
+        assert!(!reasoner.tools);
+        assert!(Capabilities::for_model("deepseek-chat").tools);
+
+        let unknown = Capabilities::for_model("my-local-model");
+        assert_eq!(unknown, Capabilities::default());
+        assert!(!unknown.streaming);
+    }
+}
@@ -0,0 +1,378 @@
+//! Text embeddings (`providers.embeddings`) for semantic memory and retrieval.
+//!
+//! Three backends: OpenAI's `/embeddings` (also served by most
+//! OpenAI-compatible gateways), Gemini's `batchEmbedContents` and Ollama's
+//! `/api/embed`. Inputs are sent in batches each API accepts; vectors come
+//! back in input order.
+
+use crate::config::Config;
+use crate::providers::error::ProviderError;
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+pub const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+pub const OPENAI_MODEL: &str = "text-embedding-3-small";
+pub const GEMINI_MODEL: &str = "text-embedding-004";
+pub const OLLAMA_API_BASE: &str = "http://localhost:11434";
+pub const OLLAMA_MODEL: &str = "nomic-embed-text";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// One vector per text, in order.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    fn model(&self) -> &str;
+}
+
+/// Cosine similarity of two vectors; 0 when either is empty or zero.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+async fn post_json(request: reqwest::RequestBuilder, body: &Value) -> Result<Value> {
+    let response = request
+        .timeout(REQUEST_TIMEOUT)
+        .json(body)
+        .send()
+        .await
+        .map_err(|err| ProviderError::Network {
+            message: err.to_string(),
+        })?;
+    let status = response.status();
+    if !status.is_success() {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+        let payload = response.text().await.unwrap_or_default();
+        let err = ProviderError::from_status(status.as_u16(), retry_after.as_deref(), &payload);
+        warn!(status = status.as_u16(), "embedding request failed: {err}");
+        return Err(err.into());
+    }
+    Ok(response.json().await?)
+}
+
+fn vector(value: &Value) -> Result<Vec<f32>> {
+    value
+        .as_array()
+        .context("embedding is not an array")?
+        .iter()
+        .map(|x| {
+            x.as_f64()
+                .map(|x| x as f32)
+                .context("embedding value is not a number")
+        })
+        .collect()
+}
+
+/// Checks that a batch got one vector per input.
+fn expect_count(vectors: Vec<Vec<f32>>, inputs: usize) -> Result<Vec<Vec<f32>>> {
+    if vectors.len() != inputs {
+        bail!("asked for {inputs} embeddings, got {}", vectors.len());
+    }
+    Ok(vectors)
+}
+
+/// `{"data": [{"index": 0, "embedding": [...]}, ...]}`, possibly out of order.
+fn parse_openai(value: &Value) -> Result<Vec<Vec<f32>>> {
+    let mut items = value
+        .get("data")
+        .and_then(Value::as_array)
+        .context("embedding response has no data")?
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let index = item
+                .get("index")
+                .and_then(Value::as_u64)
+                .unwrap_or(i as u64);
+            Ok((
+                index,
+                vector(item.get("embedding").unwrap_or(&Value::Null))?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    items.sort_by_key(|(index, _)| *index);
+    Ok(items.into_iter().map(|(_, vector)| vector).collect())
+}
+
+/// `{"embeddings": [{"values": [...]}, ...]}`
+fn parse_gemini(value: &Value) -> Result<Vec<Vec<f32>>> {
+    value
+        .get("embeddings")
+        .and_then(Value::as_array)
+        .context("embedding response has no embeddings")?
+        .iter()
+        .map(|item| vector(item.get("values").unwrap_or(&Value::Null)))
+        .collect()
+}
+
+/// `{"embeddings": [[...], ...]}`
+fn parse_ollama(value: &Value) -> Result<Vec<Vec<f32>>> {
+    value
+        .get("embeddings")
+        .and_then(Value::as_array)
+        .context("embedding response has no embeddings")?
+        .iter()
+        .map(vector)
+        .collect()
+}
+
+pub struct OpenAIEmbeddings {
+    api_key: String,
+    api_base: String,
+    model: String,
+    dimensions: Option<u32>,
+    client: Client,
+}
+
+impl OpenAIEmbeddings {
+    /// Inputs per request; the API takes up to 2048.
+    const BATCH: usize = 512;
+
+    pub fn new(
+        api_key: impl Into<String>,
+        api_base: Option<String>,
+        model: impl Into<String>,
+        dimensions: Option<u32>,
+    ) -> Self {
+        Self {
+            api_key: api_key.into(),
+            api_base: api_base.unwrap_or_else(|| OPENAI_API_BASE.to_string()),
+            model: model.into(),
+            dimensions,
+            client: crate::network::client(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let url = format!("{}/embeddings", self.api_base.trim_end_matches('/'));
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(Self::BATCH) {
+            let mut body = json!({ "model": self.model, "input": batch });
+            if let Some(dimensions) = self.dimensions {
+                body["dimensions"] = json!(dimensions);
+            }
+            let mut request = self.client.post(&url);
+            if !self.api_key.is_empty() {
+                request = request.bearer_auth(&self.api_key);
+            }
+            let response = post_json(request, &body).await?;
+            vectors.extend(expect_count(parse_openai(&response)?, batch.len())?);
+        }
+        Ok(vectors)
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+pub struct GeminiEmbeddings {
+    api_key: String,
+    api_base: String,
+    model: String,
+    dimensions: Option<u32>,
+    client: Client,
+}
+
+impl GeminiEmbeddings {
+    /// Requests per `batchEmbedContents` call allowed by the API.
+    const BATCH: usize = 100;
+
+    pub fn new(
+        api_key: impl Into<String>,
+        api_base: Option<String>,
+        model: impl Into<String>,
+        dimensions: Option<u32>,
+    ) -> Self {
+        Self {
+            api_key: api_key.into(),
+            api_base: api_base.unwrap_or_else(|| super::gemini::DEFAULT_API_BASE.to_string()),
+            model: model.into(),
+            dimensions,
+            client: crate::network::client(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for GeminiEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let model = self.model.strip_prefix("gemini/").unwrap_or(&self.model);
+        let url = format!(
+            "{}/models/{model}:batchEmbedContents",
+            self.api_base.trim_end_matches('/')
+        );
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(Self::BATCH) {
+            let requests = batch
+                .iter()
+                .map(|text| {
+                    let mut request = json!({
+                        "model": format!("models/{model}"),
+                        "content": { "parts": [{ "text": text }] },
+                    });
+                    if let Some(dimensions) = self.dimensions {
+                        request["outputDimensionality"] = json!(dimensions);
+                    }
+                    request
+                })
+                .collect::<Vec<_>>();
+            let request = self
+                .client
+                .post(&url)
+                .header("x-goog-api-key", &self.api_key);
+            let response = post_json(request, &json!({ "requests": requests })).await?;
+            vectors.extend(expect_count(parse_gemini(&response)?, batch.len())?);
+        }
+        Ok(vectors)
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+pub struct OllamaEmbeddings {
+    api_base: String,
+    model: String,
+    client: Client,
+}
+
+impl OllamaEmbeddings {
+    const BATCH: usize = 64;
+
+    pub fn new(api_base: Option<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_base: api_base.unwrap_or_else(|| OLLAMA_API_BASE.to_string()),
+            model: model.into(),
+            client: crate::network::client(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let url = format!("{}/api/embed", self.api_base.trim_end_matches('/'));
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(Self::BATCH) {
+            let body = json!({ "model": self.model, "input": batch });
+            let response = post_json(self.client.post(&url), &body).await?;
+            vectors.extend(expect_count(parse_ollama(&response)?, batch.len())?);
+        }
+        Ok(vectors)
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// The provider `providers.embeddings` selects, or `None` when it is off.
+pub fn build_embedding_provider(config: &Config) -> Result<Option<Arc<dyn EmbeddingProvider>>> {
+    let settings = &config.providers.embeddings;
+    let model = |default: &str| match settings.model.trim() {
+        "" => default.to_string(),
+        model => model.to_string(),
+    };
+    let key = |fallback: &str| match settings.api_key.trim() {
+        "" => fallback.to_string(),
+        key => key.to_string(),
+    };
+    let api_base = settings
+        .api_base
+        .clone()
+        .filter(|base| !base.trim().is_empty());
+    let provider: Arc<dyn EmbeddingProvider> =
+        match settings.provider.trim().to_ascii_lowercase().as_str() {
+            "" => return Ok(None),
+            "openai" => Arc::new(OpenAIEmbeddings::new(
+                key(&config.providers.openai.api_key),
+                api_base.or_else(|| config.providers.openai.api_base.clone()),
+                model(OPENAI_MODEL),
+                settings.dimensions,
+            )),
+            "gemini" => Arc::new(GeminiEmbeddings::new(
+                key(&config.providers.gemini.api_key),
+                api_base,
+                model(GEMINI_MODEL),
+                settings.dimensions,
+            )),
+            "ollama" => Arc::new(OllamaEmbeddings::new(api_base, model(OLLAMA_MODEL))),
+            other => {
+                bail!("unknown embeddings provider '{other}' (expected openai, gemini or ollama)")
+            }
+        };
+    Ok(Some(provider))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_backend_in_input_order() {
+        let openai = json!({"data": [
+            {"index": 1, "embedding": [0.0, 1.0]},
+            {"index": 0, "embedding": [1.0, 0.0]}
+        ]});
+        assert_eq!(
+            parse_openai(&openai).expect("openai"),
+            [vec![1.0, 0.0], vec![0.0, 1.0]]
+        );
+        let gemini = json!({"embeddings": [{"values": [0.5, 0.5]}]});
+        assert_eq!(parse_gemini(&gemini).expect("gemini"), [vec![0.5, 0.5]]);
+        let ollama = json!({"embeddings": [[0.25, -0.25], [1.0, 2.0]]});
+        assert_eq!(parse_ollama(&ollama).expect("ollama").len(), 2);
+        assert!(parse_ollama(&json!({"error": "model not found"})).is_err());
+        assert!(expect_count(vec![vec![1.0]], 2).is_err());
+
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert_eq!(cosine_similarity(&[], &[1.0]), 0.0);
+    }
+
+    #[test]
+    fn builds_the_configured_backend() {
+        let mut config = Config::default();
+        assert!(build_embedding_provider(&config).expect("off").is_none());
+
+        config.providers.embeddings.provider = "ollama".to_string();
+        let provider = build_embedding_provider(&config)
+            .expect("ollama")
+            .expect("on");
+        assert_eq!(provider.model(), OLLAMA_MODEL);
+
+        config.providers.embeddings.provider = "OpenAI".to_string();
+        config.providers.embeddings.model = "text-embedding-3-large".to_string();
+        let provider = build_embedding_provider(&config)
+            .expect("openai")
+            .expect("on");
+        assert_eq!(provider.model(), "text-embedding-3-large");
+
+        config.providers.embeddings.provider = "word2vec".to_string();
+        assert!(build_embedding_provider(&config).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Typed provider failures.
+//!
+//! Providers return these inside `anyhow::Error`; callers that need to react
+//! (wait out a rate limit, trim context, explain a refusal) look them up with
+//! `ProviderError::find`.
+
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderError {
+    /// Missing, invalid or unauthorized API key.
+    Auth { message: String },
+    /// The account is out of credit or over its spending quota.
+    Quota { message: String },
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    /// The prompt does not fit the model's context window.
+    ContextOverflow { message: String },
+    /// The provider refused the prompt or the answer on policy grounds.
+    ContentFilter { message: String },
+    /// The endpoint could not be reached or the connection broke.
+    Network { message: String },
+    /// The request was malformed: unknown model, bad parameters.
+    InvalidRequest { message: String },
+    /// The provider failed on its side (5xx, overloaded).
+    Server { status: u16, message: String },
+}
+
+const CONTEXT_OVERFLOW_MARKERS: &[&str] = &[
+    "context_length_exceeded",
+    "context length",
+    "context window",
+    "maximum context",
+    "prompt is too long",
+    "too many tokens",
+    "input is too long",
+    "reduce the length",
+];
+
+const QUOTA_MARKERS: &[&str] = &[
+    "insufficient_quota",
+    "exceeded your current quota",
+    "billing",
+    "credit balance",
+    "payment required",
+];
+
+const CONTENT_FILTER_MARKERS: &[&str] = &[
+    "content_filter",
+    "content filter",
+    "content management policy",
+    "content_policy",
+    "safety system",
+    "data_inspection_failed",
+];
+
+impl ProviderError {
+    /// Classifies a non-success HTTP response.
+    pub fn from_status(status: u16, retry_after: Option<&str>, body: &str) -> Self {
+        let message = error_message(body);
+        let lower = body.to_ascii_lowercase();
+        if CONTEXT_OVERFLOW_MARKERS.iter().any(|m| lower.contains(m)) {
+            return Self::ContextOverflow { message };
+        }
+        if CONTENT_FILTER_MARKERS.iter().any(|m| lower.contains(m)) {
+            return Self::ContentFilter { message };
+        }
+        if status == 402 || QUOTA_MARKERS.iter().any(|m| lower.contains(m)) {
+            return Self::Quota { message };
+        }
+        match status {
+            401 | 403 => Self::Auth { message },
+            429 => Self::RateLimited {
+                retry_after: retry_after
+                    .and_then(|value| value.trim().parse::<f64>().ok())
+                    .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                    .map(Duration::from_secs_f64),
+                message,
+            },
+            500..=599 => Self::Server { status, message },
+            _ => Self::InvalidRequest { message },
+        }
+    }
+
+    /// Classifies an error that only came with text, e.g. from a client library.
+    pub fn from_message(text: &str) -> Self {
+        let lower = text.to_ascii_lowercase();
+        let message = text.to_string();
+        let has = |needles: &[&str]| needles.iter().any(|n| lower.contains(n));
+        if has(CONTEXT_OVERFLOW_MARKERS) {
+            Self::ContextOverflow { message }
+        } else if has(CONTENT_FILTER_MARKERS) {
+            Self::ContentFilter { message }
+        } else if has(QUOTA_MARKERS) || has(&["402"]) {
+            Self::Quota { message }
+        } else if has(&[
+            "401",
+            "403",
+            "unauthorized",
+            "invalid api key",
+            "api key",
+            "authentication",
+        ]) {
+            Self::Auth { message }
+        } else if has(&["429", "rate limit", "rate_limit", "too many requests"]) {
+            Self::RateLimited {
+                retry_after: None,
+                message,
+            }
+        } else if has(&[
+            "timed out",
+            "timeout",
+            "connection",
+            "dns",
+            "error sending request",
+        ]) {
+            Self::Network { message }
+        } else if has(&[
+            "500",
+            "502",
+            "503",
+            "504",
+            "overloaded",
+            "internal server error",
+        ]) {
+            Self::Server {
+                status: 500,
+                message,
+            }
+        } else {
+            Self::InvalidRequest { message }
+        }
+    }
+
+    /// The provider error somewhere in `err`'s chain, if any.
+    pub fn find(err: &anyhow::Error) -> Option<&ProviderError> {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<ProviderError>())
+    }
+
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            Self::Auth { .. } => "Check the provider's apiKey in ~/.nanobot/config.json.",
+            Self::Quota { .. } => {
+                "Add credit or raise the spending limit in the provider's console."
+            }
+            Self::RateLimited { .. } => "Wait a moment and try again.",
+            Self::ContextOverflow { .. } => {
+                "Start a new conversation with /new, or /compact this one."
+            }
+            Self::ContentFilter { .. } => "Rephrase the request.",
+            Self::Network { .. } => "Check the network connection and the provider's apiBase.",
+            Self::InvalidRequest { .. } => "Check the model name and provider settings.",
+            Self::Server { .. } => "The provider is having trouble; try again later.",
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auth { message } => write!(f, "provider authentication failed: {message}"),
+            Self::Quota { message } => write!(f, "the provider account is out of quota: {message}"),
+            Self::RateLimited {
+                retry_after: Some(wait),
+                message,
+            } => write!(
+                f,
+                "rate limited by the provider (retry after {}s): {message}",
+                wait.as_secs().max(1)
+            ),
+            Self::RateLimited { message, .. } => {
+                write!(f, "rate limited by the provider: {message}")
+            }
+            Self::ContextOverflow { message } => {
+                write!(f, "the conversation is too long for the model: {message}")
+            }
+            Self::ContentFilter { message } => {
+                write!(
+                    f,
+                    "the provider's content filter blocked the request: {message}"
+                )
+            }
+            Self::Network { message } => write!(f, "could not reach the provider: {message}"),
+            Self::InvalidRequest { message } => {
+                write!(f, "the provider rejected the request: {message}")
+            }
+            Self::Server { status, message } => {
+                write!(f, "the provider failed (HTTP {status}): {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+/// `error.message` from an OpenAI-style error body, else the trimmed body.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| {
+            let Some(error) = value.get("error") else {
+                // AWS services: `{"message": "..."}`.
+                return value
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .map(ToOwned::to_owned);
+            };
+            error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .or_else(|| error.as_str())
+                .map(ToOwned::to_owned)
+        })
+        .unwrap_or_else(|| body.trim().chars().take(500).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_http_failures() {
+        let body = r#"{"error": {"message": "This model's maximum context length is 8192 tokens", "code": "context_length_exceeded"}}"#;
+        assert_eq!(
+            ProviderError::from_status(400, None, body),
+            ProviderError::ContextOverflow {
+                message: "This model's maximum context length is 8192 tokens".to_string()
+            }
+        );
+        assert!(matches!(
+            ProviderError::from_status(429, Some("2.5"), "slow down"),
+            ProviderError::RateLimited { retry_after: Some(wait), .. } if wait == Duration::from_millis(2500)
+        ));
+        assert!(matches!(
+            ProviderError::from_status(401, None, "{}"),
+            ProviderError::Auth { .. }
+        ));
+        let quota = r#"{"error": {"message": "You exceeded your current quota", "code": "insufficient_quota"}}"#;
+        assert!(matches!(
+            ProviderError::from_status(429, None, quota),
+            ProviderError::Quota { .. }
+        ));
+        assert!(matches!(
+            ProviderError::from_message("error sending request: connection refused"),
+            ProviderError::Network { .. }
+        ));
+
+        let err = anyhow::Error::new(ProviderError::from_status(503, None, "overloaded"))
+            .context("chat failed");
+        assert!(matches!(
+            ProviderError::find(&err),
+            Some(ProviderError::Server { status: 503, .. })
+        ));
+    }
+}
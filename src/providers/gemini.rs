@@ -0,0 +1,723 @@
+//! Google Generative Language API (`gemini/` models).
+//!
+//! Requests are converted from the OpenAI-style history the agent keeps:
+//! system messages become `systemInstruction`, assistant tool calls become
+//! `functionCall` parts, tool results become `functionResponse` parts, and
+//! the tool list becomes `functionDeclarations`. Gemini does not id its
+//! function calls, so ids are made up here; the thought signature that
+//! thinking models attach to a call is kept by id and sent back with it.
+
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse, ToolCallRequest, ToolChoice};
+use crate::providers::capabilities::Capabilities;
+use crate::providers::error::ProviderError;
+use crate::providers::params::ModelRules;
+use anyhow::Context;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+pub const DEFAULT_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Schema keywords Gemini accepts in function parameters; others (e.g.
+/// `additionalProperties`, `$schema`) make it reject the request.
+const SCHEMA_KEYS: &[&str] = &[
+    "type",
+    "format",
+    "description",
+    "nullable",
+    "enum",
+    "items",
+    "properties",
+    "required",
+    "anyOf",
+    "minimum",
+    "maximum",
+    "minItems",
+    "maxItems",
+];
+
+/// Thought signatures kept; the store is cleared when it would grow past this.
+const MAX_SIGNATURES: usize = 1024;
+
+pub struct GeminiProvider {
+    api_key: String,
+    api_base: String,
+    default_model: String,
+    extra_headers: HashMap<String, String>,
+    safety_settings: Option<Value>,
+    model_params: ModelRules,
+    /// Thought signature per tool call id.
+    signatures: Mutex<HashMap<String, String>>,
+    client: Client,
+}
+
+impl GeminiProvider {
+    pub fn new(
+        api_key: impl Into<String>,
+        api_base: Option<String>,
+        default_model: impl Into<String>,
+        extra_headers: Option<HashMap<String, String>>,
+    ) -> Self {
+        Self {
+            api_key: api_key.into(),
+            api_base: api_base.unwrap_or_else(|| DEFAULT_API_BASE.to_string()),
+            default_model: default_model.into(),
+            extra_headers: extra_headers.unwrap_or_default(),
+            safety_settings: None,
+            model_params: ModelRules::builtin(),
+            signatures: Mutex::new(HashMap::new()),
+            client: crate::network::client(),
+        }
+    }
+
+    /// `safetySettings` sent with every request, e.g.
+    /// `[{"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_ONLY_HIGH"}]`.
+    pub fn with_safety_settings(mut self, settings: Option<Value>) -> Self {
+        self.safety_settings = settings;
+        self
+    }
+
+    /// Per-model settings (`models.<pattern>`) applied to every request.
+    pub fn with_model_params(mut self, rules: ModelRules) -> Self {
+        self.model_params = rules;
+        self
+    }
+
+    fn request_body(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Value {
+        let signatures = self.signatures.lock().unwrap_or_else(|e| e.into_inner());
+        let (system, contents) = convert_messages(messages, &signatures);
+        let mut body = json!({
+            "contents": contents,
+            "generationConfig": {
+                "maxOutputTokens": max_tokens,
+                "temperature": temperature,
+            },
+        });
+        if !system.is_empty() {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        if let Some(tools) = tools.filter(|tools| !tools.is_empty()) {
+            body["tools"] = json!([{ "functionDeclarations": function_declarations(tools) }]);
+            let choice = options.tool_choice.clone().unwrap_or_default();
+            body["toolConfig"] =
+                json!({ "functionCallingConfig": function_calling_config(&choice) });
+        }
+        if let Some(settings) = &self.safety_settings {
+            body["safetySettings"] = settings.clone();
+        }
+        body
+    }
+
+    async fn send(
+        &self,
+        model: &str,
+        body: &Value,
+        stream: bool,
+    ) -> anyhow::Result<reqwest::Response> {
+        let model = model.strip_prefix("gemini/").unwrap_or(model);
+        let base = self.api_base.trim_end_matches('/');
+        let url = if stream {
+            format!("{base}/models/{model}:streamGenerateContent?alt=sse")
+        } else {
+            format!("{base}/models/{model}:generateContent")
+        };
+        let mut req = self
+            .client
+            .post(url)
+            .header("x-goog-api-key", &self.api_key)
+            .json(body);
+        for (k, v) in &self.extra_headers {
+            req = req.header(k, v);
+        }
+        let response = req.send().await.map_err(|err| ProviderError::Network {
+            message: err.to_string(),
+        })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+        let payload = response.text().await.unwrap_or_default();
+        let err = ProviderError::from_status(status.as_u16(), retry_after.as_deref(), &payload);
+        warn!(status = status.as_u16(), "Gemini request failed: {err}");
+        Err(err.into())
+    }
+
+    /// Remembers thought signatures by tool call id.
+    fn keep_signatures(&self, signatures: Vec<(String, String)>) {
+        if signatures.is_empty() {
+            return;
+        }
+        let mut kept = self.signatures.lock().unwrap_or_else(|e| e.into_inner());
+        if kept.len() + signatures.len() > MAX_SIGNATURES {
+            kept.clear();
+        }
+        kept.extend(signatures);
+    }
+}
+
+/// The system instruction and `contents` for an OpenAI-style history.
+/// Consecutive messages of one role are merged, as Gemini expects turns to
+/// alternate.
+fn convert_messages(
+    messages: &[Value],
+    signatures: &HashMap<String, String>,
+) -> (String, Vec<Value>) {
+    let mut system = Vec::new();
+    let mut contents: Vec<(String, Vec<Value>)> = Vec::new();
+    // Tool call id -> function name, for results that do not name it.
+    let mut call_names = HashMap::new();
+    for message in messages {
+        let role = message
+            .get("role")
+            .and_then(Value::as_str)
+            .unwrap_or("user");
+        let (role, parts) = match role {
+            "system" => {
+                system.extend(content_text(message.get("content")));
+                continue;
+            }
+            "assistant" => {
+                let mut parts = content_parts(message.get("content"));
+                for call in message
+                    .get("tool_calls")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    let id = call.get("id").and_then(Value::as_str).unwrap_or_default();
+                    let function = call.get("function").cloned().unwrap_or_default();
+                    let name = function
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    let args = match function.get("arguments") {
+                        Some(Value::String(raw)) => {
+                            serde_json::from_str(raw).unwrap_or_else(|_| json!({}))
+                        }
+                        Some(value) => value.clone(),
+                        None => json!({}),
+                    };
+                    call_names.insert(id.to_string(), name.to_string());
+                    let mut part = json!({ "functionCall": { "name": name, "args": args } });
+                    if let Some(signature) = signatures.get(id) {
+                        part["thoughtSignature"] = json!(signature);
+                    }
+                    parts.push(part);
+                }
+                ("model", parts)
+            }
+            "tool" => {
+                let name = message
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .or_else(|| {
+                        let id = message.get("tool_call_id").and_then(Value::as_str)?;
+                        call_names.get(id).map(String::as_str)
+                    })
+                    .unwrap_or("tool");
+                let result = content_text(message.get("content")).unwrap_or_default();
+                let part = json!({
+                    "functionResponse": { "name": name, "response": { "content": result } }
+                });
+                ("user", vec![part])
+            }
+            _ => ("user", content_parts(message.get("content"))),
+        };
+        if parts.is_empty() {
+            continue;
+        }
+        match contents.last_mut() {
+            Some((last, existing)) if last == role => existing.extend(parts),
+            _ => contents.push((role.to_string(), parts)),
+        }
+    }
+    let contents = contents
+        .into_iter()
+        .map(|(role, parts)| json!({ "role": role, "parts": parts }))
+        .collect();
+    (system.join("\n\n"), contents)
+}
+
+/// Plain text of a string or parts `content`.
+fn content_text(content: Option<&Value>) -> Option<String> {
+    match content? {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(parts) => {
+            let texts = parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>();
+            (!texts.is_empty()).then(|| texts.join("\n"))
+        }
+        _ => None,
+    }
+}
+
+/// Gemini parts for a string or parts `content`. Inline images become
+/// `inlineData`; remote image URLs are left as text, since Gemini only reads
+/// files it hosts.
+fn content_parts(content: Option<&Value>) -> Vec<Value> {
+    match content {
+        Some(Value::String(text)) if !text.is_empty() => vec![json!({ "text": text })],
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(Value::as_str) {
+                Some("text") => part.get("text").map(|text| json!({ "text": text })),
+                Some("image_url") => {
+                    let image = part.get("image_url")?;
+                    let url = image.get("url").or(Some(image))?.as_str()?;
+                    Some(match inline_data(url) {
+                        Some((mime, data)) => {
+                            json!({ "inlineData": { "mimeType": mime, "data": data } })
+                        }
+                        None => json!({ "text": format!("[image: {url}]") }),
+                    })
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// `(mime type, base64 data)` of a `data:` URL.
+fn inline_data(url: &str) -> Option<(&str, &str)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime = header.strip_suffix(";base64")?;
+    Some((mime, data))
+}
+
+fn function_declarations(tools: &[Value]) -> Vec<Value> {
+    tools
+        .iter()
+        .filter_map(|tool| {
+            let function = tool.get("function")?;
+            let mut declaration = json!({
+                "name": function.get("name")?,
+                "description": function.get("description").cloned().unwrap_or(json!("")),
+            });
+            // Gemini rejects objects without properties; such tools take none.
+            if let Some(parameters) = function.get("parameters").map(clean_schema).filter(|p| {
+                p.get("properties")
+                    .and_then(Value::as_object)
+                    .is_some_and(|props| !props.is_empty())
+            }) {
+                declaration["parameters"] = parameters;
+            }
+            Some(declaration)
+        })
+        .collect()
+}
+
+/// `schema` reduced to the keywords Gemini accepts.
+fn clean_schema(schema: &Value) -> Value {
+    let Some(object) = schema.as_object() else {
+        return schema.clone();
+    };
+    let mut out = Map::new();
+    for (key, value) in object {
+        if !SCHEMA_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = match key.as_str() {
+            "properties" => Value::Object(
+                value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, prop)| (name.clone(), clean_schema(prop)))
+                    .collect(),
+            ),
+            "items" => clean_schema(value),
+            "anyOf" => Value::Array(
+                value
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(clean_schema)
+                    .collect(),
+            ),
+            // A list of types (`["string", "null"]`) is one nullable type.
+            "type" => match value.as_array() {
+                Some(types) => {
+                    if types.iter().any(|t| t == "null") {
+                        out.insert("nullable".to_string(), Value::Bool(true));
+                    }
+                    types
+                        .iter()
+                        .find(|t| *t != "null")
+                        .cloned()
+                        .unwrap_or(json!("string"))
+                }
+                None => value.clone(),
+            },
+            _ => value.clone(),
+        };
+        out.insert(key.clone(), value);
+    }
+    Value::Object(out)
+}
+
+fn function_calling_config(choice: &ToolChoice) -> Value {
+    match choice {
+        ToolChoice::Auto => json!({ "mode": "AUTO" }),
+        ToolChoice::None => json!({ "mode": "NONE" }),
+        ToolChoice::Required => json!({ "mode": "ANY" }),
+        ToolChoice::Tool(name) => json!({ "mode": "ANY", "allowedFunctionNames": [name] }),
+    }
+}
+
+/// Folds `GenerateContentResponse` objects (one, or a stream of them) into
+/// a complete response.
+#[derive(Default)]
+struct ResponseAccumulator {
+    content: String,
+    reasoning: String,
+    tool_calls: Vec<ToolCallRequest>,
+    /// `(tool call id, thought signature)`.
+    signatures: Vec<(String, String)>,
+    images: Vec<String>,
+    finish_reason: Option<String>,
+    block_reason: Option<String>,
+    usage: Map<String, Value>,
+}
+
+impl ResponseAccumulator {
+    /// Applies one response and returns its answer text, if any.
+    fn apply(&mut self, response: &Value) -> Option<String> {
+        if let Some(usage) = response.get("usageMetadata") {
+            let read = |key: &str| usage.get(key).and_then(Value::as_u64).unwrap_or(0);
+            self.usage = json!({
+                "prompt_tokens": read("promptTokenCount"),
+                "completion_tokens": read("candidatesTokenCount") + read("thoughtsTokenCount"),
+                "total_tokens": read("totalTokenCount"),
+                "cache_read_tokens": read("cachedContentTokenCount"),
+            })
+            .as_object()
+            .cloned()
+            .unwrap_or_default();
+        }
+        if let Some(reason) = response
+            .get("promptFeedback")
+            .and_then(|feedback| feedback.get("blockReason"))
+            .and_then(Value::as_str)
+        {
+            self.block_reason = Some(reason.to_string());
+        }
+        let candidate = response.get("candidates")?.as_array()?.first()?;
+        if let Some(reason) = candidate.get("finishReason").and_then(Value::as_str) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        let mut delta = String::new();
+        for part in candidate
+            .get("content")
+            .and_then(|content| content.get("parts"))
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(call) = part.get("functionCall") {
+                let id = call
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .map(ToOwned::to_owned)
+                    .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple()));
+                if let Some(signature) = part.get("thoughtSignature").and_then(Value::as_str) {
+                    self.signatures.push((id.clone(), signature.to_string()));
+                }
+                self.tool_calls.push(ToolCallRequest {
+                    id,
+                    name: call
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    arguments: call
+                        .get("args")
+                        .and_then(Value::as_object)
+                        .cloned()
+                        .unwrap_or_default(),
+                });
+            } else if let Some(data) = part.get("inlineData") {
+                let mime = data
+                    .get("mimeType")
+                    .and_then(Value::as_str)
+                    .unwrap_or("image/png");
+                let data = data.get("data").and_then(Value::as_str).unwrap_or_default();
+                self.images.push(format!("data:{mime};base64,{data}"));
+            } else if let Some(text) = part.get("text").and_then(Value::as_str) {
+                if part.get("thought").and_then(Value::as_bool) == Some(true) {
+                    self.reasoning.push_str(text);
+                } else {
+                    delta.push_str(text);
+                }
+            }
+        }
+        self.content.push_str(&delta);
+        (!delta.is_empty()).then_some(delta)
+    }
+
+    /// The response, and the thought signatures to keep.
+    fn finish(self) -> anyhow::Result<(LLMResponse, Vec<(String, String)>)> {
+        if let Some(reason) = self.block_reason.filter(|_| self.content.is_empty()) {
+            return Err(ProviderError::ContentFilter {
+                message: format!("Gemini blocked the prompt: {reason}"),
+            }
+            .into());
+        }
+        let finish_reason = if !self.tool_calls.is_empty() {
+            "tool_calls"
+        } else {
+            match self.finish_reason.as_deref() {
+                Some("MAX_TOKENS") => "length",
+                Some("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII") => {
+                    "content_filter"
+                }
+                _ => "stop",
+            }
+        };
+        let response = LLMResponse {
+            content: (!self.content.is_empty()).then_some(self.content),
+            tool_calls: self.tool_calls,
+            finish_reason: finish_reason.to_string(),
+            usage: self.usage,
+            reasoning_content: (!self.reasoning.is_empty()).then_some(self.reasoning),
+            images: self.images,
+        };
+        Ok((response, self.signatures))
+    }
+}
+
+#[async_trait]
+impl LLMProvider for GeminiProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> anyhow::Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        let model_name = model.unwrap_or(&self.default_model);
+        let mut body = self.request_body(messages, tools, max_tokens, temperature, options);
+        self.model_params
+            .resolve(model_name)
+            .apply_gemini(&mut body["generationConfig"]);
+        debug!(model = %model_name, messages = messages.len(), "sending Gemini request");
+        let payload: Value = self
+            .send(model_name, &body, false)
+            .await?
+            .json()
+            .await
+            .context("failed to parse Gemini response as JSON")?;
+        let mut accumulator = ResponseAccumulator::default();
+        accumulator.apply(&payload);
+        let (response, signatures) = accumulator.finish()?;
+        self.keep_signatures(signatures);
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> anyhow::Result<LLMResponse> {
+        let model_name = model.unwrap_or(&self.default_model);
+        let mut body = self.request_body(
+            messages,
+            tools,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        );
+        self.model_params
+            .resolve(model_name)
+            .apply_gemini(&mut body["generationConfig"]);
+        debug!(model = %model_name, messages = messages.len(), "sending streaming Gemini request");
+        let mut response = self.send(model_name, &body, true).await?;
+
+        let mut accumulator = ResponseAccumulator::default();
+        let mut pending = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .context("failed to read streaming response")?
+        {
+            pending.extend_from_slice(&chunk);
+            while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+                let line = pending.drain(..=pos).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                if let Ok(event) = serde_json::from_str::<Value>(data.trim())
+                    && let Some(text) = accumulator.apply(&event)
+                {
+                    on_delta(&text);
+                }
+            }
+        }
+        let (response, signatures) = accumulator.finish()?;
+        self.keep_signatures(signatures);
+        Ok(response)
+    }
+
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        Capabilities::for_model(model).with_streaming(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_history_and_tools_to_gemini_requests() {
+        let provider = GeminiProvider::new("key", None, "gemini/gemini-2.5-flash", None)
+            .with_safety_settings(Some(json!([
+                {"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_ONLY_HIGH"}
+            ])));
+        provider.keep_signatures(vec![("call_1".to_string(), "sig".to_string())]);
+        let messages = [
+            json!({"role": "system", "content": "Be brief."}),
+            json!({"role": "user", "content": [
+                {"type": "text", "text": "What is in this file?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,AA=="}}
+            ]}),
+            json!({"role": "assistant", "content": null, "tool_calls": [{
+                "id": "call_1", "type": "function",
+                "function": {"name": "read_file", "arguments": "{\"path\": \"a.md\"}"}
+            }]}),
+            json!({"role": "tool", "tool_call_id": "call_1", "content": "hello"}),
+        ];
+        let tools = [json!({"type": "function", "function": {
+            "name": "read_file",
+            "description": "Read a file",
+            "parameters": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {"path": {"type": ["string", "null"], "default": "."}},
+                "required": ["path"]
+            }
+        }})];
+        let options = ChatOptions {
+            tool_choice: Some(ToolChoice::Tool("read_file".to_string())),
+            ..ChatOptions::default()
+        };
+        let body = provider.request_body(&messages, Some(&tools), 256, 0.2, &options);
+
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(
+            contents[0]["parts"][1]["inlineData"]["mimeType"],
+            "image/png"
+        );
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(
+            contents[1]["parts"][0]["functionCall"]["args"]["path"],
+            "a.md"
+        );
+        assert_eq!(contents[1]["parts"][0]["thoughtSignature"], "sig");
+        let result = &contents[2]["parts"][0]["functionResponse"];
+        assert_eq!(result["name"], "read_file");
+        assert_eq!(result["response"]["content"], "hello");
+
+        let declaration = &body["tools"][0]["functionDeclarations"][0];
+        assert!(
+            declaration["parameters"]
+                .get("additionalProperties")
+                .is_none()
+        );
+        let path = &declaration["parameters"]["properties"]["path"];
+        assert_eq!(path["type"], "string");
+        assert_eq!(path["nullable"], true);
+        assert!(path.get("default").is_none());
+        assert_eq!(
+            body["toolConfig"]["functionCallingConfig"]["allowedFunctionNames"][0],
+            "read_file"
+        );
+        assert_eq!(body["safetySettings"][0]["threshold"], "BLOCK_ONLY_HIGH");
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 256);
+    }
+
+    #[test]
+    fn maps_responses_to_text_tool_calls_and_usage() {
+        let mut accumulator = ResponseAccumulator::default();
+        let chunks = [
+            json!({"candidates": [{"content": {"role": "model", "parts": [
+                {"text": "Checking", "thought": true},
+                {"text": "Let me look."}
+            ]}}]}),
+            json!({
+                "candidates": [{"content": {"role": "model", "parts": [
+                    {"functionCall": {"name": "read_file", "args": {"path": "a.md"}},
+                     "thoughtSignature": "sig"}
+                ]}, "finishReason": "STOP"}],
+                "usageMetadata": {"promptTokenCount": 20, "candidatesTokenCount": 5, "totalTokenCount": 25}
+            }),
+        ];
+        let deltas = chunks
+            .iter()
+            .filter_map(|chunk| accumulator.apply(chunk))
+            .collect::<Vec<_>>();
+        assert_eq!(deltas, ["Let me look."]);
+        let (response, signatures) = accumulator.finish().unwrap();
+        assert_eq!(response.content.as_deref(), Some("Let me look."));
+        assert_eq!(response.reasoning_content.as_deref(), Some("Checking"));
+        assert_eq!(response.finish_reason, "tool_calls");
+        assert_eq!(response.tool_calls[0].arguments["path"], "a.md");
+        assert_eq!(
+            signatures,
+            [(response.tool_calls[0].id.clone(), "sig".to_string())]
+        );
+        assert_eq!(response.token_usage(), (20, 5));
+
+        let mut blocked = ResponseAccumulator::default();
+        blocked.apply(&json!({"promptFeedback": {"blockReason": "SAFETY"}}));
+        assert!(blocked.finish().is_err());
+    }
+}
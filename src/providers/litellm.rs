@@ -1,18 +1,18 @@
-use crate::providers::base::{LLMProvider, LLMResponse, ToolCallRequest};
+use crate::config::{GatewayOptions, ModelParams};
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse, ToolCallRequest};
+use crate::providers::cache;
+use crate::providers::capabilities::Capabilities;
+use crate::providers::error::ProviderError;
 use crate::providers::openai::OpenAIProvider as OpenAICompatProvider;
+use crate::providers::params::ModelRules;
 use anyhow::Result;
 use async_trait::async_trait;
 use litellm_rs::core::types::content::ContentPart;
 use litellm_rs::core::types::tools::{Tool, ToolChoice};
 use litellm_rs::{CompletionOptions, Message, MessageContent, MessageRole, completion};
-use serde_json::{Map, Value};
+use serde_json::{Map, Value, json};
 use std::collections::HashMap;
-
-#[derive(Clone, Copy)]
-struct ModelOverride {
-    pattern: &'static str,
-    temperature: Option<f32>,
-}
+use tracing::debug;
 
 #[derive(Clone, Copy)]
 struct EnvExtra {
@@ -20,6 +20,16 @@ struct EnvExtra {
     value_template: &'static str,
 }
 
+/// How a provider exposes its built-in web search.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NativeSearch {
+    None,
+    /// A `{"type": "web_search"}` entry in `tools` (Zhipu GLM).
+    WebSearchTool,
+    /// `enable_search: true` in the request body (DashScope Qwen).
+    EnableSearch,
+}
+
 #[derive(Clone, Copy)]
 struct ProviderSpec {
     name: &'static str,
@@ -29,12 +39,14 @@ struct ProviderSpec {
     skip_prefixes: &'static [&'static str],
     is_gateway: bool,
     is_local: bool,
+    /// Takes the extra request fields in `gatewayOptions`.
+    accepts_gateway_options: bool,
+    native_search: NativeSearch,
     detect_by_key_prefix: &'static str,
     detect_by_base_keyword: &'static str,
     default_api_base: &'static str,
     strip_model_prefix: bool,
     env_extras: &'static [EnvExtra],
-    model_overrides: &'static [ModelOverride],
 }
 
 const PROVIDERS: &[ProviderSpec] = &[
@@ -46,12 +58,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &[],
         is_gateway: true,
         is_local: false,
+        accepts_gateway_options: true,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "sk-or-",
         detect_by_base_keyword: "openrouter",
         default_api_base: "https://openrouter.ai/api/v1",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "aihubmix",
@@ -61,12 +74,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &[],
         is_gateway: true,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "aihubmix",
         default_api_base: "https://aihubmix.com/v1",
         strip_model_prefix: true,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "siliconflow",
@@ -76,12 +90,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &[],
         is_gateway: true,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "siliconflow",
         default_api_base: "https://api.siliconflow.cn/v1",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "volcengine",
@@ -91,12 +106,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &[],
         is_gateway: true,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "volces",
         default_api_base: "https://ark.cn-beijing.volces.com/api/v3",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "anthropic",
@@ -106,12 +122,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &[],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "openai",
@@ -121,12 +138,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &[],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "deepseek",
@@ -136,12 +154,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &["deepseek/"],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "gemini",
@@ -151,12 +170,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &["gemini/"],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "zhipu",
@@ -166,6 +186,8 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &["zhipu/", "zai/", "openrouter/", "hosted_vllm/"],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::WebSearchTool,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
@@ -174,7 +196,6 @@ const PROVIDERS: &[ProviderSpec] = &[
             key: "ZHIPUAI_API_KEY",
             value_template: "{api_key}",
         }],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "dashscope",
@@ -184,12 +205,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &["dashscope/", "openrouter/"],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::EnableSearch,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "moonshot",
@@ -199,6 +221,8 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &["moonshot/", "openrouter/"],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "https://api.moonshot.ai/v1",
@@ -207,10 +231,6 @@ const PROVIDERS: &[ProviderSpec] = &[
             key: "MOONSHOT_API_BASE",
             value_template: "{api_base}",
         }],
-        model_overrides: &[ModelOverride {
-            pattern: "kimi-k2.5",
-            temperature: Some(1.0),
-        }],
     },
     ProviderSpec {
         name: "minimax",
@@ -220,12 +240,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &["minimax/", "openrouter/"],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "https://api.minimax.io/v1",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "vllm",
@@ -235,12 +256,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &[],
         is_gateway: false,
         is_local: true,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
     ProviderSpec {
         name: "groq",
@@ -250,12 +272,13 @@ const PROVIDERS: &[ProviderSpec] = &[
         skip_prefixes: &["groq/"],
         is_gateway: false,
         is_local: false,
+        accepts_gateway_options: false,
+        native_search: NativeSearch::None,
         detect_by_key_prefix: "",
         detect_by_base_keyword: "",
         default_api_base: "",
         strip_model_prefix: false,
         env_extras: &[],
-        model_overrides: &[],
     },
 ];
 
@@ -300,6 +323,9 @@ pub struct LiteLLMProvider {
     default_model: String,
     extra_headers: HashMap<String, String>,
     gateway: Option<&'static ProviderSpec>,
+    gateway_options: Option<GatewayOptions>,
+    native_search: bool,
+    model_params: ModelRules,
 }
 
 impl LiteLLMProvider {
@@ -328,6 +354,9 @@ impl LiteLLMProvider {
             default_model,
             extra_headers: extra_headers.unwrap_or_default(),
             gateway,
+            gateway_options: None,
+            native_search: false,
+            model_params: ModelRules::builtin(),
         };
 
         if !provider.api_key.is_empty() {
@@ -337,6 +366,104 @@ impl LiteLLMProvider {
         provider
     }
 
+    /// Applies `options` when the detected gateway accepts them.
+    pub fn with_gateway_options(mut self, options: Option<GatewayOptions>) -> Self {
+        self.gateway_options = options;
+        self
+    }
+
+    /// Request fields and headers from `gatewayOptions`, if the gateway takes them.
+    fn gateway_fields(&self) -> (Map<String, Value>, HashMap<String, String>) {
+        let mut body = Map::new();
+        let mut headers = HashMap::new();
+        let Some(options) = self
+            .gateway_options
+            .as_ref()
+            .filter(|_| self.gateway.is_some_and(|g| g.accepts_gateway_options))
+        else {
+            return (body, headers);
+        };
+        if let Some(provider) = &options.provider {
+            body.insert("provider".to_string(), provider.clone());
+        }
+        if !options.models.is_empty() {
+            body.insert("models".to_string(), json!(options.models));
+        }
+        if !options.transforms.is_empty() {
+            body.insert("transforms".to_string(), json!(options.transforms));
+        }
+        if options.usage {
+            body.insert("usage".to_string(), json!({ "include": true }));
+        }
+        if !options.app_url.trim().is_empty() {
+            headers.insert("HTTP-Referer".to_string(), options.app_url.clone());
+        }
+        if !options.app_title.trim().is_empty() {
+            headers.insert("X-Title".to_string(), options.app_title.clone());
+        }
+        (body, headers)
+    }
+
+    /// Turns on the provider's built-in web search, where it has one.
+    pub fn with_native_search(mut self, enabled: bool) -> Self {
+        self.native_search = enabled;
+        self
+    }
+
+    /// Per-model settings (`models.<pattern>`) applied to every request.
+    pub fn with_model_params(mut self, rules: ModelRules) -> Self {
+        self.model_params = rules;
+        self
+    }
+
+    /// Body fields and the full tool list that enable built-in web search for
+    /// `model`; the tool list is `None` when `tools` needs no change.
+    fn native_search_fields(
+        &self,
+        model: &str,
+        tools: Option<&[Value]>,
+    ) -> (Map<String, Value>, Option<Vec<Value>>) {
+        let mut body = Map::new();
+        let kind = self
+            .gateway
+            .or_else(|| find_by_model(model))
+            .filter(|_| self.native_search)
+            .map_or(NativeSearch::None, |spec| spec.native_search);
+        match kind {
+            NativeSearch::None => (body, None),
+            NativeSearch::WebSearchTool => {
+                let mut all = tools.map(<[Value]>::to_vec).unwrap_or_default();
+                all.push(json!({
+                    "type": "web_search",
+                    "web_search": { "enable": true, "search_result": true }
+                }));
+                (body, Some(all))
+            }
+            NativeSearch::EnableSearch => {
+                body.insert("enable_search".to_string(), Value::Bool(true));
+                (body, None)
+            }
+        }
+    }
+
+    fn compat_provider(
+        &self,
+        model: &str,
+        search_body: Map<String, Value>,
+    ) -> OpenAICompatProvider {
+        let (mut extra_body, mut headers) = self.gateway_fields();
+        extra_body.extend(search_body);
+        headers.extend(self.extra_headers.clone());
+        OpenAICompatProvider::new(
+            self.api_key.clone(),
+            self.effective_api_base(model),
+            model.to_string(),
+            Some(headers),
+        )
+        .with_extra_body(extra_body)
+        .with_model_params(self.model_params.clone())
+    }
+
     fn resolve_model(&self, model: &str) -> String {
         if let Some(gateway) = self.gateway {
             let normalized = if gateway.strip_model_prefix {
@@ -365,20 +492,6 @@ impl LiteLLMProvider {
         model.to_string()
     }
 
-    fn apply_model_overrides(&self, model: &str, temperature: &mut f32) {
-        let model_lower = model.to_lowercase();
-        if let Some(spec) = find_by_model(model) {
-            for rule in spec.model_overrides {
-                if model_lower.contains(rule.pattern)
-                    && let Some(temp) = rule.temperature
-                {
-                    *temperature = temp;
-                    return;
-                }
-            }
-        }
-    }
-
     fn effective_api_base(&self, model: &str) -> Option<String> {
         if let Some(base) = &self.api_base {
             return Some(base.clone());
@@ -501,6 +614,27 @@ impl LiteLLMProvider {
             }
         }
     }
+
+    /// Generated images in `content`, as URLs (base64 data becomes a `data:` URL).
+    fn content_images(content: &MessageContent) -> Vec<String> {
+        let MessageContent::Parts(parts) = content else {
+            return Vec::new();
+        };
+        parts
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::ImageUrl { image_url } => Some(image_url.url.clone()),
+                ContentPart::Image {
+                    image_url: Some(image_url),
+                    ..
+                } => Some(image_url.url.clone()),
+                ContentPart::Image { source, .. } => {
+                    Some(format!("data:{};base64,{}", source.media_type, source.data))
+                }
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -512,26 +646,43 @@ impl LLMProvider for LiteLLMProvider {
         model: Option<&str>,
         max_tokens: u32,
         temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        chat_options: &ChatOptions,
     ) -> Result<LLMResponse> {
         let selected_model = model.unwrap_or(&self.default_model);
-        let mut effective_temperature = temperature;
         let resolved_model = self.resolve_model(selected_model);
-        self.apply_model_overrides(&resolved_model, &mut effective_temperature);
+        debug!(model = %resolved_model, "dispatching chat request");
+        let (search_body, search_tools) = self.native_search_fields(selected_model, tools);
+        let tools = search_tools.as_deref().or(tools);
 
         if self.use_openai_compat_path(selected_model) {
-            let provider = OpenAICompatProvider::new(
-                self.api_key.clone(),
-                self.effective_api_base(selected_model),
-                selected_model.to_string(),
-                Some(self.extra_headers.clone()),
-            );
-            return provider
-                .chat(
+            return self
+                .compat_provider(selected_model, search_body)
+                .chat_with_options(
                     messages,
                     tools,
                     Some(selected_model),
                     max_tokens,
-                    effective_temperature,
+                    temperature,
+                    chat_options,
                 )
                 .await;
         }
@@ -540,9 +691,10 @@ impl LLMProvider for LiteLLMProvider {
             .iter()
             .map(Self::convert_message)
             .collect::<Vec<_>>();
+        let params = self.model_params.resolve(selected_model);
         let mut options = CompletionOptions {
-            max_tokens: Some(max_tokens),
-            temperature: Some(effective_temperature),
+            max_tokens: Some(params.max_tokens.unwrap_or(max_tokens)),
+            temperature: Some(params.temperature.unwrap_or(temperature)),
             api_key: if self.api_key.is_empty() {
                 None
             } else {
@@ -562,9 +714,12 @@ impl LLMProvider for LiteLLMProvider {
                 .iter()
                 .filter_map(|item| serde_json::from_value::<Tool>(item.clone()).ok())
                 .collect::<Vec<_>>();
+            let tool_choice = chat_options.tool_choice.clone().unwrap_or_default();
             if !parsed_tools.is_empty() {
                 options.tools = Some(parsed_tools);
-                options.tool_choice = Some(ToolChoice::String("auto".to_string()));
+                options.tool_choice = serde_json::from_value::<ToolChoice>(tool_choice.to_value())
+                    .ok()
+                    .or_else(|| Some(ToolChoice::String("auto".to_string())));
             }
 
             // litellm-rs 0.3.1 conversion currently drops CompletionOptions.tools.
@@ -573,7 +728,23 @@ impl LLMProvider for LiteLLMProvider {
                 .insert("tools".to_string(), Value::Array(tool_defs.to_vec()));
             options
                 .extra_params
-                .insert("tool_choice".to_string(), Value::String("auto".to_string()));
+                .insert("tool_choice".to_string(), tool_choice.to_value());
+            if let Some(parallel) = chat_options.parallel_tool_calls {
+                options
+                    .extra_params
+                    .insert("parallel_tool_calls".to_string(), Value::Bool(parallel));
+            }
+        }
+        options.extra_params.extend(search_body);
+        let mut extra = Value::Object(Map::new());
+        ModelParams {
+            temperature: None,
+            max_tokens: None,
+            ..params
+        }
+        .apply_openai(&mut extra);
+        if let Value::Object(extra) = extra {
+            options.extra_params.extend(extra);
         }
 
         let response = match completion(
@@ -585,20 +756,21 @@ impl LLMProvider for LiteLLMProvider {
         {
             Ok(resp) => resp,
             Err(primary_err) => {
+                let primary = ProviderError::from_message(&primary_err.to_string());
                 // Fallback to raw model for better compatibility with pure
-                // OpenAI-compatible endpoints.
-                if resolved_model != selected_model {
+                // OpenAI-compatible endpoints. Other failures would only repeat.
+                if resolved_model != selected_model
+                    && matches!(primary, ProviderError::InvalidRequest { .. })
+                {
                     completion(selected_model, chat_messages, Some(options))
                         .await
                         .map_err(|fallback_err| {
-                            anyhow::anyhow!(
-                                "failed to call litellm-rs completion: primary={primary_err}; fallback={fallback_err}"
-                            )
+                            ProviderError::from_message(&format!(
+                                "primary={primary_err}; fallback={fallback_err}"
+                            ))
                         })?
                 } else {
-                    return Err(anyhow::anyhow!(
-                        "failed to call litellm-rs completion: {primary_err}"
-                    ));
+                    return Err(primary.into());
                 }
             }
         };
@@ -610,10 +782,17 @@ impl LLMProvider for LiteLLMProvider {
                 finish_reason: "stop".to_string(),
                 usage: Map::new(),
                 reasoning_content: None,
+                images: Vec::new(),
             });
         };
 
         let content = choice.message.content.as_ref().map(Self::content_to_text);
+        let images = choice
+            .message
+            .content
+            .as_ref()
+            .map(Self::content_images)
+            .unwrap_or_default();
         let reasoning_content = choice
             .message
             .thinking
@@ -651,11 +830,12 @@ impl LLMProvider for LiteLLMProvider {
             .and_then(|v| v.as_str().map(ToOwned::to_owned))
             .unwrap_or_else(|| "stop".to_string());
 
-        let usage = response
+        let mut usage = response
             .usage
             .and_then(|usage| serde_json::to_value(usage).ok())
             .and_then(|value| value.as_object().cloned())
             .unwrap_or_default();
+        cache::normalize_usage(&mut usage);
 
         Ok(LLMResponse {
             content,
@@ -663,12 +843,50 @@ impl LLMProvider for LiteLLMProvider {
             finish_reason,
             usage,
             reasoning_content,
-        })
+            images,
+        }
+        .split_reasoning())
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        let selected_model = model.unwrap_or(&self.default_model);
+        if !self.use_openai_compat_path(selected_model) {
+            let response = self
+                .chat(messages, tools, model, max_tokens, temperature)
+                .await?;
+            if let Some(content) = response.content.as_deref().filter(|c| !c.is_empty()) {
+                on_delta(content);
+            }
+            return Ok(response);
+        }
+        let (search_body, search_tools) = self.native_search_fields(selected_model, tools);
+        self.compat_provider(selected_model, search_body)
+            .chat_stream(
+                messages,
+                search_tools.as_deref().or(tools),
+                Some(selected_model),
+                max_tokens,
+                temperature,
+                on_delta,
+            )
+            .await
     }
 
     fn default_model(&self) -> &str {
         &self.default_model
     }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        Capabilities::for_model(model).with_streaming(self.use_openai_compat_path(model))
+    }
 }
 
 #[cfg(test)]
@@ -721,8 +939,58 @@ mod tests {
     #[test]
     fn model_override_applies_kimi_temperature_floor() {
         let provider = LiteLLMProvider::new("", None, "kimi-k2.5", None, None);
-        let mut temp = 0.2;
-        provider.apply_model_overrides("moonshot/kimi-k2.5", &mut temp);
-        assert!((temp - 1.0).abs() < f32::EPSILON);
+        let params = provider.model_params.resolve("moonshot/kimi-k2.5");
+        assert_eq!(params.temperature, Some(1.0));
+    }
+
+    #[test]
+    fn gateway_options_only_reach_gateways_that_accept_them() {
+        let options = GatewayOptions {
+            provider: Some(json!({"order": ["anthropic"], "allow_fallbacks": false})),
+            models: vec!["openai/gpt-4o".to_string()],
+            transforms: vec!["middle-out".to_string()],
+            usage: true,
+            app_title: "nanobot".to_string(),
+            ..GatewayOptions::default()
+        };
+        let openrouter = LiteLLMProvider::new(
+            "",
+            None,
+            "anthropic/claude-sonnet-4",
+            None,
+            Some("openrouter"),
+        )
+        .with_gateway_options(Some(options.clone()));
+        let (body, headers) = openrouter.gateway_fields();
+        assert_eq!(body["provider"]["order"][0], "anthropic");
+        assert_eq!(body["transforms"], json!(["middle-out"]));
+        assert_eq!(body["usage"], json!({"include": true}));
+        assert_eq!(headers["X-Title"], "nanobot");
+        assert!(!headers.contains_key("HTTP-Referer"));
+
+        let aihubmix = LiteLLMProvider::new("", None, "gpt-4o", None, Some("aihubmix"))
+            .with_gateway_options(Some(options));
+        let (body, headers) = aihubmix.gateway_fields();
+        assert!(body.is_empty() && headers.is_empty());
+    }
+
+    #[test]
+    fn native_search_uses_each_providers_mechanism() {
+        let tools = [json!({"type": "function", "function": {"name": "exec"}})];
+        let glm = LiteLLMProvider::new("", None, "glm-4-plus", None, None).with_native_search(true);
+        let (body, all_tools) = glm.native_search_fields("glm-4-plus", Some(&tools));
+        assert!(body.is_empty());
+        let all_tools = all_tools.expect("tools");
+        assert_eq!(all_tools.len(), 2);
+        assert_eq!(all_tools[1]["type"], "web_search");
+
+        let qwen = LiteLLMProvider::new("", None, "qwen-plus", None, None).with_native_search(true);
+        let (body, all_tools) = qwen.native_search_fields("qwen-plus", None);
+        assert_eq!(body["enable_search"], true);
+        assert!(all_tools.is_none());
+
+        let off = LiteLLMProvider::new("", None, "qwen-plus", None, None);
+        let (body, all_tools) = off.native_search_fields("qwen-plus", None);
+        assert!(body.is_empty() && all_tools.is_none());
     }
 }
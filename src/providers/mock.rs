@@ -0,0 +1,348 @@
+//! Offline providers for deterministic runs (`--mock`, `--record`).
+//!
+//! `--record <file>` forwards calls to the configured provider and writes
+//! every response to a fixture file as it arrives. `--mock <file>` answers
+//! from that fixture instead, in recorded order and without API keys, so
+//! agent-loop and tool behaviour can be replayed exactly. Fixtures are plain
+//! JSON and can be written by hand:
+//!
+//! ```json
+//! { "exchanges": [ { "response": { "content": "Hello!" } } ] }
+//! ```
+
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use anyhow::{Context, Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::warn;
+
+/// One recorded call. `model` and `lastMessage` only help reading and
+/// editing a fixture; replay serves responses in order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Exchange {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_message: Option<Value>,
+    pub response: LLMResponse,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Fixture {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Fixture {
+    pub fn from_responses(responses: impl IntoIterator<Item = LLMResponse>) -> Self {
+        Self {
+            exchanges: responses
+                .into_iter()
+                .map(|response| Exchange {
+                    model: None,
+                    last_message: None,
+                    response,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read fixture {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("invalid fixture {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write fixture {}", path.display()))
+    }
+}
+
+/// Serves the responses of a fixture in order; fails once they run out.
+pub struct MockProvider {
+    /// Shared by every provider `wrap_provider` builds, so the main and the
+    /// routed fast model draw from one sequence.
+    exchanges: Arc<Mutex<VecDeque<Exchange>>>,
+    default_model: String,
+}
+
+impl MockProvider {
+    pub fn new(fixture: Fixture, default_model: &str) -> Self {
+        Self {
+            exchanges: Arc::new(Mutex::new(fixture.exchanges.into())),
+            default_model: default_model.to_string(),
+        }
+    }
+
+    /// The same queue of responses under another default model.
+    fn sharing(&self, default_model: &str) -> Self {
+        Self {
+            exchanges: self.exchanges.clone(),
+            default_model: default_model.to_string(),
+        }
+    }
+
+    /// How many responses are left to serve.
+    pub fn remaining(&self) -> usize {
+        self.exchanges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockProvider {
+    async fn chat(
+        &self,
+        _messages: &[Value],
+        _tools: Option<&[Value]>,
+        _model: Option<&str>,
+        _max_tokens: u32,
+        _temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.exchanges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+            .map(|exchange| exchange.response)
+            .ok_or_else(|| anyhow!("mock fixture has no more responses"))
+    }
+
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+}
+
+struct Recorder {
+    path: PathBuf,
+    fixture: Mutex<Fixture>,
+}
+
+impl Recorder {
+    /// Appends an exchange and rewrites the file, so an interrupted run
+    /// keeps what it recorded.
+    fn record(&self, messages: &[Value], model: Option<&str>, response: &LLMResponse) {
+        let mut fixture = self.fixture.lock().unwrap_or_else(|e| e.into_inner());
+        fixture.exchanges.push(Exchange {
+            model: model.map(str::to_string),
+            last_message: messages.last().cloned(),
+            response: response.clone(),
+        });
+        if let Err(err) = fixture.save(&self.path) {
+            warn!("failed to record response: {err:#}");
+        }
+    }
+}
+
+/// Forwards calls to `inner` and records each response.
+pub struct RecordingProvider {
+    inner: Arc<dyn LLMProvider>,
+    recorder: Arc<Recorder>,
+}
+
+#[async_trait]
+impl LLMProvider for RecordingProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        let response = self
+            .inner
+            .chat(messages, tools, model, max_tokens, temperature)
+            .await?;
+        self.recorder.record(messages, model, &response);
+        Ok(response)
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        let response = self
+            .inner
+            .chat_with_options(messages, tools, model, max_tokens, temperature, options)
+            .await?;
+        self.recorder.record(messages, model, &response);
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        let response = self
+            .inner
+            .chat_stream(messages, tools, model, max_tokens, temperature, on_delta)
+            .await?;
+        self.recorder.record(messages, model, &response);
+        Ok(response)
+    }
+
+    fn default_model(&self) -> &str {
+        self.inner.default_model()
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.inner.capabilities(model)
+    }
+}
+
+enum Mode {
+    Replay(MockProvider),
+    Record(Arc<Recorder>),
+}
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// Answers every model call of this process from the fixture at `path`
+/// (`--mock`).
+pub fn set_replay(path: &Path) -> Result<()> {
+    let provider = MockProvider::new(Fixture::load(path)?, "");
+    MODE.set(Mode::Replay(provider))
+        .map_err(|_| anyhow!("mock provider already configured"))
+}
+
+/// Records every model call of this process to a new fixture at `path`
+/// (`--record`).
+pub fn set_record(path: &Path) -> Result<()> {
+    Fixture::default().save(path)?;
+    let recorder = Recorder {
+        path: path.to_path_buf(),
+        fixture: Mutex::new(Fixture::default()),
+    };
+    MODE.set(Mode::Record(Arc::new(recorder)))
+        .map_err(|_| anyhow!("mock provider already configured"))
+}
+
+/// Whether calls are answered from a fixture, so no API key is needed.
+pub fn replaying() -> bool {
+    matches!(MODE.get(), Some(Mode::Replay(_)))
+}
+
+/// `provider` replaced by the fixture under `--mock` or recorded under
+/// `--record`; unchanged otherwise.
+pub fn wrap_provider(provider: Arc<dyn LLMProvider>) -> Arc<dyn LLMProvider> {
+    match MODE.get() {
+        Some(Mode::Replay(mock)) => Arc::new(mock.sharing(provider.default_model())),
+        Some(Mode::Record(recorder)) => Arc::new(RecordingProvider {
+            inner: provider,
+            recorder: recorder.clone(),
+        }),
+        None => provider,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentLoop;
+    use crate::providers::base::ToolCallRequest;
+    use crate::session::SessionManager;
+    use serde_json::Map;
+
+    fn answer(content: &str) -> LLMResponse {
+        LLMResponse {
+            content: Some(content.to_string()),
+            tool_calls: Vec::new(),
+            finish_reason: "stop".to_string(),
+            usage: Map::new(),
+            reasoning_content: None,
+            images: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn recorded_fixture_replays_a_tool_turn() {
+        let dir = std::env::temp_dir().join(format!("nanobot-mock-{}", uuid::Uuid::new_v4()));
+        let workspace = dir.join("workspace");
+        std::fs::create_dir_all(&workspace).expect("workspace");
+        std::fs::write(workspace.join("note.txt"), "blue").expect("note");
+        let mut arguments = Map::new();
+        arguments.insert("path".to_string(), Value::from("note.txt"));
+        let read = LLMResponse {
+            content: None,
+            tool_calls: vec![ToolCallRequest {
+                id: "call_1".to_string(),
+                name: "read_file".to_string(),
+                arguments,
+            }],
+            finish_reason: "tool_calls".to_string(),
+            ..answer("")
+        };
+        let live = Arc::new(MockProvider::new(
+            Fixture::from_responses([read, answer("The note says blue.")]),
+            "test-model",
+        ));
+        let path = dir.join("fixture.json");
+        let recorder = Arc::new(Recorder {
+            path: path.clone(),
+            fixture: Mutex::new(Fixture::default()),
+        });
+        let recording = RecordingProvider {
+            inner: live,
+            recorder,
+        };
+        let run = |provider: Arc<dyn LLMProvider>, name: &'static str| {
+            let sessions = SessionManager::with_dir(dir.join(name)).expect("sessions");
+            let agent = AgentLoop::builder(provider)
+                .workspace(workspace.clone())
+                .sessions(Arc::new(sessions))
+                .build()
+                .expect("agent");
+            async move {
+                agent
+                    .process_direct("What does note.txt say?", Some("cli:mock"), None, None)
+                    .await
+                    .expect("reply")
+            }
+        };
+
+        assert_eq!(
+            run(Arc::new(recording), "recorded").await,
+            "The note says blue."
+        );
+        let fixture = Fixture::load(&path).expect("fixture");
+        assert_eq!(fixture.exchanges.len(), 2);
+        let prompt = fixture.exchanges[0].last_message.as_ref().expect("prompt");
+        assert!(prompt.to_string().contains("What does note.txt say?"));
+
+        let replay = Arc::new(MockProvider::new(fixture, "test-model"));
+        assert_eq!(run(replay.clone(), "replayed").await, "The note says blue.");
+        assert_eq!(replay.remaining(), 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn hand_written_fixtures_need_only_content() {
+        let fixture: Fixture =
+            serde_json::from_str(r#"{"exchanges":[{"response":{"content":"Hi"}}]}"#)
+                .expect("fixture");
+        assert_eq!(fixture.exchanges[0].response.content.as_deref(), Some("Hi"));
+        assert!(!fixture.exchanges[0].response.has_tool_calls());
+    }
+}
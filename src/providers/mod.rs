@@ -1,4 +1,95 @@
+pub mod aws;
 pub mod base;
+pub mod bedrock;
+pub mod cache;
+pub mod capabilities;
+pub mod embeddings;
+pub mod error;
+pub mod gemini;
 pub mod litellm;
+pub mod mock;
+pub mod models;
 pub mod openai;
+pub mod params;
+pub mod ratelimit;
+pub mod response_cache;
+pub mod retry;
+pub mod swap;
 pub mod transcription;
+
+use crate::config::Config;
+use base::LLMProvider;
+use bedrock::BedrockProvider;
+use gemini::GeminiProvider;
+use litellm::LiteLLMProvider;
+use params::ModelRules;
+use std::sync::Arc;
+
+/// The provider `config` selects for `model`, with rate limits, retries,
+/// usage recording and, with `providers.cache`, replayed answers. Under
+/// `--mock` or `--record` the provider itself is swapped for or wrapped by
+/// a fixture.
+pub fn build_provider(config: &Config, model: &str, api_key: String) -> Arc<dyn LLMProvider> {
+    let provider_name = config.get_provider_name(Some(model));
+    let model_params = ModelRules::new(&config.models);
+    let wrap = |provider: Arc<dyn LLMProvider>| {
+        let limited = ratelimit::wrap_with_rate_limit(
+            mock::wrap_provider(provider),
+            provider_name.as_deref(),
+            &config.providers.rate_limits,
+        );
+        let recorded = crate::usage::wrap_provider(
+            retry::wrap_with_retry(limited, &config.providers.retry),
+            config.budget.model_prices(),
+        );
+        response_cache::wrap_with_cache(recorded, config.providers.cache)
+    };
+    // `bedrock/` models go to Bedrock whatever their family (`claude`, ...).
+    if model
+        .strip_prefix("litellm/")
+        .unwrap_or(model)
+        .starts_with("bedrock/")
+    {
+        let bedrock = &config.providers.bedrock;
+        return wrap(Arc::new(
+            BedrockProvider::new(
+                Some(bedrock.region.clone()),
+                bedrock.api_base.clone(),
+                model.to_string(),
+                bedrock.extra_headers.clone(),
+            )
+            .with_api_key(Some(bedrock.api_key.clone()))
+            .with_model_params(model_params),
+        ));
+    }
+    let api_base = config.get_api_base(Some(model));
+    let provider_config = config.get_provider(Some(model));
+    let extra_headers = provider_config.and_then(|p| p.extra_headers.clone());
+    let gateway_options = provider_config.and_then(|p| p.gateway_options.clone());
+    let native_search = provider_config.is_some_and(|p| p.native_search);
+    // Google's OpenAI-compatible endpoint (`.../openai`) stays on the
+    // generic path.
+    if provider_name.as_deref() == Some("gemini")
+        && api_base
+            .as_deref()
+            .is_none_or(|base| !base.trim_end_matches('/').ends_with("/openai"))
+    {
+        return wrap(Arc::new(
+            GeminiProvider::new(api_key, api_base, model.to_string(), extra_headers)
+                .with_safety_settings(provider_config.and_then(|p| p.safety_settings.clone()))
+                .with_model_params(model_params),
+        ));
+    }
+    wrap(Arc::new(
+        LiteLLMProvider::new(
+            api_key,
+            api_base,
+            model.to_string(),
+            extra_headers,
+            provider_name.as_deref(),
+        )
+        .with_gateway_options(gateway_options)
+        .with_native_search(native_search)
+        .with_model_params(model_params),
+    ))
+}
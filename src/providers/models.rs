@@ -0,0 +1,330 @@
+//! What the configured keys can run (`nanobot models`).
+//!
+//! Each provider with a key is asked for its model list: `GET /models` on
+//! OpenAI-compatible APIs and Anthropic, `models` on Gemini. Models are
+//! annotated with tool calling, vision and context window, from the listing
+//! when the provider reports them (OpenRouter, Gemini) and from
+//! `Capabilities::for_model` otherwise. Lists are cached for a day in
+//! `~/.nanobot/cache/models.json`.
+
+use crate::config::{Config, ProviderConfig};
+use crate::providers::capabilities::Capabilities;
+use crate::utils::get_data_path;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const CACHE_HOURS: i64 = 24;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Listed models that cannot chat (embeddings, speech, images, moderation).
+const NON_CHAT: &[&str] = &[
+    "embed",
+    "whisper",
+    "tts",
+    "dall-e",
+    "moderation",
+    "transcribe",
+    "image",
+    "rerank",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Api {
+    OpenAI,
+    Anthropic,
+    Gemini,
+}
+
+/// How to reach each provider's model list when `apiBase` is not set.
+const ENDPOINTS: &[(&str, Api, &str)] = &[
+    ("openrouter", Api::OpenAI, "https://openrouter.ai/api/v1"),
+    ("aihubmix", Api::OpenAI, "https://aihubmix.com/v1"),
+    ("siliconflow", Api::OpenAI, "https://api.siliconflow.cn/v1"),
+    (
+        "volcengine",
+        Api::OpenAI,
+        "https://ark.cn-beijing.volces.com/api/v3",
+    ),
+    ("anthropic", Api::Anthropic, "https://api.anthropic.com/v1"),
+    ("openai", Api::OpenAI, "https://api.openai.com/v1"),
+    ("deepseek", Api::OpenAI, "https://api.deepseek.com"),
+    ("gemini", Api::Gemini, super::gemini::DEFAULT_API_BASE),
+    ("minimax", Api::OpenAI, "https://api.minimax.io/v1"),
+    ("zhipu", Api::OpenAI, "https://open.bigmodel.cn/api/paas/v4"),
+    (
+        "dashscope",
+        Api::OpenAI,
+        "https://dashscope.aliyuncs.com/compatible-mode/v1",
+    ),
+    ("moonshot", Api::OpenAI, "https://api.moonshot.ai/v1"),
+    ("vllm", Api::OpenAI, ""),
+    ("groq", Api::OpenAI, "https://api.groq.com/openai/v1"),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub id: String,
+    pub tools: bool,
+    pub vision: bool,
+    pub max_context: u32,
+}
+
+impl ModelInfo {
+    /// Capabilities from the model table, overridden by what the listing says.
+    fn annotate(id: &str, item: &Value) -> Self {
+        let known = Capabilities::for_model(id);
+        let context = ["context_length", "inputTokenLimit", "context_window"]
+            .iter()
+            .find_map(|key| item.get(*key).and_then(Value::as_u64))
+            .map(|tokens| tokens.min(u32::MAX as u64) as u32);
+        let vision = item
+            .pointer("/architecture/input_modalities")
+            .and_then(Value::as_array)
+            .map(|modalities| modalities.iter().any(|m| m == "image"));
+        let tools = item
+            .get("supported_parameters")
+            .and_then(Value::as_array)
+            .map(|params| params.iter().any(|p| p == "tools"));
+        Self {
+            id: id.to_string(),
+            tools: tools.unwrap_or(known.tools),
+            vision: vision.unwrap_or(known.vision),
+            max_context: context.unwrap_or(known.max_context),
+        }
+    }
+
+    /// `gpt-4o  tools vision  128k`
+    pub fn line(&self, width: usize) -> String {
+        let mut features = Vec::new();
+        if self.tools {
+            features.push("tools");
+        }
+        if self.vision {
+            features.push("vision");
+        }
+        format!(
+            "{:width$}  {:12}  {}k",
+            self.id,
+            features.join(" "),
+            self.max_context / 1000
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderModels {
+    pub provider: String,
+    pub fetched_at: DateTime<Local>,
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
+    /// Why the list could not be fetched.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The chat models in a model list response, sorted by id.
+fn parse_models(api: Api, body: &Value) -> Vec<ModelInfo> {
+    let (items, id_key) = match api {
+        Api::Gemini => (body.get("models"), "name"),
+        Api::OpenAI | Api::Anthropic => (body.get("data"), "id"),
+    };
+    let mut models = items
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|item| {
+            // Gemini lists embedding and other models next to chat ones.
+            api != Api::Gemini
+                || item
+                    .get("supportedGenerationMethods")
+                    .and_then(Value::as_array)
+                    .is_some_and(|methods| methods.iter().any(|m| m == "generateContent"))
+        })
+        .filter_map(|item| {
+            let id = item.get(id_key).and_then(Value::as_str)?;
+            let id = id.strip_prefix("models/").unwrap_or(id);
+            let lower = id.to_ascii_lowercase();
+            (!NON_CHAT.iter().any(|fragment| lower.contains(fragment)))
+                .then(|| ModelInfo::annotate(id, item))
+        })
+        .collect::<Vec<_>>();
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    models.dedup_by(|a, b| a.id == b.id);
+    models
+}
+
+async fn fetch_models(
+    client: &reqwest::Client,
+    name: &str,
+    provider: &ProviderConfig,
+    api_key: &str,
+) -> Result<Vec<ModelInfo>> {
+    let (api, default_base) = ENDPOINTS
+        .iter()
+        .find(|(provider, ..)| *provider == name)
+        .map(|&(_, api, base)| (api, base))
+        .context("model listing is not supported for this provider")?;
+    let base = provider
+        .api_base
+        .as_deref()
+        .filter(|base| !base.trim().is_empty())
+        .unwrap_or(default_base)
+        .trim_end_matches('/');
+    if base.is_empty() {
+        bail!("set providers.{name}.apiBase to list its models");
+    }
+    let mut request = match api {
+        Api::OpenAI => client.get(format!("{base}/models")).bearer_auth(api_key),
+        Api::Anthropic => client
+            .get(format!("{base}/models?limit=1000"))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION),
+        Api::Gemini => client
+            .get(format!("{base}/models?pageSize=1000"))
+            .header("x-goog-api-key", api_key),
+    };
+    for (key, value) in provider.extra_headers.iter().flatten() {
+        request = request.header(key, value);
+    }
+    let body = request
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+    Ok(parse_models(api, &body))
+}
+
+fn cache_path() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("cache").join("models.json"))
+}
+
+fn load_cache() -> BTreeMap<String, ProviderModels> {
+    cache_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(cache: &BTreeMap<String, ProviderModels>) -> Result<()> {
+    let path = cache_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(cache)?)?;
+    Ok(())
+}
+
+/// Model lists of every provider with a key (or just `only`), from the cache
+/// when it is less than a day old and `refresh` is not set. Failed lookups are
+/// reported in `error` and retried next time.
+pub async fn list_models(
+    config: &Config,
+    only: Option<&str>,
+    refresh: bool,
+) -> Result<Vec<ProviderModels>> {
+    let providers = config
+        .configured_providers()
+        .into_iter()
+        .filter(|(name, ..)| only.is_none_or(|only| only.eq_ignore_ascii_case(name)))
+        .collect::<Vec<_>>();
+    if providers.is_empty() {
+        match only {
+            Some(only) => bail!("provider '{only}' has no API key configured"),
+            None => bail!("no provider has an API key configured"),
+        }
+    }
+    let mut cache = load_cache();
+    let client = crate::network::client();
+    let fresh_after = Local::now() - ChronoDuration::hours(CACHE_HOURS);
+    let mut changed = false;
+    let mut listed = Vec::new();
+    for (name, provider, api_key) in providers {
+        let cached = cache
+            .get(name)
+            .filter(|entry| !refresh && entry.error.is_none() && entry.fetched_at > fresh_after);
+        if let Some(entry) = cached {
+            listed.push(entry.clone());
+            continue;
+        }
+        let (models, error) = match fetch_models(&client, name, provider, &api_key).await {
+            Ok(models) => (models, None),
+            Err(err) => (Vec::new(), Some(format!("{err:#}"))),
+        };
+        let entry = ProviderModels {
+            provider: name.to_string(),
+            fetched_at: Local::now(),
+            models,
+            error,
+        };
+        cache.insert(name.to_string(), entry.clone());
+        changed = true;
+        listed.push(entry);
+    }
+    if changed && let Err(err) = save_cache(&cache) {
+        tracing::warn!("failed to cache model lists: {err:#}");
+    }
+    Ok(listed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_and_annotates_model_lists() {
+        let openrouter = json!({"data": [
+            {"id": "openai/gpt-4o", "context_length": 128000,
+             "architecture": {"input_modalities": ["text", "image"]},
+             "supported_parameters": ["tools", "temperature"]},
+            {"id": "some/small-model", "context_length": 8192,
+             "architecture": {"input_modalities": ["text"]},
+             "supported_parameters": ["temperature"]}
+        ]});
+        let models = parse_models(Api::OpenAI, &openrouter);
+        assert_eq!(
+            models,
+            [
+                ModelInfo {
+                    id: "openai/gpt-4o".to_string(),
+                    tools: true,
+                    vision: true,
+                    max_context: 128_000,
+                },
+                ModelInfo {
+                    id: "some/small-model".to_string(),
+                    tools: false,
+                    vision: false,
+                    max_context: 8192,
+                },
+            ]
+        );
+        assert_eq!(models[0].line(14), "openai/gpt-4o   tools vision  128k");
+
+        let openai = json!({"data": [{"id": "text-embedding-3-small"}, {"id": "gpt-4o-mini"}]});
+        let models = parse_models(Api::OpenAI, &openai);
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].max_context, 128_000);
+
+        let gemini = json!({"models": [
+            {"name": "models/gemini-2.5-flash", "inputTokenLimit": 1048576,
+             "supportedGenerationMethods": ["generateContent", "countTokens"]},
+            {"name": "models/gemini-embedding-001", "supportedGenerationMethods": ["embedContent"]}
+        ]});
+        let models = parse_models(Api::Gemini, &gemini);
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "gemini-2.5-flash");
+        assert!(models[0].vision);
+    }
+}
@@ -1,9 +1,14 @@
-use crate::providers::base::{LLMProvider, LLMResponse, ToolCallRequest};
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse, ToolCallRequest};
+use crate::providers::cache;
+use crate::providers::capabilities::Capabilities;
+use crate::providers::error::ProviderError;
+use crate::providers::params::ModelRules;
 use anyhow::Context;
 use async_trait::async_trait;
 use reqwest::Client;
 use serde_json::{Map, Value, json};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use tracing::{debug, warn};
 
 #[derive(Clone)]
 pub struct OpenAIProvider {
@@ -11,6 +16,8 @@ pub struct OpenAIProvider {
     api_base: String,
     default_model: String,
     extra_headers: HashMap<String, String>,
+    extra_body: Map<String, Value>,
+    model_params: ModelRules,
     client: Client,
 }
 
@@ -26,22 +33,38 @@ impl OpenAIProvider {
             api_base: api_base.unwrap_or_else(|| "https://api.openai.com/v1".to_string()),
             default_model: default_model.into(),
             extra_headers: extra_headers.unwrap_or_default(),
-            client: Client::new(),
+            extra_body: Map::new(),
+            model_params: ModelRules::builtin(),
+            client: crate::network::client(),
         }
     }
-}
 
-#[async_trait]
-impl LLMProvider for OpenAIProvider {
-    async fn chat(
+    /// Fields merged into every request body, e.g. gateway options.
+    pub fn with_extra_body(mut self, extra_body: Map<String, Value>) -> Self {
+        self.extra_body = extra_body;
+        self
+    }
+
+    /// Per-model settings (`models.<pattern>`) applied to every request.
+    pub fn with_model_params(mut self, rules: ModelRules) -> Self {
+        self.model_params = rules;
+        self
+    }
+
+    fn request_body(
         &self,
         messages: &[Value],
         tools: Option<&[Value]>,
-        model: Option<&str>,
+        model_name: &str,
         max_tokens: u32,
         temperature: f32,
-    ) -> anyhow::Result<LLMResponse> {
-        let model_name = model.unwrap_or(&self.default_model).to_string();
+        options: &ChatOptions,
+    ) -> Value {
+        let messages = if cache::takes_breakpoints(model_name) {
+            cache::with_breakpoints(messages)
+        } else {
+            messages.to_vec()
+        };
         let mut body = json!({
             "model": model_name,
             "messages": messages,
@@ -51,35 +74,228 @@ impl LLMProvider for OpenAIProvider {
 
         if let Some(tool_defs) = tools {
             body["tools"] = Value::Array(tool_defs.to_vec());
-            body["tool_choice"] = Value::String("auto".to_string());
+            body["tool_choice"] = options.tool_choice.clone().unwrap_or_default().to_value();
+            if let Some(parallel) = options.parallel_tool_calls {
+                body["parallel_tool_calls"] = Value::Bool(parallel);
+            }
+        }
+        for (key, value) in &self.extra_body {
+            body[key] = value.clone();
         }
+        self.model_params
+            .resolve(model_name)
+            .apply_openai(&mut body);
+        body
+    }
 
+    async fn send(&self, body: &Value) -> anyhow::Result<reqwest::Response> {
         let url = format!("{}/chat/completions", self.api_base.trim_end_matches('/'));
-        let mut req = self.client.post(url).bearer_auth(&self.api_key).json(&body);
+        let mut req = self.client.post(url).bearer_auth(&self.api_key).json(body);
         for (k, v) in &self.extra_headers {
             req = req.header(k, v);
         }
-        let response = req
-            .send()
-            .await
-            .context("failed to call OpenAI-compatible endpoint")?;
-
+        let response = req.send().await.map_err(|err| ProviderError::Network {
+            message: err.to_string(),
+        })?;
         let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+        let payload = response.text().await.unwrap_or_default();
+        let err = ProviderError::from_status(status.as_u16(), retry_after.as_deref(), &payload);
+        warn!(status = status.as_u16(), "LLM request failed: {err}");
+        Err(err.into())
+    }
+}
+
+/// URL of an `image_url` part: `{"type": "image_url", "image_url": {"url": ..}}`.
+fn image_part_url(part: &Value) -> Option<String> {
+    let image_url = part.get("image_url")?;
+    image_url
+        .get("url")
+        .or(Some(image_url))
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+}
+
+/// Text and generated images of a response message. Images arrive in an
+/// `images` list (OpenRouter) or as parts of an array `content` (Gemini).
+fn message_content(message: &Value) -> (Option<String>, Vec<String>) {
+    let mut images = message
+        .get("images")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(image_part_url)
+        .collect::<Vec<_>>();
+    let content = match message.get("content") {
+        Some(Value::String(text)) => Some(text.clone()),
+        Some(Value::Array(parts)) => {
+            let mut texts = Vec::new();
+            for part in parts {
+                match part.get("type").and_then(Value::as_str) {
+                    Some("text") => texts.extend(part.get("text").and_then(Value::as_str)),
+                    Some("image_url") => images.extend(image_part_url(part)),
+                    _ => {}
+                }
+            }
+            (!texts.is_empty()).then(|| texts.join("\n"))
+        }
+        _ => None,
+    };
+    (content, images)
+}
+
+/// The reasoning a message or delta carries: `reasoning_content` (DeepSeek,
+/// Qwen, LiteLLM) or `reasoning` (OpenRouter, Ollama, vLLM).
+fn reasoning_text(message: &Value) -> Option<&str> {
+    ["reasoning_content", "reasoning"]
+        .iter()
+        .find_map(|key| message.get(*key).and_then(Value::as_str))
+        .filter(|text| !text.is_empty())
+}
+
+fn parse_arguments(args_raw: &str) -> Map<String, Value> {
+    let args_value: Value =
+        serde_json::from_str(args_raw).unwrap_or_else(|_| json!({ "raw": args_raw }));
+    args_value.as_object().cloned().unwrap_or_default()
+}
+
+/// Folds `chat.completion.chunk` events into a complete response.
+#[derive(Default)]
+struct StreamAccumulator {
+    content: String,
+    reasoning: String,
+    // index -> (id, name, arguments)
+    tool_calls: BTreeMap<u64, (String, String, String)>,
+    finish_reason: Option<String>,
+    usage: Map<String, Value>,
+    images: Vec<String>,
+}
+
+impl StreamAccumulator {
+    /// Applies one chunk and returns its content delta, if any.
+    fn apply(&mut self, chunk: &Value) -> Option<String> {
+        if let Some(usage) = chunk.get("usage").and_then(Value::as_object) {
+            self.usage = usage.clone();
+        }
+        let choice = chunk.get("choices")?.as_array()?.first()?;
+        if let Some(reason) = choice.get("finish_reason").and_then(Value::as_str) {
+            self.finish_reason = Some(reason.to_string());
+        }
+        let delta = choice.get("delta")?;
+        self.images.extend(
+            delta
+                .get("images")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(image_part_url),
+        );
+        if let Some(reasoning) = reasoning_text(delta) {
+            self.reasoning.push_str(reasoning);
+        }
+        for call in delta
+            .get("tool_calls")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let index = call.get("index").and_then(Value::as_u64).unwrap_or(0);
+            let entry = self.tool_calls.entry(index).or_default();
+            if let Some(id) = call.get("id").and_then(Value::as_str) {
+                entry.0 = id.to_string();
+            }
+            if let Some(function) = call.get("function") {
+                if let Some(name) = function.get("name").and_then(Value::as_str) {
+                    entry.1.push_str(name);
+                }
+                if let Some(args) = function.get("arguments").and_then(Value::as_str) {
+                    entry.2.push_str(args);
+                }
+            }
+        }
+        let text = delta.get("content").and_then(Value::as_str)?;
+        self.content.push_str(text);
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    fn finish(mut self) -> LLMResponse {
+        cache::normalize_usage(&mut self.usage);
+        let tool_calls = self
+            .tool_calls
+            .into_values()
+            .filter(|(_, name, _)| !name.is_empty())
+            .map(|(id, name, args)| ToolCallRequest {
+                id,
+                name,
+                arguments: parse_arguments(if args.trim().is_empty() { "{}" } else { &args }),
+            })
+            .collect();
+        LLMResponse {
+            content: (!self.content.is_empty()).then_some(self.content),
+            tool_calls,
+            finish_reason: self.finish_reason.unwrap_or_else(|| "stop".to_string()),
+            usage: self.usage,
+            reasoning_content: (!self.reasoning.is_empty()).then_some(self.reasoning),
+            images: self.images,
+        }
+        .split_reasoning()
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAIProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> anyhow::Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> anyhow::Result<LLMResponse> {
+        let model_name = model.unwrap_or(&self.default_model).to_string();
+        let body = self.request_body(
+            messages,
+            tools,
+            &model_name,
+            max_tokens,
+            temperature,
+            options,
+        );
+        debug!(model = %model_name, messages = messages.len(), "sending chat completion request");
+        let response = self.send(&body).await?;
+
         let payload: Value = response
             .json()
             .await
             .context("failed to parse provider response as JSON")?;
 
-        if !status.is_success() {
-            return Ok(LLMResponse {
-                content: Some(format!("Error calling LLM: {}", payload)),
-                tool_calls: Vec::new(),
-                finish_reason: "error".to_string(),
-                usage: Map::new(),
-                reasoning_content: None,
-            });
-        }
-
         let choice = payload
             .get("choices")
             .and_then(Value::as_array)
@@ -88,14 +304,8 @@ impl LLMProvider for OpenAIProvider {
             .unwrap_or_else(|| json!({}));
 
         let message = choice.get("message").cloned().unwrap_or_else(|| json!({}));
-        let content = message
-            .get("content")
-            .and_then(Value::as_str)
-            .map(ToOwned::to_owned);
-        let reasoning_content = message
-            .get("reasoning_content")
-            .and_then(Value::as_str)
-            .map(ToOwned::to_owned);
+        let (content, images) = message_content(&message);
+        let reasoning_content = reasoning_text(&message).map(ToOwned::to_owned);
 
         let tool_calls = message
             .get("tool_calls")
@@ -111,13 +321,10 @@ impl LLMProvider for OpenAIProvider {
                             .get("arguments")
                             .and_then(Value::as_str)
                             .unwrap_or("{}");
-                        let args_value: Value = serde_json::from_str(args_raw)
-                            .unwrap_or_else(|_| json!({ "raw": args_raw }));
-                        let arguments = args_value.as_object().cloned().unwrap_or_default();
                         Some(ToolCallRequest {
                             id,
                             name,
-                            arguments,
+                            arguments: parse_arguments(args_raw),
                         })
                     })
                     .collect::<Vec<_>>()
@@ -130,11 +337,12 @@ impl LLMProvider for OpenAIProvider {
             .unwrap_or("stop")
             .to_string();
 
-        let usage = payload
+        let mut usage = payload
             .get("usage")
             .and_then(Value::as_object)
             .cloned()
             .unwrap_or_default();
+        cache::normalize_usage(&mut usage);
 
         Ok(LLMResponse {
             content,
@@ -142,10 +350,169 @@ impl LLMProvider for OpenAIProvider {
             finish_reason,
             usage,
             reasoning_content,
-        })
+            images,
+        }
+        .split_reasoning())
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> anyhow::Result<LLMResponse> {
+        let model_name = model.unwrap_or(&self.default_model).to_string();
+        let mut body = self.request_body(
+            messages,
+            tools,
+            &model_name,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        );
+        body["stream"] = Value::Bool(true);
+        body["stream_options"] = json!({ "include_usage": true });
+        debug!(model = %model_name, messages = messages.len(), "sending streaming chat request");
+        let mut response = self.send(&body).await?;
+
+        let mut accumulator = StreamAccumulator::default();
+        let mut pending = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .context("failed to read streaming response")?
+        {
+            pending.extend_from_slice(&chunk);
+            // Split on raw bytes so multi-byte characters spanning chunks stay intact.
+            while let Some(pos) = pending.iter().position(|b| *b == b'\n') {
+                let line = pending.drain(..=pos).collect::<Vec<_>>();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let data = data.trim();
+                if data == "[DONE]" {
+                    continue;
+                }
+                if let Ok(event) = serde_json::from_str::<Value>(data)
+                    && let Some(text) = accumulator.apply(&event)
+                {
+                    on_delta(&text);
+                }
+            }
+        }
+        Ok(accumulator.finish())
     }
 
     fn default_model(&self) -> &str {
         &self.default_model
     }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        Capabilities::for_model(model).with_streaming(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::base::ToolChoice;
+
+    #[test]
+    fn generated_images_are_collected_from_both_shapes() {
+        let openrouter = json!({
+            "content": "Here it is.",
+            "images": [{"type": "image_url", "image_url": {"url": "data:image/png;base64,AA=="}}]
+        });
+        assert_eq!(
+            message_content(&openrouter),
+            (
+                Some("Here it is.".to_string()),
+                vec!["data:image/png;base64,AA==".to_string()]
+            )
+        );
+
+        let parts = json!({"content": [
+            {"type": "text", "text": "A cat:"},
+            {"type": "image_url", "image_url": {"url": "https://x.io/cat.png"}}
+        ]});
+        let (content, images) = message_content(&parts);
+        assert_eq!(content.as_deref(), Some("A cat:"));
+        assert_eq!(images, ["https://x.io/cat.png"]);
+    }
+
+    #[test]
+    fn request_body_applies_tool_options() {
+        let provider = OpenAIProvider::new("key", None, "gpt-4o", None);
+        let tools = [json!({"type": "function", "function": {"name": "exec"}})];
+        let body = provider.request_body(
+            &[],
+            Some(&tools),
+            "gpt-4o",
+            100,
+            0.0,
+            &ChatOptions::default(),
+        );
+        assert_eq!(body["tool_choice"], "auto");
+        assert!(body.get("parallel_tool_calls").is_none());
+
+        let options = ChatOptions {
+            tool_choice: Some(ToolChoice::Tool("exec".to_string())),
+            parallel_tool_calls: Some(false),
+        };
+        let body = provider.request_body(&[], Some(&tools), "gpt-4o", 100, 0.0, &options);
+        assert_eq!(body["tool_choice"]["function"]["name"], "exec");
+        assert_eq!(body["parallel_tool_calls"], false);
+
+        let body = provider.request_body(&[], None, "gpt-4o", 100, 0.0, &options);
+        assert!(body.get("tool_choice").is_none());
+    }
+
+    #[test]
+    fn accumulates_stream_chunks() {
+        let chunks = [
+            json!({"choices": [{"delta": {"role": "assistant", "content": "Hel"}}]}),
+            json!({"choices": [{"delta": {"content": "lo"}}]}),
+            json!({"choices": [{"delta": {"tool_calls": [
+                {"index": 0, "id": "call_1", "function": {"name": "read_file", "arguments": "{\"pa"}}
+            ]}}]}),
+            json!({"choices": [{"delta": {"tool_calls": [
+                {"index": 0, "function": {"arguments": "th\": \"a.md\"}"}}
+            ]}, "finish_reason": "tool_calls"}]}),
+            json!({"choices": [], "usage": {"prompt_tokens": 12, "completion_tokens": 3}}),
+        ];
+        let mut accumulator = StreamAccumulator::default();
+        let deltas = chunks
+            .iter()
+            .filter_map(|chunk| accumulator.apply(chunk))
+            .collect::<Vec<_>>();
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+
+        let response = accumulator.finish();
+        assert_eq!(response.content.as_deref(), Some("Hello"));
+        assert_eq!(response.finish_reason, "tool_calls");
+        assert_eq!(response.tool_calls[0].name, "read_file");
+        assert_eq!(response.tool_calls[0].arguments["path"], "a.md");
+        assert_eq!(response.token_usage(), (12, 3));
+    }
+
+    #[test]
+    fn reasoning_comes_from_either_field_or_think_tags() {
+        let mut accumulator = StreamAccumulator::default();
+        accumulator.apply(&json!({"choices": [{"delta": {"reasoning": "Thinking"}}]}));
+        accumulator.apply(&json!({"choices": [{"delta": {"content": "Done"}}]}));
+        let response = accumulator.finish();
+        assert_eq!(response.reasoning_content.as_deref(), Some("Thinking"));
+        assert_eq!(response.content.as_deref(), Some("Done"));
+
+        let mut accumulator = StreamAccumulator::default();
+        accumulator
+            .apply(&json!({"choices": [{"delta": {"content": "<think>Hmm.</think>\n\n42"}}]}));
+        let response = accumulator.finish();
+        assert_eq!(response.reasoning_content.as_deref(), Some("Hmm."));
+        assert_eq!(response.content.as_deref(), Some("42"));
+    }
 }
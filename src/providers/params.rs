@@ -0,0 +1,246 @@
+//! Per-model generation settings (`models.<pattern>` in config.json).
+//!
+//! Every rule whose glob matches the model, either the full name
+//! (`moonshot/kimi-k2.5`) or the part after the last `/`, applies, with more
+//! specific patterns (more literal characters) winning field by field. The
+//! built-in rules cover models that reject the usual settings and come
+//! first, so config can override them.
+
+use crate::config::ModelParams;
+use glob::{MatchOptions, Pattern};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+/// Models that only accept particular settings.
+fn builtin_rules() -> Vec<(&'static str, ModelParams)> {
+    vec![(
+        "kimi-k2.5*",
+        ModelParams {
+            temperature: Some(1.0),
+            ..Default::default()
+        },
+    )]
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModelRules {
+    /// Least specific first.
+    rules: Vec<(Pattern, ModelParams)>,
+}
+
+impl ModelRules {
+    /// The built-in rules followed by `config`; invalid globs are skipped
+    /// with a warning.
+    pub fn new(config: &BTreeMap<String, ModelParams>) -> Self {
+        let mut rules = Vec::new();
+        let entries = builtin_rules()
+            .into_iter()
+            .map(|(pattern, params)| (pattern.to_string(), params, true))
+            .chain(
+                config
+                    .iter()
+                    .map(|(pattern, params)| (pattern.clone(), params.clone(), false)),
+            );
+        for (pattern, params, builtin) in entries {
+            match Pattern::new(pattern.trim()) {
+                Ok(glob) => rules.push((glob, params, builtin)),
+                Err(err) => tracing::warn!("ignoring models.{pattern}: {err}"),
+            }
+        }
+        // Stable, so a configured rule beats a built-in one of the same weight.
+        rules.sort_by_key(|(glob, _, builtin)| (specificity(glob.as_str()), !builtin));
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|(glob, params, _)| (glob, params))
+                .collect(),
+        }
+    }
+
+    /// Only the built-in rules.
+    pub fn builtin() -> Self {
+        Self::new(&BTreeMap::new())
+    }
+
+    /// The merged settings for `model`; empty when no rule matches.
+    pub fn resolve(&self, model: &str) -> ModelParams {
+        let options = MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+        let short = model.rsplit('/').next().unwrap_or(model);
+        let mut params = ModelParams::default();
+        for (glob, rule) in &self.rules {
+            if glob.matches_with(model, options) || glob.matches_with(short, options) {
+                params.merge(rule);
+            }
+        }
+        params
+    }
+}
+
+/// `f32` settings as the decimal written in config (0.6, not 0.6000000238).
+fn float(value: f32) -> Value {
+    json!(value.to_string().parse::<f64>().unwrap_or(f64::from(value)))
+}
+
+fn specificity(pattern: &str) -> usize {
+    pattern
+        .chars()
+        .filter(|c| !matches!(c, '*' | '?' | '[' | ']'))
+        .count()
+}
+
+impl ModelParams {
+    /// Sets the fields of an OpenAI-style chat completions body.
+    pub fn apply_openai(&self, body: &mut Value) {
+        if let Some(temperature) = self.temperature {
+            body["temperature"] = float(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if let Some(top_p) = self.top_p {
+            body["top_p"] = float(top_p);
+        }
+        if !self.stop.is_empty() {
+            body["stop"] = json!(self.stop);
+        }
+        if !self.reasoning_effort.is_empty() {
+            body["reasoning_effort"] = json!(self.reasoning_effort);
+        }
+        // The Anthropic-style field LiteLLM and most compatible gateways take.
+        match self.thinking_budget_tokens {
+            Some(0) => body["thinking"] = json!({ "type": "disabled" }),
+            Some(budget) => {
+                body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget })
+            }
+            None => {}
+        }
+    }
+
+    /// Sets the fields of a Gemini `generationConfig`; without an explicit
+    /// thinking budget, reasoning effort picks one.
+    pub fn apply_gemini(&self, config: &mut Value) {
+        if let Some(temperature) = self.temperature {
+            config["temperature"] = float(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            config["maxOutputTokens"] = json!(max_tokens);
+        }
+        if let Some(top_p) = self.top_p {
+            config["topP"] = float(top_p);
+        }
+        if !self.stop.is_empty() {
+            config["stopSequences"] = json!(self.stop);
+        }
+        let budget = self
+            .thinking_budget_tokens
+            .or(match self.reasoning_effort.as_str() {
+                "none" => Some(0),
+                "low" => Some(1024),
+                "medium" => Some(8192),
+                "high" => Some(24576),
+                _ => None,
+            });
+        if let Some(budget) = budget {
+            config["thinkingConfig"] = json!({ "thinkingBudget": budget });
+        }
+    }
+
+    /// Sets the fields of a Bedrock Converse `inferenceConfig`.
+    pub fn apply_bedrock(&self, config: &mut Value) {
+        if let Some(temperature) = self.temperature {
+            config["temperature"] = float(temperature);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            config["maxTokens"] = json!(max_tokens);
+        }
+        if let Some(top_p) = self.top_p {
+            config["topP"] = float(top_p);
+        }
+        if !self.stop.is_empty() {
+            config["stopSequences"] = json!(self.stop);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_matching_rules_by_specificity() {
+        let mut config = BTreeMap::new();
+        config.insert(
+            "*".to_string(),
+            ModelParams {
+                max_tokens: Some(2048),
+                top_p: Some(0.9),
+                ..Default::default()
+            },
+        );
+        config.insert(
+            "openai/o*".to_string(),
+            ModelParams {
+                temperature: Some(1.0),
+                reasoning_effort: "high".to_string(),
+                ..Default::default()
+            },
+        );
+        config.insert(
+            "kimi-k2.5".to_string(),
+            ModelParams {
+                temperature: Some(0.6),
+                stop: vec!["<end>".to_string()],
+                ..Default::default()
+            },
+        );
+        let rules = ModelRules::new(&config);
+
+        let o3 = rules.resolve("openai/o3-mini");
+        assert_eq!(o3.temperature, Some(1.0));
+        assert_eq!(o3.max_tokens, Some(2048));
+        assert_eq!(o3.reasoning_effort, "high");
+
+        // The configured exact name beats the built-in `kimi-k2.5*`.
+        let kimi = rules.resolve("moonshot/Kimi-K2.5");
+        assert_eq!(kimi.temperature, Some(0.6));
+        assert_eq!(kimi.top_p, Some(0.9));
+        assert_eq!(
+            ModelRules::builtin()
+                .resolve("kimi-k2.5-preview")
+                .temperature,
+            Some(1.0)
+        );
+        assert_eq!(
+            ModelRules::builtin().resolve("gpt-4o"),
+            ModelParams::default()
+        );
+
+        let mut body = json!({ "temperature": 0.7, "max_tokens": 4096 });
+        kimi.apply_openai(&mut body);
+        assert_eq!(
+            body,
+            json!({ "temperature": 0.6, "max_tokens": 2048, "top_p": 0.9, "stop": ["<end>"] })
+        );
+        let mut generation = json!({});
+        o3.apply_gemini(&mut generation);
+        assert_eq!(generation["thinkingConfig"]["thinkingBudget"], 24576);
+
+        let budget = ModelParams {
+            thinking_budget_tokens: Some(4000),
+            ..o3
+        };
+        budget.apply_gemini(&mut generation);
+        assert_eq!(generation["thinkingConfig"]["thinkingBudget"], 4000);
+        let mut body = json!({});
+        budget.apply_openai(&mut body);
+        assert_eq!(body["reasoning_effort"], "high");
+        assert_eq!(
+            body["thinking"],
+            json!({ "type": "enabled", "budget_tokens": 4000 })
+        );
+    }
+}
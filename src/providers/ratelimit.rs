@@ -0,0 +1,303 @@
+//! Client-side rate limiting (`providers.rateLimits`).
+//!
+//! Each limited model or provider gets a pair of token buckets — requests
+//! and tokens per minute — shared by every provider built for it, so agents,
+//! subagents and cron jobs draw from one budget. A call waits until both
+//! buckets can cover it. Its token cost is estimated from the request size
+//! up front and corrected with the reported usage afterwards.
+
+use crate::config::RateLimitConfig;
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Request characters per estimated token.
+const CHARS_PER_TOKEN: usize = 4;
+
+#[derive(Debug)]
+pub struct Bucket {
+    config: RateLimitConfig,
+    requests: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    pub fn new(config: RateLimitConfig, now: Instant) -> Self {
+        Self {
+            requests: f64::from(config.requests_per_minute),
+            tokens: config.tokens_per_minute as f64,
+            config,
+            refilled: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let minutes = now.saturating_duration_since(self.refilled).as_secs_f64() / 60.0;
+        self.refilled = now;
+        let rpm = f64::from(self.config.requests_per_minute);
+        let tpm = self.config.tokens_per_minute as f64;
+        self.requests = (self.requests + rpm * minutes).min(rpm);
+        self.tokens = (self.tokens + tpm * minutes).min(tpm);
+    }
+
+    /// Takes one request and `tokens` (capped at the bucket size), or says
+    /// how long to wait before both are available.
+    pub fn take(&mut self, tokens: u64, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        let rpm = f64::from(self.config.requests_per_minute);
+        let tpm = self.config.tokens_per_minute as f64;
+        let tokens = (tokens as f64).min(tpm);
+        let request_wait = if rpm > 0.0 && self.requests < 1.0 {
+            (1.0 - self.requests) / rpm * 60.0
+        } else {
+            0.0
+        };
+        let token_wait = if tpm > 0.0 && self.tokens < tokens {
+            (tokens - self.tokens) / tpm * 60.0
+        } else {
+            0.0
+        };
+        let wait = request_wait.max(token_wait);
+        if wait > 0.0 {
+            return Err(Duration::from_secs_f64(wait));
+        }
+        if rpm > 0.0 {
+            self.requests -= 1.0;
+        }
+        if tpm > 0.0 {
+            self.tokens -= tokens;
+        }
+        Ok(())
+    }
+
+    /// Charges the difference between the estimate and the actual usage;
+    /// the bucket may go into debt, delaying the next call.
+    pub fn settle(&mut self, estimated: u64, actual: u64) {
+        let tpm = self.config.tokens_per_minute as f64;
+        if tpm > 0.0 {
+            let estimated = (estimated as f64).min(tpm);
+            self.tokens = (self.tokens + estimated - actual as f64).clamp(-tpm, tpm);
+        }
+    }
+}
+
+/// The bucket shared by everything limited under `key`.
+fn bucket(key: &str, config: &RateLimitConfig) -> Arc<Mutex<Bucket>> {
+    static BUCKETS: OnceLock<Mutex<HashMap<String, Arc<Mutex<Bucket>>>>> = OnceLock::new();
+    let mut buckets = BUCKETS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let entry = buckets
+        .entry(key.to_string())
+        .or_insert_with(|| Arc::new(Mutex::new(Bucket::new(config.clone(), Instant::now()))));
+    let mut current = entry.lock().unwrap_or_else(|e| e.into_inner());
+    if current.config != *config {
+        *current = Bucket::new(config.clone(), Instant::now());
+    }
+    drop(current);
+    entry.clone()
+}
+
+fn estimate_tokens(messages: &[Value], tools: Option<&[Value]>, max_tokens: u32) -> u64 {
+    let chars = serde_json::to_string(messages).map_or(0, |s| s.len())
+        + tools
+            .and_then(|tools| serde_json::to_string(tools).ok())
+            .map_or(0, |s| s.len());
+    // Half the completion allowance is a fair guess until usage comes back.
+    (chars / CHARS_PER_TOKEN) as u64 + u64::from(max_tokens / 2)
+}
+
+pub struct RateLimitProvider {
+    inner: Arc<dyn LLMProvider>,
+    /// Provider name, for calls whose model has no limit of its own.
+    provider: Option<String>,
+    limits: HashMap<String, RateLimitConfig>,
+}
+
+impl RateLimitProvider {
+    fn limit_for(&self, model: Option<&str>) -> Option<(&str, &RateLimitConfig)> {
+        let model = model.unwrap_or_else(|| self.inner.default_model());
+        let key = self.limits.get_key_value(model).or_else(|| {
+            self.provider
+                .as_deref()
+                .and_then(|p| self.limits.get_key_value(p))
+        })?;
+        Some((key.0.as_str(), key.1))
+    }
+
+    /// Waits for room in the bucket; returns it with the tokens taken.
+    async fn acquire(
+        &self,
+        model: Option<&str>,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        max_tokens: u32,
+    ) -> Option<(Arc<Mutex<Bucket>>, u64)> {
+        let (key, config) = self.limit_for(model)?;
+        let bucket = bucket(key, config);
+        let estimated = estimate_tokens(messages, tools, max_tokens);
+        loop {
+            let taken = bucket
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take(estimated, Instant::now());
+            match taken {
+                Ok(()) => return Some((bucket, estimated)),
+                Err(wait) => {
+                    if wait >= Duration::from_secs(1) {
+                        info!(
+                            limit = key,
+                            "rate limit reached, waiting {}ms",
+                            wait.as_millis()
+                        );
+                    }
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    fn settle(taken: Option<(Arc<Mutex<Bucket>>, u64)>, result: &Result<LLMResponse>) {
+        let Some((bucket, estimated)) = taken else {
+            return;
+        };
+        let actual = match result {
+            Ok(response) => {
+                let (prompt, completion) = response.token_usage();
+                if prompt + completion == 0 {
+                    return;
+                }
+                prompt + completion
+            }
+            // A failed call is unlikely to have used its estimate.
+            Err(_) => 0,
+        };
+        bucket
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .settle(estimated, actual);
+    }
+}
+
+#[async_trait]
+impl LLMProvider for RateLimitProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        let taken = self.acquire(model, messages, tools, max_tokens).await;
+        let result = self
+            .inner
+            .chat(messages, tools, model, max_tokens, temperature)
+            .await;
+        Self::settle(taken, &result);
+        result
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        let taken = self.acquire(model, messages, tools, max_tokens).await;
+        let result = self
+            .inner
+            .chat_with_options(messages, tools, model, max_tokens, temperature, options)
+            .await;
+        Self::settle(taken, &result);
+        result
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        let taken = self.acquire(model, messages, tools, max_tokens).await;
+        let result = self
+            .inner
+            .chat_stream(messages, tools, model, max_tokens, temperature, on_delta)
+            .await;
+        Self::settle(taken, &result);
+        result
+    }
+
+    fn default_model(&self) -> &str {
+        self.inner.default_model()
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.inner.capabilities(model)
+    }
+}
+
+/// Wraps `provider` in the limits that apply to its models, if any.
+pub fn wrap_with_rate_limit(
+    provider: Arc<dyn LLMProvider>,
+    provider_name: Option<&str>,
+    limits: &HashMap<String, RateLimitConfig>,
+) -> Arc<dyn LLMProvider> {
+    if limits.is_empty() {
+        return provider;
+    }
+    Arc::new(RateLimitProvider {
+        inner: provider,
+        provider: provider_name.map(str::to_string),
+        limits: limits.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_pace_requests_and_tokens() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(
+            RateLimitConfig {
+                requests_per_minute: 2,
+                tokens_per_minute: 6_000,
+            },
+            start,
+        );
+        assert!(bucket.take(1_000, start).is_ok());
+        assert!(bucket.take(1_000, start).is_ok());
+        let wait = bucket.take(1_000, start).unwrap_err();
+        assert_eq!(wait.as_secs_f64().round(), 30.0);
+        assert!(bucket.take(1_000, start + Duration::from_secs(30)).is_ok());
+
+        // The second call used far more than estimated: the debt is paid
+        // off before the next one fits.
+        bucket.settle(1_000, 9_000);
+        let wait = bucket
+            .take(1_000, start + Duration::from_secs(60))
+            .unwrap_err();
+        assert_eq!(wait.as_secs_f64().round(), 10.0);
+
+        let mut unlimited = Bucket::new(RateLimitConfig::default(), start);
+        for _ in 0..100 {
+            assert!(unlimited.take(1_000_000, start).is_ok());
+        }
+    }
+}
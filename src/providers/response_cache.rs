@@ -0,0 +1,263 @@
+//! Replaying answers to deterministic calls (`providers.cache`).
+//!
+//! A call at temperature 0 is keyed by the SHA-256 of its model, messages,
+//! tools, options and token limit. The first answer is stored in
+//! `~/.nanobot/cache/responses/<key>.json` and repeated calls get it back
+//! without reaching the provider, so classifier calls like `TurnGuard` and
+//! eval runs are fast and free. Replayed answers carry no usage, so budgets
+//! and usage records only count real calls. `maintenance` prunes the
+//! directory with the other caches.
+
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use crate::utils::get_data_path;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+pub struct ResponseCacheProvider {
+    inner: Arc<dyn LLMProvider>,
+    dir: PathBuf,
+}
+
+impl ResponseCacheProvider {
+    pub fn new(inner: Arc<dyn LLMProvider>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+        }
+    }
+
+    /// The cache key of a call, or `None` when its answer may vary.
+    fn key(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Option<String> {
+        if temperature != 0.0 {
+            return None;
+        }
+        let request = json!({
+            "model": model.unwrap_or(self.inner.default_model()),
+            "messages": messages,
+            "tools": tools,
+            "max_tokens": max_tokens,
+            "options": format!("{options:?}"),
+        });
+        let digest = Sha256::digest(request.to_string().as_bytes());
+        Some(digest.iter().map(|b| format!("{b:02x}")).collect())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+
+    fn load(&self, key: &str) -> Option<LLMResponse> {
+        let raw = std::fs::read_to_string(self.path(key)).ok()?;
+        let mut response = serde_json::from_str::<LLMResponse>(&raw).ok()?;
+        debug!(key, "replaying cached response");
+        response.usage = Map::new();
+        Some(response)
+    }
+
+    fn store(&self, key: &str, response: &LLMResponse) {
+        let empty =
+            response.content.as_deref().is_none_or(str::is_empty) && !response.has_tool_calls();
+        if empty {
+            return;
+        }
+        if let Err(err) = write_entry(&self.path(key), response) {
+            warn!("failed to cache response: {err:#}");
+        }
+    }
+}
+
+fn write_entry(path: &Path, response: &LLMResponse) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string(response)?)?;
+    Ok(())
+}
+
+#[async_trait]
+impl LLMProvider for ResponseCacheProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        let key = self.key(messages, tools, model, max_tokens, temperature, options);
+        if let Some(response) = key.as_deref().and_then(|key| self.load(key)) {
+            return Ok(response);
+        }
+        let response = self
+            .inner
+            .chat_with_options(messages, tools, model, max_tokens, temperature, options)
+            .await?;
+        if let Some(key) = key.as_deref() {
+            self.store(key, &response);
+        }
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        let options = ChatOptions::default();
+        let key = self.key(messages, tools, model, max_tokens, temperature, &options);
+        if let Some(response) = key.as_deref().and_then(|key| self.load(key)) {
+            if let Some(content) = response.content.as_deref().filter(|c| !c.is_empty()) {
+                on_delta(content);
+            }
+            return Ok(response);
+        }
+        let response = self
+            .inner
+            .chat_stream(messages, tools, model, max_tokens, temperature, on_delta)
+            .await?;
+        if let Some(key) = key.as_deref() {
+            self.store(key, &response);
+        }
+        Ok(response)
+    }
+
+    fn default_model(&self) -> &str {
+        self.inner.default_model()
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.inner.capabilities(model)
+    }
+}
+
+/// Where cached responses are kept.
+pub fn response_cache_dir() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("cache").join("responses"))
+}
+
+/// Wraps `provider` in the response cache when `enabled`.
+pub fn wrap_with_cache(provider: Arc<dyn LLMProvider>, enabled: bool) -> Arc<dyn LLMProvider> {
+    if !enabled {
+        return provider;
+    }
+    match response_cache_dir() {
+        Ok(dir) => Arc::new(ResponseCacheProvider::new(provider, dir)),
+        Err(err) => {
+            warn!("response cache unavailable: {err:#}");
+            provider
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Answers with how many calls it has seen.
+    struct Counting {
+        calls: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for Counting {
+        async fn chat(
+            &self,
+            _messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f32,
+        ) -> Result<LLMResponse> {
+            let mut calls = self.calls.lock().unwrap();
+            *calls += 1;
+            Ok(LLMResponse {
+                content: Some(format!("answer {calls}")),
+                tool_calls: Vec::new(),
+                finish_reason: "stop".to_string(),
+                usage: json!({"prompt_tokens": 10, "completion_tokens": 2})
+                    .as_object()
+                    .cloned()
+                    .unwrap_or_default(),
+                reasoning_content: None,
+                images: Vec::new(),
+            })
+        }
+
+        fn default_model(&self) -> &str {
+            "test-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_deterministic_calls_without_usage() {
+        let dir = std::env::temp_dir().join(format!("nanobot-rcache-{}", uuid::Uuid::new_v4()));
+        let inner = Arc::new(Counting {
+            calls: Mutex::new(0),
+        });
+        let cache = ResponseCacheProvider::new(inner.clone(), &dir);
+        let messages = [json!({"role": "user", "content": "yes or no?"})];
+
+        let first = cache
+            .chat(&messages, None, None, 5, 0.0)
+            .await
+            .expect("first");
+        let again = cache
+            .chat(&messages, None, None, 5, 0.0)
+            .await
+            .expect("again");
+        assert_eq!(first.content.as_deref(), Some("answer 1"));
+        assert_eq!(again.content.as_deref(), Some("answer 1"));
+        assert_eq!(first.token_usage(), (10, 2));
+        assert_eq!(again.token_usage(), (0, 0));
+
+        let other = [json!({"role": "user", "content": "maybe?"})];
+        let fresh = cache.chat(&other, None, None, 5, 0.0).await.expect("fresh");
+        assert_eq!(fresh.content.as_deref(), Some("answer 2"));
+        let warm = cache
+            .chat(&messages, None, None, 5, 0.7)
+            .await
+            .expect("warm");
+        assert_eq!(warm.content.as_deref(), Some("answer 3"));
+        assert_eq!(*inner.calls.lock().unwrap(), 3);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
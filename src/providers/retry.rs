@@ -0,0 +1,280 @@
+//! Retrying provider calls with exponential backoff and jitter
+//! (`providers.retry`).
+//!
+//! Rate limits, connection failures and the HTTP statuses in `retryOn` are
+//! retried up to `maxAttempts` times; a `Retry-After` from the provider is
+//! honoured when it fits under `maxDelayMs`. Streaming calls are only
+//! retried while nothing has been streamed yet.
+
+use crate::config::RetryConfig;
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use crate::providers::error::ProviderError;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::warn;
+
+pub struct RetryProvider {
+    inner: Arc<dyn LLMProvider>,
+    config: RetryConfig,
+}
+
+impl RetryProvider {
+    pub fn new(inner: Arc<dyn LLMProvider>, config: RetryConfig) -> Self {
+        Self { inner, config }
+    }
+
+    /// How long to wait before retry number `retry` (1-based) after `err`;
+    /// `None` when the error is not worth retrying.
+    fn delay(&self, err: &anyhow::Error, retry: u32) -> Option<Duration> {
+        let max = Duration::from_millis(self.config.max_delay_ms);
+        let status = match ProviderError::find(err)? {
+            ProviderError::RateLimited {
+                retry_after: Some(wait),
+                ..
+            } if self.config.retry_on.contains(&429) => {
+                return (*wait <= max).then_some(*wait);
+            }
+            ProviderError::RateLimited { .. } => 429,
+            ProviderError::Server { status, .. } => *status,
+            ProviderError::Network { .. } => return Some(backoff(&self.config, retry)),
+            _ => return None,
+        };
+        self.config
+            .retry_on
+            .contains(&status)
+            .then(|| backoff(&self.config, retry))
+    }
+
+    async fn retrying<F, Fut>(&self, call: F, streamed: Option<&AtomicBool>) -> Result<LLMResponse>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<LLMResponse>>,
+    {
+        let attempts = self.config.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            let err = match call().await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            if attempt >= attempts || streamed.is_some_and(|s| s.load(Ordering::Relaxed)) {
+                return Err(err);
+            }
+            let Some(wait) = self.delay(&err, attempt) else {
+                return Err(err);
+            };
+            warn!(
+                attempt,
+                attempts,
+                "provider call failed, retrying in {}ms: {err:#}",
+                wait.as_millis()
+            );
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// `baseDelayMs` doubled per retry and capped at `maxDelayMs`, of which a
+/// random half to all is waited so parallel callers spread out.
+pub fn backoff(config: &RetryConfig, retry: u32) -> Duration {
+    let factor = 1u64 << retry.saturating_sub(1).min(20);
+    let full = config
+        .base_delay_ms
+        .saturating_mul(factor)
+        .min(config.max_delay_ms);
+    let half = full / 2;
+    let jitter = (uuid::Uuid::new_v4().as_u128() % (u128::from(full - half) + 1)) as u64;
+    Duration::from_millis(half + jitter)
+}
+
+#[async_trait]
+impl LLMProvider for RetryProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.retrying(
+            || {
+                self.inner
+                    .chat(messages, tools, model, max_tokens, temperature)
+            },
+            None,
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        self.retrying(
+            || {
+                self.inner.chat_with_options(
+                    messages,
+                    tools,
+                    model,
+                    max_tokens,
+                    temperature,
+                    options,
+                )
+            },
+            None,
+        )
+        .await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        let streamed = AtomicBool::new(false);
+        let delta = |text: &str| {
+            streamed.store(true, Ordering::Relaxed);
+            on_delta(text);
+        };
+        self.retrying(
+            || {
+                self.inner
+                    .chat_stream(messages, tools, model, max_tokens, temperature, &delta)
+            },
+            Some(&streamed),
+        )
+        .await
+    }
+
+    fn default_model(&self) -> &str {
+        self.inner.default_model()
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.inner.capabilities(model)
+    }
+}
+
+/// Wraps `provider` in retries unless `config` turns them off.
+pub fn wrap_with_retry(
+    provider: Arc<dyn LLMProvider>,
+    config: &RetryConfig,
+) -> Arc<dyn LLMProvider> {
+    if config.max_attempts <= 1 {
+        return provider;
+    }
+    Arc::new(RetryProvider::new(provider, config.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Fails with the queued errors, then answers.
+    struct Flaky {
+        errors: Mutex<Vec<ProviderError>>,
+        calls: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for Flaky {
+        async fn chat(
+            &self,
+            _messages: &[Value],
+            _tools: Option<&[Value]>,
+            _model: Option<&str>,
+            _max_tokens: u32,
+            _temperature: f32,
+        ) -> Result<LLMResponse> {
+            *self.calls.lock().unwrap() += 1;
+            match self.errors.lock().unwrap().pop() {
+                Some(err) => Err(err.into()),
+                None => Ok(LLMResponse {
+                    content: Some("ok".to_string()),
+                    tool_calls: Vec::new(),
+                    finish_reason: "stop".to_string(),
+                    usage: Default::default(),
+                    reasoning_content: None,
+                    images: Vec::new(),
+                }),
+            }
+        }
+
+        fn default_model(&self) -> &str {
+            "test-model"
+        }
+    }
+
+    fn flaky(errors: Vec<ProviderError>) -> Arc<Flaky> {
+        Arc::new(Flaky {
+            errors: Mutex::new(errors),
+            calls: Mutex::new(0),
+        })
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_with_growing_delays() {
+        let config = RetryConfig {
+            base_delay_ms: 1,
+            max_delay_ms: 4,
+            ..RetryConfig::default()
+        };
+        let network = || ProviderError::Network {
+            message: "connection reset".to_string(),
+        };
+        let inner = flaky(vec![
+            ProviderError::Server {
+                status: 503,
+                message: "overloaded".to_string(),
+            },
+            network(),
+        ]);
+        let provider = wrap_with_retry(inner.clone(), &config);
+        let response = provider.chat(&[], None, None, 16, 0.0).await.unwrap();
+        assert_eq!(response.content.as_deref(), Some("ok"));
+        assert_eq!(*inner.calls.lock().unwrap(), 3);
+
+        let inner = flaky(vec![network(), network(), network()]);
+        let provider = wrap_with_retry(inner.clone(), &config);
+        assert!(provider.chat(&[], None, None, 16, 0.0).await.is_err());
+        assert_eq!(*inner.calls.lock().unwrap(), 3);
+
+        let inner = flaky(vec![ProviderError::Auth {
+            message: "bad key".to_string(),
+        }]);
+        let provider = wrap_with_retry(inner.clone(), &config);
+        assert!(provider.chat(&[], None, None, 16, 0.0).await.is_err());
+        assert_eq!(*inner.calls.lock().unwrap(), 1);
+
+        let config = RetryConfig {
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+            ..RetryConfig::default()
+        };
+        for _ in 0..20 {
+            let first = backoff(&config, 1).as_millis();
+            let third = backoff(&config, 3).as_millis();
+            assert!((50..=100).contains(&first), "{first}");
+            assert!((200..=400).contains(&third), "{third}");
+        }
+        assert_eq!(backoff(&config, 10).as_millis() / 500, 1);
+    }
+}
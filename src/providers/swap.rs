@@ -0,0 +1,137 @@
+//! A provider that can be replaced while the gateway runs, so a rotated API
+//! key takes effect without a restart (`nanobot config set`).
+
+use crate::config::Config;
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::sync::{Arc, RwLock};
+
+pub struct SwappableProvider {
+    inner: RwLock<Arc<dyn LLMProvider>>,
+    /// Fixed at creation; `default_model` cannot borrow through the lock.
+    default_model: String,
+}
+
+impl SwappableProvider {
+    pub fn new(provider: Arc<dyn LLMProvider>) -> Self {
+        Self {
+            default_model: provider.default_model().to_string(),
+            inner: RwLock::new(provider),
+        }
+    }
+
+    /// Sends calls from now on to `provider`; calls under way finish on the
+    /// old one.
+    pub fn replace(&self, provider: Arc<dyn LLMProvider>) {
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = provider;
+    }
+
+    fn current(&self) -> Arc<dyn LLMProvider> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[async_trait]
+impl LLMProvider for SwappableProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.current()
+            .chat(messages, tools, model, max_tokens, temperature)
+            .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        self.current()
+            .chat_with_options(messages, tools, model, max_tokens, temperature, options)
+            .await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        self.current()
+            .chat_stream(messages, tools, model, max_tokens, temperature, on_delta)
+            .await
+    }
+
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.current().capabilities(model)
+    }
+}
+
+/// Makes a tiny request through `provider` to check that its key and
+/// endpoint work before it is put in service.
+pub async fn verify(provider: &dyn LLMProvider) -> Result<()> {
+    provider
+        .chat(
+            &[json!({ "role": "user", "content": "Reply with OK." })],
+            None,
+            None,
+            16,
+            0.0,
+        )
+        .await
+        .map(|_| ())
+        .context("validation request failed")
+}
+
+/// Checks a `providers.*` change in `config` with a call through the
+/// provider that `key` sets up, not just the default model's.
+pub async fn verify_setting(config: &Config, key: &str) -> Result<()> {
+    let model = config.model_for_provider_key(key)?;
+    let api_key = config
+        .get_api_key(Some(&model))
+        .unwrap_or_else(|| "dummy".to_string());
+    let provider = crate::providers::build_provider(config, &model, api_key);
+    verify(provider.as_ref())
+        .await
+        .with_context(|| format!("checking {key} with {model}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Cassette, EvalProvider};
+
+    #[tokio::test]
+    async fn replaced_provider_serves_later_calls() {
+        let swappable = SwappableProvider::new(Arc::new(EvalProvider::replay(
+            Cassette::default(),
+            "old-model",
+        )));
+        assert!(verify(&swappable).await.is_err());
+        swappable.replace(Arc::new(EvalProvider::replay(
+            Cassette::default(),
+            "new-model",
+        )));
+        assert_eq!(swappable.default_model(), "old-model");
+        assert_eq!(swappable.current().default_model(), "new-model");
+    }
+}
@@ -7,6 +7,7 @@ use std::path::Path;
 pub struct GroqTranscriptionProvider {
     api_key: String,
     api_url: String,
+    model: String,
 }
 
 impl GroqTranscriptionProvider {
@@ -16,27 +17,48 @@ impl GroqTranscriptionProvider {
                 .or_else(|| std::env::var("GROQ_API_KEY").ok())
                 .unwrap_or_default(),
             api_url: "https://api.groq.com/openai/v1/audio/transcriptions".to_string(),
+            model: "whisper-large-v3".to_string(),
         }
     }
 
+    /// Any OpenAI-compatible `/audio/transcriptions` endpoint under `api_base`.
+    pub fn with_endpoint(mut self, api_base: &str, model: &str) -> Self {
+        if !api_base.trim().is_empty() {
+            self.api_url = format!(
+                "{}/audio/transcriptions",
+                api_base.trim().trim_end_matches('/')
+            );
+        }
+        if !model.trim().is_empty() {
+            self.model = model.trim().to_string();
+        }
+        self
+    }
+
     pub async fn transcribe(&self, file_path: &Path) -> Result<String> {
         if self.api_key.is_empty() || !file_path.exists() {
             return Ok(String::new());
         }
 
         let bytes = tokio::fs::read(file_path).await?;
-        let part = Part::bytes(bytes).file_name(
-            file_path
-                .file_name()
-                .and_then(|v| v.to_str())
-                .unwrap_or("audio.bin")
-                .to_string(),
-        );
+        let file_name = file_path
+            .file_name()
+            .and_then(|v| v.to_str())
+            .unwrap_or("audio.bin")
+            .to_string();
+        self.transcribe_bytes(bytes, file_name).await
+    }
+
+    pub async fn transcribe_bytes(&self, bytes: Vec<u8>, file_name: String) -> Result<String> {
+        if self.api_key.is_empty() {
+            return Ok(String::new());
+        }
+        let part = Part::bytes(bytes).file_name(file_name);
         let form = Form::new()
             .part("file", part)
-            .text("model", "whisper-large-v3");
+            .text("model", self.model.clone());
 
-        let client = reqwest::Client::new();
+        let client = crate::network::client();
         let response = client
             .post(&self.api_url)
             .bearer_auth(&self.api_key)
@@ -0,0 +1,248 @@
+//! Publishing a session as a Markdown note (`nanobot sessions publish`, the
+//! `publish_note` tool).
+//!
+//! The model rewrites the transcript, subagent research results included,
+//! into a standalone note that links key terms as `[[wikilinks]]` and cites
+//! the URLs found in the session as `[n]`. The note goes to
+//! `publish.notesDir` (an Obsidian vault folder, say) with YAML front matter
+//! and a Sources section.
+
+use crate::agent::AgentLoop;
+use crate::config::PublishConfig;
+use crate::providers::base::LLMProvider;
+use crate::session::SessionManager;
+use crate::utils::{expand_tilde, safe_filename};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const MAX_TOKENS: u32 = 4000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub title: String,
+    pub summary: String,
+    pub tags: Vec<String>,
+    pub body: String,
+}
+
+#[derive(Serialize)]
+struct FrontMatter<'a> {
+    title: &'a str,
+    date: String,
+    tags: &'a [String],
+    summary: &'a str,
+    source: &'a str,
+}
+
+/// Where notes are written: `publish.notesDir`, or `<workspace>/notes`.
+pub fn notes_dir(config: &PublishConfig, workspace: &Path) -> PathBuf {
+    match config.notes_dir.trim() {
+        "" => workspace.join("notes"),
+        dir => expand_tilde(dir),
+    }
+}
+
+/// URLs mentioned in `messages`, in order of first appearance.
+pub fn sources(messages: &[Value]) -> Vec<String> {
+    static URL: OnceLock<Regex> = OnceLock::new();
+    let url = URL.get_or_init(|| Regex::new(r#"https?://[^\s<>()\[\]"'`]+"#).expect("url regex"));
+    let mut found: Vec<String> = Vec::new();
+    for message in messages {
+        let Some(content) = message.get("content").and_then(Value::as_str) else {
+            continue;
+        };
+        for m in url.find_iter(content) {
+            let link = m.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?']);
+            if !found.iter().any(|seen| seen == link) {
+                found.push(link.to_string());
+            }
+        }
+    }
+    found
+}
+
+/// Reads the model's note: a `# Title` line, a summary paragraph, the body
+/// and a closing `Tags:` line.
+pub fn parse_note(text: &str) -> Result<Note> {
+    let text = text.trim();
+    let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
+    let Some(title) = first.trim().strip_prefix("# ") else {
+        bail!("note does not start with a '# Title' line");
+    };
+    let (rest, tags) = match rest.trim_end().rsplit_once('\n') {
+        Some((body, last)) if last.trim_start().starts_with("Tags:") => (body, last),
+        _ => (rest, ""),
+    };
+    let tags = tags
+        .trim_start()
+        .trim_start_matches("Tags:")
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches('#').replace(' ', "-"))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    let rest = rest.trim();
+    let (summary, body) = rest.split_once("\n\n").unwrap_or(("", rest));
+    Ok(Note {
+        title: title.trim().to_string(),
+        summary: summary.trim().to_string(),
+        tags,
+        body: body.trim().to_string(),
+    })
+}
+
+/// The note file: front matter, body and numbered sources.
+pub fn render_note(
+    note: &Note,
+    session_key: &str,
+    sources: &[String],
+    created: DateTime<Local>,
+) -> Result<String> {
+    let front = serde_yaml::to_string(&FrontMatter {
+        title: &note.title,
+        date: created.format("%Y-%m-%d").to_string(),
+        tags: &note.tags,
+        summary: &note.summary,
+        source: session_key,
+    })?;
+    let mut text = format!("---\n{front}---\n\n# {}\n\n", note.title);
+    if !note.summary.is_empty() {
+        text.push_str(&format!("> {}\n\n", note.summary));
+    }
+    text.push_str(&note.body);
+    text.push('\n');
+    if !sources.is_empty() {
+        text.push_str("\n## Sources\n\n");
+        for (i, source) in sources.iter().enumerate() {
+            text.push_str(&format!("{}. <{source}>\n", i + 1));
+        }
+    }
+    Ok(text)
+}
+
+/// Writes `text` to `<dir>/<title>.md`, adding ` 2`, ` 3`... rather than
+/// overwriting an existing note.
+pub fn write_note(dir: &Path, title: &str, text: &str) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let stem = match safe_filename(title) {
+        name if name.is_empty() => "Untitled".to_string(),
+        name => name,
+    };
+    let mut path = dir.join(format!("{stem}.md"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{stem} {n}.md"));
+        n += 1;
+    }
+    std::fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Has the model write up `session_key` and saves the note in `dir`;
+/// `title` replaces the one the model picks.
+pub async fn publish_session(
+    provider: &dyn LLMProvider,
+    model: &str,
+    sessions: &SessionManager,
+    session_key: &str,
+    dir: &Path,
+    title: Option<&str>,
+) -> Result<PathBuf> {
+    let session = sessions.load_session(session_key)?;
+    let lines = AgentLoop::transcript_lines(&session.messages);
+    if lines.is_empty() {
+        bail!("session {session_key} has nothing to publish");
+    }
+    let sources = sources(&session.messages);
+    let numbered = sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| format!("[{}] {source}", i + 1))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let prompt = format!(
+        "Rewrite this conversation as a standalone note for a personal knowledge base. \
+Keep the findings, decisions, facts and open questions; drop greetings, small talk and tool chatter. \
+Use Markdown headings and lists. Write key people, projects and concepts as [[wikilinks]]. \
+Cite the sources below as [n] where you rely on them; do not invent sources.\n\
+Reply in this format only:\n# <title>\n\n<one-sentence summary>\n\n<body>\n\nTags: <3 to 6 lowercase tags, comma separated>\n\n\
+## Sources\n{sources}\n\n## Conversation\n{conversation}",
+        sources = if numbered.is_empty() {
+            "(none)"
+        } else {
+            &numbered
+        },
+        conversation = lines.join("\n"),
+    );
+    let response = provider
+        .chat(
+            &[
+                json!({
+                    "role": "system",
+                    "content": "You turn conversations into clear, faithful notes."
+                }),
+                json!({ "role": "user", "content": prompt }),
+            ],
+            None,
+            Some(model),
+            MAX_TOKENS,
+            0.3,
+        )
+        .await?;
+    let text = response
+        .content
+        .as_deref()
+        .filter(|text| !text.trim().is_empty())
+        .context("the model returned an empty note")?;
+    let mut note = parse_note(text)?;
+    if let Some(title) = title.map(str::trim).filter(|title| !title.is_empty()) {
+        note.title = title.to_string();
+    }
+    let rendered = render_note(&note, session_key, &sources, Local::now())?;
+    write_note(dir, &note.title, &rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn renders_a_note_with_front_matter_and_sources() {
+        let messages = [
+            json!({"role": "user", "content": "Compare https://a.example/x and https://b.example/y."}),
+            json!({"role": "assistant", "content": "Per https://a.example/x, yes."}),
+        ];
+        let sources = sources(&messages);
+        assert_eq!(sources, ["https://a.example/x", "https://b.example/y"]);
+
+        let note = parse_note(
+            "# Rust: async runtimes\n\nTokio wins for servers.\n\n## Findings\n- [[Tokio]] is mature [1].\n\nTags: rust, #async, web servers",
+        )
+        .expect("parse");
+        assert_eq!(note.title, "Rust: async runtimes");
+        assert_eq!(note.summary, "Tokio wins for servers.");
+        assert_eq!(note.tags, ["rust", "async", "web-servers"]);
+        assert_eq!(note.body, "## Findings\n- [[Tokio]] is mature [1].");
+        assert!(parse_note("no title").is_err());
+
+        let created = Local.with_ymd_and_hms(2026, 10, 15, 9, 0, 0).unwrap();
+        let text = render_note(&note, "cli:direct", &sources, created).expect("render");
+        assert!(text.starts_with("---\ntitle: 'Rust: async runtimes'\ndate: 2026-10-15\n"));
+        assert!(text.contains("source: cli:direct\n---\n\n# Rust: async runtimes\n\n> Tokio wins"));
+        assert!(
+            text.ends_with("## Sources\n\n1. <https://a.example/x>\n2. <https://b.example/y>\n")
+        );
+
+        let dir = std::env::temp_dir().join(format!("nanobot-notes-{}", uuid::Uuid::new_v4()));
+        let first = write_note(&dir, &note.title, &text).expect("write");
+        let second = write_note(&dir, &note.title, &text).expect("write again");
+        assert_eq!(first.file_name().unwrap(), "Rust_ async runtimes.md");
+        assert_eq!(second.file_name().unwrap(), "Rust_ async runtimes 2.md");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::config::RedactionConfig;
+use regex::Regex;
+use std::borrow::Cow;
+use std::io;
+use std::sync::OnceLock;
+use tracing::warn;
+use tracing_subscriber::fmt::MakeWriter;
+
+const BUILTIN_DETECTORS: &[(&str, &str)] = &[
+    (
+        "private_key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+    ),
+    ("api_key", r"\bsk-(?:ant-|proj-|or-)?[A-Za-z0-9_\-]{16,}"),
+    ("aws_key", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+    ("github_token", r"\bgh[pousr]_[A-Za-z0-9]{30,}\b"),
+    ("slack_token", r"\bxox[abposr]-[A-Za-z0-9-]{10,}"),
+    ("google_key", r"\bAIza[0-9A-Za-z_\-]{35}\b"),
+    ("telegram_token", r"\b\d{8,10}:[A-Za-z0-9_-]{35}\b"),
+    (
+        "jwt",
+        r"\beyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}",
+    ),
+    ("bearer", r"(?i)\bbearer\s+[A-Za-z0-9._~+/\-]{16,}=*"),
+    (
+        "email",
+        r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b",
+    ),
+    (
+        "card",
+        r"\b(?:\d{4}[ -]\d{4}[ -]\d{4}[ -]\d{1,7}|\d{15,16})\b",
+    ),
+];
+
+struct Detector {
+    kind: String,
+    regex: Regex,
+}
+
+pub struct Redactor {
+    detectors: Vec<Detector>,
+}
+
+fn luhn_valid(candidate: &str) -> bool {
+    let digits = candidate
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .collect::<Vec<_>>();
+    if digits.len() < 13 {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Self {
+        let mut detectors = Vec::new();
+        if !config.enabled {
+            return Self { detectors };
+        }
+        if config.builtin {
+            for (kind, pattern) in BUILTIN_DETECTORS {
+                if config.disabled_detectors.iter().any(|d| d == kind) {
+                    continue;
+                }
+                detectors.push(Detector {
+                    kind: (*kind).to_string(),
+                    regex: Regex::new(pattern).expect("builtin redaction pattern is valid"),
+                });
+            }
+        }
+        for pattern in &config.patterns {
+            match Regex::new(pattern) {
+                Ok(regex) => detectors.push(Detector {
+                    kind: "custom".to_string(),
+                    regex,
+                }),
+                Err(err) => warn!("ignoring invalid redaction pattern '{pattern}': {err}"),
+            }
+        }
+        Self { detectors }
+    }
+
+    /// The process-wide redactor; builtin detectors unless `install` ran first.
+    pub fn global() -> &'static Redactor {
+        GLOBAL.get_or_init(|| Redactor::new(&RedactionConfig::default()))
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut out = Cow::Borrowed(text);
+        for detector in &self.detectors {
+            if !detector.regex.is_match(&out) {
+                continue;
+            }
+            let replaced = detector
+                .regex
+                .replace_all(&out, |caps: &regex::Captures| {
+                    let matched = &caps[0];
+                    if detector.kind == "card" && !luhn_valid(matched) {
+                        matched.to_string()
+                    } else {
+                        format!("[REDACTED:{}]", detector.kind)
+                    }
+                })
+                .into_owned();
+            out = Cow::Owned(replaced);
+        }
+        out
+    }
+}
+
+static GLOBAL: OnceLock<Redactor> = OnceLock::new();
+
+/// Installs the configured redactor; call before logging is initialised.
+pub fn install(config: &RedactionConfig) {
+    let _ = GLOBAL.set(Redactor::new(config));
+}
+
+pub fn redact(text: &str) -> Cow<'_, str> {
+    Redactor::global().redact(text)
+}
+
+/// Writer that redacts each formatted log record before passing it on.
+pub struct RedactingWriter<W> {
+    inner: W,
+}
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.inner.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wraps a `MakeWriter` so every log layer goes through the redactor.
+pub struct Redacting<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Redacting<M> {
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.0.make_writer(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_detectors_mask_secrets_and_pii() {
+        let redactor = Redactor::new(&RedactionConfig::default());
+        let text = "key sk-ant-REDACTED mail bob@example.com \
+                    card 4111 1111 1111 1111 auth Bearer abcdefghijklmnopqrstu";
+        let out = redactor.redact(text);
+        assert_eq!(
+            out,
+            "key [REDACTED:api_key] mail [REDACTED:email] \
+             card [REDACTED:card] auth [REDACTED:bearer]"
+        );
+    }
+
+    #[test]
+    fn card_detector_requires_luhn_and_leaves_ids_alone() {
+        let redactor = Redactor::new(&RedactionConfig::default());
+        assert_eq!(
+            redactor.redact("order 1234567812345678"),
+            "order 1234567812345678"
+        );
+        assert_eq!(
+            redactor.redact("discord:123456789012345678"),
+            "discord:123456789012345678"
+        );
+    }
+
+    #[test]
+    fn custom_patterns_and_disabled_detectors() {
+        let config = RedactionConfig {
+            disabled_detectors: vec!["email".to_string()],
+            patterns: vec![r"EMP-\d{6}".to_string(), "(".to_string()],
+            ..Default::default()
+        };
+        let redactor = Redactor::new(&config);
+        assert_eq!(
+            redactor.redact("bob@example.com EMP-123456"),
+            "bob@example.com [REDACTED:custom]"
+        );
+        let off = Redactor::new(&RedactionConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            off.redact("sk-abcdefghijklmnopqrst"),
+            "sk-abcdefghijklmnopqrst"
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Fast-model routing (`router` in config).
+//!
+//! `RouterProvider` looks at the request before it is sent. Greetings and
+//! short questions go to `router.fastModel`; anything long, with attachments,
+//! code, links, task words or tool results in play goes to the main model.
+//! When the heuristic is unsure and `router.scorerModel` is set, that model
+//! casts the deciding vote. A fast answer that is empty, fails or asks for a
+//! tool is thrown away and the request escalates to the main model.
+//!
+//! Cron jobs, the heartbeat and workflows run inside [`with_job_tier`]; their
+//! turns use `router.tiers[tier]` (or `router.backgroundTier`) instead of the
+//! chat's model and skip the fast-model heuristic.
+
+use crate::agent::events::{self, AgentEvent};
+use crate::config::{Config, RouterConfig};
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::future::Future;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+tokio::task_local! {
+    /// Quality tier of the background job the current task runs for; empty
+    /// for jobs that did not declare one.
+    static JOB_TIER: String;
+}
+
+/// Runs `fut` as a background job of `tier`. A job without a tier inherits
+/// the tier of the job that started it.
+pub async fn with_job_tier<F: Future>(tier: Option<&str>, fut: F) -> F::Output {
+    let tier = tier
+        .map(str::trim)
+        .filter(|tier| !tier.is_empty())
+        .map(ToOwned::to_owned)
+        .or_else(job_tier)
+        .unwrap_or_default();
+    JOB_TIER.scope(tier, fut).await
+}
+
+/// The tier of the background job the current task runs for, if any.
+pub fn job_tier() -> Option<String> {
+    JOB_TIER.try_with(Clone::clone).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Fast,
+    Smart,
+    /// Short, but not obviously trivial.
+    Unsure,
+}
+
+const SMALL_TALK: &[&str] = &[
+    "hi",
+    "hello",
+    "hey",
+    "yo",
+    "thanks",
+    "thank you",
+    "thx",
+    "ok",
+    "okay",
+    "cool",
+    "nice",
+    "great",
+    "yes",
+    "no",
+    "bye",
+    "good morning",
+    "good night",
+    "good evening",
+    "how are you",
+    "你好",
+    "您好",
+    "嗨",
+    "在吗",
+    "谢谢",
+    "好的",
+    "好",
+    "早上好",
+    "晚安",
+    "再见",
+];
+
+/// Words that usually mean work for tools or careful reasoning.
+const TASK_WORDS: &[&str] = &[
+    "file",
+    "run ",
+    "exec",
+    "search",
+    "remind",
+    "schedule",
+    "task",
+    "write",
+    "code",
+    "debug",
+    "fix",
+    "analy",
+    "compare",
+    "explain",
+    "plan",
+    "summar",
+    "translate",
+    "email",
+    "create",
+    "delete",
+    "install",
+    "step",
+    "why",
+    "文件",
+    "搜索",
+    "提醒",
+    "任务",
+    "写",
+    "代码",
+    "分析",
+    "比较",
+    "解释",
+    "计划",
+    "总结",
+    "翻译",
+    "为什么",
+];
+
+/// Classifies the request from its last user message.
+pub fn classify(messages: &[Value], max_chars: usize) -> (Route, &'static str) {
+    let tool_turn = messages.iter().any(|m| {
+        m.get("role").and_then(Value::as_str) == Some("tool") || m.get("tool_calls").is_some()
+    });
+    if tool_turn {
+        return (Route::Smart, "tool results in context");
+    }
+    let Some(last) = messages.last() else {
+        return (Route::Smart, "empty request");
+    };
+    if last.get("role").and_then(Value::as_str) != Some("user") {
+        return (Route::Smart, "not a user message");
+    }
+    let Some(text) = last.get("content").and_then(Value::as_str) else {
+        return (Route::Smart, "attachments");
+    };
+    let text = text.trim();
+    if text.starts_with("TOOL RESULT") {
+        return (Route::Smart, "tool results in context");
+    }
+    if text.chars().count() > max_chars || text.contains('\n') {
+        return (Route::Smart, "long message");
+    }
+    let lower = text.to_lowercase();
+    if ["```", "http://", "https://", "`"]
+        .iter()
+        .any(|marker| lower.contains(marker))
+    {
+        return (Route::Smart, "code or links");
+    }
+    let bare = lower
+        .trim_matches(|c: char| !c.is_alphanumeric() && !c.is_whitespace())
+        .trim();
+    if SMALL_TALK.contains(&bare) {
+        return (Route::Fast, "small talk");
+    }
+    if TASK_WORDS.iter().any(|word| lower.contains(word)) {
+        return (Route::Smart, "task words");
+    }
+    (Route::Unsure, "short message")
+}
+
+pub struct RouterProvider {
+    smart: Arc<dyn LLMProvider>,
+    fast: Arc<dyn LLMProvider>,
+    config: RouterConfig,
+}
+
+impl RouterProvider {
+    pub fn new(
+        smart: Arc<dyn LLMProvider>,
+        fast: Arc<dyn LLMProvider>,
+        config: RouterConfig,
+    ) -> Self {
+        Self {
+            smart,
+            fast,
+            config,
+        }
+    }
+
+    async fn use_fast(&self, messages: &[Value]) -> bool {
+        let (route, reason) = classify(messages, self.config.max_chars);
+        let (fast, reason) = match route {
+            Route::Fast => (true, reason.to_string()),
+            Route::Smart => (false, reason.to_string()),
+            Route::Unsure if self.config.scorer_model.trim().is_empty() => {
+                (true, reason.to_string())
+            }
+            Route::Unsure => match self.score(messages).await {
+                Ok(simple) => (
+                    simple,
+                    format!("scorer: {}", if simple { "simple" } else { "complex" }),
+                ),
+                Err(err) => {
+                    warn!("router scorer failed: {err:#}");
+                    (false, "scorer unavailable".to_string())
+                }
+            },
+        };
+        let model = if fast {
+            self.config.fast_model.as_str()
+        } else {
+            self.smart.default_model()
+        };
+        debug!(model, %reason, "routed request");
+        events::emit(AgentEvent::Routed {
+            model: model.to_string(),
+            reason,
+        });
+        fast
+    }
+
+    /// Asks the scorer model whether the last user message is simple.
+    async fn score(&self, messages: &[Value]) -> Result<bool> {
+        let text = messages
+            .last()
+            .and_then(|m| m.get("content"))
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let prompt = format!(
+            "Reply with one word. SIMPLE if a small model can answer this directly in a sentence or two without tools or up-to-date data, COMPLEX otherwise.\n\nMessage: {text}"
+        );
+        let response = self
+            .fast
+            .chat(
+                &[json!({ "role": "user", "content": prompt })],
+                None,
+                Some(&self.config.scorer_model),
+                5,
+                0.0,
+            )
+            .await?;
+        Ok(response
+            .content
+            .unwrap_or_default()
+            .to_ascii_uppercase()
+            .contains("SIMPLE"))
+    }
+
+    /// The fast model's answer, or `None` when the request should escalate.
+    async fn try_fast(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Option<LLMResponse> {
+        let tiered = job_tier().is_some_and(|tier| self.config.tier_model(&tier).is_some());
+        if tiered || !self.use_fast(messages).await {
+            return None;
+        }
+        let reason = match self
+            .fast
+            .chat_with_options(
+                messages,
+                tools,
+                Some(&self.config.fast_model),
+                max_tokens,
+                temperature,
+                options,
+            )
+            .await
+        {
+            Ok(response) if response.has_tool_calls() => "fast model wanted tools",
+            Ok(response)
+                if response
+                    .content
+                    .as_deref()
+                    .is_none_or(|c| c.trim().is_empty()) =>
+            {
+                "fast model gave no answer"
+            }
+            Ok(response) => return Some(response),
+            Err(err) => {
+                warn!("fast model failed: {err:#}");
+                "fast model error"
+            }
+        };
+        events::emit(AgentEvent::Routed {
+            model: self.smart.default_model().to_string(),
+            reason: format!("escalated: {reason}"),
+        });
+        None
+    }
+}
+
+#[async_trait]
+impl LLMProvider for RouterProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        if let Some(response) = self
+            .try_fast(messages, tools, max_tokens, temperature, options)
+            .await
+        {
+            return Ok(response);
+        }
+        self.smart
+            .chat_with_options(messages, tools, model, max_tokens, temperature, options)
+            .await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        // The fast answer is fetched whole so an escalated one never reaches
+        // the client.
+        if let Some(response) = self
+            .try_fast(
+                messages,
+                tools,
+                max_tokens,
+                temperature,
+                &ChatOptions::default(),
+            )
+            .await
+        {
+            if let Some(content) = response.content.as_deref() {
+                on_delta(content);
+            }
+            return Ok(response);
+        }
+        self.smart
+            .chat_stream(messages, tools, model, max_tokens, temperature, on_delta)
+            .await
+    }
+
+    fn default_model(&self) -> &str {
+        self.smart.default_model()
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.smart.capabilities(model)
+    }
+}
+
+/// Wraps `provider` with routing when `router.enabled` is set; `build` makes
+/// the provider for `router.fastModel`.
+pub fn wrap_provider(
+    provider: Arc<dyn LLMProvider>,
+    config: &Config,
+    build: impl FnOnce(&str) -> Arc<dyn LLMProvider>,
+) -> Arc<dyn LLMProvider> {
+    let router = &config.router;
+    let fast_model = router.fast_model.trim();
+    if !router.enabled || fast_model.is_empty() {
+        return provider;
+    }
+    let fast = build(fast_model);
+    Arc::new(RouterProvider::new(provider, fast, router.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(text: &str) -> Vec<Value> {
+        vec![
+            json!({"role": "system", "content": "You are nanobot."}),
+            json!({"role": "user", "content": text}),
+        ]
+    }
+
+    #[test]
+    fn classifies_small_talk_tasks_and_tool_turns() {
+        assert_eq!(classify(&user("Hi!"), 120).0, Route::Fast);
+        assert_eq!(classify(&user("谢谢"), 120).0, Route::Fast);
+        assert_eq!(classify(&user("capital of France?"), 120).0, Route::Unsure);
+        assert_eq!(
+            classify(&user("search the web for rust 2024"), 120),
+            (Route::Smart, "task words")
+        );
+        assert_eq!(classify(&user(&"word ".repeat(40)), 120).0, Route::Smart);
+        assert_eq!(classify(&user("see https://x.io"), 120).0, Route::Smart);
+
+        let mut messages = user("hello");
+        messages.push(json!({"role": "tool", "tool_call_id": "1", "content": "done"}));
+        assert_eq!(classify(&messages, 120).0, Route::Smart);
+    }
+
+    #[tokio::test]
+    async fn background_jobs_resolve_their_tier_model() {
+        let config = RouterConfig {
+            tiers: [
+                ("cheap", "openai/gpt-4o-mini"),
+                ("premium", "openai/gpt-4o"),
+            ]
+            .into_iter()
+            .map(|(tier, model)| (tier.to_string(), model.to_string()))
+            .collect(),
+            background_tier: "cheap".to_string(),
+            ..RouterConfig::default()
+        };
+        assert_eq!(job_tier(), None);
+        let (outer, inner) = with_job_tier(Some("premium"), async {
+            (job_tier(), with_job_tier(None, async { job_tier() }).await)
+        })
+        .await;
+        assert_eq!(outer.as_deref(), Some("premium"));
+        assert_eq!(inner.as_deref(), Some("premium"));
+
+        let tier = with_job_tier(None, async { job_tier() })
+            .await
+            .expect("tier");
+        assert_eq!(config.tier_model(&tier), Some("openai/gpt-4o-mini"));
+        assert_eq!(config.tier_model("premium"), Some("openai/gpt-4o"));
+        assert_eq!(config.tier_model("unknown"), None);
+    }
+}
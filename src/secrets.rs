@@ -0,0 +1,113 @@
+use anyhow::{Context, Result, anyhow};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::warn;
+
+/// Env var pointing at a `KEY=VALUE` file that installed services read
+/// secrets from instead of the shared config.
+pub const SECRETS_FILE_ENV: &str = "NANOBOT_SECRETS_FILE";
+
+/// Parses `KEY=VALUE` (also accepts `export KEY=VALUE` and quoted values).
+pub fn parse_env_assignment(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = line.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value);
+    Some((key.to_string(), value.to_string()))
+}
+
+pub fn parse_env_file(raw: &str) -> HashMap<String, String> {
+    raw.lines().filter_map(parse_env_assignment).collect()
+}
+
+pub fn load_secrets_file(path: &Path) -> Result<HashMap<String, String>> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read secrets file {}", path.display()))?;
+    Ok(parse_env_file(&raw))
+}
+
+/// Rejects secrets files other local users could read.
+pub fn check_secrets_file_permissions(path: &Path) -> Result<()> {
+    let meta = std::fs::metadata(path)
+        .with_context(|| format!("secrets file not found: {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = meta.permissions().mode();
+        if mode & 0o077 != 0 {
+            return Err(anyhow!(
+                "secrets file {} is accessible by other users (mode {:o}); run `chmod 600` on it",
+                path.display(),
+                mode & 0o777
+            ));
+        }
+    }
+    #[cfg(not(unix))]
+    if !meta.is_file() {
+        return Err(anyhow!("secrets file {} is not a file", path.display()));
+    }
+    Ok(())
+}
+
+fn file_secrets() -> &'static HashMap<String, String> {
+    static SECRETS: OnceLock<HashMap<String, String>> = OnceLock::new();
+    SECRETS.get_or_init(|| {
+        let Ok(path) = std::env::var(SECRETS_FILE_ENV) else {
+            return HashMap::new();
+        };
+        load_secrets_file(Path::new(&path)).unwrap_or_else(|err| {
+            warn!("{err}");
+            HashMap::new()
+        })
+    })
+}
+
+/// Looks up a secret from the process environment, then the secrets file.
+pub fn lookup(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .or_else(|| file_secrets().get(key).cloned())
+        .filter(|value| !value.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dotenv_style_lines() {
+        let parsed = parse_env_file(
+            "# comment\nOPENAI_API_KEY=sk-1\nexport ANTHROPIC_API_KEY=\"sk 2\"\nbad line\n=x\nA-B=1\n",
+        );
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed["OPENAI_API_KEY"], "sk-1");
+        assert_eq!(parsed["ANTHROPIC_API_KEY"], "sk 2");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_world_readable_secrets_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let path =
+            std::env::temp_dir().join(format!("nanobot-secrets-{}.env", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "K=V\n").expect("write");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).expect("chmod");
+        assert!(check_secrets_file_permissions(&path).is_err());
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).expect("chmod");
+        assert!(check_secrets_file_permissions(&path).is_ok());
+        let _ = std::fs::remove_file(path);
+    }
+}
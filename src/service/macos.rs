@@ -0,0 +1,357 @@
+use super::{ServiceAccount, ServiceInstallOptions, ServiceStatus, split_arguments};
+use crate::utils::write_private;
+use anyhow::{Context, Result, anyhow, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const LABEL_PREFIX: &str = "ai.nanobot";
+
+fn output_text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim().to_string()
+}
+
+fn run_command(program: &str, args: &[&str]) -> Result<Output> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("failed to execute command: {program} {}", args.join(" ")))
+}
+
+fn run_checked(program: &str, args: &[&str]) -> Result<Output> {
+    let output = run_command(program, args)?;
+    if output.status.success() {
+        return Ok(output);
+    }
+    Err(anyhow!(
+        "command failed: {program} {}\nstdout: {}\nstderr: {}",
+        args.join(" "),
+        output_text(&output.stdout),
+        output_text(&output.stderr),
+    ))
+}
+
+pub(crate) fn service_label(name: &str) -> String {
+    if name.contains('.') {
+        return name.to_string();
+    }
+    let slug = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    format!("{LABEL_PREFIX}.{slug}")
+}
+
+fn agent_dir() -> Result<PathBuf> {
+    Ok(dirs::home_dir()
+        .ok_or_else(|| anyhow!("cannot resolve home directory"))?
+        .join("Library")
+        .join("LaunchAgents"))
+}
+
+fn daemon_dir() -> PathBuf {
+    PathBuf::from("/Library/LaunchDaemons")
+}
+
+/// LocalSystem maps to a LaunchDaemon; everything else is a per-user LaunchAgent.
+fn plist_path_for(options: &ServiceInstallOptions) -> Result<PathBuf> {
+    let dir = match options.account {
+        ServiceAccount::LocalSystem => daemon_dir(),
+        _ => agent_dir()?,
+    };
+    Ok(dir.join(format!("{}.plist", service_label(&options.name))))
+}
+
+fn find_plist(name: &str) -> Result<Option<PathBuf>> {
+    let file = format!("{}.plist", service_label(name));
+    for dir in [agent_dir()?, daemon_dir()] {
+        let path = dir.join(&file);
+        if path.exists() {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn render_plist(options: &ServiceInstallOptions) -> String {
+    let label = service_label(&options.name);
+    let mut program_args = vec![options.binary_path.display().to_string()];
+    program_args.extend(split_arguments(&options.arguments));
+    let program_args = program_args
+        .iter()
+        .map(|arg| format!("        <string>{}</string>", xml_escape(arg)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let environment = options.service_environment();
+    let environment = if environment.is_empty() {
+        String::new()
+    } else {
+        let entries = environment
+            .iter()
+            .map(|(key, value)| {
+                format!(
+                    "        <key>{}</key>\n        <string>{}</string>\n",
+                    xml_escape(key),
+                    xml_escape(value)
+                )
+            })
+            .collect::<String>();
+        format!("    <key>EnvironmentVariables</key>\n    <dict>\n{entries}    </dict>\n")
+    };
+    let log = |suffix: &str| {
+        xml_escape(
+            &options
+                .log_directory
+                .join(format!("{}.{suffix}.log", options.name))
+                .display()
+                .to_string(),
+        )
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{program_args}
+    </array>
+    <key>WorkingDirectory</key>
+    <string>{workdir}</string>
+{environment}    <key>RunAtLoad</key>
+    <{autostart}/>
+    <key>KeepAlive</key>
+    <true/>
+    <key>ExitTimeOut</key>
+    <integer>{exit_timeout}</integer>
+    <key>StandardOutPath</key>
+    <string>{stdout}</string>
+    <key>StandardErrorPath</key>
+    <string>{stderr}</string>
+</dict>
+</plist>
+"#,
+        label = xml_escape(&label),
+        workdir = xml_escape(&options.working_directory.display().to_string()),
+        autostart = options.autostart,
+        exit_timeout = options.stop_timeout_s,
+        stdout = log("out"),
+        stderr = log("err"),
+    )
+}
+
+/// The plist carries the `--env` values, so only its owner may read it.
+fn write_plist(path: &Path, options: &ServiceInstallOptions) -> Result<()> {
+    write_private(path, render_plist(options).as_bytes()).with_context(|| {
+        format!(
+            "failed to write {} (LaunchDaemons require sudo)",
+            path.display()
+        )
+    })
+}
+
+fn launchctl_load(path: &Path) -> Result<()> {
+    let path = path.display().to_string();
+    run_checked("launchctl", &["load", "-w", &path]).map(|_| ())
+}
+
+fn launchctl_unload(path: &Path) -> Result<()> {
+    let path = path.display().to_string();
+    run_checked("launchctl", &["unload", "-w", &path]).map(|_| ())
+}
+
+fn is_loaded(label: &str) -> bool {
+    run_command("launchctl", &["list", label])
+        .map(|out| out.status.success())
+        .unwrap_or(false)
+}
+
+pub(crate) fn parse_list_pid(output: &str) -> Option<u32> {
+    output.lines().find_map(|line| {
+        let line = line.trim();
+        let rest = line.strip_prefix("\"PID\" =")?;
+        rest.trim().trim_end_matches(';').trim().parse().ok()
+    })
+}
+
+pub fn install_service(options: &ServiceInstallOptions) -> Result<()> {
+    if let ServiceAccount::CurrentUser { .. } = options.account {
+        bail!(
+            "--use-current-user applies to Windows services only; on macOS the job runs as you \
+             by default, or as root with --system"
+        );
+    }
+    let path = plist_path_for(options)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::create_dir_all(&options.log_directory).with_context(|| {
+        format!(
+            "failed to create log directory {}",
+            options.log_directory.display()
+        )
+    })?;
+    let label = service_label(&options.name);
+    if is_loaded(&label) {
+        let _ = launchctl_unload(&path);
+    }
+    write_plist(&path, options)?;
+    if options.autostart {
+        launchctl_load(&path)?;
+    }
+    println!(
+        "Service '{}' installed at {}.",
+        options.name,
+        path.display()
+    );
+    Ok(())
+}
+
+pub fn remove_service(name: &str) -> Result<()> {
+    let Some(path) = find_plist(name)? else {
+        println!("Service '{}' is not installed.", name);
+        return Ok(());
+    };
+    if is_loaded(&service_label(name)) {
+        launchctl_unload(&path)?;
+    }
+    fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    Ok(())
+}
+
+pub fn start_service(name: &str) -> Result<()> {
+    let path = find_plist(name)?.ok_or_else(|| anyhow!("service '{name}' is not installed"))?;
+    let label = service_label(name);
+    if !is_loaded(&label) {
+        launchctl_load(&path)?;
+    }
+    run_checked("launchctl", &["start", &label]).map(|_| ())
+}
+
+/// KeepAlive would respawn a plainly stopped job, so stopping unloads it.
+pub fn stop_service(name: &str) -> Result<()> {
+    let path = find_plist(name)?.ok_or_else(|| anyhow!("service '{name}' is not installed"))?;
+    if is_loaded(&service_label(name)) {
+        launchctl_unload(&path)?;
+    }
+    Ok(())
+}
+
+pub fn restart_service(name: &str) -> Result<()> {
+    stop_service(name)?;
+    start_service(name)
+}
+
+pub fn status_service(name: &str) -> Result<ServiceStatus> {
+    if find_plist(name)?.is_none() {
+        return Ok(ServiceStatus {
+            exists: false,
+            state: None,
+        });
+    }
+    let output = run_command("launchctl", &["list", &service_label(name)])?;
+    let state = if !output.status.success() {
+        "NOT_LOADED".to_string()
+    } else {
+        match parse_list_pid(&output_text(&output.stdout)) {
+            Some(pid) => format!("RUNNING (pid {pid})"),
+            None => "STOPPED".to_string(),
+        }
+    };
+    Ok(ServiceStatus {
+        exists: true,
+        state: Some(state),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> ServiceInstallOptions {
+        ServiceInstallOptions {
+            name: "NanobotService".to_string(),
+            binary_path: PathBuf::from("/usr/local/bin/nanobot"),
+            arguments: "serve --port 18790".to_string(),
+            working_directory: PathBuf::from("/Users/me"),
+            log_directory: PathBuf::from("/Users/me/.nanobot/logs"),
+            account: ServiceAccount::Inherit,
+            auto_install_nssm: false,
+            autostart: true,
+            environment: vec![("OPENAI_API_KEY".to_string(), "sk-<x>".to_string())],
+            secrets_file: Some(PathBuf::from("/Users/me/.nanobot/secrets.env")),
+            stop_timeout_s: 40,
+        }
+    }
+
+    #[test]
+    fn label_is_reverse_dns() {
+        assert_eq!(service_label("NanobotService"), "ai.nanobot.nanobotservice");
+        assert_eq!(service_label("My Bot"), "ai.nanobot.my-bot");
+        assert_eq!(service_label("com.example.bot"), "com.example.bot");
+    }
+
+    #[test]
+    fn plist_contains_program_keepalive_and_logs() {
+        let plist = render_plist(&options());
+        assert!(plist.contains("<string>ai.nanobot.nanobotservice</string>"));
+        assert!(plist.contains(
+            "        <string>/usr/local/bin/nanobot</string>\n        <string>serve</string>"
+        ));
+        assert!(plist.contains("<key>KeepAlive</key>\n    <true/>"));
+        assert!(plist.contains("<key>ExitTimeOut</key>\n    <integer>40</integer>"));
+        assert!(plist.contains("<key>RunAtLoad</key>\n    <true/>"));
+        assert!(plist.contains("/Users/me/.nanobot/logs/NanobotService.err.log"));
+        assert!(plist.contains("<key>OPENAI_API_KEY</key>\n        <string>sk-&lt;x&gt;</string>"));
+        assert!(plist.contains(
+            "<key>NANOBOT_SECRETS_FILE</key>\n        <string>/Users/me/.nanobot/secrets.env</string>"
+        ));
+    }
+
+    #[test]
+    fn refuses_windows_accounts() {
+        let mut options = options();
+        options.account = ServiceAccount::CurrentUser {
+            username: ".\\me".to_string(),
+            password: "secret".to_string(),
+        };
+        let err = install_service(&options).unwrap_err();
+        assert!(err.to_string().contains("--use-current-user"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plist_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let path =
+            std::env::temp_dir().join(format!("nanobot-launchd-{}.plist", uuid::Uuid::new_v4()));
+        write_plist(&path, &options()).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn parses_pid_from_launchctl_list() {
+        let output = "{\n\t\"LimitLoadToSessionType\" = \"Aqua\";\n\t\"PID\" = 4242;\n};";
+        assert_eq!(parse_list_pid(output), Some(4242));
+        assert_eq!(parse_list_pid("{\n\t\"LastExitStatus\" = 0;\n};"), None);
+    }
+}
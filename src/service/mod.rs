@@ -1,8 +1,10 @@
 use anyhow::Result;
-#[cfg(not(windows))]
+#[cfg(not(any(windows, target_os = "macos")))]
 use anyhow::anyhow;
 use std::path::PathBuf;
 
+pub mod registry;
+
 #[derive(Debug, Clone)]
 pub enum ServiceAccount {
     Inherit,
@@ -20,6 +22,64 @@ pub struct ServiceInstallOptions {
     pub account: ServiceAccount,
     pub auto_install_nssm: bool,
     pub autostart: bool,
+    pub environment: Vec<(String, String)>,
+    pub secrets_file: Option<PathBuf>,
+    /// How long the service manager waits for a clean exit on stop.
+    pub stop_timeout_s: u64,
+}
+
+impl ServiceInstallOptions {
+    /// Extra environment for the service process, including the secrets file
+    /// pointer when one is configured.
+    pub fn service_environment(&self) -> Vec<(String, String)> {
+        let mut env = self.environment.clone();
+        if let Some(path) = &self.secrets_file {
+            env.push((
+                crate::secrets::SECRETS_FILE_ENV.to_string(),
+                path.display().to_string(),
+            ));
+        }
+        env
+    }
+}
+
+/// Quotes an argument containing whitespace for a service command line.
+pub fn quote_argument(arg: &str) -> String {
+    if arg.chars().any(char::is_whitespace) {
+        format!("\"{arg}\"")
+    } else {
+        arg.to_string()
+    }
+}
+
+/// Splits a service command line on whitespace, honouring double quotes.
+pub fn split_arguments(args: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;
+    for c in args.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_token {
+                    out.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+    if has_token {
+        out.push(current);
+    }
+    out
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -30,75 +90,101 @@ pub struct ServiceStatus {
 
 #[cfg(windows)]
 mod windows;
-
 #[cfg(windows)]
+use windows as platform;
+
+#[cfg(any(target_os = "macos", test))]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+#[cfg(any(windows, target_os = "macos"))]
 pub fn install_service(options: &ServiceInstallOptions) -> Result<()> {
-    windows::install_service(options)
+    platform::install_service(options)
 }
 
-#[cfg(not(windows))]
+#[cfg(not(any(windows, target_os = "macos")))]
 pub fn install_service(_options: &ServiceInstallOptions) -> Result<()> {
     Err(anyhow!(
-        "Service management is currently supported on Windows only."
+        "Service management is currently supported on Windows and macOS only."
     ))
 }
 
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "macos"))]
 pub fn remove_service(name: &str) -> Result<()> {
-    windows::remove_service(name)
+    platform::remove_service(name)
 }
 
-#[cfg(not(windows))]
+#[cfg(not(any(windows, target_os = "macos")))]
 pub fn remove_service(_name: &str) -> Result<()> {
     Err(anyhow!(
-        "Service management is currently supported on Windows only."
+        "Service management is currently supported on Windows and macOS only."
     ))
 }
 
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "macos"))]
 pub fn start_service(name: &str) -> Result<()> {
-    windows::start_service(name)
+    platform::start_service(name)
 }
 
-#[cfg(not(windows))]
+#[cfg(not(any(windows, target_os = "macos")))]
 pub fn start_service(_name: &str) -> Result<()> {
     Err(anyhow!(
-        "Service management is currently supported on Windows only."
+        "Service management is currently supported on Windows and macOS only."
     ))
 }
 
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "macos"))]
 pub fn stop_service(name: &str) -> Result<()> {
-    windows::stop_service(name)
+    platform::stop_service(name)
 }
 
-#[cfg(not(windows))]
+#[cfg(not(any(windows, target_os = "macos")))]
 pub fn stop_service(_name: &str) -> Result<()> {
     Err(anyhow!(
-        "Service management is currently supported on Windows only."
+        "Service management is currently supported on Windows and macOS only."
     ))
 }
 
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "macos"))]
 pub fn restart_service(name: &str) -> Result<()> {
-    windows::restart_service(name)
+    platform::restart_service(name)
 }
 
-#[cfg(not(windows))]
+#[cfg(not(any(windows, target_os = "macos")))]
 pub fn restart_service(_name: &str) -> Result<()> {
     Err(anyhow!(
-        "Service management is currently supported on Windows only."
+        "Service management is currently supported on Windows and macOS only."
     ))
 }
 
-#[cfg(windows)]
+#[cfg(any(windows, target_os = "macos"))]
 pub fn status_service(name: &str) -> Result<ServiceStatus> {
-    windows::status_service(name)
+    platform::status_service(name)
 }
 
-#[cfg(not(windows))]
+#[cfg(not(any(windows, target_os = "macos")))]
 pub fn status_service(_name: &str) -> Result<ServiceStatus> {
     Err(anyhow!(
-        "Service management is currently supported on Windows only."
+        "Service management is currently supported on Windows and macOS only."
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_arguments_round_trip() {
+        let args = format!(
+            "--config {} serve -v",
+            quote_argument("C:\\My Work\\config.json")
+        );
+        assert_eq!(
+            split_arguments(&args),
+            vec!["--config", "C:\\My Work\\config.json", "serve", "-v"]
+        );
+        assert_eq!(split_arguments("  serve  \"\" "), vec!["serve", ""]);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::utils::get_home_data_path;
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// An installed service instance, tracked so `service list` can find every
+/// nanobot service regardless of which config it runs with.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceInstance {
+    pub name: String,
+    pub config_path: Option<PathBuf>,
+    pub log_directory: PathBuf,
+    pub installed_at: String,
+}
+
+impl ServiceInstance {
+    pub fn new(name: &str, config_path: Option<PathBuf>, log_directory: PathBuf) -> Self {
+        Self {
+            name: name.to_string(),
+            config_path,
+            log_directory,
+            installed_at: Local::now().to_rfc3339(),
+        }
+    }
+}
+
+fn registry_path() -> Result<PathBuf> {
+    Ok(get_home_data_path()?.join("services.json"))
+}
+
+pub fn list_instances() -> Result<Vec<ServiceInstance>> {
+    let path = registry_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let raw = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(serde_json::from_str(&raw).unwrap_or_default())
+}
+
+fn save_instances(instances: &[ServiceInstance]) -> Result<()> {
+    let path = registry_path()?;
+    std::fs::write(&path, serde_json::to_string_pretty(instances)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn upsert_instance(instances: &mut Vec<ServiceInstance>, instance: ServiceInstance) {
+    instances.retain(|existing| !existing.name.eq_ignore_ascii_case(&instance.name));
+    instances.push(instance);
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+pub fn record_instance(instance: ServiceInstance) -> Result<()> {
+    let mut instances = list_instances()?;
+    upsert_instance(&mut instances, instance);
+    save_instances(&instances)
+}
+
+pub fn forget_instance(name: &str) -> Result<()> {
+    let mut instances = list_instances()?;
+    let before = instances.len();
+    instances.retain(|existing| !existing.name.eq_ignore_ascii_case(name));
+    if instances.len() != before {
+        save_instances(&instances)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsert_replaces_by_name_and_sorts() {
+        let mut instances = vec![ServiceInstance::new(
+            "nanobot-work",
+            None,
+            PathBuf::from("/a"),
+        )];
+        upsert_instance(
+            &mut instances,
+            ServiceInstance::new("NanobotService", None, PathBuf::from("/b")),
+        );
+        upsert_instance(
+            &mut instances,
+            ServiceInstance::new(
+                "NANOBOT-WORK",
+                Some(PathBuf::from("/work/config.json")),
+                PathBuf::from("/work/logs"),
+            ),
+        );
+        let names = instances
+            .iter()
+            .map(|i| i.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["NANOBOT-WORK", "NanobotService"]);
+        assert_eq!(
+            instances[0].config_path,
+            Some(PathBuf::from("/work/config.json"))
+        );
+    }
+}
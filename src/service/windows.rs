@@ -264,6 +264,22 @@ pub fn install_service(options: &ServiceInstallOptions) -> Result<()> {
     set_service_value(&options.name, "AppRotateFiles", "1")?;
     set_service_value(&options.name, "AppRotateOnline", "1")?;
     set_service_values(&options.name, "AppExit", &["Default", "Restart"])?;
+    set_service_value(
+        &options.name,
+        "AppStopMethodConsole",
+        &(options.stop_timeout_s * 1000).to_string(),
+    )?;
+    let environment = options
+        .service_environment()
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>();
+    if environment.is_empty() {
+        let _ = run_command("nssm", &["reset", &options.name, "AppEnvironmentExtra"]);
+    } else {
+        let values = environment.iter().map(String::as_str).collect::<Vec<_>>();
+        set_service_values(&options.name, "AppEnvironmentExtra", &values)?;
+    }
     set_service_account(&options.name, &options.account)?;
     set_service_value(
         &options.name,
@@ -1,12 +1,31 @@
-use crate::utils::{get_data_path, safe_filename, timestamp};
+use crate::agent::context::build_user_content;
+use crate::config::default_sessions_dir;
+use crate::utils::{safe_filename, timestamp};
+use crate::vault::{UserKey, Vault};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value, json};
 use std::collections::HashMap;
 use std::ffi::OsStr;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Flag on the message that stands in for a compacted stretch of conversation.
+const SYNOPSIS_FLAG: &str = "synopsis";
+/// `_type` of the first line of an encrypted transcript.
+const SEALED_TYPE: &str = "sealed";
+
+/// Result of `AgentLoop::compact_session`.
+#[derive(Debug, Clone)]
+pub struct Compaction {
+    /// Messages replaced by the synopsis.
+    pub messages: usize,
+    pub chars_before: usize,
+    pub chars_after: usize,
+    /// Copy of the transcript before compaction, for `sessions compact --undo`.
+    pub archive: PathBuf,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
@@ -58,10 +77,50 @@ impl Session {
         self.updated_at = Local::now();
     }
 
+    /// Records a message with the images sent along with it, as local paths.
+    pub fn add_message_with_media(&mut self, role: &str, content: &str, media: &[String]) {
+        self.add_message(role, content);
+        if !media.is_empty()
+            && let Some(message) = self.messages.last_mut()
+        {
+            message["media"] = json!(media);
+        }
+    }
+
+    /// Summary left by the last `/compact`, if any.
+    pub fn synopsis(&self) -> Option<&str> {
+        self.messages
+            .iter()
+            .find(|m| m.get(SYNOPSIS_FLAG).and_then(Value::as_bool) == Some(true))
+            .and_then(|m| m.get("content").and_then(Value::as_str))
+    }
+
+    /// Replaces the whole transcript with a single synopsis message.
+    pub fn set_synopsis(&mut self, synopsis: &str) {
+        self.messages = vec![json!({
+            "role": "assistant",
+            "content": synopsis,
+            "timestamp": timestamp(),
+            SYNOPSIS_FLAG: true,
+        })];
+        self.updated_at = Local::now();
+    }
+
     fn to_llm_message(m: &Value) -> Value {
+        let content = m.get("content").and_then(Value::as_str).unwrap_or("");
+        let media = m
+            .get("media")
+            .and_then(Value::as_array)
+            .map(|media| {
+                media
+                    .iter()
+                    .filter_map(|path| path.as_str().map(str::to_string))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
         json!({
             "role": m.get("role").and_then(Value::as_str).unwrap_or("user"),
-            "content": m.get("content").and_then(Value::as_str).unwrap_or(""),
+            "content": build_user_content(content, Some(&media)),
         })
     }
 
@@ -75,63 +134,89 @@ impl Session {
             .collect::<Vec<_>>();
 
         let start = user_messages.len().saturating_sub(max_messages);
-        user_messages[start..]
+        let mut history = user_messages[start..]
             .iter()
             .map(|m| Self::to_llm_message(m))
-            .collect()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::Session;
-
-    #[test]
-    fn history_excludes_assistant_messages() {
-        let mut session = Session::new("cli:test");
-        session.add_message("user", "u1");
-        session.add_message("assistant", "a1");
-        session.add_message("user", "u2");
-
-        let history = session.get_history(10);
-        assert_eq!(history.len(), 2);
-        assert_eq!(history[0]["role"], "user");
-        assert_eq!(history[0]["content"], "u1");
-        assert_eq!(history[1]["role"], "user");
-        assert_eq!(history[1]["content"], "u2");
+            .collect::<Vec<_>>();
+        // A compacted session always carries its synopsis forward.
+        if let Some(synopsis) = self.synopsis() {
+            history.insert(
+                0,
+                json!({
+                    "role": "system",
+                    "content": format!("Summary of the conversation so far:\n{synopsis}"),
+                }),
+            );
+        }
+        history
     }
 }
 
 pub struct SessionManager {
     sessions_dir: PathBuf,
     cache: Mutex<HashMap<String, Session>>,
+    /// Seals the transcripts of multi-user sessions.
+    vault: Option<Arc<Vault>>,
 }
 
 impl SessionManager {
+    /// Sessions of the workspace chosen with `--workspace`, by default
+    /// `~/.nanobot/sessions`.
     pub fn new() -> Result<Self> {
-        let sessions_dir = get_data_path()?.join("sessions");
+        Self::with_dir(default_sessions_dir()?)
+    }
+
+    pub fn with_dir(sessions_dir: PathBuf) -> Result<Self> {
         std::fs::create_dir_all(&sessions_dir)?;
         Ok(Self {
             sessions_dir,
             cache: Mutex::new(HashMap::new()),
+            vault: None,
         })
     }
 
+    /// Encrypts user sessions with the keys in `vault`.
+    pub fn with_vault(mut self, vault: Option<Arc<Vault>>) -> Self {
+        self.vault = vault;
+        self
+    }
+
+    /// The key `key`'s transcript is sealed with, if it is a user session;
+    /// an error while that user is locked.
+    pub fn user_key(&self, key: &str) -> Result<Option<UserKey>> {
+        match &self.vault {
+            Some(vault) => vault.key_for(key),
+            None => Ok(None),
+        }
+    }
+
     fn session_path(&self, key: &str) -> PathBuf {
         let safe_key = safe_filename(&key.replace(':', "_"));
         self.sessions_dir.join(format!("{safe_key}.jsonl"))
     }
 
-    pub fn get_or_create(&self, key: &str) -> Session {
+    /// The session `key`, new when it has no transcript yet. A transcript
+    /// that cannot be read, such as a user's while their data is locked, is
+    /// an error rather than an empty session a later save would overwrite it
+    /// with.
+    pub fn get_or_create(&self, key: &str) -> Result<Session> {
         if let Some(cached) = self.cache.lock().ok().and_then(|c| c.get(key).cloned()) {
-            return cached;
+            return Ok(cached);
         }
 
-        let loaded = self.load(key).unwrap_or_else(|_| Session::new(key));
+        let loaded = if self.session_path(key).exists() {
+            self.load(key)?
+        } else {
+            Session::new(key)
+        };
         if let Ok(mut cache) = self.cache.lock() {
             cache.insert(key.to_string(), loaded.clone());
         }
-        loaded
+        Ok(loaded)
+    }
+
+    fn archive_dir(&self) -> PathBuf {
+        self.sessions_dir.join("archive")
     }
 
     pub fn save(&self, session: &Session) -> Result<()> {
@@ -139,7 +224,32 @@ impl SessionManager {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
+        let rendered = self.render(session)?;
+        if sealed_body(&rendered).is_none() && is_sealed(&path) {
+            anyhow::bail!(
+                "{} is encrypted; not overwriting it in plain text",
+                session.key
+            );
+        }
+        std::fs::write(&path, rendered)?;
 
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(session.key.clone(), session.clone());
+        }
+        Ok(())
+    }
+
+    /// The transcript file for `session`, sealed for user sessions.
+    fn render(&self, session: &Session) -> Result<String> {
+        let plain = Self::render_plain(session)?;
+        let Some(key) = self.user_key(&session.key)? else {
+            return Ok(plain);
+        };
+        let header = json!({ "_type": SEALED_TYPE, "key": session.key });
+        Ok(format!("{header}\n{}\n", key.seal(plain.as_bytes())?))
+    }
+
+    fn render_plain(session: &Session) -> Result<String> {
         let mut lines = Vec::new();
         lines.push(serde_json::to_string(&json!({
             "_type": "metadata",
@@ -152,12 +262,44 @@ impl SessionManager {
         for msg in &session.messages {
             lines.push(serde_json::to_string(msg)?);
         }
-        std::fs::write(&path, format!("{}\n", lines.join("\n")))?;
+        Ok(format!("{}\n", lines.join("\n")))
+    }
+
+    /// Keeps a copy of `session` under `archive/`, named by key and time.
+    pub fn archive(&self, session: &Session) -> Result<PathBuf> {
+        let dir = self.archive_dir();
+        std::fs::create_dir_all(&dir)?;
+        let stem = safe_filename(&session.key.replace(':', "_"));
+        let stamp = Local::now().format("%Y%m%d%H%M%S%3f");
+        let path = dir.join(format!("{stem}.{stamp}.jsonl"));
+        std::fs::write(&path, self.render(session)?)?;
+        Ok(path)
+    }
 
+    /// Puts back the most recent archived transcript of `key`, consuming the
+    /// archive. Returns the restored session.
+    pub fn restore_archive(&self, key: &str) -> Result<Session> {
+        let stem = safe_filename(&key.replace(':', "_"));
+        let latest = std::fs::read_dir(self.archive_dir())
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(OsStr::to_str)
+                    .and_then(|name| name.strip_prefix(&stem)?.strip_prefix('.'))
+                    .and_then(|rest| rest.strip_suffix(".jsonl"))
+                    .is_some_and(|stamp| stamp.chars().all(|c| c.is_ascii_digit()))
+            })
+            .max()
+            .with_context(|| format!("no archived transcript for {key}"))?;
+        std::fs::copy(&latest, self.session_path(key))?;
+        std::fs::remove_file(&latest)?;
         if let Ok(mut cache) = self.cache.lock() {
-            cache.insert(session.key.clone(), session.clone());
+            cache.remove(key);
         }
-        Ok(())
+        self.load(key)
     }
 
     pub fn delete(&self, key: &str) -> bool {
@@ -192,7 +334,10 @@ impl SessionManager {
             let mut restored_key = None::<String>;
             if let Some(first_line) = content.lines().find(|line| !line.trim().is_empty())
                 && let Ok(value) = serde_json::from_str::<Value>(first_line)
-                && value.get("_type").and_then(Value::as_str) == Some("metadata")
+                && matches!(
+                    value.get("_type").and_then(Value::as_str),
+                    Some("metadata" | SEALED_TYPE)
+                )
             {
                 restored_key = value
                     .get("key")
@@ -216,8 +361,14 @@ impl SessionManager {
 
     fn load(&self, key: &str) -> Result<Session> {
         let path = self.session_path(key);
-        let content = std::fs::read_to_string(&path)
+        let mut content = std::fs::read_to_string(&path)
             .with_context(|| format!("failed reading {}", path.display()))?;
+        if let Some(sealed) = sealed_body(&content) {
+            let key = self
+                .user_key(key)?
+                .with_context(|| format!("{key} is encrypted"))?;
+            content = String::from_utf8(key.open(sealed)?)?;
+        }
 
         let mut session = Session::new(key);
         for line in content
@@ -230,15 +381,15 @@ impl SessionManager {
                 if let Some(saved_key) = value.get("key").and_then(Value::as_str) {
                     session.key = saved_key.to_string();
                 }
-                if let Some(raw) = value.get("created_at").and_then(Value::as_str) {
-                    if let Ok(ts) = DateTime::parse_from_rfc3339(raw) {
-                        session.created_at = ts.with_timezone(&Local);
-                    }
+                if let Some(raw) = value.get("created_at").and_then(Value::as_str)
+                    && let Ok(ts) = DateTime::parse_from_rfc3339(raw)
+                {
+                    session.created_at = ts.with_timezone(&Local);
                 }
-                if let Some(raw) = value.get("updated_at").and_then(Value::as_str) {
-                    if let Ok(ts) = DateTime::parse_from_rfc3339(raw) {
-                        session.updated_at = ts.with_timezone(&Local);
-                    }
+                if let Some(raw) = value.get("updated_at").and_then(Value::as_str)
+                    && let Ok(ts) = DateTime::parse_from_rfc3339(raw)
+                {
+                    session.updated_at = ts.with_timezone(&Local);
                 }
                 session.metadata = value
                     .get("metadata")
@@ -252,3 +403,119 @@ impl SessionManager {
         Ok(session)
     }
 }
+
+/// The ciphertext of a sealed transcript; `None` for plain ones.
+fn sealed_body(content: &str) -> Option<&str> {
+    let (header, body) = content.trim_start().split_once('\n')?;
+    let header: Value = serde_json::from_str(header).ok()?;
+    (header.get("_type").and_then(Value::as_str) == Some(SEALED_TYPE)).then_some(body)
+}
+
+fn is_sealed(path: &Path) -> bool {
+    std::fs::read_to_string(path).is_ok_and(|content| sealed_body(&content).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Session, SessionManager};
+    use crate::vault::{UserKey, Vault};
+    use std::sync::Arc;
+
+    #[test]
+    fn history_excludes_assistant_messages() {
+        let mut session = Session::new("cli:test");
+        session.add_message("user", "u1");
+        session.add_message("assistant", "a1");
+        session.add_message("user", "u2");
+
+        let history = session.get_history(10);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0]["role"], "user");
+        assert_eq!(history[0]["content"], "u1");
+        assert_eq!(history[1]["role"], "user");
+        assert_eq!(history[1]["content"], "u2");
+    }
+
+    #[test]
+    fn images_are_kept_and_replayed_as_content_parts() {
+        let image =
+            std::env::temp_dir().join(format!("nanobot-photo-{}.png", uuid::Uuid::new_v4()));
+        std::fs::write(&image, b"\x89PNG\r\n\x1a\n").expect("write image");
+        let mut session = Session::new("cli:test");
+        let media = vec![image.display().to_string()];
+        session.add_message_with_media("user", "what is this?", &media);
+        assert_eq!(session.messages[0]["media"][0], media[0].as_str());
+
+        let history = session.get_history(10);
+        let parts = history[0]["content"].as_array().expect("parts");
+        assert_eq!(parts[0]["type"], "image_url");
+        assert!(
+            parts[0]["image_url"]["url"]
+                .as_str()
+                .is_some_and(|url| url.starts_with("data:image/png;base64,"))
+        );
+        assert_eq!(parts[1]["text"], "what is this?");
+        let _ = std::fs::remove_file(image);
+    }
+
+    #[test]
+    fn synopsis_replays_and_archive_restores_transcript() {
+        let dir = std::env::temp_dir().join(format!("nanobot-sessions-{}", uuid::Uuid::new_v4()));
+        let manager = SessionManager::with_dir(dir.clone()).expect("manager");
+        let mut session = Session::new("cli:long");
+        session.add_message("user", "plan the trip");
+        session.add_message("assistant", "sure");
+        manager.save(&session).expect("save");
+
+        manager.archive(&session).expect("archive");
+        session.set_synopsis("- Trip to Lisbon in May");
+        manager.save(&session).expect("save");
+        let history = manager
+            .get_or_create("cli:long")
+            .expect("session")
+            .get_history(0);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0]["role"], "system");
+        assert!(history[0]["content"].as_str().unwrap().contains("Lisbon"));
+
+        let restored = manager.restore_archive("cli:long").expect("restore");
+        assert_eq!(restored.messages.len(), 2);
+        assert!(restored.synopsis().is_none());
+        assert!(manager.restore_archive("cli:long").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn user_sessions_are_sealed_with_the_users_key() {
+        let dir = std::env::temp_dir().join(format!("nanobot-sessions-{}", uuid::Uuid::new_v4()));
+        let vault = Arc::new(Vault::new());
+        let manager = SessionManager::with_dir(dir.clone())
+            .expect("manager")
+            .with_vault(Some(vault.clone()));
+        let mut session = Session::new("api:alice:notes");
+        session.add_message("user", "my bank pin is 1234");
+        assert!(manager.save(&session).is_err());
+
+        vault.unlock("api:alice", UserKey::derive("nbk_a_secret", "a"));
+        manager.save(&session).expect("save");
+        let raw = std::fs::read_to_string(dir.join("api_alice_notes.jsonl")).unwrap();
+        assert!(!raw.contains("1234"));
+        assert_eq!(manager.list_session_keys().unwrap(), ["api:alice:notes"]);
+        let loaded = manager.load_session("api:alice:notes").expect("load");
+        assert_eq!(loaded.messages[0]["content"], "my bank pin is 1234");
+
+        let operator = SessionManager::with_dir(dir.clone()).expect("manager");
+        assert!(operator.load_session("api:alice:notes").is_err());
+
+        let vault = Arc::new(Vault::new());
+        let restarted = SessionManager::with_dir(dir.clone())
+            .expect("manager")
+            .with_vault(Some(vault.clone()));
+        assert!(restarted.get_or_create("api:alice:notes").is_err());
+        vault.unlock("api:alice", UserKey::derive("nbk_a_secret", "a"));
+        let reopened = restarted.get_or_create("api:alice:notes").expect("session");
+        assert_eq!(reopened.messages.len(), 1);
+        assert!(operator.save(&Session::new("api:alice:notes")).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,384 @@
+//! The user's todo list, one Markdown file per task in `<workspace>/tasks/`.
+//!
+//! ```text
+//! ---
+//! id: 3
+//! title: Renew passport
+//! status: open
+//! due: 2026-11-02T09:00:00+08:00
+//! createdAt: 2026-10-15T20:14:03+08:00
+//! ---
+//! Photos are in the desk drawer.
+//! ```
+//!
+//! Tasks with a due date get a one-shot cron job that reminds the chat the
+//! task was added from; completing or removing the task cancels it.
+
+use crate::cron::{CronSchedule, CronService};
+use crate::utils::with_path_lock;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Time of day used for due dates given without one.
+const DEFAULT_DUE_TIME: (u32, u32) = (9, 0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    #[default]
+    Open,
+    Done,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub id: u64,
+    pub title: String,
+    #[serde(default)]
+    pub status: TaskStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Local>>,
+    /// Cron job that will send the due-date reminder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reminder_job: Option<String>,
+    #[serde(skip)]
+    pub notes: String,
+}
+
+impl Task {
+    pub fn is_open(&self) -> bool {
+        self.status == TaskStatus::Open
+    }
+
+    pub fn is_overdue(&self) -> bool {
+        self.is_open() && self.due.is_some_and(|due| due < Local::now())
+    }
+
+    /// `#3 [ ] Renew passport (due 2026-11-02 09:00) #errands`
+    pub fn line(&self) -> String {
+        let mark = if self.is_open() { " " } else { "x" };
+        let mut line = format!("#{} [{mark}] {}", self.id, self.title);
+        if let Some(due) = self.due {
+            let overdue = if self.is_overdue() { ", overdue" } else { "" };
+            line.push_str(&format!(" (due {}{overdue})", due.format("%Y-%m-%d %H:%M")));
+        }
+        for tag in &self.tags {
+            line.push_str(&format!(" #{tag}"));
+        }
+        line
+    }
+
+    fn to_markdown(&self) -> Result<String> {
+        let front = serde_yaml::to_string(self)?;
+        let notes = self.notes.trim();
+        Ok(if notes.is_empty() {
+            format!("---\n{front}---\n")
+        } else {
+            format!("---\n{front}---\n{notes}\n")
+        })
+    }
+
+    fn from_markdown(text: &str) -> Result<Self> {
+        let rest = text
+            .strip_prefix("---\n")
+            .or_else(|| text.strip_prefix("---\r\n"))
+            .ok_or_else(|| anyhow!("missing front matter"))?;
+        let end = rest
+            .find("\n---")
+            .ok_or_else(|| anyhow!("unterminated front matter"))?;
+        let mut task: Task = serde_yaml::from_str(&rest[..end])?;
+        task.notes = rest[end + 4..]
+            .split_once('\n')
+            .map(|(_, body)| body.trim().to_string())
+            .unwrap_or_default();
+        Ok(task)
+    }
+}
+
+/// Fields for a new task.
+#[derive(Debug, Clone, Default)]
+pub struct NewTask {
+    pub title: String,
+    pub due: Option<DateTime<Local>>,
+    pub tags: Vec<String>,
+    pub notes: String,
+}
+
+pub struct TaskStore {
+    dir: PathBuf,
+}
+
+impl TaskStore {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            dir: workspace.join("tasks"),
+        }
+    }
+
+    fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{id}.md"))
+    }
+
+    /// Every task: open ones first by due date, then finished ones, newest first.
+    pub fn list(&self) -> Vec<Task> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut tasks = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .filter_map(|path| {
+                let text = std::fs::read_to_string(&path).ok()?;
+                match Task::from_markdown(&text) {
+                    Ok(task) => Some(task),
+                    Err(err) => {
+                        tracing::warn!("skipping task {}: {err:#}", path.display());
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        tasks.sort_by(|a, b| {
+            b.is_open()
+                .cmp(&a.is_open())
+                .then_with(|| match (a.is_open(), a.due, b.due) {
+                    (true, Some(x), Some(y)) => x.cmp(&y),
+                    (true, Some(_), None) => std::cmp::Ordering::Less,
+                    (true, None, Some(_)) => std::cmp::Ordering::Greater,
+                    (false, _, _) => b.completed_at.cmp(&a.completed_at),
+                    _ => std::cmp::Ordering::Equal,
+                })
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        tasks
+    }
+
+    pub fn get(&self, id: u64) -> Result<Task> {
+        let path = self.path(id);
+        let text = std::fs::read_to_string(&path).map_err(|_| anyhow!("task #{id} not found"))?;
+        Task::from_markdown(&text).with_context(|| format!("invalid task file {}", path.display()))
+    }
+
+    pub fn save(&self, task: &Task) -> Result<()> {
+        with_path_lock(&self.dir, || self.write(task))
+    }
+
+    fn write(&self, task: &Task) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(task.id), task.to_markdown()?)?;
+        Ok(())
+    }
+
+    pub fn add(&self, new: NewTask) -> Result<Task> {
+        let title = new.title.trim();
+        if title.is_empty() {
+            bail!("task title cannot be empty");
+        }
+        with_path_lock(&self.dir, || {
+            let id = self.list().iter().map(|task| task.id).max().unwrap_or(0) + 1;
+            let task = Task {
+                id,
+                title: title.to_string(),
+                status: TaskStatus::Open,
+                due: new.due,
+                tags: new.tags,
+                created_at: Local::now(),
+                completed_at: None,
+                reminder_job: None,
+                notes: new.notes,
+            };
+            self.write(&task)?;
+            Ok(task)
+        })
+    }
+
+    pub fn complete(&self, id: u64) -> Result<Task> {
+        with_path_lock(&self.dir, || {
+            let mut task = self.get(id)?;
+            if task.is_open() {
+                task.status = TaskStatus::Done;
+                task.completed_at = Some(Local::now());
+                self.write(&task)?;
+            }
+            Ok(task)
+        })
+    }
+
+    pub fn remove(&self, id: u64) -> Result<Task> {
+        with_path_lock(&self.dir, || {
+            let task = self.get(id)?;
+            std::fs::remove_file(self.path(id))?;
+            Ok(task)
+        })
+    }
+}
+
+/// Accepts RFC 3339, `YYYY-MM-DD HH:MM[:SS]` (or with `T`) in local time, or
+/// a bare date, which means 09:00 that day.
+pub fn parse_due(raw: &str) -> Result<DateTime<Local>> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(dt.with_timezone(&Local));
+    }
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|fmt| NaiveDateTime::parse_from_str(raw, fmt).ok())
+    .or_else(|| {
+        let (hour, minute) = DEFAULT_DUE_TIME;
+        NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| Some(date.and_time(NaiveTime::from_hms_opt(hour, minute, 0)?)))
+    })
+    .ok_or_else(|| anyhow!("invalid due date '{raw}': expected YYYY-MM-DD [HH:MM]"))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| anyhow!("due date '{raw}' does not exist in the local time zone"))
+}
+
+/// Schedules the due-date reminder, delivered to `channel`/`to`. Tasks without
+/// a future due date get none.
+pub async fn schedule_reminder(
+    cron: &CronService,
+    task: &mut Task,
+    channel: &str,
+    to: &str,
+) -> Result<()> {
+    let Some(due) = task.due.filter(|due| *due > Local::now()) else {
+        return Ok(());
+    };
+    let schedule = CronSchedule {
+        kind: "at".to_string(),
+        at_ms: Some(due.timestamp_millis()),
+        ..Default::default()
+    };
+    let message = format!(
+        "Remind the user that task #{} \"{}\" is due now. If they have finished it, they can ask you to mark it complete.",
+        task.id, task.title
+    );
+    let job = cron
+        .add_job(
+            format!("task #{}", task.id),
+            schedule,
+            message,
+            true,
+            Some(channel.to_string()),
+            Some(to.to_string()),
+            true,
+        )
+        .await?;
+    task.reminder_job = Some(job.id);
+    Ok(())
+}
+
+pub async fn cancel_reminder(cron: &CronService, task: &mut Task) -> Result<()> {
+    if let Some(job) = task.reminder_job.take() {
+        cron.remove_job(&job).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_round_trip_and_sort_open_by_due_date() {
+        let dir = std::env::temp_dir().join(format!("nanobot-tasks-{}", uuid::Uuid::new_v4()));
+        let store = TaskStore::new(&dir);
+        let later = store
+            .add(NewTask {
+                title: "File taxes".to_string(),
+                due: Some(parse_due("2099-04-15").expect("due")),
+                tags: vec!["money".to_string()],
+                notes: "Forms in the blue folder.".to_string(),
+            })
+            .expect("add");
+        let undated = store
+            .add(NewTask {
+                title: "Call mum".to_string(),
+                ..Default::default()
+            })
+            .expect("add");
+        let sooner = store
+            .add(NewTask {
+                title: "Buy milk".to_string(),
+                due: Some(parse_due("2099-01-01 18:30").expect("due")),
+                ..Default::default()
+            })
+            .expect("add");
+        assert_eq!((later.id, undated.id, sooner.id), (1, 2, 3));
+
+        store.complete(undated.id).expect("complete");
+        let ids = store.list().iter().map(|task| task.id).collect::<Vec<_>>();
+        assert_eq!(ids, [3, 1, 2]);
+
+        let loaded = store.get(later.id).expect("get");
+        assert_eq!(loaded.notes, "Forms in the blue folder.");
+        assert_eq!(
+            loaded.line(),
+            "#1 [ ] File taxes (due 2099-04-15 09:00) #money"
+        );
+        assert!(!store.get(undated.id).expect("get").is_open());
+
+        store.remove(sooner.id).expect("remove");
+        assert!(store.get(sooner.id).is_err());
+        assert!(store.add(NewTask::default()).is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn parse_due_accepts_common_formats() {
+        assert!(parse_due("2026-11-02T09:00:00+08:00").is_ok());
+        assert!(parse_due("2026-11-02 17:45").is_ok());
+        let date_only = parse_due("2026-11-02").expect("date");
+        assert_eq!(date_only.format("%H:%M").to_string(), "09:00");
+        assert!(parse_due("next tuesday").is_err());
+    }
+
+    #[test]
+    fn concurrent_adds_get_distinct_ids() {
+        let dir = std::env::temp_dir().join(format!("nanobot-tasks-{}", uuid::Uuid::new_v4()));
+        let adders = (0..4)
+            .map(|n| {
+                let store = TaskStore::new(&dir);
+                std::thread::spawn(move || {
+                    for m in 0..5 {
+                        store
+                            .add(NewTask {
+                                title: format!("Task {n}.{m}"),
+                                due: None,
+                                tags: Vec::new(),
+                                notes: String::new(),
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for adder in adders {
+            adder.join().unwrap();
+        }
+        let mut ids = TaskStore::new(&dir)
+            .list()
+            .iter()
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, (1..=20).collect::<Vec<_>>());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,73 @@
+//! Optional OpenTelemetry export of agent turn spans (`otel` feature).
+//!
+//! Spans are always recorded through `tracing`: `agent.turn` per inbound
+//! message, with `llm.chat` and `tool.execute` children, plus `channel.send`
+//! for delivery. All carry a `session_id` so traces can be correlated.
+
+use crate::config::OtlpConfig;
+
+#[cfg(feature = "otel")]
+mod otel {
+    use super::OtlpConfig;
+    use anyhow::{Result, anyhow};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+    use std::sync::OnceLock;
+
+    static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+    pub fn init_tracer(config: &OtlpConfig) -> Result<Option<SdkTracer>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let mut builder = opentelemetry_otlp::SpanExporter::builder().with_http();
+        if !config.endpoint.trim().is_empty() {
+            builder = builder.with_endpoint(config.endpoint.trim());
+        }
+        if !config.headers.is_empty() {
+            builder = builder.with_headers(config.headers.clone());
+        }
+        let exporter = builder
+            .build()
+            .map_err(|err| anyhow!("failed to build OTLP exporter: {err}"))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.service_name.clone())
+                    .build(),
+            )
+            .build();
+        let tracer = provider.tracer("nanobot");
+        let _ = PROVIDER.set(provider);
+        Ok(Some(tracer))
+    }
+
+    pub fn shutdown() {
+        if let Some(provider) = PROVIDER.get() {
+            let _ = provider.shutdown();
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+pub use otel::init_tracer;
+
+/// Flushes pending spans; a no-op without the `otel` feature.
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    otel::shutdown();
+}
+
+/// Warns when export is configured but this build cannot honour it.
+pub fn check_supported(config: &OtlpConfig) -> Option<String> {
+    if config.enabled && !cfg!(feature = "otel") {
+        return Some(
+            "logging.otlp.enabled is set but this build lacks the `otel` feature; traces are not exported"
+                .to_string(),
+        );
+    }
+    None
+}
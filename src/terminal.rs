@@ -0,0 +1,211 @@
+//! What the console can display.
+//!
+//! Unix terminals take ANSI colors and UTF-8 as they are. The Windows
+//! console needs virtual terminal processing switched on for colors and the
+//! UTF-8 code page for CJK text; older `cmd` windows may refuse the former,
+//! in which case output stays plain and the spinner uses ASCII frames.
+
+use std::io::{IsTerminal, Write};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const UNICODE_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const ASCII_FRAMES: &[&str] = &["|", "/", "-", "\\"];
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+static SPINNER_PAUSED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// ANSI escape sequences (colors, clearing a line) are interpreted.
+    pub ansi: bool,
+    /// Non-ASCII text such as box drawing and CJK is displayed as is.
+    pub unicode: bool,
+}
+
+impl Capabilities {
+    fn detect(term: Option<&str>, vt: bool, utf8: bool) -> Self {
+        let dumb = term == Some("dumb");
+        Self {
+            ansi: vt && !dumb,
+            unicode: utf8 && !dumb,
+        }
+    }
+
+    pub fn spinner_frames(self) -> &'static [&'static str] {
+        if self.unicode {
+            UNICODE_FRAMES
+        } else {
+            ASCII_FRAMES
+        }
+    }
+}
+
+/// Prepares the console and records what it supports. Call once at startup,
+/// before anything is printed.
+pub fn init() -> Capabilities {
+    *CAPABILITIES.get_or_init(|| {
+        let (vt, utf8) = platform::prepare();
+        Capabilities::detect(std::env::var("TERM").ok().as_deref(), vt, utf8)
+    })
+}
+
+pub fn capabilities() -> Capabilities {
+    init()
+}
+
+/// Whether to color stdout: a terminal that takes ANSI, without `NO_COLOR`.
+pub fn color_stdout() -> bool {
+    std::io::stdout().is_terminal() && colors_wanted()
+}
+
+/// Like `color_stdout`, for stderr.
+pub fn color_stderr() -> bool {
+    std::io::stderr().is_terminal() && colors_wanted()
+}
+
+fn colors_wanted() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && capabilities().ansi
+}
+
+/// A progress indicator on stderr while a turn runs. It stops when dropped.
+pub struct Spinner {
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Starts spinning next to `label`, or does nothing when stderr is not a
+    /// terminal.
+    pub fn start(label: &str) -> Self {
+        if !std::io::stderr().is_terminal() {
+            return Self { task: None };
+        }
+        let frames = capabilities().spinner_frames();
+        let label = label.to_string();
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_millis(100));
+            for frame in frames.iter().cycle() {
+                ticker.tick().await;
+                if !SPINNER_PAUSED.load(Ordering::Relaxed) {
+                    eprint!("\r{frame} {label}");
+                    let _ = std::io::stderr().flush();
+                }
+            }
+        });
+        Self { task: Some(task) }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+            clear_line();
+        }
+    }
+}
+
+/// Hides the spinner until the returned guard is dropped, e.g. while asking
+/// the user a question.
+pub fn pause_spinner() -> SpinnerPause {
+    SPINNER_PAUSED.store(true, Ordering::Relaxed);
+    clear_line();
+    SpinnerPause
+}
+
+pub struct SpinnerPause;
+
+impl Drop for SpinnerPause {
+    fn drop(&mut self) {
+        SPINNER_PAUSED.store(false, Ordering::Relaxed);
+    }
+}
+
+fn clear_line() {
+    if capabilities().ansi {
+        eprint!("\r\x1b[2K");
+    } else {
+        eprint!("\r{:40}\r", "");
+    }
+    let _ = std::io::stderr().flush();
+}
+
+#[cfg(not(windows))]
+mod platform {
+    /// Unix terminals need no setup.
+    pub fn prepare() -> (bool, bool) {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|key| std::env::var(key).ok().filter(|v| !v.is_empty()));
+        let utf8 = locale.is_none_or(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        });
+        (true, utf8)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+    const CP_UTF8: u32 = 65001;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(handle: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(handle: *mut c_void, mode: u32) -> i32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+        fn SetConsoleCP(code_page: u32) -> i32;
+    }
+
+    /// Turns on VT processing for stdout and stderr and switches the console
+    /// to UTF-8 for both output and input.
+    pub fn prepare() -> (bool, bool) {
+        // SAFETY: plain Win32 console calls on the process's own standard
+        // handles; failures are reported through the return values.
+        unsafe {
+            let vt = [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE]
+                .into_iter()
+                .all(|which| enable_vt(GetStdHandle(which)));
+            let utf8 = SetConsoleOutputCP(CP_UTF8) != 0;
+            SetConsoleCP(CP_UTF8);
+            (vt, utf8)
+        }
+    }
+
+    unsafe fn enable_vt(handle: *mut c_void) -> bool {
+        let mut mode = 0;
+        // Not a console (redirected): nothing to enable, escapes are only
+        // written to terminals anyway.
+        if unsafe { GetConsoleMode(handle, &mut mode) } == 0 {
+            return true;
+        }
+        mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+            || unsafe { SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) } != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_plain_ascii_output() {
+        let modern = Capabilities::detect(Some("xterm-256color"), true, true);
+        assert!(modern.ansi && modern.unicode);
+        assert_eq!(modern.spinner_frames()[0], "⠋");
+
+        let legacy = Capabilities::detect(None, false, false);
+        assert!(!legacy.ansi);
+        assert!(legacy.spinner_frames().iter().all(|frame| frame.is_ascii()));
+
+        let dumb = Capabilities::detect(Some("dumb"), true, true);
+        assert_eq!(dumb, legacy);
+    }
+}
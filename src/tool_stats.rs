@@ -0,0 +1,211 @@
+//! Per-call tool records in `~/.nanobot/tool_stats.jsonl`.
+//!
+//! The agent's `ToolRegistry` reports every execution to a
+//! `ToolStatsRecorder`, which appends a record and, with
+//! `tools.stats.autoDisable`, switches off a tool whose every call in the
+//! window has failed. `nanobot tools stats` summarizes the records.
+
+use crate::config::ToolStatsConfig;
+use crate::usage::percentile;
+use crate::utils::get_data_path;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Longest error message kept in a record.
+const ERROR_CHARS: usize = 200;
+
+pub fn tool_stats_path() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("tool_stats.jsonl"))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallRecord {
+    pub at: DateTime<Local>,
+    pub tool: String,
+    pub ok: bool,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Records since `since`, oldest first; unreadable lines are skipped.
+pub fn load_records(path: &Path, since: Option<DateTime<Local>>) -> Vec<ToolCallRecord> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| serde_json::from_str::<ToolCallRecord>(line).ok())
+        .filter(|record| since.is_none_or(|since| record.at >= since))
+        .collect()
+}
+
+pub fn append_record(path: &Path, record: &ToolCallRecord) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Calls, failures and latency for one tool.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolStats {
+    pub tool: String,
+    pub calls: usize,
+    pub failures: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub last_error: Option<String>,
+}
+
+impl ToolStats {
+    pub fn error_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.calls as f64
+        }
+    }
+}
+
+/// Per-tool summary, most failures first, then by name.
+pub fn summarize(records: &[ToolCallRecord]) -> Vec<ToolStats> {
+    let mut by_tool: BTreeMap<&str, Vec<&ToolCallRecord>> = BTreeMap::new();
+    for record in records {
+        by_tool.entry(&record.tool).or_default().push(record);
+    }
+    let mut stats = by_tool
+        .into_iter()
+        .map(|(tool, records)| {
+            let mut durations = records.iter().map(|r| r.duration_ms).collect::<Vec<_>>();
+            durations.sort_unstable();
+            ToolStats {
+                tool: tool.to_string(),
+                calls: records.len(),
+                failures: records.iter().filter(|r| !r.ok).count(),
+                p50_ms: percentile(&durations, 50).unwrap_or(0),
+                p95_ms: percentile(&durations, 95).unwrap_or(0),
+                last_error: records.iter().rev().find_map(|r| r.error.clone()),
+            }
+        })
+        .collect::<Vec<_>>();
+    stats.sort_by(|a, b| b.failures.cmp(&a.failures).then(a.tool.cmp(&b.tool)));
+    stats
+}
+
+/// Whether a tool result reports a failure. Tools return most errors as an
+/// `Error...` string rather than `Err`, and the registry does the same.
+pub fn is_failure(output: &str) -> bool {
+    output.starts_with("Error")
+}
+
+pub struct ToolStatsRecorder {
+    path: Option<PathBuf>,
+    config: ToolStatsConfig,
+    /// Recent outcomes per tool, for the auto-disable check.
+    recent: Mutex<HashMap<String, VecDeque<(Instant, bool)>>>,
+}
+
+impl ToolStatsRecorder {
+    /// Records to `path`; `None` only keeps the in-memory window.
+    pub fn new(path: Option<PathBuf>, config: ToolStatsConfig) -> Self {
+        Self {
+            path,
+            config,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.config.window_minutes.max(1) * 60)
+    }
+
+    /// Records one call; true when the tool should now be disabled.
+    pub fn record(&self, tool: &str, duration: Duration, output: &str) -> bool {
+        let ok = !is_failure(output);
+        if let Some(path) = &self.path {
+            let record = ToolCallRecord {
+                at: Local::now(),
+                tool: tool.to_string(),
+                ok,
+                duration_ms: duration.as_millis() as u64,
+                error: (!ok).then(|| {
+                    let first = output.lines().next().unwrap_or_default();
+                    first.chars().take(ERROR_CHARS).collect()
+                }),
+            };
+            if let Err(err) = append_record(path, &record) {
+                warn!("failed to record tool call: {err}");
+            }
+        }
+        if !self.config.auto_disable {
+            return false;
+        }
+
+        let now = Instant::now();
+        let window = self.window();
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        let outcomes = recent.entry(tool.to_string()).or_default();
+        outcomes.push_back((now, ok));
+        while outcomes
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > window)
+        {
+            outcomes.pop_front();
+        }
+        !ok && outcomes.len() >= self.config.min_calls.max(1) && outcomes.iter().all(|(_, ok)| !ok)
+    }
+
+    /// Forgets the window for `tool`, e.g. after it was re-enabled.
+    pub fn reset(&self, tool: &str) {
+        if let Ok(mut recent) = self.recent.lock() {
+            recent.remove(tool);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_and_summarizes_tool_calls() {
+        let dir = std::env::temp_dir().join(format!("nanobot-toolstats-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("tool_stats.jsonl");
+        let config = ToolStatsConfig {
+            auto_disable: true,
+            min_calls: 3,
+            ..ToolStatsConfig::default()
+        };
+        let recorder = ToolStatsRecorder::new(Some(path.clone()), config);
+        let ms = Duration::from_millis;
+
+        assert!(!recorder.record("read_file", ms(5), "hello"));
+        assert!(!recorder.record("exec", ms(100), "Error: timed out"));
+        assert!(!recorder.record("exec", ms(120), "Error: timed out"));
+        assert!(recorder.record("exec", ms(90), "Error: command not found\nmore"));
+        recorder.reset("exec");
+        assert!(!recorder.record("exec", ms(90), "Error: again"));
+
+        let stats = summarize(&load_records(&path, None));
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].tool, "exec");
+        assert_eq!((stats[0].calls, stats[0].failures), (4, 4));
+        assert_eq!(stats[0].p50_ms, 90);
+        assert_eq!(stats[0].last_error.as_deref(), Some("Error: again"));
+        assert_eq!(stats[1].error_rate(), 0.0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
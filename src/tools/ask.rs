@@ -0,0 +1,137 @@
+use crate::bus::MessageBus;
+use crate::tools::base::{ChatContext, Tool};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_MINUTES: u64 = 10;
+const MAX_TIMEOUT_MINUTES: u64 = 60;
+
+/// Channels where the user is already reading the reply; asking there means
+/// ending the turn with the question.
+const LOCAL_CHANNELS: &[&str] = &["cli", "webui", "system", "workflow", "cron"];
+
+/// Asks the user a question in the current chat and waits for the answer.
+pub struct AskUserTool {
+    bus: Arc<MessageBus>,
+    context: ChatContext,
+}
+
+impl AskUserTool {
+    pub fn new(bus: Arc<MessageBus>) -> Self {
+        Self {
+            bus,
+            context: ChatContext::default(),
+        }
+    }
+
+    pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
+        self.context.set(channel, chat_id);
+    }
+}
+
+#[async_trait]
+impl Tool for AskUserTool {
+    fn name(&self) -> &str {
+        "ask_user"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["messaging"]
+    }
+
+    /// Only ever asks in the current chat.
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "Ask the user a question in the current chat and wait for their answer, which is returned as the result. Use it when you cannot continue without a decision or missing detail."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "question": { "type": "string", "description": "The question to send" },
+                "timeout_minutes": {
+                    "type": "integer",
+                    "description": "How long to wait for the answer (default 10, max 60)",
+                    "minimum": 1,
+                    "maximum": MAX_TIMEOUT_MINUTES
+                }
+            },
+            "required": ["question"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let question = params
+            .get("question")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|question| !question.is_empty())
+            .ok_or_else(|| anyhow!("missing required string field: question"))?;
+        let minutes = params
+            .get("timeout_minutes")
+            .and_then(Value::as_u64)
+            .unwrap_or(DEFAULT_TIMEOUT_MINUTES)
+            .clamp(1, MAX_TIMEOUT_MINUTES);
+
+        let (channel, chat_id) = self.context.get();
+        if channel.is_empty() || chat_id.is_empty() {
+            return Ok("Error: No chat to ask in".to_string());
+        }
+        if LOCAL_CHANNELS.contains(&channel.as_str()) {
+            return Ok(format!(
+                "Error: ask_user is not available on {channel}; ask the question in your reply instead"
+            ));
+        }
+
+        let reply = self
+            .bus
+            .ask(
+                &channel,
+                &chat_id,
+                question,
+                Duration::from_secs(minutes * 60),
+            )
+            .await
+            .map_err(|err| anyhow!("Error sending question: {err}"))?;
+        Ok(match reply {
+            Some(reply) => format!("The user replied: {}", reply.content),
+            None => format!("No reply within {minutes} minutes; continue without the answer"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::InboundMessage;
+
+    #[tokio::test]
+    async fn returns_the_users_reply() {
+        let bus = Arc::new(MessageBus::new(8));
+        let tool = AskUserTool::new(bus.clone());
+        let params = json!({ "question": "Which file?" })
+            .as_object()
+            .cloned()
+            .unwrap();
+
+        tool.set_context("cli", "direct");
+        let local = tool.execute(&params).await.unwrap();
+        assert!(local.starts_with("Error: ask_user is not available on cli"));
+
+        tool.set_context("telegram", "42");
+        let asking = tokio::spawn(async move { tool.execute(&params).await });
+        assert_eq!(bus.consume_outbound().await.unwrap().content, "Which file?");
+        bus.publish_inbound(InboundMessage::new("telegram", "u", "42", "notes.md"))
+            .await
+            .unwrap();
+        assert_eq!(asking.await.unwrap().unwrap(), "The user replied: notes.md");
+        assert_eq!(bus.inbound_size(), 0);
+    }
+}
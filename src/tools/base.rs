@@ -1,5 +1,41 @@
 use async_trait::async_trait;
 use serde_json::{Map, Value, json};
+use std::sync::Mutex;
+
+tokio::task_local! {
+    /// `(channel, chat_id)` of the turn a tool call is made in.
+    static TURN_CHAT: (String, String);
+}
+
+/// Runs `fut` with the chat-bound tools acting for `channel:chat_id`, so
+/// turns of different chats can run side by side.
+pub async fn with_turn_chat<F: Future>(channel: String, chat_id: String, fut: F) -> F::Output {
+    TURN_CHAT.scope((channel, chat_id), fut).await
+}
+
+/// The chat a tool sends to or files things under: the current turn's, or
+/// else the one last given to `set` (tools used outside the agent loop).
+#[derive(Default)]
+pub struct ChatContext(Mutex<(String, String)>);
+
+impl ChatContext {
+    pub fn new(channel: impl Into<String>, chat_id: impl Into<String>) -> Self {
+        Self(Mutex::new((channel.into(), chat_id.into())))
+    }
+
+    pub fn set(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
+        if let Ok(mut guard) = self.0.lock() {
+            *guard = (channel.into(), chat_id.into());
+        }
+    }
+
+    /// `(channel, chat_id)`, empty when there is none.
+    pub fn get(&self) -> (String, String) {
+        TURN_CHAT
+            .try_with(Clone::clone)
+            .unwrap_or_else(|_| self.0.lock().map(|guard| guard.clone()).unwrap_or_default())
+    }
+}
 
 #[async_trait]
 pub trait Tool: Send + Sync {
@@ -7,6 +43,18 @@ pub trait Tool: Send + Sync {
     fn description(&self) -> &str;
     fn parameters(&self) -> Value;
 
+    /// Groups the tool belongs to, e.g. `filesystem` or `web`.
+    fn tags(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Whether this call changes files, runs commands or acts outside the
+    /// conversation; such calls are refused in read-only mode. A tool that
+    /// does not say otherwise is assumed to, so read-only mode fails closed.
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
     async fn execute(&self, params: &Map<String, Value>) -> anyhow::Result<String>;
 
     fn validate_params(&self, params: &Map<String, Value>) -> Vec<String> {
@@ -48,15 +96,15 @@ fn validate_value(value: &Value, schema: &Value, path: &str, fallback_label: &st
     match schema_type {
         "string" => {
             if let Some(s) = value.as_str() {
-                if let Some(min_len) = schema.get("minLength").and_then(Value::as_u64) {
-                    if s.len() < min_len as usize {
-                        errors.push(format!("{label} must be at least {min_len} chars"));
-                    }
+                if let Some(min_len) = schema.get("minLength").and_then(Value::as_u64)
+                    && s.len() < min_len as usize
+                {
+                    errors.push(format!("{label} must be at least {min_len} chars"));
                 }
-                if let Some(max_len) = schema.get("maxLength").and_then(Value::as_u64) {
-                    if s.len() > max_len as usize {
-                        errors.push(format!("{label} must be at most {max_len} chars"));
-                    }
+                if let Some(max_len) = schema.get("maxLength").and_then(Value::as_u64)
+                    && s.len() > max_len as usize
+                {
+                    errors.push(format!("{label} must be at most {max_len} chars"));
                 }
             } else {
                 errors.push(format!("{label} should be string"));
@@ -65,15 +113,15 @@ fn validate_value(value: &Value, schema: &Value, path: &str, fallback_label: &st
         }
         "integer" => {
             if let Some(num) = value.as_i64() {
-                if let Some(min) = schema.get("minimum").and_then(Value::as_i64) {
-                    if num < min {
-                        errors.push(format!("{label} must be >= {min}"));
-                    }
+                if let Some(min) = schema.get("minimum").and_then(Value::as_i64)
+                    && num < min
+                {
+                    errors.push(format!("{label} must be >= {min}"));
                 }
-                if let Some(max) = schema.get("maximum").and_then(Value::as_i64) {
-                    if num > max {
-                        errors.push(format!("{label} must be <= {max}"));
-                    }
+                if let Some(max) = schema.get("maximum").and_then(Value::as_i64)
+                    && num > max
+                {
+                    errors.push(format!("{label} must be <= {max}"));
                 }
             } else {
                 errors.push(format!("{label} should be integer"));
@@ -82,26 +130,24 @@ fn validate_value(value: &Value, schema: &Value, path: &str, fallback_label: &st
         }
         "number" => {
             if let Some(num) = value.as_f64() {
-                if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
-                    if num < min {
-                        errors.push(format!("{label} must be >= {min}"));
-                    }
+                if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+                    && num < min
+                {
+                    errors.push(format!("{label} must be >= {min}"));
                 }
-                if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
-                    if num > max {
-                        errors.push(format!("{label} must be <= {max}"));
-                    }
+                if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+                    && num > max
+                {
+                    errors.push(format!("{label} must be <= {max}"));
                 }
             } else {
                 errors.push(format!("{label} should be number"));
                 return errors;
             }
         }
-        "boolean" => {
-            if !value.is_boolean() {
-                errors.push(format!("{label} should be boolean"));
-                return errors;
-            }
+        "boolean" if !value.is_boolean() => {
+            errors.push(format!("{label} should be boolean"));
+            return errors;
         }
         "array" => {
             if let Some(arr) = value.as_array() {
@@ -143,7 +189,15 @@ fn validate_value(value: &Value, schema: &Value, path: &str, fallback_label: &st
                         }
                     }
                 }
+                let closed = schema.get("additionalProperties") == Some(&Value::Bool(false));
                 for (key, item) in obj {
+                    if closed && !props.contains_key(key) {
+                        if path.is_empty() {
+                            errors.push(format!("unknown {fallback_label} {key}"));
+                        } else {
+                            errors.push(format!("unknown {fallback_label} {path}.{key}"));
+                        }
+                    }
                     if let Some(prop_schema) = props.get(key) {
                         let child_path = if path.is_empty() {
                             key.to_string()
@@ -166,18 +220,94 @@ fn validate_value(value: &Value, schema: &Value, path: &str, fallback_label: &st
         _ => {}
     }
 
-    if let Some(enums) = schema.get("enum").and_then(Value::as_array) {
-        if !enums.iter().any(|candidate| candidate == value) {
-            errors.push(format!(
-                "{label} must be one of {}",
-                Value::Array(enums.clone())
-            ));
-        }
+    if let Some(enums) = schema.get("enum").and_then(Value::as_array)
+        && !enums.iter().any(|candidate| candidate == value)
+    {
+        errors.push(format!(
+            "{label} must be one of {}",
+            Value::Array(enums.clone())
+        ));
     }
 
     errors
 }
 
+const SCHEMA_TYPES: &[&str] = &[
+    "string", "integer", "number", "boolean", "array", "object", "null",
+];
+
+/// Problems with a tool's parameter schema that would make providers reject
+/// the request or leave the model guessing; empty when the schema is usable.
+pub fn check_schema(schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    if schema.get("type").and_then(Value::as_str) != Some("object") {
+        errors.push("parameters must be an object schema".to_string());
+    }
+    check_schema_node(schema, "parameters", &mut errors);
+    errors
+}
+
+fn check_schema_node(schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(node) = schema.as_object() else {
+        errors.push(format!("{path} must be a schema object"));
+        return;
+    };
+    let schema_type = node.get("type").and_then(Value::as_str);
+    if let Some(kind) = schema_type
+        && !SCHEMA_TYPES.contains(&kind)
+    {
+        errors.push(format!("{path} has unknown type '{kind}'"));
+    }
+    if let Some(enums) = node.get("enum")
+        && enums.as_array().is_none_or(|values| values.is_empty())
+    {
+        errors.push(format!("{path}.enum must be a non-empty array"));
+    }
+    if let Some(extra) = node.get("additionalProperties")
+        && !extra.is_boolean()
+        && !extra.is_object()
+    {
+        errors.push(format!(
+            "{path}.additionalProperties must be a boolean or schema"
+        ));
+    }
+    match schema_type {
+        Some("object") => {
+            let properties = match node.get("properties") {
+                None => Map::new(),
+                Some(Value::Object(properties)) => properties.clone(),
+                Some(_) => {
+                    errors.push(format!("{path}.properties must be an object"));
+                    Map::new()
+                }
+            };
+            for (name, property) in &properties {
+                check_schema_node(property, &format!("{path}.{name}"), errors);
+            }
+            match node.get("required") {
+                None => {}
+                Some(Value::Array(required)) => {
+                    for name in required {
+                        match name.as_str() {
+                            Some(name) if properties.contains_key(name) => {}
+                            Some(name) => errors.push(format!(
+                                "{path}.required lists '{name}', which is not a property"
+                            )),
+                            None => errors.push(format!("{path}.required must list names")),
+                        }
+                    }
+                }
+                Some(_) => errors.push(format!("{path}.required must be an array")),
+            }
+        }
+        Some("array") => match node.get("items") {
+            Some(items) => check_schema_node(items, &format!("{path}.items"), errors),
+            None => errors.push(format!("{path} is an array without items")),
+        },
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -305,6 +435,156 @@ mod tests {
         assert!(errors.is_empty());
     }
 
+    #[test]
+    fn closed_schemas_reject_unknown_fields() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "path": { "type": "string" } },
+            "additionalProperties": false
+        });
+        let errors = validate_value(
+            &json!({ "path": "a", "pth": "b" }),
+            &schema,
+            "",
+            "parameter",
+        );
+        assert_eq!(errors, ["unknown parameter pth"]);
+    }
+
+    #[test]
+    fn check_schema_reports_malformed_schemas() {
+        assert!(check_schema(&SampleTool.parameters()).is_empty());
+        let errors = check_schema(&json!({
+            "type": "object",
+            "properties": {
+                "when": { "type": "datetime" },
+                "tags": { "type": "array" }
+            },
+            "required": ["when", "where"]
+        }));
+        assert_eq!(
+            errors,
+            [
+                "parameters.tags is an array without items",
+                "parameters.when has unknown type 'datetime'",
+                "parameters.required lists 'where', which is not a property",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn registry_refuses_tools_with_broken_schemas() {
+        struct Broken;
+
+        #[async_trait]
+        impl Tool for Broken {
+            fn name(&self) -> &str {
+                "broken"
+            }
+
+            fn description(&self) -> &str {
+                "broken tool"
+            }
+
+            fn parameters(&self) -> Value {
+                json!({ "type": "string" })
+            }
+
+            async fn execute(&self, _params: &Map<String, Value>) -> anyhow::Result<String> {
+                Ok("ok".to_string())
+            }
+        }
+
+        let mut registry = ToolRegistry::new();
+        assert!(registry.try_register(std::sync::Arc::new(Broken)).is_err());
+        registry.register(std::sync::Arc::new(Broken));
+        assert!(!registry.has("broken"));
+    }
+
+    #[test]
+    fn registries_merge_with_tags_and_state() {
+        let mut custom = ToolRegistry::new();
+        custom.register_with_tags(std::sync::Arc::new(SampleTool), &["custom", "search"]);
+        custom.set_enabled("sample", false);
+
+        let mut registry = ToolRegistry::new();
+        registry.register_boxed(Box::new(crate::tools::shell::ExecTool::new(
+            5, None, None, None, false,
+        )));
+        registry.extend(custom);
+
+        let names = registry
+            .list()
+            .into_iter()
+            .map(|info| (info.name, info.tags, info.enabled))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                ("exec".to_string(), vec!["shell".to_string()], true),
+                (
+                    "sample".to_string(),
+                    vec!["custom".to_string(), "search".to_string()],
+                    false
+                ),
+            ]
+        );
+        assert_eq!(registry.set_enabled_by_tag("custom", true), 1);
+        assert!(registry.is_enabled("sample"));
+    }
+
+    #[tokio::test]
+    async fn read_only_registry_refuses_mutating_calls() {
+        let mut registry = ToolRegistry::new();
+        registry.register(std::sync::Arc::new(SampleTool));
+        registry.register(std::sync::Arc::new(crate::tools::shell::ExecTool::new(
+            5, None, None, None, false,
+        )));
+        registry.register(std::sync::Arc::new(
+            crate::tools::http::HttpRequestTool::new(5, 1_000),
+        ));
+        let bus = std::sync::Arc::new(crate::bus::MessageBus::new(8));
+        let message = crate::tools::message::MessageTool::new(bus.clone());
+        message.set_context("telegram", "42");
+        registry.register(std::sync::Arc::new(message));
+        registry.set_read_only(true);
+
+        // `sample` does not say it is read-only, so it is hidden.
+        let mut names = registry
+            .get_definitions()
+            .iter()
+            .map(|def| def["function"]["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["http_request", "message"]);
+
+        let elsewhere = json!({ "content": "hi", "channel": "email", "chat_id": "x@y.z" });
+        let sent = registry
+            .execute("message", elsewhere.as_object().unwrap())
+            .await;
+        assert!(sent.contains("read-only mode"));
+        let reply = json!({ "content": "hi", "channel": "telegram", "chat_id": "42" });
+        let sent = registry
+            .execute("message", reply.as_object().unwrap())
+            .await;
+        assert_eq!(sent, "Message sent to telegram:42");
+        assert_eq!(bus.outbound_size(), 1);
+
+        let exec = registry
+            .execute("exec", json!({ "command": "touch x" }).as_object().unwrap())
+            .await;
+        assert!(exec.contains("read-only mode"));
+        let post = registry
+            .execute(
+                "http_request",
+                json!({ "url": "http://127.0.0.1:9/", "method": "POST" })
+                    .as_object()
+                    .unwrap(),
+            )
+            .await;
+        assert!(post.contains("read-only mode"));
+    }
+
     #[tokio::test]
     async fn registry_returns_validation_error() {
         let mut registry = ToolRegistry::new();
@@ -0,0 +1,191 @@
+use crate::bookmarks::{Bookmark, BookmarkStore, normalize_tags};
+use crate::tools::base::Tool;
+use crate::tools::web::fetch_page_summary;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::Local;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+const SUMMARY_CHARS: usize = 300;
+
+/// The read-later tools: `save_link`, `list_links` and `mark_link_read`.
+pub struct BookmarkTools {
+    store: BookmarkStore,
+}
+
+impl BookmarkTools {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            store: BookmarkStore::new(workspace),
+        }
+    }
+
+    /// The three bookmark tools, ready to register.
+    pub fn tools(self: &Arc<Self>) -> Vec<Arc<dyn Tool>> {
+        vec![
+            Arc::new(SaveLinkTool(self.clone())),
+            Arc::new(ListLinksTool(self.clone())),
+            Arc::new(MarkLinkReadTool(self.clone())),
+        ]
+    }
+}
+
+fn required_url(params: &Map<String, Value>) -> Result<&str> {
+    params
+        .get("url")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| anyhow!("missing required string field: url"))
+}
+
+pub struct SaveLinkTool(Arc<BookmarkTools>);
+
+#[async_trait]
+impl Tool for SaveLinkTool {
+    fn name(&self) -> &str {
+        "save_link"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["bookmarks"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Save a link to the user's read-later list. The page title and opening paragraph are fetched automatically."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let url = required_url(params)?;
+        let tags = params
+            .get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| normalize_tags(tags.iter().filter_map(Value::as_str)))
+            .unwrap_or_default();
+        // An unreachable page is still worth saving; it just goes untitled.
+        let page = fetch_page_summary(url, SUMMARY_CHARS)
+            .await
+            .unwrap_or_else(|err| {
+                warn!("could not fetch {url} for its title: {err:#}");
+                Default::default()
+            });
+        let bookmark = self.0.store.save(Bookmark {
+            url: url.to_string(),
+            title: page.title.unwrap_or_default(),
+            summary: page.summary.unwrap_or_default(),
+            tags,
+            saved_at: Local::now(),
+            read_at: None,
+        })?;
+        Ok(format!("Saved {}", bookmark.line()))
+    }
+}
+
+pub struct ListLinksTool(Arc<BookmarkTools>);
+
+#[async_trait]
+impl Tool for ListLinksTool {
+    fn name(&self) -> &str {
+        "list_links"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["bookmarks"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "List the user's saved links, newest first. Unread ones by default; set include_read to see all."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "tag": { "type": "string", "description": "Only links with this tag" },
+                "include_read": { "type": "boolean" }
+            }
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let include_read = params
+            .get("include_read")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let tag = params
+            .get("tag")
+            .and_then(Value::as_str)
+            .and_then(|tag| normalize_tags([tag]).pop());
+        let lines = self
+            .0
+            .store
+            .list()
+            .into_iter()
+            .filter(|bookmark| include_read || bookmark.is_unread())
+            .filter(|bookmark| tag.as_ref().is_none_or(|tag| bookmark.tags.contains(tag)))
+            .map(|bookmark| bookmark.line())
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return Ok("No saved links.".to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+pub struct MarkLinkReadTool(Arc<BookmarkTools>);
+
+#[async_trait]
+impl Tool for MarkLinkReadTool {
+    fn name(&self) -> &str {
+        "mark_link_read"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["bookmarks"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Mark a saved link as read, taking it off the unread list."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let bookmark = self.0.store.mark_read(required_url(params)?)?;
+        Ok(format!("Marked as read: {}", bookmark.line()))
+    }
+}
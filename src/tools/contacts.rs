@@ -0,0 +1,278 @@
+use crate::contacts::{
+    Contact, ContactStore, cancel_birthday_reminder, normalize_birthday, schedule_birthday_reminder,
+};
+use crate::cron::CronService;
+use crate::tools::base::{ChatContext, Tool};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::Local;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use std::sync::Arc;
+
+/// State shared by `find_contacts`, `save_contact` and `remove_contact`: the
+/// store, the cron service for birthday reminders and the chat they go to.
+pub struct ContactTools {
+    store: ContactStore,
+    cron: Option<Arc<CronService>>,
+    context: ChatContext,
+}
+
+impl ContactTools {
+    pub fn new(workspace: &Path, cron: Option<Arc<CronService>>) -> Self {
+        Self {
+            store: ContactStore::new(workspace),
+            cron,
+            context: ChatContext::default(),
+        }
+    }
+
+    pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
+        self.context.set(channel, chat_id);
+    }
+
+    /// The three contact tools, ready to register.
+    pub fn tools(self: &Arc<Self>) -> Vec<Arc<dyn Tool>> {
+        vec![
+            Arc::new(FindContactsTool(self.clone())),
+            Arc::new(SaveContactTool(self.clone())),
+            Arc::new(RemoveContactTool(self.clone())),
+        ]
+    }
+}
+
+fn string_list(params: &Map<String, Value>, key: &str) -> Vec<String> {
+    params
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn merge(into: &mut Vec<String>, items: Vec<String>) {
+    for item in items {
+        if !into.iter().any(|have| have.eq_ignore_ascii_case(&item)) {
+            into.push(item);
+        }
+    }
+}
+
+fn required_name(params: &Map<String, Value>) -> Result<&str> {
+    params
+        .get("name")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("missing required string field: name"))
+}
+
+pub struct FindContactsTool(Arc<ContactTools>);
+
+#[async_trait]
+impl Tool for FindContactsTool {
+    fn name(&self) -> &str {
+        "find_contacts"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["contacts"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "Look people up in the user's contact book (names, relationships, birthdays, emails, phones, notes). Without a query, lists everyone. Use this rather than memory for facts about people."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Name, alias, relationship, email or any word from the notes" },
+                "birthdays_within_days": { "type": "integer", "description": "Only contacts with a birthday in the next N days, soonest first" }
+            }
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let store = &self.0.store;
+        if let Some(days) = params.get("birthdays_within_days").and_then(Value::as_i64) {
+            let today = Local::now().date_naive();
+            let lines = store
+                .upcoming_birthdays(today, days.max(0))
+                .into_iter()
+                .map(|(date, contact)| format!("{}: {}", date.format("%Y-%m-%d"), contact.line()))
+                .collect::<Vec<_>>();
+            if lines.is_empty() {
+                return Ok(format!("No birthdays in the next {days} days."));
+            }
+            return Ok(lines.join("\n"));
+        }
+        let contacts = match params
+            .get("query")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|query| !query.is_empty())
+        {
+            Some(query) => store.search(query),
+            None => store.list(),
+        };
+        if contacts.is_empty() {
+            return Ok("No contacts found.".to_string());
+        }
+        Ok(contacts
+            .iter()
+            .map(Contact::details)
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+pub struct SaveContactTool(Arc<ContactTools>);
+
+#[async_trait]
+impl Tool for SaveContactTool {
+    fn name(&self) -> &str {
+        "save_contact"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["contacts"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Add a person to the user's contact book or update one (matched by name or alias). Given lists are merged into the existing ones and notes are appended. A birthday schedules a yearly reminder in this chat."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "Full name, or an existing name/alias to update" },
+                "new_name": { "type": "string", "description": "Rename the contact" },
+                "aliases": { "type": "array", "items": { "type": "string" } },
+                "relationship": { "type": "string", "description": "e.g. sister, colleague, dentist" },
+                "birthday": { "type": "string", "description": "YYYY-MM-DD, or MM-DD when the year is unknown; empty removes it" },
+                "emails": { "type": "array", "items": { "type": "string" } },
+                "phones": { "type": "array", "items": { "type": "string" } },
+                "notes": { "type": "string", "description": "Facts to add to the contact's notes" }
+            },
+            "required": ["name"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let name = required_name(params)?;
+        let tools = &self.0;
+        let existing = tools.store.get(name);
+        let previous_name = existing.as_ref().map(|contact| contact.name.clone());
+        let mut contact = existing.unwrap_or_else(|| Contact::new(name));
+        if let Some(new_name) = params
+            .get("new_name")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            contact.name = new_name.to_string();
+        }
+        merge(&mut contact.aliases, string_list(params, "aliases"));
+        merge(&mut contact.emails, string_list(params, "emails"));
+        merge(&mut contact.phones, string_list(params, "phones"));
+        if let Some(relationship) = params.get("relationship").and_then(Value::as_str) {
+            contact.relationship = relationship.trim().to_string();
+        }
+        let old_birthday = contact.birthday.clone();
+        if let Some(birthday) = params.get("birthday").and_then(Value::as_str) {
+            contact.birthday = match birthday.trim() {
+                "" => None,
+                birthday => Some(normalize_birthday(birthday)?),
+            };
+        }
+        if let Some(notes) = params
+            .get("notes")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|notes| !notes.is_empty())
+        {
+            contact.notes = match contact.notes.trim() {
+                "" => notes.to_string(),
+                existing => format!("{existing}\n{notes}"),
+            };
+        }
+        contact.updated_at = Local::now();
+
+        let mut reminder = "";
+        if let Some(cron) = &tools.cron
+            && (contact.birthday != old_birthday
+                || previous_name.as_deref() != Some(contact.name.as_str()))
+        {
+            cancel_birthday_reminder(cron, &mut contact).await?;
+            let (channel, chat_id) = tools.context.get();
+            if contact.birthday.is_some() && !channel.is_empty() && !chat_id.is_empty() {
+                schedule_birthday_reminder(cron, &mut contact, &channel, &chat_id).await?;
+                reminder = " A yearly birthday reminder is scheduled.";
+            }
+        }
+        tools.store.save(&contact, previous_name.as_deref())?;
+        let verb = if previous_name.is_some() {
+            "Updated"
+        } else {
+            "Added"
+        };
+        Ok(format!("{verb} {}.{reminder}", contact.line()))
+    }
+}
+
+pub struct RemoveContactTool(Arc<ContactTools>);
+
+#[async_trait]
+impl Tool for RemoveContactTool {
+    fn name(&self) -> &str {
+        "remove_contact"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["contacts"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Remove a person from the user's contact book, cancelling their birthday reminder."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" }
+            },
+            "required": ["name"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let tools = &self.0;
+        let mut contact = tools.store.remove(required_name(params)?)?;
+        if let Some(cron) = &tools.cron {
+            cancel_birthday_reminder(cron, &mut contact).await?;
+        }
+        Ok(format!("Removed {}", contact.line()))
+    }
+}
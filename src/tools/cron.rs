@@ -1,35 +1,247 @@
-use crate::cron::{CronSchedule, CronService};
-use crate::tools::base::Tool;
+use crate::cron::{CronJob, CronSchedule, CronService};
+use crate::tools::base::{ChatContext, Tool};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use chrono::{Local, NaiveDateTime, TimeZone};
 use serde_json::{Map, Value, json};
-use std::sync::{Arc, Mutex};
-
-#[derive(Default)]
-struct CronContext {
-    channel: String,
-    chat_id: String,
-}
+use std::sync::Arc;
 
 pub struct CronTool {
     cron: Arc<CronService>,
-    context: Mutex<CronContext>,
+    context: ChatContext,
 }
 
 impl CronTool {
     pub fn new(cron: Arc<CronService>) -> Self {
         Self {
             cron,
-            context: Mutex::new(CronContext::default()),
+            context: ChatContext::default(),
         }
     }
 
     pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
-        if let Ok(mut guard) = self.context.lock() {
-            guard.channel = channel.into();
-            guard.chat_id = chat_id.into();
+        self.context.set(channel, chat_id);
+    }
+
+    /// `cron` with the read-only `list_scheduled` and `describe_job`, ready
+    /// to register.
+    pub fn tools(self: &Arc<Self>) -> Vec<Arc<dyn Tool>> {
+        vec![
+            self.clone(),
+            Arc::new(ListScheduledTool(self.clone())),
+            Arc::new(DescribeJobTool(self.clone())),
+        ]
+    }
+
+    fn chat(&self) -> (String, String) {
+        self.context.get()
+    }
+}
+
+/// `ms` as local date and time.
+fn format_ms(ms: i64) -> String {
+    Local
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|at| at.format("%Y-%m-%d %H:%M (%a)").to_string())
+        .unwrap_or_else(|| ms.to_string())
+}
+
+fn format_every(ms: i64) -> String {
+    let seconds = ms / 1000;
+    match seconds {
+        s if s > 0 && s % 86_400 == 0 => format!("every {} day(s)", s / 86_400),
+        s if s > 0 && s % 3_600 == 0 => format!("every {} hour(s)", s / 3_600),
+        s if s > 0 && s % 60 == 0 => format!("every {} minute(s)", s / 60),
+        s => format!("every {s} second(s)"),
+    }
+}
+
+/// When `schedule` fires, in words.
+fn describe_schedule(schedule: &CronSchedule) -> String {
+    match schedule.kind.as_str() {
+        "every" => format_every(schedule.every_ms.unwrap_or_default()),
+        "cron" => {
+            let zone = if schedule.tz.as_deref() == Some("local") {
+                "local time"
+            } else {
+                "UTC"
+            };
+            format!(
+                "cron `{}` ({zone})",
+                schedule.expr.as_deref().unwrap_or_default()
+            )
+        }
+        "at" => format!("once at {}", format_ms(schedule.at_ms.unwrap_or_default())),
+        other => other.to_string(),
+    }
+}
+
+/// What a job does when it fires.
+fn describe_payload(job: &CronJob) -> String {
+    match job.payload.kind.as_str() {
+        "agent_turn" => format!("runs the prompt \"{}\"", job.payload.message),
+        "system_event" => format!("posts \"{}\"", job.payload.message),
+        kind => format!("runs the {kind} \"{}\"", job.payload.message),
+    }
+}
+
+fn job_line(job: &CronJob) -> String {
+    let next = match job.state.next_run_at_ms {
+        _ if !job.enabled => "paused".to_string(),
+        Some(at) => format!("next {}", format_ms(at)),
+        None => "not due again".to_string(),
+    };
+    format!(
+        "- {} [id {}]: {}; {}",
+        job.name,
+        job.id,
+        describe_schedule(&job.schedule),
+        next
+    )
+}
+
+pub struct ListScheduledTool(Arc<CronTool>);
+
+#[async_trait]
+impl Tool for ListScheduledTool {
+    fn name(&self) -> &str {
+        "list_scheduled"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["scheduling"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "List the scheduled jobs (reminders, recurring prompts, briefings) with their schedule and next run. Use this to answer what is scheduled instead of guessing. By default only jobs that report to this chat; scope \"all\" includes every job."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "scope": { "type": "string", "enum": ["chat", "all"] }
+            }
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let all = params.get("scope").and_then(Value::as_str) == Some("all");
+        let (channel, chat_id) = self.0.chat();
+        let jobs = self
+            .0
+            .cron
+            .current_jobs()
+            .await
+            .into_iter()
+            .filter(|job| {
+                all || (job.payload.channel.as_deref() == Some(channel.as_str())
+                    && job.payload.to.as_deref() == Some(chat_id.as_str()))
+            })
+            .collect::<Vec<_>>();
+        if jobs.is_empty() {
+            return Ok(if all {
+                "No scheduled jobs.".to_string()
+            } else {
+                "No jobs are scheduled for this chat.".to_string()
+            });
         }
+        let lines = jobs.iter().map(job_line).collect::<Vec<_>>();
+        Ok(format!(
+            "{} scheduled job(s), now {}:\n{}",
+            jobs.len(),
+            format_ms(chrono::Utc::now().timestamp_millis()),
+            lines.join("\n")
+        ))
+    }
+}
+
+pub struct DescribeJobTool(Arc<CronTool>);
+
+#[async_trait]
+impl Tool for DescribeJobTool {
+    fn name(&self) -> &str {
+        "describe_job"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["scheduling"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "Show everything about one scheduled job by id: what it does, its schedule, where it reports, and its last and next run."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "job_id": { "type": "string" }
+            },
+            "required": ["job_id"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let job_id = params
+            .get("job_id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: job_id"))?;
+        let Some(job) = self
+            .0
+            .cron
+            .current_jobs()
+            .await
+            .into_iter()
+            .find(|job| job.id == job_id)
+        else {
+            return Ok(format!(
+                "Job {job_id} not found; use list_scheduled for the ids."
+            ));
+        };
+        let mut lines = vec![
+            format!("{} [id {}]", job.name, job.id),
+            format!("Status: {}", if job.enabled { "active" } else { "paused" }),
+            format!("Schedule: {}", describe_schedule(&job.schedule)),
+            format!("Action: {}", describe_payload(&job)),
+        ];
+        match (&job.payload.channel, &job.payload.to) {
+            (Some(channel), Some(to)) if job.payload.deliver => {
+                lines.push(format!("Reports to: {channel}:{to}"))
+            }
+            _ => lines.push("Reports to: nobody (output is not delivered)".to_string()),
+        }
+        if job.enabled {
+            lines.push(match job.state.next_run_at_ms {
+                Some(at) => format!("Next run: {}", format_ms(at)),
+                None => "Next run: none".to_string(),
+            });
+        }
+        match job.state.last_run_at_ms {
+            Some(at) => lines.push(format!(
+                "Last run: {} ({})",
+                format_ms(at),
+                job.state.last_status.as_deref().unwrap_or("unknown")
+            )),
+            None => lines.push("Last run: never".to_string()),
+        }
+        if let Some(error) = &job.state.last_error {
+            lines.push(format!("Last error: {error}"));
+        }
+        if job.delete_after_run {
+            lines.push("Removed after it runs.".to_string());
+        }
+        lines.push(format!("Created: {}", format_ms(job.created_at_ms)));
+        Ok(lines.join("\n"))
     }
 }
 
@@ -39,6 +251,17 @@ impl Tool for CronTool {
         "cron"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["scheduling"]
+    }
+
+    fn mutates(&self, params: &Map<String, Value>) -> bool {
+        matches!(
+            params.get("action").and_then(Value::as_str),
+            Some("add" | "remove")
+        )
+    }
+
     fn description(&self) -> &str {
         "Schedule reminders and recurring tasks. Actions: add, list, remove."
     }
@@ -100,13 +323,7 @@ impl CronTool {
             return Ok("Error: message is required for add".to_string());
         }
 
-        let (channel, chat_id) = {
-            let guard = self
-                .context
-                .lock()
-                .map_err(|_| anyhow!("failed to lock cron context"))?;
-            (guard.channel.clone(), guard.chat_id.clone())
-        };
+        let (channel, chat_id) = self.chat();
         if channel.is_empty() || chat_id.is_empty() {
             return Ok("Error: no session context (channel/chat_id)".to_string());
         }
@@ -125,6 +342,7 @@ impl CronTool {
             CronSchedule {
                 kind: "cron".to_string(),
                 expr: Some(expr.to_string()),
+                tz: Some("local".to_string()),
                 ..Default::default()
             }
         } else if let Some(at_raw) = at {
@@ -180,7 +398,68 @@ impl CronTool {
 
 #[cfg(test)]
 mod tests {
-    use super::CronTool;
+    use super::*;
+
+    #[tokio::test]
+    async fn lists_and_describes_the_jobs_of_this_chat() {
+        let dir = std::env::temp_dir().join(format!("nanobot-cron-tools-{}", uuid::Uuid::new_v4()));
+        let cron = Arc::new(CronService::new(dir.join("jobs.json")));
+        let every_day = CronSchedule {
+            kind: "every".to_string(),
+            every_ms: Some(86_400_000),
+            ..Default::default()
+        };
+        let job = cron
+            .add_job(
+                "Water the plants".to_string(),
+                every_day.clone(),
+                "Remind me to water the plants".to_string(),
+                true,
+                Some("telegram".to_string()),
+                Some("42".to_string()),
+                false,
+            )
+            .await
+            .expect("job");
+        cron.add_job(
+            "Other chat".to_string(),
+            every_day,
+            "ping".to_string(),
+            true,
+            Some("telegram".to_string()),
+            Some("7".to_string()),
+            false,
+        )
+        .await
+        .expect("job");
+        let tool = Arc::new(CronTool::new(cron));
+        tool.set_context("telegram", "42");
+        let tools = tool.tools();
+        let run = |name: &str, params: Value| {
+            let tool = tools.iter().find(|t| t.name() == name).unwrap().clone();
+            async move { tool.execute(params.as_object().unwrap()).await.unwrap() }
+        };
+
+        let listed = run("list_scheduled", json!({})).await;
+        assert!(listed.contains("Water the plants"));
+        assert!(listed.contains("every 1 day(s)"));
+        assert!(!listed.contains("Other chat"));
+        assert!(
+            run("list_scheduled", json!({ "scope": "all" }))
+                .await
+                .contains("Other chat")
+        );
+
+        let described = run("describe_job", json!({ "job_id": job.id })).await;
+        assert!(described.contains("Reports to: telegram:42"));
+        assert!(described.contains("Last run: never"));
+        assert!(
+            run("describe_job", json!({ "job_id": "nope" }))
+                .await
+                .contains("not found")
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
 
     #[test]
     fn parse_at_ms_accepts_rfc3339() {
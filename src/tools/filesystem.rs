@@ -87,65 +87,20 @@ impl ReadFileTool {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::resolve_path;
-    use std::path::PathBuf;
-    use uuid::Uuid;
-
-    fn test_root() -> PathBuf {
-        std::env::temp_dir().join(format!("nanobot-rs-fs-{}", Uuid::new_v4()))
-    }
-
-    #[test]
-    fn rejects_component_prefix_bypass() {
-        let root = test_root();
-        let allowed = root.join("workspace");
-        let evil = root.join("workspace_evil");
-        std::fs::create_dir_all(&allowed).expect("create allowed");
-        std::fs::create_dir_all(&evil).expect("create evil");
-
-        let evil_target = evil.join("note.txt");
-        let result = resolve_path(evil_target.to_str().expect("utf8 path"), Some(&allowed));
-        assert!(result.is_err());
-
-        let _ = std::fs::remove_dir_all(&root);
-    }
-
-    #[test]
-    fn rejects_symlink_escape_for_new_file() {
-        let root = test_root();
-        let allowed = root.join("workspace");
-        let outside = root.join("outside");
-        let link = allowed.join("link");
-        std::fs::create_dir_all(&allowed).expect("create allowed");
-        std::fs::create_dir_all(&outside).expect("create outside");
-
-        #[cfg(unix)]
-        let symlink_result = std::os::unix::fs::symlink(&outside, &link);
-        #[cfg(windows)]
-        let symlink_result = std::os::windows::fs::symlink_dir(&outside, &link);
-
-        if symlink_result.is_err() {
-            // Windows without developer mode/admin often cannot create symlinks.
-            let _ = std::fs::remove_dir_all(&root);
-            return;
-        }
-
-        let escaped_target = link.join("secret.txt");
-        let result = resolve_path(escaped_target.to_str().expect("utf8 path"), Some(&allowed));
-        assert!(result.is_err());
-
-        let _ = std::fs::remove_dir_all(&root);
-    }
-}
-
 #[async_trait]
 impl Tool for ReadFileTool {
     fn name(&self) -> &str {
         "read_file"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["filesystem"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
     fn description(&self) -> &str {
         "Read the contents of a file at the given path."
     }
@@ -192,6 +147,14 @@ impl Tool for WriteFileTool {
         "write_file"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["filesystem"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
         "Write content to a file at the given path. Creates parent directories if needed."
     }
@@ -239,6 +202,14 @@ impl Tool for EditFileTool {
         "edit_file"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["filesystem"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
         "Edit a file by replacing old_text with new_text. old_text must appear exactly once."
     }
@@ -300,6 +271,14 @@ impl Tool for ListDirTool {
         "list_dir"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["filesystem"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
     fn description(&self) -> &str {
         "List the contents of a directory."
     }
@@ -341,3 +320,56 @@ impl Tool for ListDirTool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_path;
+    use std::path::PathBuf;
+    use uuid::Uuid;
+
+    fn test_root() -> PathBuf {
+        std::env::temp_dir().join(format!("nanobot-rs-fs-{}", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn rejects_component_prefix_bypass() {
+        let root = test_root();
+        let allowed = root.join("workspace");
+        let evil = root.join("workspace_evil");
+        std::fs::create_dir_all(&allowed).expect("create allowed");
+        std::fs::create_dir_all(&evil).expect("create evil");
+
+        let evil_target = evil.join("note.txt");
+        let result = resolve_path(evil_target.to_str().expect("utf8 path"), Some(&allowed));
+        assert!(result.is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn rejects_symlink_escape_for_new_file() {
+        let root = test_root();
+        let allowed = root.join("workspace");
+        let outside = root.join("outside");
+        let link = allowed.join("link");
+        std::fs::create_dir_all(&allowed).expect("create allowed");
+        std::fs::create_dir_all(&outside).expect("create outside");
+
+        #[cfg(unix)]
+        let symlink_result = std::os::unix::fs::symlink(&outside, &link);
+        #[cfg(windows)]
+        let symlink_result = std::os::windows::fs::symlink_dir(&outside, &link);
+
+        if symlink_result.is_err() {
+            // Windows without developer mode/admin often cannot create symlinks.
+            let _ = std::fs::remove_dir_all(&root);
+            return;
+        }
+
+        let escaped_target = link.join("secret.txt");
+        let result = resolve_path(escaped_target.to_str().expect("utf8 path"), Some(&allowed));
+        assert!(result.is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -86,6 +86,15 @@ impl Tool for HttpRequestTool {
         "http_request"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["web"]
+    }
+
+    fn mutates(&self, params: &Map<String, Value>) -> bool {
+        !parse_method(params.get("method").and_then(Value::as_str))
+            .is_ok_and(|method| method.is_safe())
+    }
+
     fn description(&self) -> &str {
         "Send HTTP requests (GET/POST/PUT/PATCH/DELETE/etc.) to APIs, including localhost and LAN services."
     }
@@ -194,7 +203,7 @@ impl Tool for HttpRequestTool {
             })
             .unwrap_or_default();
 
-        let client = reqwest::Client::builder()
+        let client = crate::network::client_builder()
             .timeout(std::time::Duration::from_secs(timeout_s))
             .danger_accept_invalid_certs(insecure_tls)
             .redirect(if follow_redirects {
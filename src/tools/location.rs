@@ -0,0 +1,180 @@
+use crate::location::{LocationService, PlaceEvent};
+use crate::tools::base::{ChatContext, Tool};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::sync::Arc;
+
+/// State shared by `get_location`, `remind_at_place` and
+/// `cancel_place_reminder`: the location service and the chat reminders go to.
+pub struct LocationTools {
+    service: Arc<LocationService>,
+    context: ChatContext,
+}
+
+impl LocationTools {
+    pub fn new(service: Arc<LocationService>) -> Self {
+        Self {
+            service,
+            context: ChatContext::default(),
+        }
+    }
+
+    pub fn service(&self) -> &Arc<LocationService> {
+        &self.service
+    }
+
+    pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
+        self.context.set(channel, chat_id);
+    }
+
+    /// The location tools, ready to register; the reminder tools only when
+    /// the phone reports positions.
+    pub fn tools(self: &Arc<Self>) -> Vec<Arc<dyn Tool>> {
+        let mut tools: Vec<Arc<dyn Tool>> = vec![Arc::new(GetLocationTool(self.clone()))];
+        if self.service.source() == "webhook" {
+            tools.push(Arc::new(RemindAtPlaceTool(self.clone())));
+            tools.push(Arc::new(CancelPlaceReminderTool(self.clone())));
+        }
+        tools
+    }
+}
+
+pub struct GetLocationTool(Arc<LocationTools>);
+
+#[async_trait]
+impl Tool for GetLocationTool {
+    fn name(&self) -> &str {
+        "get_location"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["location"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "Where the user is now, their named places (home, office...) and pending place reminders. Use it for weather, 'near me' searches and directions."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    async fn execute(&self, _params: &Map<String, Value>) -> Result<String> {
+        let service = &self.0.service;
+        let mut lines = vec![match service.current().await? {
+            Some(location) => format!("Location: {}", location.describe(service.places())),
+            None => "Location: unknown".to_string(),
+        }];
+        if !service.places().is_empty() {
+            lines.push(format!(
+                "Places: {}",
+                service
+                    .places()
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        let reminders = service.reminders();
+        if !reminders.is_empty() {
+            lines.push("Place reminders:".to_string());
+            lines.extend(reminders.iter().map(|reminder| reminder.line()));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+pub struct RemindAtPlaceTool(Arc<LocationTools>);
+
+#[async_trait]
+impl Tool for RemindAtPlaceTool {
+    fn name(&self) -> &str {
+        "remind_at_place"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["location"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Remind the user in this chat when they next arrive at or leave one of their named places (\"remind me to water the plants when I get home\"). Fires once."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "place": { "type": "string", "description": "A named place, e.g. home" },
+                "on": { "type": "string", "enum": ["arrive", "leave"] },
+                "message": { "type": "string", "description": "The reminder text" }
+            },
+            "required": ["place", "message"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let string = |key: &str| params.get(key).and_then(Value::as_str).unwrap_or_default();
+        let (channel, chat_id) = self.0.context.get();
+        if channel.is_empty() || chat_id.is_empty() {
+            return Err(anyhow!("no chat to send the reminder to"));
+        }
+        let reminder = self.0.service.add_reminder(
+            string("place"),
+            PlaceEvent::parse(string("on"))?,
+            string("message"),
+            &channel,
+            &chat_id,
+        )?;
+        Ok(format!("Reminder set: {}", reminder.line()))
+    }
+}
+
+pub struct CancelPlaceReminderTool(Arc<LocationTools>);
+
+#[async_trait]
+impl Tool for CancelPlaceReminderTool {
+    fn name(&self) -> &str {
+        "cancel_place_reminder"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["location"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Cancel a place reminder by the id get_location lists."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" }
+            },
+            "required": ["id"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let id = params
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: id"))?;
+        let reminder = self.0.service.remove_reminder(id)?;
+        Ok(format!("Cancelled {}", reminder.line()))
+    }
+}
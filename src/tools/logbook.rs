@@ -0,0 +1,164 @@
+use crate::logbook::{LogEntry, Logbook, parse_amount, this_month};
+use crate::tools::base::Tool;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::Local;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The quick-capture tools: `log_entry` and `log_summary`.
+pub struct LogbookTools {
+    book: Logbook,
+}
+
+impl LogbookTools {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            book: Logbook::new(workspace),
+        }
+    }
+
+    /// Both logbook tools, ready to register.
+    pub fn tools(self: &Arc<Self>) -> Vec<Arc<dyn Tool>> {
+        vec![
+            Arc::new(LogEntryTool(self.clone())),
+            Arc::new(LogSummaryTool(self.clone())),
+        ]
+    }
+}
+
+pub struct LogEntryTool(Arc<LogbookTools>);
+
+#[async_trait]
+impl Tool for LogEntryTool {
+    fn name(&self) -> &str {
+        "log_entry"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["logbook"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Record a quick entry in the user's logbook: an expense (\"12.50 lunch\"), a measurement, or a short note. Pick a short lowercase category (food, transport, rent, weight, idea...)."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "category": { "type": "string" },
+                "amount": { "type": "string", "description": "e.g. \"12.5\", \"12,50 €\" or \"30 USD\"" },
+                "currency": { "type": "string", "description": "ISO code, when not part of amount" },
+                "text": { "type": "string", "description": "What it was" }
+            },
+            "required": ["category"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let category = params
+            .get("category")
+            .and_then(Value::as_str)
+            .map(|category| category.trim().trim_start_matches('#').to_lowercase())
+            .filter(|category| !category.is_empty())
+            .ok_or_else(|| anyhow!("missing required string field: category"))?;
+        let (amount, mut currency) = match params.get("amount") {
+            Some(Value::Number(number)) => (number.as_f64(), None),
+            Some(Value::String(text)) if !text.trim().is_empty() => {
+                let (amount, currency) = parse_amount(text)?;
+                (Some(amount), currency)
+            }
+            _ => (None, None),
+        };
+        if let Some(code) = params
+            .get("currency")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|code| !code.is_empty())
+        {
+            currency = Some(code.to_uppercase());
+        }
+        let entry = LogEntry {
+            time: Local::now(),
+            category,
+            amount,
+            currency: amount.and(currency).unwrap_or_default(),
+            text: params
+                .get("text")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        };
+        self.0.book.append(&entry)?;
+        Ok(format!("Logged {}", entry.line()))
+    }
+}
+
+pub struct LogSummaryTool(Arc<LogbookTools>);
+
+#[async_trait]
+impl Tool for LogSummaryTool {
+    fn name(&self) -> &str {
+        "log_summary"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["logbook"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "Sum up a month of the user's logbook: totals per category and currency, plus the entries themselves when asked."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "month": { "type": "string", "description": "YYYY-MM; defaults to this month" },
+                "category": { "type": "string" },
+                "include_entries": { "type": "boolean" }
+            }
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let month = params
+            .get("month")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|month| !month.is_empty())
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(this_month);
+        let category = params
+            .get("category")
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|category| !category.is_empty());
+        let book = &self.0.book;
+        let mut text = book.summarize(&month, category)?.render();
+        if params
+            .get("include_entries")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+        {
+            for entry in book.entries().into_iter().filter(|entry| {
+                entry.time.format("%Y-%m").to_string() == month
+                    && category.is_none_or(|category| entry.category.eq_ignore_ascii_case(category))
+            }) {
+                text.push_str(&format!("\n{}", entry.line()));
+            }
+        }
+        Ok(text)
+    }
+}
@@ -1,35 +1,25 @@
-use crate::bus::OutboundMessage;
-use crate::tools::base::Tool;
+use crate::bus::{MessageBus, OutboundMessage};
+use crate::tools::base::{ChatContext, Tool};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use serde_json::{Map, Value, json};
-use std::sync::Mutex;
-use tokio::sync::mpsc;
-
-#[derive(Default)]
-struct MessageContext {
-    channel: String,
-    chat_id: String,
-}
+use std::sync::Arc;
 
 pub struct MessageTool {
-    sender: mpsc::Sender<OutboundMessage>,
-    context: Mutex<MessageContext>,
+    bus: Arc<MessageBus>,
+    context: ChatContext,
 }
 
 impl MessageTool {
-    pub fn new(sender: mpsc::Sender<OutboundMessage>) -> Self {
+    pub fn new(bus: Arc<MessageBus>) -> Self {
         Self {
-            sender,
-            context: Mutex::new(MessageContext::default()),
+            bus,
+            context: ChatContext::default(),
         }
     }
 
     pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
-        if let Ok(mut guard) = self.context.lock() {
-            guard.channel = channel.into();
-            guard.chat_id = chat_id.into();
-        }
+        self.context.set(channel, chat_id);
     }
 }
 
@@ -39,6 +29,23 @@ impl Tool for MessageTool {
         "message"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["messaging"]
+    }
+
+    /// Replying in the current chat is part of the conversation; sending
+    /// anywhere else is not.
+    fn mutates(&self, params: &Map<String, Value>) -> bool {
+        let target = |field: &str| params.get(field).and_then(Value::as_str);
+        match (target("channel"), target("chat_id")) {
+            (Some(channel), Some(chat_id)) => {
+                let (current_channel, current_chat) = self.context.get();
+                channel != current_channel || chat_id != current_chat
+            }
+            _ => false,
+        }
+    }
+
     fn description(&self) -> &str {
         "Send a message to the user. Use this when you need to communicate a progress update to a chat channel."
     }
@@ -74,11 +81,7 @@ impl Tool for MessageTool {
             if let (Some(channel), Some(chat_id)) = (explicit_channel, explicit_chat_id) {
                 (channel, chat_id)
             } else {
-                let guard = self
-                    .context
-                    .lock()
-                    .map_err(|_| anyhow!("failed to lock message tool context"))?;
-                (guard.channel.clone(), guard.chat_id.clone())
+                self.context.get()
             };
 
         if channel.is_empty() || chat_id.is_empty() {
@@ -86,8 +89,8 @@ impl Tool for MessageTool {
         }
 
         let msg = OutboundMessage::new(channel.clone(), chat_id.clone(), content);
-        self.sender
-            .send(msg)
+        self.bus
+            .publish_outbound(msg)
             .await
             .map_err(|err| anyhow!("Error sending message: {err}"))?;
 
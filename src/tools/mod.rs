@@ -1,10 +1,20 @@
+pub mod ask;
 pub mod base;
+pub mod bookmarks;
+pub mod contacts;
 pub mod cron;
 pub mod filesystem;
 pub mod http;
+pub mod location;
+pub mod logbook;
 pub mod message;
+pub mod pages;
+pub mod publish;
 pub mod registry;
 pub mod sessions;
 pub mod shell;
 pub mod spawn;
+pub mod tasks;
+pub mod template;
 pub mod web;
+pub mod workflow;
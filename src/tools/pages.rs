@@ -0,0 +1,225 @@
+//! Paged tool results.
+//!
+//! A result longer than one page is cut at a line break and the rest is kept
+//! under a cursor. The model sees the first page with a footer naming the
+//! cursor and calls `continue_result` to read on, so long listings, command
+//! output and files are neither cut off silently nor dumped into the context
+//! in one piece.
+
+use crate::tools::base::Tool;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const CONTINUE_RESULT_TOOL: &str = "continue_result";
+pub const DEFAULT_PAGE_CHARS: usize = 8_000;
+/// Cursors kept at once; the oldest is dropped first.
+const MAX_STORED: usize = 32;
+const CURSOR_TTL: Duration = Duration::from_secs(30 * 60);
+/// Output past this is dropped before paging; nobody reads megabytes page by page.
+const MAX_RESULT_CHARS: usize = 1_000_000;
+
+struct Remainder {
+    cursor: String,
+    tool: String,
+    text: String,
+    /// Characters already returned.
+    shown: usize,
+    total: usize,
+    dropped: usize,
+    created_at: Instant,
+}
+
+pub struct ResultPages {
+    page_chars: usize,
+    stored: Mutex<Vec<Remainder>>,
+}
+
+/// Splits after the last line break within `max_chars`, or at `max_chars`
+/// when the first half of the page has none.
+fn split_page(text: &str, max_chars: usize) -> (&str, &str) {
+    let Some((limit, _)) = text.char_indices().nth(max_chars) else {
+        return (text, "");
+    };
+    let at = match text[..limit].rfind('\n') {
+        Some(newline) if newline >= limit / 2 => newline + 1,
+        _ => limit,
+    };
+    text.split_at(at)
+}
+
+impl ResultPages {
+    pub fn new(page_chars: usize) -> Self {
+        Self {
+            page_chars: page_chars.max(500),
+            stored: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Remainder>> {
+        self.stored.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// `output` as is when it fits on one page; otherwise its first page
+    /// and a footer with the cursor for the rest.
+    pub fn page(&self, tool: &str, output: String) -> String {
+        let total = output.chars().count();
+        if total <= self.page_chars {
+            return output;
+        }
+        let (text, dropped) = if total > MAX_RESULT_CHARS {
+            let kept = split_page(&output, MAX_RESULT_CHARS).0.to_string();
+            let kept_chars = kept.chars().count();
+            (kept, total - kept_chars)
+        } else {
+            (output, 0)
+        };
+        let total = total - dropped;
+        let (first, rest) = split_page(&text, self.page_chars);
+        let shown = first.chars().count();
+        let cursor = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+        let page = format!(
+            "{}\n\n[Showing characters 1-{shown} of {total}. Call {CONTINUE_RESULT_TOOL} with cursor \"{cursor}\" for the next page.]",
+            first.trim_end_matches('\n')
+        );
+
+        let mut stored = self.lock();
+        stored.retain(|r| r.created_at.elapsed() < CURSOR_TTL);
+        if stored.len() >= MAX_STORED {
+            stored.remove(0);
+        }
+        stored.push(Remainder {
+            cursor,
+            tool: tool.to_string(),
+            text: rest.to_string(),
+            shown,
+            total,
+            dropped,
+            created_at: Instant::now(),
+        });
+        page
+    }
+
+    /// The next page under `cursor`; the cursor stays valid until the last
+    /// page has been returned.
+    pub fn next(&self, cursor: &str) -> Result<String> {
+        let mut stored = self.lock();
+        let index = stored
+            .iter()
+            .position(|r| r.cursor == cursor.trim() && r.created_at.elapsed() < CURSOR_TTL)
+            .ok_or_else(|| {
+                anyhow!("unknown or expired cursor '{cursor}'; run the original tool call again")
+            })?;
+        let remainder = &mut stored[index];
+        let (page, rest) = split_page(&remainder.text, self.page_chars);
+        let page = page.to_string();
+        let rest = rest.to_string();
+        let start = remainder.shown + 1;
+        remainder.shown += page.chars().count();
+        let (end, total) = (remainder.shown, remainder.total);
+        if rest.is_empty() {
+            let tool = remainder.tool.clone();
+            let dropped = remainder.dropped;
+            stored.remove(index);
+            let mut footer = format!("[End of {tool} result: characters {start}-{end} of {total}.");
+            if dropped > 0 {
+                footer.push_str(&format!(" {dropped} further characters were not kept."));
+            }
+            footer.push(']');
+            return Ok(format!("{}\n\n{footer}", page.trim_end_matches('\n')));
+        }
+        remainder.text = rest;
+        Ok(format!(
+            "{}\n\n[Showing characters {start}-{end} of {total}. Call {CONTINUE_RESULT_TOOL} with cursor \"{}\" for the next page.]",
+            page.trim_end_matches('\n'),
+            remainder.cursor
+        ))
+    }
+}
+
+/// Reads the next page of a result that was split by `ResultPages`.
+pub struct ContinueResultTool {
+    pages: Arc<ResultPages>,
+}
+
+impl ContinueResultTool {
+    pub fn new(pages: Arc<ResultPages>) -> Self {
+        Self { pages }
+    }
+}
+
+#[async_trait]
+impl Tool for ContinueResultTool {
+    fn name(&self) -> &str {
+        CONTINUE_RESULT_TOOL
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "Fetch the next page of a tool result that was split into pages. Pass the cursor from the result's footer."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "cursor": { "type": "string", "description": "Cursor from the previous page's footer" }
+            },
+            "required": ["cursor"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let cursor = params
+            .get("cursor")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: cursor"))?;
+        self.pages.next(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor_of(page: &str) -> String {
+        page.rsplit("cursor \"")
+            .next()
+            .and_then(|rest| rest.split('"').next())
+            .expect("cursor")
+            .to_string()
+    }
+
+    #[test]
+    fn pages_long_output_at_line_breaks() {
+        let pages = ResultPages::new(500);
+        assert_eq!(pages.page("list_dir", "short".to_string()), "short");
+
+        let output = (0..100)
+            .map(|i| format!("[FILE] file-{i:03}.txt"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let first = pages.page("list_dir", output.clone());
+        assert!(first.starts_with("[FILE] file-000.txt\n"));
+        assert!(first.contains("[Showing characters 1-"));
+        let cursor = cursor_of(&first);
+
+        let mut seen = first.split("\n\n[").next().unwrap().to_string();
+        loop {
+            let page = pages.next(&cursor).expect("page");
+            let body = page.split("\n\n[").next().unwrap();
+            seen.push('\n');
+            seen.push_str(body);
+            if page.contains("[End of list_dir result") {
+                break;
+            }
+        }
+        assert_eq!(seen, output);
+        assert!(pages.next(&cursor).is_err());
+    }
+}
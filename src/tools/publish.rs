@@ -0,0 +1,80 @@
+use crate::providers::base::LLMProvider;
+use crate::publish::publish_session;
+use crate::session::SessionManager;
+use crate::tools::base::Tool;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub struct PublishNoteTool {
+    provider: Arc<dyn LLMProvider>,
+    model: String,
+    sessions: Arc<SessionManager>,
+    notes_dir: PathBuf,
+}
+
+impl PublishNoteTool {
+    pub fn new(
+        provider: Arc<dyn LLMProvider>,
+        model: String,
+        sessions: Arc<SessionManager>,
+        notes_dir: PathBuf,
+    ) -> Self {
+        Self {
+            provider,
+            model,
+            sessions,
+            notes_dir,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for PublishNoteTool {
+    fn name(&self) -> &str {
+        "publish_note"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["sessions"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Turn a session into a cleaned-up Markdown note with front matter, wikilinks and source citations, saved to the notes folder. Returns the note's path."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "session": { "type": "string", "description": "Session key like telegram:123456; the current one is <Channel>:<Chat ID>" },
+                "title": { "type": "string", "description": "Note title; picked from the content when omitted" }
+            },
+            "required": ["session"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let session = params
+            .get("session")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: session"))?;
+        let title = params.get("title").and_then(Value::as_str);
+        let path = publish_session(
+            self.provider.as_ref(),
+            &self.model,
+            &self.sessions,
+            session,
+            &self.notes_dir,
+            title,
+        )
+        .await?;
+        Ok(format!("Published {}", path.display()))
+    }
+}
@@ -1,25 +1,143 @@
-use crate::tools::base::Tool;
+use crate::tool_stats::ToolStatsRecorder;
+use crate::tools::base::{Tool, check_schema};
+use crate::tools::pages::{CONTINUE_RESULT_TOOL, ContinueResultTool, ResultPages};
+use anyhow::{Result, bail};
+use serde::Serialize;
 use serde_json::{Map, Value};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use tracing::{debug, warn};
 
+/// What the registry knows about one tool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolInfo {
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub enabled: bool,
+}
+
+/// The tools an agent can call. Embedders build one with their own tools and
+/// hand it to `AgentLoop::with_tools`.
 pub struct ToolRegistry {
     tools: HashMap<String, Arc<dyn Tool>>,
+    /// Tags given at registration, on top of `Tool::tags`.
+    tags: HashMap<String, BTreeSet<String>>,
+    /// Tools switched off at runtime; hidden from the model and refused.
+    disabled: RwLock<HashSet<String>>,
+    /// Splits long results into pages once `enable_paging` is called.
+    pages: Option<Arc<ResultPages>>,
+    stats: Option<Arc<ToolStatsRecorder>>,
+    /// Refuses calls that `Tool::mutates` reports as changing something.
+    read_only: bool,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            tags: HashMap::new(),
+            disabled: RwLock::new(HashSet::new()),
+            pages: None,
+            stats: None,
+            read_only: false,
         }
     }
 
+    /// Reports every execution to `recorder`, which may switch off a tool
+    /// that keeps failing.
+    pub fn set_stats(&mut self, recorder: Arc<ToolStatsRecorder>) {
+        self.stats = Some(recorder);
+    }
+
+    /// Read-only mode: tools that always mutate are hidden and every
+    /// mutating call is refused.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Whether read-only mode hides `name`: the tool changes something
+    /// whatever its arguments.
+    pub fn hidden_by_read_only(&self, name: &str) -> bool {
+        self.read_only
+            && self
+                .tools
+                .get(name)
+                .is_some_and(|tool| tool.mutates(&Map::new()))
+    }
+
+    /// Splits results longer than `page_chars` into pages and registers
+    /// `continue_result` to read them.
+    pub fn enable_paging(&mut self, page_chars: usize) {
+        let pages = Arc::new(ResultPages::new(page_chars));
+        self.register(Arc::new(ContinueResultTool::new(pages.clone())));
+        self.pages = Some(pages);
+    }
+
+    /// Registers `tool`; one with a malformed parameter schema is skipped
+    /// with a warning, since providers reject the whole request over it.
     pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        if let Err(err) = self.try_register(tool) {
+            warn!("{err}");
+        }
+    }
+
+    pub fn register_boxed(&mut self, tool: Box<dyn Tool>) {
+        self.register(Arc::from(tool));
+    }
+
+    /// Registers `tool` with extra tags.
+    pub fn register_with_tags(&mut self, tool: Arc<dyn Tool>, tags: &[&str]) {
+        let name = tool.name().to_string();
+        self.register(tool);
+        if self.has(&name) {
+            self.tags
+                .entry(name)
+                .or_default()
+                .extend(tags.iter().map(|tag| tag.to_string()));
+        }
+    }
+
+    pub fn try_register(&mut self, tool: Arc<dyn Tool>) -> Result<()> {
+        let errors = check_schema(&tool.parameters());
+        if !errors.is_empty() {
+            bail!(
+                "tool '{}' not registered, invalid schema: {}",
+                tool.name(),
+                errors.join("; ")
+            );
+        }
         self.tools.insert(tool.name().to_string(), tool);
+        Ok(())
     }
 
     pub fn unregister(&mut self, name: &str) {
         self.tools.remove(name);
+        self.tags.remove(name);
+    }
+
+    /// Moves every tool of `other` into this registry, replacing tools with
+    /// the same name and keeping their tags and disabled state.
+    pub fn extend(&mut self, other: ToolRegistry) {
+        let disabled = other
+            .disabled
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (name, tool) in other.tools {
+            // Cursors live in this registry's pages, not in the other one's.
+            if name == CONTINUE_RESULT_TOOL && self.pages.is_some() {
+                continue;
+            }
+            self.tags.remove(&name);
+            self.set_enabled(&name, true);
+            self.tools.insert(name.clone(), tool);
+            self.set_enabled(&name, !disabled.contains(&name));
+        }
+        self.tags.extend(other.tags);
     }
 
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
@@ -31,25 +149,145 @@ impl ToolRegistry {
     }
 
     pub fn get_definitions(&self) -> Vec<Value> {
-        self.tools.values().map(|tool| tool.to_schema()).collect()
+        self.tools
+            .iter()
+            .filter(|(name, _)| self.is_enabled(name) && !self.hidden_by_read_only(name))
+            .map(|(_, tool)| tool.to_schema())
+            .collect()
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.disabled
+            .read()
+            .map(|disabled| !disabled.contains(name))
+            .unwrap_or(true)
+    }
+
+    /// Turns a registered tool on or off; false when no such tool exists.
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        if !self.has(name) {
+            return false;
+        }
+        if let Ok(mut disabled) = self.disabled.write() {
+            if enabled {
+                disabled.remove(name);
+                if let Some(stats) = &self.stats {
+                    stats.reset(name);
+                }
+            } else {
+                disabled.insert(name.to_string());
+            }
+        }
+        true
+    }
+
+    /// Turns every tool tagged `tag` on or off; returns how many there were.
+    pub fn set_enabled_by_tag(&self, tag: &str, enabled: bool) -> usize {
+        let names = self.names_with_tag(tag);
+        for name in &names {
+            self.set_enabled(name, enabled);
+        }
+        names.len()
+    }
+
+    /// The tool's own tags plus those given at registration, sorted.
+    pub fn tags(&self, name: &str) -> Vec<String> {
+        let Some(tool) = self.tools.get(name) else {
+            return Vec::new();
+        };
+        let mut tags = tool
+            .tags()
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect::<BTreeSet<_>>();
+        if let Some(extra) = self.tags.get(name) {
+            tags.extend(extra.iter().cloned());
+        }
+        tags.into_iter().collect()
+    }
+
+    /// Names of tools tagged `tag`, sorted.
+    pub fn names_with_tag(&self, tag: &str) -> Vec<String> {
+        let mut names = self
+            .tools
+            .keys()
+            .filter(|name| self.tags(name).iter().any(|t| t == tag))
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn info(&self, name: &str) -> Option<ToolInfo> {
+        let tool = self.tools.get(name)?;
+        Some(ToolInfo {
+            name: name.to_string(),
+            description: tool.description().to_string(),
+            tags: self.tags(name),
+            enabled: self.is_enabled(name),
+        })
+    }
+
+    /// Every registered tool, sorted by name.
+    pub fn list(&self) -> Vec<ToolInfo> {
+        let mut names = self.tool_names();
+        names.sort();
+        names.iter().filter_map(|name| self.info(name)).collect()
     }
 
     pub async fn execute(&self, name: &str, params: &Map<String, Value>) -> String {
         let Some(tool) = self.tools.get(name) else {
+            warn!(tool = name, "tool not found");
             return format!("Error: Tool '{name}' not found");
         };
+        if !self.is_enabled(name) {
+            warn!(tool = name, "tool is disabled");
+            return format!("Error: Tool '{name}' is disabled");
+        }
+        if self.read_only && tool.mutates(params) {
+            warn!(tool = name, "refused in read-only mode");
+            return format!(
+                "Error: Tool '{name}' would change files, run commands or act outside this conversation, which read-only mode does not allow"
+            );
+        }
 
         let errors = tool.validate_params(params);
         if !errors.is_empty() {
-            return format!(
-                "Error: Invalid parameters for tool '{name}': {}",
+            warn!(
+                tool = name,
+                "invalid tool parameters: {}",
                 errors.join("; ")
             );
+            return format!(
+                "Error: Invalid parameters for tool '{name}': {}. Fix the arguments to match this schema and call it again: {}",
+                errors.join("; "),
+                tool.parameters()
+            );
         }
 
-        match tool.execute(params).await {
-            Ok(output) => output,
-            Err(err) => format!("Error executing {name}: {err}"),
+        let started = std::time::Instant::now();
+        let result = tool.execute(params).await;
+        let elapsed = started.elapsed();
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let output = match result {
+            Ok(output) => {
+                debug!(tool = name, elapsed_ms, "tool finished");
+                output
+            }
+            Err(err) => {
+                warn!(tool = name, elapsed_ms, "tool failed: {err}");
+                format!("Error executing {name}: {err}")
+            }
+        };
+        if let Some(stats) = &self.stats
+            && stats.record(name, elapsed, &output)
+        {
+            warn!(tool = name, "every recent call failed, disabling the tool");
+            self.set_enabled(name, false);
+        }
+        match &self.pages {
+            Some(pages) if name != CONTINUE_RESULT_TOOL => pages.page(name, output),
+            _ => output,
         }
     }
 
@@ -60,6 +298,10 @@ impl ToolRegistry {
     pub fn len(&self) -> usize {
         self.tools.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
 }
 
 impl Default for ToolRegistry {
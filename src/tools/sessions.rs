@@ -1,12 +1,11 @@
-use crate::bus::OutboundMessage;
+use crate::bus::{MessageBus, OutboundMessage};
 use crate::session::SessionManager;
-use crate::tools::base::Tool;
+use crate::tools::base::{ChatContext, Tool};
 use crate::utils::parse_session_key;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use serde_json::{Map, Value, json};
-use std::sync::{Arc, Mutex};
-use tokio::sync::mpsc;
+use std::sync::Arc;
 
 pub struct SessionsListTool {
     sessions: Arc<SessionManager>,
@@ -24,6 +23,14 @@ impl Tool for SessionsListTool {
         "sessions_list"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["sessions"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
     fn description(&self) -> &str {
         "List available session keys."
     }
@@ -60,6 +67,14 @@ impl Tool for SessionsHistoryTool {
         "sessions_history"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["sessions"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
     fn description(&self) -> &str {
         "Read message history from a given session."
     }
@@ -97,30 +112,21 @@ impl Tool for SessionsHistoryTool {
     }
 }
 
-#[derive(Default)]
-struct SessionsSendContext {
-    origin_channel: String,
-    origin_chat_id: String,
-}
-
 pub struct SessionsSendTool {
-    sender: mpsc::Sender<OutboundMessage>,
-    context: Mutex<SessionsSendContext>,
+    bus: Arc<MessageBus>,
+    context: ChatContext,
 }
 
 impl SessionsSendTool {
-    pub fn new(sender: mpsc::Sender<OutboundMessage>) -> Self {
+    pub fn new(bus: Arc<MessageBus>) -> Self {
         Self {
-            sender,
-            context: Mutex::new(SessionsSendContext::default()),
+            bus,
+            context: ChatContext::default(),
         }
     }
 
     pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
-        if let Ok(mut guard) = self.context.lock() {
-            guard.origin_channel = channel.into();
-            guard.origin_chat_id = chat_id.into();
-        }
+        self.context.set(channel, chat_id);
     }
 }
 
@@ -130,6 +136,14 @@ impl Tool for SessionsSendTool {
         "sessions_send"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["sessions"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
         "Send a plain message to another existing session (channel:chat_id)."
     }
@@ -157,14 +171,13 @@ impl Tool for SessionsSendTool {
 
         let (channel, chat_id) = parse_session_key(session)?;
         let mut outbound = OutboundMessage::new(channel, chat_id, content);
-        if let Ok(ctx) = self.context.lock() {
-            outbound.metadata.insert(
-                "forwarded_from".to_string(),
-                Value::String(format!("{}:{}", ctx.origin_channel, ctx.origin_chat_id)),
-            );
-        }
-        self.sender
-            .send(outbound)
+        let (origin_channel, origin_chat_id) = self.context.get();
+        outbound.metadata.insert(
+            "forwarded_from".to_string(),
+            Value::String(format!("{origin_channel}:{origin_chat_id}")),
+        );
+        self.bus
+            .publish_outbound(outbound)
             .await
             .map_err(|err| anyhow!("failed to send session message: {err}"))?;
         Ok(format!("Sent message to session {session}"))
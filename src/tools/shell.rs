@@ -1,4 +1,6 @@
 use crate::tools::base::Tool;
+use crate::usage;
+use crate::utils::safe_filename;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use regex::Regex;
@@ -7,6 +9,27 @@ use std::path::{Component, Path, PathBuf};
 use tokio::process::Command;
 use tokio::time::{Duration, timeout};
 
+/// Per-session scratch directories, under the workspace.
+pub const SANDBOX_DIR: &str = "sandbox";
+
+/// Where `exec` runs for `session`: `<workspace>/sandbox/<session>/`, named
+/// like the session's transcript.
+pub fn sandbox_dir(workspace: &Path, session: &str) -> PathBuf {
+    workspace
+        .join(SANDBOX_DIR)
+        .join(safe_filename(&session.replace(':', "_")))
+}
+
+/// Deletes `session`'s scratch directory; false when there was none.
+pub fn remove_sandbox(workspace: &Path, session: &str) -> std::io::Result<bool> {
+    let dir = sandbox_dir(workspace, session);
+    if !dir.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_dir_all(dir)?;
+    Ok(true)
+}
+
 fn normalize_path(path: &Path) -> PathBuf {
     let mut out = PathBuf::new();
     for component in path.components() {
@@ -65,13 +88,13 @@ impl ExecTool {
         let lower = trimmed.to_lowercase();
 
         for pattern in &self.deny_patterns {
-            if let Ok(re) = Regex::new(pattern) {
-                if re.is_match(&lower) {
-                    return Some(
-                        "Error: Command blocked by safety guard (dangerous pattern detected)"
-                            .to_string(),
-                    );
-                }
+            if let Ok(re) = Regex::new(pattern)
+                && re.is_match(&lower)
+            {
+                return Some(
+                    "Error: Command blocked by safety guard (dangerous pattern detected)"
+                        .to_string(),
+                );
             }
         }
 
@@ -135,8 +158,16 @@ impl Tool for ExecTool {
         "exec"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["shell"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
-        "Execute a shell command and return its output. Use with caution."
+        "Execute a shell command and return its output. Use with caution. Commands run in a scratch directory of this conversation, kept until the session is reset; reach workspace files by absolute path or $NANOBOT_WORKSPACE."
     }
 
     fn parameters(&self) -> Value {
@@ -144,7 +175,7 @@ impl Tool for ExecTool {
             "type": "object",
             "properties": {
                 "command": { "type": "string", "description": "The shell command to execute" },
-                "working_dir": { "type": "string", "description": "Optional working directory for the command" }
+                "working_dir": { "type": "string", "description": "Optional working directory for the command, relative to the workspace" }
             },
             "required": ["command"]
         })
@@ -156,14 +187,31 @@ impl Tool for ExecTool {
             .and_then(Value::as_str)
             .ok_or_else(|| anyhow!("missing required string field: command"))?;
 
-        let cwd = params
-            .get("working_dir")
-            .and_then(Value::as_str)
-            .map(PathBuf::from)
-            .or_else(|| self.working_dir.clone())
-            .unwrap_or(std::env::current_dir()?);
+        let root = match &self.working_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::current_dir()?,
+        };
+        let cwd = match params.get("working_dir").and_then(Value::as_str) {
+            Some(dir) => root.join(dir),
+            // Each session gets its own directory, so parallel chats do not
+            // trip over each other's files.
+            None => match usage::origin_session().filter(|_| self.working_dir.is_some()) {
+                Some(session) => {
+                    let dir = sandbox_dir(&root, &session);
+                    std::fs::create_dir_all(&dir)?;
+                    dir
+                }
+                None => root.clone(),
+            },
+        };
 
-        if let Some(err) = self.guard_command(command, &cwd) {
+        if self.restrict_to_workspace && !normalize_path(&cwd).starts_with(normalize_path(&root)) {
+            return Ok(
+                "Error: Command blocked by safety guard (working dir outside workspace)"
+                    .to_string(),
+            );
+        }
+        if let Some(err) = self.guard_command(command, &root) {
             return Ok(err);
         }
 
@@ -177,7 +225,10 @@ impl Tool for ExecTool {
             cmd
         };
 
-        process.current_dir(&cwd);
+        process
+            .current_dir(&cwd)
+            .env("NANOBOT_WORKSPACE", &root)
+            .envs(crate::network::proxy_env());
         let output = timeout(Duration::from_secs(self.timeout_s), process.output()).await;
         let output = match output {
             Ok(result) => result?,
@@ -211,7 +262,8 @@ impl Tool for ExecTool {
         } else {
             output_parts.join("\n")
         };
-        let max_len = 10_000;
+        // Long output is paged by the registry; this only bounds memory.
+        let max_len = 200_000;
         if result.len() > max_len {
             result = format!(
                 "{}\n... (truncated, {} more chars)",
@@ -225,8 +277,11 @@ impl Tool for ExecTool {
 
 #[cfg(test)]
 mod tests {
-    use super::ExecTool;
-    use std::path::PathBuf;
+    use super::{ExecTool, sandbox_dir};
+    use crate::tools::base::Tool;
+    use crate::usage;
+    use serde_json::{Map, Value, json};
+    use std::path::{Path, PathBuf};
 
     fn test_cwd() -> PathBuf {
         std::env::temp_dir().join("nanobot-rs-workspace")
@@ -252,4 +307,98 @@ mod tests {
         let err = tool.guard_command(cmd, &cwd);
         assert!(err.is_some(), "expected guard error");
     }
+
+    /// Runs `exec` in `workspace` for session `telegram:42`.
+    async fn run(workspace: &Path, restrict: bool, params: Value) -> String {
+        let tool = ExecTool::new(10, Some(workspace.to_path_buf()), None, None, restrict);
+        let params: Map<String, Value> = serde_json::from_value(params).unwrap();
+        usage::with_origin(
+            "telegram:42".to_string(),
+            "telegram".to_string(),
+            "telegram:7".to_string(),
+            tool.execute(&params),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn commands_run_in_the_session_sandbox_unless_told_otherwise() {
+        let workspace = std::env::temp_dir().join(format!("nanobot-exec-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(workspace.join("notes")).unwrap();
+        let workspace = workspace.canonicalize().unwrap();
+        let pwd = |output: String| PathBuf::from(output.trim());
+
+        let sandbox = sandbox_dir(&workspace, "telegram:42");
+        assert_eq!(
+            pwd(run(&workspace, true, json!({ "command": "pwd" })).await),
+            sandbox
+        );
+        assert!(sandbox.is_dir());
+        assert_eq!(
+            run(
+                &workspace,
+                true,
+                json!({ "command": "echo $NANOBOT_WORKSPACE" })
+            )
+            .await
+            .trim(),
+            workspace.to_string_lossy()
+        );
+
+        let relative = json!({ "command": "pwd", "working_dir": "notes" });
+        assert_eq!(
+            pwd(run(&workspace, true, relative).await),
+            workspace.join("notes")
+        );
+        let root = json!({ "command": "pwd", "working_dir": "." });
+        assert_eq!(pwd(run(&workspace, true, root).await), workspace);
+
+        let inside = workspace.join("notes").to_string_lossy().into_owned();
+        let absolute = json!({ "command": "pwd", "working_dir": inside });
+        assert_eq!(
+            pwd(run(&workspace, true, absolute).await),
+            workspace.join("notes")
+        );
+        let outside = json!({ "command": "pwd", "working_dir": "/" });
+        assert!(
+            run(&workspace, true, outside.clone())
+                .await
+                .contains("outside workspace")
+        );
+        assert_eq!(
+            pwd(run(&workspace, false, outside).await),
+            PathBuf::from("/")
+        );
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn restrict_to_workspace_blocks_parent_escapes() {
+        let workspace = std::env::temp_dir().join(format!("nanobot-exec-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&workspace).unwrap();
+
+        for working_dir in ["..", "notes/../..", "sandbox/../../.."] {
+            let params = json!({ "command": "pwd", "working_dir": working_dir });
+            let output = run(&workspace, true, params).await;
+            assert!(
+                output.contains("outside workspace"),
+                "{working_dir}: {output}"
+            );
+        }
+        let output = run(&workspace, true, json!({ "command": "ls ../.." })).await;
+        assert!(output.contains("path traversal"), "{output}");
+        // The sandbox is two levels down, so `..` from it stays inside; the
+        // guard still refuses it rather than resolve each path.
+        let output = run(&workspace, true, json!({ "command": "cat ../notes.txt" })).await;
+        assert!(output.contains("path traversal"), "{output}");
+
+        let output = run(&workspace, false, json!({ "command": "cd .. && pwd" })).await;
+        assert!(!output.contains("Error"), "{output}");
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
 }
@@ -1,37 +1,25 @@
 use crate::agent::subagent::SubagentManager;
-use crate::tools::base::Tool;
+use crate::tools::base::{ChatContext, Tool};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use serde_json::{Map, Value, json};
-use std::sync::{Arc, Mutex};
-
-#[derive(Default)]
-struct SpawnContext {
-    origin_channel: String,
-    origin_chat_id: String,
-}
+use std::sync::Arc;
 
 pub struct SpawnTool {
     manager: Arc<SubagentManager>,
-    context: Mutex<SpawnContext>,
+    context: ChatContext,
 }
 
 impl SpawnTool {
     pub fn new(manager: Arc<SubagentManager>) -> Self {
         Self {
             manager,
-            context: Mutex::new(SpawnContext {
-                origin_channel: "cli".to_string(),
-                origin_chat_id: "direct".to_string(),
-            }),
+            context: ChatContext::new("cli", "direct"),
         }
     }
 
     pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
-        if let Ok(mut guard) = self.context.lock() {
-            guard.origin_channel = channel.into();
-            guard.origin_chat_id = chat_id.into();
-        }
+        self.context.set(channel, chat_id);
     }
 }
 
@@ -41,6 +29,14 @@ impl Tool for SpawnTool {
         "spawn"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["agents"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
     fn description(&self) -> &str {
         "Spawn a subagent to handle a task in the background. Use this for complex or time-consuming tasks."
     }
@@ -67,13 +63,7 @@ impl Tool for SpawnTool {
             .and_then(Value::as_str)
             .map(ToOwned::to_owned);
 
-        let (origin_channel, origin_chat_id) = {
-            let guard = self
-                .context
-                .lock()
-                .map_err(|_| anyhow!("failed to lock spawn context"))?;
-            (guard.origin_channel.clone(), guard.origin_chat_id.clone())
-        };
+        let (origin_channel, origin_chat_id) = self.context.get();
 
         Ok(self
             .manager
@@ -0,0 +1,235 @@
+use crate::cron::CronService;
+use crate::tasks::{NewTask, TaskStore, cancel_reminder, parse_due, schedule_reminder};
+use crate::tools::base::{ChatContext, Tool};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use std::sync::Arc;
+
+/// State shared by `add_task`, `list_tasks` and `complete_task`: the store,
+/// the cron service for due-date reminders and the chat reminders go to.
+pub struct TaskTools {
+    store: TaskStore,
+    cron: Option<Arc<CronService>>,
+    context: ChatContext,
+}
+
+impl TaskTools {
+    pub fn new(workspace: &Path, cron: Option<Arc<CronService>>) -> Self {
+        Self {
+            store: TaskStore::new(workspace),
+            cron,
+            context: ChatContext::default(),
+        }
+    }
+
+    pub fn set_context(&self, channel: impl Into<String>, chat_id: impl Into<String>) {
+        self.context.set(channel, chat_id);
+    }
+
+    /// The three task tools, ready to register.
+    pub fn tools(self: &Arc<Self>) -> Vec<Arc<dyn Tool>> {
+        vec![
+            Arc::new(AddTaskTool(self.clone())),
+            Arc::new(ListTasksTool(self.clone())),
+            Arc::new(CompleteTaskTool(self.clone())),
+        ]
+    }
+}
+
+fn task_id(params: &Map<String, Value>) -> Result<u64> {
+    let value = params
+        .get("id")
+        .ok_or_else(|| anyhow!("missing required field: id"))?;
+    value
+        .as_u64()
+        .or_else(|| {
+            value
+                .as_str()
+                .and_then(|s| s.trim_start_matches('#').parse().ok())
+        })
+        .ok_or_else(|| anyhow!("id must be a task number"))
+}
+
+pub struct AddTaskTool(Arc<TaskTools>);
+
+#[async_trait]
+impl Tool for AddTaskTool {
+    fn name(&self) -> &str {
+        "add_task"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["tasks"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Add a task to the user's persistent todo list. A due date also schedules a reminder in this chat."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "title": { "type": "string" },
+                "due": { "type": "string", "description": "YYYY-MM-DD, YYYY-MM-DD HH:MM (local time) or RFC 3339" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "notes": { "type": "string" }
+            },
+            "required": ["title"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let title = params
+            .get("title")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: title"))?;
+        let due = params
+            .get("due")
+            .and_then(Value::as_str)
+            .filter(|due| !due.trim().is_empty())
+            .map(parse_due)
+            .transpose()?;
+        let tags = params
+            .get("tags")
+            .and_then(Value::as_array)
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(Value::as_str)
+                    .map(|tag| tag.trim_start_matches('#').to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let notes = params
+            .get("notes")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let tools = &self.0;
+        let mut task = tools.store.add(NewTask {
+            title: title.to_string(),
+            due,
+            tags,
+            notes,
+        })?;
+        let (channel, chat_id) = tools.context.get();
+        if let Some(cron) = &tools.cron
+            && !channel.is_empty()
+            && !chat_id.is_empty()
+        {
+            schedule_reminder(cron, &mut task, &channel, &chat_id).await?;
+            tools.store.save(&task)?;
+        }
+        let reminder = if task.reminder_job.is_some() {
+            " A reminder is scheduled for the due time."
+        } else {
+            ""
+        };
+        Ok(format!("Added {}.{reminder}", task.line()))
+    }
+}
+
+pub struct ListTasksTool(Arc<TaskTools>);
+
+#[async_trait]
+impl Tool for ListTasksTool {
+    fn name(&self) -> &str {
+        "list_tasks"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["tasks"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "List the user's todo list. Open tasks by default; set include_done to see finished ones too."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "include_done": { "type": "boolean" },
+                "tag": { "type": "string", "description": "Only tasks with this tag" }
+            }
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let include_done = params
+            .get("include_done")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let tag = params
+            .get("tag")
+            .and_then(Value::as_str)
+            .map(|tag| tag.trim_start_matches('#'));
+        let lines = self
+            .0
+            .store
+            .list()
+            .into_iter()
+            .filter(|task| include_done || task.is_open())
+            .filter(|task| tag.is_none_or(|tag| task.tags.iter().any(|t| t == tag)))
+            .map(|task| task.line())
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return Ok("No tasks.".to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+pub struct CompleteTaskTool(Arc<TaskTools>);
+
+#[async_trait]
+impl Tool for CompleteTaskTool {
+    fn name(&self) -> &str {
+        "complete_task"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["tasks"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        true
+    }
+
+    fn description(&self) -> &str {
+        "Mark a task on the user's todo list as done, cancelling its reminder."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "description": "Task number from list_tasks" }
+            },
+            "required": ["id"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let tools = &self.0;
+        let mut task = tools.store.complete(task_id(params)?)?;
+        if let Some(cron) = &tools.cron
+            && task.reminder_job.is_some()
+        {
+            cancel_reminder(cron, &mut task).await?;
+            tools.store.save(&task)?;
+        }
+        Ok(format!("Completed {}", task.line()))
+    }
+}
@@ -0,0 +1,105 @@
+use crate::prompts::{list_templates, load_template};
+use crate::tools::base::Tool;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+pub struct UseTemplateTool {
+    workspace: PathBuf,
+}
+
+impl UseTemplateTool {
+    pub fn new(workspace: PathBuf) -> Self {
+        Self { workspace }
+    }
+
+    fn list(&self) -> String {
+        let templates = list_templates(&self.workspace);
+        if templates.is_empty() {
+            return "No prompt templates defined.".to_string();
+        }
+        templates
+            .iter()
+            .map(|template| {
+                let variables = template
+                    .variables
+                    .iter()
+                    .map(|v| match &v.default {
+                        Some(default) => format!("{}={default}", v.name),
+                        None => v.name.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "- {}: {} [{}]",
+                    template.name,
+                    template.description,
+                    variables.join(", ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[async_trait]
+impl Tool for UseTemplateTool {
+    fn name(&self) -> &str {
+        "use_template"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["prompts"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
+    fn description(&self) -> &str {
+        "Expand a saved prompt template from the workspace prompts/ directory and return its instructions, which you should then carry out. Use action=list to see templates and their variables."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": { "type": "string", "enum": ["use", "list"] },
+                "name": { "type": "string", "description": "Template name" },
+                "vars": { "type": "object", "description": "Values for the template variables" }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let action = params
+            .get("action")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: action"))?;
+        if action == "list" {
+            return Ok(self.list());
+        }
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: name"))?;
+        let vars = params
+            .get("vars")
+            .and_then(Value::as_object)
+            .map(|vars| {
+                vars.iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            Value::String(text) => text.clone(),
+                            other => other.to_string(),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .unwrap_or_default();
+        load_template(&self.workspace, name)?.render(&vars)
+    }
+}
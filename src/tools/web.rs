@@ -16,8 +16,11 @@ const PERPLEXITY_OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
 const DEFAULT_PERPLEXITY_MODEL: &str = "perplexity/sonar-pro";
 const GROK_RESPONSES_ENDPOINT: &str = "https://api.x.ai/v1/responses";
 const DEFAULT_GROK_MODEL: &str = "grok-4-1-fast";
+const LEAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(8);
+/// Shorter paragraphs are usually captions, bylines or cookie notices.
+const LEAD_MIN_CHARS: usize = 80;
 
-fn strip_tags(text: &str) -> String {
+pub(crate) fn strip_tags(text: &str) -> String {
     let script_re = Regex::new(r"(?is)<script[\s\S]*?</script>")
         .unwrap_or_else(|_| Regex::new("^$").expect("regex"));
     let style_re = Regex::new(r"(?is)<style[\s\S]*?</style>")
@@ -29,7 +32,7 @@ fn strip_tags(text: &str) -> String {
     html_escape::decode_html_entities(&stripped).to_string()
 }
 
-fn normalize_text(text: &str) -> String {
+pub(crate) fn normalize_text(text: &str) -> String {
     let whitespace_re = Regex::new(r"[ \t]+").unwrap_or_else(|_| Regex::new("^$").expect("regex"));
     let breaks_re = Regex::new(r"\n{3,}").unwrap_or_else(|_| Regex::new("^$").expect("regex"));
     let collapsed = whitespace_re.replace_all(text, " ");
@@ -48,6 +51,227 @@ fn validate_url(url: &str) -> Result<()> {
     Ok(())
 }
 
+/// Query parameters that only track the click and never change the page.
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_")
+        || matches!(
+            name,
+            "ref" | "ref_src" | "fbclid" | "gclid" | "msclkid" | "mc_cid" | "mc_eid"
+        )
+}
+
+/// The part of a URL that identifies the page: scheme, `www.`/`m.`,
+/// fragments, tracking parameters and trailing slashes are ignored.
+pub(crate) fn canonical_url(url: &str) -> String {
+    let Ok(parsed) = Url::parse(url.trim()) else {
+        return url.trim().trim_end_matches('/').to_ascii_lowercase();
+    };
+    let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+    let host = host
+        .strip_prefix("www.")
+        .or_else(|| host.strip_prefix("m."))
+        .unwrap_or(&host);
+    let mut key = format!("{host}{}", parsed.path().trim_end_matches('/'));
+    let mut query = parsed
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>();
+    if !query.is_empty() {
+        query.sort();
+        key.push('?');
+        key.push_str(&query.join("&"));
+    }
+    key
+}
+
+/// Lowercased title words without a trailing " - Site Name" / " | Site".
+fn title_key(title: &str) -> String {
+    let title = title
+        .rsplit_once(" | ")
+        .or_else(|| title.rsplit_once(" - "))
+        .map(|(head, _)| head)
+        .unwrap_or(title);
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn host_of(url: &str) -> String {
+    canonical_url(url)
+        .split(['/', '?'])
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn clip(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut = text.chars().take(max_chars).collect::<String>();
+    let cut = cut.rsplit_once(' ').map(|(head, _)| head).unwrap_or(&cut);
+    format!("{}…", cut.trim_end_matches([',', ';', ':']))
+}
+
+/// First substantial `<p>` of a page, falling back to its meta description.
+fn lead_paragraph(html: &str) -> Option<String> {
+    let paragraph_re = Regex::new(r"(?is)<p(?:\s[^>]*)?>([\s\S]*?)</p>").ok()?;
+    let body = Regex::new(
+        r"(?is)<(script|style|nav|header|footer)[\s>][\s\S]*?</(script|style|nav|header|footer)>",
+    )
+    .map(|re| re.replace_all(html, "").to_string())
+    .unwrap_or_else(|_| html.to_string());
+    for caps in paragraph_re.captures_iter(&body) {
+        let text = normalize_text(&strip_tags(&caps[1])).replace('\n', " ");
+        if text.chars().count() >= LEAD_MIN_CHARS {
+            return Some(text);
+        }
+    }
+    let meta_re = Regex::new(
+        r#"(?is)<meta\s+[^>]*(?:name|property)=["'](?:og:)?description["'][^>]*content=["']([^"']+)["']"#,
+    )
+    .ok()?;
+    meta_re
+        .captures(html)
+        .map(|caps| normalize_text(&html_escape::decode_html_entities(&caps[1])))
+        .filter(|text| !text.is_empty())
+}
+
+/// `og:title` or `<title>` of a page.
+fn page_title(html: &str) -> Option<String> {
+    let og_re =
+        Regex::new(r#"(?is)<meta\s+[^>]*property=["']og:title["'][^>]*content=["']([^"']+)["']"#)
+            .ok()?;
+    let title_re = Regex::new(r"(?is)<title[^>]*>([\s\S]*?)</title>").ok()?;
+    og_re
+        .captures(html)
+        .or_else(|| title_re.captures(html))
+        .map(|caps| normalize_text(&html_escape::decode_html_entities(&strip_tags(&caps[1]))))
+        .filter(|title| !title.is_empty())
+}
+
+/// Title and opening paragraph of a web page.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageSummary {
+    pub title: Option<String>,
+    pub summary: Option<String>,
+}
+
+/// Fetches `url` like `web_fetch` and extracts its title and lead
+/// paragraph (clipped to `max_chars`).
+pub async fn fetch_page_summary(url: &str, max_chars: usize) -> Result<PageSummary> {
+    validate_url(url)?;
+    let client = crate::network::client_builder()
+        .redirect(reqwest::redirect::Policy::limited(5))
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    let html = client
+        .get(url)
+        .header(USER_AGENT, DEFAULT_USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(PageSummary {
+        title: page_title(&html),
+        summary: lead_paragraph(&html).map(|lead| clip(&lead, max_chars)),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SearchHit {
+    title: String,
+    url: String,
+    snippet: String,
+    lead: Option<String>,
+    /// Search backends that returned this page.
+    providers: Vec<&'static str>,
+}
+
+impl SearchHit {
+    fn new(provider: &'static str, title: &str, url: &str, snippet: &str) -> Self {
+        Self {
+            title: title.trim().to_string(),
+            url: url.trim().to_string(),
+            snippet: snippet.trim().to_string(),
+            lead: None,
+            providers: vec![provider],
+        }
+    }
+
+    fn same_page(&self, other: &SearchHit) -> bool {
+        if canonical_url(&self.url) == canonical_url(&other.url) {
+            return true;
+        }
+        let title = title_key(&self.title);
+        title.split(' ').count() >= 3
+            && title == title_key(&other.title)
+            && host_of(&self.url) == host_of(&other.url)
+    }
+}
+
+/// Folds results for the same page into the first one, keeping the longer
+/// snippet and every provider that found it.
+fn dedupe_hits(hits: Vec<SearchHit>) -> Vec<SearchHit> {
+    let mut out: Vec<SearchHit> = Vec::new();
+    for hit in hits {
+        match out.iter_mut().find(|existing| existing.same_page(&hit)) {
+            Some(existing) => {
+                if hit.snippet.len() > existing.snippet.len() {
+                    existing.snippet = hit.snippet;
+                }
+                for provider in hit.providers {
+                    if !existing.providers.contains(&provider) {
+                        existing.providers.push(provider);
+                    }
+                }
+            }
+            None => out.push(hit),
+        }
+    }
+    out
+}
+
+fn dedupe_citations(citations: Vec<String>) -> Vec<String> {
+    let mut seen = Vec::new();
+    citations
+        .into_iter()
+        .filter(|url| {
+            let key = canonical_url(url);
+            let fresh = !seen.contains(&key);
+            seen.push(key);
+            fresh
+        })
+        .collect()
+}
+
+async fn fetch_lead(client: &reqwest::Client, url: &str, max_chars: usize) -> Option<String> {
+    validate_url(url).ok()?;
+    let response = client
+        .get(url)
+        .header(USER_AGENT, DEFAULT_USER_AGENT)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let is_html = response
+        .headers()
+        .get("content-type")
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|ct| ct.contains("html"));
+    if !is_html {
+        return None;
+    }
+    let body = response.text().await.ok()?;
+    lead_paragraph(&body).map(|lead| clip(&lead, max_chars))
+}
+
 #[derive(Debug, Clone, Copy)]
 enum WebSearchProvider {
     Brave,
@@ -65,30 +289,21 @@ pub struct WebSearchTool {
     grok_model: String,
     grok_inline_citations: bool,
     max_results: usize,
+    lead_results: usize,
+    lead_chars: usize,
 }
 
-fn push_duckduckgo_result(
-    output: &mut Vec<(String, String, String)>,
-    title: &str,
-    url: &str,
-    snippet: &str,
-) {
-    let title = title.trim();
-    let url = url.trim();
-    let snippet = snippet.trim();
-    if title.is_empty() || url.is_empty() {
+fn push_duckduckgo_result(output: &mut Vec<SearchHit>, title: &str, url: &str, snippet: &str) {
+    if title.trim().is_empty() || url.trim().is_empty() {
         return;
     }
-    if output
-        .iter()
-        .any(|(_, existing_url, _)| existing_url == url)
-    {
+    if output.iter().any(|existing| existing.url == url.trim()) {
         return;
     }
-    output.push((title.to_string(), url.to_string(), snippet.to_string()));
+    output.push(SearchHit::new("DuckDuckGo", title, url, snippet));
 }
 
-fn collect_duckduckgo_related_topics(topics: &[Value], output: &mut Vec<(String, String, String)>) {
+fn collect_duckduckgo_related_topics(topics: &[Value], output: &mut Vec<SearchHit>) {
     for topic in topics {
         if let Some(nested) = topic.get("Topics").and_then(Value::as_array) {
             collect_duckduckgo_related_topics(nested, output);
@@ -223,14 +438,17 @@ impl WebSearchTool {
             grok_model,
             grok_inline_citations: config.grok.inline_citations,
             max_results: config.max_results.clamp(1, 10),
+            lead_results: config.lead_results.min(10),
+            lead_chars: config.lead_chars.max(LEAD_MIN_CHARS),
         }
     }
 
     pub fn new(api_key: Option<String>, max_results: usize) -> Self {
-        let mut config = WebSearchConfig::default();
-        config.api_key = api_key.unwrap_or_default();
-        config.max_results = max_results;
-        Self::from_config(config)
+        Self::from_config(WebSearchConfig {
+            api_key: api_key.unwrap_or_default(),
+            max_results,
+            ..Default::default()
+        })
     }
 
     fn format_search_answer(
@@ -241,7 +459,7 @@ impl WebSearchTool {
         inline_citations: bool,
     ) -> String {
         let mut lines = vec![format!("Results for: {query} ({provider_name})\n")];
-        lines.push(answer.trim().to_string());
+        lines.push(format!("Answer:\n{}", answer.trim()));
         if !citations.is_empty() {
             lines.push(String::new());
             if inline_citations {
@@ -256,28 +474,62 @@ impl WebSearchTool {
         lines.join("\n")
     }
 
-    fn format_results(
-        query: &str,
-        provider_name: &str,
-        results: &[(String, String, String)],
-        limit: usize,
-    ) -> String {
+    /// One block per result with labelled fields, the same for every backend.
+    fn format_results(query: &str, provider_name: &str, results: &[SearchHit]) -> String {
         if results.is_empty() {
             return format!("No results for: {query} ({provider_name})");
         }
 
         let mut lines = vec![format!("Results for: {query} ({provider_name})\n")];
-        for (idx, (title, url, desc)) in results.iter().take(limit).enumerate() {
-            lines.push(format!("{}. {title}\n   {url}", idx + 1));
-            if !desc.is_empty() {
-                lines.push(format!("   {desc}"));
+        for (idx, hit) in results.iter().enumerate() {
+            lines.push(format!("{}. {}", idx + 1, hit.title));
+            lines.push(format!("   URL: {}", hit.url));
+            if !hit.snippet.is_empty() {
+                lines.push(format!("   Snippet: {}", hit.snippet));
+            }
+            if let Some(lead) = &hit.lead {
+                lines.push(format!("   Lead: {lead}"));
+            }
+            if hit.providers.len() > 1 {
+                lines.push(format!("   Found by: {}", hit.providers.join(", ")));
             }
         }
         lines.join("\n")
     }
 
-    async fn search_brave(&self, query: &str, n: u64) -> Result<Vec<(String, String, String)>> {
-        let client = reqwest::Client::new();
+    /// Fetches the lead paragraph of the first `count` results concurrently;
+    /// pages that fail or time out are left without one.
+    async fn attach_leads(&self, hits: &mut [SearchHit], count: usize) {
+        let Ok(client) = crate::network::client_builder()
+            .redirect(reqwest::redirect::Policy::limited(5))
+            .timeout(LEAD_TIMEOUT)
+            .build()
+        else {
+            return;
+        };
+        let targets = hits
+            .iter()
+            .take(count)
+            // DuckDuckGo topic pages only repeat the snippet.
+            .map(|hit| (!host_of(&hit.url).ends_with("duckduckgo.com")).then_some(&hit.url))
+            .collect::<Vec<_>>();
+        let leads = futures_util::future::join_all(targets.into_iter().map(|url| {
+            let client = &client;
+            async move {
+                match url {
+                    Some(url) => fetch_lead(client, url, self.lead_chars).await,
+                    None => None,
+                }
+            }
+        }))
+        .await;
+        for (hit, lead) in hits.iter_mut().zip(leads) {
+            hit.lead = lead;
+        }
+    }
+
+    async fn search_brave(&self, query: &str, n: u64) -> Result<Vec<SearchHit>> {
+        let client = crate::network::client();
         let response = client
             .get(BRAVE_SEARCH_ENDPOINT)
             .query(&[("q", query), ("count", &n.to_string())])
@@ -312,18 +564,14 @@ impl WebSearchTool {
                 .unwrap_or_default()
                 .trim();
             if !title.is_empty() && !url.is_empty() {
-                out.push((title.to_string(), url.to_string(), desc.to_string()));
+                out.push(SearchHit::new("Brave", title, url, &strip_tags(desc)));
             }
         }
         Ok(out)
     }
 
-    async fn search_duckduckgo(
-        &self,
-        query: &str,
-        n: u64,
-    ) -> Result<Vec<(String, String, String)>> {
-        let client = reqwest::Client::new();
+    async fn search_duckduckgo(&self, query: &str, n: u64) -> Result<Vec<SearchHit>> {
+        let client = crate::network::client();
         let response = client
             .get(DUCKDUCKGO_INSTANT_ENDPOINT)
             .query(&[
@@ -378,7 +626,7 @@ impl WebSearchTool {
     }
 
     async fn search_perplexity(&self, query: &str) -> Result<(String, Vec<String>)> {
-        let client = reqwest::Client::new();
+        let client = crate::network::client();
         let endpoint = format!(
             "{}/chat/completions",
             self.perplexity_base_url.trim_end_matches('/')
@@ -460,7 +708,7 @@ impl WebSearchTool {
     }
 
     async fn search_grok(&self, query: &str) -> Result<(String, Vec<String>)> {
-        let client = reqwest::Client::new();
+        let client = crate::network::client();
         let response = client
             .post(GROK_RESPONSES_ENDPOINT)
             .header("Content-Type", "application/json")
@@ -497,8 +745,16 @@ impl Tool for WebSearchTool {
         "web_search"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["web"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
     fn description(&self) -> &str {
-        "Search the web. Returns titles, URLs, and snippets."
+        "Search the web. Returns titles, URLs and snippets, with the opening paragraph of the top pages."
     }
 
     fn parameters(&self) -> Value {
@@ -506,7 +762,8 @@ impl Tool for WebSearchTool {
             "type": "object",
             "properties": {
                 "query": { "type": "string", "description": "Search query" },
-                "count": { "type": "integer", "description": "Results (1-10)", "minimum": 1, "maximum": 10 }
+                "count": { "type": "integer", "description": "Results (1-10)", "minimum": 1, "maximum": 10 },
+                "leads": { "type": "integer", "description": "Fetch the opening paragraph of this many top results (0 to skip)", "minimum": 0, "maximum": 10 }
             },
             "required": ["query"]
         })
@@ -522,33 +779,49 @@ impl Tool for WebSearchTool {
             .and_then(Value::as_u64)
             .unwrap_or(self.max_results as u64);
         let n = count.clamp(1, 10);
-        let note = match self.provider {
+        let leads = params
+            .get("leads")
+            .and_then(Value::as_u64)
+            .map(|v| v as usize)
+            .unwrap_or(self.lead_results)
+            .min(n as usize);
+        let (hits, note) = match self.provider {
             WebSearchProvider::Brave => {
                 if !self.brave_api_key.is_empty() {
                     match self.search_brave(query, n).await {
-                        Ok(results) if !results.is_empty() => {
-                            return Ok(Self::format_results(query, "Brave", &results, n as usize));
-                        }
-                        Ok(_) => Some(
-                            "Brave returned no results, switched to DuckDuckGo fallback."
-                                .to_string(),
+                        Ok(results) if !results.is_empty() => (results, None),
+                        Ok(_) => (
+                            Vec::new(),
+                            Some(
+                                "Brave returned no results, switched to DuckDuckGo fallback."
+                                    .to_string(),
+                            ),
+                        ),
+                        Err(err) => (
+                            Vec::new(),
+                            Some(format!(
+                                "Brave search failed ({err}), switched to DuckDuckGo fallback."
+                            )),
                         ),
-                        Err(err) => Some(format!(
-                            "Brave search failed ({err}), switched to DuckDuckGo fallback."
-                        )),
                     }
                 } else {
-                    Some(
-                        "BRAVE_API_KEY not configured, using keyless DuckDuckGo fallback."
-                            .to_string(),
+                    (
+                        Vec::new(),
+                        Some(
+                            "BRAVE_API_KEY not configured, using keyless DuckDuckGo fallback."
+                                .to_string(),
+                        ),
                     )
                 }
             }
             WebSearchProvider::Perplexity => {
                 if self.perplexity_api_key.is_empty() {
-                    Some(
-                        "Perplexity API key not configured, using keyless DuckDuckGo fallback."
-                            .to_string(),
+                    (
+                        Vec::new(),
+                        Some(
+                            "Perplexity API key not configured, using keyless DuckDuckGo fallback."
+                                .to_string(),
+                        ),
                     )
                 } else {
                     match self.search_perplexity(query).await {
@@ -557,25 +830,34 @@ impl Tool for WebSearchTool {
                                 query,
                                 "Perplexity",
                                 &answer,
-                                &citations,
+                                &dedupe_citations(citations),
                                 false,
                             ));
                         }
-                        Ok(_) => Some(
-                            "Perplexity returned an empty answer, switched to DuckDuckGo fallback."
-                                .to_string(),
+                        Ok(_) => (
+                            Vec::new(),
+                            Some(
+                                "Perplexity returned an empty answer, switched to DuckDuckGo fallback."
+                                    .to_string(),
+                            ),
+                        ),
+                        Err(err) => (
+                            Vec::new(),
+                            Some(format!(
+                                "Perplexity search failed ({err}), switched to DuckDuckGo fallback."
+                            )),
                         ),
-                        Err(err) => Some(format!(
-                            "Perplexity search failed ({err}), switched to DuckDuckGo fallback."
-                        )),
                     }
                 }
             }
             WebSearchProvider::Grok => {
                 if self.grok_api_key.is_empty() {
-                    Some(
-                        "XAI_API_KEY not configured, using keyless DuckDuckGo fallback."
-                            .to_string(),
+                    (
+                        Vec::new(),
+                        Some(
+                            "XAI_API_KEY not configured, using keyless DuckDuckGo fallback."
+                                .to_string(),
+                        ),
                     )
                 } else {
                     match self.search_grok(query).await {
@@ -584,41 +866,65 @@ impl Tool for WebSearchTool {
                                 query,
                                 "Grok",
                                 &answer,
-                                &citations,
+                                &dedupe_citations(citations),
                                 self.grok_inline_citations,
                             ));
                         }
-                        Ok(_) => Some(
-                            "Grok returned an empty answer, switched to DuckDuckGo fallback."
-                                .to_string(),
+                        Ok(_) => (
+                            Vec::new(),
+                            Some(
+                                "Grok returned an empty answer, switched to DuckDuckGo fallback."
+                                    .to_string(),
+                            ),
+                        ),
+                        Err(err) => (
+                            Vec::new(),
+                            Some(format!(
+                                "Grok search failed ({err}), switched to DuckDuckGo fallback."
+                            )),
                         ),
-                        Err(err) => Some(format!(
-                            "Grok search failed ({err}), switched to DuckDuckGo fallback."
-                        )),
                     }
                 }
             }
         };
 
-        match self.search_duckduckgo(query, n).await {
-            Ok(results) => {
-                let content =
-                    Self::format_results(query, "DuckDuckGo fallback", &results, n as usize);
-                if let Some(note) = note {
-                    Ok(format!("{note}\n\n{content}"))
-                } else {
-                    Ok(content)
+        let mut hits = dedupe_hits(hits);
+        // Top up a short result list from DuckDuckGo; pages both found are merged.
+        if hits.len() < n as usize {
+            match self.search_duckduckgo(query, n).await {
+                Ok(extra) => {
+                    hits.extend(extra);
+                    hits = dedupe_hits(hits);
+                }
+                Err(err) if hits.is_empty() => {
+                    return match note {
+                        Some(note) => Ok(format!(
+                            "{note}\n\nSearch fallback failed: {err}\nTip: use web_fetch with a concrete URL for direct page access."
+                        )),
+                        None => Err(err),
+                    };
                 }
+                Err(err) => tracing::debug!("DuckDuckGo top-up failed: {err}"),
             }
-            Err(err) => {
-                if let Some(note) = note {
-                    Ok(format!(
-                        "{note}\n\nSearch fallback failed: {err}\nTip: use web_fetch with a concrete URL for direct page access."
-                    ))
-                } else {
-                    Err(err)
+        }
+        hits.truncate(n as usize);
+        self.attach_leads(&mut hits, leads).await;
+
+        let provider_name = if note.is_some() {
+            "DuckDuckGo fallback".to_string()
+        } else {
+            let mut providers: Vec<&str> = Vec::new();
+            for provider in hits.iter().flat_map(|hit| hit.providers.iter()) {
+                if !providers.contains(provider) {
+                    providers.push(provider);
                 }
             }
+            providers.join(" + ")
+        };
+        let content = Self::format_results(query, &provider_name, &hits);
+        match note {
+            Some(note) => Ok(format!("{note}\n\n{content}")),
+            None => Ok(content),
         }
     }
 }
@@ -651,6 +957,14 @@ impl Tool for WebFetchTool {
         "web_fetch"
     }
 
+    fn tags(&self) -> &[&str] {
+        &["web"]
+    }
+
+    fn mutates(&self, _params: &Map<String, Value>) -> bool {
+        false
+    }
+
     fn description(&self) -> &str {
         "Fetch URL and extract readable content (HTML -> markdown/text)."
     }
@@ -688,7 +1002,7 @@ impl Tool for WebFetchTool {
             .map(|v| v as usize)
             .unwrap_or(self.max_chars);
 
-        let client = reqwest::Client::builder()
+        let client = crate::network::client_builder()
             .redirect(reqwest::redirect::Policy::limited(5))
             .timeout(std::time::Duration::from_secs(30))
             .build()?;
@@ -750,7 +1064,10 @@ impl Tool for WebFetchTool {
 
 #[cfg(test)]
 mod tests {
-    use super::{WebSearchProvider, WebSearchTool, collect_duckduckgo_related_topics};
+    use super::{
+        SearchHit, WebSearchProvider, WebSearchTool, collect_duckduckgo_related_topics,
+        dedupe_hits, lead_paragraph, page_title,
+    };
     use serde_json::json;
 
     #[test]
@@ -775,9 +1092,79 @@ mod tests {
         collect_duckduckgo_related_topics(payload.as_array().expect("array"), &mut out);
 
         assert_eq!(out.len(), 2);
-        assert_eq!(out[0].0, "Rust");
-        assert!(out[0].1.contains("Rust_"));
-        assert_eq!(out[1].0, "Tokio");
+        assert_eq!(out[0].title, "Rust");
+        assert!(out[0].url.contains("Rust_"));
+        assert_eq!(out[1].title, "Tokio");
+    }
+
+    #[test]
+    fn dedupe_hits_merges_url_variants_and_retitled_copies() {
+        let hits = vec![
+            SearchHit::new(
+                "Brave",
+                "Tokio tutorial",
+                "https://tokio.rs/tokio/tutorial/",
+                "short",
+            ),
+            SearchHit::new(
+                "DuckDuckGo",
+                "Tokio tutorial",
+                "http://www.tokio.rs/tokio/tutorial?utm_source=ddg#intro",
+                "a longer snippet",
+            ),
+            SearchHit::new(
+                "Brave",
+                "Async Rust in depth - Example Blog",
+                "https://example.com/a?id=1",
+                "",
+            ),
+            SearchHit::new(
+                "Brave",
+                "Async Rust in depth | Example",
+                "https://example.com/a?id=1&page=all",
+                "",
+            ),
+            SearchHit::new("Brave", "Async Rust in depth", "https://other.org/a", ""),
+        ];
+        let hits = dedupe_hits(hits);
+        assert_eq!(hits.len(), 3);
+        assert_eq!(hits[0].snippet, "a longer snippet");
+        assert_eq!(hits[0].providers, vec!["Brave", "DuckDuckGo"]);
+        assert_eq!(hits[2].url, "https://other.org/a");
+
+        let text = WebSearchTool::format_results("tokio", "Brave + DuckDuckGo", &hits);
+        assert!(text.contains("1. Tokio tutorial\n   URL: https://tokio.rs/tokio/tutorial/"));
+        assert!(text.contains("   Found by: Brave, DuckDuckGo"));
+    }
+
+    #[test]
+    fn page_title_prefers_open_graph() {
+        let html = r#"<html><head><title>Fallback &amp; co</title><meta property="og:title" content="Real Title"></head></html>"#;
+        assert_eq!(page_title(html).as_deref(), Some("Real Title"));
+        assert_eq!(
+            page_title("<title>\n  Fallback &amp; co </title>").as_deref(),
+            Some("Fallback & co")
+        );
+        assert_eq!(page_title("<p>no title</p>"), None);
+    }
+
+    #[test]
+    fn lead_paragraph_skips_boilerplate() {
+        let html = r#"<html><head><meta name="description" content="Meta text"></head>
+            <body><nav><p>Home | About | A navigation paragraph long enough to be mistaken for content by a naive reader</p></nav>
+            <p>Posted by admin</p>
+            <p>Tokio is an <b>asynchronous runtime</b> for Rust. It provides the building blocks needed for writing network applications.</p>
+            </body></html>"#;
+        assert_eq!(
+            lead_paragraph(html).as_deref(),
+            Some(
+                "Tokio is an asynchronous runtime for Rust. It provides the building blocks needed for writing network applications."
+            )
+        );
+        assert_eq!(
+            lead_paragraph(r#"<meta property="og:description" content="Only meta">"#).as_deref(),
+            Some("Only meta")
+        );
     }
 
     #[test]
@@ -0,0 +1,78 @@
+use crate::tools::base::Tool;
+use crate::workflows::{WorkflowRunner, load_workflows};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Map, Value, json};
+use std::sync::Arc;
+
+pub struct RunWorkflowTool {
+    runner: Arc<WorkflowRunner>,
+}
+
+impl RunWorkflowTool {
+    pub fn new(runner: Arc<WorkflowRunner>) -> Self {
+        Self { runner }
+    }
+}
+
+#[async_trait]
+impl Tool for RunWorkflowTool {
+    fn name(&self) -> &str {
+        "run_workflow"
+    }
+
+    fn tags(&self) -> &[&str] {
+        &["workflows"]
+    }
+
+    fn mutates(&self, params: &Map<String, Value>) -> bool {
+        params.get("action").and_then(Value::as_str) == Some("run")
+    }
+
+    fn description(&self) -> &str {
+        "Run a workflow defined in the workspace workflows/ directory and return its output. Use action=list to see the available workflows."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": { "type": "string", "enum": ["run", "list"] },
+                "name": { "type": "string", "description": "Workflow name" },
+                "input": { "type": "object", "description": "Values available to the workflow as {{input.*}}" }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn execute(&self, params: &Map<String, Value>) -> Result<String> {
+        let action = params
+            .get("action")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: action"))?;
+        if action == "list" {
+            let workflows = load_workflows(self.runner.workspace());
+            if workflows.is_empty() {
+                return Ok("No workflows defined.".to_string());
+            }
+            return Ok(workflows
+                .iter()
+                .map(|workflow| format!("- {}: {}", workflow.name, workflow.description))
+                .collect::<Vec<_>>()
+                .join("\n"));
+        }
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("missing required string field: name"))?;
+        let input = params.get("input").cloned().unwrap_or_else(|| json!({}));
+        let run = self.runner.run(name, input, "tool").await?;
+        Ok(format!(
+            "Workflow '{}' {} (run {}).\n{}",
+            run.workflow,
+            run.status.as_str(),
+            run.id,
+            run.summary()
+        ))
+    }
+}
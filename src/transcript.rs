@@ -0,0 +1,171 @@
+use crate::redact::redact;
+use crate::session::Session;
+use chrono::NaiveDate;
+use serde_json::Value;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const COLLAPSED_TOOL_LINES: usize = 3;
+
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptOptions {
+    pub color: bool,
+    pub expand_tools: bool,
+    pub since: Option<NaiveDate>,
+}
+
+fn role_color(role: &str) -> &'static str {
+    match role {
+        "user" => "\x1b[1;32m",
+        "assistant" => "\x1b[1;36m",
+        "tool" => "\x1b[1;33m",
+        "system" => "\x1b[1;35m",
+        _ => "\x1b[1m",
+    }
+}
+
+fn message_date(timestamp: &str) -> Option<NaiveDate> {
+    timestamp
+        .get(..10)
+        .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+}
+
+fn content_text(msg: &Value) -> String {
+    match msg.get("content") {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(Value::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+fn tool_names(msg: &Value) -> Vec<String> {
+    if let Some(used) = msg.get("tools_used").and_then(Value::as_array) {
+        return used
+            .iter()
+            .filter_map(Value::as_str)
+            .map(ToOwned::to_owned)
+            .collect();
+    }
+    msg.get("tool_calls")
+        .and_then(Value::as_array)
+        .map(|calls| {
+            calls
+                .iter()
+                .filter_map(|call| call.get("function")?.get("name")?.as_str())
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn indent(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("  {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn render_transcript(session: &Session, options: &TranscriptOptions) -> String {
+    let paint = |style: &str, text: &str| {
+        if options.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut out = vec![
+        paint("\x1b[1m", &format!("Session: {}", session.key)),
+        format!("Messages: {}", session.messages.len()),
+    ];
+    let mut current_day = None;
+    for msg in &session.messages {
+        let role = msg.get("role").and_then(Value::as_str).unwrap_or("unknown");
+        let ts = msg.get("timestamp").and_then(Value::as_str).unwrap_or("");
+        let day = message_date(ts);
+        if let (Some(since), Some(day)) = (options.since, day)
+            && day < since
+        {
+            continue;
+        }
+        if day.is_some() && day != current_day {
+            current_day = day;
+            out.push(String::new());
+            out.push(paint(DIM, &format!("── {} ──", ts.get(..10).unwrap_or(ts))));
+        }
+
+        let time = ts.get(11..19).unwrap_or(ts);
+        out.push(format!(
+            "{} {}",
+            paint(DIM, time),
+            paint(role_color(role), role)
+        ));
+
+        let text = redact(&content_text(msg)).into_owned();
+        if role == "tool" && !options.expand_tools {
+            let lines = text.lines().collect::<Vec<_>>();
+            let shown = lines.len().min(COLLAPSED_TOOL_LINES);
+            out.push(indent(&lines[..shown].join("\n")));
+            if lines.len() > shown {
+                out.push(paint(
+                    DIM,
+                    &format!(
+                        "  … {} more lines (use --expand-tools)",
+                        lines.len() - shown
+                    ),
+                ));
+            }
+        } else if !text.is_empty() {
+            out.push(indent(&text));
+        }
+
+        let tools = tool_names(msg);
+        if !tools.is_empty() {
+            out.push(paint(DIM, &format!("  ⚙ tools: {}", tools.join(", "))));
+        }
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_session() -> Session {
+        let mut session = Session::new("cli:test");
+        session.messages = vec![
+            json!({"role": "user", "content": "hello", "timestamp": "2026-01-01T09:00:00+08:00"}),
+            json!({"role": "tool", "content": "1\n2\n3\n4\n5", "timestamp": "2026-01-02T09:00:01+08:00"}),
+            json!({"role": "assistant", "content": "done", "timestamp": "2026-01-02T09:00:02+08:00", "tools_used": ["exec"]}),
+        ];
+        session
+    }
+
+    #[test]
+    fn collapses_tool_output_and_groups_by_day() {
+        let out = render_transcript(&sample_session(), &TranscriptOptions::default());
+        assert!(out.contains("── 2026-01-01 ──"));
+        assert!(out.contains("── 2026-01-02 ──"));
+        assert!(out.contains("  3\n  … 2 more lines (use --expand-tools)"));
+        assert!(out.contains("  ⚙ tools: exec"));
+        assert!(!out.contains('\x1b'));
+    }
+
+    #[test]
+    fn since_skips_earlier_days_and_expand_shows_everything() {
+        let options = TranscriptOptions {
+            expand_tools: true,
+            since: NaiveDate::from_ymd_opt(2026, 1, 2),
+            ..Default::default()
+        };
+        let out = render_transcript(&sample_session(), &options);
+        assert!(!out.contains("hello"));
+        assert!(out.contains("  5"));
+        assert!(!out.contains("more lines"));
+    }
+}
@@ -0,0 +1,660 @@
+//! Per-call token, cost and latency records in `~/.nanobot/usage.jsonl`.
+//!
+//! `MeteredProvider` wraps the provider talking to the model endpoint, times
+//! each call (time to first token and total), adds the timings to
+//! `LLMResponse.usage` and appends a record priced at the time of the call.
+//! `nanobot usage` summarizes spend per model and session and `nanobot usage
+//! export` aggregates a month by day, model, channel and session for
+//! accounting.
+
+use crate::budget::price_for;
+use crate::config::ModelPrice;
+use crate::providers::base::{ChatOptions, LLMProvider, LLMResponse};
+use crate::providers::capabilities::Capabilities;
+use crate::utils::get_data_path;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, Months, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::warn;
+
+pub fn usage_path() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("usage.jsonl"))
+}
+
+tokio::task_local! {
+    /// Session key, channel and user of the turn a call is made for.
+    static ORIGIN: (String, String, String);
+}
+
+/// Runs `fut` with the model calls it makes attributed to `session` on
+/// `channel`, made for `user` (see `access::user_id`).
+pub async fn with_origin<F: Future>(
+    session: String,
+    channel: String,
+    user: String,
+    fut: F,
+) -> F::Output {
+    ORIGIN.scope((session, channel, user), fut).await
+}
+
+/// The session the current turn runs for, if any.
+pub fn origin_session() -> Option<String> {
+    ORIGIN
+        .try_with(|(session, _, _)| session.clone())
+        .ok()
+        .filter(|session| !session.is_empty())
+}
+
+/// The user the current turn runs for, if any.
+pub fn origin_user() -> Option<String> {
+    ORIGIN
+        .try_with(|(_, _, user)| user.clone())
+        .ok()
+        .filter(|user| !user.is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    pub at: DateTime<Local>,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Time to the first content delta; the whole call when not streamed.
+    pub ttft_ms: u64,
+    pub total_ms: u64,
+    /// Empty for calls made outside a turn and for older records.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub session: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub channel: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user: String,
+    /// Priced when recorded; `None` for unpriced models and older records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+}
+
+impl UsageRecord {
+    /// The recorded cost, else the cost at `prices`.
+    pub fn cost(&self, prices: &HashMap<String, ModelPrice>) -> Option<f64> {
+        self.cost_usd.or_else(|| {
+            price_for(prices, &self.model)
+                .map(|price| price.cost(self.prompt_tokens, self.completion_tokens))
+        })
+    }
+
+    /// Completion tokens per second of generation after the first token.
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        let generation_ms = if self.total_ms > self.ttft_ms {
+            self.total_ms - self.ttft_ms
+        } else {
+            self.total_ms
+        };
+        (self.completion_tokens > 0 && generation_ms > 0)
+            .then(|| self.completion_tokens as f64 * 1000.0 / generation_ms as f64)
+    }
+}
+
+/// Records since `since`, oldest first; unreadable lines are skipped.
+pub fn load_records(path: &std::path::Path, since: Option<DateTime<Local>>) -> Vec<UsageRecord> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| serde_json::from_str::<UsageRecord>(line).ok())
+        .filter(|record| since.is_none_or(|since| record.at >= since))
+        .collect()
+}
+
+pub fn append_record(path: &std::path::Path, record: &UsageRecord) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Latency and throughput for one model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelPerf {
+    pub model: String,
+    pub calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub ttft_p50_ms: u64,
+    pub ttft_p95_ms: u64,
+    pub total_p50_ms: u64,
+    pub total_p95_ms: u64,
+    pub tokens_per_second_p50: Option<f64>,
+}
+
+/// Nearest-rank percentile of an ascending slice.
+pub(crate) fn percentile<T: Copy>(sorted: &[T], pct: usize) -> Option<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+/// Per-model summary, sorted by model name.
+pub fn summarize(records: &[UsageRecord]) -> Vec<ModelPerf> {
+    let mut by_model: BTreeMap<&str, Vec<&UsageRecord>> = BTreeMap::new();
+    for record in records {
+        by_model.entry(&record.model).or_default().push(record);
+    }
+    by_model
+        .into_iter()
+        .map(|(model, records)| {
+            let mut ttft = records.iter().map(|r| r.ttft_ms).collect::<Vec<_>>();
+            let mut total = records.iter().map(|r| r.total_ms).collect::<Vec<_>>();
+            let mut tps = records
+                .iter()
+                .filter_map(|r| r.tokens_per_second())
+                .collect::<Vec<_>>();
+            ttft.sort_unstable();
+            total.sort_unstable();
+            tps.sort_by(f64::total_cmp);
+            ModelPerf {
+                model: model.to_string(),
+                calls: records.len(),
+                prompt_tokens: records.iter().map(|r| r.prompt_tokens).sum(),
+                completion_tokens: records.iter().map(|r| r.completion_tokens).sum(),
+                ttft_p50_ms: percentile(&ttft, 50).unwrap_or(0),
+                ttft_p95_ms: percentile(&ttft, 95).unwrap_or(0),
+                total_p50_ms: percentile(&total, 50).unwrap_or(0),
+                total_p95_ms: percentile(&total, 95).unwrap_or(0),
+                tokens_per_second_p50: percentile(&tps, 50),
+            }
+        })
+        .collect()
+}
+
+/// Calls, tokens and cost under one model or session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spend {
+    pub key: String,
+    pub calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Of the priced calls only.
+    pub cost_usd: f64,
+    pub unpriced_calls: usize,
+}
+
+/// Sums `records` by `key`, most expensive first.
+pub fn spend_by<'a>(
+    records: &'a [UsageRecord],
+    prices: &HashMap<String, ModelPrice>,
+    key: impl Fn(&'a UsageRecord) -> &'a str,
+) -> Vec<Spend> {
+    let mut groups: BTreeMap<&str, Spend> = BTreeMap::new();
+    for record in records {
+        let name = key(record);
+        let spend = groups.entry(name).or_insert_with(|| Spend {
+            key: name.to_string(),
+            calls: 0,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            cost_usd: 0.0,
+            unpriced_calls: 0,
+        });
+        spend.calls += 1;
+        spend.prompt_tokens += record.prompt_tokens;
+        spend.completion_tokens += record.completion_tokens;
+        match record.cost(prices) {
+            Some(cost) => spend.cost_usd += cost,
+            None => spend.unpriced_calls += 1,
+        }
+    }
+    let mut spends: Vec<Spend> = groups.into_values().collect();
+    spends.sort_by(|a, b| b.cost_usd.total_cmp(&a.cost_usd));
+    spends
+}
+
+/// The start of a `--since` window: a span back from `now` (`30m`, `24h`,
+/// `7d`, `2w`) or a local date (`2025-01-31`).
+pub fn parse_since(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>> {
+    let text = text.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+            .earliest()
+            .context("no local midnight on that date");
+    }
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (amount, unit) = text.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .with_context(|| format!("invalid --since '{text}', expected e.g. 7d or 2025-01-31"))?;
+    let span = match unit {
+        "m" => chrono::Duration::minutes(amount),
+        "h" => chrono::Duration::hours(amount),
+        "d" | "" => chrono::Duration::days(amount),
+        "w" => chrono::Duration::weeks(amount),
+        other => anyhow::bail!("unknown unit '{other}' in --since, use m, h, d or w"),
+    };
+    Ok(now - span)
+}
+
+/// Usage of one model in one session on one day.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRow {
+    pub date: NaiveDate,
+    pub model: String,
+    pub channel: String,
+    pub session: String,
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// `None` when some calls have no price.
+    pub cost_usd: Option<f64>,
+}
+
+/// Start and end of `month` (`2025-01`) in local time.
+pub fn month_range(month: &str) -> Result<(DateTime<Local>, DateTime<Local>)> {
+    let first = NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d")
+        .with_context(|| format!("invalid month '{month}', expected YYYY-MM"))?;
+    let next = first
+        .checked_add_months(Months::new(1))
+        .context("month out of range")?;
+    let local = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap_or_default())
+            .earliest()
+            .context("no local midnight on that date")
+    };
+    Ok((local(first)?, local(next)?))
+}
+
+/// Sums `records` by day, model, channel and session, priced with
+/// `prices`; sorted in that order.
+pub fn aggregate(records: &[UsageRecord], prices: &HashMap<String, ModelPrice>) -> Vec<UsageRow> {
+    let mut rows: BTreeMap<(NaiveDate, &str, &str, &str), UsageRow> = BTreeMap::new();
+    for record in records {
+        let date = record.at.date_naive();
+        let row = rows
+            .entry((date, &record.model, &record.channel, &record.session))
+            .or_insert_with(|| UsageRow {
+                date,
+                model: record.model.clone(),
+                channel: record.channel.clone(),
+                session: record.session.clone(),
+                calls: 0,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                cost_usd: None,
+            });
+        row.cost_usd = match (row.calls, row.cost_usd, record.cost(prices)) {
+            (0, _, cost) => cost,
+            (_, Some(sum), Some(cost)) => Some(sum + cost),
+            _ => None,
+        };
+        row.calls += 1;
+        row.prompt_tokens += record.prompt_tokens;
+        row.completion_tokens += record.completion_tokens;
+    }
+    rows.into_values().collect()
+}
+
+/// Models in `rows` without a price.
+pub fn unpriced_models(rows: &[UsageRow]) -> Vec<String> {
+    rows.iter()
+        .filter(|row| row.cost_usd.is_none())
+        .map(|row| row.model.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn rows_to_csv(rows: &[UsageRow]) -> String {
+    let mut out =
+        String::from("date,model,channel,session,calls,prompt_tokens,completion_tokens,cost_usd\n");
+    for row in rows {
+        let cost = row
+            .cost_usd
+            .map(|cost| format!("{cost:.6}"))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{cost}\n",
+            row.date,
+            csv_field(&row.model),
+            csv_field(&row.channel),
+            csv_field(&row.session),
+            row.calls,
+            row.prompt_tokens,
+            row.completion_tokens,
+        ));
+    }
+    out
+}
+
+pub struct MeteredProvider {
+    inner: Arc<dyn LLMProvider>,
+    path: Option<PathBuf>,
+    prices: HashMap<String, ModelPrice>,
+}
+
+impl MeteredProvider {
+    pub fn new(inner: Arc<dyn LLMProvider>, path: Option<PathBuf>) -> Self {
+        Self {
+            inner,
+            path,
+            prices: HashMap::new(),
+        }
+    }
+
+    pub fn with_prices(mut self, prices: HashMap<String, ModelPrice>) -> Self {
+        self.prices = prices;
+        self
+    }
+
+    fn finish(
+        &self,
+        model: &str,
+        response: &mut LLMResponse,
+        started: Instant,
+        first_token: Option<Instant>,
+    ) {
+        let total_ms = started.elapsed().as_millis() as u64;
+        let ttft_ms = first_token
+            .map(|at| at.duration_since(started).as_millis() as u64)
+            .unwrap_or(total_ms);
+        let (prompt_tokens, completion_tokens) = response.token_usage();
+        let (session, channel, user) = ORIGIN.try_with(Clone::clone).unwrap_or_default();
+        let record = UsageRecord {
+            at: Local::now(),
+            model: model.to_string(),
+            prompt_tokens,
+            completion_tokens,
+            ttft_ms,
+            total_ms,
+            session,
+            channel,
+            user,
+            cost_usd: price_for(&self.prices, model)
+                .map(|price| price.cost(prompt_tokens, completion_tokens)),
+        };
+        response
+            .usage
+            .insert("ttft_ms".to_string(), Value::from(ttft_ms));
+        response
+            .usage
+            .insert("total_ms".to_string(), Value::from(total_ms));
+        if let Some(tps) = record.tokens_per_second() {
+            response.usage.insert(
+                "tokens_per_second".to_string(),
+                Value::from((tps * 10.0).round() / 10.0),
+            );
+        }
+        if let Some(path) = &self.path
+            && let Err(err) = append_record(path, &record)
+        {
+            warn!("failed to record usage: {err:#}");
+        }
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MeteredProvider {
+    async fn chat(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<LLMResponse> {
+        self.chat_with_options(
+            messages,
+            tools,
+            model,
+            max_tokens,
+            temperature,
+            &ChatOptions::default(),
+        )
+        .await
+    }
+
+    async fn chat_with_options(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        options: &ChatOptions,
+    ) -> Result<LLMResponse> {
+        let model = model.unwrap_or_else(|| self.inner.default_model());
+        let started = Instant::now();
+        let mut response = self
+            .inner
+            .chat_with_options(
+                messages,
+                tools,
+                Some(model),
+                max_tokens,
+                temperature,
+                options,
+            )
+            .await?;
+        self.finish(model, &mut response, started, None);
+        Ok(response)
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[Value],
+        tools: Option<&[Value]>,
+        model: Option<&str>,
+        max_tokens: u32,
+        temperature: f32,
+        on_delta: &(dyn for<'d> Fn(&'d str) + Send + Sync),
+    ) -> Result<LLMResponse> {
+        let model = model.unwrap_or_else(|| self.inner.default_model());
+        let started = Instant::now();
+        let first_token = Mutex::new(None);
+        let timed_delta = |text: &str| {
+            if let Ok(mut first) = first_token.lock() {
+                first.get_or_insert_with(Instant::now);
+            }
+            on_delta(text);
+        };
+        let mut response = self
+            .inner
+            .chat_stream(
+                messages,
+                tools,
+                Some(model),
+                max_tokens,
+                temperature,
+                &timed_delta,
+            )
+            .await?;
+        let first = first_token.lock().ok().and_then(|first| *first);
+        self.finish(model, &mut response, started, first);
+        Ok(response)
+    }
+
+    fn default_model(&self) -> &str {
+        self.inner.default_model()
+    }
+
+    fn capabilities(&self, model: &str) -> Capabilities {
+        self.inner.capabilities(model)
+    }
+}
+
+/// Wraps `provider` so every call is timed, priced with `prices` and
+/// recorded in `usage.jsonl`.
+pub fn wrap_provider(
+    provider: Arc<dyn LLMProvider>,
+    prices: HashMap<String, ModelPrice>,
+) -> Arc<dyn LLMProvider> {
+    Arc::new(MeteredProvider::new(provider, usage_path().ok()).with_prices(prices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BudgetConfig;
+
+    fn record(model: &str, ttft_ms: u64, total_ms: u64, completion_tokens: u64) -> UsageRecord {
+        UsageRecord {
+            at: Local::now(),
+            model: model.to_string(),
+            prompt_tokens: 100,
+            completion_tokens,
+            ttft_ms,
+            total_ms,
+            session: String::new(),
+            channel: String::new(),
+            user: String::new(),
+            cost_usd: None,
+        }
+    }
+
+    #[test]
+    fn summarizes_percentiles_per_model() {
+        let mut records = (1..=20)
+            .map(|i| record("gpt-4o", i * 10, i * 100, 50))
+            .collect::<Vec<_>>();
+        records.push(record("llama", 50, 550, 100));
+        let dir = std::env::temp_dir().join(format!("nanobot-usage-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("usage.jsonl");
+        for r in &records {
+            append_record(&path, r).expect("append");
+        }
+        let loaded = load_records(&path, None);
+        assert_eq!(loaded.len(), 21);
+
+        let stats = summarize(&loaded);
+        assert_eq!(stats[0].model, "gpt-4o");
+        assert_eq!((stats[0].ttft_p50_ms, stats[0].ttft_p95_ms), (100, 190));
+        assert_eq!((stats[0].total_p50_ms, stats[0].total_p95_ms), (1000, 1900));
+        assert_eq!(stats[1].calls, 1);
+        assert_eq!(stats[1].tokens_per_second_p50, Some(200.0));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn spend_is_summed_per_key_from_recorded_or_current_prices() {
+        let now = Local::now();
+        assert_eq!(
+            parse_since("7d", now).unwrap(),
+            now - chrono::Duration::days(7)
+        );
+        assert_eq!(
+            parse_since("12h", now).unwrap(),
+            now - chrono::Duration::hours(12)
+        );
+        assert_eq!(
+            parse_since("2025-01-31", now)
+                .unwrap()
+                .date_naive()
+                .to_string(),
+            "2025-01-31"
+        );
+        assert!(parse_since("7y", now).is_err());
+
+        let call = |model: &str, session: &str, cost_usd: Option<f64>| UsageRecord {
+            session: session.to_string(),
+            cost_usd,
+            ..record(model, 100, 200, 1_000_000)
+        };
+        let records = vec![
+            call("gpt-4o", "telegram:1", Some(1.5)),
+            call("gpt-4o", "telegram:2", None),
+            call("local-llama", "telegram:1", None),
+        ];
+        let prices = BudgetConfig::default().model_prices();
+        let by_model = spend_by(&records, &prices, |r| r.model.as_str());
+        assert_eq!(by_model[0].key, "gpt-4o");
+        assert_eq!(by_model[0].calls, 2);
+        assert!((by_model[0].cost_usd - 11.50025).abs() < 1e-9);
+        assert_eq!(by_model[1].unpriced_calls, 1);
+        let by_session = spend_by(&records, &prices, |r| r.session.as_str());
+        assert_eq!(by_session[0].key, "telegram:2");
+        assert_eq!(by_session[1].cost_usd, 1.5);
+    }
+
+    #[test]
+    fn aggregates_a_month_by_day_model_and_session() {
+        let (start, end) = month_range("2025-01").expect("range");
+        assert_eq!(start.date_naive().to_string(), "2025-01-01");
+        assert_eq!(end.date_naive().to_string(), "2025-02-01");
+        assert!(month_range("2025-13").is_err());
+
+        let at = |day: u32| {
+            Local
+                .with_ymd_and_hms(2025, 1, day, 12, 0, 0)
+                .single()
+                .unwrap()
+        };
+        let call = |day: u32, model: &str, session: &str| UsageRecord {
+            at: at(day),
+            session: session.to_string(),
+            channel: session.split(':').next().unwrap().to_string(),
+            prompt_tokens: 1_000,
+            completion_tokens: 500,
+            ..record(model, 10, 100, 500)
+        };
+        let records = vec![
+            call(2, "openai/gpt-4o", "telegram:1"),
+            call(2, "openai/gpt-4o", "telegram:1"),
+            call(2, "openai/gpt-4o", "slack:C1,general"),
+            call(3, "local-llama", "telegram:1"),
+        ];
+        let prices = HashMap::from([(
+            "gpt-4o".to_string(),
+            ModelPrice {
+                input_per_million: 2.5,
+                output_per_million: 10.0,
+            },
+        )]);
+        let rows = aggregate(&records, &prices);
+        assert_eq!(rows.len(), 3);
+        assert_eq!((rows[1].session.as_str(), rows[1].calls), ("telegram:1", 2));
+        assert_eq!(rows[1].cost_usd, Some(0.015));
+        assert_eq!(rows[2].cost_usd, None);
+        assert_eq!(unpriced_models(&rows), ["local-llama"]);
+
+        let csv = rows_to_csv(&rows);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "date,model,channel,session,calls,prompt_tokens,completion_tokens,cost_usd"
+        );
+        assert_eq!(
+            lines[1],
+            "2025-01-02,openai/gpt-4o,slack,\"slack:C1,general\",1,1000,500,0.007500"
+        );
+        assert_eq!(
+            lines[3],
+            "2025-01-03,local-llama,telegram,telegram:1,1,1000,500,"
+        );
+    }
+}
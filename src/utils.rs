@@ -1,38 +1,145 @@
 use chrono::Local;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const DEFAULT_WORKSPACE: &str = "~/.nanobot/workspace";
+
+static DATA_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
 
 pub fn ensure_dir(path: &Path) -> std::io::Result<PathBuf> {
     std::fs::create_dir_all(path)?;
     Ok(path.to_path_buf())
 }
 
-pub fn get_data_path() -> std::io::Result<PathBuf> {
+/// Writes `contents` to a file only its owner can read, created that way
+/// rather than narrowed after the write.
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies to new files.
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(contents)
+}
+
+/// Runs `f` while holding the in-process lock for `path`, so turns running
+/// at the same time do not lose each other's read-modify-write updates.
+/// Not reentrant: `f` must not lock the same path again.
+pub fn with_path_lock<T>(path: &Path, f: impl FnOnce() -> T) -> T {
+    static LOCKS: OnceLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = OnceLock::new();
+    let lock = LOCKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone();
+    let _guard = lock.lock().unwrap_or_else(|e| e.into_inner());
+    f()
+}
+
+/// Points the data directory (sessions, cron, logs) at another location so
+/// separate instances do not share state. Only the first call takes effect.
+pub fn set_data_dir_override(path: PathBuf) {
+    let _ = DATA_DIR_OVERRIDE.set(path);
+}
+
+/// `~/.nanobot`, regardless of any instance override.
+pub fn get_home_data_path() -> std::io::Result<PathBuf> {
     let home =
         dirs::home_dir().ok_or_else(|| std::io::Error::other("cannot resolve home directory"))?;
     ensure_dir(&home.join(".nanobot"))
 }
 
+pub fn get_data_path() -> std::io::Result<PathBuf> {
+    match DATA_DIR_OVERRIDE.get() {
+        Some(dir) => ensure_dir(dir),
+        None => get_home_data_path(),
+    }
+}
+
+/// Expands a configured workspace; the default workspace follows the data
+/// directory override.
+pub fn resolve_workspace(workspace: &str) -> PathBuf {
+    if workspace == DEFAULT_WORKSPACE
+        && let Some(dir) = DATA_DIR_OVERRIDE.get()
+    {
+        return dir.join("workspace");
+    }
+    expand_tilde(workspace)
+}
+
 pub fn expand_tilde(path: &str) -> PathBuf {
-    if let Some(stripped) = path.strip_prefix("~/") {
-        if let Some(home) = dirs::home_dir() {
-            return home.join(stripped);
-        }
+    if let Some(stripped) = path.strip_prefix("~/")
+        && let Some(home) = dirs::home_dir()
+    {
+        return home.join(stripped);
     }
     PathBuf::from(path)
 }
 
 pub fn get_workspace_path(workspace: Option<&str>) -> std::io::Result<PathBuf> {
-    let path = match workspace {
-        Some(p) => expand_tilde(p),
-        None => {
-            let home = dirs::home_dir()
-                .ok_or_else(|| std::io::Error::other("cannot resolve home directory"))?;
-            home.join(".nanobot").join("workspace")
-        }
-    };
+    let path = resolve_workspace(workspace.unwrap_or(DEFAULT_WORKSPACE));
     ensure_dir(&path)
 }
 
+/// Where tzdata keeps zone files on common Unix systems.
+const ZONEINFO_DIRS: &[&str] = &[
+    "/usr/share/zoneinfo",
+    "/usr/lib/zoneinfo",
+    "/usr/share/lib/zoneinfo",
+];
+
+/// The `TZ` value for the `timezone` setting: an IANA name such as
+/// `Asia/Shanghai`, or `UTC`. `None` keeps the OS zone.
+fn tz_value(timezone: &str) -> anyhow::Result<Option<String>> {
+    let timezone = timezone.trim();
+    if timezone.is_empty() || timezone.eq_ignore_ascii_case("auto") {
+        return Ok(None);
+    }
+    if timezone.eq_ignore_ascii_case("utc") {
+        return Ok(Some("UTC0".to_string()));
+    }
+    let well_formed = !timezone.starts_with('/')
+        && timezone
+            .split('/')
+            .all(|part| !part.is_empty() && part != "..");
+    let installed = well_formed
+        && ZONEINFO_DIRS
+            .iter()
+            .map(PathBuf::from)
+            .chain(std::env::var_os("TZDIR").map(PathBuf::from))
+            .any(|dir| dir.join(timezone).is_file());
+    if !installed {
+        anyhow::bail!("unknown timezone '{timezone}': expected an IANA name such as Asia/Shanghai");
+    }
+    Ok(Some(timezone.to_string()))
+}
+
+/// Makes local time (today's date, daily memory files, cron, reminders,
+/// briefings) follow the `timezone` setting instead of the OS zone.
+pub fn set_timezone(timezone: &str) -> anyhow::Result<()> {
+    let Some(value) = tz_value(timezone)? else {
+        return Ok(());
+    };
+    if cfg!(windows) {
+        anyhow::bail!(
+            "the timezone setting is not supported on Windows; change the system time zone instead"
+        );
+    }
+    // SAFETY: runs at startup before any task reads the environment.
+    unsafe { std::env::set_var("TZ", value) };
+    Ok(())
+}
+
 pub fn today_date() -> String {
     Local::now().format("%Y-%m-%d").to_string()
 }
@@ -55,3 +162,45 @@ pub fn parse_session_key(key: &str) -> anyhow::Result<(&str, &str)> {
         .ok_or_else(|| anyhow::anyhow!("invalid session key: {key}"))?;
     Ok((channel, chat_id))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timezone_setting_maps_to_tz() {
+        assert_eq!(tz_value("").unwrap(), None);
+        assert_eq!(tz_value("auto").unwrap(), None);
+        assert_eq!(tz_value(" utc ").unwrap().as_deref(), Some("UTC0"));
+        assert!(tz_value("Mars/Olympus_Mons").is_err());
+        assert!(tz_value("../../etc/passwd").is_err());
+        assert!(tz_value("/etc/localtime").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("nanobot-private-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("dir");
+        let path = dir.join("token");
+        std::fs::write(&path, "old").expect("write");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).expect("chmod");
+
+        write_private(&path, b"new").expect("write");
+        let mode = std::fs::metadata(&path)
+            .expect("metadata")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_to_string(&path).expect("read"), "new");
+        let fresh = dir.join("fresh");
+        write_private(&fresh, b"x").expect("write");
+        let mode = std::fs::metadata(&fresh)
+            .expect("metadata")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
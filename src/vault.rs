@@ -0,0 +1,185 @@
+//! Per-user encryption of transcripts and memory (`gateway.encryptUserData`).
+//!
+//! In multi-user mode every user is an API key bound to a profile, and their
+//! sessions live under `api:<profile>`. With encryption on, those sessions
+//! and the user's memory are sealed with AES-256-GCM under a random data key
+//! per profile. The data key is stored only wrapped under a key derived
+//! (HKDF-SHA256) from each of the profile's API keys, and only a hash of the
+//! API key is stored, so data at rest cannot be read without a credential of
+//! the profile: the data key is unlocked in memory when a user
+//! authenticates and is forgotten when the gateway stops. Issuing a second
+//! key wraps the same data key again, so rotating keys keeps the data.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Prefix of the sessions of profile-bound API keys.
+pub const USER_SCOPE_PREFIX: &str = "api:";
+const KEY_INFO: &[u8] = b"nanobot user data v1";
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone)]
+pub struct UserKey([u8; 32]);
+
+impl UserKey {
+    /// The key for `credential` (the full API key), salted with its id.
+    pub fn derive(credential: &str, salt: &str) -> Self {
+        let hkdf = Hkdf::<Sha256>::new(Some(salt.as_bytes()), credential.as_bytes());
+        let mut key = [0u8; 32];
+        hkdf.expand(KEY_INFO, &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        Self(key)
+    }
+
+    /// A random data key.
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::generate_key(&mut OsRng).into())
+    }
+
+    /// `data_key` sealed under this key, for storing next to the API key.
+    pub fn wrap(&self, data_key: &UserKey) -> Result<String> {
+        self.seal(&data_key.0)
+    }
+
+    /// The data key `wrap` sealed under this key.
+    pub fn unwrap_key(&self, wrapped: &str) -> Result<UserKey> {
+        let raw = self.open(wrapped)?;
+        let key = <[u8; 32]>::try_from(raw.as_slice())
+            .map_err(|_| anyhow!("wrapped data key has the wrong length"))?;
+        Ok(Self(key))
+    }
+
+    /// `plaintext` encrypted under a fresh nonce, as base64.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<String> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow!("encryption failed"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    pub fn open(&self, sealed: &str) -> Result<Vec<u8>> {
+        let raw = STANDARD.decode(sealed.trim())?;
+        if raw.len() < NONCE_LEN {
+            return Err(anyhow!("sealed data is truncated"));
+        }
+        let (nonce, ciphertext) = raw.split_at(NONCE_LEN);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0));
+        cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("cannot decrypt: sealed with a different API key"))
+    }
+}
+
+impl std::fmt::Debug for UserKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UserKey(..)")
+    }
+}
+
+/// The user scope (`api:<profile>`) a session key belongs to.
+pub fn user_scope(session_key: &str) -> Option<&str> {
+    let rest = session_key.strip_prefix(USER_SCOPE_PREFIX)?;
+    let profile = rest.split(':').next().filter(|p| !p.is_empty())?;
+    Some(&session_key[..USER_SCOPE_PREFIX.len() + profile.len()])
+}
+
+/// Keys of the users who authenticated since the gateway started.
+#[derive(Debug, Default)]
+pub struct Vault {
+    keys: Mutex<HashMap<String, UserKey>>,
+}
+
+impl Vault {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unlock(&self, scope: &str, key: UserKey) {
+        self.keys
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(scope.to_string(), key);
+    }
+
+    /// How `session_key` is stored: `None` for plain sessions, otherwise the
+    /// user's key, or an error while the user has not authenticated.
+    pub fn key_for(&self, session_key: &str) -> Result<Option<UserKey>> {
+        let Some(scope) = user_scope(session_key) else {
+            return Ok(None);
+        };
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.get(scope)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| anyhow!("{scope} is encrypted and locked until its API key is used"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seals_user_data_under_the_credential_key() {
+        let key = UserKey::derive("nbk_abc_secret", "abc");
+        let sealed = key.seal(b"my diary").unwrap();
+        assert!(!sealed.contains("diary"));
+        assert_eq!(key.open(&sealed).unwrap(), b"my diary");
+        assert!(
+            UserKey::derive("nbk_abc_other", "abc")
+                .open(&sealed)
+                .is_err()
+        );
+
+        assert_eq!(user_scope("api:alice:notes"), Some("api:alice"));
+        assert_eq!(user_scope("api:alice"), Some("api:alice"));
+        assert_eq!(user_scope("telegram:42"), None);
+
+        let vault = Vault::new();
+        assert!(vault.key_for("telegram:42").unwrap().is_none());
+        assert!(vault.key_for("api:alice:notes").is_err());
+        vault.unlock("api:alice", key);
+        assert!(vault.key_for("api:alice:notes").unwrap().is_some());
+    }
+
+    #[test]
+    fn data_keys_open_under_every_wrapping_key() {
+        let data_key = UserKey::generate();
+        let sealed = data_key.seal(b"my diary").unwrap();
+        let first = UserKey::derive("nbk_a_secret", "a");
+        let second = UserKey::derive("nbk_b_secret", "b");
+        let wrapped = [
+            first.wrap(&data_key).unwrap(),
+            second.wrap(&data_key).unwrap(),
+        ];
+
+        assert_eq!(
+            first
+                .unwrap_key(&wrapped[0])
+                .unwrap()
+                .open(&sealed)
+                .unwrap(),
+            b"my diary"
+        );
+        assert_eq!(
+            second
+                .unwrap_key(&wrapped[1])
+                .unwrap()
+                .open(&sealed)
+                .unwrap(),
+            b"my diary"
+        );
+        assert!(second.unwrap_key(&wrapped[0]).is_err());
+    }
+}
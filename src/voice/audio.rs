@@ -0,0 +1,167 @@
+//! Default input/output devices via cpal.
+//!
+//! cpal streams are not `Send` on every platform, so they live on a
+//! dedicated thread for as long as the `AudioIo` handle exists.
+
+use super::resample;
+use anyhow::{Result, anyhow, bail};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, mpsc as std_mpsc};
+use tokio::sync::mpsc;
+
+type Playback = Arc<Mutex<VecDeque<f32>>>;
+
+pub struct AudioIo {
+    pub input_rate: u32,
+    output_rate: u32,
+    playback: Playback,
+    _shutdown: std_mpsc::Sender<()>,
+}
+
+impl AudioIo {
+    /// Opens the default microphone and speaker; mono microphone frames are sent to `frames`.
+    pub fn open(frames: mpsc::UnboundedSender<Vec<f32>>) -> Result<Self> {
+        let playback: Playback = Arc::new(Mutex::new(VecDeque::new()));
+        let (ready_tx, ready_rx) = std_mpsc::channel();
+        let (shutdown_tx, shutdown_rx) = std_mpsc::channel::<()>();
+        let queue = playback.clone();
+        std::thread::Builder::new()
+            .name("voice-audio".to_string())
+            .spawn(move || match open_streams(frames, queue) {
+                Ok((input, output, rates)) => {
+                    let _ = ready_tx.send(Ok(rates));
+                    // Blocks until the handle is dropped.
+                    let _ = shutdown_rx.recv();
+                    drop(input);
+                    drop(output);
+                }
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err));
+                }
+            })?;
+        let (input_rate, output_rate) = ready_rx
+            .recv()
+            .map_err(|_| anyhow!("audio thread exited during setup"))??;
+        Ok(Self {
+            input_rate,
+            output_rate,
+            playback,
+            _shutdown: shutdown_tx,
+        })
+    }
+
+    /// Queues mono samples recorded at `rate` for playback.
+    pub fn play(&self, samples: &[f32], rate: u32) {
+        let samples = resample(samples, rate, self.output_rate);
+        if let Ok(mut queue) = self.playback.lock() {
+            queue.extend(samples);
+        }
+    }
+
+    pub fn stop_playback(&self) {
+        if let Ok(mut queue) = self.playback.lock() {
+            queue.clear();
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.lock().is_ok_and(|queue| !queue.is_empty())
+    }
+}
+
+fn open_streams(
+    frames: mpsc::UnboundedSender<Vec<f32>>,
+    playback: Playback,
+) -> Result<(Stream, Stream, (u32, u32))> {
+    let host = cpal::default_host();
+    let input = host
+        .default_input_device()
+        .ok_or_else(|| anyhow!("no microphone found"))?;
+    let output = host
+        .default_output_device()
+        .ok_or_else(|| anyhow!("no audio output device found"))?;
+
+    let input_config = input.default_input_config()?;
+    let input_format = input_config.sample_format();
+    let input_config: StreamConfig = input_config.into();
+    let input_stream = match input_format {
+        SampleFormat::F32 => build_input::<f32>(&input, &input_config, frames)?,
+        SampleFormat::I16 => build_input::<i16>(&input, &input_config, frames)?,
+        SampleFormat::U16 => build_input::<u16>(&input, &input_config, frames)?,
+        other => bail!("unsupported microphone sample format {other}"),
+    };
+
+    let output_config = output.default_output_config()?;
+    let output_format = output_config.sample_format();
+    let output_config: StreamConfig = output_config.into();
+    let output_stream = match output_format {
+        SampleFormat::F32 => build_output::<f32>(&output, &output_config, playback)?,
+        SampleFormat::I16 => build_output::<i16>(&output, &output_config, playback)?,
+        SampleFormat::U16 => build_output::<u16>(&output, &output_config, playback)?,
+        other => bail!("unsupported speaker sample format {other}"),
+    };
+
+    input_stream.play()?;
+    output_stream.play()?;
+    Ok((
+        input_stream,
+        output_stream,
+        (input_config.sample_rate.0, output_config.sample_rate.0),
+    ))
+}
+
+fn build_input<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    frames: mpsc::UnboundedSender<Vec<f32>>,
+) -> Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = usize::from(config.channels.max(1));
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mono = data
+                .chunks(channels)
+                .map(|frame| {
+                    frame.iter().map(|s| s.to_sample::<f32>()).sum::<f32>() / frame.len() as f32
+                })
+                .collect();
+            let _ = frames.send(mono);
+        },
+        |err| tracing::warn!("microphone stream error: {err}"),
+        None,
+    )?;
+    Ok(stream)
+}
+
+fn build_output<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    playback: Playback,
+) -> Result<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels.max(1));
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut queue = playback.lock().ok();
+            for frame in data.chunks_mut(channels) {
+                let value = queue
+                    .as_mut()
+                    .and_then(|queue| queue.pop_front())
+                    .unwrap_or(0.0);
+                frame.fill(T::from_sample(value));
+            }
+        },
+        |err| tracing::warn!("speaker stream error: {err}"),
+        None,
+    )?;
+    Ok(stream)
+}
@@ -0,0 +1,345 @@
+//! Hands-free conversation for `nanobot voice`.
+//!
+//! Microphone audio is cut into utterances by a simple energy detector,
+//! transcribed, answered by the agent and spoken back. Speaking over the
+//! reply (barge-in) stops playback and starts the next utterance. Audio
+//! device access needs the `voice` feature; the rest is plain signal
+//! handling and HTTP so it builds everywhere.
+
+#[cfg(feature = "voice")]
+mod audio;
+#[cfg(feature = "voice")]
+mod session;
+
+#[cfg(feature = "voice")]
+pub use session::run_voice;
+
+use crate::config::{Config, VoiceConfig};
+use crate::providers::transcription::GroqTranscriptionProvider;
+use anyhow::{Result, anyhow, bail};
+use regex::Regex;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+/// Sample rate of the `pcm` format returned by OpenAI-compatible TTS.
+pub const TTS_SAMPLE_RATE: u32 = 24_000;
+/// Rate utterances are resampled to before upload.
+pub const STT_SAMPLE_RATE: u32 = 16_000;
+
+const WINDOW_MS: u64 = 20;
+const PREROLL_MS: u64 = 300;
+const MIN_SPEECH_MS: u64 = 200;
+const MAX_UTTERANCE_S: u64 = 30;
+
+/// Splits a stream of mono samples into utterances by RMS level.
+pub struct Segmenter {
+    threshold: f32,
+    window: usize,
+    silence_limit: usize,
+    min_speech_windows: usize,
+    max_samples: usize,
+    preroll_samples: usize,
+    pending: Vec<f32>,
+    preroll: VecDeque<f32>,
+    speech: Vec<f32>,
+    speaking: bool,
+    speech_windows: usize,
+    silence_windows: usize,
+}
+
+impl Segmenter {
+    pub fn new(sample_rate: u32, threshold: f32, silence_ms: u64) -> Self {
+        let per_ms = |ms: u64| (u64::from(sample_rate) * ms / 1000) as usize;
+        let window = per_ms(WINDOW_MS).max(1);
+        Self {
+            threshold,
+            window,
+            silence_limit: (silence_ms / WINDOW_MS).max(1) as usize,
+            min_speech_windows: (MIN_SPEECH_MS / WINDOW_MS) as usize,
+            max_samples: per_ms(MAX_UTTERANCE_S * 1000),
+            preroll_samples: per_ms(PREROLL_MS),
+            pending: Vec::new(),
+            preroll: VecDeque::new(),
+            speech: Vec::new(),
+            speaking: false,
+            speech_windows: 0,
+            silence_windows: 0,
+        }
+    }
+
+    /// Feeds samples; returns an utterance once it is followed by enough silence.
+    pub fn push(&mut self, samples: &[f32]) -> Option<Vec<f32>> {
+        self.pending.extend_from_slice(samples);
+        let mut finished = None;
+        let mut offset = 0;
+        while self.pending.len() - offset >= self.window {
+            let window = self.pending[offset..offset + self.window].to_vec();
+            offset += self.window;
+            if let Some(utterance) = self.step(&window) {
+                finished = Some(utterance);
+            }
+        }
+        self.pending.drain(..offset);
+        finished
+    }
+
+    fn step(&mut self, window: &[f32]) -> Option<Vec<f32>> {
+        let loud = rms(window) >= self.threshold;
+        if !self.speaking {
+            self.preroll.extend(window);
+            while self.preroll.len() > self.preroll_samples {
+                self.preroll.pop_front();
+            }
+            if loud {
+                self.speaking = true;
+                self.speech = self.preroll.drain(..).collect();
+                self.speech_windows = 1;
+                self.silence_windows = 0;
+            }
+            return None;
+        }
+        self.speech.extend_from_slice(window);
+        if loud {
+            self.speech_windows += 1;
+            self.silence_windows = 0;
+        } else {
+            self.silence_windows += 1;
+        }
+        if self.silence_windows < self.silence_limit && self.speech.len() < self.max_samples {
+            return None;
+        }
+        let speech = std::mem::take(&mut self.speech);
+        let long_enough = self.speech_windows >= self.min_speech_windows;
+        self.clear_utterance();
+        long_enough.then_some(speech)
+    }
+
+    /// True once sustained speech (not a click or a cough) has been heard.
+    pub fn in_speech(&self) -> bool {
+        self.speaking && self.speech_windows >= self.min_speech_windows
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.clear_utterance();
+    }
+
+    fn clear_utterance(&mut self) {
+        self.preroll.clear();
+        self.speech.clear();
+        self.speaking = false;
+        self.speech_windows = 0;
+        self.silence_windows = 0;
+    }
+
+    /// Audio of the utterance in progress, leaving the segmenter idle.
+    pub fn take_speech(&mut self) -> Vec<f32> {
+        let speech = std::mem::take(&mut self.speech);
+        self.reset();
+        speech
+    }
+
+    /// Continues an utterance whose start was heard elsewhere (barge-in).
+    pub fn resume(&mut self, speech: Vec<f32>) {
+        self.reset();
+        self.speech = speech;
+        self.speaking = true;
+        self.speech_windows = self.min_speech_windows;
+    }
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// Linear resampling; good enough for speech.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let len = (samples.len() as u64 * u64::from(to) / u64::from(from)) as usize;
+    let step = f64::from(from) / f64::from(to);
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = samples[index.min(samples.len() - 1)];
+            let b = samples[(index + 1).min(samples.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// 16-bit mono PCM WAV.
+pub fn encode_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+    out
+}
+
+/// Reply text with Markdown that reads badly aloud removed.
+pub fn speakable(text: &str) -> String {
+    static CODE: OnceLock<Regex> = OnceLock::new();
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let code = CODE.get_or_init(|| Regex::new(r"(?s)```.*?```").expect("static regex"));
+    let link = LINK.get_or_init(|| Regex::new(r"\[([^\]]+)\]\([^)]+\)").expect("static regex"));
+    let text = code.replace_all(text, " (code omitted) ");
+    let text = link.replace_all(&text, "$1");
+    text.lines()
+        .map(|line| {
+            line.trim_start_matches(['#', '>', '-', ' '])
+                .replace(['*', '`'], "")
+                .trim()
+                .to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// OpenAI-compatible `/audio/speech` client returning raw samples.
+pub struct SpeechClient {
+    api_url: String,
+    api_key: String,
+    model: String,
+    voice: String,
+    client: reqwest::Client,
+}
+
+impl SpeechClient {
+    pub fn new(api_base: &str, api_key: &str, model: &str, voice: &str) -> Self {
+        Self {
+            api_url: format!("{}/audio/speech", api_base.trim().trim_end_matches('/')),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            voice: voice.to_string(),
+            client: crate::network::client(),
+        }
+    }
+
+    /// Mono samples at `TTS_SAMPLE_RATE`.
+    pub async fn synthesize(&self, text: &str) -> Result<Vec<f32>> {
+        let response = self
+            .client
+            .post(&self.api_url)
+            .bearer_auth(&self.api_key)
+            .json(&json!({
+                "model": self.model,
+                "voice": self.voice,
+                "input": text,
+                "response_format": "pcm",
+            }))
+            .timeout(std::time::Duration::from_secs(60))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("speech request failed ({status}): {body}");
+        }
+        let bytes = response.bytes().await?;
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|pair| f32::from(i16::from_le_bytes([pair[0], pair[1]])) / 32768.0)
+            .collect())
+    }
+}
+
+/// Transcription and speech clients from `voice` config, falling back to the
+/// Groq and OpenAI provider keys.
+pub fn voice_clients(config: &Config) -> Result<(GroqTranscriptionProvider, SpeechClient)> {
+    let voice: &VoiceConfig = &config.voice;
+    let stt_key = [&voice.stt_api_key, &config.providers.groq.api_key]
+        .into_iter()
+        .find(|key| !key.is_empty())
+        .cloned();
+    let stt = GroqTranscriptionProvider::new(stt_key)
+        .with_endpoint(&voice.stt_api_base, &voice.stt_model);
+
+    let tts_key = [&voice.tts_api_key, &config.providers.openai.api_key]
+        .into_iter()
+        .find(|key| !key.is_empty())
+        .ok_or_else(|| {
+            anyhow!("no speech API key: set voice.ttsApiKey or providers.openai.apiKey")
+        })?;
+    let tts_base = if voice.tts_api_base.is_empty() {
+        config
+            .providers
+            .openai
+            .api_base
+            .clone()
+            .unwrap_or_else(|| "https://api.openai.com/v1".to_string())
+    } else {
+        voice.tts_api_base.clone()
+    };
+    let tts = SpeechClient::new(&tts_base, tts_key, &voice.tts_model, &voice.tts_voice);
+    Ok((stt, tts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(samples: usize, level: f32) -> Vec<f32> {
+        (0..samples)
+            .map(|i| if i % 2 == 0 { level } else { -level })
+            .collect()
+    }
+
+    #[test]
+    fn segments_speech_between_silences_and_ignores_blips() {
+        let rate = 1000;
+        let mut segmenter = Segmenter::new(rate, 0.1, 100);
+        assert!(segmenter.push(&tone(500, 0.0)).is_none());
+        // A 40 ms click is too short to count as speech.
+        assert!(segmenter.push(&tone(40, 0.5)).is_none());
+        assert!(segmenter.push(&tone(200, 0.0)).is_none());
+        assert!(!segmenter.in_speech());
+
+        assert!(segmenter.push(&tone(400, 0.5)).is_none());
+        assert!(segmenter.in_speech());
+        let utterance = segmenter.push(&tone(200, 0.0)).expect("utterance");
+        // Pre-roll, the speech itself and the trailing silence window.
+        assert!(utterance.len() >= 400 + 100);
+        assert!(!segmenter.in_speech());
+    }
+
+    #[test]
+    fn encodes_wav_and_resamples() {
+        let wav = encode_wav(&[0.0, 1.0, -1.0], 16_000);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + 6);
+        assert_eq!(i16::from_le_bytes([wav[46], wav[47]]), i16::MAX);
+        assert_eq!(resample(&[0.0; 480], 48_000, 16_000).len(), 160);
+    }
+
+    #[test]
+    fn strips_markdown_for_speech() {
+        let text = "# Title\nSee **this** [link](https://x.y).\n```rust\nfn main() {}\n```\n- item";
+        assert_eq!(
+            speakable(text),
+            "Title\nSee this link.\n(code omitted)\nitem"
+        );
+    }
+}
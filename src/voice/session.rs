@@ -0,0 +1,104 @@
+use super::audio::AudioIo;
+use super::{
+    STT_SAMPLE_RATE, Segmenter, TTS_SAMPLE_RATE, encode_wav, resample, speakable, voice_clients,
+};
+use crate::agent::AgentLoop;
+use crate::config::Config;
+use anyhow::Result;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Listens, answers and speaks until Ctrl+C.
+pub async fn run_voice(config: &Config, agent: &AgentLoop, session: &str) -> Result<()> {
+    let voice = &config.voice;
+    let (stt, tts) = voice_clients(config)?;
+    let (frames_tx, mut frames) = mpsc::unbounded_channel();
+    let audio = AudioIo::open(frames_tx)?;
+    let rate = audio.input_rate;
+    let mut segmenter = Segmenter::new(rate, voice.speech_threshold, voice.silence_ms);
+
+    println!("Listening... speak to nanobot, Ctrl+C to quit.");
+    loop {
+        let utterance = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            utterance = next_utterance(&mut frames, &mut segmenter) => match utterance {
+                Some(utterance) => utterance,
+                None => break,
+            },
+        };
+
+        let wav = encode_wav(
+            &resample(&utterance, rate, STT_SAMPLE_RATE),
+            STT_SAMPLE_RATE,
+        );
+        let text = match stt.transcribe_bytes(wav, "speech.wav".to_string()).await {
+            Ok(text) => text.trim().to_string(),
+            Err(err) => {
+                tracing::warn!("transcription failed: {err:#}");
+                continue;
+            }
+        };
+        if text.is_empty() {
+            continue;
+        }
+        println!("you: {text}");
+
+        let reply = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            reply = agent.process_direct(&text, Some(session), None, None) => reply?,
+        };
+        println!("nanobot: {reply}");
+
+        let speech = speakable(&reply);
+        if !speech.is_empty() {
+            match tts.synthesize(&speech).await {
+                Ok(samples) => audio.play(&samples, TTS_SAMPLE_RATE),
+                Err(err) => tracing::warn!("speech synthesis failed: {err:#}"),
+            }
+        }
+
+        // Microphone audio captured while thinking is mostly noise; drop it.
+        while frames.try_recv().is_ok() {}
+        segmenter.reset();
+
+        let mut barge = Segmenter::new(rate, voice.barge_in_threshold, voice.silence_ms);
+        let mut ticker = tokio::time::interval(Duration::from_millis(50));
+        while audio.is_playing() {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    audio.stop_playback();
+                    return Ok(());
+                }
+                frame = frames.recv() => {
+                    let Some(frame) = frame else { break };
+                    barge.push(&frame);
+                    if barge.in_speech() {
+                        audio.stop_playback();
+                        segmenter.resume(barge.take_speech());
+                        break;
+                    }
+                }
+                _ = ticker.tick() => {}
+            }
+        }
+        if !segmenter.in_speech() {
+            // Let the speaker's tail and echo die out before listening again.
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            while frames.try_recv().is_ok() {}
+        }
+    }
+    audio.stop_playback();
+    Ok(())
+}
+
+async fn next_utterance(
+    frames: &mut mpsc::UnboundedReceiver<Vec<f32>>,
+    segmenter: &mut Segmenter,
+) -> Option<Vec<f32>> {
+    while let Some(frame) = frames.recv().await {
+        if let Some(utterance) = segmenter.push(&frame) {
+            return Some(utterance);
+        }
+    }
+    None
+}
@@ -0,0 +1,328 @@
+use crate::config::{GatewayConfig, WatchdogConfig};
+use crate::listener::{RouteHandler, TrustedProxies, bind_gateway_server};
+use crate::utils::get_data_path;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Response};
+use tracing::{debug, error, info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubsystemStatus {
+    Running,
+    Restarting,
+    CrashLoop,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsystemHealth {
+    pub name: String,
+    pub status: SubsystemStatus,
+    pub restarts: u32,
+    pub consecutive_failures: u32,
+    pub last_failure_at: Option<String>,
+    pub next_retry_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthSnapshot {
+    pub healthy: bool,
+    pub pid: u32,
+    pub started_at: String,
+    pub updated_at: String,
+    pub check_interval_s: u64,
+    pub subsystems: Vec<SubsystemHealth>,
+}
+
+impl HealthSnapshot {
+    /// A snapshot not refreshed within a few check intervals means the
+    /// serving process is gone or wedged.
+    pub fn is_stale(&self, now: DateTime<Local>) -> bool {
+        let Ok(updated) = DateTime::parse_from_rfc3339(&self.updated_at) else {
+            return true;
+        };
+        let limit = (self.check_interval_s * 3).max(30) as i64;
+        now.signed_duration_since(updated).num_seconds() > limit
+    }
+
+    pub fn unhealthy_subsystems(&self) -> Vec<&SubsystemHealth> {
+        self.subsystems
+            .iter()
+            .filter(|s| s.status != SubsystemStatus::Running)
+            .collect()
+    }
+}
+
+/// Exponential restart delay: the first failure restarts immediately, later
+/// ones wait `base * 2^(n-2)` capped at `max`.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self { base, max }
+    }
+
+    pub fn delay(&self, failures: u32) -> Duration {
+        if failures <= 1 {
+            return Duration::ZERO;
+        }
+        let factor = 1u32 << (failures - 2).min(20);
+        self.base.saturating_mul(factor).min(self.max)
+    }
+}
+
+struct Entry {
+    health: SubsystemHealth,
+    running_since: Instant,
+    retry_at: Option<Instant>,
+}
+
+pub struct Watchdog {
+    backoff: Backoff,
+    stable_after: Duration,
+    crash_loop_threshold: u32,
+    check_interval: Duration,
+    started_at: String,
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Watchdog {
+    pub fn new(config: &WatchdogConfig, check_interval: Duration) -> Self {
+        Self {
+            backoff: Backoff::new(
+                Duration::from_secs(config.base_backoff_s.max(1)),
+                Duration::from_secs(config.max_backoff_s.max(1)),
+            ),
+            stable_after: Duration::from_secs(config.stable_after_s),
+            crash_loop_threshold: config.crash_loop_threshold.max(1),
+            check_interval,
+            started_at: Local::now().to_rfc3339(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Records whether `name` is alive and returns true when the caller
+    /// should restart it now.
+    pub fn observe(&mut self, name: &str, alive: bool, now: Instant) -> bool {
+        let entry = self
+            .entries
+            .entry(name.to_string())
+            .or_insert_with(|| Entry {
+                health: SubsystemHealth {
+                    name: name.to_string(),
+                    status: SubsystemStatus::Running,
+                    restarts: 0,
+                    consecutive_failures: 0,
+                    last_failure_at: None,
+                    next_retry_at: None,
+                },
+                running_since: now,
+                retry_at: None,
+            });
+
+        if alive {
+            if entry.health.status != SubsystemStatus::Running {
+                info!(subsystem = name, "subsystem recovered");
+                entry.health.status = SubsystemStatus::Running;
+                entry.health.next_retry_at = None;
+            }
+            if entry.health.consecutive_failures > 0
+                && now.duration_since(entry.running_since) >= self.stable_after
+            {
+                entry.health.consecutive_failures = 0;
+            }
+            return false;
+        }
+
+        if entry.retry_at.is_none() {
+            entry.health.consecutive_failures += 1;
+            entry.health.last_failure_at = Some(Local::now().to_rfc3339());
+            let delay = self.backoff.delay(entry.health.consecutive_failures);
+            entry.retry_at = Some(now + delay);
+            entry.health.next_retry_at = Some(
+                (Local::now() + chrono::Duration::from_std(delay).unwrap_or_default()).to_rfc3339(),
+            );
+            if entry.health.consecutive_failures >= self.crash_loop_threshold {
+                entry.health.status = SubsystemStatus::CrashLoop;
+                error!(
+                    subsystem = name,
+                    failures = entry.health.consecutive_failures,
+                    retry_in_s = delay.as_secs(),
+                    "subsystem is crash-looping"
+                );
+            } else {
+                entry.health.status = SubsystemStatus::Restarting;
+                warn!(
+                    subsystem = name,
+                    failures = entry.health.consecutive_failures,
+                    retry_in_s = delay.as_secs(),
+                    "subsystem exited"
+                );
+            }
+        }
+
+        if entry.retry_at.is_some_and(|at| now >= at) {
+            entry.retry_at = None;
+            entry.running_since = now;
+            entry.health.restarts += 1;
+            return true;
+        }
+        false
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        let subsystems = self
+            .entries
+            .values()
+            .map(|entry| entry.health.clone())
+            .collect::<Vec<_>>();
+        HealthSnapshot {
+            healthy: subsystems
+                .iter()
+                .all(|s| s.status == SubsystemStatus::Running),
+            pid: std::process::id(),
+            started_at: self.started_at.clone(),
+            updated_at: Local::now().to_rfc3339(),
+            check_interval_s: self.check_interval.as_secs(),
+            subsystems,
+        }
+    }
+}
+
+pub fn health_file_path() -> Result<PathBuf> {
+    Ok(get_data_path()?.join("health.json"))
+}
+
+pub fn write_health_snapshot(snapshot: &HealthSnapshot) -> Result<()> {
+    std::fs::write(health_file_path()?, serde_json::to_string_pretty(snapshot)?)?;
+    Ok(())
+}
+
+pub fn read_health_snapshot() -> Option<HealthSnapshot> {
+    let raw = std::fs::read_to_string(health_file_path().ok()?).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+pub type SharedHealth = Arc<RwLock<Option<HealthSnapshot>>>;
+
+/// Serves the gateway HTTP endpoints on a background thread: `GET /healthz`
+/// (200 when healthy, 503 otherwise) when `health` is given, then `routes`
+/// in order.
+pub fn spawn_gateway_http(
+    config: &GatewayConfig,
+    health: Option<SharedHealth>,
+    routes: Vec<RouteHandler>,
+) -> Result<()> {
+    let server = bind_gateway_server(config)?;
+    let proxies = TrustedProxies::new(&config.trusted_proxies);
+    std::thread::spawn(move || {
+        let content_type =
+            Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+        for request in server.incoming_requests() {
+            let path = request.url().split('?').next().unwrap_or("").to_string();
+            if let Some(client) = proxies.request_client_ip(&request) {
+                debug!("{} {path} from {client}", request.method());
+            }
+            if path == "/healthz"
+                && let Some(health) = &health
+            {
+                let snapshot = health.read().ok().and_then(|guard| guard.clone());
+                let (code, body) = match snapshot {
+                    Some(snapshot) => (
+                        if snapshot.healthy { 200 } else { 503 },
+                        serde_json::to_string(&snapshot).unwrap_or_default(),
+                    ),
+                    None => (503, r#"{"healthy":false}"#.to_string()),
+                };
+                let _ = request.respond(
+                    Response::from_string(body)
+                        .with_status_code(code)
+                        .with_header(content_type.clone()),
+                );
+                continue;
+            }
+            let unhandled = routes
+                .iter()
+                .try_fold(request, |request, route| route(request));
+            if let Some(request) = unhandled {
+                let _ = request.respond(Response::from_string("not found").with_status_code(404));
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watchdog() -> Watchdog {
+        let config = WatchdogConfig {
+            base_backoff_s: 2,
+            max_backoff_s: 10,
+            stable_after_s: 60,
+            crash_loop_threshold: 3,
+        };
+        Watchdog::new(&config, Duration::from_secs(5))
+    }
+
+    #[test]
+    fn backoff_doubles_and_caps() {
+        let backoff = Backoff::new(Duration::from_secs(2), Duration::from_secs(10));
+        let delays = (1..=5)
+            .map(|n| backoff.delay(n).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![0, 2, 4, 8, 10]);
+    }
+
+    #[test]
+    fn restarts_with_backoff_and_flags_crash_loop() {
+        let mut dog = watchdog();
+        let t0 = Instant::now();
+        assert!(!dog.observe("agent", true, t0));
+        // First failure restarts immediately.
+        assert!(dog.observe("agent", false, t0));
+        // Second failure waits 2s.
+        assert!(!dog.observe("agent", false, t0 + Duration::from_secs(1)));
+        assert!(!dog.observe("agent", false, t0 + Duration::from_secs(2)));
+        assert!(dog.observe("agent", false, t0 + Duration::from_secs(3)));
+        // Third failure crosses the crash-loop threshold.
+        assert!(!dog.observe("agent", false, t0 + Duration::from_secs(4)));
+        let snapshot = dog.snapshot();
+        assert!(!snapshot.healthy);
+        assert_eq!(snapshot.subsystems[0].status, SubsystemStatus::CrashLoop);
+        assert_eq!(snapshot.subsystems[0].restarts, 2);
+    }
+
+    #[test]
+    fn stable_run_resets_failures() {
+        let mut dog = watchdog();
+        let t0 = Instant::now();
+        assert!(dog.observe("cron", false, t0));
+        assert!(!dog.observe("cron", true, t0 + Duration::from_secs(5)));
+        assert_eq!(dog.snapshot().subsystems[0].consecutive_failures, 1);
+        assert!(!dog.observe("cron", true, t0 + Duration::from_secs(61)));
+        let snapshot = dog.snapshot();
+        assert!(snapshot.healthy);
+        assert_eq!(snapshot.subsystems[0].consecutive_failures, 0);
+    }
+
+    #[test]
+    fn stale_snapshot_is_detected() {
+        let mut snapshot = watchdog().snapshot();
+        assert!(!snapshot.is_stale(Local::now()));
+        snapshot.updated_at = (Local::now() - chrono::Duration::seconds(120)).to_rfc3339();
+        assert!(snapshot.is_stale(Local::now()));
+    }
+}
@@ -195,8 +195,31 @@ function mapHealthHint(hint) {
   return hint;
 }
 
+function authHeaders() {
+  const key = window.localStorage.getItem("nanobotApiKey");
+  return key ? { Authorization: `Bearer ${key}` } : {};
+}
+
+let apiKeyPrompted = false;
+
+// On the first 401 ask for an API key, remember it, and retry.
+async function apiFetch(url, options = {}) {
+  const send = () =>
+    fetch(url, { ...options, headers: { ...(options.headers || {}), ...authHeaders() } });
+  let response = await send();
+  if (response.status === 401 && !apiKeyPrompted) {
+    apiKeyPrompted = true;
+    const key = window.prompt("API key");
+    if (key) {
+      window.localStorage.setItem("nanobotApiKey", key.trim());
+      response = await send();
+    }
+  }
+  return response;
+}
+
 async function fetchState() {
-  const response = await fetch("/api/state", { cache: "no-store" });
+  const response = await apiFetch("/api/state", { cache: "no-store" });
   if (!response.ok) {
     throw new Error(`HTTP ${response.status}`);
   }
@@ -204,7 +227,7 @@ async function fetchState() {
 }
 
 async function postChat(message, session) {
-  const response = await fetch("/api/chat", {
+  const response = await apiFetch("/api/chat", {
     method: "POST",
     headers: { "Content-Type": "application/json" },
     body: JSON.stringify({
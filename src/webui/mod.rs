@@ -1,12 +1,16 @@
 use crate::VERSION;
-use crate::agent::AgentLoop;
+use crate::agent::events::AgentEvent;
+use crate::agent::{AgentLoopBuilder, error_reply};
+use crate::apikeys::{ApiKeyGuard, apikeys_path};
 use crate::config::{load_config, providers_status};
 use crate::health::collect_health;
+use crate::listener::{into_event_stream, write_event};
 use crate::pairing::list_pending;
-use crate::providers::base::LLMProvider;
-use crate::providers::litellm::LiteLLMProvider;
+use crate::presence;
+use crate::providers::build_provider;
 use crate::session::SessionManager;
 use crate::utils::get_data_path;
+use crate::vault::Vault;
 use anyhow::Result;
 use chrono::Local;
 use serde::Deserialize;
@@ -26,6 +30,8 @@ struct ChatPayload {
     session: Option<String>,
     channel: Option<String>,
     chat_id: Option<String>,
+    #[serde(default)]
+    stream: bool,
 }
 
 struct ChatRequest {
@@ -33,6 +39,7 @@ struct ChatRequest {
     session: Option<String>,
     channel: Option<String>,
     chat_id: Option<String>,
+    events: Option<mpsc::Sender<AgentEvent>>,
     reply_tx: mpsc::Sender<Result<String>>,
 }
 
@@ -41,7 +48,7 @@ struct ChatWorker {
 }
 
 impl ChatWorker {
-    fn new() -> Self {
+    fn new(vault: Option<Arc<Vault>>) -> Self {
         let (tx, rx) = mpsc::channel::<ChatRequest>();
         std::thread::spawn(move || {
             let config = load_config(None).unwrap_or_default();
@@ -50,8 +57,9 @@ impl ChatWorker {
             let is_bedrock = normalized_model.starts_with("bedrock/");
             let api_key = config.get_api_key(Some(&model));
             if api_key.is_none() && !is_bedrock {
-                let err = "No API key configured. Set providers.*.apiKey in ~/.nanobot/config.json."
-                    .to_string();
+                let err =
+                    "No API key configured. Set providers.*.apiKey in ~/.nanobot/config.json."
+                        .to_string();
                 while let Ok(req) = rx.recv() {
                     let _ = req.reply_tx.send(Err(anyhow::anyhow!(err.clone())));
                 }
@@ -74,35 +82,34 @@ impl ChatWorker {
             };
 
             let bus = Arc::new(crate::bus::MessageBus::new(1024));
-            let provider = build_provider(
+            let provider = crate::router::wrap_provider(
+                build_provider(
+                    &config,
+                    &model,
+                    api_key.unwrap_or_else(|| "dummy".to_string()),
+                ),
                 &config,
-                &model,
-                api_key.unwrap_or_else(|| "dummy".to_string()),
+                |fast| {
+                    let key = config.get_api_key(Some(fast)).unwrap_or_default();
+                    build_provider(&config, fast, key)
+                },
             );
             let session_manager = match SessionManager::new() {
-                Ok(m) => Arc::new(m),
+                Ok(m) => Arc::new(m.with_vault(vault)),
                 Err(err) => {
                     while let Ok(req) = rx.recv() {
-                        let _ = req
-                            .reply_tx
-                            .send(Err(anyhow::anyhow!("failed to init session manager: {err}")));
+                        let _ = req.reply_tx.send(Err(anyhow::anyhow!(
+                            "failed to init session manager: {err}"
+                        )));
                     }
                     return;
                 }
             };
-            let agent = match AgentLoop::new(
-                bus,
-                provider,
-                config.workspace_path(),
-                Some(model),
-                config.agents.defaults.max_tool_iterations,
-                config.agents.defaults.memory_window,
-                config.tools.web.search.clone(),
-                config.tools.exec.timeout,
-                config.tools.restrict_to_workspace,
-                None,
-                Some(session_manager),
-            ) {
+            let agent = match AgentLoopBuilder::from_config(provider, &config)
+                .bus(bus)
+                .sessions(session_manager)
+                .build()
+            {
                 Ok(agent) => Arc::new(agent),
                 Err(err) => {
                     while let Ok(req) = rx.recv() {
@@ -116,25 +123,52 @@ impl ChatWorker {
 
             while let Ok(req) = rx.recv() {
                 let session_key = req.session.as_deref().or(Some("webui:default"));
-                let answer = runtime.block_on(agent.process_direct(
-                    &req.message,
-                    session_key,
-                    req.channel.as_deref(),
-                    req.chat_id.as_deref(),
-                ));
+                presence::touch(
+                    &config.presence,
+                    presence::DESKTOP,
+                    session_key.unwrap_or_default(),
+                );
+                let answer = match req.events {
+                    Some(events) => runtime.block_on(async {
+                        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+                        let forward = async {
+                            while let Some(event) = rx.recv().await {
+                                let _ = events.send(event);
+                            }
+                        };
+                        let (answer, ()) = tokio::join!(
+                            agent.process_direct_streaming(
+                                &req.message,
+                                session_key,
+                                req.channel.as_deref(),
+                                req.chat_id.as_deref(),
+                                tx,
+                            ),
+                            forward
+                        );
+                        answer
+                    }),
+                    None => runtime.block_on(agent.process_direct(
+                        &req.message,
+                        session_key,
+                        req.channel.as_deref(),
+                        req.chat_id.as_deref(),
+                    )),
+                };
                 let _ = req.reply_tx.send(answer);
             }
         });
         Self { tx }
     }
 
-    fn chat(
+    fn submit(
         &self,
         message: String,
         session: Option<String>,
         channel: Option<String>,
         chat_id: Option<String>,
-    ) -> Result<String> {
+        events: Option<mpsc::Sender<AgentEvent>>,
+    ) -> Result<mpsc::Receiver<Result<String>>> {
         let (reply_tx, reply_rx) = mpsc::channel();
         self.tx
             .send(ChatRequest {
@@ -142,10 +176,21 @@ impl ChatWorker {
                 session,
                 channel,
                 chat_id,
+                events,
                 reply_tx,
             })
             .map_err(|err| anyhow::anyhow!("chat worker unavailable: {err}"))?;
-        reply_rx
+        Ok(reply_rx)
+    }
+
+    fn chat(
+        &self,
+        message: String,
+        session: Option<String>,
+        channel: Option<String>,
+        chat_id: Option<String>,
+    ) -> Result<String> {
+        self.submit(message, session, channel, chat_id, None)?
             .recv()
             .map_err(|err| anyhow::anyhow!("chat worker response error: {err}"))?
     }
@@ -153,25 +198,7 @@ impl ChatWorker {
 
 struct WebUiContext {
     chat: ChatWorker,
-}
-
-fn build_provider(
-    config: &crate::config::Config,
-    model: &str,
-    api_key: String,
-) -> Arc<dyn LLMProvider> {
-    let api_base = config.get_api_base(Some(model));
-    let extra_headers = config
-        .get_provider(Some(model))
-        .and_then(|p| p.extra_headers.clone());
-    let provider_name = config.get_provider_name(Some(model));
-    Arc::new(LiteLLMProvider::new(
-        api_key,
-        api_base,
-        model.to_string(),
-        extra_headers,
-        provider_name.as_deref(),
-    ))
+    guard: ApiKeyGuard,
 }
 
 fn content_type_header(value: &str) -> Option<Header> {
@@ -186,6 +213,66 @@ fn respond(req: Request, status: u16, content_type: &str, body: String) {
     let _ = req.respond(response);
 }
 
+fn wants_event_stream(req: &Request, payload: &ChatPayload) -> bool {
+    payload.stream
+        || req
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Accept") && h.value.as_str().contains("text/event-stream"))
+}
+
+/// Answers a chat as `text/event-stream`: `delta`, `tool_start` and
+/// `tool_end` events while the turn runs, then `done` or `error`.
+fn stream_chat(
+    req: Request,
+    ctx: &WebUiContext,
+    message: String,
+    session: Option<String>,
+    channel: Option<String>,
+    chat_id: Option<String>,
+) {
+    let (events_tx, events_rx) = mpsc::channel();
+    let reply_rx = match ctx
+        .chat
+        .submit(message, session, channel, chat_id, Some(events_tx))
+    {
+        Ok(rx) => rx,
+        Err(err) => {
+            respond(
+                req,
+                500,
+                "application/json; charset=utf-8",
+                json!({ "ok": false, "error": err.to_string() }).to_string(),
+            );
+            return;
+        }
+    };
+    let mut writer = into_event_stream(req);
+    // Keep draining after a disconnect so the worker is never blocked.
+    for event in events_rx {
+        if let Some(w) = writer.as_mut() {
+            let data = serde_json::to_value(&event).unwrap_or(Value::Null);
+            if !write_event(w.as_mut(), event.name(), &data) {
+                writer = None;
+            }
+        }
+    }
+    let (name, data) = match reply_rx.recv() {
+        Ok(Ok(answer)) => ("done", json!({ "ok": true, "response": answer })),
+        Ok(Err(err)) => (
+            "error",
+            json!({ "ok": false, "error": error_reply(&err, "webui") }),
+        ),
+        Err(err) => (
+            "error",
+            json!({ "ok": false, "error": format!("chat worker response error: {err}") }),
+        ),
+    };
+    if let Some(w) = writer.as_mut() {
+        write_event(w.as_mut(), name, &data);
+    }
+}
+
 fn read_cron_jobs() -> Vec<Value> {
     let path = match get_data_path() {
         Ok(p) => p.join("cron").join("jobs.json"),
@@ -272,10 +359,28 @@ fn read_request_body(req: &mut Request) -> String {
 fn handle_request(mut req: Request, ctx: &WebUiContext) {
     let url = req.url().to_string();
     let method = req.method().clone();
+    let api_key = if url.starts_with("/api/") {
+        match ctx.guard.check(&req) {
+            Ok(key) => key,
+            Err(err) => {
+                respond(
+                    req,
+                    err.status_code(),
+                    "application/json; charset=utf-8",
+                    json!({ "ok": false, "error": err.to_string() }).to_string(),
+                );
+                return;
+            }
+        }
+    } else {
+        None
+    };
 
     match (method, url.as_str()) {
         (Method::Get, "/") => respond(req, 200, "text/html; charset=utf-8", INDEX_HTML.to_string()),
-        (Method::Get, "/app.css") => respond(req, 200, "text/css; charset=utf-8", APP_CSS.to_string()),
+        (Method::Get, "/app.css") => {
+            respond(req, 200, "text/css; charset=utf-8", APP_CSS.to_string())
+        }
         (Method::Get, "/app.js") => respond(
             req,
             200,
@@ -318,12 +423,20 @@ fn handle_request(mut req: Request, ctx: &WebUiContext) {
                 );
                 return;
             }
-            match ctx.chat.chat(
-                payload.message,
-                payload.session,
-                payload.channel,
-                payload.chat_id,
-            ) {
+            let stream = wants_event_stream(&req, &payload);
+            // Profile-bound keys are confined to their own sessions.
+            let (session, channel, chat_id) = match api_key
+                .as_ref()
+                .and_then(|key| key.scoped_session(payload.session.as_deref()))
+            {
+                Some(session) => (Some(session), None, None),
+                None => (payload.session, payload.channel, payload.chat_id),
+            };
+            if stream {
+                stream_chat(req, ctx, payload.message, session, channel, chat_id);
+                return;
+            }
+            match ctx.chat.chat(payload.message, session, channel, chat_id) {
                 Ok(answer) => {
                     respond(
                         req,
@@ -374,8 +487,18 @@ fn handle_request(mut req: Request, ctx: &WebUiContext) {
 pub fn run_webui_server(host: &str, port: u16) -> Result<()> {
     let addr = format!("{host}:{port}");
     let server = Server::http(&addr).map_err(|err| anyhow::anyhow!(err.to_string()))?;
+    let config = load_config(None).unwrap_or_default();
+    let vault = config
+        .gateway
+        .encrypt_user_data
+        .then(|| Arc::new(Vault::new()));
     let ctx = WebUiContext {
-        chat: ChatWorker::new(),
+        chat: ChatWorker::new(vault.clone()),
+        guard: ApiKeyGuard::new(
+            apikeys_path(&config.workspace_path())?,
+            config.gateway.require_api_key,
+        )
+        .with_vault(vault),
     };
     println!("WebUI running at http://{addr}");
     for req in server.incoming_requests() {
@@ -0,0 +1,459 @@
+//! Multi-step pipelines defined in `<workspace>/workflows/*.yaml`.
+//!
+//! A workflow is a list of steps: prompts for the agent, direct tool calls,
+//! human approvals, questions for the user and `forEach` loops, each
+//! optionally guarded by `when`.
+//! Step outputs feed later steps through `{{...}}` templates. Runs are
+//! executed by [`WorkflowRunner`] and can be started from cron, a webhook
+//! or the `run_workflow` tool.
+
+mod runner;
+mod webhook;
+
+pub use runner::{RunStatus, StepRecord, WorkflowRun, WorkflowRunner};
+pub use webhook::WorkflowWebhook;
+
+use crate::cron::{CronPayload, CronSchedule, CronService};
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Cron payload kind for jobs that start a workflow (message = workflow name).
+pub const CRON_PAYLOAD_KIND: &str = "workflow";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Workflow {
+    pub name: String,
+    pub description: String,
+    pub trigger: WorkflowTrigger,
+    /// Where approvals are asked and the final output is sent.
+    pub notify: Option<NotifyTarget>,
+    /// Default retry count for steps that do not set their own.
+    pub retries: u32,
+    /// Template for the run's final output; defaults to the last step output.
+    pub output: Option<String>,
+    /// Quality tier (`router.tiers`) prompt steps run on; defaults to
+    /// `router.backgroundTier`.
+    pub tier: Option<String>,
+    pub steps: Vec<WorkflowStep>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkflowTrigger {
+    /// Cron expression (with seconds, as for `nanobot cron add`).
+    pub cron: Option<String>,
+    pub every_seconds: Option<i64>,
+    /// Accept `POST /workflows/<name>` on the gateway.
+    pub webhook: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NotifyTarget {
+    pub channel: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkflowStep {
+    pub id: String,
+    pub prompt: Option<String>,
+    pub tool: Option<String>,
+    pub args: Map<String, Value>,
+    pub approval: Option<String>,
+    /// Question sent to the `notify` chat; the user's reply is the output.
+    pub ask: Option<String>,
+    /// How long an `ask` step waits for the reply.
+    pub timeout_s: Option<u64>,
+    pub for_each: Option<String>,
+    /// Body of a `forEach` loop.
+    pub steps: Vec<WorkflowStep>,
+    pub when: Option<Condition>,
+    pub retries: Option<u32>,
+    pub retry_delay_s: Option<u64>,
+    /// Record the error as the step output and keep going.
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    Prompt,
+    Tool,
+    Approval,
+    Ask,
+    ForEach,
+}
+
+impl WorkflowStep {
+    pub fn kind(&self) -> Result<StepKind> {
+        let kinds = [
+            (self.prompt.is_some(), StepKind::Prompt),
+            (self.tool.is_some(), StepKind::Tool),
+            (self.approval.is_some(), StepKind::Approval),
+            (self.ask.is_some(), StepKind::Ask),
+            (self.for_each.is_some(), StepKind::ForEach),
+        ];
+        let mut set = kinds.iter().filter(|(present, _)| *present);
+        match (set.next(), set.next()) {
+            (Some((_, kind)), None) => Ok(*kind),
+            (None, _) => bail!(
+                "step '{}' needs one of prompt, tool, approval, ask or forEach",
+                self.id
+            ),
+            (Some(_), Some(_)) => bail!(
+                "step '{}' sets more than one of prompt, tool, approval, ask and forEach",
+                self.id
+            ),
+        }
+    }
+}
+
+/// `when:` either a template that must render truthy, or a comparison.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Condition {
+    Truthy(String),
+    Compare {
+        value: String,
+        #[serde(default)]
+        equals: Option<String>,
+        #[serde(default)]
+        contains: Option<String>,
+        #[serde(default)]
+        not: bool,
+    },
+}
+
+impl Condition {
+    pub fn holds(&self, scope: &Value) -> bool {
+        match self {
+            Condition::Truthy(template) => is_truthy(&render(template, scope)),
+            Condition::Compare {
+                value,
+                equals,
+                contains,
+                not,
+            } => {
+                let value = render(value, scope);
+                let value = value.trim();
+                let holds = match (equals, contains) {
+                    (Some(expected), _) => value == render(expected, scope).trim(),
+                    (None, Some(needle)) => value.contains(render(needle, scope).trim()),
+                    (None, None) => is_truthy(value),
+                };
+                holds != *not
+            }
+        }
+    }
+}
+
+fn is_truthy(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    !matches!(value.as_str(), "" | "false" | "no" | "0" | "null")
+}
+
+impl Workflow {
+    pub fn validate(&mut self) -> Result<()> {
+        if self.steps.is_empty() {
+            bail!("workflow '{}' has no steps", self.name);
+        }
+        if let Some(expr) = &self.trigger.cron {
+            cron::Schedule::from_str(expr)
+                .map_err(|err| anyhow!("invalid cron expression '{expr}': {err}"))?;
+        }
+        assign_step_ids(&mut self.steps, "step")
+    }
+
+    /// Cron schedule for the workflow's timer trigger, if it has one.
+    pub fn schedule(&self) -> Option<CronSchedule> {
+        if let Some(expr) = &self.trigger.cron {
+            return Some(CronSchedule {
+                kind: "cron".to_string(),
+                expr: Some(expr.clone()),
+                tz: Some("local".to_string()),
+                ..Default::default()
+            });
+        }
+        self.trigger
+            .every_seconds
+            .filter(|seconds| *seconds > 0)
+            .map(|seconds| CronSchedule {
+                kind: "every".to_string(),
+                every_ms: Some(seconds * 1000),
+                ..Default::default()
+            })
+    }
+}
+
+fn assign_step_ids(steps: &mut [WorkflowStep], prefix: &str) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    for (index, step) in steps.iter_mut().enumerate() {
+        if step.id.trim().is_empty() {
+            step.id = format!("{prefix}{}", index + 1);
+        }
+        if !seen.insert(step.id.clone()) {
+            bail!("duplicate step id '{}'", step.id);
+        }
+        if step.kind()? == StepKind::ForEach {
+            if step.steps.is_empty() {
+                bail!("forEach step '{}' has no steps", step.id);
+            }
+            let prefix = format!("{}_", step.id);
+            assign_step_ids(&mut step.steps, &prefix)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn workflows_dir(workspace: &Path) -> PathBuf {
+    workspace.join("workflows")
+}
+
+pub fn load_workflow(path: &Path) -> Result<Workflow> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let mut workflow: Workflow = serde_yaml::from_str(&text)
+        .with_context(|| format!("invalid workflow {}", path.display()))?;
+    if workflow.name.trim().is_empty() {
+        workflow.name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "workflow".to_string());
+    }
+    workflow
+        .validate()
+        .with_context(|| format!("invalid workflow {}", path.display()))?;
+    Ok(workflow)
+}
+
+/// Every workflow file in the workspace; broken files are logged and skipped.
+pub fn load_workflows(workspace: &Path) -> Vec<Workflow> {
+    let Ok(entries) = std::fs::read_dir(workflows_dir(workspace)) else {
+        return Vec::new();
+    };
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml")
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| match load_workflow(path) {
+            Ok(workflow) => Some(workflow),
+            Err(err) => {
+                tracing::warn!("skipping workflow: {err:#}");
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn find_workflow(workspace: &Path, name: &str) -> Result<Workflow> {
+    let dir = workflows_dir(workspace);
+    for ext in ["yaml", "yml"] {
+        let path = dir.join(format!("{name}.{ext}"));
+        if path.is_file() {
+            return load_workflow(&path);
+        }
+    }
+    load_workflows(workspace)
+        .into_iter()
+        .find(|workflow| workflow.name == name)
+        .ok_or_else(|| anyhow!("workflow '{name}' not found in {}", dir.display()))
+}
+
+/// Makes the cron jobs of type `workflow` match the workflows' timer triggers.
+pub async fn sync_cron_triggers(cron: &CronService, workflows: &[Workflow]) -> Result<()> {
+    let jobs = cron
+        .list_jobs(true)
+        .await
+        .into_iter()
+        .filter(|job| job.payload.kind == CRON_PAYLOAD_KIND)
+        .collect::<Vec<_>>();
+    for job in &jobs {
+        let current = workflows
+            .iter()
+            .find(|workflow| workflow.name == job.payload.message)
+            .and_then(Workflow::schedule);
+        let unchanged = current.is_some_and(|schedule| {
+            schedule.kind == job.schedule.kind
+                && schedule.expr == job.schedule.expr
+                && schedule.every_ms == job.schedule.every_ms
+        });
+        if !unchanged {
+            cron.remove_job(&job.id).await?;
+        }
+    }
+    for workflow in workflows {
+        let Some(schedule) = workflow.schedule() else {
+            continue;
+        };
+        let scheduled = jobs.iter().any(|job| {
+            job.payload.message == workflow.name
+                && job.schedule.kind == schedule.kind
+                && job.schedule.expr == schedule.expr
+                && job.schedule.every_ms == schedule.every_ms
+        });
+        if !scheduled {
+            let payload = CronPayload {
+                kind: CRON_PAYLOAD_KIND.to_string(),
+                message: workflow.name.clone(),
+                ..Default::default()
+            };
+            cron.add_job_with_payload(
+                format!("workflow:{}", workflow.name),
+                schedule,
+                payload,
+                false,
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Looks up a dotted path (`steps.fetch.title`) in the template scope.
+/// String values holding JSON are parsed so fields of tool output resolve.
+fn lookup(scope: &Value, path: &str) -> Option<Value> {
+    let mut current = scope.clone();
+    for part in path.split('.').filter(|part| !part.is_empty()) {
+        if let Value::String(text) = &current
+            && let Ok(parsed) = serde_json::from_str::<Value>(text)
+        {
+            current = parsed;
+        }
+        current = match &current {
+            Value::Object(map) => map.get(part)?.clone(),
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?.clone(),
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Replaces `{{path}}` placeholders; unknown paths render empty.
+pub fn render(template: &str, scope: &Value) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let path = rest[start + 2..start + 2 + len].trim();
+        if let Some(value) = lookup(scope, path) {
+            out.push_str(&value_text(&value));
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Like `render`, but a template that is a single placeholder keeps the
+/// value's JSON type (so tool arguments and loop lists stay structured).
+pub fn render_value(template: &Value, scope: &Value) -> Value {
+    match template {
+        Value::String(text) => {
+            let trimmed = text.trim();
+            if let Some(path) = trimmed
+                .strip_prefix("{{")
+                .and_then(|inner| inner.strip_suffix("}}"))
+                .filter(|inner| !inner.contains("{{") && !inner.contains("}}"))
+            {
+                return lookup(scope, path.trim()).unwrap_or(Value::Null);
+            }
+            Value::String(render(text, scope))
+        }
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|item| render_value(item, scope)).collect())
+        }
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| (key.clone(), render_value(value, scope)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_and_validates_definitions() {
+        let mut workflow: Workflow = serde_yaml::from_str(
+            r#"
+name: digest
+trigger: { cron: "0 0 9 * * *", webhook: true }
+steps:
+  - tool: web_fetch
+    args: { url: "{{input.url}}" }
+  - id: pages
+    forEach: "{{input.urls}}"
+    steps:
+      - prompt: "Summarize {{item}}"
+  - approval: "Send it?"
+    when: { value: "{{steps.step1}}", contains: "ok" }
+"#,
+        )
+        .expect("yaml");
+        workflow.validate().expect("valid");
+        let ids = workflow
+            .steps
+            .iter()
+            .map(|step| step.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["step1", "pages", "step3"]);
+        assert_eq!(workflow.steps[1].steps[0].id, "pages_1");
+        assert_eq!(
+            workflow.schedule().and_then(|s| s.expr).as_deref(),
+            Some("0 0 9 * * *")
+        );
+
+        let mut broken: Workflow =
+            serde_yaml::from_str("steps:\n  - prompt: a\n    tool: exec\n").expect("yaml");
+        assert!(broken.validate().is_err());
+    }
+
+    #[test]
+    fn renders_templates_and_conditions() {
+        let scope = json!({
+            "input": { "urls": ["a", "b"] },
+            "steps": { "fetch": "{\"status\": \"ok\", \"count\": 2}" },
+        });
+        assert_eq!(
+            render(
+                "status={{steps.fetch.status}} n={{ steps.fetch.count }}{{missing}}",
+                &scope
+            ),
+            "status=ok n=2"
+        );
+        assert_eq!(
+            render_value(&json!({"list": "{{input.urls}}"}), &scope),
+            json!({"list": ["a", "b"]})
+        );
+        let condition: Condition =
+            serde_yaml::from_str("{ value: '{{steps.fetch.status}}', equals: ok }").expect("yaml");
+        assert!(condition.holds(&scope));
+        assert!(!Condition::Truthy("{{steps.missing}}".to_string()).holds(&scope));
+    }
+}
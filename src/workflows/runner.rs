@@ -0,0 +1,692 @@
+use super::{StepKind, Workflow, WorkflowStep, find_workflow, render, render_value};
+use crate::agent::AgentLoop;
+use crate::agent::approval::{ApprovalPrompt, ApprovalRequest};
+use crate::bus::{MessageBus, OutboundMessage};
+use crate::router;
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Local};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const DEFAULT_RETRY_DELAY_S: u64 = 5;
+const MAX_LOOP_ITEMS: usize = 100;
+/// How long an `ask` step waits for the user by default.
+const DEFAULT_ASK_TIMEOUT_S: u64 = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// The process stopped while the run was in progress; it can be resumed.
+    Interrupted,
+}
+
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Running => "running",
+            RunStatus::Succeeded => "succeeded",
+            RunStatus::Failed => "failed",
+            RunStatus::Cancelled => "cancelled",
+            RunStatus::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// A finished top-level step; resumed runs skip these.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepRecord {
+    pub id: String,
+    /// ok | skipped | failed
+    pub status: String,
+    pub output: Value,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowRun {
+    pub id: String,
+    pub workflow: String,
+    /// manual | cron | webhook | tool
+    pub trigger: String,
+    pub status: RunStatus,
+    pub input: Value,
+    pub steps: Vec<StepRecord>,
+    pub current_step: Option<String>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub started_at: DateTime<Local>,
+    pub finished_at: Option<DateTime<Local>>,
+}
+
+impl WorkflowRun {
+    pub fn summary(&self) -> String {
+        match (self.status, &self.output, &self.error) {
+            (RunStatus::Succeeded, Some(output), _) => output.clone(),
+            (status, _, Some(error)) => format!(
+                "Workflow '{}' {} (run {}): {error}",
+                self.workflow,
+                status.as_str(),
+                self.id
+            ),
+            (status, _, None) => format!(
+                "Workflow '{}' {} (run {})",
+                self.workflow,
+                status.as_str(),
+                self.id
+            ),
+        }
+    }
+}
+
+enum Outcome {
+    Done {
+        status: &'static str,
+        output: Value,
+    },
+    /// An approval was denied; the run stops here.
+    Denied,
+}
+
+pub struct WorkflowRunner {
+    workspace: PathBuf,
+    runs_dir: PathBuf,
+    agent: OnceLock<Weak<AgentLoop>>,
+    bus: Option<Arc<MessageBus>>,
+    approval: Option<Arc<dyn ApprovalPrompt>>,
+    /// Workflows with a run in progress; a workflow runs once at a time.
+    active: Mutex<HashSet<String>>,
+}
+
+impl WorkflowRunner {
+    pub fn new(workspace: PathBuf, runs_dir: PathBuf) -> Self {
+        Self {
+            workspace,
+            runs_dir,
+            agent: OnceLock::new(),
+            bus: None,
+            approval: None,
+            active: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Final outputs go to the workflow's `notify` target through `bus`.
+    pub fn with_bus(mut self, bus: Arc<MessageBus>) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn with_approval(mut self, prompt: Arc<dyn ApprovalPrompt>) -> Self {
+        self.approval = Some(prompt);
+        self
+    }
+
+    pub fn attach(&self, agent: &Arc<AgentLoop>) {
+        let _ = self.agent.set(Arc::downgrade(agent));
+    }
+
+    pub fn workspace(&self) -> &PathBuf {
+        &self.workspace
+    }
+
+    fn agent(&self) -> Result<Arc<AgentLoop>> {
+        self.agent
+            .get()
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| anyhow!("workflow runner is not attached to an agent"))
+    }
+
+    fn run_path(&self, id: &str) -> PathBuf {
+        self.runs_dir.join(format!("{id}.json"))
+    }
+
+    fn save(&self, run: &WorkflowRun) -> Result<()> {
+        std::fs::create_dir_all(&self.runs_dir)
+            .with_context(|| format!("failed to create {}", self.runs_dir.display()))?;
+        let path = self.run_path(&run.id);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(run)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    pub fn load_run(&self, id: &str) -> Result<WorkflowRun> {
+        let path = self.run_path(id);
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("workflow run '{id}' not found"))?;
+        serde_json::from_str(&text).with_context(|| format!("invalid run file {}", path.display()))
+    }
+
+    /// Most recent runs first.
+    pub fn list_runs(&self, limit: usize) -> Vec<WorkflowRun> {
+        let Ok(entries) = std::fs::read_dir(&self.runs_dir) else {
+            return Vec::new();
+        };
+        let mut runs = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+            .filter_map(|text| serde_json::from_str::<WorkflowRun>(&text).ok())
+            .collect::<Vec<_>>();
+        runs.sort_by_key(|run| std::cmp::Reverse(run.started_at));
+        runs.truncate(limit);
+        runs
+    }
+
+    /// Marks runs left `running` by a previous process as `interrupted`.
+    pub fn mark_interrupted(&self) -> usize {
+        let mut count = 0;
+        for mut run in self.list_runs(usize::MAX) {
+            if run.status == RunStatus::Running {
+                run.status = RunStatus::Interrupted;
+                if self.save(&run).is_ok() {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    fn claim(&self, workflow: &str) -> Result<()> {
+        let mut active = self
+            .active
+            .lock()
+            .map_err(|_| anyhow!("workflow state lock poisoned"))?;
+        if !active.insert(workflow.to_string()) {
+            bail!("workflow '{workflow}' is already running");
+        }
+        Ok(())
+    }
+
+    fn release(&self, workflow: &str) {
+        if let Ok(mut active) = self.active.lock() {
+            active.remove(workflow);
+        }
+    }
+
+    /// Creates and persists a run without executing it; pair with `execute`.
+    pub fn start(&self, name: &str, input: Value, trigger: &str) -> Result<WorkflowRun> {
+        let workflow = find_workflow(&self.workspace, name)?;
+        self.claim(&workflow.name)?;
+        let id = uuid::Uuid::new_v4().simple().to_string()[..12].to_string();
+        let run = WorkflowRun {
+            id,
+            workflow: workflow.name,
+            trigger: trigger.to_string(),
+            status: RunStatus::Running,
+            input,
+            steps: Vec::new(),
+            current_step: None,
+            output: None,
+            error: None,
+            started_at: Local::now(),
+            finished_at: None,
+        };
+        if let Err(err) = self.save(&run) {
+            self.release(&run.workflow);
+            return Err(err);
+        }
+        Ok(run)
+    }
+
+    pub async fn run(&self, name: &str, input: Value, trigger: &str) -> Result<WorkflowRun> {
+        let run = self.start(name, input, trigger)?;
+        Ok(self.execute(run).await)
+    }
+
+    /// Continues an interrupted or failed run after its last finished step.
+    pub async fn resume(&self, id: &str) -> Result<WorkflowRun> {
+        let mut run = self.load_run(id)?;
+        if run.status == RunStatus::Succeeded {
+            bail!("run '{id}' already succeeded");
+        }
+        self.claim(&run.workflow)?;
+        run.status = RunStatus::Running;
+        run.error = None;
+        run.finished_at = None;
+        if let Err(err) = self.save(&run) {
+            self.release(&run.workflow);
+            return Err(err);
+        }
+        Ok(self.execute(run).await)
+    }
+
+    /// Runs the remaining steps of a started run and releases the workflow.
+    pub async fn execute(&self, mut run: WorkflowRun) -> WorkflowRun {
+        info!(workflow = %run.workflow, run = %run.id, trigger = %run.trigger, "workflow run started");
+        let result = self.execute_steps(&mut run).await;
+        match result {
+            Ok(true) => run.status = RunStatus::Succeeded,
+            Ok(false) => run.status = RunStatus::Cancelled,
+            Err(err) => {
+                run.status = RunStatus::Failed;
+                run.error = Some(format!("{err:#}"));
+            }
+        }
+        run.finished_at = Some(Local::now());
+        if let Err(err) = self.save(&run) {
+            warn!(run = %run.id, "failed to save workflow run: {err:#}");
+        }
+        self.release(&run.workflow);
+        info!(
+            workflow = %run.workflow,
+            run = %run.id,
+            status = run.status.as_str(),
+            "workflow run finished"
+        );
+        self.notify(&run).await;
+        run
+    }
+
+    /// Ok(false) when an approval was denied.
+    async fn execute_steps(&self, run: &mut WorkflowRun) -> Result<bool> {
+        let workflow = find_workflow(&self.workspace, &run.workflow)?;
+        let mut scope = json!({
+            "input": run.input,
+            "run": { "id": run.id, "workflow": run.workflow },
+            "steps": {},
+        });
+        for record in &run.steps {
+            scope["steps"][&record.id] = record.output.clone();
+        }
+        for step in &workflow.steps {
+            if run.steps.iter().any(|record| record.id == step.id) {
+                continue;
+            }
+            run.current_step = Some(step.id.clone());
+            self.save(run)?;
+            let (outcome, attempts) = self.run_step(&workflow, step, &scope).await?;
+            let (status, output) = match outcome {
+                Outcome::Done { status, output } => (status, output),
+                Outcome::Denied => {
+                    run.error = Some(format!("approval denied at step '{}'", step.id));
+                    return Ok(false);
+                }
+            };
+            scope["steps"][&step.id] = output.clone();
+            run.steps.push(StepRecord {
+                id: step.id.clone(),
+                status: status.to_string(),
+                output,
+                attempts,
+            });
+            run.current_step = None;
+            self.save(run)?;
+        }
+        run.output = Some(match &workflow.output {
+            Some(template) => render(template, &scope),
+            None => run
+                .steps
+                .iter()
+                .rev()
+                .find(|record| !record.output.is_null())
+                .map(|record| match &record.output {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                })
+                .unwrap_or_default(),
+        });
+        Ok(true)
+    }
+
+    fn run_step<'a>(
+        &'a self,
+        workflow: &'a Workflow,
+        step: &'a WorkflowStep,
+        scope: &'a Value,
+    ) -> BoxFuture<'a, Result<(Outcome, u32)>> {
+        Box::pin(async move {
+            if let Some(condition) = &step.when
+                && !condition.holds(scope)
+            {
+                let skipped = Outcome::Done {
+                    status: "skipped",
+                    output: Value::Null,
+                };
+                return Ok((skipped, 0));
+            }
+            match step.kind()? {
+                StepKind::Approval => Ok((self.approve(workflow, step, scope).await, 1)),
+                StepKind::ForEach => Ok((self.for_each(workflow, step, scope).await?, 1)),
+                StepKind::Prompt | StepKind::Tool | StepKind::Ask => {
+                    self.attempt(workflow, step, scope).await
+                }
+            }
+        })
+    }
+
+    async fn attempt(
+        &self,
+        workflow: &Workflow,
+        step: &WorkflowStep,
+        scope: &Value,
+    ) -> Result<(Outcome, u32)> {
+        let retries = step.retries.unwrap_or(workflow.retries);
+        let delay = step.retry_delay_s.unwrap_or(DEFAULT_RETRY_DELAY_S);
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match self.call(workflow, step, scope).await {
+                Ok(output) => {
+                    let done = Outcome::Done {
+                        status: "ok",
+                        output: Value::String(output),
+                    };
+                    return Ok((done, attempts));
+                }
+                Err(err) => err,
+            };
+            if attempts > retries {
+                if step.continue_on_error {
+                    let output = json!({ "error": format!("{error:#}") });
+                    return Ok((
+                        Outcome::Done {
+                            status: "failed",
+                            output,
+                        },
+                        attempts,
+                    ));
+                }
+                return Err(error.context(format!("step '{}' failed", step.id)));
+            }
+            warn!(
+                workflow = %workflow.name,
+                step = %step.id,
+                attempt = attempts,
+                "workflow step failed, retrying: {error:#}"
+            );
+            tokio::time::sleep(Duration::from_secs(delay * u64::from(attempts))).await;
+        }
+    }
+
+    fn target(workflow: &Workflow) -> (String, String) {
+        match &workflow.notify {
+            Some(target) => (target.channel.clone(), target.to.clone()),
+            None => ("workflow".to_string(), workflow.name.clone()),
+        }
+    }
+
+    async fn call(
+        &self,
+        workflow: &Workflow,
+        step: &WorkflowStep,
+        scope: &Value,
+    ) -> Result<String> {
+        if let Some(question) = &step.ask {
+            return self.ask(workflow, step, &render(question, scope)).await;
+        }
+        let agent = self.agent()?;
+        let (channel, chat_id) = Self::target(workflow);
+        if let Some(prompt) = &step.prompt {
+            let session = format!("workflow:{}", workflow.name);
+            let prompt = render(prompt, scope);
+            let turn =
+                agent.process_direct(&prompt, Some(&session), Some(&channel), Some(&chat_id));
+            return router::with_job_tier(workflow.tier.as_deref(), turn).await;
+        }
+        let tool = step.tool.as_deref().unwrap_or_default();
+        let arguments = match render_value(&Value::Object(step.args.clone()), scope) {
+            Value::Object(arguments) => arguments,
+            _ => Map::new(),
+        };
+        let result = agent.run_tool(&channel, &chat_id, tool, &arguments).await;
+        if result.starts_with("Error") {
+            bail!("{result}");
+        }
+        Ok(result)
+    }
+
+    async fn approve(&self, workflow: &Workflow, step: &WorkflowStep, scope: &Value) -> Outcome {
+        let message = render(step.approval.as_deref().unwrap_or_default(), scope);
+        let Some(prompt) = &self.approval else {
+            warn!(workflow = %workflow.name, step = %step.id, "no approval prompt available, denying");
+            return Outcome::Denied;
+        };
+        let (channel, chat_id) = Self::target(workflow);
+        let mut arguments = Map::new();
+        arguments.insert("step".to_string(), Value::String(step.id.clone()));
+        arguments.insert("message".to_string(), Value::String(message));
+        let request = ApprovalRequest::new(
+            &format!("workflow:{}", workflow.name),
+            &arguments,
+            &channel,
+            &chat_id,
+        );
+        if prompt.ask(&request).await.is_allowed() {
+            Outcome::Done {
+                status: "ok",
+                output: Value::String("approved".to_string()),
+            }
+        } else {
+            Outcome::Denied
+        }
+    }
+
+    async fn ask(
+        &self,
+        workflow: &Workflow,
+        step: &WorkflowStep,
+        question: &str,
+    ) -> Result<String> {
+        let Some(bus) = &self.bus else {
+            bail!("no message bus to ask the user on");
+        };
+        let Some(target) = workflow
+            .notify
+            .as_ref()
+            .filter(|target| !matches!(target.channel.as_str(), "" | "cli" | "webui"))
+        else {
+            bail!("ask step '{}' needs a chat channel in notify", step.id);
+        };
+        let wait = Duration::from_secs(step.timeout_s.unwrap_or(DEFAULT_ASK_TIMEOUT_S));
+        match bus.ask(&target.channel, &target.to, question, wait).await? {
+            Some(reply) => Ok(reply.content),
+            None => bail!("no reply within {}s", wait.as_secs()),
+        }
+    }
+
+    async fn for_each(
+        &self,
+        workflow: &Workflow,
+        step: &WorkflowStep,
+        scope: &Value,
+    ) -> Result<Outcome> {
+        let template = step.for_each.as_deref().unwrap_or_default();
+        let items = loop_items(render_value(&Value::String(template.to_string()), scope));
+        if items.len() > MAX_LOOP_ITEMS {
+            bail!(
+                "step '{}' would loop over {} items (limit {MAX_LOOP_ITEMS})",
+                step.id,
+                items.len()
+            );
+        }
+        let mut outputs = Vec::with_capacity(items.len());
+        for (index, item) in items.into_iter().enumerate() {
+            let mut inner = scope.clone();
+            inner["item"] = item;
+            inner["index"] = json!(index);
+            let mut last = Value::Null;
+            for body_step in &step.steps {
+                let (outcome, _) = self.run_step(workflow, body_step, &inner).await?;
+                match outcome {
+                    Outcome::Done { output, .. } => {
+                        inner["steps"][&body_step.id] = output.clone();
+                        if !output.is_null() {
+                            last = output;
+                        }
+                    }
+                    Outcome::Denied => return Ok(Outcome::Denied),
+                }
+            }
+            outputs.push(last);
+        }
+        Ok(Outcome::Done {
+            status: "ok",
+            output: Value::Array(outputs),
+        })
+    }
+
+    async fn notify(&self, run: &WorkflowRun) {
+        let Some(bus) = &self.bus else {
+            return;
+        };
+        let Ok(workflow) = find_workflow(&self.workspace, &run.workflow) else {
+            return;
+        };
+        let Some(target) = workflow.notify.filter(|target| target.channel != "cli") else {
+            return;
+        };
+        let outbound = OutboundMessage::new(target.channel, target.to, run.summary());
+        if let Err(err) = bus.publish_outbound(outbound).await {
+            warn!(run = %run.id, "failed to deliver workflow result: {err}");
+        }
+    }
+}
+
+/// A JSON array, a JSON array encoded in a string, or one item per line.
+fn loop_items(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items,
+        Value::String(text) => match serde_json::from_str::<Value>(&text) {
+            Ok(Value::Array(items)) => items,
+            _ => text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|line| Value::String(line.to_string()))
+                .collect(),
+        },
+        Value::Null => Vec::new(),
+        other => vec![other],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{Cassette, EvalProvider};
+    use crate::session::SessionManager;
+
+    #[test]
+    fn loop_items_accept_arrays_json_and_lines() {
+        assert_eq!(loop_items(json!(["a", 1])), vec![json!("a"), json!(1)]);
+        assert_eq!(loop_items(json!("[\"x\"]")), vec![json!("x")]);
+        assert_eq!(
+            loop_items(json!("a\n\n b \n")),
+            vec![json!("a"), json!("b")]
+        );
+        assert!(loop_items(Value::Null).is_empty());
+    }
+
+    #[tokio::test]
+    async fn runs_tool_steps_loops_and_resumes_after_failure() {
+        let dir = std::env::temp_dir().join(format!("nanobot-workflow-{}", uuid::Uuid::new_v4()));
+        let workspace = dir.join("workspace");
+        std::fs::create_dir_all(workspace.join("workflows")).expect("dirs");
+        std::fs::write(workspace.join("a.txt"), "alpha").expect("write");
+        std::fs::write(workspace.join("b.txt"), "beta").expect("write");
+        let definition = r#"
+steps:
+  - id: files
+    forEach: "{{input.files}}"
+    steps:
+      - tool: read_file
+        args: { path: "{{item}}" }
+  - id: check
+    when: { value: "{{steps.files}}", contains: alpha }
+    tool: read_file
+    args: { path: "{{input.missing}}" }
+    retries: 1
+    retryDelayS: 0
+"#;
+        std::fs::write(workspace.join("workflows/demo.yaml"), definition).expect("write");
+
+        let sessions = Arc::new(SessionManager::with_dir(dir.join("sessions")).expect("sessions"));
+        let agent = Arc::new(
+            AgentLoop::builder(Arc::new(EvalProvider::replay(
+                Cassette::default(),
+                "test-model",
+            )))
+            .bus(Arc::new(MessageBus::new(8)))
+            .workspace(workspace.clone())
+            .max_iterations(4)
+            .memory_window(20)
+            .exec_timeout(5)
+            .restrict_to_workspace(true)
+            .sessions(sessions)
+            .build()
+            .expect("agent"),
+        );
+        let runner = WorkflowRunner::new(workspace.clone(), dir.join("runs"));
+        runner.attach(&agent);
+
+        let input = json!({
+            "files": [workspace.join("a.txt"), workspace.join("b.txt")],
+            "missing": workspace.join("missing.txt"),
+        });
+        let run = runner.run("demo", input, "manual").await.expect("run");
+        assert_eq!(run.status, RunStatus::Failed);
+        assert_eq!(run.steps.len(), 1);
+        assert_eq!(run.steps[0].output, json!(["alpha", "beta"]));
+        assert!(run.error.as_deref().unwrap_or_default().contains("check"));
+
+        std::fs::write(workspace.join("missing.txt"), "found").expect("write");
+        let resumed = runner.resume(&run.id).await.expect("resume");
+        assert_eq!(resumed.status, RunStatus::Succeeded);
+        assert_eq!(resumed.output.as_deref(), Some("found"));
+        assert_eq!(runner.list_runs(10).len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn ask_steps_wait_for_the_reply_in_the_notify_chat() {
+        let dir = std::env::temp_dir().join(format!("nanobot-workflow-{}", uuid::Uuid::new_v4()));
+        let workspace = dir.join("workspace");
+        std::fs::create_dir_all(workspace.join("workflows")).expect("dirs");
+        let definition = r#"
+notify: { channel: telegram, to: "42" }
+steps:
+  - id: city
+    ask: "Which city, {{input.name}}?"
+output: "Weather for {{steps.city}}"
+"#;
+        std::fs::write(workspace.join("workflows/trip.yaml"), definition).expect("write");
+
+        let bus = Arc::new(MessageBus::new(8));
+        let runner = WorkflowRunner::new(workspace, dir.join("runs")).with_bus(bus.clone());
+        let user = {
+            let bus = bus.clone();
+            tokio::spawn(async move {
+                let question = bus.consume_outbound().await.expect("question");
+                assert_eq!(question.content, "Which city, Ada?");
+                bus.publish_inbound(crate::bus::InboundMessage::new(
+                    "telegram", "u", "42", "Oslo",
+                ))
+                .await
+                .expect("reply");
+            })
+        };
+        let run = runner
+            .run("trip", json!({ "name": "Ada" }), "manual")
+            .await
+            .expect("run");
+        user.await.expect("user");
+        assert_eq!(run.status, RunStatus::Succeeded);
+        assert_eq!(run.output.as_deref(), Some("Weather for Oslo"));
+        assert_eq!(bus.inbound_size(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
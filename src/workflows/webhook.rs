@@ -0,0 +1,129 @@
+use super::{WorkflowRunner, find_workflow};
+use crate::apikeys::ApiKeyGuard;
+use crate::listener::{TrustedProxies, is_json};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response};
+use tokio::runtime::Handle;
+
+const ROUTE_PREFIX: &str = "/workflows/";
+
+fn respond_json(request: Request, status: u16, body: Value) {
+    let header = Header::from_bytes("Content-Type", "application/json; charset=utf-8")
+        .expect("static header is valid");
+    let _ = request.respond(
+        Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header),
+    );
+}
+
+/// `POST /workflows/<name>` starts a run of a workflow with `trigger.webhook`;
+/// the JSON body becomes `input`. Answers 202 with the run id right away.
+pub struct WorkflowWebhook {
+    runner: Arc<WorkflowRunner>,
+    runtime: Handle,
+    guard: ApiKeyGuard,
+    proxies: TrustedProxies,
+}
+
+impl WorkflowWebhook {
+    pub fn new(
+        runner: Arc<WorkflowRunner>,
+        runtime: Handle,
+        guard: ApiKeyGuard,
+        proxies: TrustedProxies,
+    ) -> Self {
+        Self {
+            runner,
+            runtime,
+            guard,
+            proxies,
+        }
+    }
+
+    pub fn handle(&self, mut request: Request) -> Option<Request> {
+        let Some(name) = request
+            .url()
+            .split('?')
+            .next()
+            .and_then(|path| path.strip_prefix(ROUTE_PREFIX))
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .map(ToOwned::to_owned)
+        else {
+            return Some(request);
+        };
+        if request.method() != &Method::Post {
+            return Some(request);
+        }
+        match self.guard.check(&request) {
+            Err(err) => {
+                respond_json(
+                    request,
+                    err.status_code(),
+                    json!({ "ok": false, "error": err.to_string() }),
+                );
+                return None;
+            }
+            Ok(None) => {
+                if let Some((status, error)) = self.proxies.local_request_error(&request) {
+                    respond_json(request, status, json!({ "ok": false, "error": error }));
+                    return None;
+                }
+            }
+            Ok(Some(_)) => {}
+        }
+
+        let enabled = find_workflow(self.runner.workspace(), &name)
+            .is_ok_and(|workflow| workflow.trigger.webhook);
+        if !enabled {
+            respond_json(
+                request,
+                404,
+                json!({ "ok": false, "error": format!("no webhook workflow named '{name}'") }),
+            );
+            return None;
+        }
+        let mut body = String::new();
+        let _ = request.as_reader().read_to_string(&mut body);
+        if !body.trim().is_empty() && !is_json(&request) {
+            respond_json(
+                request,
+                415,
+                json!({ "ok": false, "error": "send the input as application/json" }),
+            );
+            return None;
+        }
+        let input = if body.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str::<Value>(&body) {
+                Ok(input) => input,
+                Err(err) => {
+                    respond_json(
+                        request,
+                        400,
+                        json!({ "ok": false, "error": format!("invalid JSON body: {err}") }),
+                    );
+                    return None;
+                }
+            }
+        };
+        match self.runner.start(&name, input, "webhook") {
+            Ok(run) => {
+                let id = run.id.clone();
+                let runner = self.runner.clone();
+                self.runtime.spawn(async move {
+                    runner.execute(run).await;
+                });
+                respond_json(request, 202, json!({ "ok": true, "run": id }));
+            }
+            Err(err) => respond_json(
+                request,
+                409,
+                json!({ "ok": false, "error": format!("{err:#}") }),
+            ),
+        }
+        None
+    }
+}